@@ -0,0 +1,529 @@
+use anyhow::{anyhow, Result};
+use futures_util::{
+    future::BoxFuture,
+    io::AsyncBufReadExt,
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use k8s_openapi::{
+    api::core::v1::{Event, Node, Pod, Secret},
+    apimachinery::pkg::apis::meta::v1::Status,
+};
+use kube::{
+    api::{AttachedProcess, ListParams, LogParams},
+    Api, Client, ResourceExt,
+};
+use serde_derive::Deserialize;
+use tokio::io::AsyncReadExt;
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::{exec::ExecRecorder, self_usage::UsageCounters};
+
+//the cluster operations the collectors need, so they can run against a fake in tests.
+pub trait PodAccess: Clone + Send + Sync + 'static {
+    fn list_pods<'a>(&'a self, label: &'a str, field: &'a str) -> BoxFuture<'a, Result<Vec<Pod>>>;
+    fn logs<'a>(&'a self, pod: &'a str, params: LogParams) -> BoxFuture<'a, Result<String>>;
+    //the log lines as they come, params.follow keeps the stream open while the container runs.
+    fn log_stream<'a>(
+        &'a self,
+        pod: &'a str,
+        params: LogParams,
+    ) -> BoxFuture<'a, Result<BoxStream<'static, Result<String>>>>;
+    fn exec<'a>(
+        &'a self,
+        pod: &'a str,
+        container: &'a str,
+        command: Vec<String>,
+    ) -> BoxFuture<'a, Result<String>>;
+    //like exec, with the exit code of the command.
+    fn exec_status<'a>(
+        &'a self,
+        pod: &'a str,
+        container: &'a str,
+        command: Vec<String>,
+    ) -> BoxFuture<'a, Result<(String, i32)>>;
+    //raw stdout without a tty, for binary output such as a tar stream, capped at max_bytes.
+    fn exec_bytes<'a>(
+        &'a self,
+        pod: &'a str,
+        container: &'a str,
+        command: Vec<String>,
+        max_bytes: u64,
+    ) -> BoxFuture<'a, Result<Vec<u8>>>;
+    fn list_secrets<'a>(&'a self, label: &'a str) -> BoxFuture<'a, Result<Vec<Secret>>>;
+    fn list_events(&self) -> BoxFuture<'_, Result<Vec<Event>>>;
+    fn list_nodes(&self) -> BoxFuture<'_, Result<Vec<Node>>>;
+    //GET of a port of the pod without anything running in it, the body of a 2xx answer.
+    fn http_get<'a>(&'a self, pod: &'a str, request: &'a HttpGet) -> BoxFuture<'a, Result<String>>;
+    //the same cluster seen from another namespace.
+    fn in_namespace(&self, namespace: &str) -> Self;
+}
+
+//an http request to a port of a pod, https is not verified (self-signed localhost certificates).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HttpGet {
+    pub port: u16,
+    pub tls: bool,
+    //path and query.
+    pub path: String,
+    //value of the Authorization header.
+    pub authorization: Option<String>,
+}
+
+fn list_params(label: &str, field: &str) -> ListParams {
+    ListParams {
+        label_selector: Some(label.to_string()),
+        field_selector: Some(field.to_string()),
+        ..Default::default()
+    }
+}
+
+//the kube api of one namespace.
+#[derive(Clone, Debug)]
+pub struct KubeAccess {
+    pub pods: Api<Pod>,
+    pub secrets: Api<Secret>,
+    pub events: Api<Event>,
+    pub nodes: Api<Node>,
+    //the execs started through this access, the one of the run context for the collectors.
+    pub execs: Arc<ExecRecorder>,
+}
+
+impl KubeAccess {
+    //one access per namespace, in order.
+    pub fn for_namespaces(client: &Client, namespaces: &[String]) -> Vec<Self> {
+        namespaces
+            .iter()
+            .map(|n| KubeAccess::namespaced(client.clone(), n))
+            .collect()
+    }
+
+    //the namespace of the pods api, /api/v1/namespaces/<namespace>/pods.
+    pub fn namespace(&self) -> &str {
+        self.pods
+            .resource_url()
+            .split('/')
+            .skip_while(|s| *s != "namespaces")
+            .nth(1)
+            .unwrap_or_default()
+    }
+
+    pub fn namespaced(client: Client, namespace: &str) -> Self {
+        KubeAccess {
+            pods: Api::namespaced(client.clone(), namespace),
+            secrets: Api::namespaced(client.clone(), namespace),
+            events: Api::namespaced(client.clone(), namespace),
+            nodes: Api::all(client),
+            execs: Default::default(),
+        }
+    }
+
+    pub fn with_execs(mut self, execs: Arc<ExecRecorder>) -> Self {
+        self.execs = execs;
+        self
+    }
+}
+
+fn attach_params(container: &str) -> kube::api::AttachParams {
+    kube::api::AttachParams {
+        container: Some(container.to_string()),
+        stderr: false,
+        stdin: true,
+        stdout: true,
+        tty: true,
+        ..Default::default()
+    }
+}
+
+//"Success" or a "NonZeroExitCode" failure carrying the code in its causes.
+fn exit_code(status: Option<Status>) -> i32 {
+    let Some(status) = status else {
+        return -1;
+    };
+    if status.status.as_deref() == Some("Success") {
+        return 0;
+    }
+    status
+        .details
+        .and_then(|d| d.causes)
+        .into_iter()
+        .flatten()
+        .find(|c| c.reason.as_deref() == Some("ExitCode"))
+        .and_then(|c| c.message)
+        .and_then(|m| m.parse().ok())
+        .unwrap_or(-1)
+}
+
+async fn get_output(mut attached: AttachedProcess) -> Result<String> {
+    let mut result_stout = attached
+        .stdout()
+        .ok_or_else(|| anyhow!("exec without stdout"))?;
+    let mut buf_stout = String::new();
+    result_stout.read_to_string(&mut buf_stout).await?;
+    Ok(buf_stout)
+}
+
+impl PodAccess for KubeAccess {
+    fn list_pods<'a>(&'a self, label: &'a str, field: &'a str) -> BoxFuture<'a, Result<Vec<Pod>>> {
+        Box::pin(async move { Ok(self.pods.list(&list_params(label, field)).await?.items) })
+    }
+
+    fn logs<'a>(&'a self, pod: &'a str, params: LogParams) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move { Ok(self.pods.logs(pod, &params).await?) })
+    }
+
+    fn log_stream<'a>(
+        &'a self,
+        pod: &'a str,
+        params: LogParams,
+    ) -> BoxFuture<'a, Result<BoxStream<'static, Result<String>>>> {
+        Box::pin(async move {
+            let lines = self.pods.log_stream(pod, &params).await?.lines();
+            Ok(lines.map_err(anyhow::Error::from).boxed())
+        })
+    }
+
+    fn exec<'a>(
+        &'a self,
+        pod: &'a str,
+        container: &'a str,
+        command: Vec<String>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            self.execs
+                .record(self.namespace(), pod, container, &command);
+            let result: AttachedProcess = self
+                .pods
+                .exec(pod, command, &attach_params(container))
+                .await?;
+            get_output(result).await
+        })
+    }
+
+    fn exec_status<'a>(
+        &'a self,
+        pod: &'a str,
+        container: &'a str,
+        command: Vec<String>,
+    ) -> BoxFuture<'a, Result<(String, i32)>> {
+        Box::pin(async move {
+            self.execs
+                .record(self.namespace(), pod, container, &command);
+            let mut attached = self
+                .pods
+                .exec(pod, command, &attach_params(container))
+                .await?;
+            let status = attached.take_status();
+            let output = get_output(attached).await?;
+            let status = match status {
+                Some(s) => s.await,
+                None => None,
+            };
+            Ok((output, exit_code(status)))
+        })
+    }
+
+    fn exec_bytes<'a>(
+        &'a self,
+        pod: &'a str,
+        container: &'a str,
+        command: Vec<String>,
+        max_bytes: u64,
+    ) -> BoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move {
+            let params = kube::api::AttachParams {
+                container: Some(container.to_string()),
+                stdin: false,
+                stdout: true,
+                stderr: false,
+                tty: false,
+                ..Default::default()
+            };
+            self.execs
+                .record(self.namespace(), pod, container, &command);
+            let mut attached = self.pods.exec(pod, command, &params).await?;
+            let stdout = attached
+                .stdout()
+                .ok_or_else(|| anyhow!("exec without stdout"))?;
+            let mut buf = vec![];
+            stdout.take(max_bytes + 1).read_to_end(&mut buf).await?;
+            if buf.len() as u64 > max_bytes {
+                return Err(anyhow!("output larger than {} bytes", max_bytes));
+            }
+            Ok(buf)
+        })
+    }
+
+    fn list_secrets<'a>(&'a self, label: &'a str) -> BoxFuture<'a, Result<Vec<Secret>>> {
+        Box::pin(async move { Ok(self.secrets.list(&list_params(label, "")).await?.items) })
+    }
+
+    fn list_events(&self) -> BoxFuture<'_, Result<Vec<Event>>> {
+        Box::pin(async move { Ok(self.events.list(&ListParams::default()).await?.items) })
+    }
+
+    fn list_nodes(&self) -> BoxFuture<'_, Result<Vec<Node>>> {
+        Box::pin(async move { Ok(self.nodes.list(&ListParams::default()).await?.items) })
+    }
+
+    fn http_get<'a>(&'a self, pod: &'a str, request: &'a HttpGet) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move { crate::portforward_http_get(&self.pods, pod, request).await })
+    }
+
+    fn in_namespace(&self, namespace: &str) -> Self {
+        KubeAccess::namespaced(self.pods.clone().into_client(), namespace)
+            .with_execs(self.execs.clone())
+    }
+}
+
+//in-memory cluster seeded from a fixture json:
+//{"pods": [..], "logs": {"<pod>/<container>": ".."}, "exec": {"<pod>/<container>/<command>": ".."}, ..}
+//previous logs use the "<pod>/<container>/previous" key.
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FakeAccess {
+    pub pods: Vec<Pod>,
+    pub logs: BTreeMap<String, String>,
+    pub exec: BTreeMap<String, String>,
+    //exit codes by exec key, 0 when missing.
+    pub exit_codes: BTreeMap<String, i32>,
+    pub secrets: Vec<Secret>,
+    pub events: Vec<Event>,
+    pub nodes: Vec<Node>,
+    //http bodies by "<pod>/<port><path>", a missing key behaves like a blocked port-forward.
+    pub http: BTreeMap<String, String>,
+    //pods deleted since they were listed, their execs and port-forwards fail like the api server's
+    //(404 instead of the websocket upgrade).
+    pub gone: Vec<String>,
+    //counted like the requests of the kube client: every call is a request, the logs are downloaded bytes.
+    #[serde(skip)]
+    pub usage: Arc<UsageCounters>,
+    //recorded like the execs of KubeAccess.
+    #[serde(skip)]
+    pub execs: Arc<ExecRecorder>,
+    //followed log streams by log key, one lines list per attachment. Once the attachments of a key
+    //are used up its stream stays open without lines, like a running container.
+    #[serde(skip)]
+    pub follows: Arc<Mutex<BTreeMap<String, VecDeque<Vec<String>>>>>,
+}
+
+impl FakeAccess {
+    pub fn from_fixture(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    fn check_not_gone(&self, pod: &str) -> Result<()> {
+        if self.gone.iter().any(|g| g == pod) {
+            return Err(kube::Error::UpgradeConnection(
+                kube::client::UpgradeConnectionError::ProtocolSwitch(hyper::StatusCode::NOT_FOUND),
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+//"<pod>/<container>", "/previous" appended for the previous log.
+fn log_key(pod: &str, params: &LogParams) -> String {
+    let mut key = format!(
+        "{}/{}",
+        pod,
+        params.container.as_deref().unwrap_or_default()
+    );
+    if params.previous {
+        key.push_str("/previous");
+    }
+    key
+}
+
+//equality selectors only ("a=b,c=d"), which is all the collectors use.
+fn selector_matches(selector: &str, values: &BTreeMap<String, String>) -> bool {
+    selector
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .all(|s| match s.split_once('=') {
+            Some((k, v)) => values.get(k.trim()).map(|x| x.as_str()) == Some(v.trim()),
+            None => values.contains_key(s),
+        })
+}
+
+fn pod_fields(pod: &Pod) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    fields.insert("metadata.name".to_string(), pod.name_any());
+    fields.insert(
+        "metadata.namespace".to_string(),
+        pod.namespace().unwrap_or_default(),
+    );
+    if let Some(phase) = pod.status.as_ref().and_then(|s| s.phase.clone()) {
+        fields.insert("status.phase".to_string(), phase);
+    }
+    if let Some(node) = pod.spec.as_ref().and_then(|s| s.node_name.clone()) {
+        fields.insert("spec.nodeName".to_string(), node);
+    }
+    fields
+}
+
+impl PodAccess for FakeAccess {
+    fn list_pods<'a>(&'a self, label: &'a str, field: &'a str) -> BoxFuture<'a, Result<Vec<Pod>>> {
+        Box::pin(async move {
+            self.usage.api_request();
+            Ok(self
+                .pods
+                .iter()
+                .filter(|p| selector_matches(label, p.labels()))
+                .filter(|p| selector_matches(field, &pod_fields(p)))
+                .cloned()
+                .collect())
+        })
+    }
+
+    fn logs<'a>(&'a self, pod: &'a str, params: LogParams) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            self.usage.api_request();
+            let key = log_key(pod, &params);
+            let log = self
+                .logs
+                .get(&key)
+                .cloned()
+                .ok_or_else(|| anyhow!("no logs for {}", key))?;
+            self.usage.downloaded(log.len() as u64);
+            Ok(log)
+        })
+    }
+
+    //the logs of the fixture once, as a stream that ends like the one of a terminated container.
+    fn log_stream<'a>(
+        &'a self,
+        pod: &'a str,
+        params: LogParams,
+    ) -> BoxFuture<'a, Result<BoxStream<'static, Result<String>>>> {
+        Box::pin(async move {
+            if params.follow {
+                if let Some(attachments) =
+                    self.follows.lock().unwrap().get_mut(&log_key(pod, &params))
+                {
+                    self.usage.api_request();
+                    return Ok(match attachments.pop_front() {
+                        Some(lines) => stream::iter(lines.into_iter().map(Ok)).boxed(),
+                        None => stream::pending().boxed(),
+                    });
+                }
+            }
+            let log = self.logs(pod, params).await?;
+            let lines = log.lines().map(|l| Ok(l.to_string())).collect::<Vec<_>>();
+            Ok(stream::iter(lines).boxed())
+        })
+    }
+
+    fn exec<'a>(
+        &'a self,
+        pod: &'a str,
+        container: &'a str,
+        command: Vec<String>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            self.usage.api_request();
+            self.usage.exec_session();
+            //the fake has no namespace of its own, the one of the listed pod.
+            let namespace = self
+                .pods
+                .iter()
+                .find(|p| p.name_any() == pod)
+                .and_then(|p| p.namespace())
+                .unwrap_or_default();
+            self.execs.record(&namespace, pod, container, &command);
+            self.check_not_gone(pod)?;
+            let key = format!("{}/{}/{}", pod, container, command.join(" "));
+            self.exec
+                .get(&key)
+                .cloned()
+                .ok_or_else(|| anyhow!("no exec output for {}", key))
+        })
+    }
+
+    fn exec_status<'a>(
+        &'a self,
+        pod: &'a str,
+        container: &'a str,
+        command: Vec<String>,
+    ) -> BoxFuture<'a, Result<(String, i32)>> {
+        Box::pin(async move {
+            let key = format!("{}/{}/{}", pod, container, command.join(" "));
+            let output = self.exec(pod, container, command).await?;
+            Ok((output, self.exit_codes.get(&key).copied().unwrap_or(0)))
+        })
+    }
+
+    fn exec_bytes<'a>(
+        &'a self,
+        pod: &'a str,
+        container: &'a str,
+        command: Vec<String>,
+        max_bytes: u64,
+    ) -> BoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move {
+            let output = self.exec(pod, container, command).await?.into_bytes();
+            if output.len() as u64 > max_bytes {
+                return Err(anyhow!("output larger than {} bytes", max_bytes));
+            }
+            Ok(output)
+        })
+    }
+
+    fn list_secrets<'a>(&'a self, label: &'a str) -> BoxFuture<'a, Result<Vec<Secret>>> {
+        Box::pin(async move {
+            self.usage.api_request();
+            Ok(self
+                .secrets
+                .iter()
+                .filter(|s| selector_matches(label, s.labels()))
+                .cloned()
+                .collect())
+        })
+    }
+
+    fn list_events(&self) -> BoxFuture<'_, Result<Vec<Event>>> {
+        Box::pin(async move {
+            self.usage.api_request();
+            Ok(self.events.clone())
+        })
+    }
+
+    fn list_nodes(&self) -> BoxFuture<'_, Result<Vec<Node>>> {
+        Box::pin(async move {
+            self.usage.api_request();
+            Ok(self.nodes.clone())
+        })
+    }
+
+    fn http_get<'a>(&'a self, pod: &'a str, request: &'a HttpGet) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            self.usage.api_request();
+            self.check_not_gone(pod)?;
+            let key = format!("{}/{}{}", pod, request.port, request.path);
+            self.http
+                .get(&key)
+                .cloned()
+                .ok_or_else(|| anyhow!("no http answer for {}", key))
+        })
+    }
+
+    //the pods, secrets and events of the namespace, logs and exec outputs are keyed by pod.
+    fn in_namespace(&self, namespace: &str) -> Self {
+        let mut fake = self.clone();
+        fake.pods
+            .retain(|p| p.namespace().as_deref() == Some(namespace));
+        fake.secrets
+            .retain(|s| s.namespace().as_deref() == Some(namespace));
+        fake.events
+            .retain(|e| e.namespace().as_deref() == Some(namespace));
+        fake
+    }
+}
@@ -0,0 +1,270 @@
+use anyhow::Result;
+use regex::Regex;
+use serde_derive::Serialize;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::BufWriter,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+pub const ANONYMIZATION_MAP_FILE: &str = "anonymization_map.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    Ip,
+    Node,
+    Custom,
+}
+
+impl Kind {
+    fn prefix(&self) -> &'static str {
+        match self {
+            Kind::Ip => "ip",
+            Kind::Node => "node",
+            Kind::Custom => "custom",
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize)]
+pub struct AnonymizationMap {
+    pub ip: BTreeMap<String, String>,
+    pub node: BTreeMap<String, String>,
+    pub custom: BTreeMap<String, String>,
+}
+
+//replaces ips, node hostnames and custom strings with stable tokens (ip-1, node-3, custom-2).
+//the same value always gets the same token during a run.
+#[derive(Debug)]
+pub struct Anonymizer {
+    literals: Option<Regex>,
+    literal_kinds: HashMap<String, Kind>,
+    ipv4: Regex,
+    ipv6: Regex,
+    tokens: HashMap<(Kind, String), String>,
+    counters: HashMap<Kind, usize>,
+}
+
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+impl Anonymizer {
+    pub fn new(node_names: &[String], custom_strings: &[String]) -> Result<Self> {
+        let mut literal_kinds = HashMap::new();
+        custom_strings
+            .iter()
+            .filter(|s| !s.is_empty())
+            .for_each(|s| {
+                literal_kinds.insert(s.clone(), Kind::Custom);
+            });
+        node_names.iter().filter(|s| !s.is_empty()).for_each(|s| {
+            literal_kinds.entry(s.clone()).or_insert(Kind::Node);
+        });
+
+        //longest first so overlapping literals ("node-1" and "node-10") resolve to the longest one.
+        let mut literals = literal_kinds.keys().cloned().collect::<Vec<String>>();
+        literals.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        let literals = if literals.is_empty() {
+            None
+        } else {
+            Some(Regex::new(
+                &literals
+                    .iter()
+                    .map(|l| regex::escape(l))
+                    .collect::<Vec<String>>()
+                    .join("|"),
+            )?)
+        };
+
+        Ok(Anonymizer {
+            literals,
+            literal_kinds,
+            ipv4: Regex::new(r"\d{1,3}(?:\.\d{1,3}){3}")?,
+            ipv6: Regex::new(
+                r"(?:[0-9A-Fa-f]{0,4}:){2,7}(?:\d{1,3}(?:\.\d{1,3}){3}|[0-9A-Fa-f]{1,4})?",
+            )?,
+            tokens: HashMap::new(),
+            counters: HashMap::new(),
+        })
+    }
+
+    fn token(&mut self, kind: Kind, value: &str) -> String {
+        let key = (kind, value.to_string());
+        if let Some(t) = self.tokens.get(&key) {
+            return t.clone();
+        }
+        let counter = self.counters.entry(kind).or_insert(0);
+        *counter += 1;
+        let t = format!("{}-{}", kind.prefix(), counter);
+        self.tokens.insert(key, t.clone());
+        t
+    }
+
+    //replace every match accepted by `accept` (text, start, end) with the token of `kind`.
+    fn replace_with<F>(&mut self, text: &str, re: &Regex, kind: Kind, accept: F) -> String
+    where
+        F: Fn(&str, usize, usize) -> bool,
+    {
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for m in re.find_iter(text) {
+            if !accept(text, m.start(), m.end()) {
+                continue;
+            }
+            out.push_str(&text[last..m.start()]);
+            out.push_str(&self.token(kind, m.as_str()));
+            last = m.end();
+        }
+        out.push_str(&text[last..]);
+        out
+    }
+
+    pub fn anonymize(&mut self, text: &str) -> String {
+        let mut text = text.to_string();
+
+        if let Some(literals) = self.literals.clone() {
+            let mut out = String::with_capacity(text.len());
+            let mut last = 0;
+            for m in literals.find_iter(&text) {
+                let kind = self.literal_kinds[m.as_str()];
+                out.push_str(&text[last..m.start()]);
+                out.push_str(&self.token(kind, m.as_str()));
+                last = m.end();
+            }
+            out.push_str(&text[last..]);
+            text = out;
+        }
+
+        let ipv6 = self.ipv6.clone();
+        text = self.replace_with(&text, &ipv6, Kind::Ip, |t, s, e| {
+            let b = t.as_bytes();
+            let candidate = &t[s..e];
+            (s == 0 || !(is_word_byte(b[s - 1]) || b[s - 1] == b':'))
+                && (e == b.len() || !(is_word_byte(b[e]) || b[e] == b':'))
+                && candidate != "::"
+                && candidate.parse::<Ipv6Addr>().is_ok()
+        });
+
+        let ipv4 = self.ipv4.clone();
+        text = self.replace_with(&text, &ipv4, Kind::Ip, |t, s, e| {
+            let b = t.as_bytes();
+            (s == 0 || !(is_word_byte(b[s - 1]) || b[s - 1] == b'.'))
+                && (e == b.len()
+                    || !(is_word_byte(b[e])
+                        || (b[e] == b'.' && b.get(e + 1).is_some_and(|n| n.is_ascii_digit()))))
+                && t[s..e].parse::<Ipv4Addr>().is_ok()
+        });
+
+        text
+    }
+
+    pub fn map(&self) -> AnonymizationMap {
+        let mut map = AnonymizationMap::default();
+        self.tokens.iter().for_each(|((kind, value), token)| {
+            let m = match kind {
+                Kind::Ip => &mut map.ip,
+                Kind::Node => &mut map.node,
+                Kind::Custom => &mut map.custom,
+            };
+            m.insert(value.clone(), token.clone());
+        });
+        map
+    }
+
+    pub fn write_map(&self, folder: &str) -> Result<String> {
+        let path = format!("{}/{}", folder, ANONYMIZATION_MAP_FILE);
+        let file = File::create(&path)?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &self.map())?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn anonymizer() -> Anonymizer {
+        Anonymizer::new(
+            &[
+                "node-1".to_string(),
+                "node-10".to_string(),
+                "acme-prod".to_string(),
+            ],
+            &["acme".to_string(), "acme-prod".to_string()],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn overlapping_literals_resolve_to_the_longest() {
+        let mut a = anonymizer();
+        assert_eq!(
+            a.anonymize("node-10 and node-1 on acme-prod for acme"),
+            "node-1 and node-2 on custom-1 for custom-2"
+        );
+        //a custom string that is also a node name is a custom one.
+        assert_eq!(a.map().custom["acme-prod"], "custom-1");
+        assert!(!a.map().node.contains_key("acme-prod"));
+    }
+
+    #[test]
+    fn tokens_are_stable_across_texts() {
+        let mut a = anonymizer();
+        let first = a.anonymize("10.0.0.1 node-10");
+        let second = a.anonymize("node-10 10.0.0.2 10.0.0.1");
+        assert_eq!(first, "ip-1 node-1");
+        assert_eq!(second, "node-1 ip-2 ip-1");
+    }
+
+    #[test]
+    fn ip_matches_respect_their_boundaries() {
+        let mut a = Anonymizer::new(&[], &[]).unwrap();
+        assert_eq!(
+            a.anonymize("http://10.0.0.1:8080/ version 1.2.3.4.5 999.1.1.1 a10.0.0.2"),
+            "http://ip-1:8080/ version 1.2.3.4.5 999.1.1.1 a10.0.0.2"
+        );
+        assert_eq!(
+            a.anonymize("fe80::1%eth0 [2001:db8::7]:443 ::ffff:10.0.0.1 10:30:00 ::"),
+            "ip-2%eth0 [ip-3]:443 ip-4 10:30:00 ::"
+        );
+    }
+
+    #[test]
+    fn node_names_containing_an_ip_are_replaced_as_nodes() {
+        let mut a = Anonymizer::new(&["ip-10-0-0-1.ec2.internal".to_string()], &[]).unwrap();
+        assert_eq!(
+            a.anonymize("ip-10-0-0-1.ec2.internal (10.0.0.1)"),
+            "node-1 (ip-1)"
+        );
+    }
+
+    #[test]
+    fn very_long_lines_keep_their_content() {
+        let mut a = anonymizer();
+        let filler = "x".repeat(4 * 1024 * 1024);
+        let line = format!("10.0.0.1 {} node-1 {} 10.0.0.1", filler, filler);
+        let out = a.anonymize(&line);
+        //no assert_eq, a failure would print megabytes.
+        assert!(out == format!("ip-1 {} node-1 {} ip-1", filler, filler));
+        //no separator at all: nothing to replace.
+        let digits = "1".repeat(1024 * 1024);
+        assert!(a.anonymize(&digits) == digits);
+    }
+
+    #[test]
+    fn map_is_written_by_kind() {
+        let dir = TempDir::new();
+        let mut a = anonymizer();
+        a.anonymize("node-1 10.0.0.1 acme");
+        let path = a.write_map(&dir.folder()).unwrap();
+        let map: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(map["node"]["node-1"], "node-1");
+        assert_eq!(map["ip"]["10.0.0.1"], "ip-1");
+        assert_eq!(map["custom"]["acme"], "custom-1");
+    }
+}
@@ -0,0 +1,181 @@
+use anyhow::{anyhow, Result};
+use kube::{
+    api::{ApiResource, DynamicObject, GroupVersionKind, ListParams},
+    Api, Client,
+};
+use simplelog::{__private::log::warn, info};
+
+use std::collections::BTreeSet;
+
+use crate::collector::Collector;
+
+//a resource whose group/version moved across the supported Kubernetes releases (1.22 to 1.29),
+//versions go from the preferred to the oldest shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VersionedResource {
+    pub kind: &'static str,
+    pub plural: &'static str,
+    pub versions: &'static [&'static str],
+    //file name prefix, <prefix>_<namespace>.yaml.
+    pub file: &'static str,
+}
+
+pub const VERSIONED_RESOURCES: [VersionedResource; 2] = [
+    VersionedResource {
+        kind: "HorizontalPodAutoscaler",
+        plural: "horizontalpodautoscalers",
+        versions: &["autoscaling/v2", "autoscaling/v2beta2", "autoscaling/v1"],
+        file: "hpa",
+    },
+    VersionedResource {
+        kind: "PodDisruptionBudget",
+        plural: "poddisruptionbudgets",
+        versions: &["policy/v1", "policy/v1beta1"],
+        file: "pdb",
+    },
+];
+
+//every group/version served by the cluster, "v1" for the core group.
+pub async fn served_versions(client: &Client) -> Result<BTreeSet<String>> {
+    let mut served = client
+        .list_core_api_versions()
+        .await?
+        .versions
+        .into_iter()
+        .collect::<BTreeSet<String>>();
+    client
+        .list_api_groups()
+        .await?
+        .groups
+        .iter()
+        .flat_map(|g| g.versions.iter())
+        .for_each(|v| {
+            served.insert(v.group_version.clone());
+        });
+    Ok(served)
+}
+
+//the first of the known versions the cluster serves.
+pub fn select_version<'a>(served: &BTreeSet<String>, versions: &[&'a str]) -> Option<&'a str> {
+    versions.iter().find(|v| served.contains(**v)).copied()
+}
+
+fn api(
+    client: &Client,
+    api_version: &str,
+    r: &VersionedResource,
+    namespace: &str,
+) -> Api<DynamicObject> {
+    let (group, version) = api_version.split_once('/').unwrap_or(("", api_version));
+    let ar =
+        ApiResource::from_gvk_with_plural(&GroupVersionKind::gvk(group, version, r.kind), r.plural);
+    Api::namespaced_with(client.clone(), namespace, &ar)
+}
+
+//the versioned resources of each namespace as yaml, in the version negotiated through discovery
+//which is recorded in manifest.json. A resource served in none of its known versions is skipped.
+pub async fn collect_versioned(collector: &Collector, folder: &str) -> Result<()> {
+    let served = served_versions(&collector.client).await?;
+    for r in &VERSIONED_RESOURCES {
+        let Some(version) = select_version(&served, r.versions) else {
+            warn!(
+                "None of {} is served for {}, not collected.",
+                r.versions.join(", "),
+                r.kind
+            );
+            continue;
+        };
+        info!("{} collected as {}.", r.kind, version);
+        for ns in &collector.config.context_namespace {
+            let filename = format!("{}_{}.yaml", r.file, ns);
+            let yaml = api(&collector.client, version, r, ns)
+                .list(&ListParams::default())
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|l| {
+                    let mut yaml = String::new();
+                    for mut obj in l.items {
+                        obj.metadata.managed_fields = None;
+                        yaml.push_str("---\n");
+                        yaml.push_str(&serde_yaml::to_string(&obj)?);
+                    }
+                    Ok(yaml)
+                });
+            match yaml {
+                Ok(y) if y.is_empty() => {}
+                Ok(y) => {
+                    let er = anyhow!("Empty {}.", filename);
+                    match collector
+                        .ctx
+                        .write_file(folder, y.as_bytes(), &filename, er)
+                    {
+                        Ok(_) => {
+                            collector.ctx.record_api_version(folder, &filename, version);
+                            info!("File has been created {}/{}", folder, filename)
+                        }
+                        Err(e) => warn!("{}", e),
+                    }
+                }
+                Err(e) => {
+                    let attempted = format!("list {} {} in {}", version, r.plural, ns);
+                    collector
+                        .ctx
+                        .record_failure(folder, &filename, &attempted, &e);
+                    warn!("{}", e)
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, json_client};
+
+    async fn served(apis: &str) -> BTreeSet<String> {
+        let read =
+            |name: &str| std::fs::read_to_string(fixture(&format!("discovery/{}", name))).unwrap();
+        let client = json_client(&[("/api", read("api.json")), ("/apis", read(apis))]);
+        served_versions(&client).await.unwrap()
+    }
+
+    fn selected(served: &BTreeSet<String>) -> Vec<Option<&'static str>> {
+        VERSIONED_RESOURCES
+            .iter()
+            .map(|r| select_version(served, r.versions))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn served_versions_of_the_core_and_the_groups() {
+        let served = served("apis_1_29.json").await;
+        assert_eq!(
+            served.into_iter().collect::<Vec<_>>(),
+            vec!["apps/v1", "autoscaling/v1", "autoscaling/v2", "v1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn the_newest_known_version_the_cluster_serves() {
+        //1.22: no autoscaling/v2 yet, both policy versions.
+        let old = served("apis_1_22.json").await;
+        assert_eq!(
+            selected(&old),
+            vec![Some("autoscaling/v2beta2"), Some("policy/v1")]
+        );
+        //1.29 without the policy group: PodDisruptionBudget is skipped.
+        let new = served("apis_1_29.json").await;
+        assert_eq!(selected(&new), vec![Some("autoscaling/v2"), None]);
+    }
+
+    #[test]
+    fn nothing_served_nothing_selected() {
+        assert_eq!(select_version(&BTreeSet::new(), &["policy/v1"]), None);
+        assert_eq!(
+            select_version(&BTreeSet::from(["v1".to_string()]), &[]),
+            None
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use futures_util::future::BoxFuture;
+use hyper::{Request, Response};
+use tower::{Layer, Service};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use crate::logging;
+
+pub const API_WARNINGS_FILE: &str = "api_deprecation_warnings.txt";
+
+//deprecation warnings of the api server -> the collectors (phases) that triggered them.
+//Shared by the RunContext and the layer of the clients built for the run.
+#[derive(Debug, Default)]
+pub struct ApiWarnings(Mutex<BTreeMap<String, BTreeSet<String>>>);
+
+impl ApiWarnings {
+    //the warnings received outside of any phase come from the client setup.
+    pub fn record(&self, warning: &str, collector: &str) {
+        let collector = if collector.is_empty() {
+            "client setup"
+        } else {
+            collector
+        };
+        self.0
+            .lock()
+            .unwrap()
+            .entry(warning.to_string())
+            .or_default()
+            .insert(collector.to_string());
+    }
+
+    //one line per distinct warning with the collectors that triggered it.
+    pub fn render(&self) -> String {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(w, c)| {
+                format!(
+                    "{} [{}]\n",
+                    w,
+                    c.iter().cloned().collect::<Vec<String>>().join(", ")
+                )
+            })
+            .collect()
+    }
+}
+
+//the text of a Warning header, 299 - "extensions/v1beta1 Ingress is deprecated" -> the quoted part.
+pub fn parse_warning(value: &str) -> String {
+    let text = value.splitn(3, ' ').nth(2).unwrap_or(value).trim();
+    text.strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(text)
+        .replace("\\\"", "\"")
+}
+
+fn record_warnings<B>(response: &Response<B>, warnings: &ApiWarnings) {
+    let phase = logging::current_phase();
+    response
+        .headers()
+        .get_all("warning")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .for_each(|v| warnings.record(&parse_warning(v), &phase));
+}
+
+//reads the Warning headers of every api server response, nothing else is requested.
+#[derive(Debug, Clone, Default)]
+pub struct WarningLayer(pub Arc<ApiWarnings>);
+
+impl<S> Layer<S> for WarningLayer {
+    type Service = WarningService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        WarningService {
+            inner,
+            warnings: self.0.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WarningService<S> {
+    inner: S,
+    warnings: Arc<ApiWarnings>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for WarningService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let response = self.inner.call(request);
+        let warnings = self.warnings.clone();
+        Box::pin(async move {
+            let response = response.await?;
+            record_warnings(&response, &warnings);
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Body;
+    use tower::ServiceExt;
+
+    #[test]
+    fn warning_text_from_the_header() {
+        assert_eq!(
+            parse_warning("299 - \"extensions/v1beta1 Ingress is deprecated\""),
+            "extensions/v1beta1 Ingress is deprecated"
+        );
+        assert_eq!(
+            parse_warning("299 - \"use \\\"networking.k8s.io/v1\\\"\""),
+            "use \"networking.k8s.io/v1\""
+        );
+        assert_eq!(parse_warning("unquoted"), "unquoted");
+    }
+
+    #[test]
+    fn warnings_are_deduplicated_and_grouped_by_collector() {
+        let warnings = ApiWarnings::default();
+        warnings.record("policy/v1beta1 PodDisruptionBudget is deprecated", "infra");
+        warnings.record("policy/v1beta1 PodDisruptionBudget is deprecated", "helm");
+        warnings.record("policy/v1beta1 PodDisruptionBudget is deprecated", "infra");
+        warnings.record("batch/v1beta1 CronJob is deprecated", "");
+        assert_eq!(
+            warnings.render(),
+            "batch/v1beta1 CronJob is deprecated [client setup]\n\
+             policy/v1beta1 PodDisruptionBudget is deprecated [helm, infra]\n"
+        );
+        assert_eq!(ApiWarnings::default().render(), "");
+    }
+
+    #[tokio::test]
+    async fn the_layer_records_into_its_own_run_only() {
+        let run = Arc::new(ApiWarnings::default());
+        let other = Arc::new(ApiWarnings::default());
+        let server = tower::service_fn(|_: Request<Body>| async {
+            let response = Response::builder()
+                .header(
+                    "Warning",
+                    "299 - \"extensions/v1beta1 Ingress is deprecated\"",
+                )
+                .header("Warning", "299 - \"batch/v1beta1 CronJob is deprecated\"")
+                .body(Body::empty())
+                .unwrap();
+            Ok::<_, std::io::Error>(response)
+        });
+        let service = WarningLayer(run.clone()).layer(server);
+        for _ in 0..2 {
+            service
+                .clone()
+                .oneshot(Request::new(Body::empty()))
+                .await
+                .unwrap();
+        }
+        let recorded = run.0.lock().unwrap().keys().cloned().collect::<Vec<_>>();
+        assert_eq!(
+            recorded,
+            vec![
+                "batch/v1beta1 CronJob is deprecated",
+                "extensions/v1beta1 Ingress is deprecated"
+            ]
+        );
+        assert!(other.0.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,280 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::core::v1::{Endpoints, Namespace, Pod};
+use kube::{api::ListParams, Api};
+use serde_derive::Serialize;
+use simplelog::info;
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use crate::collector::Collector;
+
+pub const APISERVER_LATENCY_FILE: &str = "apiserver_latency.json";
+pub const LATENCY_SAMPLES: usize = 5;
+pub const DEFAULT_LATENCY_THRESHOLD_MS: u64 = 1000;
+
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+//round trips of one call, in milliseconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CallLatency {
+    pub samples_ms: Vec<f64>,
+    pub errors: Vec<String>,
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub max_ms: f64,
+}
+
+impl CallLatency {
+    pub fn from_samples(samples: Vec<Result<Duration>>) -> Self {
+        let mut latency = CallLatency::default();
+        for s in samples {
+            match s {
+                Ok(d) => latency.samples_ms.push(d.as_secs_f64() * 1000.0),
+                Err(e) => latency.errors.push(e.to_string()),
+            }
+        }
+        let mut sorted = latency.samples_ms.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        if let (Some(min), Some(max)) = (sorted.first(), sorted.last()) {
+            latency.min_ms = *min;
+            latency.max_ms = *max;
+            latency.median_ms = sorted[sorted.len() / 2];
+        }
+        latency
+    }
+}
+
+//time `samples` sequential runs of the call, a failed call keeps its error instead of a duration.
+pub async fn time_call<C, F, Fut, T>(clock: &C, samples: usize, call: F) -> Vec<Result<Duration>>
+where
+    C: Clock,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut out = vec![];
+    for _ in 0..samples {
+        let start = clock.now();
+        let r = call().await;
+        let end = clock.now();
+        out.push(r.map(|_| end - start));
+    }
+    out
+}
+
+//the calls whose median is above the threshold, "<call> <median>ms".
+pub fn slow_calls(calls: &BTreeMap<String, CallLatency>, threshold_ms: u64) -> Vec<String> {
+    calls
+        .iter()
+        .filter(|(_, l)| !l.samples_ms.is_empty() && l.median_ms > threshold_ms as f64)
+        .map(|(c, l)| format!("{} {:.0}ms", c, l.median_ms))
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ApiServerLatency {
+    pub calls: BTreeMap<String, CallLatency>,
+    //the kubernetes service endpoints of the default namespace, the api server addresses.
+    pub endpoints: Option<Endpoints>,
+}
+
+pub async fn measure_latency<C: Clock>(collector: &Collector, clock: &C) -> ApiServerLatency {
+    let client = &collector.client;
+    let namespace = collector
+        .config
+        .context_namespace
+        .first()
+        .cloned()
+        .unwrap_or_else(|| "default".to_string());
+    let namespaces: Api<Namespace> = Api::all(client.clone());
+    let pods: Api<Pod> = Api::namespaced(client.clone(), &namespace);
+    let mut calls = BTreeMap::new();
+    let samples = time_call(clock, LATENCY_SAMPLES, || async {
+        Ok(namespaces.get(&namespace).await?)
+    })
+    .await;
+    calls.insert(
+        format!("get namespace {}", namespace),
+        CallLatency::from_samples(samples),
+    );
+    let samples = time_call(clock, LATENCY_SAMPLES, || async {
+        Ok(pods.list(&ListParams::default().limit(1)).await?)
+    })
+    .await;
+    calls.insert(
+        format!("list pods {} limit=1", namespace),
+        CallLatency::from_samples(samples),
+    );
+    let samples = time_call(clock, LATENCY_SAMPLES, || async {
+        Ok(client.apiserver_version().await?)
+    })
+    .await;
+    calls.insert(
+        "get /version".to_string(),
+        CallLatency::from_samples(samples),
+    );
+    let endpoints: Api<Endpoints> = Api::namespaced(client.clone(), "default");
+    ApiServerLatency {
+        calls,
+        endpoints: endpoints.get("kubernetes").await.ok(),
+    }
+}
+
+//infra/apiserver_latency.json, the slow calls go to the health summary.
+pub async fn collect_latency(collector: &Collector, folder: &str) -> Result<()> {
+    let latency = measure_latency(collector, &SystemClock).await;
+    let threshold = match collector.config.apiserver_latency_threshold_ms {
+        0 => DEFAULT_LATENCY_THRESHOLD_MS,
+        t => t,
+    };
+    let slow = slow_calls(&latency.calls, threshold);
+    collector.ctx.health.lock().unwrap().apiserver_slow_calls = Some(slow);
+    let er = anyhow!("Empty api server latency.");
+    collector.ctx.write_file(
+        folder,
+        serde_json::to_string_pretty(&latency)?.as_bytes(),
+        APISERVER_LATENCY_FILE,
+        er,
+    )?;
+    info!(
+        "File has been created {}/{}",
+        folder, APISERVER_LATENCY_FILE
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        context::RunContext,
+        test_support::{json_client, run_context, TempDir},
+        ConfigFile,
+    };
+    use std::sync::{Arc, Mutex};
+
+    //every now() moves the time by the next step, a call then lasts the step of its second now().
+    struct FakeClock {
+        state: Mutex<(Instant, Vec<Duration>)>,
+    }
+
+    impl FakeClock {
+        fn new(call_ms: &[u64]) -> Self {
+            let mut steps = call_ms
+                .iter()
+                .flat_map(|ms| [Duration::ZERO, Duration::from_millis(*ms)])
+                .collect::<Vec<_>>();
+            steps.reverse();
+            FakeClock {
+                state: Mutex::new((Instant::now(), steps)),
+            }
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            let mut state = self.state.lock().unwrap();
+            let step = state.1.pop().unwrap_or_default();
+            state.0 += step;
+            state.0
+        }
+    }
+
+    #[tokio::test]
+    async fn each_sample_is_timed_on_the_clock() {
+        let clock = FakeClock::new(&[30, 10, 20]);
+        let samples = time_call(&clock, 3, || async { Ok(()) }).await;
+        let ms = samples
+            .into_iter()
+            .map(|s| s.unwrap().as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(ms, vec![30, 10, 20]);
+    }
+
+    #[test]
+    fn latency_statistics_skip_the_errors() {
+        let latency = CallLatency::from_samples(vec![
+            Ok(Duration::from_millis(30)),
+            Err(anyhow!("timeout")),
+            Ok(Duration::from_millis(10)),
+            Ok(Duration::from_millis(20)),
+            Ok(Duration::from_millis(40)),
+        ]);
+        assert_eq!(latency.samples_ms, vec![30.0, 10.0, 20.0, 40.0]);
+        assert_eq!(latency.errors, vec!["timeout".to_string()]);
+        assert_eq!(
+            (latency.min_ms, latency.median_ms, latency.max_ms),
+            (10.0, 30.0, 40.0)
+        );
+        let failed = CallLatency::from_samples(vec![Err(anyhow!("refused"))]);
+        assert_eq!(
+            (failed.min_ms, failed.median_ms, failed.max_ms),
+            (0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn only_medians_above_the_threshold_are_slow() {
+        let calls = BTreeMap::from([
+            (
+                "get /version".to_string(),
+                CallLatency::from_samples(vec![Ok(Duration::from_millis(1500))]),
+            ),
+            (
+                "list pods".to_string(),
+                CallLatency::from_samples(vec![Ok(Duration::from_millis(1000))]),
+            ),
+            //every call failed, nothing to compare.
+            (
+                "get namespace".to_string(),
+                CallLatency::from_samples(vec![Err(anyhow!("forbidden"))]),
+            ),
+        ]);
+        assert_eq!(slow_calls(&calls, 1000), vec!["get /version 1500ms"]);
+        assert!(slow_calls(&calls, 2000).is_empty());
+    }
+
+    #[tokio::test]
+    async fn calls_are_measured_against_the_api_server() {
+        let dir = TempDir::new();
+        let client = json_client(&[
+            (
+                "/api/v1/namespaces/web",
+                r#"{"apiVersion":"v1","kind":"Namespace","metadata":{"name":"web"}}"#.to_string(),
+            ),
+            (
+                "/version",
+                r#"{"major":"1","minor":"29","gitVersion":"v1.29.0","gitCommit":"","gitTreeState":"","buildDate":"","goVersion":"","compiler":"","platform":""}"#.to_string(),
+            ),
+        ]);
+        let config = ConfigFile {
+            context_namespace: vec!["web".to_string()],
+            ..Default::default()
+        };
+        let ctx: Arc<RunContext> = Arc::new(run_context(&dir));
+        let collector = Collector::new(client, config, ctx);
+        let clock = FakeClock::new(&[5; LATENCY_SAMPLES * 3]);
+        let latency = measure_latency(&collector, &clock).await;
+        let namespace = &latency.calls["get namespace web"];
+        assert_eq!(namespace.samples_ms, vec![5.0; LATENCY_SAMPLES]);
+        assert_eq!(latency.calls["get /version"].median_ms, 5.0);
+        //the pods are not served: every sample is an error.
+        let pods = &latency.calls["list pods web limit=1"];
+        assert!(pods.samples_ms.is_empty());
+        assert_eq!(pods.errors.len(), LATENCY_SAMPLES);
+        assert!(latency.endpoints.is_none());
+    }
+}
@@ -0,0 +1,425 @@
+use anyhow::{anyhow, Result};
+use flate2::{write::GzEncoder, Compression};
+use serde_derive::{Deserialize, Serialize};
+use simplelog::{__private::log::warn, info};
+use tar::{EntryType, Header};
+use tokio_util::sync::CancellationToken;
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::write_atomic;
+
+//sidecar of the archive being built, <archive>.state.json.
+pub const ARCHIVE_STATE_SUFFIX: &str = ".state.json";
+//input bytes between two checkpoints, each checkpoint closes a gzip member.
+pub const CHECKPOINT_BYTES: u64 = 64 * 1024 * 1024;
+const BLOCK: usize = 512;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MemberRecord {
+    //relative to the collection folder.
+    pub path: String,
+    pub size: u64,
+}
+
+//the archive up to `offset` holds `members` as complete gzip members, `checkpoint` is the last of them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveState {
+    pub source: String,
+    pub prefix: String,
+    pub offset: u64,
+    pub checkpoint: String,
+    pub members: Vec<MemberRecord>,
+}
+
+pub fn state_path(archive: &Path) -> PathBuf {
+    let mut p = archive.as_os_str().to_owned();
+    p.push(ARCHIVE_STATE_SUFFIX);
+    PathBuf::from(p)
+}
+
+//files and directories of the collection, each directory followed by its content, names sorted.
+pub fn sorted_members(source: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut members = vec![];
+    walk(source, "", &mut members)?;
+    Ok(members)
+}
+
+fn walk(dir: &Path, rel: &str, out: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    entries.sort();
+    for path in entries {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let member = if rel.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", rel, name)
+        };
+        out.push((member.clone(), path.clone()));
+        if path.is_dir() {
+            walk(&path, &member, out)?;
+        }
+    }
+    Ok(())
+}
+
+fn pad<W: Write>(w: &mut W, written: u64) -> io::Result<()> {
+    let rest = (BLOCK - (written % BLOCK as u64) as usize) % BLOCK;
+    w.write_all(&[0; BLOCK][..rest])
+}
+
+//names above 100 bytes go first in a GNU long name entry, like tar::Builder does.
+fn write_header<W: Write>(w: &mut W, header: &mut Header, name: &str) -> io::Result<()> {
+    if header.set_path(name).is_err() {
+        let mut long = Header::new_gnu();
+        let link = b"././@LongLink";
+        if let Some(gnu) = long.as_gnu_mut() {
+            gnu.name[..link.len()].copy_from_slice(link);
+        }
+        long.set_mode(0o644);
+        long.set_mtime(0);
+        long.set_entry_type(EntryType::GNULongName);
+        long.set_size(name.len() as u64 + 1);
+        long.set_cksum();
+        w.write_all(long.as_bytes())?;
+        w.write_all(name.as_bytes())?;
+        w.write_all(&[0])?;
+        pad(w, name.len() as u64 + 1)?;
+        let truncated = &name.as_bytes()[..100];
+        header.as_old_mut().name.copy_from_slice(truncated);
+    }
+    header.set_cksum();
+    w.write_all(header.as_bytes())
+}
+
+//one tar entry, the file must still have the size it had when the header was made.
+fn append_path<W: Write>(w: &mut W, name: &str, path: &Path) -> io::Result<u64> {
+    let meta = fs::metadata(path)?;
+    let mut header = Header::new_gnu();
+    header.set_metadata(&meta);
+    write_header(w, &mut header, name)?;
+    if meta.is_dir() {
+        return Ok(0);
+    }
+    let copied = io::copy(&mut File::open(path)?.take(meta.len()), w)?;
+    if copied != meta.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("{} shrank while archived", path.display()),
+        ));
+    }
+    pad(w, copied)?;
+    Ok(copied)
+}
+
+fn append_data<W: Write>(w: &mut W, name: &str, data: &[u8]) -> io::Result<()> {
+    let mut header = Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp() as u64);
+    write_header(w, &mut header, name)?;
+    w.write_all(data)?;
+    pad(w, data.len() as u64)
+}
+
+//the state is only trusted when every member it lists still has its recorded size.
+fn resumable(state: &ArchiveState, source: &Path, prefix: &str, archive: &Path) -> Result<()> {
+    if state.source != source.display().to_string() || state.prefix != prefix {
+        return Err(anyhow!("the state belongs to {}", state.source));
+    }
+    let len = fs::metadata(archive)?.len();
+    if len < state.offset {
+        return Err(anyhow!(
+            "the archive is {} bytes, the checkpoint at {}",
+            len,
+            state.offset
+        ));
+    }
+    for m in &state.members {
+        let meta = fs::metadata(source.join(&m.path))?;
+        let size = if meta.is_dir() { 0 } else { meta.len() };
+        if size != m.size {
+            return Err(anyhow!(
+                "{} is {} bytes, {} when archived",
+                m.path,
+                size,
+                m.size
+            ));
+        }
+    }
+    Ok(())
+}
+
+//<prefix>/<member> for every member of source in sorted order, as a multi-member gzip: a gzip member is
+//closed and the state written every CHECKPOINT_BYTES. With resume, a valid state truncates the archive to its
+//checkpoint and the build goes on after it. Cancellation stops at the next member with the state kept.
+pub fn build_archive(
+    source: &Path,
+    prefix: &str,
+    archive: &Path,
+    compression: Compression,
+    cancel: &CancellationToken,
+    resume: bool,
+    extra: &[(String, Vec<u8>)],
+) -> Result<()> {
+    build_in_checkpoints(
+        source,
+        prefix,
+        archive,
+        compression,
+        cancel,
+        resume,
+        extra,
+        CHECKPOINT_BYTES,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_in_checkpoints(
+    source: &Path,
+    prefix: &str,
+    archive: &Path,
+    compression: Compression,
+    cancel: &CancellationToken,
+    resume: bool,
+    extra: &[(String, Vec<u8>)],
+    checkpoint_bytes: u64,
+) -> Result<()> {
+    let state_file = state_path(archive);
+    let members = sorted_members(source)?;
+    let mut state = ArchiveState {
+        source: source.display().to_string(),
+        prefix: prefix.to_string(),
+        ..Default::default()
+    };
+    if resume && state_file.exists() {
+        let previous: ArchiveState = serde_json::from_str(&fs::read_to_string(&state_file)?)?;
+        let in_order = previous
+            .members
+            .iter()
+            .zip(&members)
+            .all(|(m, (rel, _))| &m.path == rel);
+        match resumable(&previous, source, prefix, archive).and_then(|_| {
+            in_order
+                .then_some(())
+                .ok_or_else(|| anyhow!("the members changed"))
+        }) {
+            Ok(_) => {
+                info!(
+                    "Archive resumed after {} ({} members, {} bytes).",
+                    previous.checkpoint,
+                    previous.members.len(),
+                    previous.offset
+                );
+                state = previous;
+            }
+            Err(e) => warn!("Archive state not usable, {}, building from scratch.", e),
+        }
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(archive)?;
+    //offset is 0 when building from scratch.
+    file.set_len(state.offset)?;
+    file.seek(SeekFrom::Start(state.offset))?;
+
+    let mut pending = members[state.members.len()..].iter().peekable();
+    while pending.peek().is_some() {
+        let mut gz = GzEncoder::new(&mut file, compression);
+        let mut chunk = 0;
+        let mut done = vec![];
+        while chunk < checkpoint_bytes && !cancel.is_cancelled() {
+            let Some((rel, path)) = pending.next() else {
+                break;
+            };
+            let size = append_path(&mut gz, &format!("{}/{}", prefix, rel), path)?;
+            chunk += size + BLOCK as u64;
+            done.push(MemberRecord {
+                path: rel.clone(),
+                size,
+            });
+        }
+        gz.finish()?;
+        file.sync_data()?;
+        state.offset = file.stream_position()?;
+        if let Some(last) = done.last() {
+            state.checkpoint = last.path.clone();
+        }
+        state.members.extend(done);
+        write_atomic(&state_file, serde_json::to_string(&state)?.as_bytes())?;
+        if cancel.is_cancelled() {
+            return Err(anyhow!(
+                "Archive cancelled after {}, {} kept for --resume.",
+                state.checkpoint,
+                state_file.display()
+            ));
+        }
+    }
+
+    //the extra entries and the end of archive blocks in the last gzip member.
+    let mut gz = GzEncoder::new(&mut file, compression);
+    for (name, data) in extra {
+        append_data(&mut gz, name, data)?;
+    }
+    gz.write_all(&[0; 2 * BLOCK])?;
+    gz.finish()?;
+    file.sync_all()?;
+    fs::remove_file(&state_file)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use flate2::read::MultiGzDecoder;
+
+    //a small collection of three folders.
+    fn collection(dir: &TempDir) -> PathBuf {
+        dir.write("info/apps/kafka_topics.txt", b"topics\n");
+        dir.write("info/infra/nodes.json", &vec![b'n'; 3000]);
+        dir.write("info/pods/a.log", &vec![b'a'; 1500]);
+        dir.write("info/pods/b.log", b"b\n");
+        dir.path().join("info")
+    }
+
+    //(member, content) of the tar.gz, the directories with an empty content.
+    fn entries(archive: &Path) -> Vec<(String, Vec<u8>)> {
+        let mut tar = tar::Archive::new(MultiGzDecoder::new(File::open(archive).unwrap()));
+        tar.entries()
+            .unwrap()
+            .map(|e| {
+                let mut e = e.unwrap();
+                let name = e.path().unwrap().display().to_string();
+                let mut data = vec![];
+                e.read_to_end(&mut data).unwrap();
+                (name, data)
+            })
+            .collect()
+    }
+
+    fn build(
+        source: &Path,
+        archive: &Path,
+        cancel: &CancellationToken,
+        resume: bool,
+    ) -> Result<()> {
+        let extra = [("info/collection_info.json".to_string(), b"{}".to_vec())];
+        //a checkpoint after every member.
+        build_in_checkpoints(
+            source,
+            "info",
+            archive,
+            Compression::fast(),
+            cancel,
+            resume,
+            &extra,
+            1,
+        )
+    }
+
+    #[test]
+    fn members_in_sorted_order() {
+        let dir = TempDir::new();
+        let source = collection(&dir);
+        let archive = dir.path().join("info.tar.gz");
+        build(&source, &archive, &CancellationToken::new(), false).unwrap();
+        let names = entries(&archive)
+            .into_iter()
+            .map(|(n, _)| n)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "info/apps",
+                "info/apps/kafka_topics.txt",
+                "info/infra",
+                "info/infra/nodes.json",
+                "info/pods",
+                "info/pods/a.log",
+                "info/pods/b.log",
+                "info/collection_info.json",
+            ]
+        );
+        assert!(!state_path(&archive).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn interrupted_build_resumes_after_its_checkpoint() {
+        let dir = TempDir::new();
+        let source = collection(&dir);
+        std::os::unix::fs::symlink(dir.path().join("gone"), source.join("pods/c.log")).unwrap();
+        let archive = dir.path().join("info.tar.gz");
+        //the build stops at the link, half a gzip member after the last checkpoint.
+        assert!(build(&source, &archive, &CancellationToken::new(), false).is_err());
+        let state: ArchiveState =
+            serde_json::from_str(&fs::read_to_string(state_path(&archive)).unwrap()).unwrap();
+        assert_eq!(state.checkpoint, "pods/b.log");
+        assert_eq!(state.members.len(), 7);
+        assert_eq!(
+            state.members[3],
+            MemberRecord {
+                path: "infra/nodes.json".to_string(),
+                size: 3000
+            }
+        );
+        let before = fs::read(&archive).unwrap();
+        assert!(before.len() as u64 > state.offset);
+
+        fs::remove_file(source.join("pods/c.log")).unwrap();
+        dir.write("info/pods/c.log", b"c\n");
+        build(&source, &archive, &CancellationToken::new(), true).unwrap();
+        let after = fs::read(&archive).unwrap();
+        //the checkpointed part is kept as is, the rest appended.
+        assert_eq!(
+            after[..state.offset as usize],
+            before[..state.offset as usize]
+        );
+        let resumed = entries(&archive);
+        let fresh = dir.path().join("fresh.tar.gz");
+        build(&source, &fresh, &CancellationToken::new(), false).unwrap();
+        assert_eq!(resumed, entries(&fresh));
+        assert_eq!(resumed[7], ("info/pods/c.log".to_string(), b"c\n".to_vec()));
+        assert!(!state_path(&archive).exists());
+    }
+
+    #[test]
+    fn cancelled_build_keeps_its_state() {
+        let dir = TempDir::new();
+        let source = collection(&dir);
+        let archive = dir.path().join("info.tar.gz");
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let e = build(&source, &archive, &cancel, false).unwrap_err();
+        assert!(e.to_string().starts_with("Archive cancelled after "));
+        assert!(state_path(&archive).exists());
+        build(&source, &archive, &CancellationToken::new(), true).unwrap();
+        assert_eq!(entries(&archive).len(), 8);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn changed_members_are_archived_again_from_scratch() {
+        let dir = TempDir::new();
+        let source = collection(&dir);
+        std::os::unix::fs::symlink(dir.path().join("gone"), source.join("pods/c.log")).unwrap();
+        let archive = dir.path().join("info.tar.gz");
+        assert!(build(&source, &archive, &CancellationToken::new(), false).is_err());
+        fs::remove_file(source.join("pods/c.log")).unwrap();
+        //a member before the checkpoint grew since.
+        dir.write("info/pods/a.log", &vec![b'a'; 2000]);
+        build(&source, &archive, &CancellationToken::new(), true).unwrap();
+        let entries = entries(&archive);
+        assert_eq!(entries.len(), 8);
+        assert_eq!(entries[5].1.len(), 2000);
+    }
+}
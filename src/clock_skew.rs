@@ -0,0 +1,187 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use simplelog::info;
+
+use crate::{access::PodAccess, collector::Collector, PodInfo};
+
+pub const CLOCK_SKEW_FILE: &str = "clock_skew.txt";
+pub const DEFAULT_CLOCK_SKEW_THRESHOLD_SECONDS: u64 = 30;
+//the date in the pod, busybox date understands the format as well.
+pub const POD_DATE_COMMAND: [&str; 3] = ["date", "-u", "+%Y-%m-%dT%H:%M:%SZ"];
+
+//remote clock minus the local one, the local time taken in the middle of the round trip.
+pub fn offset(remote: DateTime<Utc>, before: DateTime<Utc>, after: DateTime<Utc>) -> f64 {
+    let local = before + (after - before) / 2;
+    (remote - local).num_milliseconds() as f64 / 1000.0
+}
+
+//the Date response header, "Sun, 06 Nov 1994 08:49:37 GMT".
+pub fn parse_http_date(header: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(header.trim())
+        .map(|d| d.with_timezone(&Utc))
+        .map_err(|e| anyhow!("Date header {:?}: {}", header, e))
+}
+
+//the output of POD_DATE_COMMAND.
+pub fn parse_pod_date(output: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(output.trim())
+        .map(|d| d.with_timezone(&Utc))
+        .map_err(|e| anyhow!("date output {:?}: {}", output.trim(), e))
+}
+
+//"<source>: <offset>s", with the mark of an offset above the threshold.
+pub fn render_line(source: &str, offset: &Result<f64>, threshold: u64) -> String {
+    match offset {
+        Ok(o) if o.abs() > threshold as f64 => {
+            format!("{}: {:+.1}s, above {}s", source, o, threshold)
+        }
+        Ok(o) => format!("{}: {:+.1}s", source, o),
+        Err(e) => format!("{}: unknown, {}", source, e),
+    }
+}
+
+async fn apiserver_offset(collector: &Collector) -> Result<f64> {
+    let request = hyper::Request::get("/version").body(hyper::Body::empty())?;
+    let before = Utc::now();
+    let response = collector.client.send(request).await?;
+    let after = Utc::now();
+    let date = response
+        .headers()
+        .get("date")
+        .ok_or_else(|| anyhow!("No Date header in the api server response."))?
+        .to_str()?;
+    Ok(offset(parse_http_date(date)?, before, after))
+}
+
+async fn pod_offset(pod: &PodInfo) -> Result<f64> {
+    let container = pod
+        .containers
+        .first()
+        .ok_or_else(|| anyhow!("Pod {} has no container.", pod.name))?;
+    let command = POD_DATE_COMMAND.iter().map(|c| c.to_string()).collect();
+    let before = Utc::now();
+    let output = pod.api.exec(&pod.name, container, command).await?;
+    let after = Utc::now();
+    Ok(offset(parse_pod_date(&output)?, before, after))
+}
+
+//infra/clock_skew.txt: the api server and the first running pod of each namespace against the local clock,
+//the offsets above clock_skew_threshold_seconds go to the health summary.
+pub async fn collect_clock_skew(
+    collector: &Collector,
+    pods_list: &[PodInfo],
+    folder: &str,
+) -> Result<()> {
+    let threshold = match collector.config.clock_skew_threshold_seconds {
+        0 => DEFAULT_CLOCK_SKEW_THRESHOLD_SECONDS,
+        t => t,
+    };
+    let mut offsets = vec![("api server".to_string(), apiserver_offset(collector).await)];
+    //the nodes clocks through one pod each, no exec in read-only mode.
+    if !collector.config.read_only {
+        for ns in &collector.config.context_namespace {
+            let running = pods_list.iter().find(|p| {
+                p.namespace == *ns
+                    && p.pod.status.as_ref().and_then(|s| s.phase.as_deref()) == Some("Running")
+            });
+            if let Some(p) = running {
+                offsets.push((
+                    format!("pod {}/{} on {}", p.namespace, p.name, p.node_name),
+                    pod_offset(p).await,
+                ));
+            }
+        }
+    }
+    let skewed = offsets
+        .iter()
+        .filter_map(|(source, o)| match o {
+            Ok(o) if o.abs() > threshold as f64 => Some(format!("{} {:+.1}s", source, o)),
+            _ => None,
+        })
+        .collect::<Vec<String>>();
+    collector.ctx.health.lock().unwrap().clock_skew = Some(skewed);
+    let report = offsets
+        .iter()
+        .map(|(source, o)| render_line(source, o, threshold))
+        .collect::<Vec<String>>()
+        .join("\n")
+        + "\n";
+    let er = anyhow!("Empty {}.", CLOCK_SKEW_FILE);
+    collector
+        .ctx
+        .write_file(folder, report.as_bytes(), CLOCK_SKEW_FILE, er)?;
+    info!("File has been created {}/{}", folder, CLOCK_SKEW_FILE);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn at(h: u32, m: u32, s: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 17, h, m, s).unwrap()
+    }
+
+    #[test]
+    fn offset_against_the_middle_of_the_round_trip() {
+        let before = at(8, 0, 0);
+        let after = before + Duration::seconds(4);
+        //in sync: the remote read its clock halfway.
+        assert_eq!(offset(at(8, 0, 2), before, after), 0.0);
+        assert_eq!(offset(at(8, 0, 47), before, after), 45.0);
+        assert_eq!(offset(at(7, 59, 0), before, after), -62.0);
+        let after = before + Duration::milliseconds(300);
+        assert_eq!(offset(at(8, 0, 1), before, after), 0.85);
+    }
+
+    #[test]
+    fn date_header_of_the_api_server() {
+        assert_eq!(
+            parse_http_date("Sat, 17 Oct 2026 08:00:05 GMT").unwrap(),
+            at(8, 0, 5)
+        );
+        assert_eq!(
+            parse_http_date(" Sat, 17 Oct 2026 10:00:05 +0200\r").unwrap(),
+            at(8, 0, 5)
+        );
+        let e = parse_http_date("2026-10-17T08:00:05Z").unwrap_err();
+        assert!(e
+            .to_string()
+            .starts_with("Date header \"2026-10-17T08:00:05Z\": "));
+    }
+
+    #[test]
+    fn date_output_of_a_pod() {
+        assert_eq!(
+            parse_pod_date("2026-10-17T08:00:05Z\n").unwrap(),
+            at(8, 0, 5)
+        );
+        //a date without the format argument support.
+        let e = parse_pod_date("Sat Oct 17 08:00:05 UTC 2026\n").unwrap_err();
+        assert!(e
+            .to_string()
+            .starts_with("date output \"Sat Oct 17 08:00:05 UTC 2026\": "));
+        assert!(parse_pod_date("").is_err());
+    }
+
+    #[test]
+    fn lines_above_the_threshold_are_marked() {
+        assert_eq!(
+            render_line("api server", &Ok(0.25), 30),
+            "api server: +0.2s"
+        );
+        assert_eq!(
+            render_line("pod a/b on n", &Ok(-30.0), 30),
+            "pod a/b on n: -30.0s"
+        );
+        assert_eq!(
+            render_line("pod a/b on n", &Ok(-30.5), 30),
+            "pod a/b on n: -30.5s, above 30s"
+        );
+        assert_eq!(
+            render_line("api server", &Err(anyhow!("No Date header.")), 30),
+            "api server: unknown, No Date header."
+        );
+    }
+}
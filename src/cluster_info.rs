@@ -0,0 +1,137 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::{
+    apps::v1::{DaemonSet, Deployment},
+    core::v1::{Node, PodSpec},
+};
+use kube::{api::ListParams, Api};
+use simplelog::{__private::log::warn, info};
+
+use std::fs;
+
+use crate::{access::PodAccess, collector::Collector, get_logs, get_pod_list};
+
+pub const CLUSTER_INFO_FOLDER: &str = "cluster_info";
+pub const SYSTEM_NAMESPACE: &str = "kube-system";
+const SYSTEM_LOG_TAIL_LINES: i64 = 500;
+
+//cloud provider from the node providerID prefixes ("aws:///eu-west-1a/i-0..." -> aws).
+pub fn cloud_provider(nodes: &[Node]) -> String {
+    let mut providers = nodes
+        .iter()
+        .filter_map(|n| n.spec.as_ref()?.provider_id.clone())
+        .filter_map(|p| p.split_once("://").map(|(s, _)| s.to_string()))
+        .collect::<Vec<String>>();
+    providers.sort();
+    providers.dedup();
+    match providers.len() {
+        0 => "unknown".to_string(),
+        _ => providers.join(", "),
+    }
+}
+
+fn images(spec: Option<&PodSpec>) -> String {
+    spec.map(|s| {
+        s.containers
+            .iter()
+            .map(|c| c.image.clone().unwrap_or_default())
+            .collect::<Vec<String>>()
+            .join(", ")
+    })
+    .unwrap_or_default()
+}
+
+//deployments and daemonsets of kube-system with their images and readiness.
+async fn system_workloads(collector: &Collector) -> Result<String> {
+    let mut out = String::new();
+    let deployments: Api<Deployment> = Api::namespaced(collector.client.clone(), SYSTEM_NAMESPACE);
+    for d in deployments.list(&ListParams::default()).await?.items {
+        let status = d.status.clone().unwrap_or_default();
+        out.push_str(&format!(
+            "deployment {} ready {}/{} images {}\n",
+            d.metadata.name.clone().unwrap_or_default(),
+            status.ready_replicas.unwrap_or_default(),
+            status.replicas.unwrap_or_default(),
+            images(d.spec.as_ref().and_then(|s| s.template.spec.as_ref()))
+        ));
+    }
+    let daemonsets: Api<DaemonSet> = Api::namespaced(collector.client.clone(), SYSTEM_NAMESPACE);
+    for d in daemonsets.list(&ListParams::default()).await?.items {
+        let status = d.status.clone().unwrap_or_default();
+        out.push_str(&format!(
+            "daemonset {} ready {}/{} images {}\n",
+            d.metadata.name.clone().unwrap_or_default(),
+            status.number_ready,
+            status.desired_number_scheduled,
+            images(d.spec.as_ref().and_then(|s| s.template.spec.as_ref()))
+        ));
+    }
+    Ok(out)
+}
+
+//managed clusters hide the control plane, this is what is still visible from kube-system.
+//kube-system has its own access since it is usually not in context_namespace.
+pub async fn collect_cluster_info(collector: &Collector, infra_folder: &str) -> Result<()> {
+    let folder = format!("{}/{}", infra_folder, CLUSTER_INFO_FOLDER);
+    fs::create_dir_all(&folder)?;
+    let ctx = &collector.ctx;
+    let access = collector.access.in_namespace(SYSTEM_NAMESPACE);
+
+    let provider = cloud_provider(&access.list_nodes().await?);
+    info!("Cloud provider detected: {}.", provider);
+    if let Err(e) = collector
+        .kubectl_to_file(&["cluster-info"], &folder, "cluster_info.txt")
+        .await
+    {
+        warn!("{}", e)
+    }
+    let er = anyhow!("Empty cloud provider.");
+    ctx.write_file(
+        &folder,
+        format!("{}\n", provider).as_bytes(),
+        "cloud_provider.txt",
+        er,
+    )?;
+
+    match system_workloads(collector).await {
+        Ok(w) => {
+            let er = anyhow!("No workload found in {}.", SYSTEM_NAMESPACE);
+            if let Err(e) = ctx.write_file(&folder, w.as_bytes(), "workloads.txt", er) {
+                warn!("{}", e)
+            }
+        }
+        Err(e) => ctx.record_failure(
+            &folder,
+            "workloads.txt",
+            "list kube-system deployments and daemonsets",
+            &e,
+        ),
+    }
+
+    for p in get_pod_list(vec![access], "".to_string(), "".to_string()).await? {
+        for c in &p.containers {
+            let filename = format!("logs_{}_{}.log", p.name, c);
+            let logs = get_logs(
+                p.name.clone(),
+                c.clone(),
+                p.api.clone(),
+                false,
+                None,
+                Some(SYSTEM_LOG_TAIL_LINES),
+                None,
+            )
+            .await
+            .and_then(|l| {
+                let er = anyhow!("No Log found {} on container {}.", p.name, c);
+                ctx.write_file(&folder, l.as_bytes(), &filename, er)
+            });
+            if let Err(e) = logs {
+                warn!("{}", e)
+            }
+        }
+    }
+
+    collector
+        .namespace_events(SYSTEM_NAMESPACE, &folder)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,254 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::apps::v1::DaemonSet;
+use kube::{api::ListParams, Api};
+use simplelog::{__private::log::warn, info};
+
+use std::{collections::BTreeMap, fs};
+
+use crate::{
+    access::PodAccess, collector::Collector, get_logs, get_pod_list, preset, send_command, PodInfo,
+};
+
+pub const CNI_FOLDER: &str = "cni";
+const CNI_LOG_TAIL_LINES: i64 = 500;
+//one pod per node, the failing ones first.
+const CNI_MAX_PODS: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CniFlavor {
+    Calico,
+    Cilium,
+    Flannel,
+    Weave,
+    KubeProxy,
+    //a network daemonset of an unsupported plugin, logs only.
+    Other(String),
+}
+
+//name fragments of the network daemonsets without a dedicated collector.
+const OTHER_CNI_NAMES: [&str; 6] = ["cni", "antrea", "ovn", "kube-router", "canal", "multus"];
+
+//flavor from the well-known labels first (k8s-app, app, name), then from the daemonset name.
+pub fn cni_flavor(name: &str, labels: &BTreeMap<String, String>) -> Option<CniFlavor> {
+    let candidates = ["k8s-app", "app", "name", "app.kubernetes.io/name"]
+        .iter()
+        .filter_map(|k| labels.get(*k).map(|v| v.as_str()))
+        .chain([name]);
+    for c in candidates {
+        let flavor = match c {
+            "calico-node" => Some(CniFlavor::Calico),
+            "cilium" | "cilium-agent" => Some(CniFlavor::Cilium),
+            "flannel" | "kube-flannel-ds" | "kube-flannel" => Some(CniFlavor::Flannel),
+            "weave-net" => Some(CniFlavor::Weave),
+            "kube-proxy" => Some(CniFlavor::KubeProxy),
+            _ => None,
+        };
+        if flavor.is_some() {
+            return flavor;
+        }
+    }
+    OTHER_CNI_NAMES
+        .iter()
+        .any(|o| name.contains(o))
+        .then(|| CniFlavor::Other(name.to_string()))
+}
+
+//status command run in the first container of a daemonset pod, None when there is none.
+pub fn status_command(flavor: &CniFlavor) -> Option<(&'static str, &'static str)> {
+    match flavor {
+        CniFlavor::Calico => Some((
+            "calicoctl node status 2>&1 || calico-node -felix-live -bird-live 2>&1",
+            "calico_node_status.log",
+        )),
+        CniFlavor::Cilium => Some((
+            "cilium status --verbose 2>&1 || cilium-dbg status --verbose 2>&1",
+            "cilium_status.log",
+        )),
+        _ => None,
+    }
+}
+
+fn selector(ds: &DaemonSet) -> String {
+    ds.spec
+        .as_ref()
+        .and_then(|s| s.selector.match_labels.as_ref())
+        .map(|l| {
+            l.iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<String>>()
+                .join(",")
+        })
+        .unwrap_or_default()
+}
+
+async fn collect_daemonset(
+    collector: &Collector,
+    ds: &DaemonSet,
+    flavor: &CniFlavor,
+    folder: &str,
+) -> Result<()> {
+    let ctx = &collector.ctx;
+    let name = ds.metadata.name.clone().unwrap_or_default();
+    let namespace = ds.metadata.namespace.clone().unwrap_or_default();
+    let access = collector.access.in_namespace(&namespace);
+    let mut pods: Vec<PodInfo> = get_pod_list(vec![access], selector(ds), "".to_string())
+        .await?
+        .into_iter()
+        .filter(|p| {
+            collector.config.node_names.is_empty()
+                || collector.config.node_names.contains(&p.node_name)
+        })
+        .collect();
+    pods.sort_by_key(|p| !preset::pod_failing(&p.pod));
+    pods.truncate(CNI_MAX_PODS);
+    info!(
+        "CNI {:?}: daemonset {}/{}, {} pods collected.",
+        flavor,
+        namespace,
+        name,
+        pods.len()
+    );
+
+    for p in &pods {
+        for c in &p.containers {
+            let filename = format!("logs_{}_{}_{}.log", name, p.name, c);
+            let logs = get_logs(
+                p.name.clone(),
+                c.clone(),
+                p.api.clone(),
+                false,
+                None,
+                Some(CNI_LOG_TAIL_LINES),
+                None,
+            )
+            .await
+            .and_then(|l| {
+                let er = anyhow!("No Log found {} on container {}.", p.name, c);
+                ctx.write_file(folder, l.as_bytes(), &filename, er)
+            });
+            if let Err(e) = logs {
+                warn!("{}", e)
+            }
+        }
+        if collector.config.read_only {
+            continue;
+        }
+        if let (Some((command, file)), Some(container)) =
+            (status_command(flavor), p.containers.first())
+        {
+            let filename = format!("{}_{}", p.name, file);
+            let status = send_command(
+                p.name.clone(),
+                p.api.clone(),
+                container.clone(),
+                ["/bin/sh", "-c", command],
+            )
+            .await
+            .inspect_err(|e| ctx.record_failure(folder, &filename, command, e))
+            .and_then(|s| {
+                let er = anyhow!("Empty {} status on {}.", name, p.name);
+                ctx.write_file(folder, s.as_bytes(), &filename, er)
+            });
+            match status {
+                Ok(_) => info!("File has been created {}/{}", folder, filename),
+                Err(e) => warn!("{}", e),
+            }
+        }
+    }
+    Ok(())
+}
+
+//network plugin and kube-proxy daemonsets of any namespace, logs and status under infra/cni.
+pub async fn collect_cni(collector: &Collector, infra_folder: &str) -> Result<()> {
+    let folder = format!("{}/{}", infra_folder, CNI_FOLDER);
+    fs::create_dir_all(&folder)?;
+    let daemonsets: Api<DaemonSet> = Api::all(collector.client.clone());
+    let found = daemonsets
+        .list(&ListParams::default())
+        .await?
+        .items
+        .into_iter()
+        .filter_map(|ds| {
+            let name = ds.metadata.name.clone().unwrap_or_default();
+            let labels = ds.metadata.labels.clone().unwrap_or_default();
+            cni_flavor(&name, &labels).map(|f| (ds, f))
+        })
+        .collect::<Vec<_>>();
+    if found.is_empty() {
+        warn!("No CNI daemonset found.");
+    }
+    for (ds, flavor) in &found {
+        if let Err(e) = collect_daemonset(collector, ds, flavor, &folder).await {
+            warn!("CNI {:?}: {}", flavor, e);
+            collector.ctx.record_folder(&folder, false);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+    use kube::ResourceExt;
+
+    fn flavors() -> Vec<(String, Option<CniFlavor>)> {
+        let json = fs::read_to_string(fixture("cni/daemonsets.json")).unwrap();
+        let daemonsets: Vec<DaemonSet> = serde_json::from_str(&json).unwrap();
+        daemonsets
+            .iter()
+            .map(|d| (d.name_any(), cni_flavor(&d.name_any(), d.labels())))
+            .collect()
+    }
+
+    #[test]
+    fn flavor_of_the_well_known_daemonsets() {
+        let flavors = flavors();
+        let flavor = |name: &str| flavors.iter().find(|(n, _)| n == name).unwrap().1.clone();
+        assert_eq!(flavor("calico-node"), Some(CniFlavor::Calico));
+        assert_eq!(flavor("cilium"), Some(CniFlavor::Cilium));
+        assert_eq!(flavor("kube-flannel-ds"), Some(CniFlavor::Flannel));
+        assert_eq!(flavor("weave-net"), Some(CniFlavor::Weave));
+        assert_eq!(flavor("kube-proxy"), Some(CniFlavor::KubeProxy));
+        //the label wins over a name that says nothing.
+        assert_eq!(flavor("aws-node"), Some(CniFlavor::Cilium));
+    }
+
+    #[test]
+    fn unknown_network_daemonsets_fall_back_to_logs_only() {
+        let flavors = flavors();
+        let flavor = |name: &str| flavors.iter().find(|(n, _)| n == name).unwrap().1.clone();
+        let canal = flavor("rke2-canal");
+        assert_eq!(canal, Some(CniFlavor::Other("rke2-canal".to_string())));
+        assert_eq!(status_command(&canal.unwrap()), None);
+        assert_eq!(flavor("node-exporter"), None);
+        assert_eq!(flavor("fluent-bit"), None);
+    }
+
+    #[test]
+    fn name_alone_is_enough_without_labels() {
+        let none = BTreeMap::new();
+        assert_eq!(cni_flavor("calico-node", &none), Some(CniFlavor::Calico));
+        assert_eq!(cni_flavor("cilium-agent", &none), Some(CniFlavor::Cilium));
+        assert_eq!(
+            cni_flavor("antrea-agent", &none),
+            Some(CniFlavor::Other("antrea-agent".to_string()))
+        );
+        assert_eq!(cni_flavor("calico-kube-controllers", &none), None);
+    }
+
+    #[test]
+    fn status_commands_of_calico_and_cilium_only() {
+        assert_eq!(
+            status_command(&CniFlavor::Calico).unwrap().1,
+            "calico_node_status.log"
+        );
+        assert_eq!(
+            status_command(&CniFlavor::Cilium).unwrap().1,
+            "cilium_status.log"
+        );
+        for f in [CniFlavor::Flannel, CniFlavor::Weave, CniFlavor::KubeProxy] {
+            assert_eq!(status_command(&f), None);
+        }
+    }
+}
@@ -0,0 +1,967 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use k8s_openapi::api::core::v1::{ContainerStatus, Event};
+use kube::Client;
+use simplelog::{__private::log::warn, info};
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{
+    access::{KubeAccess, PodAccess},
+    capped_file_name, component_command,
+    context::RunContext,
+    events,
+    external::{self, run_external, ExternalError, ExternalOutput},
+    get_logs, get_pod_list, kubectl_command, log_capped,
+    log_queue::{self, LogTask},
+    logging::{self, LogScope},
+    node_pressure, preset, spark, timeline, ConfigFile, PodInfo,
+};
+
+//what the kubelet kept of the previous run of a container, there even when its logs are gone.
+pub fn last_state_report(status: &ContainerStatus) -> Option<String> {
+    let t = status.last_state.as_ref()?.terminated.as_ref()?;
+    let time = |t: &Option<k8s_openapi::apimachinery::pkg::apis::meta::v1::Time>| {
+        t.as_ref().map(|t| t.0.to_rfc3339()).unwrap_or_default()
+    };
+    Some(format!(
+        "container: {}\nexitCode: {}\nreason: {}\nsignal: {}\nstartedAt: {}\nfinishedAt: {}\nmessage: {}\n",
+        status.name,
+        t.exit_code,
+        t.reason.clone().unwrap_or_default(),
+        t.signal.map(|s| s.to_string()).unwrap_or_default(),
+        time(&t.started_at),
+        time(&t.finished_at),
+        t.message.clone().unwrap_or_default()
+    ))
+}
+
+//the pods a run collects, and why some of them are kept whatever the filters say.
+#[derive(Debug, Clone)]
+pub struct PodPlan<A = KubeAccess> {
+    pub pods: Vec<PodInfo<A>>,
+    //(namespace, pod) -> reason, the pods named by Warning events, evicted pods and spark executors.
+    pub forced: BTreeMap<(String, String), String>,
+    //(namespace, pod) of the spark executors -> application id, they go under pods/spark/<application id>.
+    pub executors: BTreeMap<(String, String), String>,
+    //node_names in node mode, empty otherwise.
+    pub node_names: Vec<String>,
+}
+
+impl<A> PodPlan<A> {
+    pub fn forced_reason(&self, pod: &PodInfo<A>) -> Option<&String> {
+        self.forced.get(&(pod.namespace.clone(), pod.name.clone()))
+    }
+
+    pub fn forced(&self, pod: &PodInfo<A>) -> bool {
+        self.forced_reason(pod).is_some()
+    }
+
+    //where the per pod outputs go below the pods folder, the node and application names anonymized.
+    pub fn folder(&self, pod: &PodInfo<A>, pods_folder: &str, ctx: &RunContext) -> String {
+        if let Some(app) = self
+            .executors
+            .get(&(pod.namespace.clone(), pod.name.clone()))
+        {
+            ctx.subfolder(&format!("{}/{}", pods_folder, spark::SPARK_FOLDER), app)
+        } else if self.node_names.contains(&pod.node_name) {
+            ctx.subfolder(pods_folder, &pod.node_name)
+        } else {
+            pods_folder.to_string()
+        }
+    }
+}
+
+//the pod level collectors shared by the regular run and the watch mode.
+//The pods, their logs and execs go through access, the client is left to the cluster level collectors.
+#[derive(Clone)]
+pub struct Collector<A = KubeAccess> {
+    pub client: Client,
+    pub access: A,
+    pub config: ConfigFile,
+    pub ctx: Arc<RunContext>,
+}
+
+impl Collector {
+    pub fn new(client: Client, config: ConfigFile, ctx: Arc<RunContext>) -> Self {
+        let access = KubeAccess::namespaced(client.clone(), client.default_namespace())
+            .with_execs(ctx.execs.clone());
+        Collector::with_access(client, access, config, ctx)
+    }
+
+    //for library users holding an authenticated client, nothing is read from a kubeconfig.
+    pub fn from_client(client: Client, config: ConfigFile) -> Self {
+        Collector::new(client, config, Arc::new(RunContext::default()))
+    }
+}
+
+impl<A: PodAccess> Collector<A> {
+    pub fn with_access(
+        client: Client,
+        access: A,
+        config: ConfigFile,
+        ctx: Arc<RunContext>,
+    ) -> Self {
+        Collector {
+            client,
+            access,
+            config,
+            ctx,
+        }
+    }
+
+    //the access of each configured namespace, in order.
+    pub fn pod_access(&self) -> Vec<A> {
+        self.config
+            .context_namespace
+            .iter()
+            .map(|n| self.access.in_namespace(n))
+            .collect()
+    }
+
+    //every pod of the configured namespaces.
+    pub async fn list_pods(&self) -> Result<Vec<PodInfo<A>>> {
+        get_pod_list(self.pod_access(), "".to_string(), "".to_string()).await
+    }
+
+    //the events of the configured namespaces, a namespace whose events cannot be listed is left out.
+    pub async fn list_events(&self) -> Vec<Event> {
+        let mut events = vec![];
+        for a in self.pod_access() {
+            match a.list_events().await {
+                Ok(e) => events.extend(e),
+                Err(e) => warn!("{}", e),
+            }
+        }
+        events
+    }
+
+    //the pods of the run: the failing ones with only_failing_pods, the ones scheduled on node_names
+    //in node mode. The pods named by Warning events, the evicted ones and the executors of the spark
+    //drivers are collected whatever the filters say.
+    pub fn plan_pods(&self, mut pods: Vec<PodInfo<A>>, events: &[Event]) -> PodPlan<A> {
+        let warning_pods = events::warning_event_pods(events, &self.config.context_namespace);
+        let executors = spark::driver_executors(&pods);
+        let forced = pods
+            .iter()
+            .filter_map(|p| {
+                let key = (p.namespace.clone(), p.name.clone());
+                let reason = if warning_pods.contains(&key) {
+                    events::WARNING_EVENT_REASON.to_string()
+                } else if node_pressure::eviction_message(&p.pod).is_some() {
+                    //no logs left, their status is written by the node pressure report.
+                    node_pressure::EVICTED_REASON.to_string()
+                } else {
+                    let app = executors.get(&key)?;
+                    format!("{} of {}", spark::SPARK_EXECUTOR_REASON, app)
+                };
+                Some((key, reason))
+            })
+            .collect::<BTreeMap<_, _>>();
+        let is_forced =
+            |p: &PodInfo<A>| forced.contains_key(&(p.namespace.clone(), p.name.clone()));
+        if self.config.only_failing_pods {
+            pods.retain(|p| preset::pod_failing(&p.pod) || is_forced(p));
+            info!("{} failing pods selected.", pods.len());
+        }
+        let node_names = &self.config.node_names;
+        if !node_names.is_empty() {
+            pods.retain(|p| node_names.contains(&p.node_name) || is_forced(p));
+            info!(
+                "<yellow>Node mode, {} pods scheduled on {}.</>",
+                pods.len(),
+                node_names.join(", ")
+            );
+        }
+        let forced = forced
+            .into_iter()
+            .filter(|((n, p), _)| pods.iter().any(|x| &x.namespace == n && &x.name == p))
+            .collect();
+        PodPlan {
+            pods,
+            forced,
+            executors,
+            node_names: node_names.clone(),
+        }
+    }
+
+    //the logs of the queued tasks, at most concurrency pods at a time. A pod's containers are
+    //fetched one after the other so they share the connection instead of competing for permits,
+    //and a pod is only spawned once it holds a permit so the queue order is the fetch order.
+    //Every task comes back with whether its log was fetched.
+    pub async fn collect_logs(
+        &self,
+        tasks: Vec<LogTask<A>>,
+        concurrency: usize,
+        folder: impl Fn(&PodInfo<A>) -> String,
+    ) -> Result<Vec<(LogTask<A>, bool)>> {
+        let permits = Arc::new(tokio::sync::Semaphore::new(concurrency));
+        let mut handles = vec![];
+        for tasks in log_queue::by_pod(log_queue::order(tasks)) {
+            let permit = permits.clone().acquire_owned().await?;
+            let collector = self.clone();
+            let folder = folder(&tasks[0].pod);
+            let handle = tokio::task::spawn(async move {
+                //written once for the pod, the next containers of a deleted pod point to it.
+                let mut remains: Option<String> = None;
+                let mut fetched = vec![];
+                for task in tasks {
+                    let scope = LogScope::pod(&task.pod.namespace, &task.pod.name, &task.container);
+                    //an error is kept to its container, the next ones are still fetched.
+                    let ok =
+                        logging::scoped(scope, collector.queued_log(&task, &folder, &mut remains))
+                            .await;
+                    fetched.push((task, ok));
+                }
+                drop(permit);
+                fetched
+            });
+            handles.push(handle);
+        }
+        let mut fetched = vec![];
+        for handle in handles {
+            match handle.await {
+                Ok(f) => fetched.extend(f),
+                Err(e) => {
+                    warn!("{}", e);
+                    if let Some(pods) = self.ctx.folders.first() {
+                        self.ctx.record_folder(pods, false);
+                    }
+                }
+            }
+        }
+        Ok(fetched)
+    }
+
+    async fn queued_log(
+        &self,
+        task: &LogTask<A>,
+        folder: &str,
+        remains: &mut Option<String>,
+    ) -> bool {
+        let LogTask {
+            pod,
+            container,
+            previous,
+            since_seconds,
+            priority,
+        } = task;
+        let l = self
+            .pod_logs(pod, container, *previous, *since_seconds, folder)
+            .await;
+        let filename = self
+            .config
+            .file_name_templates
+            .log(pod, container, *previous);
+        self.ctx.record_queue_priority(folder, &filename, *priority);
+        let Err(e) = l else {
+            return true;
+        };
+        //the events collector still knows about a pod deleted meanwhile.
+        if pod.deletion_timestamp.is_some() && component_command::pod_gone(&e) {
+            if remains.is_none() {
+                match self.terminated_pod_remains(pod, folder).await {
+                    Ok(f) => *remains = Some(f),
+                    Err(e) => warn!("{}", e),
+                }
+            }
+            if let Some(f) = remains {
+                self.ctx.record_note(
+                    folder,
+                    &filename,
+                    &format!(
+                        "pod deleted during the collection, last status and events in {}",
+                        f
+                    ),
+                );
+            }
+        }
+        warn!("{}", e);
+        false
+    }
+
+    pub async fn kubectl(&self, args: &[&str]) -> Result<ExternalOutput, ExternalError> {
+        let timeout = external::timeout(self.config.external_command_timeout_seconds);
+        run_external(
+            kubectl_command(&self.config, &self.ctx.kubeconfig),
+            args,
+            timeout,
+            &self.ctx.usage,
+        )
+        .await
+    }
+
+    //run a kubectl command against the configured context and write its stdout.
+    pub async fn kubectl_to_file(&self, args: &[&str], folder: &str, filename: &str) -> Result<()> {
+        let o = self.kubectl(args).await?;
+        o.warn_stderr();
+        let er = anyhow!("{} empty response", o.command);
+        self.ctx.write_file(folder, &o.stdout, filename, er)?;
+        info!("File has been created {}/{}", folder, filename);
+        Ok(())
+    }
+
+    //kubectl describe followed by the timeline of the pod events and container restarts.
+    pub async fn describe_pod<P>(
+        &self,
+        pod: &PodInfo<P>,
+        events: &[Event],
+        folder: &str,
+    ) -> Result<()> {
+        let filename = self.config.file_name_templates.description(pod);
+        self.ctx
+            .claim_output(folder, &filename, &pod.namespace, &pod.name)?;
+        let o = self
+            .kubectl(&["describe", "pod", &pod.name, "-n", &pod.namespace])
+            .await?;
+        o.warn_stderr();
+        let er = anyhow!("{} empty response", o.command);
+        let mut description = o.stdout;
+        if !description.is_empty() {
+            description.extend(timeline::pod_timeline(&pod.pod, events, Utc::now()).as_bytes());
+        }
+        self.ctx.write_file(folder, &description, &filename, er)?;
+        info!("File has been created {}/{}", folder, filename);
+        Ok(())
+    }
+
+    //the pod object already fetched by the listing, without managedFields.
+    pub fn pod_manifest<P>(&self, pod: &PodInfo<P>, folder: &str) -> Result<()> {
+        let mut manifest = pod.pod.clone();
+        manifest.metadata.managed_fields = None;
+        let yaml = serde_yaml::to_string(&manifest)?;
+        let filename = format!("{}_{}.yaml", pod.namespace, pod.name);
+        self.ctx
+            .claim_output(folder, &filename, &pod.namespace, &pod.name)?;
+        let er = anyhow!("Empty manifest for pod {}.", pod.name);
+        self.ctx
+            .write_file(folder, yaml.as_bytes(), &filename, er)?;
+        info!("File has been created {}/{}", folder, filename);
+        Ok(())
+    }
+
+    //<ns>_<pod>_<container>.lastState.txt for every container with a terminated last state.
+    pub fn last_states<P>(&self, pod: &PodInfo<P>, folder: &str) -> Result<()> {
+        let statuses = pod.pod.status.as_ref().into_iter().flat_map(|s| {
+            s.init_container_statuses
+                .iter()
+                .flatten()
+                .chain(s.container_statuses.iter().flatten())
+        });
+        for status in statuses {
+            if let Some(report) = last_state_report(status) {
+                let filename = format!(
+                    "{}_{}_{}.lastState.txt",
+                    pod.namespace, pod.name, status.name
+                );
+                self.ctx
+                    .claim_output(folder, &filename, &pod.namespace, &pod.name)?;
+                let er = anyhow!(
+                    "Empty last state for {} on container {}.",
+                    pod.name,
+                    status.name
+                );
+                self.ctx
+                    .write_file(folder, report.as_bytes(), &filename, er)?;
+                info!("File has been created {}/{}", folder, filename);
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn namespace_events(&self, namespace: &str, folder: &str) -> Result<()> {
+        let filename = format!("kubernetes_events_{}.events", namespace);
+        self.kubectl_to_file(&["get", "events", "-n", namespace], folder, &filename)
+            .await
+    }
+
+    //fetch and write the logs of one container. Only a failed fetch is an error,
+    //an empty log is reported as a warning.
+    pub async fn pod_logs<P: PodAccess>(
+        &self,
+        pod: &PodInfo<P>,
+        container: &str,
+        previous: bool,
+        since_seconds: Option<i64>,
+        folder: &str,
+    ) -> Result<()> {
+        let kind = if previous { "previous" } else { "current" };
+        let limit_bytes = (self.config.log_limit_bytes > 0).then_some(self.config.log_limit_bytes);
+        let mut filename = self
+            .config
+            .file_name_templates
+            .log(pod, container, previous);
+        self.ctx
+            .claim_output(folder, &filename, &pod.namespace, &pod.name)?;
+        self.ctx.claim_output(
+            folder,
+            &capped_file_name(&filename),
+            &pod.namespace,
+            &pod.name,
+        )?;
+        let fetch = || {
+            get_logs(
+                pod.name.clone(),
+                container.to_string(),
+                pod.api.clone(),
+                previous,
+                since_seconds,
+                (self.config.log_tail_lines > 0).then_some(self.config.log_tail_lines),
+                limit_bytes,
+            )
+        };
+        let mut l = fetch().await;
+        //a terminating pod will not be there for a later retry, the second try is right away.
+        if l.is_err() && pod.deletion_timestamp.is_some() {
+            warn!(
+                "{} logs of terminating pod {} container {} failed, retrying.",
+                kind, pod.name, container
+            );
+            l = fetch().await;
+        }
+        let l = l.inspect_err(|e| {
+            let attempted = format!(
+                "{} logs of {}/{} container {}",
+                kind, pod.namespace, pod.name, container
+            );
+            self.ctx.record_failure(folder, &filename, &attempted, e)
+        })?;
+        if l.is_empty() {
+            self.ctx.record_folder(folder, true);
+            warn!("No Log found {} on container {}.", pod.name, container);
+            return Ok(());
+        }
+        let capped = log_capped(&l, limit_bytes);
+        if capped {
+            filename = capped_file_name(&filename);
+            warn!(
+                "{} logs of {} container {} reached log_limit_bytes, they may be truncated.",
+                kind, pod.name, container
+            );
+        }
+        let er = anyhow!("No Log found {} on container {}.", pod.name, container);
+        self.ctx.write_file(folder, l.as_bytes(), &filename, er)?;
+        if capped {
+            self.ctx.mark_capped(folder, &filename);
+        }
+        info!("File has been created {}/{}", folder, filename);
+        Ok(())
+    }
+
+    //what is left of a pod deleted during the run: its last listed status and its events,
+    //in <folder>/<ns>_<pod>.terminated_status.yaml whose name is returned.
+    pub async fn terminated_pod_remains<P: PodAccess>(
+        &self,
+        pod: &PodInfo<P>,
+        folder: &str,
+    ) -> Result<String> {
+        let filename = format!("{}_{}.terminated_status.yaml", pod.namespace, pod.name);
+        self.ctx
+            .claim_output(folder, &filename, &pod.namespace, &pod.name)?;
+        let mut content = serde_yaml::to_string(&pod.pod.status)?;
+        content.push_str("events:\n");
+        match pod.api.list_events().await {
+            Ok(events) => events
+                .iter()
+                .filter(|e| {
+                    e.involved_object.kind.as_deref() == Some("Pod")
+                        && e.involved_object.name.as_deref() == Some(&pod.name)
+                })
+                .for_each(|e| content.push_str(&format!("  - {:?}\n", events::event_line(e)))),
+            Err(e) => content.push_str(&format!("  # events unavailable: {}\n", e)),
+        }
+        let er = anyhow!("Empty status of the terminated pod {}.", pod.name);
+        self.ctx
+            .write_file(folder, content.as_bytes(), &filename, er)?;
+        info!("File has been created {}/{}", folder, filename);
+        Ok(filename)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        access::FakeAccess,
+        anonymize::Anonymizer,
+        context::FileStatus,
+        get_pod_list,
+        test_support::{fixture, offline_client, run_context, TempDir},
+        SubprocessKubeconfig,
+    };
+
+    fn statuses() -> Vec<ContainerStatus> {
+        let json = std::fs::read_to_string(fixture("last_state/statuses.json")).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn last_state_report_has_the_oom_kill_message() {
+        let report = last_state_report(&statuses()[0]).unwrap();
+        assert_eq!(
+            report,
+            "container: oom\nexitCode: 137\nreason: OOMKilled\nsignal: \n\
+             startedAt: 2026-10-17T07:00:00+00:00\nfinishedAt: 2026-10-17T07:59:00+00:00\n\
+             message: memory limit of 512Mi exceeded\n"
+        );
+    }
+
+    #[test]
+    fn last_state_report_leaves_missing_fields_empty() {
+        let report = last_state_report(&statuses()[1]).unwrap();
+        assert!(report.contains("exitCode: 143\nreason: Error\nsignal: 15\n"));
+        assert!(report.contains("startedAt: \nfinishedAt: \nmessage: \n"));
+    }
+
+    #[test]
+    fn no_last_state_report_without_a_terminated_state() {
+        assert_eq!(last_state_report(&statuses()[2]), None);
+        let mut status = statuses()[2].clone();
+        status.last_state = None;
+        assert_eq!(last_state_report(&status), None);
+    }
+
+    fn collector(dir: &TempDir, namespaces: &[&str]) -> Collector<FakeAccess> {
+        let config = ConfigFile {
+            context_namespace: namespaces.iter().map(|n| n.to_string()).collect(),
+            ..Default::default()
+        };
+        with_config(dir, "access/cluster.json", config)
+    }
+
+    fn with_config(dir: &TempDir, cluster: &str, config: ConfigFile) -> Collector<FakeAccess> {
+        let access = FakeAccess::from_fixture(&fixture(cluster)).unwrap();
+        Collector::with_access(offline_client(), access, config, Arc::new(run_context(dir)))
+    }
+
+    #[tokio::test]
+    async fn injected_client_subprocesses_never_read_the_default_kubeconfig() {
+        let dir = TempDir::new();
+        let config = ConfigFile {
+            context_name: "injected".to_string(),
+            context_namespace: vec!["web".to_string()],
+            ..Default::default()
+        };
+        let kubeconfig = SubprocessKubeconfig::for_run(&config, "", true).unwrap();
+        let access = FakeAccess::from_fixture(&fixture("access/cluster.json")).unwrap();
+        let ctx = Arc::new(run_context(&dir).with_kubeconfig(kubeconfig));
+        let c = Collector::with_access(offline_client(), access, config, ctx);
+
+        let null = SubprocessKubeconfig::isolated().path;
+        let cmd = kubectl_command(&c.config, &c.ctx.kubeconfig);
+        let kubeconfig = cmd
+            .get_envs()
+            .find(|(k, _)| *k == "KUBECONFIG")
+            .and_then(|(_, v)| v.map(|v| v.to_string_lossy().to_string()));
+        assert_eq!(kubeconfig, null);
+        //the KUBECONFIG of the tool is replaced, not inherited.
+        let mut shell = std::process::Command::new("sh");
+        shell.args(["-c", "printf %s \"$KUBECONFIG\""]);
+        shell.env("KUBECONFIG", "/home/user/.kube/config");
+        c.ctx.kubeconfig.apply(&mut shell);
+        let out = shell.output().unwrap();
+        assert_eq!(Some(String::from_utf8(out.stdout).unwrap()), null);
+        //an explicit path of the caller is still used.
+        let config = ConfigFile::default();
+        let given = SubprocessKubeconfig::for_run(&config, "/etc/kube/ci.yaml", true).unwrap();
+        assert_eq!(given.path.as_deref(), Some("/etc/kube/ci.yaml"));
+        let own = SubprocessKubeconfig::for_run(&config, "", false).unwrap();
+        assert_eq!(own.path, None);
+    }
+
+    //the pods of selection.json kept by the filters of config, with their forced reasons.
+    async fn plan(config: ConfigFile) -> (Vec<String>, BTreeMap<String, String>) {
+        let dir = TempDir::new();
+        let config = ConfigFile {
+            context_namespace: vec!["apps".to_string()],
+            ..config
+        };
+        let c = with_config(&dir, "access/selection.json", config);
+        let pods = c.list_pods().await.unwrap();
+        let events = c.list_events().await;
+        let plan = c.plan_pods(pods, &events);
+        let names = plan.pods.iter().map(|p| p.name.clone()).collect();
+        let forced = plan
+            .forced
+            .into_iter()
+            .map(|((_, p), reason)| (p, reason))
+            .collect();
+        (names, forced)
+    }
+
+    #[tokio::test]
+    async fn pod_access_lists_the_configured_namespaces_only() {
+        let dir = TempDir::new();
+        let c = collector(&dir, &["kafka", "web"]);
+        let pods = get_pod_list(c.pod_access(), "".to_string(), "".to_string())
+            .await
+            .unwrap();
+        let names = pods
+            .iter()
+            .map(|p| format!("{}/{}", p.namespace, p.name))
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["kafka/kafka-0", "kafka/kafka-1", "web/web-0"]);
+    }
+
+    #[tokio::test]
+    async fn plan_without_filters_keeps_every_pod() {
+        let (pods, forced) = plan(ConfigFile::default()).await;
+        assert_eq!(
+            pods,
+            [
+                "driver-0",
+                "exec-1",
+                "healthy-0",
+                "crashing-0",
+                "evicted-0",
+                "warned-0"
+            ]
+        );
+        assert_eq!(forced["warned-0"], events::WARNING_EVENT_REASON);
+        assert_eq!(forced["evicted-0"], node_pressure::EVICTED_REASON);
+        assert_eq!(forced["exec-1"], "spark executor of spark-1");
+        assert_eq!(forced.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn only_failing_pods_keeps_the_forced_ones() {
+        let config = ConfigFile {
+            only_failing_pods: true,
+            ..Default::default()
+        };
+        let (pods, forced) = plan(config).await;
+        assert_eq!(pods, ["exec-1", "crashing-0", "evicted-0", "warned-0"]);
+        assert_eq!(forced.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn node_mode_keeps_the_node_pods_and_the_forced_ones() {
+        let dir = TempDir::new();
+        let config = ConfigFile {
+            context_namespace: vec!["apps".to_string()],
+            node_names: vec!["node-a".to_string()],
+            ..Default::default()
+        };
+        let c = with_config(&dir, "access/selection.json", config);
+        let pods = c.list_pods().await.unwrap();
+        let events = c.list_events().await;
+        let plan = c.plan_pods(pods, &events);
+        let folders = plan
+            .pods
+            .iter()
+            .map(|p| {
+                (
+                    p.name.as_str(),
+                    plan.folder(p, "pods", &RunContext::default()),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            folders,
+            [
+                ("driver-0", "pods/node-a".to_string()),
+                ("exec-1", "pods/spark/spark-1".to_string()),
+                ("healthy-0", "pods/node-a".to_string()),
+                ("evicted-0", "pods".to_string()),
+                ("warned-0", "pods".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn node_and_application_folders_are_anonymized() {
+        let dir = TempDir::new();
+        let config = ConfigFile {
+            context_namespace: vec!["apps".to_string()],
+            node_names: vec!["node-a".to_string()],
+            ..Default::default()
+        };
+        let access = FakeAccess::from_fixture(&fixture("access/selection.json")).unwrap();
+        let anonymizer =
+            Anonymizer::new(&["node-a".to_string()], &["spark-1".to_string()]).unwrap();
+        let ctx = Arc::new(run_context(&dir).with_anonymizer(anonymizer));
+        let c = Collector::with_access(offline_client(), access, config, ctx.clone());
+        let pods = c.list_pods().await.unwrap();
+        let plan = c.plan_pods(pods, &[]);
+        let folder = |name: &str| {
+            let p = plan.pods.iter().find(|p| p.name == name).unwrap();
+            plan.folder(p, "pods", &ctx)
+        };
+        assert_eq!(
+            folder("healthy-0"),
+            format!("pods/{}", ctx.anonymize("node-a"))
+        );
+        assert_eq!(
+            folder("exec-1"),
+            format!("pods/spark/{}", ctx.anonymize("spark-1"))
+        );
+        assert!(!folder("healthy-0").contains("node-a"));
+        assert!(!folder("exec-1").contains("spark-1"));
+    }
+
+    #[tokio::test]
+    async fn collect_logs_reports_each_task() {
+        let dir = TempDir::new();
+        let c = collector(&dir, &["kafka", "web"]);
+        let pods = c.list_pods().await.unwrap();
+        let tasks = pods
+            .iter()
+            .flat_map(|p| {
+                p.containers
+                    .iter()
+                    .map(|container| LogTask::new(p.clone(), container.clone(), false))
+            })
+            .collect();
+        let folder = format!("{}/pods", dir.folder());
+        let fetched = c.collect_logs(tasks, 2, |_| folder.clone()).await.unwrap();
+        let mut outcome = fetched
+            .iter()
+            .map(|(t, ok)| (format!("{}/{}", t.pod.name, t.container), *ok))
+            .collect::<Vec<_>>();
+        outcome.sort();
+        assert_eq!(
+            outcome,
+            [
+                ("kafka-0/kafka".to_string(), true),
+                ("kafka-1/kafka".to_string(), false),
+                ("web-0/nginx".to_string(), true),
+                ("web-0/sidecar".to_string(), true),
+            ]
+        );
+        assert!(dir
+            .path()
+            .join("pods/logs_current_web_web-0_nginx.log")
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn failing_container_does_not_stop_the_others_of_its_pod() {
+        let dir = TempDir::new();
+        let mut access = FakeAccess::from_fixture(&fixture("access/cluster.json")).unwrap();
+        //the first container of web-0 has no log, the sidecar has both of its logs.
+        access.logs.remove("web-0/nginx");
+        access
+            .logs
+            .insert("web-0/sidecar".to_string(), "sidecar up\n".to_string());
+        access.logs.insert(
+            "web-0/sidecar/previous".to_string(),
+            "sidecar crashed\n".to_string(),
+        );
+        let config = ConfigFile {
+            context_namespace: vec!["web".to_string()],
+            ..Default::default()
+        };
+        let ctx = Arc::new(run_context(&dir));
+        let c = Collector::with_access(offline_client(), access, config, ctx);
+        let pods = c.list_pods().await.unwrap();
+        let web = pods.iter().find(|p| p.name == "web-0").unwrap();
+        let tasks = vec![
+            LogTask::new(web.clone(), "nginx".to_string(), false),
+            LogTask::new(web.clone(), "sidecar".to_string(), false),
+            LogTask::new(web.clone(), "sidecar".to_string(), true),
+        ];
+        let folder = format!("{}/pods", dir.folder());
+        //one permit, the pod is a single task fetching its containers in turn.
+        let fetched = c.collect_logs(tasks, 1, |_| folder.clone()).await.unwrap();
+        let outcome = fetched
+            .iter()
+            .map(|(t, ok)| (t.container.as_str(), t.previous, *ok))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcome,
+            [
+                ("nginx", false, false),
+                ("sidecar", false, true),
+                ("sidecar", true, true)
+            ]
+        );
+        assert!(dir
+            .read("pods/logs_current_web_web-0_nginx.log.error")
+            .contains("no logs for web-0/nginx"));
+        assert_eq!(
+            dir.read("pods/logs_current_web_web-0_sidecar.log"),
+            "sidecar up\n"
+        );
+        assert_eq!(
+            dir.read("pods/logs_previous_web_web-0_sidecar.log"),
+            "sidecar crashed\n"
+        );
+        let manifest = c.ctx.manifest();
+        assert_eq!(
+            manifest["pods/logs_current_web_web-0_nginx.log"].status,
+            FileStatus::Failed
+        );
+        assert_eq!(
+            manifest["pods/logs_previous_web_web-0_sidecar.log"].status,
+            FileStatus::Ok
+        );
+    }
+
+    #[tokio::test]
+    async fn pod_logs_writes_current_and_previous_logs() {
+        let dir = TempDir::new();
+        let c = collector(&dir, &["kafka"]);
+        let pods = get_pod_list(c.pod_access(), "".to_string(), "".to_string())
+            .await
+            .unwrap();
+        let folder = format!("{}/pods", dir.folder());
+        c.pod_logs(&pods[0], "kafka", false, None, &folder)
+            .await
+            .unwrap();
+        c.pod_logs(&pods[0], "kafka", true, None, &folder)
+            .await
+            .unwrap();
+        assert!(dir
+            .read("pods/logs_current_kafka_kafka-0_kafka.log")
+            .contains("INFO ready"));
+        assert!(dir
+            .read("pods/logs_previous_kafka_kafka-0_kafka.log")
+            .contains("OutOfMemoryError"));
+    }
+
+    #[tokio::test]
+    async fn failed_log_fetch_leaves_an_error_file() {
+        let dir = TempDir::new();
+        let c = collector(&dir, &["kafka"]);
+        let pods = get_pod_list(c.pod_access(), "".to_string(), "".to_string())
+            .await
+            .unwrap();
+        let folder = format!("{}/pods", dir.folder());
+        assert!(c
+            .pod_logs(&pods[1], "kafka", false, None, &folder)
+            .await
+            .is_err());
+        let error = dir.read("pods/logs_current_kafka_kafka-1_kafka.log.error");
+        assert!(error.contains("current logs of kafka/kafka-1 container kafka"));
+        assert!(!dir
+            .path()
+            .join("pods/logs_current_kafka_kafka-1_kafka.log")
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn empty_log_writes_nothing() {
+        let dir = TempDir::new();
+        let c = collector(&dir, &["web"]);
+        let pods = get_pod_list(c.pod_access(), "".to_string(), "".to_string())
+            .await
+            .unwrap();
+        let folder = format!("{}/pods", dir.folder());
+        c.pod_logs(&pods[0], "sidecar", false, None, &folder)
+            .await
+            .unwrap();
+        assert!(!dir
+            .path()
+            .join("pods/logs_current_web_web-0_sidecar.log")
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn manifest_and_last_state_come_from_the_listed_pod() {
+        let dir = TempDir::new();
+        let c = collector(&dir, &["kafka"]);
+        let pods = get_pod_list(c.pod_access(), "".to_string(), "".to_string())
+            .await
+            .unwrap();
+        let folder = format!("{}/pods", dir.folder());
+        c.pod_manifest(&pods[0], &folder).unwrap();
+        c.last_states(&pods[0], &folder).unwrap();
+        c.last_states(&pods[1], &folder).unwrap();
+        let manifest = dir.read("pods/kafka_kafka-0.yaml");
+        assert!(manifest.contains("name: kafka-0"));
+        assert!(!manifest.contains("managedFields"));
+        let last = dir.read("pods/kafka_kafka-0_kafka.lastState.txt");
+        assert!(last.contains("exitCode: 137\nreason: OOMKilled\n"));
+        assert!(!dir
+            .path()
+            .join("pods/kafka_kafka-1_kafka.lastState.txt")
+            .exists());
+    }
+
+    //web-0 of the fixture and a pod of the same name in staging.
+    fn same_name_collector(dir: &TempDir, config: ConfigFile) -> Collector<FakeAccess> {
+        let mut access = FakeAccess::from_fixture(&fixture("access/cluster.json")).unwrap();
+        let mut staging = access
+            .pods
+            .iter()
+            .find(|p| p.metadata.name.as_deref() == Some("web-0"))
+            .unwrap()
+            .clone();
+        staging.metadata.namespace = Some("staging".to_string());
+        access.pods.push(staging);
+        let config = ConfigFile {
+            context_namespace: vec!["web".to_string(), "staging".to_string()],
+            ..config
+        };
+        Collector::with_access(offline_client(), access, config, Arc::new(run_context(dir)))
+    }
+
+    #[tokio::test]
+    async fn pods_of_the_same_name_keep_their_own_outputs() {
+        let dir = TempDir::new();
+        let c = same_name_collector(&dir, ConfigFile::default());
+        let pods = c.list_pods().await.unwrap();
+        let pods = pods
+            .iter()
+            .filter(|p| p.name == "web-0")
+            .collect::<Vec<_>>();
+        assert_eq!(pods.len(), 2);
+        let folder = format!("{}/pods", dir.folder());
+        for p in &pods {
+            c.pod_manifest(p, &folder).unwrap();
+            c.pod_logs(p, "nginx", false, None, &folder).await.unwrap();
+        }
+        assert!(dir.read("pods/web_web-0.yaml").contains("namespace: web\n"));
+        assert!(dir
+            .read("pods/staging_web-0.yaml")
+            .contains("namespace: staging\n"));
+        let manifest = c.ctx.manifest();
+        for ns in ["web", "staging"] {
+            let log = format!("pods/logs_current_{}_web-0_nginx.log", ns);
+            assert_eq!(manifest[&log].status, FileStatus::Ok);
+        }
+    }
+
+    #[tokio::test]
+    async fn colliding_outputs_are_refused_not_overwritten() {
+        let dir = TempDir::new();
+        let config = ConfigFile {
+            file_name_templates: crate::naming::FileNameTemplates {
+                log: "logs_{kind}_{pod}_{container}.log".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let c = same_name_collector(&dir, config);
+        let pods = c.list_pods().await.unwrap();
+        let web = pods
+            .iter()
+            .find(|p| p.name == "web-0" && p.namespace == "web")
+            .unwrap();
+        let staging = pods
+            .iter()
+            .find(|p| p.name == "web-0" && p.namespace == "staging")
+            .unwrap();
+        let folder = format!("{}/pods", dir.folder());
+        c.pod_logs(web, "nginx", false, None, &folder)
+            .await
+            .unwrap();
+        let e = c
+            .pod_logs(staging, "nginx", false, None, &folder)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains(
+            "Output pods/logs_current_web-0_nginx.log of pod staging/web-0 collides with the one of pod web/web-0"
+        ));
+        //the first one is still there and owned by web/web-0.
+        assert!(dir
+            .path()
+            .join("pods/logs_current_web-0_nginx.log")
+            .exists());
+        assert_eq!(
+            c.ctx.output_owners.lock().unwrap()["pods/logs_current_web-0_nginx.log"],
+            "web/web-0"
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use anyhow::{anyhow, Error, Result};
+use hyper::StatusCode;
+use k8s_openapi::api::core::v1::Pod;
+use kube::client::UpgradeConnectionError;
+use simplelog::{__private::log::warn, info};
+
+use std::{
+    io::ErrorKind,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    access::{HttpGet, KubeAccess, PodAccess},
+    components::ComponentScope,
+    context::RunContext,
+    credentials::{self, Credentials},
+    http_or_exec,
+    logging::{self, LogScope},
+    PodInfo,
+};
+
+//the pod is gone (404, on the api call or the websocket upgrade of an exec or port-forward) or
+//its connection dropped, another pod of the component may answer. Any cause of the chain counts.
+pub fn pod_gone(e: &Error) -> bool {
+    e.chain().any(|cause| {
+        if let Some(k) = cause.downcast_ref::<kube::Error>() {
+            return match k {
+                kube::Error::Api(ae) => ae.code == 404,
+                kube::Error::UpgradeConnection(UpgradeConnectionError::ProtocolSwitch(status)) => {
+                    *status == StatusCode::NOT_FOUND
+                }
+                _ => false,
+            };
+        }
+        if let Some(h) = cause.downcast_ref::<hyper::Error>() {
+            return h.is_closed() || h.is_incomplete_message() || h.is_connect();
+        }
+        cause.downcast_ref::<std::io::Error>().is_some_and(|io| {
+            matches!(
+                io.kind(),
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionRefused
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::UnexpectedEof
+            )
+        })
+    })
+}
+
+pub fn pod_ready(pod: &Pod) -> bool {
+    pod.metadata.deletion_timestamp.is_none()
+        && pod
+            .status
+            .as_ref()
+            .and_then(|s| s.conditions.as_ref())
+            .into_iter()
+            .flatten()
+            .any(|c| c.type_ == "Ready" && c.status == "True")
+}
+
+//the pod a component collector runs its commands in, shared by its tasks.
+//Without a scope (a spark driver is not interchangeable) the pod is never replaced.
+pub struct ComponentTarget<A = KubeAccess> {
+    pub component: String,
+    pod: Mutex<PodInfo<A>>,
+    scope: Option<(ComponentScope, A)>,
+}
+
+impl<A: PodAccess> ComponentTarget<A> {
+    pub fn new(component: &str, pod: PodInfo<A>) -> Self {
+        ComponentTarget {
+            component: component.to_string(),
+            pod: Mutex::new(pod),
+            scope: None,
+        }
+    }
+
+    pub fn with_scope(mut self, scope: ComponentScope, access: A) -> Self {
+        self.scope = Some((scope, access));
+        self
+    }
+
+    pub fn pod(&self) -> PodInfo<A> {
+        self.pod.lock().unwrap().clone()
+    }
+
+    //another Ready pod of the scope than the failed one, kept for the next commands.
+    //A task that failed on the same pod after another one already replaced it gets the replacement.
+    async fn reselect(&self, failed: &str) -> Option<PodInfo<A>> {
+        let (scope, access) = self.scope.as_ref()?;
+        let pods = scope
+            .pods(access)
+            .await
+            .inspect_err(|e| warn!("Listing the {} pods again: {}", self.component, e))
+            .ok()?;
+        let mut current = self.pod.lock().unwrap();
+        if current.name != failed {
+            return Some(current.clone());
+        }
+        let candidate = pods
+            .into_iter()
+            .find(|p| p.name != failed && pod_ready(&p.pod))?;
+        *current = candidate;
+        Some(current.clone())
+    }
+}
+
+//one output of a component collector.
+#[derive(Debug, Clone, Default)]
+pub struct ComponentCommand {
+    //key of the output for the collector post-processing, e.g. "health".
+    pub name: String,
+    pub filename: String,
+    //run with /bin/sh -c in the first container, the fallback when http is set.
+    pub shell: String,
+    //tried over a port-forward first.
+    pub http: Option<HttpGet>,
+    //reach the shell as credentials::USERNAME_ENV and PASSWORD_ENV.
+    pub credentials: Option<Credentials>,
+    //json re-indented before it is written.
+    pub pretty_json: bool,
+    //written through write_compressible.
+    pub compressible: bool,
+}
+
+async fn run_once<A: PodAccess>(pod: &PodInfo<A>, command: &ComponentCommand) -> Result<String> {
+    let container = pod
+        .containers
+        .first()
+        .ok_or_else(|| anyhow!("Pod {} has no container.", pod.name))?;
+    match &command.http {
+        Some(r) => {
+            http_or_exec(
+                pod,
+                container,
+                r,
+                &command.shell,
+                command.credentials.as_ref(),
+            )
+            .await
+        }
+        None => {
+            let exec = credentials::env_command(command.credentials.as_ref(), &command.shell);
+            pod.api.exec(&pod.name, container, exec).await
+        }
+    }
+}
+
+//run the command on the component pod, and once more on another Ready pod of the component
+//when that one is gone. The output comes with the name of the replacement pod, if any.
+pub async fn run_component_command<A: PodAccess>(
+    target: &ComponentTarget<A>,
+    command: &ComponentCommand,
+) -> Result<(String, Option<String>)> {
+    let pod = target.pod();
+    match run_once(&pod, command).await {
+        Ok(data) => Ok((data, None)),
+        Err(e) if pod_gone(&e) => {
+            let Some(other) = target.reselect(&pod.name).await else {
+                return Err(e.context(format!(
+                    "Pod {} is gone and no other {} pod is Ready.",
+                    pod.name, target.component
+                )));
+            };
+            warn!(
+                "Pod {} is gone ({}), running {} on {}.",
+                pod.name, e, command.name, other.name
+            );
+            let data = run_once(&other, command).await?;
+            Ok((data, Some(other.name)))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+//the command recorded in a .error file, without the basic auth password.
+pub fn attempted(command: &str) -> String {
+    format!(
+        "exec /bin/sh -c {}",
+        credentials::redact_basic_auth(command)
+    )
+}
+
+//every command in its own task, written to folder; the (name, output) of the ones that succeeded.
+pub async fn collect_component_outputs<A: PodAccess>(
+    target: Arc<ComponentTarget<A>>,
+    ctx: Arc<RunContext>,
+    folder: &str,
+    commands: Vec<ComponentCommand>,
+) -> Vec<(String, String)> {
+    let mut handles = vec![];
+    for c in commands {
+        let target = target.clone();
+        let ctx = ctx.clone();
+        let folder = folder.to_string();
+        let pod = target.pod();
+        let container = pod.containers.first().cloned().unwrap_or_default();
+        let scope = LogScope::pod(&pod.namespace, &pod.name, &container);
+        handles.push(tokio::task::spawn(logging::scoped(scope, async move {
+            //claimed for the pod the command starts on, a replacement pod writes the same output.
+            if let Err(e) = ctx.claim_output(&folder, &c.filename, &pod.namespace, &pod.name) {
+                warn!("{}", e);
+                return None;
+            }
+            let (data, substitute) = match run_component_command(&target, &c).await {
+                Ok(d) => d,
+                Err(e) => {
+                    warn!("{}", e);
+                    ctx.record_failure(&folder, &c.filename, &attempted(&c.shell), &e);
+                    return None;
+                }
+            };
+            let data = if c.pretty_json {
+                jsonxf::pretty_print(&data).unwrap_or(data)
+            } else {
+                data
+            };
+            let er = anyhow!("kubectl command empty response {:#?}", c.shell);
+            let written = if c.compressible {
+                ctx.write_compressible(&folder, data.as_bytes(), &c.filename, er)
+            } else {
+                ctx.write_file(&folder, data.as_bytes(), &c.filename, er)
+            };
+            match written {
+                Ok(_) => {
+                    info!("File has been created {}/{}", &folder, &c.filename);
+                    if let Some(s) = substitute {
+                        ctx.record_substitution(&folder, &c.filename, &s);
+                    }
+                }
+                Err(e) => warn!("{}", e),
+            }
+            Some((c.name, data))
+        })));
+    }
+    let mut outputs = vec![];
+    for handle in handles {
+        match handle.await {
+            Ok(Some(o)) => outputs.push(o),
+            Ok(None) => {}
+            Err(e) => {
+                warn!("{}", e);
+                ctx.record_folder(folder, false);
+            }
+        }
+    }
+    outputs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{access::FakeAccess, pod_selection::PodSelection, test_support::fixture};
+    use kube::core::ErrorResponse;
+
+    fn api_error(code: u16) -> Error {
+        kube::Error::Api(ErrorResponse {
+            status: "Failure".to_string(),
+            message: "pods \"es-0\" not found".to_string(),
+            reason: "NotFound".to_string(),
+            code,
+        })
+        .into()
+    }
+
+    #[test]
+    fn gone_by_error_kind_not_by_text() {
+        assert!(pod_gone(&api_error(404)));
+        assert!(pod_gone(&api_error(404).context("exec in es-0")));
+        assert!(!pod_gone(&api_error(403)));
+        let upgrade = |s| -> Error {
+            kube::Error::UpgradeConnection(UpgradeConnectionError::ProtocolSwitch(s)).into()
+        };
+        assert!(pod_gone(&upgrade(StatusCode::NOT_FOUND)));
+        assert!(!pod_gone(&upgrade(StatusCode::FORBIDDEN)));
+        let io = |k| -> Error { std::io::Error::new(k, "stream").into() };
+        assert!(pod_gone(&io(ErrorKind::ConnectionReset)));
+        assert!(pod_gone(
+            &io(ErrorKind::UnexpectedEof).context("reading stdout")
+        ));
+        assert!(!pod_gone(&io(ErrorKind::PermissionDenied)));
+        //the text of a command output is not an error kind.
+        assert!(!pod_gone(&anyhow!("index not found, connection refused")));
+    }
+
+    fn target() -> ComponentTarget<FakeAccess> {
+        let access = FakeAccess::from_fixture(&fixture("access/component_gone.json")).unwrap();
+        let first = PodInfo::from_pod(&access.pods[0], access.clone());
+        let scope = ComponentScope {
+            selector: "app=elasticsearch".to_string(),
+            namespaces: vec!["elastic".to_string()],
+            ordinals: None,
+            pod_selection: PodSelection::First,
+        };
+        ComponentTarget::new("elasticsearch", first).with_scope(scope, access)
+    }
+
+    fn health() -> ComponentCommand {
+        ComponentCommand {
+            name: "health".to_string(),
+            filename: "health.json".to_string(),
+            shell: "curl -s localhost:9200/_cluster/health".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn a_gone_pod_is_replaced_by_a_ready_one() {
+        let target = target();
+        let (data, substitute) = run_component_command(&target, &health()).await.unwrap();
+        assert_eq!(data, "{\"status\": \"green\"}");
+        //es-1 is not Ready.
+        assert_eq!(substitute.as_deref(), Some("es-2"));
+        assert_eq!(target.pod().name, "es-2");
+        //the next commands start on the replacement.
+        let (_, substitute) = run_component_command(&target, &health()).await.unwrap();
+        assert_eq!(substitute, None);
+    }
+
+    #[tokio::test]
+    async fn other_failures_and_unscoped_targets_are_not_retried() {
+        let target = target();
+        let missing = ComponentCommand {
+            shell: "curl -s localhost:9200/_cat/indices".to_string(),
+            ..health()
+        };
+        //es-0 is gone, es-2 has no output for it: the error of the replacement.
+        let e = run_component_command(&target, &missing).await.unwrap_err();
+        assert!(e.to_string().starts_with("no exec output for es-2/"));
+
+        let access = FakeAccess::from_fixture(&fixture("access/component_gone.json")).unwrap();
+        let driver =
+            ComponentTarget::new("spark", PodInfo::from_pod(&access.pods[0], access.clone()));
+        let e = run_component_command(&driver, &health()).await.unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Pod es-0 is gone and no other spark pod is Ready."
+        );
+    }
+}
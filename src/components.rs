@@ -0,0 +1,461 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_derive::Serialize;
+
+use std::collections::BTreeSet;
+
+use crate::{
+    access::PodAccess,
+    context::CoverageOutcome,
+    get_pod_list,
+    ordinals::{self, OrdinalRange},
+    pod_selection::PodSelection,
+    ConfigFile, PodInfo,
+};
+
+//label selectors of the app collectors.
+pub const ELASTICSEARCH_SELECTOR: &str = "elasticsearch.k8s.elastic.co/node-master=true";
+pub const ELASTICSEARCH_SECRET_SELECTOR: &str =
+    "eck.k8s.elastic.co/owner-kind=Elasticsearch, eck.k8s.elastic.co/credentials=true";
+pub const STREAMING_CORE_SELECTOR: &str =
+    "spark-role=driver,app.kubernetes.io/component=streaming-core-consumer";
+pub const HADOOP_SELECTOR: &str = "app.kubernetes.io/component=datanode";
+pub const HBASE_SELECTOR: &str = "app.kubernetes.io/name=hbase, app.kubernetes.io/component=master";
+pub const KAFKA_SELECTOR: &str = "app.kubernetes.io/name=kafka";
+pub const KAFKA_MESSAGE_BUS_SELECTOR: &str = "app.kubernetes.io/name=eric-data-message-bus-kf";
+pub const PROMETHEUS_SELECTOR: &str = "app.kubernetes.io/name=prometheus";
+pub const KAFKA_CONNECT_SELECTOR: &str = "app.kubernetes.io/name=kafka-connect";
+pub const SCHEMA_REGISTRY_SELECTOR: &str = "app.kubernetes.io/name=schema-registry";
+
+//app_selectors keys and their default label.
+pub const APP_COMPONENTS: [(&str, &str); 9] = [
+    ("elasticsearch", ELASTICSEARCH_SELECTOR),
+    ("streaming_core", STREAMING_CORE_SELECTOR),
+    ("hadoop", HADOOP_SELECTOR),
+    ("hbase", HBASE_SELECTOR),
+    ("kafka", KAFKA_SELECTOR),
+    ("kafka_message_bus", KAFKA_MESSAGE_BUS_SELECTOR),
+    ("prometheus", PROMETHEUS_SELECTOR),
+    ("kafka_connect", KAFKA_CONNECT_SELECTOR),
+    ("schema_registry", SCHEMA_REGISTRY_SELECTOR),
+];
+
+//override of one app component: its label and the namespaces it is looked for in.
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct AppSelector {
+    //the built-in selector when empty.
+    #[serde(default)]
+    pub label: String,
+    //every configured namespace when empty.
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+    //StatefulSet ordinals of the matched pods to keep, e.g. "0-2,5", every pod when empty.
+    #[serde(default)]
+    pub ordinals: String,
+    //in-container globs of log files (e.g. /opt/app/logs/*.log), copied under apps/<component>/<ns>_<pod>/files.
+    #[serde(default)]
+    pub log_files: Vec<String>,
+    //first (default), all or leader pod of the matched ones; the streaming core drivers are all collected.
+    #[serde(default)]
+    pub pod_selection: PodSelection,
+}
+
+//where and how the pods of a component are looked for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentScope {
+    pub selector: String,
+    pub namespaces: Vec<String>,
+    pub ordinals: Option<OrdinalRange>,
+    pub pod_selection: PodSelection,
+}
+
+impl ComponentScope {
+    //the access of each namespace of the scope.
+    pub fn access<A: PodAccess>(&self, access: &A) -> Vec<A> {
+        self.namespaces
+            .iter()
+            .map(|n| access.in_namespace(n))
+            .collect()
+    }
+
+    pub async fn pods<A: PodAccess>(&self, access: &A) -> Result<Vec<PodInfo<A>>> {
+        let pods = get_pod_list(self.access(access), self.selector.clone(), "".to_string()).await?;
+        Ok(match &self.ordinals {
+            Some(o) => ordinals::filter_pods(pods, o),
+            None => pods,
+        })
+    }
+
+    //the coverage of a component without pods, disabled when it has no namespace to look in.
+    pub fn skipped(&self) -> CoverageOutcome {
+        if self.namespaces.is_empty() {
+            CoverageOutcome::Disabled
+        } else {
+            CoverageOutcome::Skipped {
+                reason: format!("no pods matched selector {}", self.selector),
+            }
+        }
+    }
+}
+
+//the app_selectors entry of the component on top of the built-in selector and the configured namespaces,
+//no namespace at all with skip_app_collectors.
+pub fn component_scope(config: &ConfigFile, component: &str) -> ComponentScope {
+    let default = APP_COMPONENTS
+        .iter()
+        .find(|(c, _)| *c == component)
+        .map(|(_, s)| s.to_string())
+        .unwrap_or_default();
+    let custom = config.app_selectors.get(component);
+    let selector = custom
+        .map(|c| c.label.clone())
+        .filter(|l| !l.is_empty())
+        .unwrap_or(default);
+    let namespaces = if config.skip_app_collectors {
+        vec![]
+    } else {
+        custom
+            .map(|c| c.namespaces.clone())
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| config.context_namespace.clone())
+    };
+    //checked by validate_app_selectors.
+    let ordinals = custom
+        .filter(|c| !c.ordinals.is_empty())
+        .and_then(|c| OrdinalRange::parse(&c.ordinals).ok());
+    ComponentScope {
+        selector,
+        namespaces,
+        ordinals,
+        pod_selection: custom.map(|c| c.pod_selection).unwrap_or_default(),
+    }
+}
+
+pub fn validate_app_selectors(config: &ConfigFile) -> Result<()> {
+    for (key, selector) in &config.app_selectors {
+        if !APP_COMPONENTS.iter().any(|(c, _)| c == key) {
+            return Err(anyhow!(
+                "Unknown app_selectors component {}, expected one of {}.",
+                key,
+                APP_COMPONENTS.map(|(c, _)| c).join(", ")
+            ));
+        }
+        if !selector.ordinals.is_empty() {
+            OrdinalRange::parse(&selector.ordinals)
+                .map_err(|e| anyhow!("app_selectors.{}.ordinals: {}", key, e))?;
+        }
+    }
+    Ok(())
+}
+
+//(component, selector, secret selector, what the collector runs in the first matched pod).
+const COMPONENTS: [(&str, &str, Option<&str>, &[&str]); 9] = [
+    (
+        "elasticsearch",
+        ELASTICSEARCH_SELECTOR,
+        Some(ELASTICSEARCH_SECRET_SELECTOR),
+        &["curl https://localhost:9200/_cluster/{health,settings,state,stats}, _cat/{indices,nodes,shards}"],
+    ),
+    (
+        "streaming core",
+        STREAMING_CORE_SELECTOR,
+        None,
+        &["curl localhost:4040/api/v1/applications/<id>/{environment,executors,streaming/statistics,streaming/batches}"],
+    ),
+    (
+        "hadoop",
+        HADOOP_SELECTOR,
+        None,
+        &[
+            "hdfs dfsadmin -report",
+            "hdfs dfsadmin -safemode get",
+            "dd if=/dev/zero of=/dfs/test conv=fsync bs=384k count=10K",
+        ],
+    ),
+    (
+        "hbase",
+        HBASE_SELECTOR,
+        None,
+        &["echo \"status 'detailed'\" | hbase shell"],
+    ),
+    (
+        "kafka",
+        KAFKA_SELECTOR,
+        None,
+        &["bin/kafka-topics.sh --list/--describe", "bin/kafka-consumer-groups.sh --list/--describe --all-groups", "bin/kafka-broker-api-versions.sh"],
+    ),
+    (
+        "kafka message bus",
+        KAFKA_MESSAGE_BUS_SELECTOR,
+        None,
+        &["kafka-topics.sh --list/--describe", "kafka-consumer-groups.sh --list/--describe --all-groups", "kafka-broker-api-versions.sh"],
+    ),
+    (
+        "prometheus",
+        PROMETHEUS_SELECTOR,
+        None,
+        &["wget http://127.0.0.1:9090/<path>/prometheus/api/v1/{rules,alerts,targets,status/runtimeinfo,status/buildinfo}"],
+    ),
+    (
+        "kafka connect",
+        KAFKA_CONNECT_SELECTOR,
+        None,
+        &["curl localhost:8083/connectors?expand=status"],
+    ),
+    (
+        "schema registry",
+        SCHEMA_REGISTRY_SELECTOR,
+        None,
+        &["curl localhost:8081/{subjects,config}"],
+    ),
+];
+
+//the pod selectors of the built-in app collectors.
+pub fn app_selectors() -> Vec<&'static str> {
+    COMPONENTS.iter().map(|c| c.1).collect()
+}
+
+//the pods matched by any of the app selectors, once each, in the order of the selectors.
+pub async fn app_pods<A: PodAccess>(access: Vec<A>) -> Result<Vec<PodInfo<A>>> {
+    let mut seen = BTreeSet::new();
+    let mut pods = vec![];
+    for selector in app_selectors() {
+        for p in get_pod_list(access.clone(), selector.to_string(), "".to_string()).await? {
+            if seen.insert((p.namespace.clone(), p.name.clone())) {
+                pods.push(p);
+            }
+        }
+    }
+    Ok(pods)
+}
+
+//what the apps phase would find and run, nothing is executed in the pods.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ComponentReport {
+    pub component: String,
+    pub selector: String,
+    //"<namespace>/<pod>".
+    pub pods: Vec<String>,
+    pub commands: Vec<String>,
+    //"<namespace>/<secret>" of the credential secrets.
+    pub secrets: Vec<String>,
+}
+
+pub async fn inspect_components<A: PodAccess>(
+    access: Vec<A>,
+    config: &ConfigFile,
+) -> Result<Vec<ComponentReport>> {
+    let mut reports = vec![];
+    for (component, selector, secret_selector, commands) in COMPONENTS {
+        let pods = get_pod_list(access.clone(), selector.to_string(), "".to_string()).await?;
+        let mut secrets = vec![];
+        if let (Some(s), false) = (secret_selector, pods.is_empty()) {
+            for a in &access {
+                a.list_secrets(s).await?.iter().for_each(|s| {
+                    secrets.push(format!(
+                        "{}/{}",
+                        s.metadata.namespace.clone().unwrap_or_default(),
+                        s.metadata.name.clone().unwrap_or_default()
+                    ))
+                });
+            }
+        }
+        reports.push(ComponentReport {
+            component: component.to_string(),
+            selector: selector.to_string(),
+            commands: if pods.is_empty() {
+                vec![]
+            } else {
+                commands.iter().map(|c| c.to_string()).collect()
+            },
+            pods: pods
+                .iter()
+                .map(|p| format!("{}/{}", p.namespace, p.name))
+                .collect(),
+            secrets,
+        });
+    }
+    for fc in &config.custom_collectors.file_copies {
+        let pods = get_pod_list(access.clone(), fc.selector.clone(), "".to_string()).await?;
+        reports.push(ComponentReport {
+            component: "file copies".to_string(),
+            selector: fc.selector.clone(),
+            commands: if pods.is_empty() {
+                vec![]
+            } else {
+                fc.paths.iter().map(|p| format!("tar cf - {}", p)).collect()
+            },
+            pods: pods
+                .iter()
+                .map(|p| format!("{}/{}", p.namespace, p.name))
+                .collect(),
+            secrets: vec![],
+        });
+    }
+    Ok(reports)
+}
+
+//component -> matched pods -> would-run commands.
+pub fn render_components(reports: &[ComponentReport]) -> String {
+    let mut out = String::new();
+    for r in reports {
+        out.push_str(&format!(
+            "{} ({}): {} pods\n",
+            r.component,
+            r.selector,
+            r.pods.len()
+        ));
+        r.pods
+            .iter()
+            .for_each(|p| out.push_str(&format!("  pod     {}\n", p)));
+        r.secrets
+            .iter()
+            .for_each(|s| out.push_str(&format!("  secret  {}\n", s)));
+        r.commands
+            .iter()
+            .for_each(|c| out.push_str(&format!("  command {}\n", c)));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{access::FakeAccess, test_support::fixture};
+
+    fn with_selectors(selectors: &[(&str, AppSelector)]) -> ConfigFile {
+        ConfigFile {
+            context_namespace: vec!["kafka".to_string(), "web".to_string()],
+            app_selectors: selectors
+                .iter()
+                .map(|(c, s)| (c.to_string(), s.clone()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn scope_defaults_to_the_built_in_selector_and_every_namespace() {
+        let scope = component_scope(&with_selectors(&[]), "elasticsearch");
+        assert_eq!(
+            scope,
+            ComponentScope {
+                selector: ELASTICSEARCH_SELECTOR.to_string(),
+                namespaces: vec!["kafka".to_string(), "web".to_string()],
+                ordinals: None,
+                pod_selection: PodSelection::First,
+            }
+        );
+    }
+
+    #[test]
+    fn scope_takes_the_namespaces_and_label_of_the_component() {
+        let selector = AppSelector {
+            label: "app=es".to_string(),
+            namespaces: vec!["search".to_string()],
+            ordinals: "0-1".to_string(),
+            pod_selection: PodSelection::Leader,
+            ..Default::default()
+        };
+        let config = with_selectors(&[("elasticsearch", selector)]);
+        let scope = component_scope(&config, "elasticsearch");
+        assert_eq!(scope.selector, "app=es");
+        assert_eq!(scope.namespaces, vec!["search"]);
+        assert_eq!(scope.ordinals, Some(OrdinalRange::parse("0-1").unwrap()));
+        assert_eq!(scope.pod_selection, PodSelection::Leader);
+        //the other components keep the defaults.
+        assert_eq!(
+            component_scope(&config, "kafka").namespaces,
+            vec!["kafka", "web"]
+        );
+        //an override with only namespaces keeps the built-in label.
+        let config = with_selectors(&[(
+            "kafka",
+            AppSelector {
+                namespaces: vec!["kafka".to_string()],
+                ..Default::default()
+            },
+        )]);
+        assert_eq!(component_scope(&config, "kafka").selector, KAFKA_SELECTOR);
+    }
+
+    #[test]
+    fn skipped_app_collectors_have_no_namespace() {
+        let mut config = with_selectors(&[]);
+        config.skip_app_collectors = true;
+        let scope = component_scope(&config, "kafka");
+        assert!(scope.namespaces.is_empty());
+        assert_eq!(scope.skipped(), CoverageOutcome::Disabled);
+        let scope = component_scope(&with_selectors(&[]), "kafka");
+        assert!(matches!(scope.skipped(), CoverageOutcome::Skipped { .. }));
+    }
+
+    #[test]
+    fn unknown_components_and_invalid_ordinals_are_rejected() {
+        assert!(
+            validate_app_selectors(&with_selectors(&[("mongodb", AppSelector::default())]))
+                .is_err()
+        );
+        let bad = AppSelector {
+            ordinals: "2-a".to_string(),
+            ..Default::default()
+        };
+        let e = validate_app_selectors(&with_selectors(&[("kafka", bad)])).unwrap_err();
+        assert!(e.to_string().starts_with("app_selectors.kafka.ordinals"));
+        validate_app_selectors(&with_selectors(&[("kafka", AppSelector::default())])).unwrap();
+    }
+
+    #[tokio::test]
+    async fn pods_are_only_looked_for_in_the_namespaces_of_the_scope() {
+        let access = FakeAccess::from_fixture(&fixture("access/cluster.json")).unwrap();
+        let scoped = |namespaces: &[&str]| AppSelector {
+            namespaces: namespaces.iter().map(|n| n.to_string()).collect(),
+            ..Default::default()
+        };
+        let names =
+            |pods: Vec<PodInfo<FakeAccess>>| pods.into_iter().map(|p| p.name).collect::<Vec<_>>();
+        let config = with_selectors(&[("kafka", scoped(&["web"]))]);
+        let pods = component_scope(&config, "kafka")
+            .pods(&access)
+            .await
+            .unwrap();
+        assert!(pods.is_empty());
+        let config = with_selectors(&[("kafka", scoped(&["kafka"]))]);
+        let pods = component_scope(&config, "kafka")
+            .pods(&access)
+            .await
+            .unwrap();
+        assert_eq!(names(pods), vec!["kafka-0", "kafka-1"]);
+        let config = with_selectors(&[(
+            "kafka",
+            AppSelector {
+                ordinals: "1".to_string(),
+                ..scoped(&["kafka"])
+            },
+        )]);
+        let pods = component_scope(&config, "kafka")
+            .pods(&access)
+            .await
+            .unwrap();
+        assert_eq!(names(pods), vec!["kafka-1"]);
+    }
+
+    #[tokio::test]
+    async fn app_pods_are_listed_once() {
+        let mut access = FakeAccess::from_fixture(&fixture("access/cluster.json")).unwrap();
+        //kafka-1 matched by the hadoop selector as well.
+        access.pods[1]
+            .metadata
+            .labels
+            .get_or_insert_with(Default::default)
+            .insert(
+                "app.kubernetes.io/component".to_string(),
+                "datanode".to_string(),
+            );
+        let namespaces = [access.in_namespace("kafka"), access.in_namespace("web")];
+        let pods = app_pods(namespaces.to_vec()).await.unwrap();
+        let names = pods.iter().map(|p| p.name.as_str()).collect::<Vec<_>>();
+        //in the order of the selectors, hadoop before kafka.
+        assert_eq!(names, ["kafka-1", "kafka-0"]);
+    }
+}
@@ -0,0 +1,805 @@
+use anyhow::{anyhow, Error, Result};
+use chrono::Utc;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::{
+    anonymize::Anonymizer,
+    api_warnings::ApiWarnings,
+    capped_file_name, classify_log,
+    credentials::Credentials,
+    exec::ExecRecorder,
+    health::HealthInputs,
+    line_endings,
+    log_queue::LogPriority,
+    logging::WarningAggregator,
+    read_log_sample,
+    report::{Manifest, PhaseResult},
+    self_usage::UsageCounters,
+    write_atomic, write_file, write_gzip_atomic, LogFormat, SubprocessKubeconfig, GZIP_SUFFIX,
+};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const ERROR_FILE_SUFFIX: &str = ".error";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    #[default]
+    Ok,
+    Failed,
+}
+
+//one intended output of the run, listed in manifest.json.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ManifestEntry {
+    pub status: FileStatus,
+    //companion file holding the error, next to where the output would have been.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_file: Option<String>,
+    //the output reached a size cap (log_limit_bytes) and is possibly truncated.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub capped: bool,
+    //group/version the objects were read with, when negotiated (api_versions).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    //sizes of an output written gzipped (compress_outputs_over_mb).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uncompressed_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compressed_bytes: Option<u64>,
+    //the pod that answered when the pod of the component collector was gone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub substituted_pod: Option<String>,
+    //place of a log in the log queue.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_priority: Option<LogPriority>,
+    //why the output is missing or what replaces it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    //exit code of the external command that produced the output (plugins).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    //layout of a .log output and the share of its sampled lines that fit it (classify_log).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_format: Option<LogFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_format_confidence: Option<f64>,
+}
+
+//what became of a collector the configuration asked for, in the coverage of collection_info.json.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum CoverageOutcome {
+    Collected { files: usize },
+    Skipped { reason: String },
+    Disabled,
+    Failed { error: String },
+}
+
+impl CoverageOutcome {
+    //a collector recorded more than once (one per namespace, pod or step) keeps the worst outcome,
+    //the files of its collected steps add up.
+    fn merge(self, other: CoverageOutcome) -> CoverageOutcome {
+        use CoverageOutcome::*;
+        match (self, other) {
+            (Failed { error }, _) | (_, Failed { error }) => Failed { error },
+            (Collected { files: a }, Collected { files: b }) => Collected { files: a + b },
+            (c @ Collected { .. }, _) | (_, c @ Collected { .. }) => c,
+            (s @ Skipped { .. }, _) | (_, s @ Skipped { .. }) => s,
+            (Disabled, Disabled) => Disabled,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        match self {
+            CoverageOutcome::Collected { files } => format!("collected {} files", files),
+            CoverageOutcome::Skipped { reason } => format!("skipped ({})", reason),
+            CoverageOutcome::Disabled => "skipped (disabled)".to_string(),
+            CoverageOutcome::Failed { error } => format!("failed ({})", error),
+        }
+    }
+}
+
+//the companion of <folder>/<filename> is <folder>/<filename>.error.
+pub fn error_file_path(folder: &str, filename: &str) -> String {
+    format!("{}/{}{}", folder, filename, ERROR_FILE_SUFFIX)
+}
+
+//state shared by every collector task of a run.
+#[derive(Debug, Default)]
+pub struct RunContext {
+    pub folders: Vec<String>,
+    pub anonymizer: Option<Mutex<Anonymizer>>,
+    pub phases: Mutex<BTreeMap<String, PhaseResult>>,
+    //by path relative to the collection folder.
+    pub manifest: Mutex<BTreeMap<String, ManifestEntry>>,
+    //filled by the collectors for health_summary.txt.
+    pub health: Mutex<HealthInputs>,
+    //name and start of each phase, in order.
+    pub phase_starts: Mutex<Vec<(String, Instant)>>,
+    //outputs of the compressible collectors above this size are written gzipped, never when 0.
+    pub compress_over_bytes: u64,
+    //deprecation warnings of the api server, filled by the layer of the client of the run.
+    pub api_warnings: Arc<ApiWarnings>,
+    //execs into the pods, recorded by the pod accesses of the run for commands_executed.sh.
+    pub execs: Arc<ExecRecorder>,
+    //repeated warnings of the current phase kept off the terminal, summed up when the phase ends.
+    pub log_warnings: Arc<WarningAggregator>,
+    //credentials section resolved at startup, by component name. Never logged.
+    pub credentials: BTreeMap<String, Credentials>,
+    //collector name -> outcome, for the coverage of the run summary.
+    pub coverage: Mutex<BTreeMap<String, CoverageOutcome>>,
+    //path of a per pod output relative to the collection folder -> "<namespace>/<pod>" it belongs to.
+    pub output_owners: Mutex<BTreeMap<String, String>>,
+    //text outputs written with LF line endings and without BOM.
+    pub normalize_line_endings: bool,
+    //api requests, exec sessions, subprocesses, bytes and memory of the tool itself.
+    pub usage: Arc<UsageCounters>,
+    //KUBECONFIG of the kubectl and helm subprocesses.
+    pub kubeconfig: SubprocessKubeconfig,
+}
+
+impl RunContext {
+    pub fn new(folders: Vec<String>) -> Self {
+        RunContext {
+            folders,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_compression(mut self, over_bytes: u64) -> Self {
+        self.compress_over_bytes = over_bytes;
+        self
+    }
+
+    pub fn with_line_endings(mut self, normalize: bool) -> Self {
+        self.normalize_line_endings = normalize;
+        self
+    }
+
+    //the counters the kube client of the run was built with.
+    pub fn with_usage(mut self, usage: Arc<UsageCounters>) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    //the warnings the layer of the client of the run records into.
+    pub fn with_api_warnings(mut self, api_warnings: Arc<ApiWarnings>) -> Self {
+        self.api_warnings = api_warnings;
+        self
+    }
+
+    //the recorder the pod accesses of the run record their execs into.
+    pub fn with_execs(mut self, execs: Arc<ExecRecorder>) -> Self {
+        self.execs = execs;
+        self
+    }
+
+    //the aggregator the terminal logger admits the warnings of the run with.
+    pub fn with_log_warnings(mut self, log_warnings: Arc<WarningAggregator>) -> Self {
+        self.log_warnings = log_warnings;
+        self
+    }
+
+    pub fn with_kubeconfig(mut self, kubeconfig: SubprocessKubeconfig) -> Self {
+        self.kubeconfig = kubeconfig;
+        self
+    }
+
+    pub fn with_credentials(mut self, credentials: BTreeMap<String, Credentials>) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    pub fn with_anonymizer(mut self, anonymizer: Anonymizer) -> Self {
+        self.anonymizer = Some(Mutex::new(anonymizer));
+        self
+    }
+
+    //anonymize the text when the mode is enabled, otherwise return it untouched.
+    pub fn anonymize(&self, text: &str) -> String {
+        match &self.anonymizer {
+            Some(a) => a.lock().unwrap().anonymize(text),
+            None => text.to_string(),
+        }
+    }
+
+    //parent/name for a folder named after the cluster (node, namespace, pod), anonymized like the file names.
+    pub fn subfolder(&self, parent: &str, name: &str) -> String {
+        format!("{}/{}", parent, self.anonymize(name))
+    }
+
+    pub fn record(&self, phase: &str, ok: bool) {
+        let mut phases = self.phases.lock().unwrap();
+        let p = phases.entry(phase.to_string()).or_default();
+        if ok {
+            p.succeeded += 1;
+        } else {
+            p.failed += 1;
+        }
+    }
+
+    //the phase of a collection folder is the name of the run folder it belongs to (pods, infra, helm, apps),
+    //or its last path component outside of them.
+    pub fn record_folder(&self, folder: &str, ok: bool) {
+        let folder = self
+            .folders
+            .iter()
+            .take(4)
+            .find(|f| Path::new(folder).starts_with(f))
+            .map(|f| f.as_str())
+            .unwrap_or(folder);
+        let phase = Path::new(folder)
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.record(&phase, ok);
+    }
+
+    pub fn start_phase(&self, phase: &str) {
+        self.phase_starts
+            .lock()
+            .unwrap()
+            .push((phase.to_string(), Instant::now()));
+    }
+
+    //a phase lasts until the next one starts, the current one until now.
+    pub fn phase_durations(&self) -> BTreeMap<String, f64> {
+        let starts = self.phase_starts.lock().unwrap();
+        let now = Instant::now();
+        starts
+            .iter()
+            .enumerate()
+            .map(|(i, (name, start))| {
+                let end = starts.get(i + 1).map(|(_, s)| *s).unwrap_or(now);
+                (name.clone(), (end - *start).as_secs_f64())
+            })
+            .collect()
+    }
+
+    pub fn record_api_warning(&self, warning: &str, collector: &str) {
+        self.api_warnings.record(warning, collector)
+    }
+
+    pub fn render_api_warnings(&self) -> String {
+        self.api_warnings.render()
+    }
+
+    pub fn record_coverage(&self, collector: &str, outcome: CoverageOutcome) {
+        let mut coverage = self.coverage.lock().unwrap();
+        let outcome = match coverage.remove(collector) {
+            Some(previous) => previous.merge(outcome),
+            None => outcome,
+        };
+        coverage.insert(collector.to_string(), outcome);
+    }
+
+    //(ok, failed) outputs listed so far, what a collector produced is the difference before and after it ran.
+    pub fn output_counts(&self) -> (usize, usize) {
+        let manifest = self.manifest.lock().unwrap();
+        let ok = manifest
+            .values()
+            .filter(|e| e.status == FileStatus::Ok)
+            .count();
+        (ok, manifest.len() - ok)
+    }
+
+    //Collected with the outputs added since `before` (output_counts), Failed when every output added failed.
+    pub fn record_outputs(&self, collector: &str, before: (usize, usize)) {
+        let (ok, failed) = self.output_counts();
+        let files = ok.saturating_sub(before.0);
+        let outcome = if files == 0 && failed > before.1 {
+            CoverageOutcome::Failed {
+                error: format!("{} outputs failed", failed - before.1),
+            }
+        } else {
+            CoverageOutcome::Collected { files }
+        };
+        self.record_coverage(collector, outcome);
+    }
+
+    pub fn record_collected<T>(&self, collector: &str, before: (usize, usize), result: &Result<T>) {
+        match result {
+            Ok(_) => self.record_outputs(collector, before),
+            Err(e) => self.record_coverage(
+                collector,
+                CoverageOutcome::Failed {
+                    error: e.to_string(),
+                },
+            ),
+        }
+    }
+
+    pub fn coverage(&self) -> BTreeMap<String, CoverageOutcome> {
+        self.coverage.lock().unwrap().clone()
+    }
+
+    //one line per collector, the failed ones first.
+    pub fn render_coverage(&self) -> Vec<String> {
+        let coverage = self.coverage();
+        let mut lines = coverage.iter().collect::<Vec<_>>();
+        lines.sort_by_key(|(_, o)| !matches!(o, CoverageOutcome::Failed { .. }));
+        lines
+            .into_iter()
+            .map(|(c, o)| format!("{}: {}", c, o.render()))
+            .collect()
+    }
+
+    pub fn phase_results(&self) -> BTreeMap<String, PhaseResult> {
+        self.phases.lock().unwrap().clone()
+    }
+
+    //the path relative to the collection folder, as listed in the manifest.
+    pub fn relative(&self, path: &str) -> String {
+        self.folders
+            .get(5)
+            .and_then(|root| Path::new(path).strip_prefix(root).ok())
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| path.to_string())
+    }
+
+    //every per pod output goes through here before it is written: a path already claimed by another pod
+    //(a file name template or a path without the namespace) is an error instead of an overwrite.
+    pub fn claim_output(
+        &self,
+        folder: &str,
+        filename: &str,
+        namespace: &str,
+        pod: &str,
+    ) -> Result<()> {
+        let path = self.relative(&format!("{}/{}", folder, filename));
+        let owner = format!("{}/{}", namespace, pod);
+        let mut owners = self.output_owners.lock().unwrap();
+        match owners.get(&path) {
+            Some(other) if *other != owner => Err(anyhow!(
+                "Output {} of pod {} collides with the one of pod {}, not written.",
+                path,
+                owner,
+                other
+            )),
+            Some(_) => Ok(()),
+            None => {
+                owners.insert(path, owner);
+                Ok(())
+            }
+        }
+    }
+
+    fn manifest_entry(&self, folder: &str, filename: &str, entry: ManifestEntry) {
+        let path = self.relative(&format!("{}/{}", folder, filename));
+        self.manifest.lock().unwrap().insert(path, entry);
+    }
+
+    //a task that could not produce <folder>/<filename>: the error chain, what was attempted
+    //and when go to the .error companion file, and the output is listed as failed.
+    pub fn record_failure(&self, folder: &str, filename: &str, attempted: &str, error: &Error) {
+        self.record_folder(folder, false);
+        let filename = self.anonymize(filename);
+        let mut content = format!("timestamp: {}\n", Utc::now().to_rfc3339());
+        if !attempted.is_empty() {
+            content.push_str(&format!("attempted: {}\n", attempted));
+        }
+        content.push_str("error:\n");
+        error
+            .chain()
+            .for_each(|c| content.push_str(&format!("  {}\n", c)));
+        let path = error_file_path(folder, &filename);
+        let error_file = write_atomic(Path::new(&path), self.anonymize(&content).as_bytes())
+            .ok()
+            .map(|_| self.relative(&path));
+        self.manifest_entry(
+            folder,
+            &filename,
+            ManifestEntry {
+                status: FileStatus::Failed,
+                error_file,
+                ..Default::default()
+            },
+        );
+    }
+
+    fn update_entry(&self, folder: &str, filename: &str, update: impl FnOnce(&mut ManifestEntry)) {
+        let path = self.relative(&format!("{}/{}", folder, self.anonymize(filename)));
+        if let Some(e) = self.manifest.lock().unwrap().get_mut(&path) {
+            update(e);
+        }
+    }
+
+    pub fn mark_capped(&self, folder: &str, filename: &str) {
+        self.update_entry(folder, filename, |e| e.capped = true);
+    }
+
+    //the log may have been renamed when capped.
+    pub fn record_queue_priority(&self, folder: &str, filename: &str, priority: LogPriority) {
+        for f in [filename.to_string(), capped_file_name(filename)] {
+            self.update_entry(folder, &f, |e| e.queue_priority = Some(priority));
+        }
+    }
+
+    pub fn record_note(&self, folder: &str, filename: &str, note: &str) {
+        self.update_entry(folder, filename, |e| e.note = Some(note.to_string()));
+    }
+
+    pub fn record_exit_code(&self, folder: &str, filename: &str, code: i32) {
+        self.update_entry(folder, filename, |e| e.exit_code = Some(code));
+    }
+
+    //a file written outside of write_file (by a plugin), an entry already there is kept.
+    pub fn record_output(&self, folder: &str, filename: &str) {
+        if filename.ends_with(ERROR_FILE_SUFFIX) {
+            return;
+        }
+        let path = self.relative(&format!("{}/{}", folder, filename));
+        self.manifest.lock().unwrap().entry(path).or_default();
+    }
+
+    //the output may have been written gzipped (write_compressible).
+    pub fn record_substitution(&self, folder: &str, filename: &str, pod: &str) {
+        for f in [filename.to_string(), format!("{}{}", filename, GZIP_SUFFIX)] {
+            self.update_entry(folder, &f, |e| e.substituted_pod = Some(pod.to_string()));
+        }
+    }
+
+    pub fn record_api_version(&self, folder: &str, filename: &str, api_version: &str) {
+        self.update_entry(folder, filename, |e| {
+            e.api_version = Some(api_version.to_string())
+        });
+    }
+
+    //the format of every .log output written, from a sample of its start.
+    pub fn record_log_formats(&self) {
+        let Some(root) = self.folders.get(5) else {
+            return;
+        };
+        let logs = self
+            .manifest()
+            .into_iter()
+            .filter(|(path, e)| path.ends_with(".log") && e.status == FileStatus::Ok)
+            .map(|(path, _)| path)
+            .collect::<Vec<String>>();
+        for path in logs {
+            let classified = read_log_sample(&Path::new(root).join(&path))
+                .ok()
+                .and_then(|s| classify_log(&s));
+            if let Some((format, confidence)) = classified {
+                if let Some(e) = self.manifest.lock().unwrap().get_mut(&path) {
+                    e.log_format = Some(format);
+                    e.log_format_confidence = Some(confidence);
+                }
+            }
+        }
+    }
+
+    //path relative to the collection folder -> format of the logs classified by record_log_formats.
+    pub fn log_formats(&self) -> BTreeMap<String, LogFormat> {
+        self.manifest
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(path, e)| Some((path.clone(), e.log_format?)))
+            .collect()
+    }
+
+    pub fn manifest(&self) -> BTreeMap<String, ManifestEntry> {
+        self.manifest.lock().unwrap().clone()
+    }
+
+    pub fn write_manifest(&self, folder: &str) -> Result<()> {
+        write_atomic(
+            &Path::new(folder).join(MANIFEST_FILE),
+            serde_json::to_string_pretty(&Manifest::new(self.manifest()))?.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    //write_file going through the anonymizer (data and file name) when enabled,
+    //the outcome is recorded under the phase named after the folder.
+    pub fn write_file(
+        &self,
+        folder: &str,
+        data: &[u8],
+        filename: &str,
+        error: Error,
+    ) -> Result<()> {
+        let r = self.write_file_inner(folder, data, filename, error);
+        match &r {
+            Err(e) => self.record_failure(folder, filename, "", e),
+            Ok(_) => {
+                self.record_folder(folder, true);
+                self.manifest_entry(folder, &self.anonymize(filename), ManifestEntry::default());
+            }
+        }
+        r
+    }
+
+    //for the collectors whose outputs are big and compress well (cluster states, json dumps):
+    //above compress_over_bytes the output is written as <filename>.gz, the manifest keeps both sizes.
+    pub fn write_compressible(
+        &self,
+        folder: &str,
+        data: &[u8],
+        filename: &str,
+        error: Error,
+    ) -> Result<()> {
+        let data = self.normalized(data);
+        let data = data.as_ref();
+        if self.compress_over_bytes == 0 || data.len() as u64 <= self.compress_over_bytes {
+            return self.write_file(folder, data, filename, error);
+        }
+        let data = self.anonymize(&String::from_utf8_lossy(data));
+        let filename = format!("{}{}", self.anonymize(filename), GZIP_SUFFIX);
+        let r = write_gzip_atomic(&Path::new(folder).join(&filename), data.as_bytes());
+        match r {
+            Ok(compressed) => {
+                self.record_folder(folder, true);
+                self.manifest_entry(
+                    folder,
+                    &filename,
+                    ManifestEntry {
+                        uncompressed_bytes: Some(data.len() as u64),
+                        compressed_bytes: Some(compressed),
+                        ..Default::default()
+                    },
+                );
+                Ok(())
+            }
+            Err(e) => {
+                self.record_failure(folder, &filename, "", &e);
+                Err(e)
+            }
+        }
+    }
+
+    fn normalized<'a>(&self, data: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.normalize_line_endings {
+            line_endings::normalize_text(data)
+        } else {
+            Cow::Borrowed(data)
+        }
+    }
+
+    fn write_file_inner(
+        &self,
+        folder: &str,
+        data: &[u8],
+        filename: &str,
+        error: Error,
+    ) -> Result<()> {
+        let data = self.normalized(data);
+        let data = data.as_ref();
+        match &self.anonymizer {
+            Some(a) => {
+                let mut a = a.lock().unwrap();
+                let data = a.anonymize(&String::from_utf8_lossy(data));
+                let filename = a.anonymize(filename);
+                drop(a);
+                write_file(folder, data.as_bytes(), &filename, error)
+            }
+            None => write_file(folder, data, filename, error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{run_context, TempDir};
+
+    #[test]
+    fn error_file_path_mirrors_the_output_path() {
+        assert_eq!(
+            error_file_path("/out/apps/elastic", "elastic_search_health.json"),
+            "/out/apps/elastic/elastic_search_health.json.error"
+        );
+        assert_eq!(error_file_path("pods", "a_b.log"), "pods/a_b.log.error");
+    }
+
+    #[test]
+    fn failure_is_listed_with_its_error_file_next_to_the_output() {
+        let dir = TempDir::new();
+        let ctx = run_context(&dir);
+        let folder = ctx.folders[3].clone();
+        let error = anyhow::anyhow!("connection refused").context("elastic health");
+        ctx.record_failure(&folder, "elastic_search_health.json", "exec curl", &error);
+
+        let content = dir.read("apps/elastic_search_health.json.error");
+        assert!(content.starts_with("timestamp: "));
+        assert!(content.contains("attempted: exec curl\n"));
+        assert!(content.contains("error:\n  elastic health\n  connection refused\n"));
+        let manifest = ctx.manifest();
+        let entry = &manifest["apps/elastic_search_health.json"];
+        assert_eq!(entry.status, FileStatus::Failed);
+        assert_eq!(
+            entry.error_file.as_deref(),
+            Some("apps/elastic_search_health.json.error")
+        );
+    }
+
+    fn gunzip(path: &Path) -> Vec<u8> {
+        let mut out = vec![];
+        let file = std::fs::File::open(path).unwrap();
+        std::io::Read::read_to_end(&mut flate2::read::GzDecoder::new(file), &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn compressible_output_round_trips_through_gzip() {
+        let dir = TempDir::new();
+        let ctx = run_context(&dir).with_compression(64);
+        let folder = ctx.folders[1].clone();
+        let data = "{\"kind\": \"List\"}\n".repeat(20);
+        ctx.write_compressible(
+            &folder,
+            data.as_bytes(),
+            "state.json",
+            anyhow::anyhow!("empty"),
+        )
+        .unwrap();
+
+        let path = Path::new(&folder).join("state.json.gz");
+        assert!(!Path::new(&folder).join("state.json").exists());
+        assert_eq!(gunzip(&path), data.as_bytes());
+        let entry = &ctx.manifest()["infra/state.json.gz"];
+        assert_eq!(entry.status, FileStatus::Ok);
+        assert_eq!(entry.uncompressed_bytes, Some(data.len() as u64));
+        assert_eq!(
+            entry.compressed_bytes,
+            Some(std::fs::metadata(&path).unwrap().len())
+        );
+        assert!(entry.compressed_bytes < entry.uncompressed_bytes);
+    }
+
+    #[test]
+    fn small_or_uncompressed_outputs_stay_plain() {
+        let dir = TempDir::new();
+        let ctx = run_context(&dir).with_compression(64);
+        let folder = ctx.folders[1].clone();
+        ctx.write_compressible(&folder, b"{}", "small.json", anyhow::anyhow!("empty"))
+            .unwrap();
+        assert_eq!(dir.read("infra/small.json"), "{}");
+        assert_eq!(ctx.manifest()["infra/small.json"].uncompressed_bytes, None);
+
+        let ctx = run_context(&dir);
+        let data = "x".repeat(1000);
+        ctx.write_compressible(
+            &folder,
+            data.as_bytes(),
+            "big.json",
+            anyhow::anyhow!("empty"),
+        )
+        .unwrap();
+        assert_eq!(dir.read("infra/big.json"), data);
+    }
+
+    #[test]
+    fn line_endings_are_normalized_before_the_size_check() {
+        let dir = TempDir::new();
+        let ctx = run_context(&dir)
+            .with_compression(40)
+            .with_line_endings(true);
+        let folder = ctx.folders[1].clone();
+        //42 bytes with CRLF, 40 once normalized: not compressed.
+        let data = "a".repeat(19) + "\r\n" + &"b".repeat(19) + "\r\n";
+        ctx.write_compressible(
+            &folder,
+            data.as_bytes(),
+            "crlf.txt",
+            anyhow::anyhow!("empty"),
+        )
+        .unwrap();
+        assert_eq!(dir.read("infra/crlf.txt"), data.replace("\r\n", "\n"));
+
+        let data = data.repeat(4);
+        ctx.write_compressible(
+            &folder,
+            data.as_bytes(),
+            "crlf_big.txt",
+            anyhow::anyhow!("empty"),
+        )
+        .unwrap();
+        let unpacked = gunzip(&Path::new(&folder).join("crlf_big.txt.gz"));
+        assert_eq!(unpacked, data.replace("\r\n", "\n").as_bytes());
+    }
+
+    fn collected(files: usize) -> CoverageOutcome {
+        CoverageOutcome::Collected { files }
+    }
+
+    fn failed(error: &str) -> CoverageOutcome {
+        CoverageOutcome::Failed {
+            error: error.to_string(),
+        }
+    }
+
+    fn skipped(reason: &str) -> CoverageOutcome {
+        CoverageOutcome::Skipped {
+            reason: reason.to_string(),
+        }
+    }
+
+    #[test]
+    fn coverage_keeps_the_worst_outcome() {
+        let ctx = RunContext::default();
+        //the steps of a collector add their files up.
+        ctx.record_coverage("events", collected(2));
+        ctx.record_coverage("events", collected(3));
+        //a failed step wins over collected ones, before or after it.
+        ctx.record_coverage("logs", collected(4));
+        ctx.record_coverage("logs", failed("pod web-0 gone"));
+        ctx.record_coverage("logs", collected(1));
+        //the first failure is kept.
+        ctx.record_coverage("helm", failed("no helm"));
+        ctx.record_coverage("helm", failed("timeout"));
+        //collected wins over skipped and disabled, skipped over disabled.
+        ctx.record_coverage("kafka", skipped("no broker in web"));
+        ctx.record_coverage("kafka", collected(1));
+        ctx.record_coverage("kafka", CoverageOutcome::Disabled);
+        ctx.record_coverage("spark", CoverageOutcome::Disabled);
+        ctx.record_coverage("spark", skipped("no spark in web"));
+        ctx.record_coverage("plugins", CoverageOutcome::Disabled);
+        ctx.record_coverage("plugins", CoverageOutcome::Disabled);
+
+        assert_eq!(
+            ctx.coverage(),
+            BTreeMap::from([
+                ("events".to_string(), collected(5)),
+                ("helm".to_string(), failed("no helm")),
+                ("kafka".to_string(), collected(1)),
+                ("logs".to_string(), failed("pod web-0 gone")),
+                ("plugins".to_string(), CoverageOutcome::Disabled),
+                ("spark".to_string(), skipped("no spark in web")),
+            ])
+        );
+        assert_eq!(
+            ctx.render_coverage(),
+            vec![
+                "helm: failed (no helm)",
+                "logs: failed (pod web-0 gone)",
+                "events: collected 5 files",
+                "kafka: collected 1 files",
+                "plugins: skipped (disabled)",
+                "spark: skipped (no spark in web)",
+            ]
+        );
+    }
+
+    #[test]
+    fn coverage_of_outputs_recorded_per_step() {
+        let dir = TempDir::new();
+        let ctx = run_context(&dir);
+        let folder = ctx.folders[1].clone();
+        let error = anyhow::anyhow!("refused");
+
+        let before = ctx.output_counts();
+        ctx.record_output(&folder, "a.json");
+        ctx.record_failure(&folder, "b.json", "get b", &error);
+        ctx.record_outputs("nodes", before);
+        //a step where every output failed turns the collector failed.
+        let before = ctx.output_counts();
+        ctx.record_failure(&folder, "c.json", "get c", &error);
+        ctx.record_failure(&folder, "d.json", "get d", &error);
+        ctx.record_outputs("nodes", before);
+        assert_eq!(ctx.coverage()["nodes"], failed("2 outputs failed"));
+
+        let before = ctx.output_counts();
+        ctx.record_output(&folder, "e.json");
+        ctx.record_collected("storage", before, &Ok(()));
+        let before = ctx.output_counts();
+        ctx.record_output(&folder, "f.json");
+        ctx.record_output(&folder, "g.json");
+        ctx.record_collected("storage", before, &Ok(()));
+        assert_eq!(ctx.coverage()["storage"], collected(3));
+        ctx.record_collected::<()>("storage", ctx.output_counts(), &Err(error));
+        assert_eq!(ctx.coverage()["storage"], failed("refused"));
+    }
+}
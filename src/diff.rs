@@ -0,0 +1,444 @@
+use anyhow::Result;
+use flate2::read::MultiGzDecoder;
+use serde::Deserialize;
+use serde_derive::Serialize;
+use serde_json::Value;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::sha256_hex;
+
+pub const DIFF_JSON_FILE: &str = "diff.json";
+
+//collected files an archive diff looks at, relative to the archive root folder.
+fn is_diff_input(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    (name.starts_with("helm_list_") && name.ends_with(".log"))
+        || (name.starts_with("kubernetes_pods_") && name.ends_with(".json"))
+        || name == "kubernetes_nodes_list.json"
+        || name == "elastic_search_health.json"
+        || name == "kafka_topics.log"
+        || name == "hadoop_report_dfsadmin.log"
+        || is_configmap_export(path)
+}
+
+//the configmaps of the manifest export, pods/manifests_<ns>/configmaps.yaml.
+fn is_configmap_export(path: &str) -> bool {
+    let mut parts = path.rsplit('/');
+    parts.next() == Some("configmaps.yaml")
+        && parts.next().is_some_and(|f| f.starts_with("manifests_"))
+}
+
+//read the interesting members of a collection archive in memory, keyed by path without the root folder.
+pub fn read_archive(path: &Path) -> Result<BTreeMap<String, String>> {
+    let mut archive = tar::Archive::new(MultiGzDecoder::new(File::open(path)?));
+    let mut files = BTreeMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let member = entry.path()?.to_path_buf();
+        let relative = member
+            .components()
+            .skip(1)
+            .collect::<PathBuf>()
+            .display()
+            .to_string();
+        if !is_diff_input(&relative) {
+            continue;
+        }
+        let mut content = vec![];
+        entry.read_to_end(&mut content)?;
+        files.insert(relative, String::from_utf8_lossy(&content).to_string());
+    }
+    Ok(files)
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize)]
+pub struct Snapshot {
+    pub helm_releases: Option<BTreeMap<String, String>>,
+    pub image_digests: Option<BTreeMap<String, String>>,
+    pub node_versions: Option<BTreeMap<String, String>>,
+    pub pod_restarts: Option<BTreeMap<String, String>>,
+    pub health: Option<BTreeMap<String, String>>,
+    pub configmap_checksums: Option<BTreeMap<String, String>>,
+}
+
+fn files_matching<'a>(
+    files: &'a BTreeMap<String, String>,
+    prefix: &'a str,
+    suffix: &'a str,
+) -> impl Iterator<Item = (&'a String, &'a String)> {
+    files.iter().filter(move |(k, _)| {
+        let name = k.rsplit('/').next().unwrap_or(k);
+        name.starts_with(prefix) && name.ends_with(suffix)
+    })
+}
+
+//"helm ls" table: NAME NAMESPACE REVISION UPDATED STATUS CHART APP VERSION, tab separated.
+fn helm_releases(files: &BTreeMap<String, String>) -> Option<BTreeMap<String, String>> {
+    let mut found = false;
+    let mut releases = BTreeMap::new();
+    for (_, content) in files_matching(files, "helm_list_", ".log") {
+        found = true;
+        content.lines().skip(1).for_each(|l| {
+            let cols = l.split('\t').map(|c| c.trim()).collect::<Vec<&str>>();
+            if cols.len() >= 7 {
+                releases.insert(
+                    format!("{}/{}", cols[1], cols[0]),
+                    format!("{} (app {}, revision {})", cols[5], cols[6], cols[2]),
+                );
+            }
+        });
+    }
+    found.then_some(releases)
+}
+
+fn pod_items(files: &BTreeMap<String, String>) -> Option<Vec<Value>> {
+    let mut found = false;
+    let mut items = vec![];
+    for (_, content) in files_matching(files, "kubernetes_pods_", ".json") {
+        found = true;
+        if let Ok(v) = serde_json::from_str::<Value>(content) {
+            items.extend(v["items"].as_array().cloned().unwrap_or_default());
+        }
+    }
+    found.then_some(items)
+}
+
+fn container_statuses(pod: &Value) -> Vec<&Value> {
+    ["initContainerStatuses", "containerStatuses"]
+        .iter()
+        .filter_map(|k| pod["status"][k].as_array())
+        .flatten()
+        .collect()
+}
+
+fn image_digests(pods: &[Value]) -> BTreeMap<String, String> {
+    let mut images: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    pods.iter().flat_map(container_statuses).for_each(|c| {
+        if let (Some(image), Some(id)) = (c["image"].as_str(), c["imageID"].as_str()) {
+            let digest = id.rsplit('@').next().unwrap_or(id).to_string();
+            images.entry(image.to_string()).or_default().insert(digest);
+        }
+    });
+    images
+        .into_iter()
+        .map(|(k, v)| (k, v.into_iter().collect::<Vec<String>>().join(", ")))
+        .collect()
+}
+
+fn pod_restarts(pods: &[Value]) -> BTreeMap<String, String> {
+    pods.iter()
+        .map(|p| {
+            let restarts: i64 = container_statuses(p)
+                .iter()
+                .filter_map(|c| c["restartCount"].as_i64())
+                .sum();
+            (
+                format!(
+                    "{}/{}",
+                    p["metadata"]["namespace"].as_str().unwrap_or_default(),
+                    p["metadata"]["name"].as_str().unwrap_or_default()
+                ),
+                restarts.to_string(),
+            )
+        })
+        .collect()
+}
+
+fn node_versions(files: &BTreeMap<String, String>) -> Option<BTreeMap<String, String>> {
+    let content = files
+        .iter()
+        .find(|(k, _)| k.ends_with("kubernetes_nodes_list.json"))?
+        .1;
+    let v: Value = serde_json::from_str(content).ok()?;
+    let mut nodes = v["items"]
+        .as_array()?
+        .iter()
+        .map(|n| {
+            (
+                n["metadata"]["name"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                n["status"]["nodeInfo"]["kubeletVersion"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            )
+        })
+        .collect::<BTreeMap<String, String>>();
+    nodes.insert("(node count)".to_string(), nodes.len().to_string());
+    Some(nodes)
+}
+
+fn health(files: &BTreeMap<String, String>) -> Option<BTreeMap<String, String>> {
+    let mut health = BTreeMap::new();
+    let get = |name: &str| files.iter().find(|(k, _)| k.ends_with(name)).map(|f| f.1);
+
+    if let Some(es) = get("elastic_search_health.json") {
+        let status = serde_json::from_str::<Value>(es)
+            .ok()
+            .and_then(|v| v["status"].as_str().map(|s| s.to_string()))
+            .unwrap_or_else(|| "unknown".to_string());
+        health.insert("elasticsearch status".to_string(), status);
+    }
+    if let Some(kf) = get("kafka_topics.log") {
+        let topics = kf.lines().filter(|l| !l.trim().is_empty()).count();
+        health.insert("kafka topics".to_string(), topics.to_string());
+    }
+    if let Some(hd) = get("hadoop_report_dfsadmin.log") {
+        for key in [
+            "Missing blocks:",
+            "Under replicated blocks:",
+            "Live datanodes",
+        ] {
+            if let Some(l) = hd.lines().find(|l| l.trim_start().starts_with(key)) {
+                health.insert(
+                    format!("hdfs {}", key.trim_end_matches(':')),
+                    l.trim().to_string(),
+                );
+            }
+        }
+    }
+    (!health.is_empty()).then_some(health)
+}
+
+//"<ns>/<configmap>:<key>" -> checksum of the value, from the multi document yaml of the
+//manifest export (export_manifests). binaryData values are hashed in their base64 form.
+fn configmap_checksums(files: &BTreeMap<String, String>) -> Option<BTreeMap<String, String>> {
+    let mut found = false;
+    let mut checksums = BTreeMap::new();
+    for (_, content) in files.iter().filter(|(k, _)| is_configmap_export(k)) {
+        found = true;
+        for document in serde_yaml::Deserializer::from_str(content) {
+            let Ok(cm) = Value::deserialize(document) else {
+                continue;
+            };
+            let name = format!(
+                "{}/{}",
+                cm["metadata"]["namespace"].as_str().unwrap_or_default(),
+                cm["metadata"]["name"].as_str().unwrap_or_default()
+            );
+            for data in ["data", "binaryData"] {
+                for (k, value) in cm[data].as_object().into_iter().flatten() {
+                    let value = value.as_str().unwrap_or_default();
+                    checksums.insert(format!("{}:{}", name, k), sha256_hex(value.as_bytes()));
+                }
+            }
+        }
+    }
+    found.then_some(checksums)
+}
+
+pub fn snapshot(files: &BTreeMap<String, String>) -> Snapshot {
+    let pods = pod_items(files);
+    Snapshot {
+        helm_releases: helm_releases(files),
+        image_digests: pods.as_deref().map(image_digests),
+        node_versions: node_versions(files),
+        pod_restarts: pods.as_deref().map(pod_restarts),
+        health: health(files),
+        configmap_checksums: configmap_checksums(files),
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub key: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize)]
+pub struct CategoryDiff {
+    pub category: String,
+    //set when one of the archives does not contain the data for this category.
+    pub unavailable: Option<String>,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize)]
+pub struct ArchiveDiff {
+    pub archive_a: String,
+    pub archive_b: String,
+    pub categories: Vec<CategoryDiff>,
+}
+
+pub fn diff_maps(
+    category: &str,
+    a: &Option<BTreeMap<String, String>>,
+    b: &Option<BTreeMap<String, String>>,
+) -> CategoryDiff {
+    let (a, b) = match (a, b) {
+        (Some(a), Some(b)) => (a, b),
+        (a, b) => {
+            let missing = match (a.is_none(), b.is_none()) {
+                (true, true) => "not available in either archive",
+                (true, false) => "not available in the first archive",
+                _ => "not available in the second archive",
+            };
+            return CategoryDiff {
+                category: category.to_string(),
+                unavailable: Some(missing.to_string()),
+                changes: vec![],
+            };
+        }
+    };
+    let keys = a.keys().chain(b.keys()).collect::<BTreeSet<&String>>();
+    let changes = keys
+        .into_iter()
+        .filter(|k| a.get(*k) != b.get(*k))
+        .map(|k| Change {
+            key: k.clone(),
+            before: a.get(k).cloned(),
+            after: b.get(k).cloned(),
+        })
+        .collect();
+    CategoryDiff {
+        category: category.to_string(),
+        unavailable: None,
+        changes,
+    }
+}
+
+pub fn diff_snapshots(a: &Snapshot, b: &Snapshot) -> Vec<CategoryDiff> {
+    vec![
+        diff_maps("helm releases", &a.helm_releases, &b.helm_releases),
+        diff_maps("image digests", &a.image_digests, &b.image_digests),
+        diff_maps("nodes", &a.node_versions, &b.node_versions),
+        diff_maps("pod restarts", &a.pod_restarts, &b.pod_restarts),
+        diff_maps("health", &a.health, &b.health),
+        diff_maps(
+            "configmap checksums",
+            &a.configmap_checksums,
+            &b.configmap_checksums,
+        ),
+    ]
+}
+
+pub fn diff_archives(archive_a: &Path, archive_b: &Path) -> Result<ArchiveDiff> {
+    let a = snapshot(&read_archive(archive_a)?);
+    let b = snapshot(&read_archive(archive_b)?);
+    Ok(ArchiveDiff {
+        archive_a: archive_a.display().to_string(),
+        archive_b: archive_b.display().to_string(),
+        categories: diff_snapshots(&a, &b),
+    })
+}
+
+pub fn render_diff(diff: &ArchiveDiff) -> String {
+    let mut out = format!("Changes from {} to {}\n", diff.archive_a, diff.archive_b);
+    for c in &diff.categories {
+        out.push_str(&format!("\n== {} ==\n", c.category));
+        if let Some(u) = &c.unavailable {
+            out.push_str(&format!("  {}\n", u));
+            continue;
+        }
+        if c.changes.is_empty() {
+            out.push_str("  no changes\n");
+        }
+        for ch in &c.changes {
+            let line = match (&ch.before, &ch.after) {
+                (None, Some(a)) => format!("  + {}: {}\n", ch.key, a),
+                (Some(b), None) => format!("  - {}: {}\n", ch.key, b),
+                (Some(b), Some(a)) => format!("  ~ {}: {} -> {}\n", ch.key, b, a),
+                (None, None) => continue,
+            };
+            out.push_str(&line);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, TempDir};
+
+    use flate2::{write::GzEncoder, Compression};
+
+    //tests/fixtures/diff/<side> archived under a root folder like the collection archives.
+    fn archive(dir: &TempDir, side: &str) -> PathBuf {
+        let path = dir.path().join(format!("info_{}.tar.gz", side));
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(&path).unwrap(),
+            Compression::default(),
+        ));
+        builder
+            .append_dir_all(format!("info_{}", side), fixture(&format!("diff/{}", side)))
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+        path
+    }
+
+    fn category<'a>(diff: &'a ArchiveDiff, name: &str) -> &'a CategoryDiff {
+        diff.categories.iter().find(|c| c.category == name).unwrap()
+    }
+
+    #[test]
+    fn configmaps_of_the_manifest_export_are_compared() {
+        let dir = TempDir::new();
+        let diff = diff_archives(&archive(&dir, "a"), &archive(&dir, "b")).unwrap();
+        let configmaps = category(&diff, "configmap checksums");
+        assert_eq!(configmaps.unavailable, None);
+        let changes = configmaps
+            .changes
+            .iter()
+            .map(|c| (c.key.as_str(), c.before.is_some(), c.after.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                ("kafka/kafka-config:server.properties", true, true),
+                ("kafka/kafka-config:truststore.jks", false, true),
+                ("kafka/kafka-scripts:setup.sh", true, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn archives_are_compared_by_category() {
+        let dir = TempDir::new();
+        let diff = diff_archives(&archive(&dir, "a"), &archive(&dir, "b")).unwrap();
+        let helm = &category(&diff, "helm releases").changes[0];
+        assert_eq!(helm.key, "kafka/kafka");
+        assert_eq!(
+            helm.after.as_deref(),
+            Some("kafka-23.0.0 (app 3.5.1, revision 4)")
+        );
+        assert!(category(&diff, "image digests").changes.is_empty());
+        let nodes = &category(&diff, "nodes").changes;
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].after.as_deref(), Some("v1.28.1"));
+        let restarts = &category(&diff, "pod restarts").changes[0];
+        assert_eq!(
+            (restarts.before.as_deref(), restarts.after.as_deref()),
+            (Some("0"), Some("7"))
+        );
+        let health = &category(&diff, "health").changes[0];
+        assert_eq!(health.after.as_deref(), Some("yellow"));
+        let report = render_diff(&diff);
+        assert!(report.contains("  ~ elasticsearch status: green -> yellow\n"));
+    }
+
+    #[test]
+    fn a_category_missing_on_one_side_is_unavailable() {
+        let mut b = read_archive(&archive(&TempDir::new(), "b")).unwrap();
+        b.retain(|k, _| !is_configmap_export(k));
+        let a = read_archive(&archive(&TempDir::new(), "a")).unwrap();
+        let categories = diff_snapshots(&snapshot(&a), &snapshot(&b));
+        let configmaps = categories
+            .iter()
+            .find(|c| c.category == "configmap checksums")
+            .unwrap();
+        assert_eq!(
+            configmaps.unavailable.as_deref(),
+            Some("not available in the second archive")
+        );
+    }
+}
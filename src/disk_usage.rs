@@ -0,0 +1,211 @@
+use anyhow::{anyhow, Result};
+use simplelog::{__private::log::warn, info};
+
+use crate::{access::PodAccess, components, context::RunContext};
+
+pub const DEFAULT_DISK_USAGE_PATHS: [&str; 4] = ["/var/log", "/data", "/dfs", "/kafka"];
+pub const DISK_USAGE_SUMMARY_FILE: &str = "disk_usage_summary.txt";
+
+//fullest filesystem of one container, from df -hP.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fullest {
+    pub pod: String,
+    pub container: String,
+    pub mount: String,
+    pub used_percent: u32,
+}
+
+//the (use%, mount) of the fullest filesystem in a df -P output.
+pub fn fullest_filesystem(df: &str) -> Option<(u32, String)> {
+    df.lines()
+        .skip(1)
+        .filter_map(|l| {
+            let cols = l.split_whitespace().collect::<Vec<&str>>();
+            let used = cols.get(4)?.trim_end_matches('%').parse::<u32>().ok()?;
+            Some((used, cols.get(5)?.to_string()))
+        })
+        .max_by_key(|(used, _)| *used)
+}
+
+pub fn render_summary(fullest: &[Fullest], skipped: &[String]) -> String {
+    let mut sorted = fullest.to_vec();
+    sorted.sort_by_key(|f| std::cmp::Reverse(f.used_percent));
+    let mut out = String::from("containers by fullest filesystem:\n");
+    sorted.iter().for_each(|f| {
+        out.push_str(&format!(
+            "  {:>3}% {} {}/{}\n",
+            f.used_percent, f.mount, f.pod, f.container
+        ))
+    });
+    if !skipped.is_empty() {
+        out.push_str("\nskipped (no df in the container):\n");
+        skipped
+            .iter()
+            .for_each(|s| out.push_str(&format!("  {}\n", s)));
+    }
+    out
+}
+
+//df -hP and du -sh of the paths in every container of the app pods, apps/disk_usage_<ns>_<pod>.log each.
+//a container without df (distroless) fails the exec or exits non-zero, it is noted and skipped.
+pub async fn collect_disk_usage<A: PodAccess>(
+    access: Vec<A>,
+    paths: &[String],
+    ctx: &RunContext,
+    folder: &str,
+) -> Result<()> {
+    let paths = if paths.is_empty() {
+        DEFAULT_DISK_USAGE_PATHS
+            .iter()
+            .map(|p| p.to_string())
+            .collect()
+    } else {
+        paths.to_vec()
+    };
+    let pods = components::app_pods(access).await?;
+
+    let mut fullest = vec![];
+    let mut skipped = vec![];
+    for p in &pods {
+        let mut report = String::new();
+        for c in &p.containers {
+            report.push_str(&format!("=== container {}\n", c));
+            let df = p
+                .api
+                .exec_status(&p.name, c, vec!["df".to_string(), "-hP".to_string()])
+                .await;
+            let df = match df {
+                Ok((out, 0)) => out,
+                Ok((_, code)) => {
+                    report.push_str(&format!("skipped: df exited with {}\n\n", code));
+                    skipped.push(format!("{}/{} (exit code {})", p.name, c, code));
+                    continue;
+                }
+                Err(e) => {
+                    report.push_str(&format!("skipped: {}\n\n", e));
+                    skipped.push(format!("{}/{} ({})", p.name, c, e));
+                    continue;
+                }
+            };
+            report.push_str(&format!("$ df -hP\n{}\n", df));
+            if let Some((used_percent, mount)) = fullest_filesystem(&df) {
+                fullest.push(Fullest {
+                    pod: p.name.clone(),
+                    container: c.clone(),
+                    mount,
+                    used_percent,
+                });
+            }
+            //du reports the missing paths on stderr and exits 1, what it found is still kept.
+            let mut du = vec!["du".to_string(), "-sh".to_string()];
+            du.extend(paths.iter().cloned());
+            match p.api.exec_status(&p.name, c, du).await {
+                Ok((out, _)) => {
+                    report.push_str(&format!("$ du -sh {}\n{}\n", paths.join(" "), out))
+                }
+                Err(e) => report.push_str(&format!("du failed: {}\n", e)),
+            }
+            report.push('\n');
+        }
+        let filename = format!("disk_usage_{}_{}.log", p.namespace, p.name);
+        let er = anyhow!("Empty disk usage for {}.", p.name);
+        let written = ctx
+            .claim_output(folder, &filename, &p.namespace, &p.name)
+            .and_then(|_| ctx.write_file(folder, report.as_bytes(), &filename, er));
+        match written {
+            Ok(_) => info!("File has been created {}/{}", folder, filename),
+            Err(e) => warn!("{}", e),
+        }
+    }
+
+    if !pods.is_empty() {
+        let summary = render_summary(&fullest, &skipped);
+        let er = anyhow!("Empty disk usage summary.");
+        ctx.write_file(folder, summary.as_bytes(), DISK_USAGE_SUMMARY_FILE, er)?;
+        info!(
+            "File has been created {}/{}",
+            folder, DISK_USAGE_SUMMARY_FILE
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    fn df(name: &str) -> String {
+        std::fs::read_to_string(fixture(&format!("disk_usage/{}", name))).unwrap()
+    }
+
+    fn fullest(pod: &str, mount: &str, used_percent: u32) -> Fullest {
+        Fullest {
+            pod: pod.to_string(),
+            container: "main".to_string(),
+            mount: mount.to_string(),
+            used_percent,
+        }
+    }
+
+    #[test]
+    fn fullest_filesystem_of_df_outputs() {
+        assert_eq!(
+            fullest_filesystem(&df("df_kafka.txt")),
+            Some((94, "/var/lib/kafka/data".to_string()))
+        );
+        //busybox, a "-" Use% is skipped.
+        assert_eq!(
+            fullest_filesystem(&df("df_busybox.txt")),
+            Some((100, "/data".to_string()))
+        );
+    }
+
+    #[test]
+    fn the_header_and_unparsable_lines_are_skipped() {
+        //a header only, "Use%" is not a number either way.
+        assert_eq!(
+            fullest_filesystem("Filesystem Size Used Avail Use% Mounted on\n"),
+            None
+        );
+        //the first line is always the header, even without the usual titles.
+        assert_eq!(
+            fullest_filesystem("overlay 98G 97G 1G 99% /\ntmpfs 64M 0 64M 0% /dev\n"),
+            Some((0, "/dev".to_string()))
+        );
+        let df = "Filesystem Size Used Avail Use% Mounted on\n\
+                  proc 0 0 0 - /proc\n\
+                  broken 1G\n\
+                  overlay 10G 5G 5G 50 /\n\
+                  tmpfs 64M 1M 63M 2% \n";
+        //50 without % still parses, the line without a mount does not.
+        assert_eq!(fullest_filesystem(df), Some((50, "/".to_string())));
+        assert_eq!(fullest_filesystem(""), None);
+    }
+
+    #[test]
+    fn summary_sorted_by_use() {
+        let summary = render_summary(
+            &[
+                fullest("web-0", "/", 40),
+                fullest("kafka-0", "/var/lib/kafka/data", 94),
+                fullest("es-0", "/data", 71),
+            ],
+            &["distroless-0/main (exit code 127)".to_string()],
+        );
+        assert_eq!(
+            summary,
+            "containers by fullest filesystem:\n\
+             \x20  94% /var/lib/kafka/data kafka-0/main\n\
+             \x20  71% /data es-0/main\n\
+             \x20  40% / web-0/main\n\
+             \n\
+             skipped (no df in the container):\n\
+             \x20 distroless-0/main (exit code 127)\n"
+        );
+        assert_eq!(
+            render_summary(&[], &[]),
+            "containers by fullest filesystem:\n"
+        );
+    }
+}
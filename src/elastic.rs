@@ -0,0 +1,197 @@
+use anyhow::Result;
+use regex::Regex;
+use serde_derive::Deserialize;
+
+use std::collections::BTreeMap;
+
+pub const INDEX_REPORT_FILE: &str = "elastic_search_index_report.txt";
+pub const INDEX_REPORT_TOP: usize = 20;
+
+//one row of _cat/indices?format=json&bytes=b, every value is a string and closed indices have nulls.
+#[derive(Debug, Clone, Default, Deserialize)]
+struct CatIndex {
+    index: Option<String>,
+    health: Option<String>,
+    #[serde(rename = "docs.count")]
+    docs_count: Option<String>,
+    #[serde(rename = "store.size")]
+    store_size: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexInfo {
+    pub index: String,
+    pub health: String,
+    pub docs: u64,
+    pub store_bytes: u64,
+}
+
+pub fn parse_indices(json: &str) -> Result<Vec<IndexInfo>> {
+    let rows: Vec<CatIndex> = serde_json::from_str(json)?;
+    let number = |v: &Option<String>| v.as_deref().and_then(|v| v.parse().ok()).unwrap_or(0);
+    Ok(rows
+        .iter()
+        .map(|r| IndexInfo {
+            index: r.index.clone().unwrap_or_default(),
+            health: r.health.clone().unwrap_or_default(),
+            docs: number(&r.docs_count),
+            store_bytes: number(&r.store_size),
+        })
+        .collect())
+}
+
+//the index name without its rollover counter and date suffix:
+//logs-app-2024.01.15 -> logs-app-*, .ds-metrics-000012 -> .ds-metrics-*, kibana stays kibana.
+pub fn index_pattern(index: &str) -> String {
+    let suffixes = [
+        Regex::new(r"[-_.]\d{6}$").unwrap(),
+        Regex::new(r"[-_.]\d{4}([-_.]?\d{2}){0,2}$").unwrap(),
+    ];
+    let mut name = index.to_string();
+    let mut stripped = false;
+    loop {
+        let before = name.len();
+        suffixes
+            .iter()
+            .for_each(|s| name = s.replace(&name, "").to_string());
+        if name.len() == before {
+            break;
+        }
+        stripped = true;
+    }
+    if stripped {
+        format!("{}-*", name)
+    } else {
+        name
+    }
+}
+
+pub fn human(bytes: u64) -> String {
+    let units = ["b", "kb", "mb", "gb", "tb"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1}{}", value, units[unit])
+}
+
+pub fn render_index_report(indices: &[IndexInfo]) -> String {
+    let mut sorted = indices.to_vec();
+    sorted.sort_by_key(|i| std::cmp::Reverse(i.store_bytes));
+    let mut out = format!(
+        "top {} indices by store size:\n{:<60} {:>7} {:>12} {:>10}\n",
+        INDEX_REPORT_TOP, "INDEX", "HEALTH", "DOCS", "STORE"
+    );
+    for i in sorted.iter().take(INDEX_REPORT_TOP) {
+        out.push_str(&format!(
+            "{:<60} {:>7} {:>12} {:>10}\n",
+            i.index,
+            i.health,
+            i.docs,
+            human(i.store_bytes)
+        ));
+    }
+
+    let mut patterns: BTreeMap<String, (usize, u64, u64)> = BTreeMap::new();
+    for i in indices {
+        let p = patterns.entry(index_pattern(&i.index)).or_default();
+        p.0 += 1;
+        p.1 += i.docs;
+        p.2 += i.store_bytes;
+    }
+    let mut patterns = patterns.into_iter().collect::<Vec<_>>();
+    patterns.sort_by_key(|(_, p)| std::cmp::Reverse(p.2));
+    out.push_str(&format!(
+        "\ntotals per index pattern:\n{:<60} {:>7} {:>12} {:>10}\n",
+        "PATTERN", "INDICES", "DOCS", "STORE"
+    ));
+    for (name, (count, docs, bytes)) in &patterns {
+        out.push_str(&format!(
+            "{:<60} {:>7} {:>12} {:>10}\n",
+            name,
+            count,
+            docs,
+            human(*bytes)
+        ));
+    }
+    out.push_str(&format!(
+        "\ntotal: {} indices, {} docs, {}\n",
+        indices.len(),
+        indices.iter().map(|i| i.docs).sum::<u64>(),
+        human(indices.iter().map(|i| i.store_bytes).sum())
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    fn indices() -> Vec<IndexInfo> {
+        let json = std::fs::read_to_string(fixture("elastic/cat_indices.json")).unwrap();
+        parse_indices(&json).unwrap()
+    }
+
+    #[test]
+    fn closed_indices_count_as_empty() {
+        let indices = indices();
+        assert_eq!(indices.len(), 6);
+        assert_eq!(
+            indices[5],
+            IndexInfo {
+                index: "old-logs-2023.12".to_string(),
+                ..Default::default()
+            }
+        );
+        assert_eq!((indices[1].docs, indices[1].store_bytes), (3000, 6291456));
+    }
+
+    #[test]
+    fn index_pattern_strips_dates_and_rollover_counters() {
+        assert_eq!(index_pattern("logs-app-2024.01.15"), "logs-app-*");
+        assert_eq!(index_pattern("logs_app_20240115"), "logs_app-*");
+        assert_eq!(index_pattern(".ds-metrics-000012"), ".ds-metrics-*");
+        assert_eq!(
+            index_pattern(".ds-logs-app-2024.01.15-000003"),
+            ".ds-logs-app-*"
+        );
+        assert_eq!(index_pattern("old-logs-2023.12"), "old-logs-*");
+        assert_eq!(index_pattern("kibana"), "kibana");
+        assert_eq!(index_pattern("app-v2"), "app-v2");
+    }
+
+    #[test]
+    fn report_groups_the_indices_by_pattern() {
+        let report = render_index_report(&indices());
+        let totals = report.split("totals per index pattern:").nth(1).unwrap();
+        let rows = totals
+            .lines()
+            .skip(2)
+            .take_while(|l| !l.is_empty())
+            .map(|l| l.split_whitespace().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["logs-app-*", "2", "4000", "8.0mb"],
+                vec![".ds-metrics-*", "2", "120", "3.0kb"],
+                vec!["kibana", "1", "12", "512.0b"],
+                vec!["old-logs-*", "1", "0", "0.0b"],
+            ]
+        );
+        assert!(report.ends_with("total: 6 indices, 4132 docs, 8.0mb\n"));
+        //the biggest index first.
+        let top = report.lines().nth(2).unwrap();
+        assert!(top.starts_with("logs-app-2024.01.16"));
+    }
+
+    #[test]
+    fn human_sizes() {
+        assert_eq!(human(0), "0.0b");
+        assert_eq!(human(1536), "1.5kb");
+        assert_eq!(human(5 * 1024 * 1024 * 1024 * 1024 * 1024), "5120.0tb");
+    }
+}
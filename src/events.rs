@@ -0,0 +1,155 @@
+use chrono::Utc;
+use futures_util::{stream, StreamExt};
+use k8s_openapi::api::core::v1::Event;
+use kube::{
+    runtime::{watcher, WatchStreamExt},
+    Api, Client,
+};
+use simplelog::__private::log::warn;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use std::collections::BTreeSet;
+
+pub const WARNING_EVENT_REASON: &str = "included due to warning event";
+pub const EVENTS_DURING_COLLECTION_FILE: &str = "events_during_collection.log";
+
+//(namespace, pod) of every pod named by a Warning event in the given namespaces.
+pub fn warning_event_pods(events: &[Event], namespaces: &[String]) -> BTreeSet<(String, String)> {
+    events
+        .iter()
+        .filter(|e| e.type_.as_deref() == Some("Warning"))
+        .filter(|e| e.involved_object.kind.as_deref() == Some("Pod"))
+        .filter_map(|e| {
+            let namespace = e
+                .involved_object
+                .namespace
+                .clone()
+                .or_else(|| e.metadata.namespace.clone())?;
+            let name = e.involved_object.name.clone()?;
+            namespaces.contains(&namespace).then_some((namespace, name))
+        })
+        .collect()
+}
+
+//one line per event: received at, namespace, type, reason, object, count and message.
+pub fn event_line(e: &Event) -> String {
+    format!(
+        "{} {} {} {} {}/{} x{}: {}",
+        Utc::now().to_rfc3339(),
+        e.metadata.namespace.clone().unwrap_or_default(),
+        e.type_.clone().unwrap_or_default(),
+        e.reason.clone().unwrap_or_default(),
+        e.involved_object.kind.clone().unwrap_or_default(),
+        e.involved_object.name.clone().unwrap_or_default(),
+        e.count.unwrap_or(1),
+        e.message.clone().unwrap_or_default().trim()
+    )
+}
+
+fn version(e: &Event) -> (String, String) {
+    (
+        e.metadata.uid.clone().unwrap_or_default(),
+        e.metadata.resource_version.clone().unwrap_or_default(),
+    )
+}
+
+//the events of the namespaces created or updated until cancel, as event_line lines.
+//The events existing at start are skipped, they are in the point in time dump. The watcher
+//relists on restarts (resourceVersion too old), only the versions not seen yet are kept then.
+pub fn watch_events(
+    client: &Client,
+    namespaces: &[String],
+    cancel: CancellationToken,
+) -> JoinHandle<Vec<String>> {
+    let streams = namespaces
+        .iter()
+        .map(|n| {
+            let api: Api<Event> = Api::namespaced(client.clone(), n);
+            let namespace = n.clone();
+            watcher(api, watcher::Config::default())
+                .default_backoff()
+                .map(move |e| (namespace.clone(), e))
+                .boxed()
+        })
+        .collect::<Vec<_>>();
+    tokio::task::spawn(async move {
+        let mut events = stream::select_all(streams);
+        let mut seen = BTreeSet::new();
+        let mut listed = BTreeSet::new();
+        let mut lines = vec![];
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                event = events.next() => match event {
+                    Some((namespace, Ok(event))) => {
+                        let (objects, initial) = match event {
+                            watcher::Event::Applied(e) => (vec![e], false),
+                            watcher::Event::Deleted(_) => continue,
+                            watcher::Event::Restarted(all) => (all, listed.insert(namespace)),
+                        };
+                        for e in objects {
+                            if seen.insert(version(&e)) && !initial {
+                                lines.push(event_line(&e));
+                            }
+                        }
+                    }
+                    Some((namespace, Err(e))) => warn!("Event watch of {}: {}", namespace, e),
+                    None => break,
+                }
+            }
+        }
+        lines
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    fn events() -> Vec<Event> {
+        serde_json::from_str(&std::fs::read_to_string(fixture("events/warnings.json")).unwrap())
+            .unwrap()
+    }
+
+    fn pods(namespaces: &[&str]) -> Vec<(String, String)> {
+        let namespaces = namespaces.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        warning_event_pods(&events(), &namespaces)
+            .into_iter()
+            .collect()
+    }
+
+    fn key(namespace: &str, pod: &str) -> (String, String) {
+        (namespace.to_string(), pod.to_string())
+    }
+
+    #[test]
+    fn warning_events_name_their_pods_once() {
+        assert_eq!(
+            pods(&["kafka"]),
+            [key("kafka", "kafka-0"), key("kafka", "zk-0")]
+        );
+    }
+
+    #[test]
+    fn pods_of_other_namespaces_are_left_out() {
+        assert_eq!(
+            pods(&["kafka", "web"]),
+            [
+                key("kafka", "kafka-0"),
+                key("kafka", "zk-0"),
+                key("web", "web-0")
+            ]
+        );
+        assert!(pods(&["monitoring"]).is_empty());
+    }
+
+    #[test]
+    fn event_line_keeps_the_object_and_count() {
+        let line = event_line(&events()[0]);
+        assert!(line.ends_with(
+            " kafka Warning BackOff Pod/kafka-0 x1: Back-off restarting failed container"
+        ));
+    }
+}
@@ -0,0 +1,238 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt};
+use simplelog::info;
+
+use std::sync::Mutex;
+
+use crate::{
+    access::PodAccess,
+    context::RunContext,
+    credentials::redact_command,
+    get_pod_list,
+    ordinals::{self, OrdinalRange},
+};
+
+pub const EXEC_CONCURRENCY: usize = 8;
+pub const COMMANDS_EXECUTED_FILE: &str = "commands_executed.sh";
+
+//an exec into a pod, recorded by the access layer when it starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecRecord {
+    pub timestamp: DateTime<Utc>,
+    pub namespace: String,
+    pub pod: String,
+    pub container: String,
+    //already redacted (redact_command).
+    pub command: Vec<String>,
+}
+
+//the execs of a run, in order. Shared by the RunContext and the pod accesses of the run.
+#[derive(Debug, Default)]
+pub struct ExecRecorder(Mutex<Vec<ExecRecord>>);
+
+impl ExecRecorder {
+    //the command is redacted before it is kept.
+    pub fn record(&self, namespace: &str, pod: &str, container: &str, command: &[String]) {
+        self.0.lock().unwrap().push(ExecRecord {
+            timestamp: Utc::now(),
+            namespace: namespace.to_string(),
+            pod: pod.to_string(),
+            container: container.to_string(),
+            command: redact_command(command),
+        });
+    }
+
+    pub fn records(&self) -> Vec<ExecRecord> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_./:=@%+,-".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+//commands_executed.sh: each exec as a commented kubectl exec with its start time, to be uncommented by hand.
+pub fn render_script(records: &[ExecRecord]) -> String {
+    let mut out = String::from(
+        "#!/bin/sh\n# Commands run inside the pods by the collection, in execution order.\n# Credentials are shown as ***.\n",
+    );
+    for r in records {
+        let command = r
+            .command
+            .iter()
+            .map(|a| shell_quote(a))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let line = format!(
+            "kubectl exec -n {} {} -c {} -- {}",
+            shell_quote(&r.namespace),
+            shell_quote(&r.pod),
+            shell_quote(&r.container),
+            command
+        );
+        out.push_str(&format!(
+            "\n# {}\n# {}\n",
+            r.timestamp.to_rfc3339(),
+            line.replace('\n', "\n# ")
+        ));
+    }
+    out
+}
+
+//commands_executed.sh in folder with the execs of the run, nothing when there was none.
+pub fn write_commands_executed(ctx: &RunContext, folder: &str) -> Result<()> {
+    let execs = ctx.execs.records();
+    if execs.is_empty() {
+        return Ok(());
+    }
+    let er = anyhow!("No command executed in the pods.");
+    ctx.write_file(
+        folder,
+        render_script(&execs).as_bytes(),
+        COMMANDS_EXECUTED_FILE,
+        er,
+    )?;
+    info!(
+        "File has been created {}/{}",
+        folder, COMMANDS_EXECUTED_FILE
+    );
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecResult {
+    pub namespace: String,
+    pub pod: String,
+    pub container: String,
+    pub output: String,
+    //-1 when the exec itself failed or no status came back.
+    pub exit_code: i32,
+    pub error: Option<String>,
+}
+
+impl ExecResult {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none() && self.exit_code == 0
+    }
+}
+
+//run the same command on every pod matching the selector (and the ordinal range), at most `concurrency` at a time.
+pub async fn exec_on_pods<A: PodAccess>(
+    pods: Vec<A>,
+    selector: &str,
+    ordinal_range: Option<&OrdinalRange>,
+    container: Option<&str>,
+    command: Vec<String>,
+    concurrency: usize,
+) -> Result<Vec<ExecResult>> {
+    let mut pods = get_pod_list(pods, selector.to_string(), "".to_string()).await?;
+    if let Some(o) = ordinal_range {
+        pods = ordinals::filter_pods(pods, o);
+    }
+    let results = stream::iter(pods)
+        .map(|p| {
+            let container = container
+                .map(|c| c.to_string())
+                .or_else(|| p.containers.first().cloned())
+                .unwrap_or_default();
+            let command = command.clone();
+            async move {
+                let r = p.api.exec_status(&p.name, &container, command).await;
+                let (output, exit_code, error) = match r {
+                    Ok((o, c)) => (o, c, None),
+                    Err(e) => (String::new(), -1, Some(e.to_string())),
+                };
+                ExecResult {
+                    namespace: p.namespace,
+                    pod: p.name,
+                    container,
+                    output,
+                    exit_code,
+                    error,
+                }
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect::<Vec<ExecResult>>()
+        .await;
+    let mut results = results;
+    results.sort_by(|a, b| (&a.namespace, &a.pod).cmp(&(&b.namespace, &b.pod)));
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        access::FakeAccess,
+        credentials::{env_command, Credentials},
+        test_support::{fixture, run_context, TempDir},
+    };
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn credentials_never_reach_commands_executed() {
+        let dir = TempDir::new();
+        let execs = Arc::new(ExecRecorder::default());
+        let ctx = run_context(&dir).with_execs(execs.clone());
+        let mut access = FakeAccess::from_fixture(&fixture("access/cluster.json")).unwrap();
+        access.execs = execs;
+        let credentials = Credentials {
+            username: "elastic".to_string(),
+            password: "s3cr3t-env".to_string(),
+        };
+        let with_env = env_command(Some(&credentials), "curl -s http://localhost:9200");
+        let basic_auth = [
+            "/bin/sh",
+            "-c",
+            "curl -u admin:s3cr3t-basic http://localhost:8080",
+        ]
+        .map(|s| s.to_string())
+        .to_vec();
+        //recorded even when the exec itself fails.
+        let _ = access.exec("kafka-0", "kafka", with_env).await;
+        let _ = access
+            .in_namespace("web")
+            .exec_status("web-0", "nginx", basic_auth)
+            .await;
+        let folder = ctx.folders[3].clone();
+        write_commands_executed(&ctx, &folder).unwrap();
+
+        let script = dir.read(&format!("apps/{}", COMMANDS_EXECUTED_FILE));
+        assert!(!script.contains("s3cr3t"));
+        assert!(script.contains(
+            "# kubectl exec -n kafka kafka-0 -c kafka -- env ANTLOG_USERNAME=elastic 'ANTLOG_PASSWORD=***' /bin/sh -c 'curl -s http://localhost:9200'\n"
+        ));
+        assert!(script.contains(
+            "# kubectl exec -n web web-0 -c nginx -- /bin/sh -c 'curl -u admin:*** http://localhost:8080'\n"
+        ));
+    }
+
+    #[test]
+    fn no_script_without_execs() {
+        let dir = TempDir::new();
+        let ctx = run_context(&dir);
+        let folder = ctx.folders[3].clone();
+        write_commands_executed(&ctx, &folder).unwrap();
+        assert!(!dir
+            .path()
+            .join("apps")
+            .join(COMMANDS_EXECUTED_FILE)
+            .exists());
+    }
+
+    #[test]
+    fn each_run_records_its_own_execs() {
+        let run = ExecRecorder::default();
+        let other = ExecRecorder::default();
+        run.record("web", "web-0", "nginx", &["ls".to_string()]);
+        assert_eq!(run.records().len(), 1);
+        assert!(other.records().is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+use simplelog::__private::log::warn;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use std::{
+    ffi::{OsStr, OsString},
+    fmt, io,
+    process::Stdio,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use crate::self_usage::UsageCounters;
+
+pub const DEFAULT_EXTERNAL_TIMEOUT_SECONDS: u64 = 300;
+pub const DEFAULT_EXTERNAL_CONCURRENCY: usize = 16;
+
+//kubectl, helm and plugin processes running at the same time, over every collector.
+static PERMITS: OnceLock<Semaphore> = OnceLock::new();
+
+//the first call wins, the run sets it from external_command_concurrency before any command.
+pub fn set_concurrency(n: usize) {
+    let n = match n {
+        0 => DEFAULT_EXTERNAL_CONCURRENCY,
+        n => n,
+    };
+    let _ = PERMITS.set(Semaphore::new(n));
+}
+
+fn permits() -> &'static Semaphore {
+    PERMITS.get_or_init(|| Semaphore::new(DEFAULT_EXTERNAL_CONCURRENCY))
+}
+
+//one of the external_command_concurrency slots, held while a process of the tool runs.
+pub async fn permit() -> Option<SemaphorePermit<'static>> {
+    //the semaphore is never closed.
+    permits().acquire().await.ok()
+}
+
+//external_command_timeout_seconds, DEFAULT_EXTERNAL_TIMEOUT_SECONDS when 0.
+pub fn timeout(seconds: u64) -> Duration {
+    Duration::from_secs(match seconds {
+        0 => DEFAULT_EXTERNAL_TIMEOUT_SECONDS,
+        s => s,
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct ExternalOutput {
+    //program and the args given to run_external, never the authentication args of the command.
+    pub command: String,
+    //None when killed by a signal.
+    pub code: Option<i32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub duration: Duration,
+}
+
+impl ExternalOutput {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+
+    pub fn warn_stderr(&self) {
+        if !self.stderr.is_empty() {
+            warn!("{}", String::from_utf8_lossy(&self.stderr))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ExternalError {
+    NotFound { command: String },
+    Spawn { command: String, source: io::Error },
+    Timeout { command: String, after: Duration },
+}
+
+impl ExternalError {
+    pub fn command(&self) -> &str {
+        match self {
+            ExternalError::NotFound { command }
+            | ExternalError::Spawn { command, .. }
+            | ExternalError::Timeout { command, .. } => command,
+        }
+    }
+}
+
+impl fmt::Display for ExternalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExternalError::NotFound { command } => {
+                let program = command.split(' ').next().unwrap_or_default();
+                write!(
+                    f,
+                    "{}: {} is not installed or not in PATH.",
+                    command, program
+                )
+            }
+            ExternalError::Spawn { command, source } => write!(f, "{}: {}", command, source),
+            ExternalError::Timeout { command, after } => {
+                write!(f, "{}: killed after {:?}.", command, after)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExternalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExternalError::Spawn { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+//cmd (kubectl_command, helm_command) with args, at most external_command_concurrency at a time and killed
+//after timeout. A non-zero exit is not an error, its code is in the output. usage counts the spawn.
+pub async fn run_external<I, S>(
+    cmd: std::process::Command,
+    args: I,
+    timeout: Duration,
+    usage: &UsageCounters,
+) -> Result<ExternalOutput, ExternalError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let args = args
+        .into_iter()
+        .map(|a| a.as_ref().to_os_string())
+        .collect::<Vec<OsString>>();
+    let command = std::iter::once(cmd.get_program())
+        .chain(args.iter().map(|a| a.as_os_str()))
+        .map(|a| a.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut cmd = tokio::process::Command::from(cmd);
+    cmd.args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let _permit = permit().await;
+    let started = Instant::now();
+    usage.subprocess();
+    let child = cmd.spawn().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => ExternalError::NotFound {
+            command: command.clone(),
+        },
+        _ => ExternalError::Spawn {
+            command: command.clone(),
+            source: e,
+        },
+    })?;
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| ExternalError::Timeout {
+            command: command.clone(),
+            after: timeout,
+        })?
+        .map_err(|e| ExternalError::Spawn {
+            command: command.clone(),
+            source: e,
+        })?;
+    Ok(ExternalOutput {
+        command,
+        code: output.status.code(),
+        stdout: output.stdout,
+        stderr: output.stderr,
+        duration: started.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sh() -> std::process::Command {
+        std::process::Command::new("sh")
+    }
+
+    #[tokio::test]
+    async fn output_and_exit_code_of_the_command() {
+        let usage = UsageCounters::default();
+        let script = "echo out; echo err >&2; exit 3";
+        let output = run_external(sh(), ["-c", script], Duration::from_secs(10), &usage)
+            .await
+            .unwrap();
+        //a non-zero exit is an output, not an error.
+        assert_eq!(output.code, Some(3));
+        assert!(!output.success());
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+        assert_eq!(output.command, format!("sh -c {}", script));
+        let output = run_external(sh(), ["-c", "true"], Duration::from_secs(10), &usage)
+            .await
+            .unwrap();
+        assert!(output.success());
+        assert_eq!(usage.snapshot().subprocesses, 2);
+    }
+
+    #[tokio::test]
+    async fn command_killed_after_the_timeout() {
+        let usage = UsageCounters::default();
+        let started = Instant::now();
+        let e = run_external(sh(), ["-c", "sleep 30"], Duration::from_millis(300), &usage)
+            .await
+            .unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(matches!(e, ExternalError::Timeout { .. }));
+        assert_eq!(e.command(), "sh -c sleep 30");
+        assert_eq!(e.to_string(), "sh -c sleep 30: killed after 300ms.");
+    }
+
+    #[tokio::test]
+    async fn missing_binary_is_told_apart() {
+        let usage = UsageCounters::default();
+        let cmd = std::process::Command::new("kubectl-not-installed");
+        let e = run_external(cmd, ["get", "pods"], Duration::from_secs(10), &usage)
+            .await
+            .unwrap_err();
+        assert!(matches!(e, ExternalError::NotFound { .. }));
+        assert_eq!(
+            e.to_string(),
+            "kubectl-not-installed get pods: kubectl-not-installed is not installed or not in PATH."
+        );
+        //a path that is no executable.
+        let cmd = std::process::Command::new(std::env::temp_dir());
+        let e = run_external(cmd, [""; 0], Duration::from_secs(10), &usage)
+            .await
+            .unwrap_err();
+        assert!(matches!(e, ExternalError::Spawn { .. }));
+        assert!(std::error::Error::source(&e).is_some());
+    }
+
+    #[test]
+    fn timeout_defaults_when_unset() {
+        assert_eq!(
+            timeout(0),
+            Duration::from_secs(DEFAULT_EXTERNAL_TIMEOUT_SECONDS)
+        );
+        assert_eq!(timeout(5), Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::{anyhow, Result};
+use glob::Pattern;
+
+use crate::{ConfigFile, PodInfo};
+
+pub const DEFAULT_CONTAINER_ANNOTATION: &str = "kubectl.kubernetes.io/default-container";
+
+pub fn compile_globs(globs: &[String]) -> Result<Vec<Pattern>> {
+    globs
+        .iter()
+        .map(|g| Pattern::new(g).map_err(|e| anyhow!("Invalid glob {}: {}", g, e)))
+        .collect()
+}
+
+//an empty include list keeps everything, exclude wins over include.
+pub fn matches_filters(name: &str, include: &[Pattern], exclude: &[Pattern]) -> bool {
+    (include.is_empty() || include.iter().any(|p| p.matches(name)))
+        && !exclude.iter().any(|p| p.matches(name))
+}
+
+//which containers of a pod get their logs collected.
+#[derive(Default, Debug, Clone)]
+pub struct ContainerFilter {
+    pub include: Vec<Pattern>,
+    pub exclude: Vec<Pattern>,
+    pub only_default_container: bool,
+}
+
+impl ContainerFilter {
+    pub fn from_config(config: &ConfigFile) -> Result<Self> {
+        Ok(ContainerFilter {
+            include: compile_globs(&config.include_containers)?,
+            exclude: compile_globs(&config.exclude_containers)?,
+            only_default_container: config.only_default_container,
+        })
+    }
+
+    pub fn containers<A>(&self, pod: &PodInfo<A>) -> Vec<String> {
+        let default = pod
+            .pod
+            .metadata
+            .annotations
+            .as_ref()
+            .and_then(|a| a.get(DEFAULT_CONTAINER_ANNOTATION))
+            .filter(|d| self.only_default_container && pod.containers.contains(d));
+        pod.containers
+            .iter()
+            .filter(|c| default.is_none_or(|d| d == *c))
+            .filter(|c| matches_filters(c, &self.include, &self.exclude))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::pod_info;
+
+    fn pod(containers: &[&str], default: Option<&str>) -> PodInfo<()> {
+        let mut annotations = serde_json::Map::new();
+        if let Some(d) = default {
+            annotations.insert(DEFAULT_CONTAINER_ANNOTATION.to_string(), d.into());
+        }
+        pod_info(serde_json::json!({
+            "metadata": {"name": "app-0", "namespace": "apps", "annotations": annotations},
+            "spec": {"containers": containers.iter().map(|c| serde_json::json!({"name": c})).collect::<Vec<_>>()}
+        }))
+    }
+
+    fn filter(include: &[&str], exclude: &[&str], only_default: bool) -> ContainerFilter {
+        let config = ConfigFile {
+            include_containers: include.iter().map(|s| s.to_string()).collect(),
+            exclude_containers: exclude.iter().map(|s| s.to_string()).collect(),
+            only_default_container: only_default,
+            ..Default::default()
+        };
+        ContainerFilter::from_config(&config).unwrap()
+    }
+
+    #[test]
+    fn empty_filters_keep_every_container() {
+        let p = pod(&["app", "istio-proxy", "fluent-bit"], None);
+        assert_eq!(
+            filter(&[], &[], false).containers(&p),
+            ["app", "istio-proxy", "fluent-bit"]
+        );
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let p = pod(&["app", "app-sidecar", "istio-proxy"], None);
+        assert_eq!(
+            filter(&["app*"], &["*-sidecar"], false).containers(&p),
+            ["app"]
+        );
+        assert_eq!(
+            filter(&[], &["istio-*"], false).containers(&p),
+            ["app", "app-sidecar"]
+        );
+    }
+
+    #[test]
+    fn only_default_container_uses_the_annotation() {
+        let p = pod(&["app", "istio-proxy"], Some("app"));
+        assert_eq!(filter(&[], &[], true).containers(&p), ["app"]);
+        //the annotation alone does not filter.
+        assert_eq!(
+            filter(&[], &[], false).containers(&p),
+            ["app", "istio-proxy"]
+        );
+        //and the globs still apply to the default container.
+        assert!(filter(&[], &["app"], true).containers(&p).is_empty());
+    }
+
+    #[test]
+    fn default_container_missing_from_the_pod_keeps_every_container() {
+        let p = pod(&["app", "istio-proxy"], Some("renamed"));
+        assert_eq!(
+            filter(&[], &[], true).containers(&p),
+            ["app", "istio-proxy"]
+        );
+        let p = pod(&["app", "istio-proxy"], None);
+        assert_eq!(
+            filter(&[], &[], true).containers(&p),
+            ["app", "istio-proxy"]
+        );
+    }
+
+    #[test]
+    fn invalid_glob_is_an_error() {
+        let config = ConfigFile {
+            include_containers: vec!["app[".to_string()],
+            ..Default::default()
+        };
+        let e = ContainerFilter::from_config(&config).unwrap_err();
+        assert!(e.to_string().contains("Invalid glob app["));
+    }
+}
@@ -0,0 +1,214 @@
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use simplelog::__private::log::warn;
+
+use std::{
+    fmt::Write,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use crate::{
+    context::RunContext,
+    list_files,
+    report::{FindingsDocument, SCHEMA_VERSION},
+};
+
+pub const FINDINGS_REPORT_FILE: &str = "findings_report.txt";
+pub const FINDINGS_JSON_FILE: &str = "findings.json";
+
+pub fn default_findings_patterns() -> Vec<String> {
+    [
+        "ERROR",
+        "FATAL",
+        "OutOfMemoryError",
+        "Too many open files",
+        "connection refused",
+    ]
+    .iter()
+    .map(|p| p.to_string())
+    .collect()
+}
+
+pub fn default_findings_max_matches() -> usize {
+    100
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Finding {
+    pub file: String,
+    pub line_number: usize,
+    pub pattern: String,
+    pub line: String,
+}
+
+//scan a single file line by line, keeping at most `max_matches` findings.
+pub fn scan_file(path: &Path, patterns: &[String], max_matches: usize) -> Result<Vec<Finding>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut findings = vec![];
+    let mut buf = vec![];
+    let mut line_number = 0;
+
+    while findings.len() < max_matches {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        line_number += 1;
+        let line = String::from_utf8_lossy(&buf);
+        if let Some(p) = patterns.iter().find(|p| line.contains(p.as_str())) {
+            findings.push(Finding {
+                file: path.display().to_string(),
+                line_number,
+                pattern: p.clone(),
+                line: line.trim_end_matches(['\r', '\n']).to_string(),
+            });
+        }
+    }
+
+    Ok(findings)
+}
+
+//scan every *.log file below `dir`, file paths in the findings are relative to `dir`.
+//a file that cannot be read is skipped with a warning, the other ones are still scanned.
+pub fn scan_directory(dir: &Path, patterns: &[String], max_matches: usize) -> Result<Vec<Finding>> {
+    let files = list_files(dir)?
+        .into_iter()
+        .filter(|f| f.extension().is_some_and(|e| e == "log"));
+
+    let mut findings = vec![];
+    for f in files {
+        let mut found = match scan_file(&f, patterns, max_matches) {
+            Ok(found) => found,
+            Err(e) => {
+                warn!("Findings scan skipped {}: {}", f.display(), e);
+                continue;
+            }
+        };
+        let relative = f.strip_prefix(dir).unwrap_or(&f).display().to_string();
+        found.iter_mut().for_each(|fi| fi.file = relative.clone());
+        findings.append(&mut found);
+    }
+    Ok(findings)
+}
+
+//written through ctx: atomically, and listed in manifest.json.
+pub fn write_findings(ctx: &RunContext, folder: &str, findings: &[Finding]) -> Result<()> {
+    let mut report = String::new();
+    writeln!(report, "Total findings: {}", findings.len())?;
+    let mut current = "";
+    for f in findings {
+        if f.file != current {
+            writeln!(report, "\n== {} ==", f.file)?;
+            current = &f.file;
+        }
+        writeln!(report, "{}:[{}] {}", f.line_number, f.pattern, f.line)?;
+    }
+    let er = anyhow!("Empty {}.", FINDINGS_REPORT_FILE);
+    ctx.write_file(folder, report.as_bytes(), FINDINGS_REPORT_FILE, er)?;
+
+    let document = FindingsDocument {
+        schema_version: SCHEMA_VERSION.to_string(),
+        findings: findings.to_vec(),
+    };
+    let json = serde_json::to_string_pretty(&document)?;
+    let er = anyhow!("Empty {}.", FINDINGS_JSON_FILE);
+    ctx.write_file(folder, json.as_bytes(), FINDINGS_JSON_FILE, er)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, TempDir};
+
+    use std::fs;
+
+    fn copy_fixtures(dir: &TempDir) {
+        for (name, to) in [
+            ("app.log", "pods/app.log"),
+            ("crlf.log", "apps/kafka/crlf.log"),
+            ("notes.txt", "pods/notes.txt"),
+        ] {
+            let content = fs::read(fixture(&format!("findings/{}", name))).unwrap();
+            dir.write(to, &content);
+        }
+    }
+
+    #[test]
+    fn scan_file_reports_the_first_matching_pattern_of_each_line() {
+        let findings = scan_file(
+            &fixture("findings/app.log"),
+            &default_findings_patterns(),
+            100,
+        )
+        .unwrap();
+        let found = findings
+            .iter()
+            .map(|f| (f.line_number, f.pattern.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![(3, "ERROR"), (5, "ERROR"), (7, "FATAL")]);
+        assert_eq!(
+            findings[0].line,
+            "2024-03-01 10:00:02 ERROR Failed to register with zookeeper: connection refused"
+        );
+    }
+
+    #[test]
+    fn scan_file_stops_at_max_matches() {
+        let findings = scan_file(
+            &fixture("findings/app.log"),
+            &default_findings_patterns(),
+            2,
+        )
+        .unwrap();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[1].line_number, 5);
+    }
+
+    #[test]
+    fn scan_file_trims_crlf_line_endings() {
+        let patterns = vec!["Too many open files".to_string()];
+        let findings = scan_file(&fixture("findings/crlf.log"), &patterns, 10).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].line_number, 2);
+        assert!(findings[0].line.ends_with("Too many open files"));
+    }
+
+    #[test]
+    fn scan_directory_scans_logs_only_with_relative_paths() {
+        let dir = TempDir::new();
+        copy_fixtures(&dir);
+        let findings = scan_directory(dir.path(), &default_findings_patterns(), 100).unwrap();
+        let mut files = findings.iter().map(|f| f.file.as_str()).collect::<Vec<_>>();
+        files.dedup();
+        assert_eq!(files, vec!["apps/kafka/crlf.log", "pods/app.log"]);
+        assert_eq!(findings.len(), 4);
+    }
+
+    #[test]
+    fn scan_directory_skips_unreadable_files() {
+        let dir = TempDir::new();
+        copy_fixtures(&dir);
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.path().join("gone"), dir.path().join("pods/broken.log"))
+            .unwrap();
+        let findings = scan_directory(dir.path(), &default_findings_patterns(), 100).unwrap();
+        assert_eq!(findings.len(), 4);
+    }
+
+    #[test]
+    fn write_findings_writes_the_report_and_the_json() {
+        let dir = TempDir::new();
+        let findings = scan_file(&fixture("findings/app.log"), &["FATAL".to_string()], 10).unwrap();
+        let ctx = RunContext::new(vec![]);
+        write_findings(&ctx, &dir.folder(), &findings).unwrap();
+        let report = dir.read(FINDINGS_REPORT_FILE);
+        assert!(report.starts_with("Total findings: 1\n"));
+        assert!(report.contains("7:[FATAL] 2024-03-01 10:00:09 FATAL Broker shutting down"));
+        let document: serde_json::Value =
+            serde_json::from_str(&dir.read(FINDINGS_JSON_FILE)).unwrap();
+        assert_eq!(document["findings"][0]["line_number"], 7);
+    }
+}
@@ -0,0 +1,371 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use k8s_openapi::api::core::v1::{Node, Pod, Secret};
+use openssl::{asn1::Asn1Time, x509::X509};
+
+use std::collections::BTreeMap;
+
+use crate::access::PodAccess;
+
+pub const HEALTH_SUMMARY_FILE: &str = "health_summary.txt";
+pub const UNKNOWN: &str = "unknown";
+//certificates ending within this many days count as expiring soon.
+pub const CERTIFICATE_EXPIRY_DAYS: i64 = 30;
+const TLS_SECRET_TYPE: &str = "kubernetes.io/tls";
+
+//what the collectors found, None when the corresponding collector did not run or failed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HealthInputs {
+    //(not ready, total).
+    pub nodes_not_ready: Option<(usize, usize)>,
+    pub pods_not_running: Option<BTreeMap<String, usize>>,
+    pub containers_restarted_last_hour: Option<usize>,
+    pub elasticsearch_status: Option<String>,
+    pub kafka_under_replicated: Option<usize>,
+    //FAILED connectors and tasks of kafka connect.
+    pub kafka_connect_failed: Option<usize>,
+    pub hdfs_missing_blocks: Option<u64>,
+    pub prometheus_targets_down: Option<usize>,
+    //tls.crt of the kubernetes.io/tls secrets of the configured namespaces.
+    pub certificates_expiring: Option<usize>,
+    //"<call> <median>ms" above apiserver_latency_threshold_ms.
+    pub apiserver_slow_calls: Option<Vec<String>>,
+    //"<source> <offset>s" above clock_skew_threshold_seconds.
+    pub clock_skew: Option<Vec<String>>,
+    //"<ns>/<pod> <setting> <value> < <minimum>" of the app pods.
+    pub limits_below_minimum: Option<Vec<String>>,
+}
+
+pub fn nodes_not_ready(nodes: &[Node]) -> (usize, usize) {
+    let not_ready = nodes
+        .iter()
+        .filter(|n| {
+            !n.status
+                .as_ref()
+                .and_then(|s| s.conditions.as_ref())
+                .into_iter()
+                .flatten()
+                .any(|c| c.type_ == "Ready" && c.status == "True")
+        })
+        .count();
+    (not_ready, nodes.len())
+}
+
+//pods neither Running nor Succeeded, by namespace.
+pub fn pods_not_running<'a>(pods: impl Iterator<Item = &'a Pod>) -> BTreeMap<String, usize> {
+    let mut out = BTreeMap::new();
+    for p in pods {
+        let phase = p
+            .status
+            .as_ref()
+            .and_then(|s| s.phase.clone())
+            .unwrap_or_default();
+        if phase != "Running" && phase != "Succeeded" {
+            *out.entry(p.metadata.namespace.clone().unwrap_or_default())
+                .or_default() += 1;
+        }
+    }
+    out
+}
+
+//containers whose last termination ended after now - 1h, the restart count itself has no time.
+pub fn containers_restarted_since<'a>(
+    pods: impl Iterator<Item = &'a Pod>,
+    now: DateTime<Utc>,
+) -> usize {
+    let since = now - Duration::hours(1);
+    pods.filter_map(|p| p.status.as_ref())
+        .flat_map(|s| s.container_statuses.iter().flatten())
+        .filter(|c| {
+            c.last_state
+                .as_ref()
+                .and_then(|s| s.terminated.as_ref())
+                .and_then(|t| t.finished_at.as_ref())
+                .is_some_and(|f| f.0 > since)
+        })
+        .count()
+}
+
+//"Missing blocks: N" of hdfs dfsadmin -report.
+pub fn hdfs_missing_blocks(report: &str) -> Option<u64> {
+    report
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("Missing blocks:"))
+        .and_then(|n| n.trim().parse().ok())
+}
+
+//status of _cluster/health.
+pub fn elasticsearch_status(health: &str) -> Option<String> {
+    let v: serde_json::Value = serde_json::from_str(health).ok()?;
+    v.get("status")?.as_str().map(|s| s.to_string())
+}
+
+//kubernetes.io/tls secrets whose certificate (the first of tls.crt) ends before now + days,
+//the ones without a readable certificate are left out.
+pub fn certificates_expiring(secrets: &[Secret], now: DateTime<Utc>, days: i64) -> usize {
+    let Ok(limit) = Asn1Time::from_unix((now + Duration::days(days)).timestamp()) else {
+        return 0;
+    };
+    secrets
+        .iter()
+        .filter(|s| s.type_.as_deref() == Some(TLS_SECRET_TYPE))
+        .filter_map(|s| s.data.as_ref()?.get("tls.crt"))
+        .filter_map(|crt| X509::from_pem(&crt.0).ok())
+        .filter(|c| c.not_after() < limit)
+        .count()
+}
+
+//over the tls secrets of every namespace of access, an error when one cannot be listed.
+pub async fn count_expiring_certificates<A: PodAccess>(
+    access: &[A],
+    now: DateTime<Utc>,
+) -> Result<usize> {
+    let mut expiring = 0;
+    for a in access {
+        expiring += certificates_expiring(&a.list_secrets("").await?, now, CERTIFICATE_EXPIRY_DAYS);
+    }
+    Ok(expiring)
+}
+
+fn or_unknown<T>(value: &Option<T>, show: impl Fn(&T) -> String) -> String {
+    value
+        .as_ref()
+        .map(show)
+        .unwrap_or_else(|| UNKNOWN.to_string())
+}
+
+pub fn summary_lines(h: &HealthInputs) -> Vec<String> {
+    vec![
+        format!(
+            "nodes NotReady: {}",
+            or_unknown(&h.nodes_not_ready, |(n, t)| format!("{} of {}", n, t))
+        ),
+        format!(
+            "pods not Running: {}",
+            or_unknown(&h.pods_not_running, |m| {
+                if m.is_empty() {
+                    "0".to_string()
+                } else {
+                    m.iter()
+                        .map(|(ns, c)| format!("{}={}", ns, c))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                }
+            })
+        ),
+        format!(
+            "containers restarted in the last hour: {}",
+            or_unknown(&h.containers_restarted_last_hour, |c| c.to_string())
+        ),
+        format!(
+            "elasticsearch cluster: {}",
+            or_unknown(&h.elasticsearch_status, |s| s.clone())
+        ),
+        format!(
+            "kafka under-replicated partitions: {}",
+            or_unknown(&h.kafka_under_replicated, |c| c.to_string())
+        ),
+        format!(
+            "kafka connect failed connectors and tasks: {}",
+            or_unknown(&h.kafka_connect_failed, |c| c.to_string())
+        ),
+        format!(
+            "hdfs missing blocks: {}",
+            or_unknown(&h.hdfs_missing_blocks, |c| c.to_string())
+        ),
+        format!(
+            "prometheus targets down: {}",
+            or_unknown(&h.prometheus_targets_down, |c| c.to_string())
+        ),
+        format!(
+            "certificates expiring soon: {}",
+            or_unknown(&h.certificates_expiring, |c| c.to_string())
+        ),
+        format!(
+            "api server slow calls: {}",
+            or_unknown(&h.apiserver_slow_calls, |s| {
+                if s.is_empty() {
+                    "0".to_string()
+                } else {
+                    s.join(", ")
+                }
+            })
+        ),
+        format!(
+            "clock skew: {}",
+            or_unknown(&h.clock_skew, |s| {
+                if s.is_empty() {
+                    "0".to_string()
+                } else {
+                    s.join(", ")
+                }
+            })
+        ),
+        format!(
+            "limits below the recommended minimums: {}",
+            or_unknown(&h.limits_below_minimum, |s| {
+                if s.is_empty() {
+                    "0".to_string()
+                } else {
+                    s.join(", ")
+                }
+            })
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::ByteString;
+    use openssl::{
+        hash::MessageDigest,
+        pkey::PKey,
+        rsa::Rsa,
+        x509::{X509Builder, X509NameBuilder},
+    };
+
+    fn now() -> DateTime<Utc> {
+        "2026-10-17T10:00:00Z".parse().unwrap()
+    }
+
+    fn pod(json: serde_json::Value) -> Pod {
+        serde_json::from_value(json).unwrap()
+    }
+
+    //a self signed certificate ending days after now().
+    fn certificate(days: i64) -> Vec<u8> {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "test").unwrap();
+        let name = name.build();
+        let mut b = X509Builder::new().unwrap();
+        b.set_subject_name(&name).unwrap();
+        b.set_issuer_name(&name).unwrap();
+        b.set_pubkey(&key).unwrap();
+        let start = Asn1Time::from_unix((now() - Duration::days(365)).timestamp()).unwrap();
+        let end = Asn1Time::from_unix((now() + Duration::days(days)).timestamp()).unwrap();
+        b.set_not_before(&start).unwrap();
+        b.set_not_after(&end).unwrap();
+        b.sign(&key, MessageDigest::sha256()).unwrap();
+        b.build().to_pem().unwrap()
+    }
+
+    fn secret(type_: &str, crt: &[u8]) -> Secret {
+        Secret {
+            type_: Some(type_.to_string()),
+            data: Some(BTreeMap::from([(
+                "tls.crt".to_string(),
+                ByteString(crt.to_vec()),
+            )])),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn certificates_ending_within_the_window_are_expiring() {
+        let secrets = [
+            secret(TLS_SECRET_TYPE, &certificate(5)),
+            secret(TLS_SECRET_TYPE, &certificate(-2)),
+            secret(TLS_SECRET_TYPE, &certificate(90)),
+            //not a tls secret, not a certificate.
+            secret("Opaque", &certificate(5)),
+            secret(TLS_SECRET_TYPE, b"not a pem"),
+            Secret::default(),
+        ];
+        assert_eq!(
+            certificates_expiring(&secrets, now(), CERTIFICATE_EXPIRY_DAYS),
+            2
+        );
+        assert_eq!(certificates_expiring(&secrets, now(), 100), 3);
+    }
+
+    #[test]
+    fn nodes_without_a_true_ready_condition_are_not_ready() {
+        let node = |status: &str| -> Node {
+            serde_json::from_value(serde_json::json!({
+                "status": {"conditions": [{"type": "Ready", "status": status}]}
+            }))
+            .unwrap()
+        };
+        let nodes = [
+            node("True"),
+            node("False"),
+            node("Unknown"),
+            Node::default(),
+        ];
+        assert_eq!(nodes_not_ready(&nodes), (3, 4));
+        assert_eq!(nodes_not_ready(&[]), (0, 0));
+    }
+
+    #[test]
+    fn pods_not_running_by_namespace() {
+        let pods = [
+            pod(
+                serde_json::json!({"metadata": {"namespace": "a"}, "status": {"phase": "Running"}}),
+            ),
+            pod(
+                serde_json::json!({"metadata": {"namespace": "a"}, "status": {"phase": "Pending"}}),
+            ),
+            pod(
+                serde_json::json!({"metadata": {"namespace": "b"}, "status": {"phase": "Succeeded"}}),
+            ),
+            pod(serde_json::json!({"metadata": {"namespace": "b"}, "status": {"phase": "Failed"}})),
+            pod(serde_json::json!({"metadata": {"namespace": "b"}})),
+        ];
+        assert_eq!(
+            pods_not_running(pods.iter()),
+            BTreeMap::from([("a".to_string(), 1), ("b".to_string(), 2)])
+        );
+    }
+
+    #[test]
+    fn containers_restarted_in_the_last_hour() {
+        let terminated = |finished: &str| {
+            serde_json::json!({
+                "name": "c", "image": "i", "imageID": "", "ready": true, "restartCount": 1,
+                "lastState": {"terminated": {"exitCode": 1, "finishedAt": finished}}
+            })
+        };
+        let p = pod(serde_json::json!({"status": {"containerStatuses": [
+            terminated("2026-10-17T09:30:00Z"),
+            terminated("2026-10-17T08:30:00Z"),
+        ]}}));
+        assert_eq!(containers_restarted_since([&p].into_iter(), now()), 1);
+    }
+
+    #[test]
+    fn app_outputs_parsers() {
+        let report = "Configured Capacity: 1\nMissing blocks: 7\nMissing blocks (with replication factor 1): 0\n";
+        assert_eq!(hdfs_missing_blocks(report), Some(7));
+        assert_eq!(hdfs_missing_blocks("Safe mode is ON"), None);
+        assert_eq!(
+            elasticsearch_status(r#"{"cluster_name": "es", "status": "yellow"}"#),
+            Some("yellow".to_string())
+        );
+        assert_eq!(elasticsearch_status("<html>"), None);
+    }
+
+    #[test]
+    fn collectors_that_did_not_run_are_unknown() {
+        let lines = summary_lines(&HealthInputs::default());
+        assert_eq!(lines.len(), 12);
+        assert!(
+            lines.iter().all(|l| l.ends_with(": unknown")),
+            "{:?}",
+            lines
+        );
+        let h = HealthInputs {
+            nodes_not_ready: Some((1, 3)),
+            pods_not_running: Some(BTreeMap::new()),
+            certificates_expiring: Some(2),
+            clock_skew: Some(vec!["node-a 12s".to_string()]),
+            ..Default::default()
+        };
+        let lines = summary_lines(&h);
+        assert_eq!(lines[0], "nodes NotReady: 1 of 3");
+        assert_eq!(lines[1], "pods not Running: 0");
+        assert_eq!(lines[8], "certificates expiring soon: 2");
+        assert_eq!(lines[10], "clock skew: node-a 12s");
+    }
+}
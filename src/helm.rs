@@ -0,0 +1,291 @@
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use flate2::read::GzDecoder;
+use serde_yaml::{Mapping, Value};
+
+use k8s_openapi::api::core::v1::Secret;
+
+use std::{collections::BTreeMap, io::Read};
+
+use crate::access::PodAccess;
+
+pub const REDACTED: &str = "***REDACTED***";
+
+//keys whose values never leave the cluster, matched case insensitively on the key name.
+const SECRET_KEY_PARTS: [&str; 7] = [
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "apikey",
+    "credential",
+    "privatekey",
+];
+
+pub fn secret_key_name(key: &str) -> bool {
+    let key = key.to_lowercase().replace(['_', '-', '.'], "");
+    SECRET_KEY_PARTS.iter().any(|p| key.contains(p))
+}
+
+fn secret_key(key: &Value) -> bool {
+    key.as_str().is_some_and(secret_key_name)
+}
+
+//replace the scalar values of secret looking keys, at any depth. Like values_drift, every scalar
+//below such a key is redacted, the items of its lists and the values of its nested maps included.
+pub fn scrub_secrets(value: &mut Value) {
+    match value {
+        Value::Mapping(m) => m.iter_mut().for_each(|(k, v)| {
+            if secret_key(k) {
+                redact(v)
+            } else {
+                scrub_secrets(v)
+            }
+        }),
+        Value::Sequence(s) => s.iter_mut().for_each(scrub_secrets),
+        _ => {}
+    }
+}
+
+//every scalar of the value, the keys of its maps are kept.
+fn redact(value: &mut Value) {
+    match value {
+        Value::Mapping(m) => m.iter_mut().for_each(|(_, v)| redact(v)),
+        Value::Sequence(s) => s.iter_mut().for_each(redact),
+        Value::Tagged(t) => redact(&mut t.value),
+        Value::Null => {}
+        v => *v = Value::String(REDACTED.to_string()),
+    }
+}
+
+//the keys of `values` that differ from `defaults`, maps are compared key by key, lists as a whole.
+pub fn values_diff(values: &Value, defaults: &Value) -> Option<Value> {
+    match (values, defaults) {
+        (Value::Mapping(v), Value::Mapping(d)) => {
+            let diff = v
+                .iter()
+                .filter_map(|(k, vv)| match d.get(k) {
+                    Some(dv) => values_diff(vv, dv).map(|x| (k.clone(), x)),
+                    None => Some((k.clone(), vv.clone())),
+                })
+                .collect::<Mapping>();
+            (!diff.is_empty()).then_some(Value::Mapping(diff))
+        }
+        (v, d) if v == d => None,
+        (v, _) => Some(v.clone()),
+    }
+}
+
+//a release secret (sh.helm.release.v1.<name>.v<revision>) holds base64 of the gzipped release json,
+//itself base64 encoded by the secret.
+fn decode_release(secret: &Secret, release: &str) -> Result<serde_json::Value> {
+    let data = secret
+        .data
+        .as_ref()
+        .and_then(|d| d.get("release"))
+        .ok_or_else(|| anyhow!("Release secret of {} without release data.", release))?;
+    let gz = base64::engine::general_purpose::STANDARD.decode(&data.0)?;
+    let mut json = String::new();
+    GzDecoder::new(gz.as_slice()).read_to_string(&mut json)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+fn json_to_yaml(value: Option<&serde_json::Value>) -> Result<Value> {
+    Ok(serde_yaml::to_value(value.cloned().unwrap_or_default())?)
+}
+
+//the chart default values of the deployed revision.
+pub async fn release_default_values<A: PodAccess>(access: &A, release: &str) -> Result<Value> {
+    let secrets = access
+        .list_secrets(&format!("owner=helm,name={},status=deployed", release))
+        .await?;
+    let secret = secrets
+        .first()
+        .ok_or_else(|| anyhow!("No deployed release secret for {}.", release))?;
+    json_to_yaml(decode_release(secret, release)?.pointer("/chart/values"))
+}
+
+//the user supplied values (helm get values without --all) of the two most recent revisions, newest first.
+pub async fn recent_user_values<A: PodAccess>(
+    access: &A,
+    release: &str,
+) -> Result<Vec<(i64, Value)>> {
+    let mut revisions = vec![];
+    for secret in access
+        .list_secrets(&format!("owner=helm,name={}", release))
+        .await?
+    {
+        let r = decode_release(&secret, release)?;
+        let version = r
+            .get("version")
+            .and_then(|v| v.as_i64())
+            .unwrap_or_default();
+        revisions.push((version, json_to_yaml(r.get("config"))?));
+    }
+    revisions.sort_by_key(|(v, _)| std::cmp::Reverse(*v));
+    revisions.truncate(2);
+    Ok(revisions)
+}
+
+//"a.b.c" -> scalar or list, a null or empty map counts as absent.
+fn flatten(value: &Value, prefix: &str, out: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Mapping(m) => m.iter().for_each(|(k, v)| {
+            let key = match k {
+                Value::String(s) => s.clone(),
+                k => serde_yaml::to_string(k)
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+            };
+            let path = if prefix.is_empty() {
+                key
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+            flatten(v, &path, out)
+        }),
+        Value::Null => {}
+        v => {
+            out.insert(prefix.to_string(), v.clone());
+        }
+    }
+}
+
+fn secret_path(path: &str) -> bool {
+    path.split('.')
+        .any(|k| secret_key(&Value::String(k.to_string())))
+}
+
+fn shown(path: &str, value: &Value) -> Value {
+    if secret_path(path) {
+        Value::String(REDACTED.to_string())
+    } else {
+        value.clone()
+    }
+}
+
+//the user supplied keys added, removed or changed from the previous revision to the current one,
+//values under secret looking keys are redacted.
+pub fn values_drift(current: &Value, previous: &Value) -> Value {
+    let (mut now, mut before) = (BTreeMap::new(), BTreeMap::new());
+    flatten(current, "", &mut now);
+    flatten(previous, "", &mut before);
+    let (mut added, mut removed, mut changed) = (Mapping::new(), Mapping::new(), Mapping::new());
+    for (path, v) in &now {
+        match before.get(path) {
+            None => {
+                added.insert(Value::String(path.clone()), shown(path, v));
+            }
+            Some(b) if b != v => {
+                let mut c = Mapping::new();
+                c.insert(Value::String("from".to_string()), shown(path, b));
+                c.insert(Value::String("to".to_string()), shown(path, v));
+                changed.insert(Value::String(path.clone()), Value::Mapping(c));
+            }
+            _ => {}
+        }
+    }
+    for (path, b) in &before {
+        if !now.contains_key(path) {
+            removed.insert(Value::String(path.clone()), shown(path, b));
+        }
+    }
+    let mut drift = Mapping::new();
+    for (name, m) in [("added", added), ("removed", removed), ("changed", changed)] {
+        drift.insert(Value::String(name.to_string()), Value::Mapping(m));
+    }
+    Value::Mapping(drift)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn scrub_secrets_redacts_the_scalars_of_secret_keys() {
+        let mut values = yaml("db:\n  host: db\n  password: hunter2\n  port: 5432\napiKey: 42\n");
+        scrub_secrets(&mut values);
+        assert_eq!(
+            values,
+            yaml("db:\n  host: db\n  password: '***REDACTED***'\n  port: 5432\napiKey: '***REDACTED***'\n")
+        );
+    }
+
+    #[test]
+    fn scrub_secrets_redacts_nested_maps_and_lists_below_a_secret_key() {
+        let mut values = yaml(
+            "secrets:\n  admin:\n    user: admin\n    pass: hunter2\n  tokens:\n    - abc\n    - def\n  empty: null\nusers:\n  - name: a\n    token: t1\n  - name: b\n",
+        );
+        scrub_secrets(&mut values);
+        assert_eq!(
+            values,
+            yaml(
+                "secrets:\n  admin:\n    user: '***REDACTED***'\n    pass: '***REDACTED***'\n  tokens:\n    - '***REDACTED***'\n    - '***REDACTED***'\n  empty: null\nusers:\n  - name: a\n    token: '***REDACTED***'\n  - name: b\n",
+            )
+        );
+    }
+
+    #[test]
+    fn values_drift_lists_added_removed_and_changed_keys() {
+        let previous =
+            yaml("replicas: 1\nimage:\n  tag: '1.0'\n  pullPolicy: Always\nlegacy: true\n");
+        let current = yaml("replicas: 3\nimage:\n  tag: '1.0'\n  pullPolicy: Always\nresources:\n  limits:\n    cpu: 2\n");
+        assert_eq!(
+            values_drift(&current, &previous),
+            yaml(
+                "added:\n  resources.limits.cpu: 2\nremoved:\n  legacy: true\nchanged:\n  replicas:\n    from: 1\n    to: 3\n"
+            )
+        );
+    }
+
+    #[test]
+    fn values_drift_compares_lists_as_a_whole_and_ignores_nulls() {
+        let previous = yaml("hosts: [a, b]\nextra: null\nempty: {}\n");
+        let current = yaml("hosts: [a, c]\n");
+        assert_eq!(
+            values_drift(&current, &previous),
+            yaml("added: {}\nremoved: {}\nchanged:\n  hosts:\n    from: [a, b]\n    to: [a, c]\n")
+        );
+        let same = yaml("a: {b: 1}\n");
+        assert_eq!(
+            values_drift(&same, &same),
+            yaml("added: {}\nremoved: {}\nchanged: {}\n")
+        );
+    }
+
+    #[test]
+    fn values_drift_redacts_secret_paths() {
+        let previous = yaml("db:\n  password: old\nauth:\n  user: a\n");
+        let current = yaml("db:\n  password: new\napiToken: t\n");
+        let drift = values_drift(&current, &previous);
+        let redacted = Value::String(REDACTED.to_string());
+        assert_eq!(drift["changed"]["db.password"]["from"], redacted);
+        assert_eq!(drift["changed"]["db.password"]["to"], redacted);
+        assert_eq!(drift["added"]["apiToken"], redacted);
+        assert_eq!(
+            drift["removed"]["auth.user"],
+            Value::String("a".to_string())
+        );
+    }
+
+    #[test]
+    fn scrub_secrets_agrees_with_the_drift_redaction() {
+        let mut values = yaml("auth:\n  credentials:\n    - user: a\n      key: k\n");
+        let drift = values_drift(&values, &Value::Null);
+        scrub_secrets(&mut values);
+        assert!(!serde_yaml::to_string(&values).unwrap().contains(": k"));
+        assert_eq!(
+            drift["added"]["auth.credentials"],
+            Value::String(REDACTED.to_string())
+        );
+        assert_eq!(
+            values["auth"]["credentials"][0]["key"],
+            Value::String(REDACTED.to_string())
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use k8s_openapi::api::core::v1::{ContainerStatus, Event};
+
+use crate::PodInfo;
+
+pub const IMAGE_PULL_FAILURES_FILE: &str = "image_pull_failures.txt";
+
+const IMAGE_PULL_REASONS: [&str; 2] = ["ImagePullBackOff", "ErrImagePull"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImagePullFailure {
+    pub namespace: String,
+    pub pod: String,
+    pub container: String,
+    pub image: String,
+    pub node: String,
+    pub reason: String,
+}
+
+//the waiting reason when the container is stuck pulling its image.
+pub fn image_pull_reason(status: &ContainerStatus) -> Option<String> {
+    status
+        .state
+        .as_ref()
+        .and_then(|s| s.waiting.as_ref())
+        .and_then(|w| w.reason.clone())
+        .filter(|r| IMAGE_PULL_REASONS.contains(&r.as_str()))
+}
+
+//containers (init containers included) of the pod that cannot pull their image.
+pub fn image_pull_failures<A>(pod: &PodInfo<A>) -> Vec<ImagePullFailure> {
+    let Some(status) = pod.pod.status.as_ref() else {
+        return vec![];
+    };
+    status
+        .init_container_statuses
+        .iter()
+        .flatten()
+        .chain(status.container_statuses.iter().flatten())
+        .filter_map(|c| {
+            image_pull_reason(c).map(|reason| ImagePullFailure {
+                namespace: pod.namespace.clone(),
+                pod: pod.name.clone(),
+                container: c.name.clone(),
+                image: c.image.clone(),
+                node: pod.node_name.clone(),
+                reason,
+            })
+        })
+        .collect()
+}
+
+//one entry per failing container with the event messages of its pod, the registry error lives there.
+pub fn render_failures(failures: &[ImagePullFailure], events: &[Event]) -> String {
+    let mut out = String::new();
+    for f in failures {
+        out.push_str(&format!(
+            "{}/{} container {}\n  image: {}\n  node: {}\n  reason: {}\n  events:\n",
+            f.namespace, f.pod, f.container, f.image, f.node, f.reason
+        ));
+        let messages = events
+            .iter()
+            .filter(|e| {
+                e.involved_object.name.as_deref() == Some(f.pod.as_str())
+                    && e.involved_object.namespace.as_deref() == Some(f.namespace.as_str())
+            })
+            .filter(|e| {
+                e.involved_object
+                    .field_path
+                    .as_deref()
+                    .is_none_or(|p| p.contains(&format!("{{{}}}", f.container)))
+            })
+            .filter_map(|e| e.message.clone())
+            .collect::<Vec<String>>();
+        if messages.is_empty() {
+            out.push_str("    (no event found)\n");
+        }
+        messages
+            .iter()
+            .for_each(|m| out.push_str(&format!("    {}\n", m)));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, pod_info};
+
+    fn pod() -> PodInfo<()> {
+        let pod = std::fs::read_to_string(fixture("image_pull/pod.json")).unwrap();
+        pod_info(serde_json::from_str(&pod).unwrap())
+    }
+
+    fn events() -> Vec<Event> {
+        serde_json::from_str(&std::fs::read_to_string(fixture("image_pull/events.json")).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn only_the_pull_reasons_count() {
+        let pod = pod();
+        let statuses = pod.pod.status.as_ref().unwrap();
+        let reasons = statuses
+            .container_statuses
+            .iter()
+            .flatten()
+            .map(image_pull_reason)
+            .collect::<Vec<_>>();
+        assert_eq!(reasons, [Some("ImagePullBackOff".to_string()), None, None]);
+    }
+
+    #[test]
+    fn init_containers_come_first() {
+        let failures = image_pull_failures(&pod());
+        let found = failures
+            .iter()
+            .map(|f| (f.container.as_str(), f.reason.as_str(), f.image.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                (
+                    "migrate",
+                    "ErrImagePull",
+                    "registry.example.com/shop/migrate:1.2"
+                ),
+                (
+                    "api",
+                    "ImagePullBackOff",
+                    "registry.example.com/shop/api:2.0"
+                ),
+            ]
+        );
+        assert!(failures
+            .iter()
+            .all(|f| f.namespace == "shop" && f.pod == "api-0" && f.node == "node-a"));
+    }
+
+    #[test]
+    fn pod_without_status_has_no_failure() {
+        let mut pod = pod();
+        pod.pod.status = None;
+        assert!(image_pull_failures(&pod).is_empty());
+    }
+
+    #[test]
+    fn report_keeps_the_events_of_each_container() {
+        let report = render_failures(&image_pull_failures(&pod()), &events());
+        let (migrate, api) = report.split_once("\n\n").unwrap();
+        assert!(migrate.starts_with("shop/api-0 container migrate\n"));
+        assert!(migrate.contains("unauthorized"));
+        assert!(!migrate.contains("manifest unknown"));
+        assert!(api.contains("manifest unknown"));
+        assert!(api.contains("Successfully assigned"));
+        assert!(!report.contains("another namespace"));
+    }
+
+    #[test]
+    fn report_says_when_no_event_is_left() {
+        let report = render_failures(&image_pull_failures(&pod()), &[]);
+        assert_eq!(report.matches("(no event found)").count(), 2);
+    }
+}
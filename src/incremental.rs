@@ -0,0 +1,185 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::PodInfo;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContainerState {
+    pub last_collected: DateTime<Utc>,
+    pub restart_count: i32,
+    pub started_at: Option<String>,
+}
+
+//last successful collection per "namespace/pod/container".
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IncrementalState {
+    pub containers: BTreeMap<String, ContainerState>,
+}
+
+pub fn state_key(namespace: &str, pod: &str, container: &str) -> String {
+    format!("{}/{}/{}", namespace, pod, container)
+}
+
+//state file per context, stored next to the archives.
+pub fn state_path(folder: &str, context_name: &str) -> String {
+    format!("{}/antlog_state_{}.json", folder, context_name)
+}
+
+//a missing state file means this is the first incremental run.
+pub fn read_state(path: &str) -> Result<IncrementalState> {
+    if !Path::new(path).exists() {
+        return Ok(IncrementalState::default());
+    }
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+pub fn write_state(path: &str, state: &IncrementalState) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(state)?)?;
+    Ok(())
+}
+
+//restart count and start time of a container as seen now, stamped with `now`.
+pub fn current_container_state<A>(
+    pod: &PodInfo<A>,
+    container: &str,
+    now: DateTime<Utc>,
+) -> ContainerState {
+    let status = pod.container_status(container);
+    let started_at = status
+        .and_then(|s| s.state.as_ref())
+        .and_then(|s| s.running.as_ref())
+        .and_then(|r| r.started_at.as_ref())
+        .or_else(|| pod.pod.status.as_ref().and_then(|s| s.start_time.as_ref()))
+        .map(|t| t.0.to_rfc3339());
+    ContainerState {
+        last_collected: now,
+        restart_count: status.map(|s| s.restart_count).unwrap_or(0),
+        started_at,
+    }
+}
+
+pub fn restarted(previous: &ContainerState, current: &ContainerState) -> bool {
+    previous.restart_count != current.restart_count || previous.started_at != current.started_at
+}
+
+//seconds of logs to request, None means the full log must be collected.
+pub fn since_seconds(
+    previous: Option<&ContainerState>,
+    current: &ContainerState,
+    now: DateTime<Utc>,
+) -> Option<i64> {
+    let previous = previous?;
+    if restarted(previous, current) {
+        return None;
+    }
+    let elapsed = (now - previous.last_collected).num_seconds();
+    if elapsed < 0 {
+        return None;
+    }
+    //one extra second so the line written right at the previous run's end is not lost.
+    Some(elapsed + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{pod_info, TempDir};
+
+    use chrono::TimeZone;
+
+    fn pod(restart_count: i32, started_at: Option<&str>) -> PodInfo<()> {
+        let mut state = serde_json::json!({ "waiting": { "reason": "CrashLoopBackOff" } });
+        if let Some(s) = started_at {
+            state = serde_json::json!({ "running": { "startedAt": s } });
+        }
+        pod_info(serde_json::json!({
+            "metadata": { "name": "kafka-0", "namespace": "kafka" },
+            "spec": { "containers": [{ "name": "kafka" }] },
+            "status": {
+                "startTime": "2024-03-01T08:00:00Z",
+                "containerStatuses": [{
+                    "name": "kafka", "image": "kafka", "imageID": "", "ready": true,
+                    "restartCount": restart_count, "state": state
+                }]
+            }
+        }))
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn state_round_trips_through_the_file() {
+        let dir = TempDir::new();
+        let path = state_path(&dir.folder(), "prod");
+        assert_eq!(read_state(&path).unwrap(), IncrementalState::default());
+        let mut state = IncrementalState::default();
+        state.containers.insert(
+            state_key("kafka", "kafka-0", "kafka"),
+            current_container_state(&pod(2, Some("2024-03-01T09:00:00Z")), "kafka", at(10, 0)),
+        );
+        write_state(&path, &state).unwrap();
+        assert!(path.ends_with("/antlog_state_prod.json"));
+        assert_eq!(read_state(&path).unwrap(), state);
+        let json: serde_json::Value =
+            serde_json::from_str(&dir.read("antlog_state_prod.json")).unwrap();
+        let c = &json["containers"]["kafka/kafka-0/kafka"];
+        assert_eq!(c["last_collected"], "2024-03-01T10:00:00Z");
+        assert_eq!(c["restart_count"], 2);
+        assert_eq!(c["started_at"], "2024-03-01T09:00:00+00:00");
+    }
+
+    #[test]
+    fn container_start_falls_back_to_the_pod_start() {
+        let running =
+            current_container_state(&pod(0, Some("2024-03-01T09:00:00Z")), "kafka", at(10, 0));
+        assert_eq!(
+            running.started_at.as_deref(),
+            Some("2024-03-01T09:00:00+00:00")
+        );
+        let waiting = current_container_state(&pod(3, None), "kafka", at(10, 0));
+        assert_eq!(
+            waiting.started_at.as_deref(),
+            Some("2024-03-01T08:00:00+00:00")
+        );
+        assert_eq!(waiting.restart_count, 3);
+        let unknown = current_container_state(&pod(3, None), "zookeeper", at(10, 0));
+        assert_eq!(unknown.restart_count, 0);
+    }
+
+    #[test]
+    fn since_seconds_covers_the_time_since_the_last_run() {
+        let previous =
+            current_container_state(&pod(1, Some("2024-03-01T09:00:00Z")), "kafka", at(10, 0));
+        let current =
+            current_container_state(&pod(1, Some("2024-03-01T09:00:00Z")), "kafka", at(11, 30));
+        assert!(!restarted(&previous, &current));
+        assert_eq!(
+            since_seconds(Some(&previous), &current, at(11, 30)),
+            Some(5401)
+        );
+        //first run for the container.
+        assert_eq!(since_seconds(None, &current, at(11, 30)), None);
+        //clock moved backwards.
+        assert_eq!(since_seconds(Some(&previous), &current, at(9, 0)), None);
+    }
+
+    #[test]
+    fn restarted_containers_are_collected_in_full() {
+        let previous =
+            current_container_state(&pod(1, Some("2024-03-01T09:00:00Z")), "kafka", at(10, 0));
+        let more_restarts =
+            current_container_state(&pod(2, Some("2024-03-01T09:00:00Z")), "kafka", at(11, 0));
+        let new_start =
+            current_container_state(&pod(1, Some("2024-03-01T10:30:00Z")), "kafka", at(11, 0));
+        for current in [more_restarts, new_start] {
+            assert!(restarted(&previous, &current));
+            assert_eq!(since_seconds(Some(&previous), &current, at(11, 0)), None);
+        }
+    }
+}
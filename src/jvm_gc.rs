@@ -0,0 +1,314 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use futures_util::future::join_all;
+use simplelog::{__private::log::warn, info};
+
+use std::time::Duration;
+
+use crate::{access::PodAccess, components, context::RunContext, copy_from_pod, PodInfo};
+
+//cap of the GC log file when jvm_gc_log_max_mb is 0.
+pub const DEFAULT_GC_LOG_MAX_BYTES: u64 = 50 * 1024 * 1024;
+pub const HEAP_SAMPLES: usize = 3;
+pub const HEAP_SAMPLE_INTERVAL: Duration = Duration::from_secs(20);
+//the -XX flags and the command line of the jvm running as pid 1, -Xlog and -Xloggc are only in the latter.
+const FLAGS_COMMAND: &str = "jcmd 1 VM.flags && jcmd 1 VM.command_line";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GcLogSyntax {
+    //-Xlog:gc*:file=/var/log/gc.log:time:filecount=5,filesize=10m (jdk 9+).
+    Unified,
+    //-Xloggc:/var/log/gc.log (jdk 8).
+    Legacy,
+}
+
+//the GC log file of the jvm, %p/%t placeholders turned into a glob.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GcLog {
+    pub path: String,
+    pub syntax: GcLogSyntax,
+}
+
+fn unplaceholder(path: &str) -> String {
+    path.trim_matches(['"', '\''])
+        .replace("%p", "*")
+        .replace("%t", "*")
+        .replace("%hn", "*")
+}
+
+//splits on sep outside double quotes, a quoted file name may hold ':' and spaces.
+fn split_unquoted(text: &str, sep: impl Fn(char) -> bool) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut start, mut quoted) = (0, false);
+    for (i, c) in text.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if !quoted && sep(c) {
+            parts.push(&text[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+//a selection logging gc: all, gc, gc*, gc+heap=debug or heap+gc, not at level off.
+fn selects_gc(selection: &str) -> bool {
+    let (tags, level) = selection.split_once('=').unwrap_or((selection, ""));
+    let tags = tags.trim_end_matches('*');
+    level != "off" && (tags == "all" || tags.split('+').any(|t| t == "gc"))
+}
+
+//the output of an -Xlog option whose selections include gc, None when it goes to stdout/stderr.
+fn unified_output(option: &str) -> Option<String> {
+    let parts = split_unquoted(option, |c| c == ':');
+    let mut parts = parts.into_iter();
+    let selectors = parts.next()?;
+    if !selectors.is_empty() && !selectors.split(',').any(selects_gc) {
+        return None;
+    }
+    let output = parts.next()?;
+    let path = output.strip_prefix("file=").unwrap_or(output);
+    (!path.is_empty() && path != "stdout" && path != "stderr").then(|| unplaceholder(path))
+}
+
+//the GC log file in the VM.flags and VM.command_line output, the last option wins like for the jvm.
+pub fn gc_log_path(flags: &str) -> Option<GcLog> {
+    split_unquoted(flags, char::is_whitespace)
+        .into_iter()
+        .filter_map(|t| {
+            if let Some(option) = t.strip_prefix("-Xlog:") {
+                unified_output(option).map(|path| GcLog {
+                    path,
+                    syntax: GcLogSyntax::Unified,
+                })
+            } else {
+                t.strip_prefix("-Xloggc:").map(|path| GcLog {
+                    path: unplaceholder(path),
+                    syntax: GcLogSyntax::Legacy,
+                })
+            }
+        })
+        .next_back()
+}
+
+//the most recently written file of the log and its rotations (gc.log.0, gc.log.1.current...).
+fn latest_command(path: &str) -> String {
+    format!("ls -t {}* 2>/dev/null | head -1", path)
+}
+
+async fn collect_pod<A: PodAccess>(
+    p: &PodInfo<A>,
+    max_bytes: u64,
+    ctx: &RunContext,
+    folder: &str,
+) -> Result<()> {
+    let container = p
+        .containers
+        .first()
+        .ok_or_else(|| anyhow!("Pod {} has no container.", p.name))?;
+    let sh = |command: String| vec!["/bin/sh".to_string(), "-c".to_string(), command];
+    let flags = match p
+        .api
+        .exec_status(&p.name, container, sh(FLAGS_COMMAND.to_string()))
+        .await?
+    {
+        (out, 0) => out,
+        //not a jvm or no jcmd in the image.
+        (_, code) => {
+            info!("No jcmd in pod {} (exit code {}), skipped.", p.name, code);
+            return Ok(());
+        }
+    };
+    let filename = format!("jvm_flags_{}_{}.log", p.namespace, p.name);
+    ctx.claim_output(folder, &filename, &p.namespace, &p.name)?;
+    let er = anyhow!("Empty jvm flags for {}.", p.name);
+    ctx.write_file(folder, flags.as_bytes(), &filename, er)?;
+
+    match gc_log_path(&flags) {
+        Some(gc) => {
+            let latest = p
+                .api
+                .exec(&p.name, container, sh(latest_command(&gc.path)))
+                .await?;
+            let latest = latest.trim();
+            if latest.is_empty() {
+                warn!(
+                    "GC log {} ({:?}) of {} not found.",
+                    gc.path, gc.syntax, p.name
+                );
+            } else {
+                let local_dir = format!("{}/jvm_gc_{}_{}", folder, p.namespace, p.name);
+                match copy_from_pod(
+                    p.api.clone(),
+                    &p.name,
+                    container,
+                    latest,
+                    &local_dir,
+                    max_bytes,
+                    ctx,
+                )
+                .await
+                {
+                    Ok(_) => info!("File has been copied {} to {}", latest, local_dir),
+                    Err(e) => {
+                        warn!("{}", e);
+                        let filename = format!(
+                            "jvm_gc_{}_{}{}",
+                            p.namespace,
+                            p.name,
+                            latest.replace('/', "_")
+                        );
+                        ctx.record_failure(
+                            folder,
+                            &filename,
+                            &format!("exec tar cf - {}", latest),
+                            &e,
+                        );
+                    }
+                }
+            }
+        }
+        None => info!("No GC log file in the jvm flags of {}.", p.name),
+    }
+
+    let mut trend = String::new();
+    for i in 0..HEAP_SAMPLES {
+        if i > 0 {
+            tokio::time::sleep(HEAP_SAMPLE_INTERVAL).await;
+        }
+        let command = ["jcmd", "1", "GC.heap_info"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        let sample = p
+            .api
+            .exec(&p.name, container, command)
+            .await
+            .unwrap_or_else(|e| format!("failed: {}\n", e));
+        trend.push_str(&format!("=== {}\n{}\n", Utc::now().to_rfc3339(), sample));
+    }
+    let filename = format!("jvm_heap_trend_{}_{}.log", p.namespace, p.name);
+    ctx.claim_output(folder, &filename, &p.namespace, &p.name)?;
+    let er = anyhow!("Empty heap trend for {}.", p.name);
+    ctx.write_file(folder, trend.as_bytes(), &filename, er)?;
+    info!("File has been created {}/{}", folder, filename);
+    Ok(())
+}
+
+//flags, latest GC log and HEAP_SAMPLES heap_info samples of the jvm of every app pod, the pods in parallel.
+pub async fn collect_jvm_gc<A: PodAccess>(
+    access: Vec<A>,
+    max_bytes: u64,
+    ctx: &RunContext,
+    folder: &str,
+) -> Result<()> {
+    let pods = components::app_pods(access).await?;
+    let results = join_all(pods.iter().map(|p| collect_pod(p, max_bytes, ctx, folder))).await;
+    for (p, r) in pods.iter().zip(results) {
+        if let Err(e) = r {
+            warn!("JVM diagnostics of {}: {}", p.name, e);
+            ctx.record_folder(folder, false);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    fn command_line(name: &str) -> String {
+        std::fs::read_to_string(fixture(&format!("jvm_gc/{}", name))).unwrap()
+    }
+
+    fn unified(path: &str) -> Option<GcLog> {
+        Some(GcLog {
+            path: path.to_string(),
+            syntax: GcLogSyntax::Unified,
+        })
+    }
+
+    #[test]
+    fn gc_log_of_the_jvm_command_line() {
+        assert_eq!(
+            gc_log_path(&command_line("kafka_jdk17.txt")),
+            unified("/opt/kafka/logs/kafkaServer-gc-*.log")
+        );
+        assert_eq!(
+            gc_log_path(&command_line("elasticsearch_jdk8.txt")),
+            Some(GcLog {
+                path: "/usr/share/elasticsearch/logs/gc-*.log".to_string(),
+                syntax: GcLogSyntax::Legacy,
+            })
+        );
+        //gc on stdout and a file of safepoint logs only.
+        assert_eq!(gc_log_path(&command_line("stdout_only.txt")), None);
+    }
+
+    #[test]
+    fn unified_options() {
+        assert_eq!(
+            gc_log_path("-Xlog:gc*:file=/var/log/gc-%p.log:time:filecount=5"),
+            unified("/var/log/gc-*.log")
+        );
+        assert_eq!(
+            gc_log_path("-Xlog:gc*:/var/log/gc.log"),
+            unified("/var/log/gc.log")
+        );
+        assert_eq!(gc_log_path("-Xlog:gc"), None);
+        assert_eq!(gc_log_path("-Xlog:gc:stdout"), None);
+        assert_eq!(gc_log_path("-Xlog:gc*=debug:stderr:uptime"), None);
+        assert_eq!(
+            gc_log_path("-Xlog:safepoint:file=/var/log/safepoint.log"),
+            None
+        );
+        //every tag, gc included.
+        assert_eq!(
+            gc_log_path("-Xlog:all:file=/var/log/jvm.log"),
+            unified("/var/log/jvm.log")
+        );
+        assert_eq!(
+            gc_log_path("-Xlog:safepoint,gc+heap=debug:file=/var/log/jvm-%t.log"),
+            unified("/var/log/jvm-*.log")
+        );
+        assert_eq!(
+            gc_log_path("-Xlog:heap+gc=trace:file=/var/log/heap.log"),
+            unified("/var/log/heap.log")
+        );
+        assert_eq!(gc_log_path("-Xlog:all=off:file=/var/log/jvm.log"), None);
+        assert_eq!(gc_log_path("-Xlog:gcid:file=/var/log/gcid.log"), None);
+    }
+
+    #[test]
+    fn quoted_paths() {
+        //a quoted file name may hold ':' and spaces.
+        assert_eq!(
+            gc_log_path("-Xmx1g -Xlog:gc*:file=\"C:/logs/gc %p.log\":time -Xss1m"),
+            unified("C:/logs/gc *.log")
+        );
+        assert_eq!(
+            gc_log_path("-Xloggc:'/var/log/gc-%t.log'").map(|g| g.path),
+            Some("/var/log/gc-*.log".to_string())
+        );
+    }
+
+    #[test]
+    fn the_last_option_wins() {
+        assert_eq!(
+            gc_log_path("-Xloggc:/var/log/old.log -Xlog:gc*:file=/var/log/new.log"),
+            unified("/var/log/new.log")
+        );
+        assert_eq!(
+            gc_log_path("-Xlog:gc*:file=/var/log/new.log -Xloggc:/var/log/old-%p.log")
+                .map(|g| (g.path, g.syntax)),
+            Some(("/var/log/old-*.log".to_string(), GcLogSyntax::Legacy))
+        );
+        //an option without a file does not hide the earlier one.
+        assert_eq!(
+            gc_log_path("-Xlog:gc*:file=/var/log/gc.log -Xlog:gc:stdout"),
+            unified("/var/log/gc.log")
+        );
+    }
+}
@@ -0,0 +1,503 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde_derive::Serialize;
+use simplelog::{__private::log::warn, info};
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{
+    access::PodAccess,
+    collector::Collector,
+    component_command::{self, ComponentCommand, ComponentTarget},
+    components,
+    context::CoverageOutcome,
+    pod_selection,
+};
+
+pub const KAFKA_HEALTH_FILE: &str = "kafka_health_summary.json";
+pub const KAFKA_HEALTH_SUMMARY_FILE: &str = "kafka_health_summary.txt";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TopicHealth {
+    pub partitions: u32,
+    pub replication_factor: u32,
+    //partitions with fewer in-sync replicas than replicas.
+    pub under_replicated: Vec<u32>,
+    //partitions without a leader (Leader: -1 or none).
+    pub offline: Vec<u32>,
+    //the Configs: overrides of the topic line, as printed.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub configs: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct KafkaHealth {
+    pub topics: BTreeMap<String, TopicHealth>,
+    pub leaders_per_broker: BTreeMap<i32, u32>,
+    //most loaded broker against the average, 1.0 when balanced.
+    pub leader_skew: f64,
+    //"<topic>-<partition>".
+    pub under_replicated: Vec<String>,
+    pub offline: Vec<String>,
+}
+
+fn fields(re: &Regex, line: &str) -> (BTreeMap<String, String>, String) {
+    //Configs: holds comma separated overrides which may contain ':' and spaces, it ends the line.
+    let (head, configs) = match line.find("Configs:") {
+        Some(i) => (&line[..i], line[i + "Configs:".len()..].trim().to_string()),
+        None => (line, String::new()),
+    };
+    let map = re
+        .captures_iter(head)
+        .map(|c| (c[1].to_string(), c[2].to_string()))
+        .collect();
+    (map, configs)
+}
+
+fn ids(list: Option<&String>) -> Vec<i32> {
+    list.map(|l| l.split(',').filter_map(|i| i.trim().parse().ok()).collect())
+        .unwrap_or_default()
+}
+
+//kafka-topics.sh --describe output, "Topic: x\tPartitionCount: ..." and "Topic:x\tPartitionCount:..." forms.
+pub fn parse_topics_describe(output: &str) -> KafkaHealth {
+    let mut health = KafkaHealth::default();
+    let re = Regex::new(r"(\w+):\s*(\S*)").unwrap();
+    for line in output.lines().filter(|l| !l.trim().is_empty()) {
+        let (f, configs) = fields(&re, line);
+        let Some(topic) = f.get("Topic") else {
+            continue;
+        };
+        let t = health.topics.entry(topic.clone()).or_default();
+        if let Some(count) = f.get("PartitionCount") {
+            t.partitions = count.parse().unwrap_or_default();
+            t.replication_factor = f
+                .get("ReplicationFactor")
+                .and_then(|r| r.parse().ok())
+                .unwrap_or_default();
+            t.configs = configs;
+            continue;
+        }
+        let Some(partition) = f.get("Partition").and_then(|p| p.parse::<u32>().ok()) else {
+            continue;
+        };
+        let leader = f
+            .get("Leader")
+            .and_then(|l| l.parse::<i32>().ok())
+            .unwrap_or(-1);
+        if leader < 0 {
+            t.offline.push(partition);
+            health.offline.push(format!("{}-{}", topic, partition));
+        } else {
+            *health.leaders_per_broker.entry(leader).or_default() += 1;
+        }
+        if ids(f.get("Isr")).len() < ids(f.get("Replicas")).len() {
+            t.under_replicated.push(partition);
+            health
+                .under_replicated
+                .push(format!("{}-{}", topic, partition));
+        }
+    }
+    let leaders = health
+        .leaders_per_broker
+        .values()
+        .copied()
+        .collect::<Vec<u32>>();
+    if let Some(max) = leaders.iter().max() {
+        let average = leaders.iter().sum::<u32>() as f64 / leaders.len() as f64;
+        health.leader_skew = *max as f64 / average;
+    }
+    health
+}
+
+pub fn render_summary(health: &KafkaHealth) -> String {
+    let mut out = format!(
+        "{:<50} {:>10} {:>8} {:>5} {:>7}\n",
+        "TOPIC", "PARTITIONS", "REPLICAS", "URP", "OFFLINE"
+    );
+    for (name, t) in &health.topics {
+        out.push_str(&format!(
+            "{:<50} {:>10} {:>8} {:>5} {:>7}{}\n",
+            name,
+            t.partitions,
+            t.replication_factor,
+            t.under_replicated.len(),
+            t.offline.len(),
+            if t.under_replicated.is_empty() && t.offline.is_empty() {
+                ""
+            } else {
+                " WARNING"
+            }
+        ));
+    }
+    out.push_str("\nleaders per broker:");
+    health
+        .leaders_per_broker
+        .iter()
+        .for_each(|(b, c)| out.push_str(&format!(" {}={}", b, c)));
+    out.push_str(&format!("\nleader skew: {:.2}\n", health.leader_skew));
+    if !health.under_replicated.is_empty() {
+        out.push_str(&format!(
+            "under-replicated partitions: {}\n",
+            health.under_replicated.join(", ")
+        ));
+    }
+    if !health.offline.is_empty() {
+        out.push_str(&format!(
+            "offline partitions: {}\n",
+            health.offline.join(", ")
+        ));
+    }
+    out
+}
+
+pub fn to_json(health: &KafkaHealth) -> Result<String> {
+    Ok(serde_json::to_string_pretty(health)?)
+}
+
+//the topics, groups and brokers of kafka, of the message bus when there is no kafka, and the
+//partition health out of the topics description.
+pub async fn collect_kafka<A: PodAccess>(collector: &Collector<A>, folder: &str) -> Result<()> {
+    let ctx = &collector.ctx;
+    let mut kafka_pods = vec![];
+    for k in ["kafka", "kafka_message_bus"] {
+        let scope = components::component_scope(&collector.config, k);
+        let kf = scope.pods(&collector.access).await?;
+        if kf.is_empty() {
+            ctx.record_coverage(k, scope.skipped());
+        } else {
+            kafka_pods.push((kf, scope, k));
+        }
+    }
+    if let Some((kf, scope, component)) = kafka_pods.first() {
+        let before = ctx.output_counts();
+        let prefix = match *component {
+            "kafka" => "bin/",
+            _ => "",
+        };
+
+        let targets =
+            pod_selection::select_pods(component, scope, &collector.access, kf.clone(), None).await;
+        let several = targets.len() > 1;
+        for (i, kafka_pod) in targets.iter().enumerate() {
+            let command_kf = [
+                (
+                    prefix.to_owned() + "kafka-topics.sh --bootstrap-server localhost:9092 --list",
+                    "topics",
+                ),
+                (
+                    prefix.to_owned() + "kafka-topics.sh --bootstrap-server localhost:9092 --describe",
+                    "topics_description",
+                ),
+                (
+                    prefix.to_owned()
+                        + "kafka-consumer-groups.sh --bootstrap-server localhost:9092 --list",
+                    "groups_list",
+                ),
+                (
+                    prefix.to_owned()
+                        + "kafka-broker-api-versions.sh --bootstrap-server localhost:9092 | awk '/^[a-z]/ {print $1}'",
+                    "brokers_list",
+                ),
+                (
+                    prefix.to_owned()
+                        + "kafka-consumer-groups.sh --bootstrap-server localhost:9092 --describe --all-groups",
+                    "groups_describe",
+                ),
+            ]
+            .map(|(shell, name)| ComponentCommand {
+                name: name.to_string(),
+                filename: collector.config.file_name_templates.app_output(
+                    kafka_pod,
+                    &kafka_pod.containers[0],
+                    &pod_selection::output_kind(
+                        kafka_pod,
+                        &format!("kafka_{}.log", name),
+                        several,
+                    ),
+                ),
+                shell,
+                ..Default::default()
+            });
+            let target = ComponentTarget::new("kafka", kafka_pod.clone())
+                .with_scope(scope.clone(), collector.access.clone());
+            let outputs = component_command::collect_component_outputs(
+                Arc::new(target),
+                ctx.clone(),
+                folder,
+                command_kf.to_vec(),
+            )
+            .await;
+            //the topics of the cluster, the same from every broker.
+            if i > 0 {
+                continue;
+            }
+            //partition counts, leader skew and under-replicated partitions from the describe output.
+            if let Some((_, data)) = outputs.iter().find(|(n, _)| n == "topics_description") {
+                let health = parse_topics_describe(data);
+                ctx.health.lock().unwrap().kafka_under_replicated =
+                    Some(health.under_replicated.len());
+                if !health.under_replicated.is_empty() || !health.offline.is_empty() {
+                    warn!(
+                        "<yellow>Kafka: {} under-replicated and {} offline partitions.</>",
+                        health.under_replicated.len(),
+                        health.offline.len()
+                    );
+                }
+                match to_json(&health) {
+                    Ok(json) => {
+                        for (data, file_name) in [
+                            (json, KAFKA_HEALTH_FILE),
+                            (render_summary(&health), KAFKA_HEALTH_SUMMARY_FILE),
+                        ] {
+                            let er = anyhow!("Empty {}.", file_name);
+                            match ctx.write_file(folder, data.as_bytes(), file_name, er) {
+                                Ok(_) => {
+                                    info!("File has been created {}/{}", folder, file_name)
+                                }
+                                Err(e) => warn!("{}", e),
+                            }
+                        }
+                    }
+                    Err(e) => warn!("{}", e),
+                }
+            }
+        }
+        ctx.record_outputs(component, before);
+        for (_, _, other) in kafka_pods.iter().skip(1) {
+            let reason = format!("{} collected instead", component);
+            ctx.record_coverage(other, CoverageOutcome::Skipped { reason });
+        }
+    }
+    Ok(())
+}
+
+pub const CONNECT_STATUS_FILE: &str = "kafka_connect_status.json";
+pub const CONNECT_FAILURES_FILE: &str = "kafka_connect_failed_tasks.txt";
+pub const SCHEMA_REGISTRY_SUBJECTS_FILE: &str = "schema_registry_subjects.json";
+pub const SCHEMA_REGISTRY_CONFIG_FILE: &str = "schema_registry_config.json";
+
+//one connector or one of its tasks, out of /connectors?expand=status.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConnectState {
+    pub connector: String,
+    //None for the connector itself.
+    pub task: Option<i64>,
+    pub state: String,
+    pub worker_id: String,
+    //the stack trace of a FAILED connector or task.
+    pub trace: Option<String>,
+}
+
+fn connect_state(connector: &str, task: Option<i64>, v: &serde_json::Value) -> ConnectState {
+    let text = |k: &str| v.get(k).and_then(|s| s.as_str()).map(|s| s.to_string());
+    ConnectState {
+        connector: connector.to_string(),
+        task,
+        state: text("state").unwrap_or_default(),
+        worker_id: text("worker_id").unwrap_or_default(),
+        trace: text("trace"),
+    }
+}
+
+//{"<connector>": {"status": {"connector": {..}, "tasks": [{"id": 0, ..}]}}} flattened,
+//each connector followed by its tasks.
+pub fn flatten_connect_status(json: &str) -> Result<Vec<ConnectState>> {
+    let v: serde_json::Value = serde_json::from_str(json)?;
+    let connectors = v
+        .as_object()
+        .ok_or_else(|| anyhow!("The connect status is not an object."))?;
+    let mut states = vec![];
+    for (name, c) in connectors {
+        let status = c.get("status").unwrap_or(c);
+        if let Some(connector) = status.get("connector") {
+            states.push(connect_state(name, None, connector));
+        }
+        for t in status
+            .get("tasks")
+            .and_then(|t| t.as_array())
+            .into_iter()
+            .flatten()
+        {
+            states.push(connect_state(name, t.get("id").and_then(|i| i.as_i64()), t));
+        }
+    }
+    Ok(states)
+}
+
+pub fn failed_connect_states(states: &[ConnectState]) -> Vec<&ConnectState> {
+    states.iter().filter(|s| s.state == "FAILED").collect()
+}
+
+pub fn render_connect_failures(failed: &[&ConnectState]) -> String {
+    let mut out = String::new();
+    for s in failed {
+        let what = match s.task {
+            Some(t) => format!("{} task {}", s.connector, t),
+            None => format!("{} connector", s.connector),
+        };
+        out.push_str(&format!("{} FAILED on {}\n", what, s.worker_id));
+        if let Some(trace) = &s.trace {
+            trace
+                .lines()
+                .for_each(|l| out.push_str(&format!("  {}\n", l)));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        access::FakeAccess,
+        test_support::{fixture, offline_client, run_context, TempDir},
+        ConfigFile,
+    };
+
+    fn describe(name: &str) -> KafkaHealth {
+        let output = std::fs::read_to_string(fixture(&format!("kafka/{}", name))).unwrap();
+        parse_topics_describe(&output)
+    }
+
+    #[test]
+    fn topics_describe_partitions_and_replication() {
+        let health = describe("topics_describe.txt");
+        let orders = &health.topics["orders"];
+        assert_eq!((orders.partitions, orders.replication_factor), (3, 3));
+        assert_eq!(orders.under_replicated, vec![1, 2]);
+        assert_eq!(orders.offline, vec![2]);
+        assert_eq!(health.under_replicated, vec!["orders-1", "orders-2"]);
+        assert_eq!(health.offline, vec!["orders-2"]);
+        //the isr in another order than the replicas is not under replicated.
+        assert!(health.topics["__consumer_offsets"]
+            .under_replicated
+            .is_empty());
+        assert_eq!(health.leaders_per_broker, BTreeMap::from([(1, 3), (2, 1)]));
+        assert_eq!(health.leader_skew, 1.5);
+    }
+
+    #[test]
+    fn config_overrides_keep_their_colons_and_commas() {
+        let health = describe("topics_describe.txt");
+        assert_eq!(
+            health.topics["orders"].configs,
+            "min.insync.replicas=2,leader.replication.throttled.replicas=0:1,1:2,cleanup.policy=compact"
+        );
+        //no field of the overrides leaks into the topic line.
+        assert_eq!(health.topics["orders"].partitions, 3);
+        assert_eq!(
+            health.topics["__consumer_offsets"].configs,
+            "compression.type=producer,segment.bytes=104857600"
+        );
+    }
+
+    #[test]
+    fn topics_describe_of_older_brokers() {
+        let health = describe("topics_describe_old.txt");
+        let payments = &health.topics["payments"];
+        assert_eq!((payments.partitions, payments.replication_factor), (2, 2));
+        assert_eq!(payments.configs, "");
+        assert_eq!(payments.offline, vec![1]);
+        assert_eq!(payments.under_replicated, vec![1]);
+        assert_eq!(health.leader_skew, 1.0);
+    }
+
+    #[test]
+    fn empty_describe_output_is_healthy() {
+        let health = parse_topics_describe("Error while executing topic command\n\n");
+        assert_eq!(health, KafkaHealth::default());
+    }
+
+    #[tokio::test]
+    async fn collect_kafka_runs_the_commands_in_the_first_broker() {
+        let dir = TempDir::new();
+        let access = FakeAccess::from_fixture(&fixture("access/cluster.json")).unwrap();
+        let config = ConfigFile {
+            context_namespace: vec!["kafka".to_string(), "web".to_string()],
+            ..Default::default()
+        };
+        let ctx = Arc::new(run_context(&dir));
+        let collector = Collector::with_access(offline_client(), access, config, ctx.clone());
+        let folder = format!("{}/apps", dir.folder());
+        collect_kafka(&collector, &folder).await.unwrap();
+
+        assert_eq!(dir.read("apps/kafka_topics.log"), "orders\npayments\n");
+        assert!(dir
+            .read("apps/kafka_brokers_list.log")
+            .contains("kafka-1.kafka:9092"));
+        //no output for the groups description in the fixture.
+        assert!(dir
+            .path()
+            .join("apps/kafka_groups_describe.log.error")
+            .exists());
+        let health: serde_json::Value =
+            serde_json::from_str(&dir.read(&format!("apps/{}", KAFKA_HEALTH_FILE))).unwrap();
+        assert_eq!(health["under_replicated"], serde_json::json!(["orders-1"]));
+        assert_eq!(
+            health["topics"]["orders"]["configs"],
+            "retention.ms=1000,cleanup.policy=compact"
+        );
+        assert_eq!(ctx.health.lock().unwrap().kafka_under_replicated, Some(1));
+        assert!(matches!(
+            ctx.coverage.lock().unwrap().get("kafka_message_bus"),
+            Some(CoverageOutcome::Skipped { .. })
+        ));
+    }
+
+    fn connect_status() -> Vec<ConnectState> {
+        let json = std::fs::read_to_string(fixture("kafka/connect_status.json")).unwrap();
+        flatten_connect_status(&json).unwrap()
+    }
+
+    #[test]
+    fn connect_status_flattened_connector_then_tasks() {
+        let states = connect_status();
+        let flat = states
+            .iter()
+            .map(|s| (s.connector.as_str(), s.task, s.state.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            flat,
+            vec![
+                ("orders-sink", None, "RUNNING"),
+                ("orders-sink", Some(0), "RUNNING"),
+                ("orders-sink", Some(1), "FAILED"),
+                ("paused-sink", None, "PAUSED"),
+                ("paused-sink", Some(0), "PAUSED"),
+                ("payments-source", None, "FAILED"),
+                //a status without the expand=status wrapper.
+                ("unassigned-source", None, "UNASSIGNED"),
+            ]
+        );
+        assert_eq!(states[2].worker_id, "10.0.0.6:8083");
+        assert_eq!(states[0].trace, None);
+    }
+
+    #[test]
+    fn connect_status_that_is_not_an_object() {
+        assert!(flatten_connect_status("[]")
+            .unwrap_err()
+            .to_string()
+            .contains("not an object"));
+        assert!(flatten_connect_status("<html>").is_err());
+        assert!(flatten_connect_status("{}").unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_connectors_and_tasks_with_their_trace() {
+        let states = connect_status();
+        let failed = failed_connect_states(&states);
+        assert_eq!(failed.len(), 2);
+        assert_eq!(
+            render_connect_failures(&failed),
+            "orders-sink task 1 FAILED on 10.0.0.6:8083\n  \
+             org.apache.kafka.connect.errors.ConnectException: Exiting WorkerSinkTask due to unrecoverable exception.\n  \
+             \tat org.apache.kafka.connect.runtime.WorkerSinkTask.deliverMessages(WorkerSinkTask.java:611)\n\n\
+             payments-source connector FAILED on 10.0.0.6:8083\n  \
+             java.lang.IllegalArgumentException: Invalid database.hostname\n\n"
+        );
+        assert!(failed_connect_states(&states[3..5]).is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+use anyhow::{anyhow, Result};
+use kube::ResourceExt;
+use serde_derive::Serialize;
+use simplelog::info;
+
+use std::collections::BTreeMap;
+
+use crate::{context::RunContext, PodInfo};
+
+pub const LABEL_VALUES_FILE: &str = "label_values_report.txt";
+pub const LABEL_VALUES_JSON_FILE: &str = "label_values_report.json";
+//what report_labels falls back to: the versions and the charts deployed.
+pub const DEFAULT_REPORT_LABELS: [&str; 2] = ["app.kubernetes.io/version", "helm.sh/chart"];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LabelValues {
+    pub label: String,
+    //distinct value -> pods carrying it.
+    pub values: BTreeMap<String, usize>,
+    pub pods_without_label: usize,
+}
+
+//report_labels, DEFAULT_REPORT_LABELS when empty.
+pub fn report_labels(configured: &[String]) -> Vec<String> {
+    if configured.is_empty() {
+        DEFAULT_REPORT_LABELS.map(String::from).to_vec()
+    } else {
+        configured.to_vec()
+    }
+}
+
+//the distinct values of each label over the pods with their pod counts, in the order of labels.
+pub fn aggregate<A>(pods: &[PodInfo<A>], labels: &[String]) -> Vec<LabelValues> {
+    labels
+        .iter()
+        .map(|label| {
+            let mut values = LabelValues {
+                label: label.clone(),
+                ..Default::default()
+            };
+            for p in pods {
+                match p.pod.labels().get(label) {
+                    Some(v) => *values.values.entry(v.clone()).or_default() += 1,
+                    None => values.pods_without_label += 1,
+                }
+            }
+            values
+        })
+        .collect()
+}
+
+//one block per label, its values by pod count.
+pub fn render(report: &[LabelValues]) -> String {
+    let mut out = String::new();
+    for l in report {
+        out.push_str(&format!(
+            "{}: {} values, {} pods without the label\n",
+            l.label,
+            l.values.len(),
+            l.pods_without_label
+        ));
+        let mut values = l.values.iter().collect::<Vec<_>>();
+        values.sort_by_key(|(_, pods)| std::cmp::Reverse(**pods));
+        for (value, pods) in values {
+            out.push_str(&format!("  {:<50} {:>5} pods\n", value, pods));
+        }
+    }
+    out
+}
+
+//pods/label_values_report.txt and .json over the collected pods.
+pub fn write_report<A>(
+    ctx: &RunContext,
+    folder: &str,
+    pods: &[PodInfo<A>],
+    labels: &[String],
+) -> Result<()> {
+    let report = aggregate(pods, labels);
+    let er = anyhow!("Empty {}.", LABEL_VALUES_FILE);
+    ctx.write_file(folder, render(&report).as_bytes(), LABEL_VALUES_FILE, er)?;
+    info!("File has been created {}/{}", folder, LABEL_VALUES_FILE);
+    let er = anyhow!("Empty {}.", LABEL_VALUES_JSON_FILE);
+    let json = serde_json::to_string_pretty(&report)?;
+    ctx.write_file(folder, json.as_bytes(), LABEL_VALUES_JSON_FILE, er)?;
+    info!(
+        "File has been created {}/{}",
+        folder, LABEL_VALUES_JSON_FILE
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, pod_info, run_context, TempDir};
+
+    fn pods() -> Vec<PodInfo<()>> {
+        let text = std::fs::read_to_string(fixture("label_values/pods.json")).unwrap();
+        let pods: Vec<serde_json::Value> = serde_json::from_str(&text).unwrap();
+        pods.into_iter().map(pod_info).collect()
+    }
+
+    fn labels(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn the_defaults_unless_configured() {
+        assert_eq!(report_labels(&[]), labels(&DEFAULT_REPORT_LABELS));
+        assert_eq!(report_labels(&labels(&["team"])), labels(&["team"]));
+    }
+
+    #[test]
+    fn values_are_counted_with_the_pods_missing_the_label() {
+        let report = aggregate(
+            &pods(),
+            &labels(&["app.kubernetes.io/version", "helm.sh/chart"]),
+        );
+        //an empty value is a value, only the pods without the key are missing it.
+        assert_eq!(
+            report[0],
+            LabelValues {
+                label: "app.kubernetes.io/version".to_string(),
+                values: BTreeMap::from([
+                    ("".to_string(), 1),
+                    ("3.4.0".to_string(), 1),
+                    ("3.5.1".to_string(), 2),
+                ]),
+                pods_without_label: 1,
+            }
+        );
+        assert_eq!(
+            report[1].values,
+            BTreeMap::from([("kafka-24.0.1".to_string(), 3)])
+        );
+        assert_eq!(report[1].pods_without_label, 2);
+    }
+
+    #[test]
+    fn a_label_no_pod_has() {
+        let report = aggregate(&pods(), &labels(&["app.kubernetes.io/instance"]));
+        assert!(report[0].values.is_empty());
+        assert_eq!(report[0].pods_without_label, 5);
+        assert_eq!(
+            render(&report),
+            "app.kubernetes.io/instance: 0 values, 5 pods without the label\n"
+        );
+        //without pods every label is empty.
+        assert_eq!(
+            aggregate::<()>(&[], &labels(&["team"]))[0],
+            LabelValues {
+                label: "team".to_string(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn render_lists_the_values_by_pod_count() {
+        let report = aggregate(&pods(), &labels(&["app.kubernetes.io/version", "team"]));
+        let rendered = render(&report);
+        let lines = rendered
+            .lines()
+            .map(|l| l.trim_end())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            lines[0],
+            "app.kubernetes.io/version: 3 values, 1 pods without the label"
+        );
+        assert!(lines[1].trim_start().starts_with("3.5.1"));
+        assert!(lines[1].ends_with("2 pods"));
+        //equal counts keep the order of the values.
+        assert!(lines[2].trim().starts_with("1 pods"));
+        assert!(lines[3].trim_start().starts_with("3.4.0"));
+        assert_eq!(lines[4], "team: 1 values, 4 pods without the label");
+        assert!(lines[5].trim_start().starts_with("frontend"));
+    }
+
+    #[test]
+    fn write_report_writes_the_text_and_json() {
+        let dir = TempDir::new();
+        let ctx = run_context(&dir);
+        let folder = ctx.folders[0].clone();
+        write_report(&ctx, &folder, &pods(), &labels(&["team"])).unwrap();
+        assert!(dir
+            .read(&format!("pods/{}", LABEL_VALUES_FILE))
+            .starts_with("team: 1 values, 4 pods without the label\n"));
+        let json: serde_json::Value =
+            serde_json::from_str(&dir.read(&format!("pods/{}", LABEL_VALUES_JSON_FILE))).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"label": "team", "values": {"frontend": 1}, "pods_without_label": 4}
+            ])
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use k8s_openapi::api::coordination::v1::Lease;
+use kube::{api::ListParams, Api};
+use simplelog::{__private::log::warn, info};
+
+use std::{collections::BTreeSet, fs};
+
+use crate::{collector::Collector, timeline};
+
+pub const LEASES_FOLDER: &str = "leases";
+pub const LEASES_SUMMARY_FILE: &str = "leases_summary.txt";
+//the leader election leases of the control plane live there.
+pub const SYSTEM_NAMESPACE: &str = "kube-system";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaseSummary {
+    pub namespace: String,
+    pub name: String,
+    pub holder: Option<String>,
+    pub duration_seconds: Option<i32>,
+    pub renew_time: Option<DateTime<Utc>>,
+    //last renewal older than twice the lease duration, the holder stopped renewing.
+    pub stale: bool,
+}
+
+//a lease without renew time or duration cannot be told stale.
+pub fn is_stale(
+    renew_time: Option<DateTime<Utc>>,
+    duration_seconds: Option<i32>,
+    now: DateTime<Utc>,
+) -> bool {
+    match (renew_time, duration_seconds) {
+        (Some(r), Some(d)) => (now - r).num_seconds() > 2 * d as i64,
+        _ => false,
+    }
+}
+
+pub fn summarize(leases: &[Lease], now: DateTime<Utc>) -> Vec<LeaseSummary> {
+    leases
+        .iter()
+        .map(|l| {
+            let spec = l.spec.as_ref();
+            let renew_time = spec.and_then(|s| s.renew_time.as_ref()).map(|t| t.0);
+            let duration_seconds = spec.and_then(|s| s.lease_duration_seconds);
+            LeaseSummary {
+                namespace: l.metadata.namespace.clone().unwrap_or_default(),
+                name: l.metadata.name.clone().unwrap_or_default(),
+                holder: spec.and_then(|s| s.holder_identity.clone()),
+                duration_seconds,
+                renew_time,
+                stale: is_stale(renew_time, duration_seconds, now),
+            }
+        })
+        .collect()
+}
+
+pub fn render_summary(summaries: &[LeaseSummary], now: DateTime<Utc>) -> String {
+    let mut out = String::new();
+    for s in summaries {
+        let renewed = match s.renew_time {
+            Some(t) => format!("{} ({} ago)", t.to_rfc3339(), timeline::age(t, now)),
+            None => "-".to_string(),
+        };
+        out.push_str(&format!(
+            "{}/{} holder={} duration={} renewed={}{}\n",
+            s.namespace,
+            s.name,
+            s.holder.as_deref().unwrap_or("-"),
+            s.duration_seconds
+                .map(|d| format!("{}s", d))
+                .unwrap_or_else(|| "-".to_string()),
+            renewed,
+            if s.stale { " STALE" } else { "" }
+        ));
+    }
+    out
+}
+
+fn to_yaml(leases: &[Lease]) -> Result<String> {
+    let mut yaml = String::new();
+    for lease in leases {
+        let mut lease = lease.clone();
+        lease.metadata.managed_fields = None;
+        yaml.push_str("---\n");
+        yaml.push_str(&serde_yaml::to_string(&lease)?);
+    }
+    Ok(yaml)
+}
+
+//the leases of the configured namespaces and kube-system under infra/leases, one yaml per namespace
+//and a summary of their holders flagging the ones not renewed for twice their duration.
+pub async fn collect_leases(collector: &Collector, infra_folder: &str) -> Result<()> {
+    let ctx = &collector.ctx;
+    let folder = format!("{}/{}", infra_folder, LEASES_FOLDER);
+    fs::create_dir_all(&folder)?;
+    let namespaces = collector
+        .config
+        .context_namespace
+        .iter()
+        .map(|n| n.as_str())
+        .chain([SYSTEM_NAMESPACE])
+        .collect::<BTreeSet<&str>>();
+    let now = Utc::now();
+    let mut summaries = vec![];
+    for ns in namespaces {
+        let filename = format!("leases_{}.yaml", ns);
+        let api: Api<Lease> = Api::namespaced(collector.client.clone(), ns);
+        let leases = match api.list(&ListParams::default()).await {
+            Ok(l) => l.items,
+            Err(e) => {
+                let e = e.into();
+                warn!("Leases of {}: {}", ns, e);
+                ctx.record_failure(&folder, &filename, "list leases", &e);
+                continue;
+            }
+        };
+        if leases.is_empty() {
+            continue;
+        }
+        let written = to_yaml(&leases).and_then(|y| {
+            let er = anyhow!("Empty {}.", filename);
+            ctx.write_file(&folder, y.as_bytes(), &filename, er)
+        });
+        match written {
+            Ok(_) => info!("File has been created {}/{}", folder, filename),
+            Err(e) => warn!("{}", e),
+        }
+        summaries.extend(summarize(&leases, now));
+    }
+    if summaries.is_empty() {
+        info!("No lease found.");
+        return Ok(());
+    }
+    let stale = summaries.iter().filter(|s| s.stale).count();
+    if stale > 0 {
+        warn!(
+            "<yellow>{} leases not renewed for twice their duration, see {}/{}.</>",
+            stale, folder, LEASES_SUMMARY_FILE
+        );
+    }
+    let er = anyhow!("Empty {}.", LEASES_SUMMARY_FILE);
+    match ctx.write_file(
+        &folder,
+        render_summary(&summaries, now).as_bytes(),
+        LEASES_SUMMARY_FILE,
+        er,
+    ) {
+        Ok(_) => info!("File has been created {}/{}", folder, LEASES_SUMMARY_FILE),
+        Err(e) => warn!("{}", e),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().into()
+    }
+
+    fn leases() -> Vec<Lease> {
+        let json = fs::read_to_string(fixture("leases/leases.json")).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn stale_after_twice_the_duration() {
+        let renewed = Some(at("2026-10-17T08:00:00Z"));
+        assert!(!is_stale(renewed, Some(15), at("2026-10-17T08:00:30Z")));
+        assert!(is_stale(renewed, Some(15), at("2026-10-17T08:00:31Z")));
+        //a renewal seen in the future (clock skew) is not stale.
+        assert!(!is_stale(renewed, Some(15), at("2026-10-17T07:59:00Z")));
+        assert!(!is_stale(None, Some(15), at("2026-10-17T09:00:00Z")));
+        assert!(!is_stale(renewed, None, at("2026-10-17T09:00:00Z")));
+    }
+
+    #[test]
+    fn summaries_flag_the_leases_not_renewed() {
+        let now = at("2026-10-17T08:00:00Z");
+        let summaries = summarize(&leases(), now);
+        let stale = summaries
+            .iter()
+            .map(|s| (s.name.as_str(), s.stale))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stale,
+            vec![
+                ("kube-controller-manager", false),
+                ("kube-scheduler", true),
+                ("operator-lock", true),
+                ("released", false),
+            ]
+        );
+        assert_eq!(summaries[3].holder, None);
+        assert_eq!(summaries[3].renew_time, None);
+        //the same leases a little earlier were all fresh.
+        let earlier = summarize(&leases(), at("2026-10-17T07:59:30Z"));
+        assert!(!earlier[1].stale);
+    }
+
+    #[test]
+    fn summary_lines_with_the_age_of_the_renewal() {
+        let now = at("2026-10-17T08:00:00Z");
+        let report = render_summary(&summarize(&leases(), now), now);
+        assert_eq!(
+            report,
+            "kube-system/kube-controller-manager holder=master-1_0f3c duration=15s renewed=2026-10-17T07:59:55+00:00 (5s ago)\n\
+             kube-system/kube-scheduler holder=master-2_9a1b duration=15s renewed=2026-10-17T07:59:00+00:00 (60s ago) STALE\n\
+             kafka/operator-lock holder=strimzi-operator-7d9f6c duration=15s renewed=2026-10-17T06:00:00+00:00 (2h ago) STALE\n\
+             kafka/released holder=- duration=60s renewed=-\n"
+        );
+    }
+}
@@ -2,146 +2,2395 @@ use anyhow::Error;
 use anyhow::Ok;
 use anyhow::Result;
 
-use k8s_openapi::api::core::v1::Pod;
+use access::{HttpGet, KubeAccess, PodAccess};
+use api_warnings::{ApiWarnings, WarningLayer};
+use context::RunContext;
+use flate2::{write::GzEncoder, Compression};
+use futures_util::StreamExt;
+use k8s_openapi::api::{
+    authorization::v1::{ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec},
+    core::v1::{ContainerStatus, Pod, Toleration},
+};
 use kube::{
-    api::{AttachedProcess, ListParams, LogParams},
+    api::LogParams,
     config::{KubeConfigOptions, Kubeconfig},
     Api, Client, Config, ResourceExt,
 };
+use schemars::JsonSchema;
+use self_usage::{UsageCounters, UsageLayer};
 use serde::Deserialize;
-use tokio::io::AsyncReadExt;
+use serde_derive::Serialize;
+use sha2::{Digest, Sha256};
+use simplelog::{__private::log::warn, info};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
 
 use std::{
+    collections::BTreeMap,
     fs,
-    io::{BufWriter, Write},
+    io::{BufWriter, Read, Write},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+pub mod access;
+pub mod anonymize;
+pub mod api_versions;
+pub mod api_warnings;
+pub mod apiserver;
+pub mod archive;
+pub mod clock_skew;
+pub mod cluster_info;
+pub mod cni;
+pub mod collector;
+pub mod component_command;
+pub mod components;
+pub mod context;
+pub mod credentials;
+pub mod diff;
+pub mod disk_usage;
+pub mod elastic;
+pub mod events;
+pub mod exec;
+pub mod external;
+pub mod filters;
+pub mod findings;
+pub mod health;
+pub mod helm;
+pub mod image_pull;
+pub mod incremental;
+pub mod jvm_gc;
+pub mod kafka;
+pub mod label_values;
+pub mod leases;
+pub mod limits;
+pub mod line_endings;
+pub mod log_files;
+pub mod log_queue;
+pub mod logging;
+pub mod manifests;
+pub mod manual_changes;
+pub mod namespaces;
+pub mod naming;
+pub mod node_debug;
+pub mod node_pressure;
+pub mod openshift;
+pub mod ordinals;
+pub mod output;
+pub mod placement;
+pub mod plugins;
+pub mod pod_bundle;
+pub mod pod_selection;
+pub mod preset;
+pub mod previous_logs;
+pub mod prometheus;
+pub mod proxy;
+pub mod pushgateway;
+pub mod qos;
+pub mod reference_check;
+pub mod release_ownership;
+pub mod report;
+pub mod rules;
+pub mod run;
+pub mod scheduling;
+pub mod secrets_allowlist;
+pub mod self_usage;
+pub mod size_breakdown;
+pub mod sizing;
+pub mod spark;
+pub mod storage_csi;
+#[cfg(test)]
+mod test_support;
+pub mod timeline;
+pub mod verify;
+pub mod watch;
+
+pub use run::{run_collection, CollectionReport, RunOptions};
+
 #[derive(Default, Debug, Clone, PartialEq, Deserialize)]
 pub struct ConfigFile {
+    //derived from the api server in in-cluster mode when empty.
+    #[serde(default)]
     pub context_name: String,
     pub context_namespace: Vec<String>,
     pub output_directory_path: String,
+    //local disk where the collection and the archive are built, only the archive goes to
+    //output_directory_path (an NFS share...). The output directory when empty.
+    #[serde(default)]
+    pub work_directory_path: String,
     pub previous_logs: bool,
     pub current_logs: bool,
+    #[serde(default = "findings::default_findings_patterns")]
+    pub findings_patterns: Vec<String>,
+    #[serde(default = "findings::default_findings_max_matches")]
+    pub findings_max_matches_per_file: usize,
+    #[serde(default)]
+    pub rules_file: String,
+    #[serde(default)]
+    pub anonymize_strings: Vec<String>,
+    #[serde(default)]
+    pub retention_max_archives: usize,
+    //use the pod service account instead of a kubeconfig context.
+    #[serde(default)]
+    pub in_cluster: bool,
+    //direct authentication without a kubeconfig file.
+    #[serde(default)]
+    pub api_server_url: String,
+    //name of the environment variable holding the bearer token.
+    #[serde(default)]
+    pub bearer_token_env: String,
+    #[serde(default)]
+    pub ca_cert_path: String,
+    #[serde(default)]
+    pub client_cert_path: String,
+    #[serde(default)]
+    pub client_key_path: String,
+    #[serde(default)]
+    pub insecure_skip_tls_verify: bool,
+    //takes precedence over HTTPS_PROXY/NO_PROXY for the api server. http:// or socks5://.
+    #[serde(default)]
+    pub proxy_url: String,
+    //container name globs for the log collection.
+    #[serde(default)]
+    pub include_containers: Vec<String>,
+    #[serde(default)]
+    pub exclude_containers: Vec<String>,
+    //only the kubectl.kubernetes.io/default-container of a pod when it has one.
+    #[serde(default)]
+    pub only_default_container: bool,
+    //every namespaced kind of the configured namespaces as yaml.
+    #[serde(default)]
+    pub export_manifests: bool,
+    #[serde(default)]
+    pub export_manifests_keep_status: bool,
+    #[serde(default)]
+    pub custom_collectors: CustomCollectors,
+    //only the pods scheduled on these nodes, grouped under pods/<node>/.
+    #[serde(default)]
+    pub node_names: Vec<String>,
+    //runtime and kubelet info through a privileged debug pod on each node.
+    #[serde(default)]
+    pub collect_node_debug: bool,
+    #[serde(default)]
+    pub node_debug_image: String,
+    //namespace of the debug pods, it must allow privileged pods (kube-system when empty). A missing
+    //namespace is created with the privileged pod security level and deleted after the collection.
+    #[serde(default)]
+    pub node_debug_namespace: String,
+    //tolerations of the debug pods, every taint is tolerated when empty.
+    #[serde(default)]
+    pub node_debug_tolerations: Vec<Toleration>,
+    #[serde(default)]
+    pub node_debug_runtime_class: String,
+    //seconds a debug pod has to run before its node is reported failed, 120 when 0.
+    #[serde(default)]
+    pub node_debug_timeout_seconds: u64,
+    //previous logs only of the containers whose last crash ended within these hours, no limit when unset.
+    #[serde(default)]
+    pub previous_logs_max_age_hours: Option<u64>,
+    //last lines of each log, the whole log when 0.
+    #[serde(default)]
+    pub log_tail_lines: i64,
+    //bytes of each log sent by the API server, no cap when 0. The server applies
+    //since and tail first, the cap then keeps the beginning of that window.
+    #[serde(default)]
+    pub log_limit_bytes: i64,
+    //cap of each in-container log file (log_files), log_files::DEFAULT_LOG_FILE_MAX_BYTES when 0.
+    #[serde(default)]
+    pub log_files_max_mb: u64,
+    //pods whose logs are fetched at the same time, log_queue::DEFAULT_LOG_CONCURRENCY when 0.
+    #[serde(default)]
+    pub log_concurrency: usize,
+    //pods not running, not ready, waiting or restarted, plus the ones named by Warning events.
+    #[serde(default)]
+    pub only_failing_pods: bool,
+    #[serde(default)]
+    pub skip_app_collectors: bool,
+    //no pod exec, port-forward or debug pod: logs, describes, events, manifests and api summaries only.
+    #[serde(default)]
+    pub read_only: bool,
+    //gzip level of the archive (0-9), the gzip default when unset.
+    #[serde(default)]
+    pub archive_compression_level: Option<u32>,
+    //values_diff_<release>_<ns>.yaml with the keys differing from the chart defaults.
+    #[serde(default)]
+    pub helm_values_diff: bool,
+    //kube-system workloads, logs and events plus the cloud provider, for managed clusters.
+    #[serde(default)]
+    pub collect_cluster_info: bool,
+    //logs of the CNI and kube-proxy daemonsets, calico/cilium status.
+    #[serde(default)]
+    pub collect_cni: bool,
+    //csi drivers, csi nodes and volume attachments, the not attached ones summarized,
+    //plus the logs of the csi controller/node plugins.
+    #[serde(default)]
+    pub collect_storage_csi: bool,
+    //df and du inside the app pods, disk_usage_paths defaults to /var/log, /data, /dfs and /kafka.
+    #[serde(default)]
+    pub collect_disk_usage: bool,
+    #[serde(default)]
+    pub disk_usage_paths: Vec<String>,
+    //jvm flags, latest GC log and heap_info trend of the app pods (jcmd against pid 1).
+    #[serde(default)]
+    pub collect_jvm_gc: bool,
+    //cap of the GC log file, jvm_gc::DEFAULT_GC_LOG_MAX_BYTES when 0.
+    #[serde(default)]
+    pub jvm_gc_log_max_mb: u64,
+    //names of the log, description and app output files, see naming::FileNameTemplates.
+    #[serde(default)]
+    pub file_name_templates: naming::FileNameTemplates,
+    //per app component label and namespaces, see components::APP_COMPONENTS for the keys.
+    #[serde(default)]
+    pub app_selectors: BTreeMap<String, components::AppSelector>,
+    //median round trip above which an api server call is flagged in the health summary, 1000 when 0.
+    #[serde(default)]
+    pub apiserver_latency_threshold_ms: u64,
+    //clock offset of the api server or a pod flagged in the health summary, 30 when 0.
+    #[serde(default)]
+    pub clock_skew_threshold_seconds: u64,
+    //component -> minimums of vm.max_map_count, fs.file-max and nofile its pods are compared to,
+    //limits::default_minimums for the components not listed.
+    #[serde(default)]
+    pub limit_minimums: BTreeMap<String, limits::LimitMinimums>,
+    //outputs of the big json collectors (elasticsearch state and settings, streaming core) above
+    //this size are written as .gz, never when 0.
+    #[serde(default)]
+    pub compress_outputs_over_mb: u64,
+    //labels whose distinct values over the collected pods go to pods/label_values_report.txt,
+    //label_values::DEFAULT_REPORT_LABELS when empty.
+    #[serde(default)]
+    pub report_labels: Vec<String>,
+    //LF line endings and no BOM in the text outputs, for archives opened on Windows. Binary outputs are untouched.
+    #[serde(default)]
+    pub normalize_line_endings: bool,
+    //component name -> secret holding its credentials, read once at startup.
+    #[serde(default)]
+    pub credentials: BTreeMap<String, credentials::CredentialSource>,
+    //the only secrets whose data is collected, the listed keys still go through the redaction.
+    #[serde(default)]
+    pub secrets_allowlist: Vec<secrets_allowlist::AllowedSecret>,
+    //metrics of each run pushed to this Prometheus Pushgateway, e.g. http://pushgateway:9091.
+    #[serde(default)]
+    pub pushgateway_url: String,
+    //pods and events above which the collection asks for confirmation (--yes).
+    #[serde(default)]
+    pub object_count_limits: sizing::ObjectCountLimits,
+    //ConfigMaps/Secrets and keys referenced by the collected pods checked for existence, pods/reference_check_<ns>.txt.
+    #[serde(default)]
+    pub check_references: bool,
+    //kill time of a kubectl, helm or plugin process, 300 when 0.
+    #[serde(default)]
+    pub external_command_timeout_seconds: u64,
+    //kubectl, helm and plugin processes running at the same time, 16 when 0.
+    #[serde(default)]
+    pub external_command_concurrency: usize,
+    //external collectors run after the built-in ones, outputs under apps/plugins/.
+    #[serde(default)]
+    pub plugins: Vec<plugins::Plugin>,
+}
+
+//user declared collections on top of the built-in ones.
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct CustomCollectors {
+    #[serde(default)]
+    pub file_copies: Vec<FileCopy>,
+}
+
+pub const DEFAULT_FILE_COPY_MAX_BYTES: u64 = 100 * 1024 * 1024;
+
+//files or directories fetched from every pod matching the selector.
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct FileCopy {
+    pub selector: String,
+    //the first container of the pod when empty.
+    #[serde(default)]
+    pub container: String,
+    pub paths: Vec<String>,
+    //cap of each path, as the size of its tar stream.
+    #[serde(default = "default_file_copy_max_bytes")]
+    pub max_bytes: u64,
+    //in-container globs of log files, copied under apps/custom/<ns>_<pod>/files.
+    #[serde(default)]
+    pub log_files: Vec<String>,
+}
+
+fn default_file_copy_max_bytes() -> u64 {
+    DEFAULT_FILE_COPY_MAX_BYTES
+}
+
+impl ConfigFile {
+    pub fn direct_auth(&self) -> bool {
+        !self.api_server_url.is_empty()
+    }
+
+    //the direct authentication fields only make sense together with api_server_url and without a kubeconfig.
+    pub fn validate_auth(&self, explicit_kube_config: bool) -> Result<()> {
+        let direct_fields = !self.bearer_token_env.is_empty()
+            || !self.ca_cert_path.is_empty()
+            || !self.client_cert_path.is_empty()
+            || !self.client_key_path.is_empty()
+            || self.insecure_skip_tls_verify;
+        if direct_fields && !self.direct_auth() {
+            return Err(anyhow::anyhow!(
+                "bearer_token_env, ca_cert_path, client_cert_path, client_key_path and insecure_skip_tls_verify require api_server_url."
+            ));
+        }
+        if self.direct_auth() && self.in_cluster {
+            return Err(anyhow::anyhow!(
+                "api_server_url and in_cluster can not be used together."
+            ));
+        }
+        if self.direct_auth() && explicit_kube_config {
+            return Err(anyhow::anyhow!(
+                "api_server_url and a kube config path can not be used together."
+            ));
+        }
+        if self.client_cert_path.is_empty() != self.client_key_path.is_empty() {
+            return Err(anyhow::anyhow!(
+                "client_cert_path and client_key_path must be given together."
+            ));
+        }
+        Ok(())
+    }
+
+    fn bearer_token(&self) -> Result<Option<String>> {
+        if self.bearer_token_env.is_empty() {
+            return Ok(None);
+        }
+        std::env::var(&self.bearer_token_env)
+            .map(Some)
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "Environment variable {} with the bearer token is not set.",
+                    self.bearer_token_env
+                )
+            })
+    }
+
+    //in memory kubeconfig with a single context built from the direct authentication fields.
+    pub fn direct_auth_kubeconfig(&self) -> Result<Kubeconfig> {
+        let mut cluster = serde_json::json!({ "server": self.api_server_url });
+        if !self.ca_cert_path.is_empty() {
+            cluster["certificate-authority"] = self.ca_cert_path.clone().into();
+        }
+        if self.insecure_skip_tls_verify {
+            cluster["insecure-skip-tls-verify"] = true.into();
+        }
+        let mut user = serde_json::json!({});
+        if let Some(token) = self.bearer_token()? {
+            user["token"] = token.into();
+        }
+        if !self.client_cert_path.is_empty() {
+            user["client-certificate"] = self.client_cert_path.clone().into();
+            user["client-key"] = self.client_key_path.clone().into();
+        }
+        Ok(serde_json::from_value(serde_json::json!({
+            "clusters": [{ "name": self.context_name, "cluster": cluster }],
+            "users": [{ "name": self.context_name, "user": user }],
+            "contexts": [{
+                "name": self.context_name,
+                "context": { "cluster": self.context_name, "user": self.context_name }
+            }],
+            "current-context": self.context_name,
+        }))?)
+    }
+}
+
+pub const SERVICE_ACCOUNT_TOKEN_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
+
+//running inside a pod with a service account token and no kubeconfig file around.
+pub fn detect_in_cluster(kube_config_path: &str) -> bool {
+    std::env::var_os("KUBERNETES_SERVICE_HOST").is_some()
+        && Path::new(SERVICE_ACCOUNT_TOKEN_PATH).exists()
+        && !kubeconfig_paths(kube_config_path)
+            .iter()
+            .any(|p| p.exists())
 }
 
+//name used for the output folders when there is no kubeconfig context, the api server host.
+pub fn in_cluster_context_name() -> Result<String> {
+    Ok(context_name_from_url(
+        &Config::incluster()?.cluster_url.to_string(),
+    ))
+}
+
+//"https://10.0.0.1:6443/" gives "10.0.0.1_6443".
+pub fn context_name_from_url(url: &str) -> String {
+    let host = url.split("://").last().unwrap_or(url);
+    host.split('/').next().unwrap_or(host).replace(':', "_")
+}
+
+static TEMPORARY_KUBECONFIGS: std::sync::atomic::AtomicUsize =
+    std::sync::atomic::AtomicUsize::new(0);
+
+//a kubeconfig written for the subprocesses, readable by the user only and removed when dropped.
+#[derive(Debug)]
+pub struct TemporaryKubeconfig(PathBuf);
+
+impl TemporaryKubeconfig {
+    pub fn write(kubeconfig: &Kubeconfig) -> Result<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "logpv2_kubeconfig_{}_{}.yaml",
+            std::process::id(),
+            TEMPORARY_KUBECONFIGS.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        ));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&path)?;
+        let written = TemporaryKubeconfig(path);
+        file.write_all(serde_yaml::to_string(kubeconfig)?.as_bytes())?;
+        Ok(written)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TemporaryKubeconfig {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+//the kubeconfig of the kubectl and helm subprocesses, set in their KUBECONFIG so a merged list
+//reaches them like it reached the client, and a bearer token never shows on their command line.
+#[derive(Debug, Clone, Default)]
+pub struct SubprocessKubeconfig {
+    //None inside the cluster, kubectl and helm use the service account then.
+    pub path: Option<String>,
+    //the file of the direct authentication, kept while a clone is around.
+    _temporary: Option<Arc<TemporaryKubeconfig>>,
+}
+
+impl SubprocessKubeconfig {
+    //the kubeconfig files of the run, several ones separated like in KUBECONFIG.
+    pub fn files(kube_config_path: &str) -> Self {
+        SubprocessKubeconfig {
+            path: (!kube_config_path.is_empty()).then(|| kube_config_path.to_string()),
+            _temporary: None,
+        }
+    }
+
+    //direct_auth_kubeconfig in a temporary file, the token included.
+    pub fn direct_auth(config: &ConfigFile) -> Result<Self> {
+        let file = TemporaryKubeconfig::write(&config.direct_auth_kubeconfig()?)?;
+        Ok(SubprocessKubeconfig {
+            path: Some(file.path().display().to_string()),
+            _temporary: Some(Arc::new(file)),
+        })
+    }
+
+    //an empty kubeconfig: the subprocesses of a run with an injected client must not fall back
+    //to ~/.kube/config or the KUBECONFIG of the tool, they fail instead.
+    pub fn isolated() -> Self {
+        let null = if cfg!(windows) { "NUL" } else { "/dev/null" };
+        SubprocessKubeconfig::files(null)
+    }
+
+    //injected_client: the client of the run was given by the caller (RunOptions::client).
+    pub fn for_run(
+        config: &ConfigFile,
+        kube_config_path: &str,
+        injected_client: bool,
+    ) -> Result<Self> {
+        if config.in_cluster {
+            Ok(SubprocessKubeconfig::default())
+        } else if config.direct_auth() {
+            SubprocessKubeconfig::direct_auth(config)
+        } else if injected_client && kube_config_path.is_empty() {
+            Ok(SubprocessKubeconfig::isolated())
+        } else {
+            Ok(SubprocessKubeconfig::files(kube_config_path))
+        }
+    }
+
+    pub fn apply(&self, cmd: &mut std::process::Command) {
+        if let Some(p) = &self.path {
+            cmd.env("KUBECONFIG", p);
+        }
+    }
+}
+
+//kubectl against the configured context, or the in-cluster service account.
+pub fn kubectl_command(
+    config: &ConfigFile,
+    kubeconfig: &SubprocessKubeconfig,
+) -> std::process::Command {
+    let mut cmd = std::process::Command::new("kubectl");
+    proxy::set_proxy_env(&mut cmd, config);
+    kubeconfig.apply(&mut cmd);
+    if !config.in_cluster {
+        cmd.args(["--context", config.context_name.as_str()]);
+    }
+    cmd
+}
+
+pub const AUTH_CHECK_INTERVAL_SECONDS: u64 = 120;
+
+//fail early with a clear message when the context user relies on an exec plugin that is not installed.
+pub fn check_exec_plugin(kube_config: &Kubeconfig, context_name: &str) -> Result<()> {
+    let user = kube_config
+        .contexts
+        .iter()
+        .find(|c| c.name == context_name)
+        .and_then(|c| c.context.as_ref())
+        .map(|c| c.user.clone());
+    let Some(command) = user
+        .and_then(|u| kube_config.auth_infos.iter().find(|a| a.name == u))
+        .and_then(|a| a.auth_info.as_ref())
+        .and_then(|a| a.exec.as_ref())
+        .and_then(|e| e.command.clone())
+    else {
+        return Ok(());
+    };
+    let found = if command.contains(std::path::MAIN_SEPARATOR) {
+        Path::new(&command).exists()
+    } else {
+        std::env::var_os("PATH")
+            .map(|p| std::env::split_paths(&p).any(|d| d.join(&command).exists()))
+            .unwrap_or(false)
+    };
+    if !found {
+        return Err(anyhow::anyhow!(
+            "Context {} authenticates with the exec credential plugin {} which was not found, install it or add it to PATH.",
+            context_name,
+            command
+        ));
+    }
+    Ok(())
+}
+
+//cheap periodic api call so an expired or revoked credential shows up as a warning during long runs.
+//the check stops when the returned guard is dropped.
+pub fn spawn_auth_check(client: Client, every: std::time::Duration) -> AbortOnDrop {
+    AbortOnDrop(tokio::task::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            //a second attempt gives the auth layer the chance to refresh an expiring token.
+            if client.apiserver_version().await.is_ok() {
+                continue;
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+            match client.apiserver_version().await.err() {
+                None => {}
+                Some(kube::Error::Api(e)) if e.code == 401 => warn!(
+                    "API server rejected the credentials, the token may have expired: {}",
+                    e.message
+                ),
+                Some(e) => warn!("API server check failed: {}", e),
+            }
+        }
+    }))
+}
+
+//SelfSubjectAccessReview of "create pods/exec" in the namespace, what every exec collector needs.
+pub async fn can_exec(client: Client, namespace: &str) -> Result<bool> {
+    let review = SelfSubjectAccessReview {
+        spec: SelfSubjectAccessReviewSpec {
+            resource_attributes: Some(ResourceAttributes {
+                namespace: Some(namespace.to_string()),
+                verb: Some("create".to_string()),
+                resource: Some("pods".to_string()),
+                subresource: Some("exec".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let reviews: Api<SelfSubjectAccessReview> = Api::all(client);
+    let review = reviews.create(&Default::default(), &review).await?;
+    Ok(review.status.is_some_and(|s| s.allowed))
+}
+
+pub struct AbortOnDrop(pub tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+//a kubeconfig value can be a list of files like KUBECONFIG (colon separated, semicolon on windows).
+pub fn kubeconfig_paths(value: &str) -> Vec<PathBuf> {
+    std::env::split_paths(value)
+        .filter(|p| !p.as_os_str().is_empty())
+        .collect()
+}
+
+//read and merge the kubeconfig files in order, the first file wins like with kubectl.
+pub fn read_kubeconfig(value: &str) -> Result<Kubeconfig> {
+    let mut merged: Option<Kubeconfig> = None;
+    for path in kubeconfig_paths(value) {
+        let next = Kubeconfig::read_from(&path)
+            .map_err(|e| anyhow::anyhow!("Kubeconfig {}: {}", path.display(), e))?;
+        merged = Some(match merged {
+            Some(m) => m
+                .merge(next)
+                .map_err(|e| anyhow::anyhow!("Kubeconfig {}: {}", path.display(), e))?,
+            None => next,
+        });
+    }
+    merged.ok_or_else(|| anyhow::anyhow!("No kubeconfig file given."))
+}
+
+//usage counts the requests of the client for the run summary, warnings keeps its deprecation warnings.
 pub async fn kubernetes_client(
-    kube_config_path: &String,
+    kube_config_path: &str,
     config_file: ConfigFile,
+    usage: Arc<UsageCounters>,
+    warnings: Arc<ApiWarnings>,
 ) -> Result<Client> {
-    let kube_config = Kubeconfig::read_from(kube_config_path)?;
+    if config_file.in_cluster {
+        return client_from_config(Config::incluster()?, &config_file, usage, warnings).await;
+    }
+    let kube_config = if config_file.direct_auth() {
+        if config_file.insecure_skip_tls_verify {
+            warn!(
+                "!!! TLS VERIFICATION OF THE API SERVER IS DISABLED (insecure_skip_tls_verify) !!!"
+            );
+        }
+        config_file.direct_auth_kubeconfig()?
+    } else {
+        read_kubeconfig(kube_config_path)?
+    };
+    check_exec_plugin(&kube_config, &config_file.context_name)?;
 
     //options for the kubernetes configuration.
     let kube_config_options = KubeConfigOptions {
         //context name.
-        context: Some(config_file.context_name),
+        context: Some(config_file.context_name.clone()),
         ..Default::default()
     };
 
-    //create kubernetes configuration.
+    //create kubernetes configuration, exec credential plugins are re-run by the client when their token expires.
     let k_config = Config::from_custom_kubeconfig(kube_config, &kube_config_options).await?;
 
+    client_from_config(k_config, &config_file, usage, warnings).await
+}
+
+//the client goes through a proxy when one applies, a first version call makes connection problems explicit.
+async fn client_from_config(
+    k_config: Config,
+    config_file: &ConfigFile,
+    usage: Arc<UsageCounters>,
+    warnings: Arc<ApiWarnings>,
+) -> Result<Client> {
+    let server = k_config.cluster_url.to_string();
+    let kubeconfig_proxy = k_config.proxy_url.as_ref().map(|p| p.to_string());
+    let proxy = proxy::proxy_for(config_file, &server, kubeconfig_proxy);
+
     //create kubernetes client.
-    let client: Client =
-        Client::try_from(k_config).expect("Expected a valid KUBECONFIG environment variable.");
+    let client: Client = match &proxy {
+        Some(p) => {
+            info!(
+                "Using proxy {} for the API server {}.",
+                proxy::display_proxy(p),
+                server
+            );
+            proxy::proxied_client(k_config, p, usage, warnings)?
+        }
+        None => kube::client::ClientBuilder::try_from(k_config)?
+            .with_layer(&WarningLayer(warnings))
+            .with_layer(&UsageLayer(usage))
+            .build(),
+    };
+    if let Err(e) = client.apiserver_version().await {
+        let through = match &proxy {
+            Some(p) => format!("through proxy {}", proxy::display_proxy(p)),
+            None => "without a proxy".to_string(),
+        };
+        return Err(anyhow::anyhow!(
+            "Could not reach the API server {} {}: {}",
+            server,
+            through,
+            e
+        ));
+    }
 
     Ok(client)
 }
 
+//output_directory_path without trailing separator, or the current directory.
+pub fn output_directory(c: &ConfigFile) -> String {
+    if !c.output_directory_path.is_empty() {
+        c.output_directory_path
+            .strip_suffix(std::path::is_separator)
+            .unwrap_or(&c.output_directory_path)
+            .to_string()
+    } else {
+        std::env::current_dir().unwrap().display().to_string()
+    }
+}
+
+//absolute, with the symlinks of its existing part resolved and the . and .. of the rest applied
+//lexically, for the paths not created yet.
+pub fn resolved_path(path: &Path) -> Result<PathBuf> {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+    let mut resolved = PathBuf::new();
+    for c in absolute.components() {
+        match c {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            c => {
+                resolved.push(c);
+                //a symlink is followed before the next component, as the kernel does.
+                if let std::result::Result::Ok(real) = resolved.canonicalize() {
+                    resolved = real;
+                }
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+//the (label, path) of the run outputs that cannot live in the archived folder: the archive would
+//take itself or the growing tool log in, or be removed with the folder.
+pub fn check_not_nested(archived: &Path, paths: &[(&str, &Path)]) -> Result<()> {
+    let root = resolved_path(archived)?;
+    for (label, path) in paths {
+        if resolved_path(path)?.starts_with(&root) {
+            return Err(anyhow::anyhow!(
+                "The {} {} is inside {}, the folder being archived; choose an output_directory_path and work_directory_path outside of it.",
+                label,
+                path.display(),
+                archived.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+//work_directory_path without trailing separator, or the output directory.
+pub fn work_directory(c: &ConfigFile) -> String {
+    if !c.work_directory_path.is_empty() {
+        c.work_directory_path
+            .strip_suffix(std::path::is_separator)
+            .unwrap_or(&c.work_directory_path)
+            .to_string()
+    } else {
+        output_directory(c)
+    }
+}
+
+//the archive moved into dest_dir, a rename when on the same filesystem. Otherwise it is copied
+//to <dest>.tmp, synced, checked against the size and checksum of the source and renamed; the
+//source is only removed after that, a failed move leaves it where it was.
+pub fn move_archive(src: &Path, dest_dir: &Path) -> Result<PathBuf> {
+    let name = src
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("{} has no file name.", src.display()))?;
+    let dest = dest_dir.join(name);
+    if src == dest {
+        return Ok(dest);
+    }
+    if fs::rename(src, &dest).is_ok() {
+        return Ok(dest);
+    }
+    if let Err(e) = copy_verified(src, &dest) {
+        let _ = fs::remove_file(tmp_path(&dest));
+        return Err(e.context(format!("The archive stays at {}", src.display())));
+    }
+    fs::remove_file(src)?;
+    Ok(dest)
+}
+
+//the copy of move_archive across filesystems, through <dest>.tmp. The source is left as is.
+fn copy_verified(src: &Path, dest: &Path) -> Result<()> {
+    let tmp = tmp_path(dest);
+    fs::copy(src, &tmp)?;
+    fs::File::open(&tmp)?.sync_all()?;
+    let (size, dest_size) = (fs::metadata(src)?.len(), fs::metadata(&tmp)?.len());
+    if size != dest_size {
+        return Err(anyhow::anyhow!(
+            "Copy of {} to {} has {} bytes instead of {}.",
+            src.display(),
+            tmp.display(),
+            dest_size,
+            size
+        ));
+    }
+    if sha256_file(src)? != sha256_file(&tmp)? {
+        return Err(anyhow::anyhow!(
+            "Copy of {} to {} differs from the source.",
+            src.display(),
+            tmp.display()
+        ));
+    }
+    fs::rename(&tmp, dest)?;
+    //the rename itself is durable once the directory is synced.
+    #[cfg(unix)]
+    if let Some(dir) = dest.parent() {
+        fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+//total size of the files under path.
+pub fn dir_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+//durations like "90s", "30m", "6h" or "1d".
+pub fn parse_duration(value: &str) -> Result<std::time::Duration> {
+    let value = value.trim();
+    let (number, unit) = value.split_at(
+        value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len()),
+    );
+    let number: u64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid duration {}.", value))?;
+    let unit_seconds: u64 = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(anyhow::anyhow!("Invalid duration unit {}.", value)),
+    };
+    let seconds = number
+        .checked_mul(unit_seconds)
+        .ok_or_else(|| anyhow::anyhow!("Invalid duration {}.", value))?;
+    if seconds == 0 {
+        return Err(anyhow::anyhow!("Duration must be greater than zero."));
+    }
+    Ok(std::time::Duration::from_secs(seconds))
+}
+
+//keep only the `keep` most recent archives of the context, returns the removed ones.
+pub fn apply_retention(folder: &str, context_name: &str, keep: usize) -> Result<Vec<String>> {
+    let prefix = format!("info_{}_", context_name);
+    let mut archives = fs::read_dir(folder)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|n| n.starts_with(&prefix) && n.ends_with(".tar.gz"))
+        .collect::<Vec<String>>();
+    //names end with the %Y%m%d%H%M%S timestamp so the lexical order is the chronological one.
+    archives.sort();
+    let remove = archives.len().saturating_sub(keep);
+    let mut removed = vec![];
+    for a in archives.into_iter().take(remove) {
+        let path = format!("{}/{}", folder, a);
+        fs::remove_file(&path)?;
+        removed.push(path);
+    }
+    Ok(removed)
+}
+
 pub fn write_file(folder: &str, data: &[u8], filename: &str, error: Error) -> Result<()> {
     if !data.is_empty() {
+        append_atomic(Path::new(&(folder.to_owned() + "/" + filename)), data)?;
+    } else {
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+pub const TMP_SUFFIX: &str = ".tmp";
+
+pub fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(TMP_SUFFIX);
+    PathBuf::from(tmp)
+}
+
+//the data goes to <path>.tmp (after the current content of path, if any) which is renamed over path,
+//a failed or cancelled write never leaves a half file behind.
+pub fn append_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let tmp = tmp_path(path);
+    let written = (|| {
+        if path.exists() {
+            fs::copy(path, &tmp)?;
+        }
         let file = fs::OpenOptions::new()
             .create(true)
             .append(true)
-            .open(folder.to_owned() + "/" + filename)?;
+            .open(&tmp)?;
         let mut file = BufWriter::new(file);
         file.write_all(data)?;
-    } else {
-        return Err(error);
+        file.into_inner()?.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
     }
+    Ok(written?)
+}
 
-    Ok(())
+pub const GZIP_SUFFIX: &str = ".gz";
+
+//data gzipped into path through <path>.tmp, the compressed size is returned.
+pub fn write_gzip_atomic(path: &Path, data: &[u8]) -> Result<u64> {
+    let tmp = tmp_path(path);
+    let written = (|| {
+        let file = BufWriter::new(fs::File::create(&tmp)?);
+        let mut gz = GzEncoder::new(file, Compression::default());
+        gz.write_all(data)?;
+        let file = gz.finish()?.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        let size = file.metadata()?.len();
+        fs::rename(&tmp, path)?;
+        std::io::Result::Ok(size)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    Ok(written?)
+}
+
+//same as append_atomic, replacing any previous content.
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    append_atomic(path, data)
+}
+
+//orphan .tmp files and directories left by cancelled writes, removed before the archive is built.
+pub fn remove_tmp_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut removed = vec![];
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(d) = pending.pop() {
+        for entry in fs::read_dir(&d)? {
+            let path = entry?.path();
+            let tmp = path.to_string_lossy().ends_with(TMP_SUFFIX);
+            if path.is_dir() {
+                if tmp {
+                    fs::remove_dir_all(&path)?;
+                    removed.push(path);
+                } else {
+                    pending.push(path);
+                }
+            } else if tmp {
+                fs::remove_file(&path)?;
+                removed.push(path);
+            }
+        }
+    }
+    Ok(removed)
+}
+
+//recursive listing of every regular file below `dir`, sorted.
+pub fn list_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(d) = pending.pop() {
+        for entry in fs::read_dir(&d)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[derive(Clone, Debug)]
+pub struct PodInfo<A = KubeAccess> {
+    pub name: String,
+    pub namespace: String,
+    pub api: A,
+    pub containers: Vec<String>,
+    //empty while the pod is not scheduled.
+    pub node_name: String,
+    //set once the pod is being deleted (Terminating), it may vanish at any time.
+    pub deletion_timestamp: Option<chrono::DateTime<chrono::Utc>>,
+    pub pod: Pod,
+}
+
+impl<A> PodInfo<A> {
+    pub fn from_pod(pod: &Pod, api: A) -> Self {
+        PodInfo {
+            name: pod.name_any(),
+            namespace: pod.namespace().unwrap_or_default(),
+            api,
+            containers: pod
+                .spec
+                .as_ref()
+                .map(|s| s.containers.iter().map(|c| c.name.clone()).collect())
+                .unwrap_or_default(),
+            node_name: pod
+                .spec
+                .as_ref()
+                .and_then(|s| s.node_name.clone())
+                .unwrap_or_default(),
+            deletion_timestamp: pod.metadata.deletion_timestamp.as_ref().map(|t| t.0),
+            pod: pod.clone(),
+        }
+    }
+
+    pub fn container_status(&self, container: &str) -> Option<&ContainerStatus> {
+        self.pod
+            .status
+            .as_ref()?
+            .container_statuses
+            .as_ref()?
+            .iter()
+            .find(|c| c.name == container)
+    }
 }
 
-pub async fn get_pod_list(
-    pods: Vec<Api<Pod>>,
+pub async fn get_pod_list<A: PodAccess>(
+    pods: Vec<A>,
     plabel: String,
     pfield: String,
-) -> Result<Vec<(String, String, Api<Pod>, Vec<String>)>> {
+) -> Result<Vec<PodInfo<A>>> {
     let mut plns = vec![];
     for p in pods {
-        p.list(&ListParams {
-            label_selector: Some(plabel.clone()),
-            field_selector: Some(pfield.clone()),
-            ..Default::default()
-        })
-        .await?
-        .items
-        .iter()
-        .for_each(|i| {
-            let pl = (
-                i.name_any(),
-                i.namespace().as_ref().unwrap().to_string(),
-                p.clone(),
-                i.spec
-                    .as_ref()
-                    .unwrap()
-                    .containers
-                    .iter()
-                    .map(|c| c.clone().name)
-                    .collect::<Vec<String>>(),
-            );
-            plns.push(pl);
-        })
+        p.list_pods(&plabel, &pfield)
+            .await?
+            .iter()
+            .for_each(|i| plns.push(PodInfo::from_pod(i, p.clone())))
     }
     Ok(plns)
 }
 
-pub async fn get_logs(
+//get_pod_list over an explicit namespace subset.
+pub async fn get_pod_list_in(
+    client: &Client,
+    namespaces: &[String],
+    plabel: String,
+) -> Result<Vec<PodInfo>> {
+    get_pod_list(
+        KubeAccess::for_namespaces(client, namespaces),
+        plabel,
+        "".to_string(),
+    )
+    .await
+}
+
+//the pod api of each namespace, for callers bringing their own client.
+pub fn namespaced_pod_apis(client: &Client, namespaces: &[String]) -> Vec<Api<Pod>> {
+    namespaces
+        .iter()
+        .map(|n| Api::namespaced(client.clone(), n))
+        .collect()
+}
+
+//since_seconds and tail_lines select the lines, limit_bytes caps what is sent of them,
+//the zero or negative values of the config mean unset.
+pub fn log_params(
+    container: String,
+    previous: bool,
+    since_seconds: Option<i64>,
+    tail_lines: Option<i64>,
+    limit_bytes: Option<i64>,
+) -> LogParams {
+    LogParams {
+        container: Some(container),
+        pretty: true,
+        previous,
+        since_seconds: since_seconds.filter(|s| *s > 0),
+        tail_lines: tail_lines.filter(|t| *t > 0),
+        limit_bytes: limit_bytes.filter(|l| *l > 0),
+        ..Default::default()
+    }
+}
+
+//a log as long as the cap was most likely cut by it.
+pub fn log_capped(log: &str, limit_bytes: Option<i64>) -> bool {
+    limit_bytes.is_some_and(|l| l > 0 && log.len() as i64 >= l)
+}
+
+//logs_x.log -> logs_x.capped.log.
+pub fn capped_file_name(filename: &str) -> String {
+    match filename.strip_suffix(".log") {
+        Some(stem) => format!("{}.capped.log", stem),
+        None => format!("{}.capped", filename),
+    }
+}
+
+//the layout of a collected log, in manifest.json for the downstream analysis tools.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    JsonLines,
+    Logfmt,
+    //log4j/logback style entries with java stack traces continued over several lines.
+    MultilineStacktrace,
+    PlainText,
+}
+
+impl LogFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogFormat::JsonLines => "json_lines",
+            LogFormat::Logfmt => "logfmt",
+            LogFormat::MultilineStacktrace => "multiline_stacktrace",
+            LogFormat::PlainText => "plain_text",
+        }
+    }
+}
+
+//non empty lines looked at by classify_log, from the start of the log.
+pub const LOG_FORMAT_SAMPLE_LINES: usize = 200;
+//read from a collected log for the sample, long json lines included.
+pub const LOG_FORMAT_SAMPLE_BYTES: u64 = 256 * 1024;
+//a format holds when at least half of the sampled lines parse as it.
+const LOG_FORMAT_MIN_SHARE: f64 = 0.5;
+//an exception has a few frames, one stray "at ..." line does not make a stack trace.
+const MIN_STACKTRACE_LINES: usize = 2;
+
+fn json_object_line(line: &str) -> bool {
+    line.starts_with('{')
+        && serde_json::from_str::<serde_json::Value>(line).is_ok_and(|v| v.is_object())
+}
+
+//"key=value" with a key of letters, digits, '_', '.' or '-'.
+fn logfmt_pair(token: &str) -> bool {
+    match token.split_once('=') {
+        Some((key, _)) => {
+            !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c))
+        }
+        None => false,
+    }
+}
+
+//at least two pairs making most of the tokens, the quoted values split on spaces aside:
+//ts=2024-01-01T00:00:00Z caller=main.go:42 level=info msg="Server is ready".
+fn logfmt_line(line: &str) -> bool {
+    let tokens = line.split_whitespace().collect::<Vec<&str>>();
+    let pairs = tokens.iter().filter(|t| logfmt_pair(t)).count();
+    let quoted = tokens.iter().filter(|t| t.contains('"')).count();
+    pairs >= 2 && (pairs * 2 >= tokens.len() || pairs + quoted > tokens.len() / 2)
+}
+
+//"\tat org.apache.kafka.Foo.bar(Foo.java:42)", "Caused by: ..", "\t... 12 more", "Suppressed: ..".
+fn stacktrace_line(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    let indented = trimmed.len() < line.len();
+    (indented && trimmed.starts_with("at ") && trimmed.ends_with(')'))
+        || trimmed.starts_with("Caused by: ")
+        || (indented && trimmed.starts_with("Suppressed: "))
+        || (trimmed.starts_with("... ") && trimmed.ends_with(" more"))
+}
+
+//the start of a log4j/logback entry: a date, a [thread] or a level, not indented.
+fn entry_header(line: &str) -> bool {
+    const LEVELS: [&str; 6] = ["TRACE", "DEBUG", "INFO", "WARN", "ERROR", "FATAL"];
+    line.starts_with(|c: char| c.is_ascii_digit() || c == '[')
+        || LEVELS.iter().any(|l| line.starts_with(l))
+}
+
+//the format of a log from its first LOG_FORMAT_SAMPLE_LINES non empty lines, with the share of the
+//sampled lines the format explains as confidence (0 to 1). None for a log without any line.
+pub fn classify_log(text: &str) -> Option<(LogFormat, f64)> {
+    let lines = text
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .take(LOG_FORMAT_SAMPLE_LINES)
+        .collect::<Vec<&str>>();
+    if lines.is_empty() {
+        return None;
+    }
+    let total = lines.len() as f64;
+    let share = |n: usize| (n as f64 / total * 100.0).round() / 100.0;
+    let json = lines.iter().filter(|l| json_object_line(l.trim())).count();
+    if json as f64 >= total * LOG_FORMAT_MIN_SHARE {
+        return Some((LogFormat::JsonLines, share(json)));
+    }
+    let logfmt = lines.iter().filter(|l| logfmt_line(l)).count();
+    if logfmt as f64 >= total * LOG_FORMAT_MIN_SHARE {
+        return Some((LogFormat::Logfmt, share(logfmt)));
+    }
+    let stacktrace = lines.iter().filter(|l| stacktrace_line(l)).count();
+    if stacktrace >= MIN_STACKTRACE_LINES {
+        //the other lines should start entries, the exception line before the frames aside.
+        let explained = lines
+            .iter()
+            .enumerate()
+            .filter(|(i, l)| {
+                stacktrace_line(l)
+                    || entry_header(l)
+                    || lines.get(i + 1).is_some_and(|n| stacktrace_line(n))
+            })
+            .count();
+        return Some((LogFormat::MultilineStacktrace, share(explained)));
+    }
+    Some((
+        LogFormat::PlainText,
+        share(lines.len() - json.max(logfmt).max(stacktrace)),
+    ))
+}
+
+//the start of a file for classify_log, without the line cut by the sample size.
+pub fn read_log_sample(path: &Path) -> Result<String> {
+    let mut sample = vec![];
+    fs::File::open(path)?
+        .take(LOG_FORMAT_SAMPLE_BYTES)
+        .read_to_end(&mut sample)?;
+    if sample.len() as u64 == LOG_FORMAT_SAMPLE_BYTES {
+        if let Some(end) = sample.iter().rposition(|b| *b == b'\n') {
+            sample.truncate(end + 1);
+        }
+    }
+    Ok(String::from_utf8_lossy(&sample).into_owned())
+}
+
+pub async fn get_logs<A: PodAccess>(
     pname: String,
     pcontainer: String,
-    pods: Api<Pod>,
+    pods: A,
     previous: bool,
+    since_seconds: Option<i64>,
+    tail_lines: Option<i64>,
+    limit_bytes: Option<i64>,
 ) -> Result<String> {
     let l = pods
         .logs(
             &pname,
-            &LogParams {
-                container: Some(pcontainer),
-                pretty: true,
-                previous: (previous),
-                ..Default::default()
-            },
+            log_params(pcontainer, previous, since_seconds, tail_lines, limit_bytes),
         )
         .await?;
 
     Ok(l)
 }
 
-pub async fn send_command(
+//pause between the end of a followed stream and the next attach, the container is restarting.
+pub const REATTACH_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Default)]
+pub struct FollowOptions {
+    //lines before the followed ones, all of them when unset.
+    pub tail_lines: Option<i64>,
+    pub since_seconds: Option<i64>,
+    pub timestamps: bool,
+    //a stream ending while the pod still runs is a container restart, attach to the new one.
+    pub reattach_on_restart: bool,
+}
+
+//a pod gone, succeeded, failed or being deleted will not restart its containers.
+async fn pod_terminated<A: PodAccess>(api: &A, pod: &str) -> Result<bool> {
+    let pods = api.list_pods("", &format!("metadata.name={}", pod)).await?;
+    Ok(match pods.first() {
+        Some(p) => {
+            p.metadata.deletion_timestamp.is_some()
+                || p.status
+                    .as_ref()
+                    .and_then(|s| s.phase.as_deref())
+                    .is_some_and(|phase| phase == "Succeeded" || phase == "Failed")
+        }
+        None => true,
+    })
+}
+
+//writes the log lines of the container to sink as they come, until cancelled or the pod terminates.
+//without reattach_on_restart the end of the stream ends the follow.
+pub async fn follow_logs<A: PodAccess, W: AsyncWrite + Unpin>(
+    api: &A,
+    pod: &str,
+    container: &str,
+    options: &FollowOptions,
+    sink: W,
+    cancel: CancellationToken,
+) -> Result<()> {
+    follow_with_delay(api, pod, container, options, sink, cancel, REATTACH_DELAY).await
+}
+
+async fn follow_with_delay<A: PodAccess, W: AsyncWrite + Unpin>(
+    api: &A,
+    pod: &str,
+    container: &str,
+    options: &FollowOptions,
+    mut sink: W,
+    cancel: CancellationToken,
+    reattach_delay: Duration,
+) -> Result<()> {
+    let mut params = LogParams {
+        container: Some(container.to_string()),
+        follow: true,
+        since_seconds: options.since_seconds,
+        tail_lines: options.tail_lines.filter(|t| *t >= 0),
+        timestamps: options.timestamps,
+        ..Default::default()
+    };
+    let mut attached = false;
+    let mut ended = Instant::now();
+    loop {
+        let stream = tokio::select! {
+            _ = cancel.cancelled() => return Ok(()),
+            s = api.log_stream(pod, params.clone()) => s,
+        };
+        match stream {
+            Result::Ok(mut lines) => {
+                attached = true;
+                loop {
+                    let line = tokio::select! {
+                        _ = cancel.cancelled() => return Ok(sink.flush().await?),
+                        l = lines.next() => l,
+                    };
+                    match line {
+                        Some(line) => {
+                            sink.write_all(line?.as_bytes()).await?;
+                            sink.write_all(b"\n").await?;
+                            sink.flush().await?;
+                        }
+                        None => break,
+                    }
+                }
+                ended = Instant::now();
+            }
+            //the restarted container can still be waiting to start.
+            Err(e) if attached => info!("Logs of {}/{} not available yet: {}", pod, container, e),
+            Err(e) => return Err(e),
+        }
+        if !options.reattach_on_restart || pod_terminated(api, pod).await? {
+            return Ok(());
+        }
+        tokio::select! {
+            _ = cancel.cancelled() => return Ok(()),
+            _ = tokio::time::sleep(reattach_delay) => {}
+        }
+        info!("Attaching again to the logs of {}/{}.", pod, container);
+        //only the lines of the new container, whose log starts after the restart.
+        params.tail_lines = None;
+        params.since_seconds = Some(ended.elapsed().as_secs() as i64 + 1);
+    }
+}
+
+//one HTTP/1.1 GET over an established stream (a port-forward), the body of a 2xx answer.
+pub async fn http_over_stream<S>(stream: S, request: &HttpGet) -> Result<String>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, connection) = hyper::client::conn::handshake(stream).await?;
+    let connection = tokio::spawn(connection);
+    let mut builder = hyper::Request::get(request.path.as_str()).header("Host", "localhost");
+    if let Some(a) = &request.authorization {
+        builder = builder.header("Authorization", a.as_str());
+    }
+    let response = sender
+        .send_request(builder.body(hyper::Body::empty())?)
+        .await;
+    let response = match response {
+        std::result::Result::Ok(r) => r,
+        Err(e) => {
+            connection.abort();
+            return Err(e.into());
+        }
+    };
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await;
+    connection.abort();
+    let body = String::from_utf8_lossy(&body?).to_string();
+    if !status.is_success() {
+        return Err(anyhow::anyhow!(
+            "GET {} answered {}: {}",
+            request.path,
+            status,
+            body.chars().take(200).collect::<String>()
+        ));
+    }
+    Ok(body)
+}
+
+//http over Api::portforward to the pod port, the pod needs no http client of its own.
+pub async fn portforward_http_get(pods: &Api<Pod>, pod: &str, request: &HttpGet) -> Result<String> {
+    let mut forwarder = pods.portforward(pod, &[request.port]).await?;
+    let stream = forwarder
+        .take_stream(request.port)
+        .ok_or_else(|| anyhow::anyhow!("No stream for port {} of pod {}.", request.port, pod))?;
+    let body = if request.tls {
+        let mut connector = openssl::ssl::SslConnector::builder(openssl::ssl::SslMethod::tls())?;
+        connector.set_verify(openssl::ssl::SslVerifyMode::NONE);
+        let ssl = connector
+            .build()
+            .configure()?
+            .verify_hostname(false)
+            .into_ssl("localhost")?;
+        let mut stream = tokio_openssl::SslStream::new(ssl, stream)?;
+        std::pin::Pin::new(&mut stream).connect().await?;
+        http_over_stream(stream, request).await
+    } else {
+        http_over_stream(stream, request).await
+    };
+    forwarder.abort();
+    body
+}
+
+//port-forward first, the exec of a shell command (curl, wget) when it is blocked or fails.
+//The credentials reach the shell command as credentials::USERNAME_ENV and PASSWORD_ENV.
+pub async fn http_or_exec<A: PodAccess>(
+    pod: &PodInfo<A>,
+    container: &str,
+    request: &HttpGet,
+    exec_command: &str,
+    credentials: Option<&credentials::Credentials>,
+) -> Result<String> {
+    match pod.api.http_get(&pod.name, request).await {
+        std::result::Result::Ok(body) => Ok(body),
+        Err(e) => {
+            warn!(
+                "Port-forward to {}:{} failed ({}), running the command in the container.",
+                pod.name, request.port, e
+            );
+            pod.api
+                .exec(
+                    &pod.name,
+                    container,
+                    credentials::env_command(credentials, exec_command),
+                )
+                .await
+        }
+    }
+}
+
+pub async fn send_command<A: PodAccess>(
     pod_name: String,
-    pods: Api<Pod>,
+    pods: A,
     container: String,
     command: [&str; 3],
 ) -> Result<String> {
-    let ap = kube::api::AttachParams {
-        container: Some(container),
-        stderr: false,
-        stdin: true,
-        stdout: true,
-        tty: true,
-        ..Default::default()
-    };
+    let command = command.iter().map(|c| c.to_string()).collect();
+    pods.exec(&pod_name, &container, command).await
+    //end of the function.
+}
 
-    let result: AttachedProcess = pods.exec(&pod_name, command, &ap).await?;
-    let buf_std_out_err = get_output(result).await?;
+//the regular files of a tar stream by their relative path, read to the end before anything is written.
+//Links (a copy never follows them), empty files and paths leaving the copy (.., absolute) are left out.
+pub fn tar_files(data: &[u8]) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+    let mut files = vec![];
+    for entry in tar::Archive::new(data).entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        if !entry.header().entry_type().is_file() {
+            if entry.header().entry_type().is_symlink()
+                || entry.header().entry_type().is_hard_link()
+            {
+                info!("{} is a link, not copied.", path.display());
+            }
+            continue;
+        }
+        if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            warn!(
+                "{} leaves the copied directory, not copied.",
+                path.display()
+            );
+            continue;
+        }
+        let mut content = vec![];
+        entry.read_to_end(&mut content)?;
+        if !content.is_empty() {
+            files.push((path, content));
+        }
+    }
+    Ok(files)
+}
 
-    Ok(buf_std_out_err)
-    //end of the function.
+//kubectl cp: tar the remote path inside the container, its files go under local_dir with their
+//directory structure (without the leading /). They are written like any output: text files anonymized
+//and normalized, binary files as is, never when anonymizing. The paths written are returned.
+pub async fn copy_from_pod<A: PodAccess>(
+    pods: A,
+    pod_name: &str,
+    container: &str,
+    remote_path: &str,
+    local_dir: &str,
+    max_bytes: u64,
+    ctx: &RunContext,
+) -> Result<Vec<String>> {
+    let (_, code) = pods
+        .exec_status(
+            pod_name,
+            container,
+            vec![
+                "test".to_string(),
+                "-e".to_string(),
+                remote_path.to_string(),
+            ],
+        )
+        .await?;
+    if code != 0 {
+        return Err(anyhow::anyhow!(
+            "{} does not exist in pod {} container {}.",
+            remote_path,
+            pod_name,
+            container
+        ));
+    }
+    let command = ["tar", "cf", "-", remote_path]
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    let data = pods
+        .exec_bytes(pod_name, container, command, max_bytes)
+        .await
+        .map_err(|e| anyhow::anyhow!("Copy of {} from pod {}: {}", remote_path, pod_name, e))?;
+    //a broken stream fails here and leaves nothing in local_dir.
+    let files = tar_files(&data)
+        .map_err(|e| anyhow::anyhow!("Copy of {} from pod {}: {}", remote_path, pod_name, e))?;
+    let mut written = vec![];
+    for (path, content) in files {
+        let folder = path
+            .parent()
+            .into_iter()
+            .flat_map(|p| p.components())
+            .fold(local_dir.to_string(), |f, c| {
+                ctx.subfolder(&f, &c.as_os_str().to_string_lossy())
+            });
+        let filename = path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if ctx.anonymizer.is_some() && !line_endings::is_text(&content) {
+            let e = anyhow::anyhow!(
+                "/{} is binary and cannot be anonymized, not copied.",
+                path.display()
+            );
+            warn!("{}", e);
+            fs::create_dir_all(&folder)?;
+            ctx.record_failure(
+                &folder,
+                &filename,
+                &format!("exec tar cf - {}", remote_path),
+                &e,
+            );
+            continue;
+        }
+        //copied once, a path given twice (a directory and a file in it) would append otherwise.
+        if Path::new(&folder).join(ctx.anonymize(&filename)).exists() {
+            continue;
+        }
+        fs::create_dir_all(&folder)?;
+        let er = anyhow::anyhow!("Empty /{}.", path.display());
+        ctx.write_file(&folder, &content, &filename, er)?;
+        written.push(format!("{}/{}", folder, ctx.anonymize(&filename)));
+    }
+    Ok(written)
 }
-async fn get_output(mut attached: AttachedProcess) -> Result<String> {
-    let mut result_stout = attached.stdout().unwrap();
-    let mut buf_stout = String::new();
-    result_stout.read_to_string(&mut buf_stout).await?;
-    Ok(buf_stout)
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        access::FakeAccess,
+        anonymize::Anonymizer,
+        test_support::{fixture, run_context, TempDir},
+    };
+    use std::collections::VecDeque;
+
+    //the cluster fixture with the attachments of web-0/nginx queued, each a list of lines.
+    fn following(attachments: &[&[&str]]) -> FakeAccess {
+        let access = FakeAccess::from_fixture(&fixture("access/cluster.json")).unwrap();
+        let queued = attachments
+            .iter()
+            .map(|lines| lines.iter().map(|l| l.to_string()).collect())
+            .collect::<VecDeque<Vec<String>>>();
+        access
+            .follows
+            .lock()
+            .unwrap()
+            .insert("web-0/nginx".to_string(), queued);
+        access
+    }
+
+    //cancelled once the sink holds `until`, after a second at most.
+    async fn follow_until(
+        access: &FakeAccess,
+        pod: &str,
+        options: &FollowOptions,
+        until: &str,
+    ) -> (Result<()>, String) {
+        let cancel = CancellationToken::new();
+        let (sink, mut reader) = tokio::io::duplex(1024);
+        let watcher = {
+            let cancel = cancel.clone();
+            let until = until.to_string();
+            tokio::spawn(async move {
+                let mut out = vec![];
+                let mut buf = [0u8; 256];
+                let deadline = tokio::time::Instant::now() + Duration::from_secs(1);
+                while !String::from_utf8_lossy(&out).contains(&until) {
+                    let read = tokio::time::timeout_at(
+                        deadline,
+                        tokio::io::AsyncReadExt::read(&mut reader, &mut buf),
+                    );
+                    match read.await {
+                        std::result::Result::Ok(std::result::Result::Ok(n)) if n > 0 => {
+                            out.extend(&buf[..n])
+                        }
+                        _ => break,
+                    }
+                }
+                cancel.cancel();
+                String::from_utf8_lossy(&out).to_string()
+            })
+        };
+        let result =
+            follow_with_delay(access, pod, "nginx", options, sink, cancel, Duration::ZERO).await;
+        (result, watcher.await.unwrap())
+    }
+
+    #[test]
+    fn log_params_leave_the_unset_values_out() {
+        let p = log_params("main".to_string(), true, None, Some(0), Some(-1));
+        assert_eq!(p.container.as_deref(), Some("main"));
+        assert!(p.previous);
+        assert_eq!(
+            (p.since_seconds, p.tail_lines, p.limit_bytes),
+            (None, None, None)
+        );
+        //the api server rejects a since of 0.
+        let p = log_params("main".to_string(), false, Some(0), None, None);
+        assert_eq!(p.since_seconds, None);
+    }
+
+    #[test]
+    fn log_params_combine_since_tail_and_limit() {
+        let p = log_params("main".to_string(), false, Some(3600), Some(500), Some(1024));
+        assert_eq!(
+            (p.since_seconds, p.tail_lines, p.limit_bytes),
+            (Some(3600), Some(500), Some(1024))
+        );
+        assert!(!p.previous);
+    }
+
+    #[test]
+    fn a_log_reaching_the_limit_is_capped() {
+        assert!(log_capped("0123456789", Some(10)));
+        assert!(log_capped("0123456789ab", Some(10)));
+        assert!(!log_capped("012345678", Some(10)));
+        assert!(!log_capped("0123456789", None));
+        assert!(!log_capped("0123456789", Some(0)));
+        assert_eq!(
+            capped_file_name("logs_current_a_b_c.log"),
+            "logs_current_a_b_c.capped.log"
+        );
+        assert_eq!(capped_file_name("gc.txt"), "gc.txt.capped");
+    }
+
+    #[test]
+    fn failed_write_leaves_no_partial_file() {
+        let dir = TempDir::new();
+        //the rename over a non empty directory fails once the data is written.
+        let target = dir
+            .write("out.json.gz/keep", b"x")
+            .parent()
+            .unwrap()
+            .to_path_buf();
+        assert!(write_gzip_atomic(&target, b"{\"a\": 1}").is_err());
+        assert!(!tmp_path(&target).exists());
+        assert_eq!(dir.read("out.json.gz/keep"), "x");
+
+        let ctx = run_context(&dir).with_compression(1);
+        let folder = dir.folder();
+        let er = anyhow::anyhow!("empty");
+        assert!(ctx
+            .write_compressible(&folder, b"{\"a\": 1}", "out.json", er)
+            .is_err());
+        assert!(!tmp_path(&target).exists());
+        assert_eq!(
+            ctx.manifest()["out.json.gz"].status,
+            context::FileStatus::Failed
+        );
+    }
+
+    #[test]
+    fn append_keeps_the_previous_content_and_no_tmp_file() {
+        let dir = TempDir::new();
+        let path = dir.path().join("a.log");
+        append_atomic(&path, b"one\n").unwrap();
+        append_atomic(&path, b"two\n").unwrap();
+        assert_eq!(dir.read("a.log"), "one\ntwo\n");
+        write_atomic(&path, b"three\n").unwrap();
+        assert_eq!(dir.read("a.log"), "three\n");
+        assert!(!tmp_path(&path).exists());
+    }
+
+    #[test]
+    fn orphan_tmp_files_are_removed_before_the_archive() {
+        let dir = TempDir::new();
+        dir.write("pods/a.log", b"a");
+        dir.write("pods/b.log.tmp", b"half");
+        dir.write("apps/files_x.tmp/c", b"half");
+        let mut removed = remove_tmp_files(dir.path()).unwrap();
+        removed.sort();
+        assert_eq!(
+            removed,
+            vec![
+                dir.path().join("apps/files_x.tmp"),
+                dir.path().join("pods/b.log.tmp")
+            ]
+        );
+        assert_eq!(dir.read("pods/a.log"), "a");
+    }
+
+    fn merged_list() -> String {
+        std::env::join_paths([fixture("kubeconfig/a.yaml"), fixture("kubeconfig/b.yaml")])
+            .unwrap()
+            .into_string()
+            .unwrap()
+    }
+
+    fn env(cmd: &std::process::Command, name: &str) -> Option<String> {
+        cmd.get_envs()
+            .find(|(k, _)| *k == name)
+            .and_then(|(_, v)| v)
+            .map(|v| v.to_string_lossy().to_string())
+    }
+
+    #[test]
+    fn kubeconfig_list_merges_with_the_first_file_winning() {
+        let merged = read_kubeconfig(&merged_list()).unwrap();
+        let contexts = merged
+            .contexts
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(contexts, ["prod", "staging"]);
+        assert_eq!(merged.current_context.as_deref(), Some("prod"));
+        let shared = merged.clusters.iter().find(|c| c.name == "shared").unwrap();
+        assert_eq!(
+            shared.cluster.as_ref().unwrap().server.as_deref(),
+            Some("https://shared-a.example.com:6443")
+        );
+    }
+
+    #[test]
+    fn kubectl_gets_the_merged_list_in_kubeconfig() {
+        let list = merged_list();
+        let config = ConfigFile {
+            context_name: "staging".to_string(),
+            ..Default::default()
+        };
+        let cmd = kubectl_command(&config, &SubprocessKubeconfig::files(&list));
+        assert_eq!(env(&cmd, "KUBECONFIG"), Some(list));
+        let args = cmd
+            .get_args()
+            .map(|a| a.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(args, ["--context", "staging"]);
+    }
+
+    #[test]
+    fn bearer_token_goes_through_a_private_kubeconfig() {
+        std::env::set_var("LOGPV2_TEST_BEARER_TOKEN", "s3cr3t-token");
+        let config = ConfigFile {
+            context_name: "direct".to_string(),
+            api_server_url: "https://10.0.0.1:6443".to_string(),
+            bearer_token_env: "LOGPV2_TEST_BEARER_TOKEN".to_string(),
+            ..Default::default()
+        };
+        let kubeconfig = SubprocessKubeconfig::for_run(&config, "", false).unwrap();
+        let path = PathBuf::from(kubeconfig.path.clone().unwrap());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let written = read_kubeconfig(&path.display().to_string()).unwrap();
+        assert_eq!(written.current_context.as_deref(), Some("direct"));
+        assert!(fs::read_to_string(&path).unwrap().contains("s3cr3t-token"));
+
+        let cmd = kubectl_command(&config, &kubeconfig);
+        assert_eq!(env(&cmd, "KUBECONFIG"), kubeconfig.path.clone());
+        assert!(!cmd
+            .get_args()
+            .any(|a| a.to_string_lossy().contains("s3cr3t-token")));
+
+        //the file goes with the last clone.
+        let clone = kubeconfig.clone();
+        drop(kubeconfig);
+        assert!(path.exists());
+        drop(clone);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn no_kubeconfig_path_sets_nothing() {
+        let cmd = kubectl_command(&ConfigFile::default(), &SubprocessKubeconfig::files(""));
+        assert_eq!(env(&cmd, "KUBECONFIG"), None);
+    }
+
+    //a pod port answering one request with the raw response, returns the raw request received.
+    fn http_pod(
+        response: impl Into<String>,
+    ) -> (tokio::io::DuplexStream, tokio::task::JoinHandle<String>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let response = response.into();
+        let (client, mut server) = tokio::io::duplex(4096);
+        let handle = tokio::spawn(async move {
+            let mut request = vec![];
+            let mut buffer = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = server.read(&mut buffer).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..n]);
+            }
+            server.write_all(response.as_bytes()).await.unwrap();
+            server.shutdown().await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (client, handle)
+    }
+
+    fn get(path: &str, authorization: Option<&str>) -> HttpGet {
+        HttpGet {
+            port: 9200,
+            tls: false,
+            path: path.to_string(),
+            authorization: authorization.map(String::from),
+        }
+    }
+
+    #[tokio::test]
+    async fn http_body_of_a_successful_get() {
+        let (stream, pod) = http_pod(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n7\r\n{\"ok\": \r\n5\r\ntrue}\r\n0\r\n\r\n",
+        );
+        let request = get("/_cluster/health?pretty", Some("Basic ZWxhc3RpYzpwdw=="));
+        let body = http_over_stream(stream, &request).await.unwrap();
+        assert_eq!(body, "{\"ok\": true}");
+        let received = pod.await.unwrap();
+        assert!(received.starts_with("GET /_cluster/health?pretty HTTP/1.1\r\n"));
+        assert!(received.contains("host: localhost\r\n"));
+        assert!(received.contains("authorization: Basic ZWxhc3RpYzpwdw==\r\n"));
+    }
+
+    #[tokio::test]
+    async fn http_error_status_keeps_the_start_of_the_body() {
+        let body = "x".repeat(300);
+        let response = format!(
+            "HTTP/1.1 401 Unauthorized\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let (stream, pod) = http_pod(response);
+        let error = http_over_stream(stream, &get("/metrics", None))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            format!(
+                "GET /metrics answered 401 Unauthorized: {}",
+                "x".repeat(200)
+            )
+        );
+        assert!(!pod.await.unwrap().contains("authorization"));
+    }
+
+    #[tokio::test]
+    async fn http_stream_closed_before_an_answer() {
+        let (stream, pod) = http_pod("");
+        assert!(http_over_stream(stream, &get("/", None)).await.is_err());
+        pod.await.unwrap();
+        //a body cut short of its length.
+        let (stream, _pod) = http_pod("HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nabc");
+        assert!(http_over_stream(stream, &get("/", None)).await.is_err());
+    }
+
+    //tar cf - of a directory: the files given, a symlink and an entry leaving the directory.
+    fn pod_tar(files: &[(&str, &[u8])]) -> String {
+        let mut tar = tar::Builder::new(vec![]);
+        for (path, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            tar.append_data(&mut header, path, *data).unwrap();
+        }
+        let mut link = tar::Header::new_gnu();
+        link.set_entry_type(tar::EntryType::Symlink);
+        link.set_size(0);
+        tar.append_link(&mut link, "opt/app/logs/current.log", "/etc/passwd")
+            .unwrap();
+        //set_path refuses .., a hostile tar does not.
+        let mut escape = tar::Header::new_gnu();
+        escape.as_old_mut().name[..13].copy_from_slice(b"../escape.txt");
+        escape.set_size(4);
+        escape.set_mode(0o644);
+        escape.set_cksum();
+        tar.append(&escape, &b"evil"[..]).unwrap();
+        String::from_utf8(tar.into_inner().unwrap()).unwrap()
+    }
+
+    fn copy_access(tar: String) -> FakeAccess {
+        let mut access = FakeAccess::default();
+        access
+            .exec
+            .insert("app-0/app/test -e /opt/app/logs".to_string(), String::new());
+        access
+            .exec
+            .insert("app-0/app/tar cf - /opt/app/logs".to_string(), tar);
+        access
+    }
+
+    const SERVER_LOG: &[u8] = b"\xEF\xBB\xBFstarted\r\nready\r\n";
+    const CORE: &[u8] = b"\x7fELF\x00\x01\x02";
+
+    #[tokio::test]
+    async fn copied_files_are_written_like_any_output() {
+        let dir = TempDir::new();
+        let ctx = run_context(&dir).with_line_endings(true);
+        let access = copy_access(pod_tar(&[
+            ("opt/app/logs/server.log", SERVER_LOG),
+            ("opt/app/logs/core", CORE),
+            ("opt/app/logs/empty.log", b""),
+        ]));
+        let local_dir = format!("{}/files", ctx.folders[3]);
+        let written = copy_from_pod(
+            access,
+            "app-0",
+            "app",
+            "/opt/app/logs",
+            &local_dir,
+            1 << 20,
+            &ctx,
+        )
+        .await
+        .unwrap();
+        assert_eq!(written.len(), 2);
+        assert_eq!(
+            dir.read("apps/files/opt/app/logs/server.log"),
+            "started\nready\n"
+        );
+        //binary content passes untouched.
+        let core = fs::read(dir.path().join("apps/files/opt/app/logs/core")).unwrap();
+        assert_eq!(core, CORE);
+        //no link, nothing outside of the directory, no empty file.
+        assert!(!dir
+            .path()
+            .join("apps/files/opt/app/logs/current.log")
+            .exists());
+        assert!(!dir.path().join("apps/escape.txt").exists());
+        assert!(!dir
+            .path()
+            .join("apps/files/opt/app/logs/empty.log")
+            .exists());
+        assert!(ctx
+            .manifest()
+            .contains_key("apps/files/opt/app/logs/server.log"));
+    }
+
+    #[tokio::test]
+    async fn anonymized_copies_leave_the_binary_files_out() {
+        let dir = TempDir::new();
+        let anonymizer = Anonymizer::new(&["worker-a".to_string()], &[]).unwrap();
+        let ctx = run_context(&dir).with_anonymizer(anonymizer);
+        let access = copy_access(pod_tar(&[
+            ("opt/app/logs/worker-a/server.log", b"started on worker-a\n"),
+            ("opt/app/logs/core", CORE),
+        ]));
+        let local_dir = format!("{}/files", ctx.folders[3]);
+        let written = copy_from_pod(
+            access,
+            "app-0",
+            "app",
+            "/opt/app/logs",
+            &local_dir,
+            1 << 20,
+            &ctx,
+        )
+        .await
+        .unwrap();
+        assert_eq!(written.len(), 1);
+        //the directory named after the node is anonymized too.
+        assert_eq!(
+            dir.read("apps/files/opt/app/logs/node-1/server.log"),
+            "started on node-1\n"
+        );
+        let copied = fs::read_dir(dir.path().join("apps/files/opt/app/logs"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(copied, ["core.error", "node-1"].map(String::from).into());
+        assert!(!dir.path().join("apps/files/opt/app/logs/core").exists());
+        assert!(dir
+            .read("apps/files/opt/app/logs/core.error")
+            .contains("/opt/app/logs/core is binary and cannot be anonymized, not copied."));
+    }
+
+    #[tokio::test]
+    async fn missing_and_oversized_paths_fail() {
+        let dir = TempDir::new();
+        let ctx = run_context(&dir);
+        let mut access = copy_access(pod_tar(&[("opt/app/logs/server.log", SERVER_LOG)]));
+        let local_dir = format!("{}/files", ctx.folders[3]);
+        let e = copy_from_pod(
+            access.clone(),
+            "app-0",
+            "app",
+            "/opt/app/logs",
+            &local_dir,
+            100,
+            &ctx,
+        )
+        .await
+        .unwrap_err();
+        assert!(e
+            .to_string()
+            .starts_with("Copy of /opt/app/logs from pod app-0: output larger than 100 bytes"));
+        access
+            .exit_codes
+            .insert("app-0/app/test -e /opt/app/logs".to_string(), 1);
+        let e = copy_from_pod(
+            access,
+            "app-0",
+            "app",
+            "/opt/app/logs",
+            &local_dir,
+            1 << 20,
+            &ctx,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "/opt/app/logs does not exist in pod app-0 container app."
+        );
+        assert!(!Path::new(&local_dir).exists());
+    }
+
+    fn config_dirs(output: &str, work: &str) -> ConfigFile {
+        ConfigFile {
+            output_directory_path: output.to_string(),
+            work_directory_path: work.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn work_directory_defaults_to_the_output_one() {
+        let c = config_dirs("/mnt/nfs/out/", "");
+        assert_eq!(output_directory(&c), "/mnt/nfs/out");
+        assert_eq!(work_directory(&c), "/mnt/nfs/out");
+        let c = config_dirs("/mnt/nfs/out", "/var/tmp/work/");
+        assert_eq!(work_directory(&c), "/var/tmp/work");
+        let current = std::env::current_dir().unwrap().display().to_string();
+        assert_eq!(work_directory(&config_dirs("", "")), current);
+    }
+
+    #[test]
+    fn archive_renamed_on_the_same_filesystem() {
+        let dir = TempDir::new();
+        let src = dir.write("work/info_c_1.tar.gz", b"archive");
+        fs::create_dir_all(dir.path().join("out")).unwrap();
+        let dest = move_archive(&src, &dir.path().join("out")).unwrap();
+        assert_eq!(dest, dir.path().join("out/info_c_1.tar.gz"));
+        assert_eq!(dir.read("out/info_c_1.tar.gz"), "archive");
+        assert!(!src.exists());
+        //already in place.
+        assert_eq!(move_archive(&dest, &dir.path().join("out")).unwrap(), dest);
+        assert!(dest.exists());
+    }
+
+    #[test]
+    fn cross_filesystem_copy_is_verified_then_renamed() {
+        let dir = TempDir::new();
+        let src = dir.write("work/info_c_1.tar.gz", &vec![7u8; 300_000]);
+        //a leftover of an interrupted copy is replaced.
+        dir.write("out/info_c_1.tar.gz.tmp", b"half");
+        let dest = dir.path().join("out/info_c_1.tar.gz");
+        copy_verified(&src, &dest).unwrap();
+        assert_eq!(sha256_file(&dest).unwrap(), sha256_file(&src).unwrap());
+        assert!(!tmp_path(&dest).exists());
+        //the source goes once the copy is in place, by move_archive.
+        assert!(src.exists());
+    }
+
+    #[test]
+    fn failed_move_keeps_the_archive() {
+        let dir = TempDir::new();
+        let src = dir.write("work/info_c_1.tar.gz", b"archive");
+        let e = move_archive(&src, &dir.path().join("missing")).unwrap_err();
+        assert!(e.to_string().starts_with("The archive stays at "));
+        assert_eq!(dir.read("work/info_c_1.tar.gz"), "archive");
+        //copied and checked, but the final rename fails: the copy goes, the source stays.
+        fs::create_dir_all(dir.path().join("out/info_c_1.tar.gz/taken")).unwrap();
+        assert!(move_archive(&src, &dir.path().join("out")).is_err());
+        assert_eq!(dir.read("work/info_c_1.tar.gz"), "archive");
+        assert!(!dir.path().join("out/info_c_1.tar.gz.tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn follow_reattaches_to_the_restarted_container() {
+        let access = following(&[&["first 1", "first 2"], &["second 1"]]);
+        let options = FollowOptions {
+            reattach_on_restart: true,
+            ..Default::default()
+        };
+        //web-0 keeps running, the third attachment stays open until cancelled.
+        let (result, out) = follow_until(&access, "web-0", &options, "second 1\n").await;
+        result.unwrap();
+        assert_eq!(out, "first 1\nfirst 2\nsecond 1\n");
+        assert!(access.follows.lock().unwrap()["web-0/nginx"].is_empty());
+    }
+
+    #[tokio::test]
+    async fn follow_ends_with_the_stream_without_reattach() {
+        let access = following(&[&["first 1"], &["second 1"]]);
+        let (result, out) =
+            follow_until(&access, "web-0", &FollowOptions::default(), "never").await;
+        result.unwrap();
+        assert_eq!(out, "first 1\n");
+        assert_eq!(access.follows.lock().unwrap()["web-0/nginx"].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn follow_ends_when_the_pod_is_gone() {
+        let access = following(&[]);
+        let queued = VecDeque::from([vec!["last line".to_string()], vec![]]);
+        access
+            .follows
+            .lock()
+            .unwrap()
+            .insert("deleted-0/nginx".to_string(), queued);
+        let options = FollowOptions {
+            reattach_on_restart: true,
+            ..Default::default()
+        };
+        //not listed anymore, no restart to wait for.
+        let (result, out) = follow_until(&access, "deleted-0", &options, "never").await;
+        result.unwrap();
+        assert_eq!(out, "last line\n");
+        assert_eq!(access.follows.lock().unwrap()["deleted-0/nginx"].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn follow_cancelled_while_waiting_for_lines() {
+        //no attachment queued left: the stream stays open without lines.
+        let access = following(&[&["only line"]]);
+        let options = FollowOptions {
+            reattach_on_restart: true,
+            ..Default::default()
+        };
+        let (result, out) = follow_until(&access, "web-0", &options, "only line\n").await;
+        result.unwrap();
+        assert_eq!(out, "only line\n");
+    }
+
+    #[tokio::test]
+    async fn follow_fails_when_the_first_attach_fails() {
+        let access = FakeAccess::default();
+        let (result, out) =
+            follow_until(&access, "web-0", &FollowOptions::default(), "never").await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("no logs for web-0/nginx"));
+        assert_eq!(out, "");
+    }
+
+    #[test]
+    fn nested_outputs_are_refused() {
+        let dir = TempDir::new();
+        let collect = dir.path().join("collect");
+        fs::create_dir_all(collect.join("deep")).unwrap();
+        let sibling = dir.path().join("output");
+        let inside = collect.join("output");
+        check_not_nested(&collect, &[("output_directory_path", &sibling)]).unwrap();
+        let e = check_not_nested(
+            &collect,
+            &[
+                ("output_directory_path", &sibling),
+                ("work_directory_path", &inside),
+            ],
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(e.starts_with(&format!(
+            "The work_directory_path {} is inside {}, the folder being archived",
+            inside.display(),
+            collect.display()
+        )));
+        //a name only starting like the folder is not inside it.
+        check_not_nested(&collect, &[("output", &dir.path().join("collect-2"))]).unwrap();
+    }
+
+    #[test]
+    fn dot_dot_is_applied_after_the_symlinks() {
+        let dir = TempDir::new();
+        let collect = dir.path().join("collect");
+        fs::create_dir_all(collect.join("deep")).unwrap();
+        check_not_nested(&collect, &[("output", &collect.join("../output"))]).unwrap();
+        assert!(check_not_nested(
+            &collect,
+            &[("output", &dir.path().join("x/../collect/out"))]
+        )
+        .is_err());
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link");
+            std::os::unix::fs::symlink(&collect, &link).unwrap();
+            //through a symlink to the folder, or with the folder given as one.
+            assert!(check_not_nested(&collect, &[("output", &link.join("out"))]).is_err());
+            assert!(check_not_nested(&link, &[("output", &collect.join("out"))]).is_err());
+            //link/deep/.. is collect, as the kernel resolves it, not the folder of the link.
+            let deep = dir.path().join("deep_link");
+            std::os::unix::fs::symlink(collect.join("deep"), &deep).unwrap();
+            assert_eq!(
+                resolved_path(&deep.join("../out")).unwrap(),
+                collect.canonicalize().unwrap().join("out")
+            );
+            assert!(check_not_nested(&collect, &[("output", &deep.join("../out"))]).is_err());
+        }
+    }
+
+    #[test]
+    fn relative_paths_resolve_from_the_current_directory() {
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+        //not created: the rest of the path is applied lexically.
+        assert_eq!(
+            resolved_path(Path::new("not_created/./a/../b")).unwrap(),
+            cwd.join("not_created/b")
+        );
+        let archived = Path::new("not_created");
+        assert!(check_not_nested(archived, &[("work", Path::new("./not_created/work"))]).is_err());
+        check_not_nested(archived, &[("work", Path::new("not_created/../work"))]).unwrap();
+    }
+
+    fn classified(name: &str) -> (LogFormat, f64) {
+        let text = fs::read_to_string(fixture(&format!("log_format/{}", name))).unwrap();
+        classify_log(&text).unwrap()
+    }
+
+    #[test]
+    fn representative_logs_are_classified() {
+        //a go panic line among the json ones, the blank line is not sampled.
+        assert_eq!(classified("json_lines.log"), (LogFormat::JsonLines, 0.8));
+        //prometheus, quoted values with spaces included.
+        assert_eq!(classified("logfmt.log"), (LogFormat::Logfmt, 0.8));
+        //kafka log4j entries, the exception line before its frames is explained.
+        assert_eq!(
+            classified("stacktrace.log"),
+            (LogFormat::MultilineStacktrace, 1.0)
+        );
+        //nginx, a single indented "at ..(..)" line is not a stack trace.
+        assert_eq!(classified("plain_text.log"), (LogFormat::PlainText, 0.8));
+    }
+
+    #[test]
+    fn classify_log_edge_cases() {
+        assert_eq!(classify_log(""), None);
+        assert_eq!(classify_log("\n  \n\t\n"), None);
+        //json arrays and scalars are not json lines.
+        assert_eq!(
+            classify_log("[1,2]\n\"text\"\n42\n"),
+            Some((LogFormat::PlainText, 1.0))
+        );
+        //a single pair per line is not logfmt.
+        assert_eq!(
+            classify_log("ready=true\nready=false\n").map(|(f, _)| f),
+            Some(LogFormat::PlainText)
+        );
+        //only the first LOG_FORMAT_SAMPLE_LINES lines are sampled.
+        let mut text = "starting\n".repeat(LOG_FORMAT_SAMPLE_LINES);
+        text.push_str(&"{\"msg\":\"ready\"}\n".repeat(LOG_FORMAT_SAMPLE_LINES));
+        assert_eq!(classify_log(&text), Some((LogFormat::PlainText, 1.0)));
+    }
+
+    #[test]
+    fn log_sample_ends_on_a_whole_line() {
+        let dir = TempDir::new();
+        let line = format!("{{\"msg\":\"{}\"}}\n", "x".repeat(1000));
+        let big = line.repeat(LOG_FORMAT_SAMPLE_BYTES as usize / line.len() + 2);
+        let sample = read_log_sample(&dir.write("big.log", big.as_bytes())).unwrap();
+        assert!(sample.len() as u64 <= LOG_FORMAT_SAMPLE_BYTES);
+        assert!(sample.ends_with('\n'));
+        assert_eq!(sample.len() % line.len(), 0);
+        assert_eq!(classify_log(&sample), Some((LogFormat::JsonLines, 1.0)));
+        //a short file is read whole, its last line without newline included.
+        let sample = read_log_sample(&dir.write("short.log", b"a=1 b=2\nc=3 d=4")).unwrap();
+        assert_eq!(sample, "a=1 b=2\nc=3 d=4");
+    }
+
+    #[test]
+    fn context_name_of_the_api_server_url() {
+        assert_eq!(
+            context_name_from_url("https://10.0.0.1:6443/"),
+            "10.0.0.1_6443"
+        );
+        assert_eq!(
+            context_name_from_url("https://10.96.0.1:443"),
+            "10.96.0.1_443"
+        );
+        assert_eq!(
+            context_name_from_url("https://kubernetes.default.svc/api"),
+            "kubernetes.default.svc"
+        );
+        assert_eq!(context_name_from_url("10.0.0.1:6443"), "10.0.0.1_6443");
+    }
+
+    #[test]
+    fn durations_with_units() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration(" 30m ").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_duration("6h").unwrap(), Duration::from_secs(6 * 3600));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86400));
+    }
+
+    #[test]
+    fn invalid_durations() {
+        let error = |v: &str| parse_duration(v).unwrap_err().to_string();
+        assert_eq!(error("0m"), "Duration must be greater than zero.");
+        assert_eq!(error("0"), "Duration must be greater than zero.");
+        assert_eq!(error("5w"), "Invalid duration unit 5w.");
+        assert_eq!(error("1.5h"), "Invalid duration unit 1.5h.");
+        assert_eq!(error("h"), "Invalid duration h.");
+        assert_eq!(error(""), "Invalid duration .");
+        assert_eq!(error("-5m"), "Invalid duration -5m.");
+        //overflows are errors, not a panic or a wrapped value.
+        assert_eq!(
+            error("999999999999999999d"),
+            "Invalid duration 999999999999999999d."
+        );
+        assert_eq!(
+            error("99999999999999999999"),
+            "Invalid duration 99999999999999999999."
+        );
+    }
 }
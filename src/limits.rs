@@ -0,0 +1,286 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use simplelog::{__private::log::warn, info};
+
+use std::collections::BTreeMap;
+
+use crate::{
+    access::PodAccess,
+    collector::Collector,
+    components::{self, APP_COMPONENTS},
+    PodInfo,
+};
+
+//the section headers of the output, what parse_limits splits it on.
+pub const LIMITS_COMMAND: &str = "echo '=== ulimit -a'; ulimit -a 2>&1; \
+echo '=== /proc/sys/vm/max_map_count'; cat /proc/sys/vm/max_map_count 2>&1; \
+echo '=== /proc/sys/fs/file-nr'; cat /proc/sys/fs/file-nr 2>&1; \
+echo '=== /proc/self/limits'; cat /proc/self/limits 2>&1";
+
+//recommended minimums of a component, the unset ones are not compared.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct LimitMinimums {
+    //vm.max_map_count.
+    pub max_map_count: Option<u64>,
+    //fs.file-max, the last field of /proc/sys/fs/file-nr.
+    pub file_max: Option<u64>,
+    //soft Max open files of /proc/self/limits.
+    pub nofile: Option<u64>,
+}
+
+//the vendor recommendations, limit_minimums of the config replaces the entry of a component.
+pub fn default_minimums(component: &str) -> LimitMinimums {
+    match component {
+        "elasticsearch" => LimitMinimums {
+            max_map_count: Some(262144),
+            nofile: Some(65535),
+            ..Default::default()
+        },
+        "kafka" | "kafka_message_bus" => LimitMinimums {
+            max_map_count: Some(262144),
+            nofile: Some(100000),
+            ..Default::default()
+        },
+        "hbase" | "hadoop" => LimitMinimums {
+            nofile: Some(32768),
+            ..Default::default()
+        },
+        _ => LimitMinimums::default(),
+    }
+}
+
+pub fn minimums(overrides: &BTreeMap<String, LimitMinimums>, component: &str) -> LimitMinimums {
+    overrides
+        .get(component)
+        .copied()
+        .unwrap_or_else(|| default_minimums(component))
+}
+
+pub fn validate(overrides: &BTreeMap<String, LimitMinimums>) -> Result<()> {
+    for key in overrides.keys() {
+        if !APP_COMPONENTS.iter().any(|(c, _)| c == key) {
+            return Err(anyhow!(
+                "Unknown limit_minimums component {}, expected one of {}.",
+                key,
+                APP_COMPONENTS.map(|(c, _)| c).join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+//what LIMITS_COMMAND found, None for a file the container could not read.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitValues {
+    pub max_map_count: Option<u64>,
+    pub file_max: Option<u64>,
+    pub nofile: Option<u64>,
+}
+
+fn section<'a>(output: &'a str, header: &str) -> &'a str {
+    let marker = format!("=== {}\n", header);
+    output
+        .split_once(&marker)
+        .map(|(_, rest)| rest.split("\n=== ").next().unwrap_or(rest))
+        .unwrap_or("")
+}
+
+//unlimited counts as the largest value.
+fn limit_value(value: &str) -> Option<u64> {
+    match value {
+        "unlimited" => Some(u64::MAX),
+        v => v.parse().ok(),
+    }
+}
+
+pub fn parse_limits(output: &str) -> LimitValues {
+    let max_map_count = section(output, "/proc/sys/vm/max_map_count")
+        .trim()
+        .parse()
+        .ok();
+    //allocated, unused and maximum file handles.
+    let file_max = section(output, "/proc/sys/fs/file-nr")
+        .split_whitespace()
+        .nth(2)
+        .and_then(|v| v.parse().ok());
+    //"Max open files            1048576              1048576              files".
+    let nofile = section(output, "/proc/self/limits")
+        .lines()
+        .find_map(|l| l.strip_prefix("Max open files"))
+        .and_then(|l| l.split_whitespace().next())
+        .and_then(limit_value);
+    LimitValues {
+        max_map_count,
+        file_max,
+        nofile,
+    }
+}
+
+//"<setting> <value> < <minimum>" of the values below their minimum.
+pub fn below_minimums(values: &LimitValues, minimums: &LimitMinimums) -> Vec<String> {
+    [
+        (
+            "vm.max_map_count",
+            values.max_map_count,
+            minimums.max_map_count,
+        ),
+        ("fs.file-max", values.file_max, minimums.file_max),
+        ("nofile", values.nofile, minimums.nofile),
+    ]
+    .into_iter()
+    .filter_map(|(name, value, minimum)| match (value, minimum) {
+        (Some(v), Some(m)) if v < m => Some(format!("{} {} < {}", name, v, m)),
+        _ => None,
+    })
+    .collect()
+}
+
+async fn pod_limits<A: PodAccess>(pod: &PodInfo<A>) -> Result<String> {
+    let container = pod
+        .containers
+        .first()
+        .ok_or_else(|| anyhow!("Pod {} has no container.", pod.name))?;
+    let command = vec![
+        "/bin/sh".to_string(),
+        "-c".to_string(),
+        LIMITS_COMMAND.to_string(),
+    ];
+    pod.api.exec(&pod.name, container, command).await
+}
+
+//ulimit -a, vm.max_map_count, fs.file-nr and the process limits of every pod of the app components,
+//apps/<component>_<ns>_<pod>_limits.log each; the values below the component minimums go to the health summary.
+pub async fn collect_limits(collector: &Collector, folder: &str) -> Result<()> {
+    let ctx = &collector.ctx;
+    let mut below = vec![];
+    let mut looked = false;
+    for (component, _) in APP_COMPONENTS {
+        let scope = components::component_scope(&collector.config, component);
+        looked |= !scope.namespaces.is_empty();
+        let minimums = minimums(&collector.config.limit_minimums, component);
+        for p in scope.pods(&collector.access).await? {
+            let filename = format!("{}_{}_{}_limits.log", component, p.namespace, p.name);
+            if let Err(e) = ctx.claim_output(folder, &filename, &p.namespace, &p.name) {
+                warn!("{}", e);
+                continue;
+            }
+            let output = match pod_limits(&p).await {
+                Ok(o) => o,
+                Err(e) => {
+                    warn!("Limits of {}: {}", p.name, e);
+                    ctx.record_failure(folder, &filename, LIMITS_COMMAND, &e);
+                    continue;
+                }
+            };
+            let er = anyhow!("Empty limits of {}.", p.name);
+            match ctx.write_file(folder, output.as_bytes(), &filename, er) {
+                Ok(_) => info!("File has been created {}/{}", folder, filename),
+                Err(e) => warn!("{}", e),
+            }
+            for b in below_minimums(&parse_limits(&output), &minimums) {
+                below.push(format!("{}/{} {}", p.namespace, p.name, b));
+            }
+        }
+    }
+    //unknown in the health summary with skip_app_collectors.
+    if !looked {
+        return Ok(());
+    }
+    if !below.is_empty() {
+        warn!(
+            "<yellow>Limits below the recommended minimums: {}.</>",
+            below.join(", ")
+        );
+    }
+    ctx.health.lock().unwrap().limits_below_minimum = Some(below);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    fn captured(name: &str) -> String {
+        std::fs::read_to_string(fixture(&format!("limits/{}.txt", name))).unwrap()
+    }
+
+    #[test]
+    fn limits_parsed_from_each_section() {
+        assert_eq!(
+            parse_limits(&captured("low")),
+            LimitValues {
+                max_map_count: Some(65530),
+                file_max: Some(9223372036854775807),
+                //the soft limit, not the hard one.
+                nofile: Some(1024),
+            }
+        );
+        assert_eq!(
+            parse_limits(&captured("tuned")),
+            LimitValues {
+                max_map_count: Some(262144),
+                file_max: Some(1048576),
+                nofile: Some(u64::MAX),
+            }
+        );
+        //the files a restricted container cannot read are unknown, not zero.
+        assert_eq!(
+            parse_limits(&captured("restricted")),
+            LimitValues {
+                max_map_count: None,
+                file_max: None,
+                nofile: Some(65536),
+            }
+        );
+    }
+
+    #[test]
+    fn values_below_the_component_minimums() {
+        let es = minimums(&BTreeMap::new(), "elasticsearch");
+        assert_eq!(
+            below_minimums(&parse_limits(&captured("low")), &es),
+            vec!["vm.max_map_count 65530 < 262144", "nofile 1024 < 65535"]
+        );
+        assert!(below_minimums(&parse_limits(&captured("tuned")), &es).is_empty());
+        //an unknown value is never reported.
+        let kafka = minimums(&BTreeMap::new(), "kafka");
+        assert_eq!(
+            below_minimums(&parse_limits(&captured("restricted")), &kafka),
+            vec!["nofile 65536 < 100000"]
+        );
+        //the minimum itself is enough.
+        let exact = LimitValues {
+            max_map_count: Some(262144),
+            file_max: None,
+            nofile: Some(65535),
+        };
+        assert!(below_minimums(&exact, &es).is_empty());
+    }
+
+    #[test]
+    fn configured_minimums_replace_the_defaults() {
+        let overrides = BTreeMap::from([(
+            "elasticsearch".to_string(),
+            LimitMinimums {
+                file_max: Some(2000000),
+                ..Default::default()
+            },
+        )]);
+        validate(&overrides).unwrap();
+        let es = minimums(&overrides, "elasticsearch");
+        assert_eq!(
+            below_minimums(&parse_limits(&captured("tuned")), &es),
+            vec!["fs.file-max 1048576 < 2000000"]
+        );
+        //the other components keep theirs, the ones without any compare nothing.
+        assert_eq!(minimums(&overrides, "hbase").nofile, Some(32768));
+        assert_eq!(minimums(&overrides, "prometheus"), LimitMinimums::default());
+        let unknown = BTreeMap::from([("redis".to_string(), LimitMinimums::default())]);
+        assert!(validate(&unknown)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Unknown limit_minimums component redis"));
+    }
+}
@@ -0,0 +1,188 @@
+use std::borrow::Cow;
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+//the start of an output looked at to tell text from binary.
+const SNIFF_BYTES: usize = 8192;
+
+//text when the start has no NUL byte and is UTF-8, a character cut at the end of the sample aside.
+//Tar streams, gzip files and heap dumps all fail one of the two.
+pub fn is_text(data: &[u8]) -> bool {
+    let sample = &data[..data.len().min(SNIFF_BYTES)];
+    if sample.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && sample.len() < data.len(),
+    }
+}
+
+//LF line endings for a stream of chunks: CRLF, CR runs before a LF (the TTY exec artifacts) and
+//bare CRs all become one LF, the BOM at the start is dropped. A CR or a BOM cut by the end of a
+//chunk is held until the next one.
+#[derive(Debug, Default)]
+pub struct LineEndingNormalizer {
+    //bytes of a possible BOM seen so far, until the BOM is ruled out.
+    start: Vec<u8>,
+    started: bool,
+    pending_cr: bool,
+}
+
+impl LineEndingNormalizer {
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<u8> {
+        let mut input = chunk;
+        let held;
+        if !self.started {
+            self.start.extend_from_slice(chunk);
+            if self.start.len() < BOM.len() && BOM.starts_with(&self.start) {
+                return vec![];
+            }
+            self.started = true;
+            held = std::mem::take(&mut self.start);
+            input = held.strip_prefix(BOM).unwrap_or(&held);
+        }
+        let mut out = Vec::with_capacity(input.len());
+        for &b in input {
+            match b {
+                b'\r' => self.pending_cr = true,
+                b'\n' => {
+                    self.pending_cr = false;
+                    out.push(b'\n');
+                }
+                b => {
+                    if self.pending_cr {
+                        out.push(b'\n');
+                        self.pending_cr = false;
+                    }
+                    out.push(b);
+                }
+            }
+        }
+        out
+    }
+
+    //what is still held: a CR at the very end, or a start shorter than a BOM.
+    pub fn finish(&mut self) -> Vec<u8> {
+        let mut out = vec![];
+        if !self.started {
+            self.started = true;
+            let start = std::mem::take(&mut self.start);
+            out = self.push(&start);
+        }
+        if self.pending_cr {
+            self.pending_cr = false;
+            out.push(b'\n');
+        }
+        out
+    }
+}
+
+pub fn normalize(data: &[u8]) -> Vec<u8> {
+    let mut n = LineEndingNormalizer::default();
+    let mut out = n.push(data);
+    out.extend(n.finish());
+    out
+}
+
+//the data normalized when it is text, binary data passes untouched.
+pub fn normalize_text(data: &[u8]) -> Cow<'_, [u8]> {
+    if is_text(data) && (data.contains(&b'\r') || data.starts_with(BOM)) {
+        Cow::Owned(normalize(data))
+    } else {
+        Cow::Borrowed(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //the output of the normalizer fed with the chunks.
+    fn streamed(chunks: &[&[u8]]) -> Vec<u8> {
+        let mut n = LineEndingNormalizer::default();
+        let mut out = vec![];
+        chunks.iter().for_each(|c| out.extend(n.push(c)));
+        out.extend(n.finish());
+        out
+    }
+
+    #[test]
+    fn line_endings_become_one_lf() {
+        assert_eq!(normalize(b"a\r\nb\r\n"), b"a\nb\n");
+        //the TTY exec artifacts.
+        assert_eq!(normalize(b"a\r\r\nb\r\r\r\n"), b"a\nb\n");
+        assert_eq!(normalize(b"a\rb\r"), b"a\nb\n");
+        assert_eq!(normalize(b"a\n\nb"), b"a\n\nb");
+        assert_eq!(normalize(b""), b"");
+    }
+
+    #[test]
+    fn only_the_leading_bom_is_dropped() {
+        assert_eq!(normalize(b"\xEF\xBB\xBFa\r\n"), b"a\n");
+        assert_eq!(normalize(b"a\xEF\xBB\xBF"), b"a\xEF\xBB\xBF");
+        //a start that is only the beginning of a BOM is kept.
+        assert_eq!(normalize(b"\xEF\xBB"), b"\xEF\xBB");
+        assert_eq!(normalize(b"\xEFa"), b"\xEFa");
+    }
+
+    #[test]
+    fn crlf_split_across_chunks() {
+        let mut n = LineEndingNormalizer::default();
+        assert_eq!(n.push(b"a\r"), b"a");
+        assert_eq!(n.push(b"\nb"), b"\nb");
+        //a CR ending the stream is a line end.
+        assert_eq!(n.push(b"\r"), b"");
+        assert_eq!(n.finish(), b"\n");
+        assert_eq!(streamed(&[b"a\r", b"\r", b"\n", b"b"]), b"a\nb");
+    }
+
+    #[test]
+    fn bom_split_across_chunks() {
+        let mut n = LineEndingNormalizer::default();
+        assert_eq!(n.push(b"\xEF"), b"");
+        assert_eq!(n.push(b"\xBB"), b"");
+        assert_eq!(n.push(b"\xBFa\r\n"), b"a\n");
+        assert_eq!(streamed(&[b"\xEF\xBB", b"x"]), b"\xEF\xBBx");
+        assert_eq!(streamed(&[b"\xEF", b""]), b"\xEF");
+    }
+
+    #[test]
+    fn every_split_gives_the_whole_result() {
+        let data = b"\xEF\xBB\xBFfirst\r\nsecond\r\r\nthird\rfourth\r";
+        let whole = normalize(data);
+        assert_eq!(whole, b"first\nsecond\nthird\nfourth\n");
+        for i in 0..=data.len() {
+            assert_eq!(streamed(&[&data[..i], &data[i..]]), whole, "split at {}", i);
+        }
+        let bytes = data.iter().map(std::slice::from_ref).collect::<Vec<_>>();
+        assert_eq!(streamed(&bytes), whole);
+    }
+
+    #[test]
+    fn text_told_from_binary() {
+        assert!(is_text(b"plain text\r\n"));
+        assert!(is_text("héllo".as_bytes()));
+        assert!(is_text(b""));
+        assert!(!is_text(b"text\0with a NUL"));
+        //gzip magic and invalid UTF-8.
+        assert!(!is_text(b"\x1f\x8b\x08\x00\xff\xfe"));
+        //a character cut by the end of the sample, not by the end of the data.
+        let mut long = vec![b'a'; SNIFF_BYTES - 1];
+        long.extend("é".as_bytes());
+        assert!(is_text(&long));
+        assert!(!is_text(&"é".as_bytes()[..1]));
+        //a NUL after the sample is not looked at.
+        let mut late = vec![b'a'; SNIFF_BYTES];
+        late.push(0);
+        assert!(is_text(&late));
+    }
+
+    #[test]
+    fn binary_and_clean_text_pass_untouched() {
+        let binary = b"\x1f\x8b\x08\x00\r\n\0";
+        assert!(matches!(normalize_text(binary), Cow::Borrowed(b) if b == binary));
+        assert!(matches!(normalize_text(b"clean\n"), Cow::Borrowed(_)));
+        assert_eq!(normalize_text(b"dos\r\n").as_ref(), b"dos\n");
+        assert_eq!(normalize_text(b"\xEF\xBB\xBFbom\n").as_ref(), b"bom\n");
+    }
+}
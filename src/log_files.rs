@@ -0,0 +1,229 @@
+use anyhow::{anyhow, Result};
+use simplelog::{__private::log::warn, info};
+
+use std::collections::BTreeSet;
+
+use crate::{access::PodAccess, context::RunContext, copy_from_pod, PodInfo};
+
+//cap of each in-container log file when log_files_max_mb is 0.
+pub const DEFAULT_LOG_FILE_MAX_BYTES: u64 = 50 * 1024 * 1024;
+pub const LOG_FILES_NOTES_FILE: &str = "log_files_notes.txt";
+
+//"<size>\t<path>\t<symlink target>" of the regular files (symlinks followed) matching the glob,
+//the shell leaves a glob matching nothing as is and the -f test drops it.
+pub fn listing_command(glob: &str) -> String {
+    format!(
+        "for f in {}; do [ -f \"$f\" ] || continue; t=''; [ -L \"$f\" ] && t=$(readlink -f \"$f\"); \
+         printf '%s\\t%s\\t%s\\n' \"$(wc -c < \"$f\")\" \"$f\" \"$t\"; done",
+        glob
+    )
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ListedFile {
+    pub path: String,
+    pub size: u64,
+    //resolved target when the path is a symlink.
+    pub link_target: Option<String>,
+}
+
+pub fn parse_listing(listing: &str) -> Vec<ListedFile> {
+    listing
+        .lines()
+        .filter_map(|l| {
+            let mut fields = l.splitn(3, '\t');
+            let size = fields.next()?.trim().parse().ok()?;
+            let path = fields.next()?.to_string();
+            let link_target = fields
+                .next()
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty());
+            Some(ListedFile {
+                path,
+                size,
+                link_target,
+            })
+        })
+        .collect()
+}
+
+//the paths to copy and the notes about the others: a symlink is copied as its target (tar would only
+//keep the link) and only once when several matches point to the same file, files above max_bytes are skipped.
+pub fn select_files(files: &[ListedFile], max_bytes: u64) -> (Vec<String>, Vec<String>) {
+    let mut copy = vec![];
+    let mut notes = vec![];
+    let mut seen = BTreeSet::new();
+    for f in files {
+        let real = f.link_target.clone().unwrap_or_else(|| f.path.clone());
+        if !seen.insert(real.clone()) {
+            continue;
+        }
+        if f.size > max_bytes {
+            notes.push(format!(
+                "{} skipped, {} bytes above the {} bytes cap",
+                f.path, f.size, max_bytes
+            ));
+            continue;
+        }
+        if let Some(t) = &f.link_target {
+            notes.push(format!("{} is a symlink, copied as {}", f.path, t));
+        }
+        copy.push(real);
+    }
+    (copy, notes)
+}
+
+//the files matching the globs in the container, under <folder>/files/ with their path kept.
+//Globs matching nothing and skipped files are noted in <folder>/log_files_notes.txt.
+pub async fn collect_log_files<A: PodAccess>(
+    pod: &PodInfo<A>,
+    container: &str,
+    globs: &[String],
+    max_bytes: u64,
+    folder: &str,
+    ctx: &RunContext,
+) -> Result<()> {
+    let files_dir = format!("{}/files", folder);
+    let mut notes = vec![];
+    for glob in globs {
+        let command = vec![
+            "/bin/sh".to_string(),
+            "-c".to_string(),
+            listing_command(glob),
+        ];
+        let listing = pod
+            .api
+            .exec(&pod.name, container, command)
+            .await
+            .map_err(|e| anyhow!("Listing {} in pod {}: {}", glob, pod.name, e))?;
+        let files = parse_listing(&listing);
+        if files.is_empty() {
+            notes.push(format!("{} matches no file", glob));
+            continue;
+        }
+        let (copy, skipped) = select_files(&files, max_bytes);
+        notes.extend(skipped);
+        for path in copy {
+            //the tar stream is slightly bigger than the file.
+            let cap = max_bytes + 64 * 1024;
+            match copy_from_pod(
+                pod.api.clone(),
+                &pod.name,
+                container,
+                &path,
+                &files_dir,
+                cap,
+                ctx,
+            )
+            .await
+            {
+                Ok(_) => info!("File has been copied {} to {}", path, files_dir),
+                Err(e) => {
+                    warn!("{}", e);
+                    let filename = format!("files{}", path.replace('/', "_"));
+                    ctx.record_failure(folder, &filename, &format!("exec tar cf - {}", path), &e);
+                }
+            }
+        }
+    }
+    if !notes.is_empty() {
+        notes
+            .iter()
+            .for_each(|n| info!("Log files of {}: {}.", pod.name, n));
+        let er = anyhow!("Empty {}.", LOG_FILES_NOTES_FILE);
+        ctx.write_file(
+            folder,
+            (notes.join("\n") + "\n").as_bytes(),
+            LOG_FILES_NOTES_FILE,
+            er,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        access::FakeAccess,
+        test_support::{run_context, TempDir},
+    };
+    use k8s_openapi::api::core::v1::Pod;
+
+    const LISTING: &str = "120\t/opt/app/logs/server.log\t\n\
+                           120\t/opt/app/logs/current.log\t/opt/app/logs/server.log\n\
+                           90\t/opt/app/logs/gc.log\t/var/log/gc/gc.0.log\n\
+                           734003200\t/opt/app/logs/heap.hprof.log\t\n\
+                           not a listing line\n";
+
+    #[test]
+    fn listing_with_sizes_and_link_targets() {
+        let files = parse_listing(LISTING);
+        assert_eq!(files.len(), 4);
+        assert_eq!(
+            files[1],
+            ListedFile {
+                path: "/opt/app/logs/current.log".to_string(),
+                size: 120,
+                link_target: Some("/opt/app/logs/server.log".to_string()),
+            }
+        );
+        assert_eq!(files[0].link_target, None);
+        assert_eq!(files[3].size, 734003200);
+    }
+
+    #[test]
+    fn links_copied_as_their_target_once_and_huge_files_skipped() {
+        let (copy, notes) = select_files(&parse_listing(LISTING), 50 * 1024 * 1024);
+        //current.log points to server.log, already copied.
+        assert_eq!(
+            copy,
+            vec!["/opt/app/logs/server.log", "/var/log/gc/gc.0.log"]
+        );
+        assert_eq!(
+            notes,
+            vec![
+                "/opt/app/logs/gc.log is a symlink, copied as /var/log/gc/gc.0.log",
+                "/opt/app/logs/heap.hprof.log skipped, 734003200 bytes above the 52428800 bytes cap",
+            ]
+        );
+        //the cap applies to the size of the target.
+        let (copy, notes) = select_files(&parse_listing(LISTING), 100);
+        assert_eq!(copy, vec!["/var/log/gc/gc.0.log"]);
+        assert_eq!(notes.len(), 3);
+    }
+
+    #[test]
+    fn listing_command_keeps_only_regular_files() {
+        let command = listing_command("/opt/app/logs/*.log");
+        assert!(command.starts_with("for f in /opt/app/logs/*.log; do [ -f \"$f\" ] || continue;"));
+        assert!(command.contains("readlink -f"));
+    }
+
+    #[tokio::test]
+    async fn unmatched_globs_are_noted() {
+        let dir = TempDir::new();
+        let ctx = run_context(&dir);
+        let pod: Pod = serde_json::from_value(serde_json::json!({
+            "metadata": {"name": "app-0", "namespace": "apps"},
+            "spec": {"containers": [{"name": "app"}]}
+        }))
+        .unwrap();
+        let mut access = FakeAccess::default();
+        for glob in ["/opt/app/logs/*.log", "/var/log/none/*"] {
+            let key = format!("app-0/app//bin/sh -c {}", listing_command(glob));
+            access.exec.insert(key, String::new());
+        }
+        let pod = PodInfo::from_pod(&pod, access);
+        let folder = format!("{}/app", ctx.folders[3]);
+        std::fs::create_dir_all(&folder).unwrap();
+        let globs = ["/opt/app/logs/*.log", "/var/log/none/*"].map(String::from);
+        collect_log_files(&pod, "app", &globs, 1024, &folder, &ctx)
+            .await
+            .unwrap();
+        assert_eq!(
+            dir.read("apps/app/log_files_notes.txt"),
+            "/opt/app/logs/*.log matches no file\n/var/log/none/* matches no file\n"
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+
+use std::{cmp::Reverse, collections::HashMap};
+
+use crate::{preset, previous_logs::last_crash, PodInfo};
+
+//pods whose logs are fetched at the same time when log_concurrency is 0.
+pub const DEFAULT_LOG_CONCURRENCY: usize = 32;
+
+//order of the log queue, the first ones are the most likely to be lost when fetched late.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum LogPriority {
+    //any log of a pod being deleted, it is gone once its grace period ends.
+    Terminating,
+    //previous logs of a container with a known crash, they rotate away with the next restart.
+    CrashedPrevious,
+    //current logs of a pod that is not healthy.
+    FailingCurrent,
+    Other,
+}
+
+pub fn priority<A>(pod: &PodInfo<A>, container: &str, previous: bool) -> LogPriority {
+    if pod.deletion_timestamp.is_some() {
+        LogPriority::Terminating
+    } else if previous {
+        if last_crash(pod, container).is_some() {
+            LogPriority::CrashedPrevious
+        } else {
+            LogPriority::Other
+        }
+    } else if preset::pod_failing(&pod.pod) {
+        LogPriority::FailingCurrent
+    } else {
+        LogPriority::Other
+    }
+}
+
+//one log to fetch, current or previous, with the incremental window of a current log.
+#[derive(Debug, Clone)]
+pub struct LogTask<A> {
+    pub pod: PodInfo<A>,
+    pub container: String,
+    pub previous: bool,
+    pub since_seconds: Option<i64>,
+    pub priority: LogPriority,
+}
+
+impl<A> LogTask<A> {
+    pub fn new(pod: PodInfo<A>, container: String, previous: bool) -> Self {
+        let priority = priority(&pod, &container, previous);
+        LogTask {
+            pod,
+            container,
+            previous,
+            since_seconds: None,
+            priority,
+        }
+    }
+}
+
+//by priority, the most recent crashes first among the previous logs, otherwise in the given order.
+pub fn order<A>(mut tasks: Vec<LogTask<A>>) -> Vec<LogTask<A>> {
+    tasks.sort_by_key(|t| {
+        let crash = t
+            .previous
+            .then(|| last_crash(&t.pod, &t.container))
+            .flatten();
+        (t.priority, Reverse(crash))
+    });
+    tasks
+}
+
+//the ordered tasks as one unit of work per pod, placed where its first task was, its containers in queue order.
+pub fn by_pod<A>(tasks: Vec<LogTask<A>>) -> Vec<Vec<LogTask<A>>> {
+    let mut pods: Vec<Vec<LogTask<A>>> = vec![];
+    let mut index: HashMap<(String, String), usize> = HashMap::new();
+    for t in tasks {
+        let key = (t.pod.namespace.clone(), t.pod.name.clone());
+        match index.get(&key) {
+            Some(&i) => pods[i].push(t),
+            None => {
+                index.insert(key, pods.len());
+                pods.push(vec![t]);
+            }
+        }
+    }
+    pods
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::pod_info;
+    use serde_json::json;
+
+    //a Running pod with one container, restarted when crashed_at is set.
+    fn pod(namespace: &str, name: &str, ready: bool, crashed_at: Option<&str>) -> PodInfo<()> {
+        let last_state = match crashed_at {
+            Some(t) => json!({"terminated": {"exitCode": 1, "finishedAt": t}}),
+            None => json!({}),
+        };
+        pod_info(json!({
+            "metadata": {"name": name, "namespace": namespace},
+            "spec": {"containers": [{"name": "app"}, {"name": "sidecar"}]},
+            "status": {"phase": "Running", "containerStatuses": [
+                {"name": "app", "image": "app", "imageID": "", "ready": ready,
+                 "restartCount": if crashed_at.is_some() { 1 } else { 0 }, "lastState": last_state},
+                {"name": "sidecar", "image": "s", "imageID": "", "ready": true, "restartCount": 0}
+            ]}
+        }))
+    }
+
+    //the same pod with a deletionTimestamp, as listed while it is Terminating.
+    fn terminating(mut pod: PodInfo<()>) -> PodInfo<()> {
+        let at = chrono::DateTime::parse_from_rfc3339("2026-10-17T08:00:00Z").unwrap();
+        pod.deletion_timestamp = Some(at.with_timezone(&chrono::Utc));
+        pod
+    }
+
+    fn keys(tasks: &[LogTask<()>]) -> Vec<String> {
+        tasks
+            .iter()
+            .map(|t| {
+                format!(
+                    "{}/{}{}",
+                    t.pod.name,
+                    t.container,
+                    if t.previous { "/previous" } else { "" }
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn priority_of_current_and_previous_logs() {
+        let healthy = pod("ns", "healthy", true, None);
+        let crashed = pod("ns", "crashed", true, Some("2026-10-17T07:00:00Z"));
+        let not_ready = pod("ns", "not-ready", false, None);
+        assert_eq!(priority(&healthy, "app", false), LogPriority::Other);
+        //no known crash, nothing to lose.
+        assert_eq!(priority(&healthy, "app", true), LogPriority::Other);
+        assert_eq!(
+            priority(&crashed, "app", true),
+            LogPriority::CrashedPrevious
+        );
+        assert_eq!(priority(&crashed, "sidecar", true), LogPriority::Other);
+        //restarted once: the whole pod is failing.
+        assert_eq!(
+            priority(&crashed, "sidecar", false),
+            LogPriority::FailingCurrent
+        );
+        assert_eq!(
+            priority(&not_ready, "app", false),
+            LogPriority::FailingCurrent
+        );
+    }
+
+    #[test]
+    fn order_by_priority_then_latest_crash() {
+        let early = pod("ns", "early", true, Some("2026-10-17T06:00:00Z"));
+        let late = pod("ns", "late", true, Some("2026-10-17T07:00:00Z"));
+        let healthy = pod("ns", "healthy", true, None);
+        let tasks = vec![
+            LogTask::new(healthy.clone(), "app".to_string(), false),
+            LogTask::new(early.clone(), "app".to_string(), false),
+            LogTask::new(early, "app".to_string(), true),
+            LogTask::new(healthy, "sidecar".to_string(), false),
+            LogTask::new(late.clone(), "app".to_string(), true),
+            LogTask::new(late, "app".to_string(), false),
+        ];
+        assert_eq!(
+            keys(&order(tasks)),
+            vec![
+                "late/app/previous",
+                "early/app/previous",
+                "early/app",
+                "late/app",
+                "healthy/app",
+                "healthy/sidecar",
+            ]
+        );
+    }
+
+    #[test]
+    fn one_unit_per_pod_where_its_first_task_was() {
+        let a = pod("ns", "a", true, None);
+        let other_a = pod("other", "a", true, None);
+        let b = pod("ns", "b", true, None);
+        let tasks = vec![
+            LogTask::new(b.clone(), "app".to_string(), false),
+            LogTask::new(a.clone(), "app".to_string(), false),
+            LogTask::new(other_a, "app".to_string(), false),
+            LogTask::new(b, "sidecar".to_string(), false),
+            LogTask::new(a, "sidecar".to_string(), false),
+        ];
+        let units = by_pod(tasks);
+        let units = units
+            .iter()
+            .map(|u| (u[0].pod.namespace.as_str(), keys(u)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            units,
+            vec![
+                ("ns", vec!["b/app".to_string(), "b/sidecar".to_string()]),
+                ("ns", vec!["a/app".to_string(), "a/sidecar".to_string()]),
+                ("other", vec!["a/app".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn terminating_pods_come_first_whatever_their_health() {
+        let healthy = terminating(pod("ns", "healthy", true, None));
+        let crashed = terminating(pod("ns", "crashed", false, Some("2026-10-17T07:00:00Z")));
+        for (p, container, previous) in [
+            (&healthy, "app", false),
+            (&healthy, "app", true),
+            (&crashed, "app", true),
+            (&crashed, "sidecar", false),
+        ] {
+            assert_eq!(priority(p, container, previous), LogPriority::Terminating);
+        }
+        assert!(LogPriority::Terminating < LogPriority::CrashedPrevious);
+    }
+
+    #[test]
+    fn terminating_pods_at_the_front_of_the_queue() {
+        let crashed = pod("ns", "crashed", true, Some("2026-10-17T07:00:00Z"));
+        let not_ready = pod("ns", "not-ready", false, None);
+        let deleted = terminating(pod("ns", "deleted", true, None));
+        let tasks = vec![
+            LogTask::new(not_ready, "app".to_string(), false),
+            LogTask::new(crashed.clone(), "app".to_string(), true),
+            LogTask::new(deleted.clone(), "app".to_string(), false),
+            LogTask::new(crashed, "app".to_string(), false),
+            LogTask::new(deleted.clone(), "sidecar".to_string(), false),
+            LogTask::new(deleted, "app".to_string(), true),
+        ];
+        let ordered = order(tasks);
+        assert_eq!(
+            keys(&ordered),
+            vec![
+                "deleted/app",
+                "deleted/sidecar",
+                "deleted/app/previous",
+                "crashed/app/previous",
+                "not-ready/app",
+                "crashed/app",
+            ]
+        );
+        //the whole pod is one unit, fetched first.
+        let units = by_pod(ordered);
+        assert_eq!(keys(&units[0]).len(), 3);
+        assert_eq!(units[0][0].pod.name, "deleted");
+    }
+}
@@ -0,0 +1,691 @@
+use chrono::Utc;
+use regex::Regex;
+use serde_derive::Serialize;
+use simplelog::{
+    __private::log::{warn, Level, Log, Metadata, Record},
+    Config, LevelFilter, SharedLogger,
+};
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::{self, File},
+    future::Future,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::SystemTime,
+};
+
+//phase of the run, set by RunOptions::phase.
+static CURRENT_PHASE: Mutex<String> = Mutex::new(String::new());
+
+//start of the summary lines, never grouped themselves.
+pub const SUPPRESSED_PREFIX: &str = "Suppressed";
+//error kinds grouped on the terminal, by the text found in the warning (lowercase).
+const WARNING_KINDS: [(&str, &str); 7] = [
+    ("forbidden", "Forbidden"),
+    ("unauthorized", "Unauthorized"),
+    ("not found", "NotFound"),
+    ("notfound", "NotFound"),
+    ("timed out", "Timeout"),
+    ("connection refused", "ConnectionRefused"),
+    ("no log found", "NoLog"),
+];
+
+tokio::task_local! {
+    static LOG_SCOPE: LogScope;
+}
+
+//what a collector task works on, attached to every message it logs.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LogScope {
+    pub namespace: String,
+    pub pod: String,
+    pub container: String,
+    //the node of the node level collectors, which have no pod.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub node: String,
+}
+
+impl LogScope {
+    pub fn pod(namespace: &str, pod: &str, container: &str) -> Self {
+        LogScope {
+            namespace: namespace.to_string(),
+            pod: pod.to_string(),
+            container: container.to_string(),
+            ..Default::default()
+        }
+    }
+
+    //the kubectl calls over a whole namespace.
+    pub fn namespace(namespace: &str) -> Self {
+        LogScope::pod(namespace, "", "")
+    }
+
+    pub fn node(node: &str) -> Self {
+        LogScope {
+            node: node.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Display for LogScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.node.is_empty() {
+            return write!(f, "node/{}", self.node);
+        }
+        write!(f, "{}", self.namespace)?;
+        if !self.pod.is_empty() {
+            write!(f, "/{}", self.pod)?;
+        }
+        if !self.container.is_empty() {
+            write!(f, "/{}", self.container)?;
+        }
+        Ok(())
+    }
+}
+
+//the suppressed warnings of the ending phase are summed up before the next one starts.
+pub fn set_phase(phase: &str, warnings: &WarningAggregator) {
+    flush_warnings(warnings);
+    *CURRENT_PHASE.lock().unwrap() = phase.to_string();
+}
+
+pub fn warning_kind(message: &str) -> Option<&'static str> {
+    let message = message.to_lowercase();
+    WARNING_KINDS
+        .iter()
+        .find(|(text, _)| message.contains(text))
+        .map(|(_, kind)| *kind)
+}
+
+//(error kind, namespace of the task or the phase outside of one), None for a warning shown as is.
+pub fn warning_key(
+    message: &str,
+    scope: Option<&LogScope>,
+    phase: &str,
+) -> Option<(String, String)> {
+    if message.starts_with(SUPPRESSED_PREFIX) {
+        return None;
+    }
+    let kind = warning_kind(message)?;
+    let group = match scope {
+        Some(s) if !s.namespace.is_empty() => format!("namespace {}", s.namespace),
+        _ if phase.is_empty() => "client setup".to_string(),
+        _ => format!("{} phase", phase),
+    };
+    Some((kind.to_string(), group))
+}
+
+//count of the warnings of each group of the current phase, the first one is shown and the others counted.
+//One per run (RunContext::log_warnings), shared with the AggregatedLogger of the terminal.
+#[derive(Debug, Default)]
+pub struct WarningAggregator {
+    groups: Mutex<BTreeMap<(String, String), usize>>,
+}
+
+impl WarningAggregator {
+    //true when the warning is to be shown.
+    pub fn admit(&self, key: Option<(String, String)>) -> bool {
+        let Some(key) = key else {
+            return true;
+        };
+        let mut groups = self.groups.lock().unwrap();
+        let count = groups.entry(key).or_default();
+        *count += 1;
+        *count == 1
+    }
+
+    //"Suppressed <n> similar warnings (<kind>, <group>)." of the groups with repeats, the groups start over.
+    pub fn flush(&self) -> Vec<String> {
+        std::mem::take(&mut *self.groups.lock().unwrap())
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|((kind, group), count)| {
+                format!(
+                    "{} {} similar warnings ({}, {}).",
+                    SUPPRESSED_PREFIX,
+                    count - 1,
+                    kind,
+                    group
+                )
+            })
+            .collect()
+    }
+}
+
+//the summary lines go through the loggers, after the lock is released.
+pub fn flush_warnings(warnings: &WarningAggregator) {
+    let lines = warnings.flush();
+    lines.iter().for_each(|l| warn!("{}", l));
+}
+
+pub fn current_phase() -> String {
+    CURRENT_PHASE.lock().unwrap().clone()
+}
+
+fn current_scope() -> Option<LogScope> {
+    LOG_SCOPE.try_with(|s| s.clone()).ok()
+}
+
+//run the future with the scope attached to its messages, used around each collector task.
+pub async fn scoped<F: Future>(scope: LogScope, f: F) -> F::Output {
+    LOG_SCOPE.scope(scope, f).await
+}
+
+//prefixes the messages of a scoped task with [namespace/pod/container].
+pub struct ScopedLogger(pub Box<dyn SharedLogger>);
+
+impl Log for ScopedLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        match current_scope() {
+            Some(scope) => self.0.log(
+                &Record::builder()
+                    .args(format_args!("[{}] {}", scope, record.args()))
+                    .metadata(record.metadata().clone())
+                    .module_path(record.module_path())
+                    .file(record.file())
+                    .line(record.line())
+                    .build(),
+            ),
+            None => self.0.log(record),
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush()
+    }
+}
+
+impl SharedLogger for ScopedLogger {
+    fn level(&self) -> LevelFilter {
+        self.0.level()
+    }
+
+    fn config(&self) -> Option<&Config> {
+        self.0.config()
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+//terminal side: a warning repeated within a phase (same error kind, same namespace or collector)
+//is shown once, the repeats are counted and summed up by flush_warnings. The file logger gets them all.
+pub struct AggregatedLogger(pub Box<dyn SharedLogger>, pub Arc<WarningAggregator>);
+
+impl Log for AggregatedLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() == Level::Warn {
+            let key = warning_key(
+                &record.args().to_string(),
+                current_scope().as_ref(),
+                &current_phase(),
+            );
+            if !self.1.admit(key) {
+                return;
+            }
+        }
+        self.0.log(record)
+    }
+
+    fn flush(&self) {
+        self.0.flush()
+    }
+}
+
+impl SharedLogger for AggregatedLogger {
+    fn level(&self) -> LevelFilter {
+        self.0.level()
+    }
+
+    fn config(&self) -> Option<&Config> {
+        self.0.config()
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    timestamp: String,
+    level: String,
+    target: &'a str,
+    phase: String,
+    #[serde(flatten)]
+    scope: Option<LogScope>,
+    message: String,
+}
+
+fn ansi() -> &'static Regex {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    ANSI.get_or_init(|| Regex::new("\x1b\\[[0-9;]*m").unwrap())
+}
+
+//--log-format json: one json object per line, without the color codes.
+pub struct JsonLogger {
+    level: LevelFilter,
+    file: Mutex<RotatingFile>,
+}
+
+impl JsonLogger {
+    pub fn new(level: LevelFilter, file: RotatingFile) -> Box<Self> {
+        Box::new(JsonLogger {
+            level,
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = JsonLine {
+            timestamp: Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
+            level: record.level().to_string(),
+            target: record.target(),
+            phase: current_phase(),
+            scope: current_scope(),
+            message: ansi()
+                .replace_all(&record.args().to_string(), "")
+                .to_string(),
+        };
+        if let Ok(l) = serde_json::to_string(&line) {
+            let _ = writeln!(self.file.lock().unwrap(), "{}", l);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = self.file.lock().unwrap().flush();
+    }
+}
+
+impl SharedLogger for JsonLogger {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+//--log-max-mb and --log-keep when not given.
+pub const DEFAULT_LOG_MAX_BYTES: u64 = 100 * 1024 * 1024;
+pub const DEFAULT_LOG_KEEP: usize = 5;
+
+//held while the segments are renamed or read, so a reader never sees a segment twice or misses one.
+static ROTATION: Mutex<()> = Mutex::new(());
+
+//<path>.<index>, 1 is the most recent rotated segment.
+pub fn segment_path(path: &Path, index: usize) -> PathBuf {
+    let mut p = path.as_os_str().to_owned();
+    p.push(format!(".{}", index));
+    PathBuf::from(p)
+}
+
+//the tool log file, rolled to <path>.1 once above max_bytes (never when 0) with the older segments shifted
+//up to <path>.<keep>. A record is never split: the file only rolls at the start of a line.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_bytes: u64,
+    keep: usize,
+    line_start: bool,
+}
+
+impl RotatingFile {
+    pub fn create(path: &Path, max_bytes: u64, keep: usize) -> io::Result<Self> {
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            file: File::create(path)?,
+            size: 0,
+            max_bytes,
+            keep,
+            line_start: true,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let _rotation = ROTATION.lock().unwrap();
+        if self.keep > 0 {
+            let _ = fs::remove_file(segment_path(&self.path, self.keep));
+            for i in (1..self.keep).rev() {
+                let from = segment_path(&self.path, i);
+                if from.exists() {
+                    fs::rename(&from, segment_path(&self.path, i + 1))?;
+                }
+            }
+            fs::rename(&self.path, segment_path(&self.path, 1))?;
+        }
+        self.file = File::create(&self.path)?;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.max_bytes > 0 && self.size >= self.max_bytes && self.line_start {
+            //a failed rotation keeps writing to the current file, tried again after max_bytes more.
+            if let Err(e) = self.rotate() {
+                eprintln!("Log rotation of {}: {}", self.path.display(), e);
+            }
+            self.size = 0;
+        }
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        if n > 0 {
+            self.line_start = buf[n - 1] == b'\n';
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+//the segments of the tool log oldest first, the live file last.
+pub fn log_segments(path: &Path) -> Vec<PathBuf> {
+    let mut segments = (1..)
+        .map(|i| segment_path(path, i))
+        .take_while(|p| p.exists())
+        .collect::<Vec<PathBuf>>();
+    segments.reverse();
+    if path.exists() {
+        segments.push(path.to_path_buf());
+    }
+    segments
+}
+
+//the content of the segments written to since `since` (the start of the run), oldest first.
+pub fn read_segments(path: &Path, since: SystemTime) -> Vec<(PathBuf, io::Result<Vec<u8>>)> {
+    let _rotation = ROTATION.lock().unwrap();
+    log_segments(path)
+        .into_iter()
+        .filter(|p| {
+            fs::metadata(p)
+                .and_then(|m| m.modified())
+                .map_or(true, |m| m >= since)
+        })
+        .map(|p| {
+            let content = fs::read(&p);
+            (p, content)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_display_what_the_task_works_on() {
+        assert_eq!(
+            LogScope::pod("kafka", "kafka-0", "kafka").to_string(),
+            "kafka/kafka-0/kafka"
+        );
+        assert_eq!(
+            LogScope::pod("kafka", "kafka-0", "").to_string(),
+            "kafka/kafka-0"
+        );
+        assert_eq!(LogScope::namespace("kafka").to_string(), "kafka");
+        assert_eq!(LogScope::node("node-a").to_string(), "node/node-a");
+    }
+
+    #[tokio::test]
+    async fn a_scoped_task_groups_its_warnings_by_namespace() {
+        let key = scoped(LogScope::namespace("kafka"), async {
+            warning_key("pods is forbidden", current_scope().as_ref(), "pods")
+        })
+        .await;
+        assert_eq!(
+            key,
+            Some(("Forbidden".to_string(), "namespace kafka".to_string()))
+        );
+        let key = scoped(LogScope::node("node-a"), async {
+            warning_key("pods is forbidden", current_scope().as_ref(), "infra")
+        })
+        .await;
+        assert_eq!(
+            key,
+            Some(("Forbidden".to_string(), "infra phase".to_string()))
+        );
+        assert_eq!(current_scope().map(|s| s.to_string()), None);
+    }
+
+    //the messages that reach the terminal.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<String>>>);
+
+    impl Log for Captured {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    impl SharedLogger for Captured {
+        fn level(&self) -> LevelFilter {
+            LevelFilter::Info
+        }
+
+        fn config(&self) -> Option<&Config> {
+            None
+        }
+
+        fn as_log(self: Box<Self>) -> Box<dyn Log> {
+            Box::new(*self)
+        }
+    }
+
+    fn log(logger: &AggregatedLogger, level: Level, message: &str) {
+        logger.log(
+            &Record::builder()
+                .args(format_args!("{}", message))
+                .level(level)
+                .build(),
+        );
+    }
+
+    fn key(kind: &str, group: &str) -> Option<(String, String)> {
+        Some((kind.to_string(), group.to_string()))
+    }
+
+    #[test]
+    fn repeats_counted_by_kind_and_group() {
+        let warnings = WarningAggregator::default();
+        assert!(warnings.admit(key("Forbidden", "namespace kafka")));
+        assert!(!warnings.admit(key("Forbidden", "namespace kafka")));
+        assert!(!warnings.admit(key("Forbidden", "namespace kafka")));
+        //another namespace or another kind is a group of its own.
+        assert!(warnings.admit(key("Forbidden", "namespace web")));
+        assert!(warnings.admit(key("NotFound", "namespace kafka")));
+        assert!(!warnings.admit(key("NotFound", "namespace kafka")));
+        //a warning without a kind is always shown.
+        assert!(warnings.admit(None));
+        assert!(warnings.admit(None));
+        assert_eq!(
+            warnings.flush(),
+            vec![
+                "Suppressed 2 similar warnings (Forbidden, namespace kafka).",
+                "Suppressed 1 similar warnings (NotFound, namespace kafka).",
+            ]
+        );
+        //the groups start over with the next phase.
+        assert!(warnings.flush().is_empty());
+        assert!(warnings.admit(key("Forbidden", "namespace kafka")));
+    }
+
+    #[tokio::test]
+    async fn terminal_shows_the_first_warning_of_a_group() {
+        let warnings = Arc::new(WarningAggregator::default());
+        let captured = Captured::default();
+        let logger = AggregatedLogger(Box::new(captured.clone()), warnings.clone());
+        scoped(LogScope::namespace("kafka"), async {
+            log(&logger, Level::Warn, "pods is forbidden: User cannot list");
+            log(&logger, Level::Warn, "pods is forbidden: User cannot list");
+        })
+        .await;
+        for _ in 0..3 {
+            log(&logger, Level::Warn, "pods is forbidden: User cannot list");
+        }
+        log(&logger, Level::Warn, "Connection timed out");
+        log(&logger, Level::Warn, "Connection timed out");
+        //only the warnings are grouped.
+        log(&logger, Level::Info, "pods is forbidden: User cannot list");
+        log(&logger, Level::Warn, "Disk almost full");
+        log(&logger, Level::Warn, "Disk almost full");
+        for line in warnings.flush() {
+            log(&logger, Level::Warn, &line);
+        }
+        assert_eq!(
+            *captured.0.lock().unwrap(),
+            vec![
+                "pods is forbidden: User cannot list",
+                "pods is forbidden: User cannot list",
+                "Connection timed out",
+                "pods is forbidden: User cannot list",
+                "Disk almost full",
+                "Disk almost full",
+                "Suppressed 2 similar warnings (Forbidden, client setup).",
+                "Suppressed 1 similar warnings (Forbidden, namespace kafka).",
+                "Suppressed 1 similar warnings (Timeout, client setup).",
+            ]
+        );
+    }
+
+    #[test]
+    fn each_run_counts_its_own_warnings() {
+        let dir = crate::test_support::TempDir::new();
+        let first = crate::test_support::run_context(&dir);
+        let second = crate::test_support::run_context(&dir);
+        let forbidden = warning_key("pods is forbidden", None, "pods");
+        assert!(first.log_warnings.admit(forbidden.clone()));
+        assert!(!first.log_warnings.admit(forbidden.clone()));
+        assert!(second.log_warnings.admit(forbidden.clone()));
+        //the terminal logger and the run share the one given to the context.
+        let shared = Arc::new(WarningAggregator::default());
+        let ctx = crate::context::RunContext::new(vec![]).with_log_warnings(shared.clone());
+        assert!(ctx.log_warnings.admit(forbidden.clone()));
+        assert!(!shared.admit(forbidden));
+        assert_eq!(second.log_warnings.flush().len() + shared.flush().len(), 1);
+    }
+
+    fn segments(dir: &crate::test_support::TempDir) -> Vec<(String, String)> {
+        log_segments(&dir.path().join("tool.log"))
+            .iter()
+            .map(|p| {
+                let name = p.file_name().unwrap().to_string_lossy().to_string();
+                (name, fs::read_to_string(p).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rotation_keeps_the_newest_segments_and_whole_records() {
+        let dir = crate::test_support::TempDir::new();
+        let path = dir.path().join("tool.log");
+        let mut file = RotatingFile::create(&path, 10, 2).unwrap();
+        file.write_all(b"aaaaaaaa\n").unwrap();
+        file.write_all(b"bbbbbbbb\n").unwrap();
+        //the first line over the size starts a new segment.
+        file.write_all(b"cccccccccccc").unwrap();
+        //the rest of a record stays with its start, even past the size.
+        file.write_all(b"cc\n").unwrap();
+        assert_eq!(
+            segments(&dir),
+            vec![
+                ("tool.log.1".to_string(), "aaaaaaaa\nbbbbbbbb\n".to_string()),
+                ("tool.log".to_string(), "cccccccccccccc\n".to_string()),
+            ]
+        );
+        file.write_all(b"dddd\n").unwrap();
+        file.write_all(b"eeee\n").unwrap();
+        file.write_all(b"ffff\n").unwrap();
+        file.write_all(b"gggg\n").unwrap();
+        //the oldest segment is dropped once keep are there.
+        assert_eq!(
+            segments(&dir),
+            vec![
+                ("tool.log.2".to_string(), "cccccccccccccc\n".to_string()),
+                ("tool.log.1".to_string(), "dddd\neeee\n".to_string()),
+                ("tool.log".to_string(), "ffff\ngggg\n".to_string()),
+            ]
+        );
+        assert!(!segment_path(&path, 3).exists());
+    }
+
+    #[test]
+    fn no_rotation_when_unlimited_and_truncation_without_segments() {
+        let dir = crate::test_support::TempDir::new();
+        let path = dir.path().join("tool.log");
+        let mut file = RotatingFile::create(&path, 0, 2).unwrap();
+        for _ in 0..10 {
+            file.write_all(b"aaaaaaaa\n").unwrap();
+        }
+        assert_eq!(log_segments(&path), vec![path.clone()]);
+        assert_eq!(fs::read(&path).unwrap().len(), 90);
+        //keep 0 starts the file over.
+        let mut file = RotatingFile::create(&path, 10, 0).unwrap();
+        file.write_all(b"aaaaaaaaaa\n").unwrap();
+        file.write_all(b"bbbb\n").unwrap();
+        assert_eq!(
+            segments(&dir),
+            vec![("tool.log".to_string(), "bbbb\n".to_string())]
+        );
+    }
+
+    #[test]
+    fn segments_read_oldest_first_since_the_run_started() {
+        let dir = crate::test_support::TempDir::new();
+        let path = dir.path().join("tool.log");
+        assert!(log_segments(&path).is_empty());
+        let mut file = RotatingFile::create(&path, 5, 3).unwrap();
+        for line in ["one\n", "two\n", "three\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+            file.write_all(b"....\n").unwrap();
+        }
+        let read = read_segments(&path, SystemTime::UNIX_EPOCH)
+            .into_iter()
+            .map(|(_, c)| String::from_utf8(c.unwrap()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            read,
+            vec!["one\n....\n", "two\n....\n", "three\n", "....\n"]
+        );
+        //the segments of an earlier run are left out.
+        let later = SystemTime::now() + std::time::Duration::from_secs(3600);
+        assert!(read_segments(&path, later).is_empty());
+    }
+}
@@ -1,77 +1,47 @@
-use anyhow::anyhow;
 use anyhow::Result;
 use chrono::Utc;
 use clap::Command;
-use flate2::write::GzEncoder;
-use flate2::Compression;
 use home::home_dir;
-use k8s_openapi::api::core::v1::{Node, Pod, Secret};
 
-use kube::{api::ListParams, Api, ResourceExt};
-use logpv2::*;
-use serde_derive::Deserialize;
-use serde_derive::Serialize;
+use logpv2::{
+    access::PodAccess,
+    collector::Collector,
+    logging::{self, AggregatedLogger, JsonLogger, RotatingFile, ScopedLogger, WarningAggregator},
+    ordinals::OrdinalRange,
+    report::CollectionInfo,
+    *,
+};
 use simplelog::{
-    info, ColorChoice, CombinedLogger, ConfigBuilder, LevelFilter, TermLogger, TerminalMode,
-    WriteLogger, __private::log::warn,
+    info, ColorChoice, CombinedLogger, ConfigBuilder, LevelFilter, SharedLogger, TermLogger,
+    TerminalMode, WriteLogger, __private::log::warn,
 };
 
 use std::time::Duration;
 
-use std::{
-    env::current_dir,
-    fs::{self, File},
-    path,
-    path::Path,
-};
+use std::{collections::BTreeSet, fs, io::IsTerminal, path::Path, sync::Arc};
 use time::macros::format_description;
+use tokio_util::sync::CancellationToken;
 
-use indicatif::{ProgressBar, ProgressStyle};
 fn read_config_file<P: AsRef<Path>>(path: P) -> Result<ConfigFile> {
     let content = fs::read_to_string(path)?;
     let config_file: ConfigFile = serde_json::from_str(&content)?;
-    Ok(config_file)
-}
-
-fn folder_creation(c: ConfigFile) -> Result<Vec<String>> {
-    let date = Utc::now().format("%Y%m%d%H%M%S");
-    let file_name_gz = format!("info_{}_{}.tar.gz", c.context_name, date);
-    let folder_to_save = if !c.output_directory_path.is_empty() {
-        c.output_directory_path
-            .strip_suffix(path::is_separator)
-            .unwrap_or(&c.output_directory_path)
-            .to_string()
-    } else {
-        current_dir().unwrap().display().to_string()
-    };
-
-    let folder_vec = ["pods", "infra", "helm", "apps"];
-
-    let mut folder_vec = folder_vec
+    //the app selectors can look for their pods in namespaces of their own.
+    let namespaces = config_file
+        .context_namespace
         .iter()
-        .map(|f| format!("{}/info_{}_{}/{}", folder_to_save, c.context_name, date, f))
-        .collect::<Vec<String>>();
-
-    let folder_src_tar = format!("{}/info_{}_{}", folder_to_save, c.context_name, date);
-    folder_vec.push(file_name_gz);
-    folder_vec.push(folder_src_tar);
-    folder_vec.push(folder_to_save);
-    Ok(folder_vec)
-}
-
-pub type LsHelm = Vec<Helm>;
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Helm {
-    pub name: String,
-    pub namespace: String,
-    pub revision: String,
-    pub updated: String,
-    pub status: String,
-    pub chart: String,
-    #[serde(rename = "app_version")]
-    pub app_version: String,
+        .chain(
+            config_file
+                .app_selectors
+                .values()
+                .flat_map(|s| &s.namespaces),
+        )
+        .collect::<BTreeSet<&String>>();
+    config_file.file_name_templates.validate(namespaces.len())?;
+    components::validate_app_selectors(&config_file)?;
+    secrets_allowlist::validate(&config_file.secrets_allowlist)?;
+    plugins::validate(&config_file.plugins)?;
+    limits::validate(&config_file.limit_minimums)?;
+    Ok(config_file)
 }
 
 #[tokio::main]
@@ -82,896 +52,904 @@ async fn main() -> Result<()> {
         ))
         .build();
     let date = Utc::now().format("%Y%m%d%H%M%S");
-    CombinedLogger::init(vec![
-        TermLogger::new(
-            LevelFilter::Info,
-            config.clone(),
-            TerminalMode::Mixed,
-            ColorChoice::Auto,
-        ),
-        WriteLogger::new(
-            LevelFilter::Info,
-            config.clone(),
-            File::create(format!("output_antlog_gather_tool_{}.log", date)).unwrap(),
-        ),
-    ])
-    .unwrap();
-    let kube_config_path = home_dir().unwrap().join(".kube/config").into_os_string();
+    //KUBECONFIG may hold several files, they are merged like kubectl does.
+    let kube_config_path = std::env::var_os("KUBECONFIG")
+        .filter(|k| !k.is_empty())
+        .unwrap_or_else(|| home_dir().unwrap().join(".kube/config").into_os_string());
     //Clap outin
     let value_name = clap::Arg::new("config")
         .short('c')
         .long("config")
         .value_name("CONFIG_FILE_PATH");
+    let value_name = value_name.help("Config File Path").required(true);
+    let in_cluster_arg = clap::Arg::new("in_cluster")
+        .long("in-cluster")
+        .help("Use the pod service account, for running inside the cluster as a Job.")
+        .action(clap::ArgAction::SetTrue);
+    let kube_config_arg = clap::Arg::new("kube_config_path")
+        .short('k')
+        .long("kube_config_path")
+        .value_name("KUBE_CONFIG_PATH")
+        .help("Kubernetes custom config file path, several files can be given like KUBECONFIG.")
+        .default_value(kube_config_path)
+        .required(false);
     let m = Command::new("Antlog its a Gather Debug Logs Tools.")
         .version("1.0.5")
         .author("tuxedo <wtuxedo@proton.me>")
         .about("Gather useful information for debugging issues raised by the support team.")
-        .arg(value_name.help("Config File Path").required(true))
+        .subcommand_negates_reqs(true)
+        .arg(value_name.clone())
+        .arg(kube_config_arg.clone())
+        .arg(in_cluster_arg.clone())
+        .arg(
+            clap::Arg::new("api_server_url")
+                .long("api-server-url")
+                .value_name("URL")
+                .help("Connect to this API server directly instead of using a kubeconfig."),
+        )
+        .arg(
+            clap::Arg::new("bearer_token_env")
+                .long("bearer-token-env")
+                .value_name("ENV_VAR")
+                .help("Environment variable holding the bearer token for --api-server-url."),
+        )
+        .arg(
+            clap::Arg::new("ca_cert_path")
+                .long("ca-cert-path")
+                .value_name("CA_BUNDLE_PATH")
+                .help("CA bundle used to verify --api-server-url."),
+        )
+        .arg(
+            clap::Arg::new("client_cert_path")
+                .long("client-cert-path")
+                .value_name("CERT_PATH")
+                .help("Client certificate for --api-server-url."),
+        )
+        .arg(
+            clap::Arg::new("client_key_path")
+                .long("client-key-path")
+                .value_name("KEY_PATH")
+                .help("Client certificate key for --api-server-url."),
+        )
+        .arg(
+            clap::Arg::new("insecure_skip_tls_verify")
+                .long("insecure-skip-tls-verify")
+                .help("Do not verify the API server certificate, recorded in collection_info.json.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            clap::Arg::new("anonymize")
+                .long("anonymize")
+                .help("Replace IPs, node hostnames and custom strings with stable tokens.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            clap::Arg::new("quick")
+                .long("quick")
+                .help("First response preset: failing pods only, last 2000 log lines, no app collectors, fast compression.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            clap::Arg::new("read_only")
+                .long("read-only")
+                .help("Read API access only: no pod exec, port-forward or debug pod, so no app diagnostics.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            clap::Arg::new("resume")
+                .long("resume")
+                .value_name("COLLECTION_DIR")
+                .help("Finish the archive of a collection folder whose archive step was interrupted, from its last checkpoint.")
+                .conflicts_with_all(["repeat_every", "incremental"]),
+        )
+        .arg(
+            clap::Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .help("Collect even when the object counts or the free disk space are above the configured limits.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            clap::Arg::new("tail_lines")
+                .long("tail-lines")
+                .value_name("LINES")
+                .help("Only the last lines of each log, 0 for the whole log.")
+                .value_parser(clap::value_parser!(i64)),
+        )
+        .arg(
+            clap::Arg::new("log_limit_bytes")
+                .long("log-limit-bytes")
+                .value_name("BYTES")
+                .help("Bytes of each log sent by the API server, 0 for no cap.")
+                .value_parser(clap::value_parser!(i64)),
+        )
+        .arg(
+            clap::Arg::new("node")
+                .long("node")
+                .value_name("NODE_NAME")
+                .help("Only collect the pods scheduled on this node, can be repeated.")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            clap::Arg::new("label_values")
+                .long("label-values")
+                .value_name("LABEL")
+                .help("Report the distinct values of this pod label, can be repeated. Replaces report_labels.")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            clap::Arg::new("incremental")
+                .long("incremental")
+                .help("Only collect the log lines written since the previous successful run.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            clap::Arg::new("repeat_every")
+                .long("repeat-every")
+                .value_name("DURATION")
+                .help("Repeat the collection on a timer, e.g. 30m, 6h or 1d."),
+        )
+        .arg(
+            clap::Arg::new("max_runs")
+                .long("max-runs")
+                .value_name("COUNT")
+                .help("Stop after this many scheduled runs.")
+                .value_parser(clap::value_parser!(usize))
+                .requires("repeat_every"),
+        )
         .arg(
-            clap::Arg::new("kube_config_path")
-                .short('k')
-                .long("kube_config_path")
-                .value_name("KUBE_CONFIG_PATH")
-                .help("Kubernetes custom config file path.")
-                .default_value(kube_config_path)
-                .required(false),
+            clap::Arg::new("log_format")
+                .long("log-format")
+                .value_name("FORMAT")
+                .help("Format of the tool log file, json writes one object per line with the phase and pod fields.")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .global(true),
+        )
+        .arg(
+            clap::Arg::new("log_max_mb")
+                .long("log-max-mb")
+                .value_name("MB")
+                .help("Roll the tool log file over above this size, 0 never rolls it.")
+                .value_parser(clap::value_parser!(u64))
+                .default_value((logging::DEFAULT_LOG_MAX_BYTES / 1024 / 1024).to_string())
+                .global(true),
+        )
+        .arg(
+            clap::Arg::new("log_keep")
+                .long("log-keep")
+                .value_name("COUNT")
+                .help("Rolled over tool log files kept next to the current one.")
+                .value_parser(clap::value_parser!(usize))
+                .default_value(logging::DEFAULT_LOG_KEEP.to_string())
+                .global(true),
+        )
+        .arg(
+            clap::Arg::new("summary_format")
+                .long("summary-format")
+                .value_name("FORMAT")
+                .help("Print a run summary on stdout at the end of the collection, logs go to stderr.")
+                .value_parser(["json"]),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Compare two collection archives.")
+                .arg(clap::Arg::new("archive_a").required(true))
+                .arg(clap::Arg::new("archive_b").required(true))
+                .arg(
+                    clap::Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("DIFF_JSON_PATH")
+                        .help("Path of the machine readable diff.")
+                        .default_value(diff::DIFF_JSON_FILE),
+                ),
+        )
+        .subcommand(
+            Command::new("schema")
+                .about("Print the JSON Schema of manifest.json, collection_info.json or findings.json.")
+                .arg(
+                    clap::Arg::new("document")
+                        .value_parser(report::SCHEMA_DOCUMENTS)
+                        .help("All of them when omitted."),
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check that an archive reads to the end, matches its checksums and its manifest, and report its failed collectors.")
+                .arg(clap::Arg::new("archive").required(true)),
+        )
+        .subcommand(
+            Command::new("inspect")
+                .about("Report the components the apps phase would detect and the commands it would run, without running them.")
+                .arg(value_name.clone())
+                .arg(kube_config_arg.clone())
+                .arg(in_cluster_arg.clone())
+                .arg(
+                    clap::Arg::new("summary_format")
+                        .long("summary-format")
+                        .value_name("FORMAT")
+                        .help("Print the report as json on stdout, logs go to stderr.")
+                        .value_parser(["json"]),
+                ),
+        )
+        .subcommand(
+            Command::new("exec")
+                .about("Run a command in every pod matching a label selector and print each output.")
+                .arg(value_name.clone())
+                .arg(kube_config_arg.clone())
+                .arg(in_cluster_arg.clone())
+                .arg(
+                    clap::Arg::new("selector")
+                        .short('l')
+                        .long("selector")
+                        .value_name("LABEL_SELECTOR")
+                        .help("Label selector of the pods, e.g. app.kubernetes.io/name=kafka.")
+                        .required(true),
+                )
+                .arg(
+                    clap::Arg::new("ordinals")
+                        .long("ordinals")
+                        .value_name("RANGE")
+                        .help("Only the StatefulSet pods with these ordinals, e.g. 0-2,5."),
+                )
+                .arg(
+                    clap::Arg::new("container")
+                        .long("container")
+                        .value_name("CONTAINER")
+                        .help("Container to run the command in, the first container by default."),
+                )
+                .arg(
+                    clap::Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("DIRECTORY")
+                        .help("Also write each pod output to <DIRECTORY>/<namespace>_<pod>.log, - streams the outputs alone to stdout and tar:- a tar archive."),
+                )
+                .arg(
+                    clap::Arg::new("command")
+                        .value_name("COMMAND")
+                        .num_args(1..)
+                        .last(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("pod")
+                .about("Collect the logs, description, manifest, events, owner and node of the given pods into a small archive.")
+                .arg(value_name.clone())
+                .arg(kube_config_arg.clone())
+                .arg(in_cluster_arg.clone())
+                .arg(
+                    clap::Arg::new("logs_only")
+                        .long("logs-only")
+                        .help("Only the current logs of the containers, written to --output.")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    clap::Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("DIRECTORY")
+                        .help("Directory of the archive, or of the logs with --logs-only where - streams them to stdout and tar:- as a tar archive."),
+                )
+                .arg(
+                    clap::Arg::new("pods")
+                        .value_name("NAMESPACE/POD")
+                        .num_args(1..)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("tail")
+                .about("Follow the logs of a pod container on stdout until Ctrl-C or the pod terminates.")
+                .arg(value_name.clone())
+                .arg(kube_config_arg.clone())
+                .arg(in_cluster_arg.clone())
+                .arg(
+                    clap::Arg::new("tail_lines")
+                        .long("tail")
+                        .value_name("LINES")
+                        .help("Lines before the followed ones, all of them by default.")
+                        .value_parser(clap::value_parser!(i64)),
+                )
+                .arg(
+                    clap::Arg::new("timestamps")
+                        .long("timestamps")
+                        .help("Prefix each line with its timestamp.")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    clap::Arg::new("reattach")
+                        .long("reattach")
+                        .help("Keep following the container across its restarts.")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    clap::Arg::new("pod")
+                        .value_name("NAMESPACE/POD")
+                        .required(true),
+                )
+                .arg(
+                    clap::Arg::new("container")
+                        .value_name("CONTAINER")
+                        .help("The first container of the pod by default."),
+                ),
+        )
+        .subcommand(
+            Command::new("watch")
+                .about(
+                    "Collect an incident folder whenever a pod crash loops, is OOMKilled or fails.",
+                )
+                .arg(value_name)
+                .arg(kube_config_arg)
+                .arg(in_cluster_arg)
+                .arg(
+                    clap::Arg::new("debounce")
+                        .long("debounce")
+                        .value_name("SECONDS")
+                        .help("Minimum time between two incident collections of the same pod.")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value(watch::DEFAULT_DEBOUNCE_SECONDS.to_string()),
+                ),
         )
         .get_matches();
-    //Pod
-
-    let config_file_path = m.get_one::<String>("config").unwrap();
-
-    let config_file = read_config_file(config_file_path)?;
 
-    let kube_config_path = m.get_one::<String>("kube_config_path").unwrap();
-
-    let client = kubernetes_client(kube_config_path, config_file.clone()).await?;
-
-    let mut pods = vec![];
-    config_file.context_namespace.iter().for_each(|cn| {
-        let p: Api<Pod> = Api::namespaced(client.clone(), cn);
-        pods.push(p);
-    });
-
-    let mut secret = vec![];
-    config_file.context_namespace.iter().for_each(|cn| {
-        let s: Api<Secret> = Api::namespaced(client.clone(), cn);
-        secret.push(s);
-    });
-
-    std::process::Command::new("clear").status().unwrap();
-    info!("<green>Starting Log collection...</>");
-    info!(
-        "The following kube config path will be use: {}",
-        &kube_config_path
-    );
-
-    let folders = folder_creation(config_file.clone()).unwrap();
-
-    folders.clone()[0..4]
-        .iter()
-        .for_each(|fo| match fs::create_dir_all(fo) {
-            Ok(_) => info!("Directory has been created {}.", fo),
-            Err(e) => {
-                panic!("{}", e)
-            }
-        });
-    info!("Context Name: {}.", &config_file.context_name);
-    info!(
-        "Context NameSpace: {}.",
-        &config_file.context_namespace.join(", ")
-    );
-
-    let mut cmdk = vec![];
-    config_file.context_namespace.iter().for_each(|cn| {
-        let mut cmd = std::process::Command::new("kubectl");
-        cmd.args([
-            "get",
-            "pod",
-            "-n",
-            cn,
-            "--context",
-            &config_file.context_name,
-            "-o",
-            "wide",
-        ]);
-        let file_name = format!("kubernetes_pods_{}.list", cn);
-        cmdk.push((cmd, file_name));
-        let mut cmd = std::process::Command::new("kubectl");
-        cmd.args([
-            "get",
-            "pod",
-            "-n",
-            cn,
-            "--context",
-            &config_file.context_name,
-            "-o",
-            "json",
-        ]);
-        let file_name = format!("kubernetes_pods_{}.json", cn);
-        cmdk.push((cmd, file_name))
-    });
-
-    //Get list pods.
-
-    let pods_list: Vec<(String, String, Api<Pod>, Vec<String>)> =
-        get_pod_list(pods.clone(), "".to_string(), "".to_string()).await?;
-
-    pods_list.iter().for_each(|p| {
-        let file_name = format!("{}_{}.description", p.1, p.0);
-        let mut cmd = std::process::Command::new("kubectl");
-        cmd.args([
-            "describe",
-            "pod",
-            &p.0,
-            "-n",
-            &p.1,
-            "--context",
-            &config_file.context_name,
-        ]);
-
-        cmdk.push((cmd, file_name));
-    });
-    let mut fut_handle_kb: Vec<tokio::task::JoinHandle<()>> = vec![];
-    cmdk.into_iter().for_each(|mut c| {
-        let folders = folders.clone();
-        let task = tokio::task::spawn(async move {
-            let o = c.0.output().expect("kubectl command failed to start");
-            let er = anyhow!("kubectl command empty response {:#?}", c.0);
-            match write_file(&folders[0], &o.stdout, &c.1, er) {
-                Ok(_) => info!("File has been created {}/{}", &folders[0], &c.1),
-                Err(e) => warn!("{}", e),
-            }
-
-            if !o.stderr.is_empty() {
-                warn!("{}", String::from_utf8_lossy(&o.stderr))
-            }
-        });
-        fut_handle_kb.push(task);
-    });
+    //with a json summary stdout only carries the summary, human logs go to stderr.
+    let summary_json = m
+        .subcommand_matches("inspect")
+        .unwrap_or(&m)
+        .get_one::<String>("summary_format")
+        .is_some_and(|f| f == "json");
+    //same when the collected content itself is streamed to stdout.
+    let stream_output = ["exec", "pod"].into_iter().any(|s| {
+        m.subcommand_matches(s)
+            .is_some_and(|s| output::is_stream(s.get_one::<String>("output").map(|o| o.as_str())))
+    }) || m.subcommand_matches("tail").is_some();
+    let terminal_mode = if summary_json || stream_output {
+        TerminalMode::Stderr
+    } else {
+        TerminalMode::Mixed
+    };
+    //messages of a collector task carry its namespace/pod/container.
+    //rolled over in long watch and --repeat-every runs, the archive takes the segments of its run.
+    let log_file = RotatingFile::create(
+        Path::new(&format!("output_antlog_gather_tool_{}.log", date)),
+        m.get_one::<u64>("log_max_mb").copied().unwrap_or_default() * 1024 * 1024,
+        m.get_one::<usize>("log_keep").copied().unwrap_or_default(),
+    )
+    .unwrap();
+    let file_logger: Box<dyn SharedLogger> = if m
+        .get_one::<String>("log_format")
+        .is_some_and(|f| f == "json")
+    {
+        JsonLogger::new(LevelFilter::Info, log_file)
+    } else {
+        Box::new(ScopedLogger(WriteLogger::new(
+            LevelFilter::Info,
+            config.clone(),
+            log_file,
+        )))
+    };
+    //the collection runs one after the other, each counts its repeated warnings in it.
+    let log_warnings = Arc::new(WarningAggregator::default());
+    CombinedLogger::init(vec![
+        Box::new(AggregatedLogger(
+            Box::new(ScopedLogger(TermLogger::new(
+                LevelFilter::Info,
+                config.clone(),
+                terminal_mode,
+                ColorChoice::Auto,
+            ))),
+            log_warnings.clone(),
+        )),
+        file_logger,
+    ])
+    .unwrap();
 
-    for handle in fut_handle_kb {
-        match handle.await {
-            Ok(_) => {}
-            Err(e) => {
-                warn!("{}", e)
-            }
-        }
+    if let Some(d) = m.subcommand_matches("diff") {
+        let result = diff::diff_archives(
+            Path::new(d.get_one::<String>("archive_a").unwrap()),
+            Path::new(d.get_one::<String>("archive_b").unwrap()),
+        )?;
+        println!("{}", diff::render_diff(&result));
+        let output = d.get_one::<String>("output").unwrap();
+        fs::write(output, serde_json::to_string_pretty(&result)?)?;
+        info!("File has been created {}", output);
+        return Ok(());
     }
-    let mut fut_handle_lc: Vec<tokio::task::JoinHandle<()>> = vec![];
-    if config_file.current_logs {
-        pods_list.clone().into_iter().for_each(|pl| {
-            let container = pl.3.clone();
-            for c in container {
-                let pl = pl.clone();
-                let pname = pl.0.clone();
-                let folders = folders.clone();
-                let task = tokio::task::spawn(async move {
-                    let l = get_logs(pname, c.to_string(), pl.2, false).await;
-                    match l {
-                        Ok(l) => {
-                            let filename = format!("logs_current_{}_{}_{}.log", &pl.1, pl.0, c);
-                            let er = anyhow!("No Log found {} on container {}.", pl.0, c);
-                            match write_file(&folders[0], l.as_bytes(), &filename, er) {
-                                Ok(_) => {
-                                    info!("File has been created {}/{}", &folders[0], filename)
-                                }
-                                Err(e) => {
-                                    warn!("{}", e)
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            warn!("{}", e)
-                        }
-                    }
-                });
 
-                fut_handle_lc.push(task);
-            }
-        });
-    }
-    for handle in fut_handle_lc {
-        match handle.await {
-            Ok(_) => {}
-            Err(e) => {
-                warn!("{}", e)
+    if let Some(s) = m.subcommand_matches("schema") {
+        match s.get_one::<String>("document") {
+            Some(d) => println!("{}", report::schema(d)?),
+            None => {
+                for d in report::SCHEMA_DOCUMENTS {
+                    println!("{}", report::schema(d)?);
+                }
             }
         }
+        return Ok(());
     }
-    let mut fut_handle_lp: Vec<tokio::task::JoinHandle<()>> = vec![];
-    if config_file.previous_logs {
-        pods_list.clone().into_iter().for_each(|pl| {
-            let container = pl.3.clone();
-            for c in container {
-                let pl = pl.clone();
-                let folders = folders.clone();
-                let pname = pl.0.clone();
-                let task = tokio::task::spawn(async move {
-                    let l = get_logs(pl.0, c.to_string(), pl.2, true).await;
-                    match l {
-                        Ok(l) => {
-                            let filename = format!("logs_previous_{}_{}_{}.log", &pl.1, &pname, c);
-                            let er = anyhow!("No Log found {} on container {}.", pname, c);
-                            match write_file(&folders[0], l.as_bytes(), &filename, er) {
-                                Ok(_) => {
-                                    info!("File has been created {}/{}", &folders[0], filename)
-                                }
-                                Err(e) => {
-                                    warn!("{}", e)
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            warn!("{}", e)
-                        }
-                    }
-                });
-                fut_handle_lp.push(task);
-            }
-        });
+
+    if let Some(v) = m.subcommand_matches("verify") {
+        let report = verify::verify_archive(Path::new(v.get_one::<String>("archive").unwrap()));
+        print!("{}", verify::render_report(&report));
+        std::process::exit(report.exit_code());
     }
 
-    for handle in fut_handle_lp {
-        match handle.await {
-            Ok(_) => {}
-            Err(e) => {
-                warn!("{}", e)
-            }
+    if let Some(i) = m.subcommand_matches("inspect") {
+        let mut config_file = read_config_file(i.get_one::<String>("config").unwrap())?;
+        let kube_config_path = i.get_one::<String>("kube_config_path").unwrap();
+        resolve_auth(
+            &mut config_file,
+            &AuthArgs::from_matches(i),
+            kube_config_path,
+        )?;
+        let client = kubernetes_client(
+            kube_config_path,
+            config_file.clone(),
+            Default::default(),
+            Default::default(),
+        )
+        .await?;
+        let collector = Collector::from_client(client, config_file);
+        let reports =
+            components::inspect_components(collector.pod_access(), &collector.config).await?;
+        if summary_json {
+            println!("{}", serde_json::to_string(&reports)?);
+        } else {
+            print!("{}", components::render_components(&reports));
         }
+        return Ok(());
     }
 
-    // Infra
-
-    let nodes: Api<Node> = Api::all(client.clone());
-
-    let nodes_list = nodes.list(&ListParams::default()).await?;
-
-    let nodes_list = nodes_list
-        .items
-        .iter()
-        .map(|n| n.name_any())
-        .collect::<Vec<String>>();
-
-    let mut cmdki = vec![];
-    let mut fut_handle_infra = vec![];
-    let mut cmd = std::process::Command::new("kubectl");
-    cmd.args([
-        "get",
-        "nodes",
-        "--context",
-        &config_file.context_name,
-        "-o",
-        "wide",
-    ]);
-    let file_name = "kubernetes_nodes.list".to_string();
-    cmdki.push((cmd, file_name));
-
-    let mut cmd = std::process::Command::new("kubectl");
-    cmd.args([
-        "get",
-        "nodes",
-        "--context",
-        &config_file.context_name,
-        "-o",
-        "json",
-    ]);
-    let file_name = "kubernetes_nodes_list.json".to_string();
-    cmdki.push((cmd, file_name));
-
-    let mut cmd = std::process::Command::new("kubectl");
-    cmd.args([
-        "version",
-        "--context",
-        &config_file.context_name,
-        "-o",
-        "json",
-    ]);
-    let file_name = "kubernetes_version.json".to_string();
-    cmdki.push((cmd, file_name));
-
-    let mut cmd = std::process::Command::new("kubectl");
-    cmd.args([
-        "get",
-        "events",
-        "-A",
-        "--context",
-        &config_file.context_name,
-    ]);
-    let file_name = "kubernetes_cluster.events".to_string();
-    cmdki.push((cmd, file_name));
-
-    nodes_list.iter().for_each(|n| {
-        let mut cmd = std::process::Command::new("kubectl");
-        cmd.args([
-            "describe",
-            "node",
-            n,
-            "--context",
-            &config_file.context_name,
-        ]);
-
-        let file_name = format!("{}.description", n);
-        cmdki.push((cmd, file_name));
-    });
-
-    cmdki.into_iter().for_each(|mut c| {
-        let folders = folders.clone();
-        let task = tokio::task::spawn(async move {
-            let o = c.0.output().expect("kubectl command failed to start");
-            let er = anyhow!("kubectl command empty response {:#?}", c.0);
-            match write_file(&folders[1], &o.stdout, &c.1, er) {
-                Ok(_) => info!("File has been created {}/{}", &folders[1], &c.1),
-                Err(e) => warn!("{}", e),
-            }
-
-            if !o.stderr.is_empty() {
-                warn!("{}", String::from_utf8_lossy(&o.stderr))
-            }
-        });
-        fut_handle_infra.push(task);
-    });
-
-    for handle in fut_handle_infra {
-        match handle.await {
-            Ok(_) => {}
-            Err(e) => {
-                warn!("{}", e)
-            }
+    if let Some(p) = m.subcommand_matches("pod") {
+        let refs = p
+            .get_many::<String>("pods")
+            .unwrap()
+            .map(|r| pod_bundle::parse_pod_ref(r))
+            .collect::<Result<Vec<_>>>()?;
+        let output = p.get_one::<String>("output");
+        let logs_only = p.get_flag("logs_only");
+        if !logs_only {
+            output::reject_stream(
+                output.map(|o| o.as_str()),
+                "The pod bundle (without --logs-only)",
+            )?;
+        }
+        let mut config_file = read_config_file(p.get_one::<String>("config").unwrap())?;
+        let kube_config_path = p.get_one::<String>("kube_config_path").unwrap();
+        resolve_auth(
+            &mut config_file,
+            &AuthArgs::from_matches(p),
+            kube_config_path,
+        )?;
+        let client = kubernetes_client(
+            kube_config_path,
+            config_file.clone(),
+            Default::default(),
+            Default::default(),
+        )
+        .await?;
+        if logs_only {
+            let collector = Collector::from_client(client, config_file);
+            let output = output
+                .cloned()
+                .unwrap_or_else(|| output_directory(&collector.config));
+            let mut target = output::OutputTarget::parse(&output)?;
+            pod_bundle::collect_logs(&collector, &refs, &mut target).await?;
+            target.finish()?;
+            return Ok(());
         }
+        if let Some(o) = output {
+            config_file.output_directory_path = o.clone();
+        }
+        let collector = Collector::from_client(client, config_file);
+        let path = pod_bundle::collect_pods(&collector, &refs).await?;
+        info!("<green>tar file has been created on ... {}</>", path);
+        return Ok(());
     }
 
-    //helm
-    //get helm version
-    //list helm charts
-    //get helm chart values.
-    let mut cmdhelms = vec![];
-    let mut fut_handle_helm = vec![];
-    let context = config_file.context_name;
-    let arg1 = format!("--kubeconfig={}", kube_config_path);
-    let arg2 = format!("--kube-context={}", &context);
-    let mut cmd = std::process::Command::new("helm");
-    cmd.args([&arg1, &arg2, "version"]);
-    let file_name = "helm_version.log".to_string();
-    cmdhelms.push((cmd, file_name));
-
-    config_file.context_namespace.iter().for_each(|n| {
-        let mut cmd = std::process::Command::new("helm");
-        cmd.args([&arg1, &arg2, "ls", "-n", n]);
-        let file_name = format!("helm_list_{}.log", n);
-        cmdhelms.push((cmd, file_name));
-        let mut cmdt = std::process::Command::new("helm");
-        cmdt.args([&arg1, &arg2, "ls", "-n", n, "-o", "json"]);
-        let o = cmdt.output().unwrap();
-        let o: LsHelm = serde_json::from_str(&String::from_utf8_lossy(&o.stdout)).unwrap();
-        o.iter().for_each(|h| {
-            let file_name = format!("helm_values_{}_{}.yaml", h.name, n);
-            let mut cmd = std::process::Command::new("helm");
-            cmd.args([
-                &arg1,
-                &arg2,
-                "get",
-                "values",
-                "--all",
-                h.name.as_str(),
-                "-n",
-                n,
-                "-o",
-                "yaml",
-            ]);
-            cmdhelms.push((cmd, file_name));
-        })
-    });
-
-    cmdhelms.into_iter().for_each(|mut c| {
-        let folders = folders.clone();
-        let task = tokio::task::spawn(async move {
-            let o = c.0.output().expect("helm command failed to start");
-            let er = anyhow!("kubectl command empty response {:#?}", c.0);
-            match write_file(&folders[2], &o.stdout, &c.1, er) {
-                Ok(_) => info!("File has been created {}/{}", &folders[2], &c.1),
-                Err(e) => warn!("{}", e),
+    if let Some(x) = m.subcommand_matches("exec") {
+        let mut config_file = read_config_file(x.get_one::<String>("config").unwrap())?;
+        let kube_config_path = x.get_one::<String>("kube_config_path").unwrap();
+        resolve_auth(
+            &mut config_file,
+            &AuthArgs::from_matches(x),
+            kube_config_path,
+        )?;
+        let client = kubernetes_client(
+            kube_config_path,
+            config_file.clone(),
+            Default::default(),
+            Default::default(),
+        )
+        .await?;
+        let collector = Collector::from_client(client, config_file);
+        let command: Vec<String> = x.get_many::<String>("command").unwrap().cloned().collect();
+        let ordinal_range = x
+            .get_one::<String>("ordinals")
+            .map(|o| OrdinalRange::parse(o))
+            .transpose()?;
+        let mut output = x
+            .get_one::<String>("output")
+            .map(|o| output::OutputTarget::parse(o))
+            .transpose()?;
+        //streamed outputs come one pod at a time.
+        let concurrency = if output.as_ref().is_some_and(|o| o.is_stream()) {
+            1
+        } else {
+            exec::EXEC_CONCURRENCY
+        };
+        let results = exec::exec_on_pods(
+            collector.pod_access(),
+            x.get_one::<String>("selector").unwrap(),
+            ordinal_range.as_ref(),
+            x.get_one::<String>("container").map(|c| c.as_str()),
+            command,
+            concurrency,
+        )
+        .await?;
+        if results.is_empty() {
+            warn!("No pod matches the selector.");
+            std::process::exit(1);
+        }
+        for r in &results {
+            let header = format!(
+                "==== {}/{} [{}] (exit {}) ====",
+                r.namespace, r.pod, r.container, r.exit_code
+            );
+            let streamed = output.as_ref().is_some_and(|o| o.is_stream());
+            //the headers go to the logs (stderr) when stdout carries the data.
+            if streamed {
+                info!("{}", header);
+            } else {
+                println!("{}", header);
             }
-
-            if !o.stderr.is_empty() {
-                warn!("{}", String::from_utf8_lossy(&o.stderr))
+            match &r.error {
+                Some(e) => warn!("{}/{}: {}", r.namespace, r.pod, e),
+                None if !streamed => print!("{}", r.output),
+                None => {}
             }
-        });
-        fut_handle_helm.push(task);
-    });
-
-    for handle in fut_handle_helm {
-        match handle.await {
-            Ok(_) => {}
-            Err(e) => {
-                warn!("{}", e)
+            if let Some(o) = output.as_mut() {
+                let name = format!("{}_{}.log", r.namespace, r.pod);
+                o.write(&name, r.output.as_bytes())?;
+                if !streamed {
+                    info!("File has been created {}", o.describe(&name));
+                }
             }
         }
+        if let Some(o) = output {
+            o.finish()?;
+        }
+        let failed = results.iter().filter(|r| !r.succeeded()).count();
+        if failed > 0 {
+            warn!("{} of {} pods failed.", failed, results.len());
+            std::process::exit(1);
+        }
+        return Ok(());
     }
-    //Streaming Cores info.
-    //ElasticSearch.
-    //Hadoop hdfs info.
-    //Hbase info.
-    //Kafka info.
-    //Prometheus info.
 
-    //ElasticSearch
-    let mut fut_handle_es = vec![];
-    let es_pods = get_pod_list(
-        pods.clone(),
-        "elasticsearch.k8s.elastic.co/node-master=true".to_string(),
-        "".to_string(),
-    )
-    .await?;
-    let mut secret_user = String::new();
-    if !es_pods.clone().is_empty() {
-        let mut secret_list = vec![];
-        for sec in secret {
-            let s = sec
-            .list(&ListParams {
-                label_selector: Some("eck.k8s.elastic.co/owner-kind=Elasticsearch, eck.k8s.elastic.co/credentials=true".to_string()),
-                ..Default::default()
-            })
-            .await
-            .unwrap()
-            .items;
-            secret_list.push(s);
-        }
+    if let Some(t) = m.subcommand_matches("tail") {
+        let (namespace, pod) = pod_bundle::parse_pod_ref(t.get_one::<String>("pod").unwrap())?;
+        let mut config_file = read_config_file(t.get_one::<String>("config").unwrap())?;
+        let kube_config_path = t.get_one::<String>("kube_config_path").unwrap();
+        resolve_auth(
+            &mut config_file,
+            &AuthArgs::from_matches(t),
+            kube_config_path,
+        )?;
+        let client = kubernetes_client(
+            kube_config_path,
+            config_file,
+            Default::default(),
+            Default::default(),
+        )
+        .await?;
+        let api = access::KubeAccess::namespaced(client, &namespace);
+        let container = match t.get_one::<String>("container") {
+            Some(c) => c.clone(),
+            None => api
+                .list_pods("", &format!("metadata.name={}", pod))
+                .await?
+                .first()
+                .and_then(|p| p.spec.as_ref())
+                .and_then(|s| s.containers.first())
+                .map(|c| c.name.clone())
+                .ok_or_else(|| anyhow::anyhow!("Pod {}/{} not found.", namespace, pod))?,
+        };
+        let options = FollowOptions {
+            tail_lines: t.get_one::<i64>("tail_lines").copied(),
+            timestamps: t.get_flag("timestamps"),
+            reattach_on_restart: t.get_flag("reattach"),
+            ..Default::default()
+        };
+        let cancel = CancellationToken::new();
+        let _interrupt = cancel_on_interrupt(cancel.clone());
+        follow_logs(
+            &api,
+            &pod,
+            &container,
+            &options,
+            tokio::io::stdout(),
+            cancel,
+        )
+        .await?;
+        return Ok(());
+    }
 
-        secret_list.iter().for_each(|s| {
-            s.iter().for_each(|s| {
-                let es_user = s
-                    .data
-                    .as_ref()
-                    .unwrap()
-                    .get("elastic")
-                    .unwrap()
-                    .0
-                    .to_owned();
-                secret_user = String::from_utf8(es_user).unwrap();
-            })
-        });
+    if let Some(w) = m.subcommand_matches("watch") {
+        let mut config_file = read_config_file(w.get_one::<String>("config").unwrap())?;
+        let kube_config_path = w.get_one::<String>("kube_config_path").unwrap();
+        resolve_auth(
+            &mut config_file,
+            &AuthArgs::from_matches(w),
+            kube_config_path,
+        )?;
+        let client = kubernetes_client(
+            kube_config_path,
+            config_file.clone(),
+            Default::default(),
+            Default::default(),
+        )
+        .await?;
+        let collector = Collector::from_client(client, config_file);
+        let debounce = Duration::from_secs(*w.get_one::<u64>("debounce").unwrap());
+        return watch::watch(collector, debounce).await;
+    }
 
-        let command_es = [
-            ("curl -k -u elastic:".to_string()
-                + secret_user.as_str()
-                + " -X GET \"https://localhost:9200/_cluster/health?pretty\"", "health"),
-            ("curl -k -u elastic:".to_string()
-                + secret_user.as_str()
-                + " -X GET \"https://localhost:9200/_cat/indices?h=health,status,index,id,p,r,dc,dd,ss,creation.date.string,&v&s=creation.date:desc\"","indices"),
-            ("curl -k -u elastic:".to_string()
-                + secret_user.as_str()
-                + " -X GET \"https://localhost:9200/_cluster/settings?pretty\"","settings"),
-            ("curl -k -u elastic:".to_string()
-                + secret_user.as_str()
-                + " -X GET \"https://localhost:9200/_cluster/settings?include_defaults=true&pretty\"","defaults_settings"),
-            ("curl -k -u elastic:".to_string()
-                + secret_user.as_str()
-                + " -X GET \"https://localhost:9200/_cat/nodes?v&pretty\"","nodes"),
-            ("curl -k -u elastic:".to_string()
-                + secret_user.as_str()
-                + " -X GET \"https://localhost:9200/_cat/_cat/shards?v\"","shards"),
-            ("curl -k -u elastic:".to_string()
-                + secret_user.as_str()
-                + " -X GET \"https://localhost:9200/_cluster/state?pretty\"","state"),
-            ("curl -k -u elastic:".to_string()
-                + secret_user.as_str()
-                + " -X GET \"https://localhost:9200/_cluster/stats?human&pretty\"","stats_human")
-        ];
+    if let Some(collection) = m.get_one::<String>("resume") {
+        let config_file = read_config_file(m.get_one::<String>("config").unwrap())?;
+        let archive = run::resume_archive(&config_file, Path::new(collection))?;
+        info!("<green>Archive has been completed {}</>", archive.display());
+        return Ok(());
+    }
 
-        for c in command_es {
-            let folders = folders.clone();
-            let es_pods = es_pods.clone();
-            let task = tokio::task::spawn(async move {
-                let pod_name = &es_pods[0].0;
-                let apipod = &es_pods[0].2;
-                let container = &es_pods[0].3[0];
-                let cmd = ["/bin/sh", "-c", &c.0];
-                let filename = format!("elastic_search_{}.json", &c.1);
-                let data = send_command(pod_name.clone(), apipod.clone(), container.clone(), cmd)
-                    .await
-                    .unwrap();
+    let args = RunArgs {
+        config_file_path: m.get_one::<String>("config").unwrap().clone(),
+        kube_config_path: m.get_one::<String>("kube_config_path").unwrap().clone(),
+        anonymize: m.get_flag("anonymize"),
+        incremental: m.get_flag("incremental"),
+        quick: m.get_flag("quick"),
+        read_only: m.get_flag("read_only"),
+        yes: m.get_flag("yes"),
+        tail_lines: m.get_one::<i64>("tail_lines").copied(),
+        log_limit_bytes: m.get_one::<i64>("log_limit_bytes").copied(),
+        nodes: m
+            .get_many::<String>("node")
+            .map(|n| n.cloned().collect())
+            .unwrap_or_default(),
+        label_values: m
+            .get_many::<String>("label_values")
+            .map(|l| l.cloned().collect())
+            .unwrap_or_default(),
+        auth: AuthArgs::from_matches(&m),
+        log_file: format!("output_antlog_gather_tool_{}.log", date),
+        summary_json,
+        log_warnings,
+    };
 
-                let er = anyhow!("kubectl command empty response {:#?}", c.0);
-                match write_file(&folders[3], data.as_bytes(), &filename, er) {
-                    Ok(_) => info!("File has been created {}/{}", &folders[3], &filename),
-                    Err(e) => warn!("{}", e),
-                }
-            });
-            fut_handle_es.push(task);
+    match m.get_one::<String>("repeat_every") {
+        Some(every) => {
+            let max_runs = m.get_one::<usize>("max_runs").copied();
+            schedule(args, parse_duration(every)?, max_runs).await
         }
-        for handle in fut_handle_es {
-            match handle.await {
-                Ok(_) => {}
-                Err(e) => {
-                    warn!("{}", e)
-                }
+        None => {
+            let cancel = CancellationToken::new();
+            let _interrupt = cancel_on_interrupt(cancel.clone());
+            let info = collect(args, cancel).await?;
+            if summary_json {
+                println!("{}", serde_json::to_string(&info)?);
+                std::process::exit(info.exit_code);
             }
+            Ok(())
         }
     }
+}
 
-    //Streaming Cores info
-    let streaming_core_pods = get_pod_list(
-        pods.clone(),
-        "spark-role=driver,app.kubernetes.io/component=streaming-core-consumer".to_string(),
-        "".to_string(),
-    )
-    .await?;
-    let mut fut_handle_sc = vec![];
-    if !streaming_core_pods.is_empty() {
-        for sc in streaming_core_pods {
-            let cmd = [
-                "/bin/sh",
-                "-c",
-                "curl -s localhost:4040/api/v1/applications | jq -r  '.[0] | .id' | tr -d '\n'",
-            ];
-
-            let application_id = send_command(sc.0.clone(), sc.2.clone(), sc.3[0].to_string(), cmd)
-                .await
-                .unwrap();
+#[derive(Clone, Debug)]
+struct RunArgs {
+    config_file_path: String,
+    kube_config_path: String,
+    anonymize: bool,
+    incremental: bool,
+    quick: bool,
+    read_only: bool,
+    yes: bool,
+    tail_lines: Option<i64>,
+    log_limit_bytes: Option<i64>,
+    nodes: Vec<String>,
+    label_values: Vec<String>,
+    auth: AuthArgs,
+    log_file: String,
+    summary_json: bool,
+    log_warnings: Arc<WarningAggregator>,
+}
 
-            let command_sc = [
-                (
-                    format!(
-                        "curl \"localhost:4040/api/v1/applications/{}/environment\"",
-                        application_id
-                    ),
-                    "environment.json",
-                ),
-                (
-                    format!(
-                        "curl \"localhost:4040/api/v1/applications/{}/executors\"",
-                        application_id
-                    ),
-                    "executors.json",
-                ),
-                (
-                    format!(
-                        "curl \"localhost:4040/api/v1/applications/{}/streaming/statistics\"",
-                        application_id
-                    ),
-                    "streaming_statistics.json",
-                ),
-                (
-                    format!(
-                        "curl \"localhost:4040/api/v1/applications/{}/streaming/batches\"",
-                        application_id
-                    ),
-                    "streaming_batches.json",
-                ),
-            ];
+//command line authentication options, they take precedence over the config file.
+#[derive(Clone, Debug, Default)]
+struct AuthArgs {
+    in_cluster: bool,
+    explicit_kube_config: bool,
+    api_server_url: Option<String>,
+    bearer_token_env: Option<String>,
+    ca_cert_path: Option<String>,
+    client_cert_path: Option<String>,
+    client_key_path: Option<String>,
+    insecure_skip_tls_verify: bool,
+}
 
-            for c in command_sc {
-                let folders = folders.clone();
-                let sc = sc.clone();
-                let task = tokio::task::spawn(async move {
-                    let cmd = ["/bin/sh", "-c", &c.0];
-                    let filename = format!("{}_{}", sc.0, &c.1);
-                    let data = send_command(sc.0, sc.2, sc.3[0].to_string(), cmd)
-                        .await
-                        .unwrap();
-                    let data = jsonxf::pretty_print(&data).unwrap();
-                    let er = anyhow!("kubectl command empty response {:#?}", c.0);
-                    match write_file(&folders[3], data.as_bytes(), &filename, er) {
-                        Ok(_) => info!("File has been created {}/{}", &folders[3], &filename),
-                        Err(e) => warn!("{}", e),
-                    }
-                });
-                fut_handle_sc.push(task);
-            }
-        }
-        for handle in fut_handle_sc {
-            match handle.await {
-                Ok(_) => {}
-                Err(e) => {
-                    warn!("{}", e)
-                }
-            }
+impl AuthArgs {
+    fn from_matches(m: &clap::ArgMatches) -> Self {
+        let string = |id: &str| m.try_get_one::<String>(id).ok().flatten().cloned();
+        let flag = |id: &str| m.try_get_one::<bool>(id).ok().flatten() == Some(&true);
+        AuthArgs {
+            in_cluster: flag("in_cluster"),
+            explicit_kube_config: m.value_source("kube_config_path")
+                == Some(clap::parser::ValueSource::CommandLine),
+            api_server_url: string("api_server_url"),
+            bearer_token_env: string("bearer_token_env"),
+            ca_cert_path: string("ca_cert_path"),
+            client_cert_path: string("client_cert_path"),
+            client_key_path: string("client_key_path"),
+            insecure_skip_tls_verify: flag("insecure_skip_tls_verify"),
         }
     }
+}
 
-    //Hadoop hdfs info
-    let hadoop_pods = get_pod_list(
-        pods.clone(),
-        "app.kubernetes.io/component=datanode".to_string(),
-        "".to_string(),
-    )
-    .await?;
-    let mut fut_handle_hd = vec![];
-    if !hadoop_pods.is_empty() {
-        let command_hd = [
-            ("hdfs dfsadmin -report", "report_dfsadmin"),
-            ("hdfs dfsadmin -safemode get", "safe_mode"),
-            (
-                "time dd if=/dev/zero of=/dfs/test conv=fsync bs=384k count=10K",
-                "hdfs_diskwrite_perf",
-            ),
-        ];
+//--in-cluster, the config setting or a detected service account all switch to the in-cluster client,
+//api_server_url switches to direct authentication without a kubeconfig.
+fn resolve_auth(
+    config_file: &mut ConfigFile,
+    auth: &AuthArgs,
+    kube_config_path: &str,
+) -> Result<()> {
+    for (field, value) in [
+        (&mut config_file.api_server_url, &auth.api_server_url),
+        (&mut config_file.bearer_token_env, &auth.bearer_token_env),
+        (&mut config_file.ca_cert_path, &auth.ca_cert_path),
+        (&mut config_file.client_cert_path, &auth.client_cert_path),
+        (&mut config_file.client_key_path, &auth.client_key_path),
+    ] {
+        if let Some(v) = value {
+            *field = v.clone();
+        }
+    }
+    config_file.insecure_skip_tls_verify |= auth.insecure_skip_tls_verify;
+    config_file.in_cluster |= auth.in_cluster;
+    config_file.validate_auth(auth.explicit_kube_config)?;
 
-        for c in command_hd {
-            let folders = folders.clone();
-            let hadoop_pods = hadoop_pods.clone();
-            let task = tokio::task::spawn(async move {
-                let pod_name = &hadoop_pods.first().as_ref().unwrap().0;
-                let apipod = &hadoop_pods.first().as_ref().unwrap().2;
-                let container = &hadoop_pods.first().as_ref().unwrap().3[0];
-                let cmd = ["/bin/sh", "-c", &c.0];
-                let filename = format!("hadoop_{}.log", &c.1);
-                let data = send_command(pod_name.clone(), apipod.clone(), container.clone(), cmd)
-                    .await
-                    .unwrap();
-                let er = anyhow!("kubectl command empty response {:#?}", c.0);
-                match write_file(&folders[3], data.as_bytes(), &filename, er) {
-                    Ok(_) => info!("File has been created {}/{}", &folders[3], &filename),
-                    Err(e) => warn!("{}", e),
-                }
-            });
-            fut_handle_hd.push(task);
+    if config_file.direct_auth() {
+        if config_file.context_name.is_empty() {
+            config_file.context_name = context_name_from_url(&config_file.api_server_url);
         }
-        for handle in fut_handle_hd {
-            match handle.await {
-                Ok(_) => {}
-                Err(e) => {
-                    warn!("{}", e)
-                }
-            }
+        return Ok(());
+    }
+    config_file.in_cluster |= detect_in_cluster(kube_config_path);
+    if config_file.in_cluster {
+        info!("<yellow>In-cluster mode, using the pod service account.</>");
+        if config_file.context_name.is_empty() {
+            config_file.context_name = in_cluster_context_name()?;
         }
     }
-    //Hbase info
-    let hbase_pods = get_pod_list(
-        pods.clone(),
-        "app.kubernetes.io/name=hbase, app.kubernetes.io/component=master".to_string(),
-        "".to_string(),
-    )
-    .await?;
-
-    let mut fut_handle_hb = vec![];
-    if !hbase_pods.is_empty() {
-        let command_hb = [(
-            "echo \"status 'detailed'\" | hbase shell",
-            "status_detailed",
-        )];
+    Ok(())
+}
 
-        for c in command_hb {
-            let folders = folders.clone();
-            let hbase_pods = hbase_pods.clone();
-            let task = tokio::task::spawn(async move {
-                let pod_name = &hbase_pods.first().as_ref().unwrap().0;
-                let apipod = &hbase_pods.first().as_ref().unwrap().2;
-                let container = &hbase_pods.first().as_ref().unwrap().3[0];
-                let cmd = ["/bin/sh", "-c", &c.0];
-                let filename = format!("hbase_{}.log", &c.1);
-                let data = send_command(pod_name.clone(), apipod.clone(), container.clone(), cmd)
-                    .await
-                    .unwrap();
-                let er = anyhow!("kubectl command empty response {:#?}", c.0);
-                match write_file(&folders[3], data.as_bytes(), &filename, er) {
-                    Ok(_) => info!("File has been created {}/{}", &folders[3], &filename),
-                    Err(e) => warn!("{}", e),
-                }
-            });
-            fut_handle_hb.push(task);
+//the first Ctrl-C cancels, the second one exits at once.
+fn cancel_on_interrupt(cancel: CancellationToken) -> AbortOnDrop {
+    AbortOnDrop(tokio::task::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            info!("<yellow>Ctrl-C received, stopping (press it again to exit at once).</>");
+            cancel.cancel();
         }
-        for handle in fut_handle_hb {
-            match handle.await {
-                Ok(_) => {}
-                Err(e) => {
-                    warn!("{}", e)
-                }
-            }
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
         }
-    }
+    }))
+}
 
-    //Kafka info
-    let label_k = [
-        "app.kubernetes.io/name=kafka",
-        "app.kubernetes.io/name=eric-data-message-bus-kf",
-    ];
-    let mut kafka_pods = vec![];
-    let mut p = "";
-    for k in label_k {
-        let kf = get_pod_list(pods.clone(), k.to_string(), "".to_string()).await?;
-        if !kf.is_empty() {
-            kafka_pods.push(kf);
-            p = k;
+//one collection run from the command line arguments, it stops at the next phase once cancel is.
+async fn collect(args: RunArgs, cancel: CancellationToken) -> Result<CollectionInfo> {
+    let mut config_file = read_config_file(&args.config_file_path)?;
+    if config_file.rules_file.is_empty() {
+        let default_rules = rules::default_rules_path(Path::new(&args.config_file_path));
+        if default_rules.exists() {
+            config_file.rules_file = default_rules.display().to_string();
         }
     }
-    let mut fut_handle_kf = vec![];
-    if !kafka_pods.is_empty() {
-        let prefix = match p {
-            "app.kubernetes.io/name=kafka" => "bin/",
-            "app.kubernetes.io/name=eric-data-message-bus-kf" => "",
-            _ => "",
-        };
+    resolve_auth(&mut config_file, &args.auth, &args.kube_config_path)?;
+    if args.quick {
+        info!(
+            "<yellow>Quick mode, failing pods only with the last {} log lines.</>",
+            preset::QUICK_TAIL_LINES
+        );
+        preset::apply_quick(&mut config_file);
+    }
+    if args.read_only || config_file.read_only {
+        info!("<yellow>Read-only mode, the exec and node debug collectors are skipped.</>");
+        preset::apply_read_only(&mut config_file);
+    }
+    if let Some(t) = args.tail_lines {
+        config_file.log_tail_lines = t;
+    }
+    if let Some(b) = args.log_limit_bytes {
+        config_file.log_limit_bytes = b;
+    }
+    if !args.nodes.is_empty() {
+        config_file.node_names = args.nodes;
+    }
+    if !args.label_values.is_empty() {
+        config_file.report_labels = args.label_values;
+    }
+    //not in a job or a pipe, where a minimal image may not even have clear.
+    if !args.summary_json && !config_file.in_cluster && std::io::stdout().is_terminal() {
+        let _ = std::process::Command::new("clear").status();
+    }
+    let options = RunOptions {
+        kube_config_path: args.kube_config_path,
+        anonymize: args.anonymize,
+        incremental: args.incremental,
+        assume_yes: args.yes,
+        log_file: Some(args.log_file),
+        cancel,
+        log_warnings: args.log_warnings,
+        ..Default::default()
+    };
+    run_collection(config_file, options).await
+}
 
-        let command_kf = [
-            (
-                prefix.to_owned() + "kafka-topics.sh --bootstrap-server localhost:9092 --list",
-                "topics",
-            ),
-            (
-                prefix.to_owned() + "kafka-topics.sh --bootstrap-server localhost:9092 --describe",
-                "topics_description",
-            ),
-            (
-                prefix.to_owned()
-                    + "kafka-consumer-groups.sh --bootstrap-server localhost:9092 --list",
-                "groups_list",
-            ),
-            (
-                prefix.to_owned()
-                    + "kafka-broker-api-versions.sh --bootstrap-server localhost:9092 | awk '/^[a-z]/ {print $1}'",
-                "brokers_list",
-            ),
-            (
-                prefix.to_owned()
-                    + "kafka-consumer-groups.sh --bootstrap-server localhost:9092 --describe --all-groups",
-                "groups_describe",
-            ),
-        ];
-        for c in command_kf {
-            let folders = folders.clone();
-            let kafka_pods = kafka_pods.clone();
-            let task = tokio::task::spawn(async move {
-                let pod_name = &kafka_pods[0].first().as_ref().unwrap().0;
-                let apipod = &kafka_pods[0].first().as_ref().unwrap().2;
-                let container = &kafka_pods[0].first().as_ref().unwrap().3[0];
-                let cmd = ["/bin/sh", "-c", &c.0];
-                let filename = format!("kafka_{}.log", &c.1);
-                let data = send_command(pod_name.clone(), apipod.clone(), container.clone(), cmd)
-                    .await
-                    .unwrap();
-                let er = anyhow!("kubectl command empty response {:#?}", c.0);
-                match write_file(&folders[3], data.as_bytes(), &filename, er) {
-                    Ok(_) => info!("File has been created {}/{}", &folders[3], &filename),
-                    Err(e) => warn!("{}", e),
+//re-run the collection every `every`, a cycle is skipped while the previous run is still going.
+async fn schedule(args: RunArgs, every: Duration, max_runs: Option<usize>) -> Result<()> {
+    let mut interval = tokio::time::interval(every);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut runs = 0;
+    let mut in_flight: Option<tokio::task::JoinHandle<Result<CollectionInfo>>> = None;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if in_flight.as_ref().is_some_and(|h| !h.is_finished()) {
+                    warn!("Previous collection is still running, skipping this cycle.");
+                    continue;
                 }
-            });
-            fut_handle_kf.push(task);
-        }
-        for handle in fut_handle_kf {
-            match handle.await {
-                Ok(_) => {}
-                Err(e) => {
-                    warn!("{}", e)
+                if let Some(h) = in_flight.take() {
+                    report_run(h.await, args.summary_json);
                 }
+                runs += 1;
+                info!("<green>Starting scheduled collection run {}.</>", runs);
+                //Ctrl-C lets the in-flight run finish, below.
+                in_flight = Some(tokio::task::spawn(collect(args.clone(), CancellationToken::new())));
+                if max_runs.is_some_and(|m| runs >= m) {
+                    break;
+                }
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("<yellow>Ctrl-C received, finishing the in-flight collection.</>");
+                break;
             }
         }
     }
-    //Prometheus info
-    let mut fut_handle_pro = vec![];
-    let prometheus_pods = get_pod_list(
-        pods.clone(),
-        "app.kubernetes.io/name=prometheus".to_string(),
-        "".to_string(),
-    )
-    .await?;
-    if !prometheus_pods.is_empty() {
-        let pod_name = prometheus_pods.first().as_ref().unwrap().0.as_str();
-        let mut path = ["midlayer", "session", "titan-ns"]
-            .into_iter()
-            .filter(|&i| pod_name.contains(i))
-            .collect::<Vec<&str>>();
-        if path.is_empty() {
-            path.push(&prometheus_pods.first().as_ref().unwrap().1)
-        }
-        let command_prometheus = [
-            (
-                format!(
-                    "wget -q 'http://127.0.0.1:9090/{}/prometheus/api/v1/rules' -O -",
-                    path[0]
-                ),
-                "rules.json",
-            ),
-            (
-                format!(
-                    "wget -q 'http://127.0.0.1:9090/{}/prometheus/api/v1/alerts' -O -",
-                    path[0]
-                ),
-                "alerts.json",
-            ),
-            (
-                format!(
-                    "wget -q 'http://127.0.0.1:9090/{}/prometheus/api/v1/targets' -O -",
-                    path[0]
-                ),
-                "targets.json",
-            ),
-            (
-                format!(
-                    "wget -q 'http://127.0.0.1:9090/{}/prometheus/api/v1/status/runtimeinfo' -O -",
-                    path[0]
-                ),
-                "runtime_info.json",
-            ),
-            (
-                format!(
-                    "wget -q 'http://127.0.0.1:9090/{}/prometheus/api/v1/status/buildinfo' -O -",
-                    path[0]
-                ),
-                "build_info.json",
-            ),
-        ];
-        for c in command_prometheus {
-            let folders = folders.clone();
-            let prometheus_pods = prometheus_pods.clone();
-            let task = tokio::task::spawn(async move {
-                let pod_name = &prometheus_pods.first().as_ref().unwrap().0;
-                let apipod = &prometheus_pods.first().as_ref().unwrap().2;
-                let container = &prometheus_pods.first().as_ref().unwrap().3[0];
-                let namespace = &prometheus_pods.first().as_ref().unwrap().1;
-                let cmd = ["/bin/sh", "-c", &c.0];
-                let filename = format!("prometheus_{}_{}", namespace, &c.1);
-                let data = send_command(pod_name.clone(), apipod.clone(), container.clone(), cmd)
-                    .await
-                    .unwrap();
+    if let Some(h) = in_flight {
+        report_run(h.await, args.summary_json);
+    }
+    Ok(())
+}
 
-                let data = jsonxf::pretty_print(&data).unwrap();
-                let er = anyhow!("kubectl command empty response {:#?}", c.0);
-                match write_file(&folders[3], data.as_bytes(), &filename, er) {
-                    Ok(_) => info!("File has been created {}/{}", &folders[3], &filename),
+//every scheduled run prints its own summary line.
+fn report_run(
+    r: std::result::Result<Result<CollectionInfo>, tokio::task::JoinError>,
+    summary_json: bool,
+) {
+    match r {
+        Ok(Ok(info)) => {
+            if summary_json {
+                match serde_json::to_string(&info) {
+                    Ok(s) => println!("{}", s),
                     Err(e) => warn!("{}", e),
                 }
-            });
-            fut_handle_pro.push(task);
-        }
-        for handle in fut_handle_pro {
-            match handle.await {
-                Ok(_) => {}
-                Err(e) => {
-                    warn!("{}", e)
-                }
             }
         }
-    }
-    //tar file process
-
-    let path = format!("{}/{}", &folders[6], &folders[4]);
-    info!(
-        "tar file is being created and then then it will be copied to the following path ...{}",
-        &path
-    );
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ")
-            .template("[{elapsed_precise}] {spinner:.yellow} {msg:.yellow}")?,
-    );
-    spinner.enable_steady_tick(Duration::from_millis(100)); // Update every 100ms
-    spinner.set_message("this action will take a few minutes...");
-
-    let tar_gz = File::create(&path)?;
-    let enc = GzEncoder::new(tar_gz, Compression::default());
-    let mut tar = tar::Builder::new(enc);
-    tar.append_dir_all(folders[6].split('/').last().unwrap(), &folders[5])?;
-
-    spinner.finish_and_clear();
-    info!("tar file has been created on ... {}", &path);
-
-    //Finish log Collection Msg.
-    info!("<green>LOG collection has been completed!!</>");
-
-    let antlog = format!("output_antlog_gather_tool_{}.log", date);
-    let mut log_antlog = File::open(format!("output_antlog_gather_tool_{}.log", date)).unwrap();
-
-    match tar.append_file(&antlog, &mut log_antlog) {
-        Ok(_) => info!(
-            "output_antlog_gather_tool_{}.log has been add it to the tar file.",
-            date
-        ),
-        Err(e) => warn!("{}", e),
-    }
-    info!("<yellow>Starting Cleaning Phase!!</>");
-    match tar.into_inner() {
-        Ok(_) => info!("tar file {} integrity its OK", path),
+        Ok(Err(e)) => warn!("Collection run failed: {}", e),
         Err(e) => warn!("{}", e),
     }
-
-    match fs::remove_dir_all(&folders[5]) {
-        Ok(_) => info!("Folder has been remove {}", folders[5]),
-        Err(e) => warn!("{}", e),
-    }
-    info!("<yellow>Finishing Cleaning Phase!!</>");
-    info!("<green>END!!</>");
-    Ok(())
 }
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use kube::{
+    api::{DynamicObject, ListParams},
+    discovery::{verbs, Discovery, Scope},
+    Api, Client,
+};
+use simplelog::{__private::log::warn, info};
+
+use std::fs;
+
+use crate::context::RunContext;
+
+pub const SKIPPED_KINDS_FILE: &str = "skipped_kinds.txt";
+
+//secrets are never exported, events are collected on their own.
+fn excluded(group: &str, plural: &str) -> bool {
+    plural == "secrets" || plural == "events" || group == "events.k8s.io"
+}
+
+fn strip(obj: &mut DynamicObject, keep_status: bool) {
+    obj.metadata.managed_fields = None;
+    if !keep_status {
+        if let Some(data) = obj.data.as_object_mut() {
+            data.remove("status");
+        }
+    }
+}
+
+//every listable namespaced kind of a namespace, one multi document yaml per kind under <folder>/manifests_<ns>.
+pub async fn export_namespace(
+    client: Client,
+    namespace: &str,
+    folder: &str,
+    keep_status: bool,
+    ctx: &RunContext,
+) -> Result<()> {
+    let folder = ctx.subfolder(folder, &format!("manifests_{}", namespace));
+    fs::create_dir_all(&folder)?;
+    let discovery = Discovery::new(client.clone()).run().await?;
+    let mut skipped = vec![];
+
+    for group in discovery.groups() {
+        for (ar, caps) in group.recommended_resources() {
+            if caps.scope != Scope::Namespaced
+                || !caps.supports_operation(verbs::LIST)
+                || excluded(&ar.group, &ar.plural)
+            {
+                continue;
+            }
+            let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), namespace, &ar);
+            let items = match api.list(&ListParams::default()).await {
+                Ok(l) => l.items,
+                Err(e) => {
+                    skipped.push(format!("{} ({}): {}", ar.kind, ar.api_version, e));
+                    continue;
+                }
+            };
+            if items.is_empty() {
+                continue;
+            }
+            let mut yaml = String::new();
+            for mut obj in items {
+                strip(&mut obj, keep_status);
+                obj.types = Some(kube::core::TypeMeta {
+                    api_version: ar.api_version.clone(),
+                    kind: ar.kind.clone(),
+                });
+                yaml.push_str("---\n");
+                yaml.push_str(&serde_yaml::to_string(&obj)?);
+            }
+            let filename = if ar.group.is_empty() {
+                format!("{}.yaml", ar.plural)
+            } else {
+                format!("{}.{}.yaml", ar.plural, ar.group)
+            };
+            let er = anyhow::anyhow!("Empty manifest export {}.", filename);
+            match ctx.write_file(&folder, yaml.as_bytes(), &filename, er) {
+                Ok(_) => info!("File has been created {}/{}", folder, filename),
+                Err(e) => warn!("{}", e),
+            }
+        }
+    }
+
+    if !skipped.is_empty() {
+        warn!(
+            "{} kinds could not be listed in namespace {}, see {}/{}",
+            skipped.len(),
+            namespace,
+            folder,
+            SKIPPED_KINDS_FILE
+        );
+        let er = anyhow::anyhow!("Empty {}.", SKIPPED_KINDS_FILE);
+        let skipped = skipped.join("\n") + "\n";
+        ctx.write_file(&folder, skipped.as_bytes(), SKIPPED_KINDS_FILE, er)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,405 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::{
+    api::apps::v1::{Deployment, StatefulSet},
+    apimachinery::pkg::api::resource::Quantity,
+};
+use kube::{api::ListParams, Api, ResourceExt};
+use serde_json::Value;
+use simplelog::{__private::log::warn, info};
+
+use crate::{collector::Collector, qos::quantity_value};
+
+pub const MANUAL_CHANGE_REPORT_FILE: &str = "manual_change_report.txt";
+pub const LAST_APPLIED_ANNOTATION: &str = "kubectl.kubernetes.io/last-applied-configuration";
+//fields the api server or the controllers fill in when the manifest leaves them out.
+pub const DEFAULTED_FIELDS: [&str; 30] = [
+    "apiVersion",
+    "creationTimestamp",
+    "defaultMode",
+    "dnsPolicy",
+    "enableServiceLinks",
+    "failureThreshold",
+    "imagePullPolicy",
+    "kind",
+    "minReadySeconds",
+    "periodSeconds",
+    "persistentVolumeClaimRetentionPolicy",
+    "podManagementPolicy",
+    "progressDeadlineSeconds",
+    "protocol",
+    "replicas",
+    "restartPolicy",
+    "revisionHistoryLimit",
+    "schedulerName",
+    "scheme",
+    "securityContext",
+    "serviceAccount",
+    "status",
+    "strategy",
+    "successThreshold",
+    "terminationGracePeriodSeconds",
+    "terminationMessagePath",
+    "terminationMessagePolicy",
+    "timeoutSeconds",
+    "updateStrategy",
+    "volumeMode",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldChange {
+    Changed {
+        path: String,
+        applied: Value,
+        live: Value,
+    },
+    Removed {
+        path: String,
+        applied: Value,
+    },
+    Added {
+        path: String,
+        live: Value,
+    },
+}
+
+impl FieldChange {
+    pub fn render(&self) -> String {
+        match self {
+            FieldChange::Changed {
+                path,
+                applied,
+                live,
+            } => format!("changed {}: {} -> {}", path, applied, live),
+            FieldChange::Removed { path, applied } => format!("removed {}: was {}", path, applied),
+            FieldChange::Added { path, live } => format!("added {}: {}", path, live),
+        }
+    }
+}
+
+//`metadata: {creationTimestamp: null}` of a pod template is as empty as no metadata.
+fn empty(v: &Value) -> bool {
+    match v {
+        Value::Null => true,
+        Value::Object(m) => m.values().all(empty),
+        Value::Array(a) => a.is_empty(),
+        _ => false,
+    }
+}
+
+//1 and "1", "1000m" and "1", "1Gi" and "1024Mi" are the same once the api server normalized them.
+fn same_scalar(applied: &Value, live: &Value) -> bool {
+    if applied == live {
+        return true;
+    }
+    let text = |v: &Value| match v {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    };
+    match (text(applied), text(live)) {
+        (Some(a), Some(l)) if a == l => true,
+        (Some(a), Some(l)) => matches!(
+            (quantity_value(&Quantity(a)), quantity_value(&Quantity(l))),
+            (Some(a), Some(l)) if (a - l).abs() <= f64::EPSILON * a.abs().max(1.0)
+        ),
+        _ => false,
+    }
+}
+
+//lists of objects carrying a name (containers, env, ports, volumes) are matched by name.
+fn named(items: &[Value]) -> Option<Vec<&str>> {
+    items
+        .iter()
+        .map(|i| i.get("name").and_then(|n| n.as_str()))
+        .collect()
+}
+
+//the changes from the applied manifest to the live object: a field of the manifest that differs or is gone,
+//and a field only in the live object unless the api server defaults it (DEFAULTED_FIELDS) or it is empty.
+pub fn value_diff(applied: &Value, live: &Value, path: &str, out: &mut Vec<FieldChange>) {
+    let child = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    match (applied, live) {
+        //`resources: {}`, `strategy: {}` of generated manifests leave everything to the defaults.
+        (Value::Object(a), Value::Object(_)) if a.is_empty() => {}
+        (Value::Object(a), Value::Object(l)) => {
+            for (k, av) in a {
+                match l.get(k) {
+                    Some(lv) => value_diff(av, lv, &child(k), out),
+                    None if empty(av) => {}
+                    None => out.push(FieldChange::Removed {
+                        path: child(k),
+                        applied: av.clone(),
+                    }),
+                }
+            }
+            for (k, lv) in l {
+                if !a.contains_key(k) && !empty(lv) && !DEFAULTED_FIELDS.contains(&k.as_str()) {
+                    out.push(FieldChange::Added {
+                        path: child(k),
+                        live: lv.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(l)) => match (named(a), named(l)) {
+            (Some(an), Some(ln)) => {
+                for (name, av) in an.iter().zip(a) {
+                    let item = format!("{}[name={}]", path, name);
+                    match ln.iter().position(|n| n == name) {
+                        Some(i) => value_diff(av, &l[i], &item, out),
+                        None => out.push(FieldChange::Removed {
+                            path: item,
+                            applied: av.clone(),
+                        }),
+                    }
+                }
+                for (name, lv) in ln.iter().zip(l) {
+                    if !an.contains(name) {
+                        out.push(FieldChange::Added {
+                            path: format!("{}[name={}]", path, name),
+                            live: lv.clone(),
+                        });
+                    }
+                }
+            }
+            _ if a.len() == l.len() => {
+                for (i, (av, lv)) in a.iter().zip(l).enumerate() {
+                    value_diff(av, lv, &format!("{}[{}]", path, i), out);
+                }
+            }
+            _ => out.push(FieldChange::Changed {
+                path: path.to_string(),
+                applied: applied.clone(),
+                live: live.clone(),
+            }),
+        },
+        _ if same_scalar(applied, live) => {}
+        _ => out.push(FieldChange::Changed {
+            path: path.to_string(),
+            applied: applied.clone(),
+            live: live.clone(),
+        }),
+    }
+}
+
+//the spec of the last applied configuration against the live spec, None without the annotation.
+pub fn spec_drift(live: &Value) -> Result<Option<Vec<FieldChange>>> {
+    let Some(applied) = live
+        .pointer("/metadata/annotations")
+        .and_then(|a| a.get(LAST_APPLIED_ANNOTATION))
+        .and_then(|a| a.as_str())
+    else {
+        return Ok(None);
+    };
+    let applied: Value = serde_json::from_str(applied)?;
+    let mut changes = vec![];
+    value_diff(
+        applied.get("spec").unwrap_or(&Value::Null),
+        live.get("spec").unwrap_or(&Value::Null),
+        "spec",
+        &mut changes,
+    );
+    Ok(Some(changes))
+}
+
+//one section per Deployment/StatefulSet carrying the annotation, "<namespace>/<Kind>/<name>".
+pub fn render_report(drifts: &[(String, Result<Vec<FieldChange>>)]) -> String {
+    let changed = drifts
+        .iter()
+        .filter(|(_, d)| d.as_ref().is_ok_and(|c| !c.is_empty()))
+        .count();
+    let mut out = format!(
+        "{} workloads with a {} annotation, {} changed by hand since.\n",
+        drifts.len(),
+        LAST_APPLIED_ANNOTATION,
+        changed
+    );
+    for (workload, drift) in drifts {
+        match drift {
+            Ok(changes) if changes.is_empty() => {
+                out.push_str(&format!("\n{}: as applied\n", workload))
+            }
+            Ok(changes) => {
+                out.push_str(&format!("\n{}: {} fields\n", workload, changes.len()));
+                for c in changes {
+                    out.push_str(&format!("  {}\n", c.render()));
+                }
+            }
+            Err(e) => out.push_str(&format!("\n{}: unreadable annotation, {}\n", workload, e)),
+        }
+    }
+    out
+}
+
+async fn namespace_drifts(
+    collector: &Collector,
+    ns: &str,
+    drifts: &mut Vec<(String, Result<Vec<FieldChange>>)>,
+) -> Result<()> {
+    let deployments: Api<Deployment> = Api::namespaced(collector.client.clone(), ns);
+    let statefulsets: Api<StatefulSet> = Api::namespaced(collector.client.clone(), ns);
+    let mut objects = vec![];
+    for d in deployments.list(&ListParams::default()).await?.items {
+        objects.push((
+            format!("{}/Deployment/{}", ns, d.name_any()),
+            serde_json::to_value(d)?,
+        ));
+    }
+    for s in statefulsets.list(&ListParams::default()).await?.items {
+        objects.push((
+            format!("{}/StatefulSet/{}", ns, s.name_any()),
+            serde_json::to_value(s)?,
+        ));
+    }
+    for (workload, live) in objects {
+        match spec_drift(&live) {
+            Ok(None) => {}
+            Ok(Some(changes)) => drifts.push((workload, Ok(changes))),
+            Err(e) => drifts.push((workload, Err(e))),
+        }
+    }
+    Ok(())
+}
+
+pub async fn collect_manual_changes(collector: &Collector, folder: &str) -> Result<()> {
+    let mut drifts = vec![];
+    for ns in &collector.config.context_namespace {
+        if let Err(e) = namespace_drifts(collector, ns, &mut drifts).await {
+            warn!("Manual changes in {}: {}", ns, e);
+            collector.ctx.record_folder(folder, false);
+        }
+    }
+    let report = render_report(&drifts);
+    let er = anyhow!("Empty {}.", MANUAL_CHANGE_REPORT_FILE);
+    collector
+        .ctx
+        .write_file(folder, report.as_bytes(), MANUAL_CHANGE_REPORT_FILE, er)?;
+    info!(
+        "File has been created {}/{}",
+        folder, MANUAL_CHANGE_REPORT_FILE
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+    use serde_json::json;
+
+    fn diff(applied: Value, live: Value) -> Vec<String> {
+        let mut out = vec![];
+        value_diff(&applied, &live, "spec", &mut out);
+        let mut rendered = out.iter().map(|c| c.render()).collect::<Vec<_>>();
+        rendered.sort();
+        rendered
+    }
+
+    fn deployment() -> Value {
+        serde_json::from_str(
+            &std::fs::read_to_string(fixture("manual_changes/deployment.json")).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn defaulted_and_normalized_fields_are_no_drift() {
+        let applied = json!({"strategy": {}, "template": {"spec": {"containers": [
+            {"name": "app", "resources": {"limits": {"cpu": "1", "memory": "1Gi"}}, "ports": [{"containerPort": 8080}]}
+        ]}}});
+        let live = json!({"strategy": {"type": "RollingUpdate"}, "revisionHistoryLimit": 10,
+        "template": {"metadata": {"creationTimestamp": null}, "spec": {"dnsPolicy": "ClusterFirst", "containers": [
+            {"name": "app", "imagePullPolicy": "Always", "resources": {"limits": {"cpu": "1000m", "memory": "1024Mi"}},
+             "ports": [{"containerPort": 8080, "protocol": "TCP"}]}
+        ]}}});
+        assert!(diff(applied, live).is_empty());
+        //a number the api server turned into a string.
+        assert!(diff(json!({"x": 1}), json!({"x": "1"})).is_empty());
+    }
+
+    #[test]
+    fn changed_removed_and_added_fields() {
+        let applied =
+            json!({"replicas": 3, "paused": false, "selector": {"app": "web"}, "labels": {}});
+        let live = json!({"replicas": 5, "selector": {"app": "web"}, "minReadySeconds": 5, "hostNetwork": true});
+        assert_eq!(
+            diff(applied, live),
+            vec![
+                "added spec.hostNetwork: true",
+                "changed spec.replicas: 3 -> 5",
+                "removed spec.paused: was false",
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_matched_by_name_or_position() {
+        let applied = json!({"env": [{"name": "A", "value": "1"}, {"name": "B", "value": "2"}]});
+        let live = json!({"env": [{"name": "C", "value": "3"}, {"name": "A", "value": "1"}]});
+        assert_eq!(
+            diff(applied, live),
+            vec![
+                "added spec.env[name=C]: {\"name\":\"C\",\"value\":\"3\"}",
+                "removed spec.env[name=B]: was {\"name\":\"B\",\"value\":\"2\"}",
+            ]
+        );
+        assert_eq!(
+            diff(json!({"args": ["a", "b"]}), json!({"args": ["a", "c"]})),
+            vec!["changed spec.args[1]: \"b\" -> \"c\""]
+        );
+        assert_eq!(
+            diff(json!({"args": ["a"]}), json!({"args": ["a", "b"]})),
+            vec!["changed spec.args: [\"a\"] -> [\"a\",\"b\"]"]
+        );
+    }
+
+    #[test]
+    fn hand_changes_of_a_live_deployment() {
+        let changes = spec_drift(&deployment()).unwrap().unwrap();
+        let mut rendered = changes.iter().map(|c| c.render()).collect::<Vec<_>>();
+        rendered.sort();
+        assert_eq!(
+            rendered,
+            vec![
+                "added spec.template.spec.containers[name=app].env[name=DEBUG]: {\"name\":\"DEBUG\",\"value\":\"true\"}",
+                "added spec.template.spec.containers[name=proxy].args: [\"--log-level\",\"debug\"]",
+                "changed spec.replicas: 3 -> 5",
+                "changed spec.template.spec.containers[name=app].image: \"web:1.4.0\" -> \"web:1.4.1-hotfix\"",
+            ]
+        );
+        //the status is never compared.
+        assert!(changes.iter().all(|c| !c.render().contains("status")));
+    }
+
+    #[test]
+    fn annotation_missing_or_unreadable() {
+        let mut live = deployment();
+        live["metadata"]["annotations"][LAST_APPLIED_ANNOTATION] = json!("{not json");
+        let e = spec_drift(&live).unwrap_err();
+        live["metadata"]["annotations"]
+            .as_object_mut()
+            .unwrap()
+            .remove(LAST_APPLIED_ANNOTATION);
+        assert_eq!(spec_drift(&live).unwrap(), None);
+        let mut changes = spec_drift(&deployment()).unwrap().unwrap();
+        changes.truncate(1);
+        let report = render_report(&[
+            ("shop/Deployment/web".to_string(), Ok(changes)),
+            ("shop/StatefulSet/db".to_string(), Ok(vec![])),
+            ("shop/Deployment/bad".to_string(), Err(e)),
+        ]);
+        assert!(report.starts_with(&format!(
+            "3 workloads with a {} annotation, 1 changed by hand since.\n",
+            LAST_APPLIED_ANNOTATION
+        )));
+        assert!(report.contains("\nshop/Deployment/web: 1 fields\n  "));
+        assert!(report.contains("\nshop/StatefulSet/db: as applied\n"));
+        assert!(report.contains("\nshop/Deployment/bad: unreadable annotation, "));
+    }
+}
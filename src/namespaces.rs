@@ -0,0 +1,215 @@
+use anyhow::Result;
+use k8s_openapi::api::core::v1::Namespace;
+use kube::{api::ListParams, Api, Client};
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use simplelog::{__private::log::warn, info};
+
+use std::collections::BTreeSet;
+
+use crate::{pod_bundle::edit_distance, PodInfo};
+
+//a typo is at most that many edits away from the namespace meant.
+pub const MAX_SUGGESTION_DISTANCE: usize = 2;
+pub const MAX_SUGGESTIONS: usize = 3;
+
+//a configured namespace without any pod, in collection_info.json.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct EmptyNamespace {
+    pub namespace: String,
+    //the namespace exists and is genuinely empty, otherwise a likely typo.
+    pub exists: bool,
+    //cluster namespaces close to a missing one, closest first.
+    pub suggestions: Vec<String>,
+}
+
+//cluster namespaces at most MAX_SUGGESTION_DISTANCE edits away or sharing a prefix with the
+//name (kafka for kafka-prod), by distance then name.
+pub fn namespace_suggestions(name: &str, cluster_namespaces: &[String]) -> Vec<String> {
+    let mut matches = cluster_namespaces
+        .iter()
+        .filter(|c| c.as_str() != name)
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|(d, c)| {
+            *d <= MAX_SUGGESTION_DISTANCE
+                || (!name.is_empty() && (c.starts_with(name) || name.starts_with(c.as_str())))
+        })
+        .collect::<Vec<_>>();
+    matches.sort();
+    matches
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c.clone())
+        .collect()
+}
+
+//the configured namespaces no pod came from, in configuration order.
+pub fn empty_namespaces<A>(
+    configured: &[String],
+    pods: &[PodInfo<A>],
+    cluster_namespaces: &[String],
+) -> Vec<EmptyNamespace> {
+    let with_pods = pods
+        .iter()
+        .map(|p| p.namespace.as_str())
+        .collect::<BTreeSet<&str>>();
+    configured
+        .iter()
+        .filter(|n| !with_pods.contains(n.as_str()))
+        .map(|n| {
+            let exists = cluster_namespaces.contains(n);
+            EmptyNamespace {
+                namespace: n.clone(),
+                exists,
+                suggestions: if exists {
+                    vec![]
+                } else {
+                    namespace_suggestions(n, cluster_namespaces)
+                },
+            }
+        })
+        .collect()
+}
+
+pub fn describe(empty: &EmptyNamespace) -> String {
+    match (empty.exists, empty.suggestions.is_empty()) {
+        (true, _) => format!("Namespace {} exists but has no pod.", empty.namespace),
+        (false, true) => format!(
+            "Namespace {} does not exist in the cluster.",
+            empty.namespace
+        ),
+        (false, false) => format!(
+            "Namespace {} does not exist in the cluster, did you mean {}?",
+            empty.namespace,
+            empty.suggestions.join(", ")
+        ),
+    }
+}
+
+//compares the namespaces that produced no pod with the namespaces of the cluster, a typo in
+//context_namespace would otherwise only show as an empty collection.
+pub async fn check_empty_namespaces<A>(
+    client: &Client,
+    configured: &[String],
+    pods: &[PodInfo<A>],
+) -> Result<Vec<EmptyNamespace>> {
+    if configured
+        .iter()
+        .all(|n| pods.iter().any(|p| &p.namespace == n))
+    {
+        return Ok(vec![]);
+    }
+    let cluster_namespaces = Api::<Namespace>::all(client.clone())
+        .list(&ListParams::default())
+        .await?
+        .items
+        .into_iter()
+        .filter_map(|n| n.metadata.name)
+        .collect::<Vec<String>>();
+    let empty = empty_namespaces(configured, pods, &cluster_namespaces);
+    for e in &empty {
+        if e.exists {
+            info!("{}", describe(e));
+        } else {
+            warn!("<yellow>{}</>", describe(e));
+        }
+    }
+    Ok(empty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{json_client, offline_client, pod_info};
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn pod(namespace: &str) -> PodInfo<()> {
+        pod_info(serde_json::json!({
+            "metadata": {"name": "app-0", "namespace": namespace},
+            "spec": {"containers": [{"name": "main"}]}
+        }))
+    }
+
+    #[test]
+    fn typos_and_prefixes_are_suggested() {
+        let cluster = names(&[
+            "kafka-prod",
+            "kafak",
+            "monitoring",
+            "kube-system",
+            "default",
+        ]);
+        //two edits away, or the configured name is a prefix.
+        assert_eq!(
+            namespace_suggestions("kafka", &cluster),
+            vec!["kafak", "kafka-prod"]
+        );
+        assert_eq!(
+            namespace_suggestions("monitorng", &cluster),
+            vec!["monitoring"]
+        );
+        //kafka-prod-eu starts with the cluster one.
+        assert_eq!(
+            namespace_suggestions("kafka-prod-eu", &cluster),
+            vec!["kafka-prod"]
+        );
+        assert!(namespace_suggestions("payments", &cluster).is_empty());
+        assert!(namespace_suggestions("", &cluster).is_empty());
+    }
+
+    #[test]
+    fn closest_first_then_by_name_at_most_three() {
+        let cluster = names(&["web-b", "web-a", "wbe", "web", "web-dev", "web-staging"]);
+        //the name itself is never suggested.
+        assert_eq!(
+            namespace_suggestions("web", &cluster),
+            vec!["wbe", "web-a", "web-b"]
+        );
+        assert_eq!(
+            namespace_suggestions("web-c", &cluster),
+            vec!["web-a", "web-b", "web"]
+        );
+    }
+
+    //async for the offline client of the pods.
+    #[tokio::test]
+    async fn empty_namespaces_tell_missing_from_empty() {
+        let cluster = names(&["kafka", "monitoring", "web"]);
+        let configured = names(&["kafka", "monitorng", "web", "payments"]);
+        let empty = empty_namespaces(&configured, &[pod("kafka")], &cluster);
+        assert_eq!(
+            empty.iter().map(describe).collect::<Vec<_>>(),
+            vec![
+                "Namespace monitorng does not exist in the cluster, did you mean monitoring?",
+                "Namespace web exists but has no pod.",
+                "Namespace payments does not exist in the cluster.",
+            ]
+        );
+        assert!(empty[1].suggestions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn namespaces_listed_only_when_one_has_no_pod() {
+        let list = serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "NamespaceList",
+            "metadata": {},
+            "items": [{"metadata": {"name": "kafka"}}, {"metadata": {"name": "monitoring"}}]
+        });
+        let client = json_client(&[("/api/v1/namespaces", list.to_string())]);
+        let empty =
+            check_empty_namespaces(&client, &names(&["kafka", "monitorng"]), &[pod("kafka")])
+                .await
+                .unwrap();
+        assert_eq!(empty.len(), 1);
+        assert_eq!(empty[0].suggestions, vec!["monitoring"]);
+        //every namespace has pods: the offline client is never called.
+        let empty = check_empty_namespaces(&offline_client(), &names(&["kafka"]), &[pod("kafka")])
+            .await
+            .unwrap();
+        assert!(empty.is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::PodInfo;
+
+pub const PLACEHOLDERS: [&str; 5] = ["namespace", "pod", "container", "uid", "kind"];
+
+//file names of the per pod outputs, the defaults are the historical names.
+//{kind} is current/previous for logs and the built-in output name for app outputs.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FileNameTemplates {
+    pub log: String,
+    pub description: String,
+    pub app_output: String,
+}
+
+impl Default for FileNameTemplates {
+    fn default() -> Self {
+        FileNameTemplates {
+            log: "logs_{kind}_{namespace}_{pod}_{container}.log".to_string(),
+            description: "{namespace}_{pod}.description".to_string(),
+            app_output: "{kind}".to_string(),
+        }
+    }
+}
+
+//the placeholders used by a template, an error on unknown or unbalanced ones.
+pub fn placeholders(template: &str) -> Result<Vec<String>> {
+    let mut found = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        if rest[start..].starts_with('}') {
+            return Err(anyhow!(
+                "Unbalanced '}}' in file name template {}.",
+                template
+            ));
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("Unbalanced '{{' in file name template {}.", template))?;
+        let name = &rest[start + 1..start + end];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(anyhow!(
+                "Unknown placeholder {{{}}} in file name template {}, expected one of {}.",
+                name,
+                template,
+                PLACEHOLDERS.map(|p| format!("{{{}}}", p)).join(", ")
+            ));
+        }
+        found.push(name.to_string());
+        rest = &rest[start + end + 1..];
+    }
+    Ok(found)
+}
+
+fn check(name: &str, template: &str, required: &[&[&str]]) -> Result<()> {
+    if template.is_empty() || template.contains('/') {
+        return Err(anyhow!(
+            "file_name_templates.{} must be a non empty file name without '/'.",
+            name
+        ));
+    }
+    let used = placeholders(template)?;
+    for alternatives in required {
+        if !alternatives.iter().any(|a| used.iter().any(|u| u == a)) {
+            return Err(anyhow!(
+                "file_name_templates.{} ({}) needs {} or distinct outputs would overwrite each other.",
+                name,
+                template,
+                alternatives
+                    .iter()
+                    .map(|a| format!("{{{}}}", a))
+                    .collect::<Vec<String>>()
+                    .join(" or ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl FileNameTemplates {
+    //pod names only repeat across namespaces, so {namespace} is needed with several of them unless {uid} is used.
+    pub fn validate(&self, namespaces: usize) -> Result<()> {
+        let mut pod_required: Vec<&[&str]> = vec![&["pod", "uid"]];
+        if namespaces > 1 {
+            pod_required.push(&["namespace", "uid"]);
+        }
+        let mut log_required = pod_required.clone();
+        log_required.extend([&["container"][..], &["kind"]]);
+        check("log", &self.log, &log_required)?;
+        check("description", &self.description, &pod_required)?;
+        check("app_output", &self.app_output, &[&["kind"]])?;
+        Ok(())
+    }
+
+    pub fn log<A>(&self, pod: &PodInfo<A>, container: &str, previous: bool) -> String {
+        let kind = if previous { "previous" } else { "current" };
+        expand(&self.log, pod, container, kind)
+    }
+
+    pub fn description<A>(&self, pod: &PodInfo<A>) -> String {
+        expand(&self.description, pod, "", "")
+    }
+
+    //kind is the built-in name of the output (elastic_search_nodes.json, kafka_topics.log, ...).
+    pub fn app_output<A>(&self, pod: &PodInfo<A>, container: &str, kind: &str) -> String {
+        expand(&self.app_output, pod, container, kind)
+    }
+}
+
+pub fn expand<A>(template: &str, pod: &PodInfo<A>, container: &str, kind: &str) -> String {
+    let uid = pod.pod.metadata.uid.clone().unwrap_or_default();
+    template
+        .replace("{namespace}", &pod.namespace)
+        .replace("{pod}", &pod.name)
+        .replace("{container}", container)
+        .replace("{uid}", &uid)
+        .replace("{kind}", kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{pod_info, run_context, TempDir};
+
+    fn pod(namespace: &str, name: &str, uid: &str) -> PodInfo<()> {
+        pod_info(serde_json::json!({
+            "metadata": {"name": name, "namespace": namespace, "uid": uid},
+            "spec": {"containers": [{"name": "main"}]}
+        }))
+    }
+
+    fn templates(log: &str, description: &str) -> FileNameTemplates {
+        FileNameTemplates {
+            log: log.to_string(),
+            description: description.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn defaults_are_the_historical_names() {
+        let t = FileNameTemplates::default();
+        t.validate(3).unwrap();
+        let p = pod("kafka", "kafka-0", "u1");
+        assert_eq!(
+            t.log(&p, "kafka", false),
+            "logs_current_kafka_kafka-0_kafka.log"
+        );
+        assert_eq!(
+            t.log(&p, "kafka", true),
+            "logs_previous_kafka_kafka-0_kafka.log"
+        );
+        assert_eq!(t.description(&p), "kafka_kafka-0.description");
+        assert_eq!(
+            t.app_output(&p, "kafka", "kafka_topics.log"),
+            "kafka_topics.log"
+        );
+    }
+
+    #[test]
+    fn expand_fills_every_placeholder() {
+        let p = pod("web", "web-0", "0b1c");
+        assert_eq!(
+            expand(
+                "{uid}-{namespace}.{pod}.{container}.{kind}",
+                &p,
+                "nginx",
+                "current"
+            ),
+            "0b1c-web.web-0.nginx.current"
+        );
+        //a pod without uid gets an empty one.
+        let mut p = p;
+        p.pod.metadata.uid = None;
+        assert_eq!(expand("{pod}_{uid}.log", &p, "", ""), "web-0_.log");
+    }
+
+    #[test]
+    fn placeholders_reject_unknown_and_unbalanced_braces() {
+        assert_eq!(
+            placeholders("{pod}_{kind}.log").unwrap(),
+            vec!["pod", "kind"]
+        );
+        assert!(placeholders("{node}.log").is_err());
+        assert!(placeholders("{pod.log").is_err());
+        assert!(placeholders("pod}.log").is_err());
+    }
+
+    #[test]
+    fn validate_needs_the_distinguishing_placeholders() {
+        //one namespace: the pod name is enough.
+        templates("{pod}_{container}_{kind}.log", "{pod}.txt")
+            .validate(1)
+            .unwrap();
+        let e = templates("{pod}_{container}_{kind}.log", "{pod}.txt").validate(2);
+        assert!(e.unwrap_err().to_string().contains("{namespace} or {uid}"));
+        //the uid stands for both the pod and the namespace.
+        templates("{uid}_{container}_{kind}.log", "{uid}.txt")
+            .validate(2)
+            .unwrap();
+        for log in [
+            "{pod}_{kind}.log",
+            "{pod}_{container}.log",
+            "{namespace}_{container}_{kind}.log",
+        ] {
+            assert!(templates(log, "{pod}.txt").validate(1).is_err(), "{}", log);
+        }
+        assert!(templates("{pod}_{container}_{kind}.log", "")
+            .validate(1)
+            .is_err());
+        assert!(templates("{pod}/{container}_{kind}.log", "{pod}")
+            .validate(1)
+            .is_err());
+        let app = FileNameTemplates {
+            app_output: "{pod}.out".to_string(),
+            ..Default::default()
+        };
+        assert!(app.validate(1).is_err());
+    }
+
+    #[test]
+    fn template_without_the_namespace_collides_across_namespaces() {
+        let dir = TempDir::new();
+        let ctx = run_context(&dir);
+        let t = templates("{pod}_{container}_{kind}.log", "{pod}.description");
+        let (a, b) = (pod("ns-a", "web-0", "u1"), pod("ns-b", "web-0", "u2"));
+        let folder = ctx.folders[0].clone();
+        let claim =
+            |p: &PodInfo<()>| ctx.claim_output(&folder, &t.description(p), &p.namespace, &p.name);
+        claim(&a).unwrap();
+        claim(&a).unwrap();
+        let e = claim(&b).unwrap_err().to_string();
+        assert!(
+            e.contains("pod ns-b/web-0 collides with the one of pod ns-a/web-0"),
+            "{}",
+            e
+        );
+        //the default names keep them apart.
+        let d = FileNameTemplates::default();
+        for p in [&a, &b] {
+            ctx.claim_output(&folder, &d.description(p), &p.namespace, &p.name)
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,663 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::core::v1::{Event, Namespace, Pod, Toleration};
+use kube::{
+    api::{DeleteParams, ListParams, PostParams},
+    Api, Client,
+};
+use simplelog::{__private::log::warn, info};
+
+use std::{fs, time::Duration};
+
+use crate::{
+    access::{KubeAccess, PodAccess},
+    context::RunContext,
+    ConfigFile,
+};
+
+pub const DEFAULT_NODE_DEBUG_IMAGE: &str = "busybox:1.36";
+//privileged pods are usually allowed there, a restricted cluster needs node_debug_namespace.
+pub const DEFAULT_NODE_DEBUG_NAMESPACE: &str = "kube-system";
+pub const DEFAULT_DEBUG_POD_TIMEOUT_SECONDS: u64 = 120;
+pub const RUNTIME_PATH_FILE: &str = "runtime_path.txt";
+const DEBUG_POD_POLL: Duration = Duration::from_secs(2);
+const DEBUG_CONTAINER: &str = "debugger";
+const PSS_ENFORCE_LABEL: &str = "pod-security.kubernetes.io/enforce";
+//the events and container states a debug pod does not recover from.
+const FAILURE_EVENT_REASONS: [&str; 3] = ["FailedScheduling", "FailedCreatePodSandBox", "Failed"];
+const FAILURE_WAITING_REASONS: [&str; 5] = [
+    "ErrImagePull",
+    "ImagePullBackOff",
+    "InvalidImageName",
+    "CreateContainerConfigError",
+    "CreateContainerError",
+];
+
+//how the debug pods are made, from the node_debug_* settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugPodTemplate {
+    pub image: String,
+    pub namespace: String,
+    pub tolerations: Vec<Toleration>,
+    pub runtime_class: Option<String>,
+    pub timeout: Duration,
+}
+
+impl DebugPodTemplate {
+    pub fn from_config(config: &ConfigFile) -> Self {
+        let or = |value: &str, default: &str| match value {
+            "" => default.to_string(),
+            v => v.to_string(),
+        };
+        DebugPodTemplate {
+            image: or(&config.node_debug_image, DEFAULT_NODE_DEBUG_IMAGE),
+            namespace: or(&config.node_debug_namespace, DEFAULT_NODE_DEBUG_NAMESPACE),
+            //every taint tolerated, masters and dedicated pools included.
+            tolerations: if config.node_debug_tolerations.is_empty() {
+                vec![Toleration {
+                    operator: Some("Exists".to_string()),
+                    ..Default::default()
+                }]
+            } else {
+                config.node_debug_tolerations.clone()
+            },
+            runtime_class: Some(config.node_debug_runtime_class.clone()).filter(|c| !c.is_empty()),
+            timeout: Duration::from_secs(match config.node_debug_timeout_seconds {
+                0 => DEFAULT_DEBUG_POD_TIMEOUT_SECONDS,
+                s => s,
+            }),
+        }
+    }
+
+    //privileged pod with the host root mounted on /host, bound to the node by the scheduler
+    //(like a daemonset pod) so taints and selectors are checked and a refusal shows as an event.
+    pub fn pod(&self, node: &str) -> Result<Pod> {
+        let name = format!("antlog-node-debug-{}", node)
+            .chars()
+            .take(63)
+            .collect::<String>()
+            .trim_end_matches(['-', '.'])
+            .to_string();
+        let mut spec = serde_json::json!({
+            "affinity": {"nodeAffinity": {"requiredDuringSchedulingIgnoredDuringExecution": {
+                "nodeSelectorTerms": [{"matchFields": [
+                    {"key": "metadata.name", "operator": "In", "values": [node]}
+                ]}]
+            }}},
+            "hostPID": true,
+            "hostNetwork": true,
+            "restartPolicy": "Never",
+            "tolerations": self.tolerations,
+            "containers": [{
+                "name": DEBUG_CONTAINER,
+                "image": self.image,
+                "command": ["sleep", "3600"],
+                "securityContext": {"privileged": true},
+                "volumeMounts": [{"name": "host", "mountPath": "/host"}]
+            }],
+            "volumes": [{"name": "host", "hostPath": {"path": "/"}}]
+        });
+        if let Some(class) = &self.runtime_class {
+            spec["runtimeClassName"] = serde_json::json!(class);
+        }
+        Ok(serde_json::from_value(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {
+                "name": name,
+                "namespace": self.namespace,
+                "labels": {"app.kubernetes.io/managed-by": "antlog"}
+            },
+            "spec": spec
+        }))?)
+    }
+}
+
+//why a debug pod will never run: failed, unschedulable, an image that cannot be pulled or
+//a warning event the pod does not recover from. None while it may still start.
+pub fn debug_pod_failure(pod: &Pod, events: &[Event]) -> Option<String> {
+    let status = pod.status.as_ref();
+    if let Some(s) = status.filter(|s| s.phase.as_deref() == Some("Failed")) {
+        return Some(format!(
+            "pod failed: {}",
+            s.message
+                .clone()
+                .or_else(|| s.reason.clone())
+                .unwrap_or_default()
+        ));
+    }
+    let unschedulable = status
+        .and_then(|s| s.conditions.as_ref())
+        .into_iter()
+        .flatten()
+        .find(|c| {
+            c.type_ == "PodScheduled"
+                && c.status == "False"
+                && c.reason.as_deref() == Some("Unschedulable")
+        });
+    if let Some(c) = unschedulable {
+        return Some(format!(
+            "unschedulable: {}",
+            c.message.clone().unwrap_or_default()
+        ));
+    }
+    let waiting = status
+        .and_then(|s| s.container_statuses.as_ref())
+        .into_iter()
+        .flatten()
+        .filter_map(|c| c.state.as_ref()?.waiting.as_ref())
+        .find(|w| {
+            w.reason
+                .as_deref()
+                .is_some_and(|r| FAILURE_WAITING_REASONS.contains(&r))
+        });
+    if let Some(w) = waiting {
+        return Some(format!(
+            "{}: {}",
+            w.reason.clone().unwrap_or_default(),
+            w.message.clone().unwrap_or_default()
+        ));
+    }
+    events
+        .iter()
+        .filter(|e| e.type_.as_deref() == Some("Warning"))
+        .find(|e| {
+            e.reason
+                .as_deref()
+                .is_some_and(|r| FAILURE_EVENT_REASONS.contains(&r))
+        })
+        .map(|e| {
+            format!(
+                "{}: {}",
+                e.reason.clone().unwrap_or_default(),
+                e.message.clone().unwrap_or_default()
+            )
+        })
+}
+
+//the namespace of the debug pods, created with the privileged pod security level when missing.
+//true when it was created, delete_namespace removes it after the collection.
+pub async fn ensure_namespace(client: &Client, namespace: &str) -> Result<bool> {
+    let namespaces: Api<Namespace> = Api::all(client.clone());
+    //without the right to read namespaces, the pod creation tells.
+    let existing = match namespaces.get_opt(namespace).await {
+        Ok(ns) => ns,
+        Err(kube::Error::Api(e)) if e.code == 403 => {
+            warn!("Namespace {} cannot be read: {}", namespace, e.message);
+            return Ok(false);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(ns) = existing {
+        let level = ns
+            .metadata
+            .labels
+            .as_ref()
+            .and_then(|l| l.get(PSS_ENFORCE_LABEL))
+            .cloned();
+        if level.as_deref().is_some_and(|l| l != "privileged") {
+            warn!(
+                "<yellow>Namespace {} enforces the {} pod security level, the privileged debug pods will be rejected: set node_debug_namespace.</>",
+                namespace,
+                level.unwrap_or_default()
+            );
+        }
+        return Ok(false);
+    }
+    let ns: Namespace = serde_json::from_value(serde_json::json!({
+        "apiVersion": "v1",
+        "kind": "Namespace",
+        "metadata": {
+            "name": namespace,
+            "labels": {
+                PSS_ENFORCE_LABEL: "privileged",
+                "app.kubernetes.io/managed-by": "antlog"
+            }
+        }
+    }))?;
+    namespaces.create(&PostParams::default(), &ns).await?;
+    info!("Namespace {} created for the debug pods.", namespace);
+    Ok(true)
+}
+
+pub async fn delete_namespace(client: &Client, namespace: &str) {
+    let namespaces: Api<Namespace> = Api::all(client.clone());
+    match namespaces.delete(namespace, &DeleteParams::default()).await {
+        Ok(_) => info!("Namespace {} of the debug pods deleted.", namespace),
+        Err(e) => warn!("Namespace {} could not be deleted: {}", namespace, e),
+    }
+}
+
+//which runtime cli the node has, the commands differ between containerd and docker nodes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuntimePath {
+    Crictl,
+    Docker,
+    Unknown,
+}
+
+impl RuntimePath {
+    pub fn from_probe(output: &str) -> Self {
+        match output.trim() {
+            "crictl" => RuntimePath::Crictl,
+            "docker" => RuntimePath::Docker,
+            _ => RuntimePath::Unknown,
+        }
+    }
+
+    //(command run in the host root, file name).
+    pub fn commands(&self) -> Vec<(&'static str, &'static str)> {
+        let mut commands = match self {
+            RuntimePath::Crictl => vec![
+                ("crictl info", "crictl_info.json"),
+                ("crictl ps -a | head -n 200", "crictl_ps.txt"),
+                (
+                    "systemctl status containerd --no-pager",
+                    "containerd_status.txt",
+                ),
+            ],
+            RuntimePath::Docker => vec![
+                ("docker info", "docker_info.txt"),
+                ("docker ps -a | head -n 200", "docker_ps.txt"),
+                ("systemctl status docker --no-pager", "docker_status.txt"),
+            ],
+            RuntimePath::Unknown => vec![],
+        };
+        commands.push(("cat /var/lib/kubelet/config.yaml", "kubelet_config.yaml"));
+        commands.push(("systemctl status kubelet --no-pager", "kubelet_status.txt"));
+        commands
+    }
+}
+
+async fn host_command(access: &KubeAccess, pod: &str, command: &str) -> Result<String> {
+    access
+        .exec(
+            pod,
+            DEBUG_CONTAINER,
+            ["chroot", "/host", "sh", "-c", command]
+                .iter()
+                .map(|c| c.to_string())
+                .collect(),
+        )
+        .await
+}
+
+//runtime and kubelet info of one node under <folder>/nodes/<node>/runtime, the debug pod is always removed.
+//A pod that cannot be created or started fails the node, recorded next to its outputs.
+pub async fn collect_node_runtime(
+    client: Client,
+    template: &DebugPodTemplate,
+    node: &str,
+    folder: &str,
+    ctx: &RunContext,
+) -> Result<()> {
+    let folder = format!(
+        "{}/runtime",
+        ctx.subfolder(&format!("{}/nodes", folder), node)
+    );
+    fs::create_dir_all(&folder)?;
+    let namespace = template.namespace.as_str();
+    let pods: Api<Pod> = Api::namespaced(client.clone(), namespace);
+    let created = match pods
+        .create(&PostParams::default(), &template.pod(node)?)
+        .await
+    {
+        Ok(p) => p,
+        Err(e) => {
+            let e = if e.to_string().contains("violates PodSecurity") {
+                anyhow!(
+                    "Debug pod rejected by the pod security of namespace {}, set node_debug_namespace to a namespace allowing privileged pods: {}",
+                    namespace,
+                    e
+                )
+            } else {
+                e.into()
+            };
+            ctx.record_failure(&folder, RUNTIME_PATH_FILE, "create the node debug pod", &e);
+            return Err(e);
+        }
+    };
+    let name = created.metadata.name.unwrap_or_default();
+    info!(
+        "Debug pod {}/{} created for node {}.",
+        namespace, name, node
+    );
+    let guard = DebugPodGuard::new(pods, namespace, &name);
+
+    let result = match wait_running(&client, template, &name).await {
+        Ok(_) => collect_with_pod(client, namespace, &name, node, &folder, ctx).await,
+        Err(e) => {
+            let e = anyhow!("Debug pod {} on node {}: {}", name, node, e);
+            ctx.record_failure(&folder, RUNTIME_PATH_FILE, "start the node debug pod", &e);
+            Err(e)
+        }
+    };
+
+    guard.delete().await;
+    result
+}
+
+async fn delete_debug_pod(pods: &Api<Pod>, namespace: &str, name: &str) {
+    match pods.delete(name, &DeleteParams::default()).await {
+        Ok(_) => info!("Debug pod {}/{} deleted.", namespace, name),
+        Err(e) => warn!(
+            "Debug pod {}/{} could not be deleted: {}",
+            namespace, name, e
+        ),
+    }
+}
+
+//deletes the debug pod when dropped before delete, a cancelled or panicked collection must not
+//leave a privileged pod with the host root mounted behind it.
+struct DebugPodGuard {
+    pods: Api<Pod>,
+    namespace: String,
+    name: String,
+    deleted: bool,
+}
+
+impl DebugPodGuard {
+    fn new(pods: Api<Pod>, namespace: &str, name: &str) -> Self {
+        DebugPodGuard {
+            pods,
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            deleted: false,
+        }
+    }
+
+    async fn delete(mut self) {
+        self.deleted = true;
+        delete_debug_pod(&self.pods, &self.namespace, &self.name).await;
+    }
+}
+
+impl Drop for DebugPodGuard {
+    fn drop(&mut self) {
+        if self.deleted {
+            return;
+        }
+        let (pods, namespace, name) = (
+            self.pods.clone(),
+            std::mem::take(&mut self.namespace),
+            std::mem::take(&mut self.name),
+        );
+        //drop cannot wait, the deletion goes on in the runtime of the collection.
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                warn!(
+                    "Node debug of pod {}/{} interrupted, deleting the pod.",
+                    namespace, name
+                );
+                runtime.spawn(async move { delete_debug_pod(&pods, &namespace, &name).await });
+            }
+            Err(_) => warn!(
+                "<yellow>Debug pod {}/{} left running, delete it with kubectl.</>",
+                namespace, name
+            ),
+        }
+    }
+}
+
+//polls the pod and its events until it runs, fails for good (debug_pod_failure) or the timeout.
+async fn wait_running(client: &Client, template: &DebugPodTemplate, name: &str) -> Result<()> {
+    let pods: Api<Pod> = Api::namespaced(client.clone(), &template.namespace);
+    let events: Api<Event> = Api::namespaced(client.clone(), &template.namespace);
+    let lp = ListParams::default().fields(&format!("involvedObject.name={}", name));
+    let started = tokio::time::Instant::now();
+    loop {
+        let pod = pods.get(name).await?;
+        if pod.status.as_ref().and_then(|s| s.phase.as_deref()) == Some("Running") {
+            return Ok(());
+        }
+        let events = events.list(&lp).await.map(|l| l.items).unwrap_or_default();
+        if let Some(failure) = debug_pod_failure(&pod, &events) {
+            return Err(anyhow!("{}", failure));
+        }
+        if started.elapsed() >= template.timeout {
+            return Err(anyhow!("not running after {}s", template.timeout.as_secs()));
+        }
+        tokio::time::sleep(DEBUG_POD_POLL).await;
+    }
+}
+
+async fn collect_with_pod(
+    client: Client,
+    namespace: &str,
+    name: &str,
+    node: &str,
+    folder: &str,
+    ctx: &RunContext,
+) -> Result<()> {
+    let access = KubeAccess::namespaced(client, namespace).with_execs(ctx.execs.clone());
+    let probe_command = "if command -v crictl >/dev/null 2>&1; then echo crictl; elif command -v docker >/dev/null 2>&1; then echo docker; fi";
+    let probe = host_command(&access, name, probe_command)
+        .await
+        .inspect_err(|e| ctx.record_failure(folder, RUNTIME_PATH_FILE, probe_command, e))?;
+    let runtime = RuntimePath::from_probe(&probe);
+    info!("Node {} runtime path {:?}.", node, runtime);
+    let er = anyhow!("Empty runtime path for node {}.", node);
+    ctx.write_file(
+        folder,
+        format!("{:?}\n", runtime).as_bytes(),
+        RUNTIME_PATH_FILE,
+        er,
+    )?;
+
+    for (command, filename) in runtime.commands() {
+        let er = anyhow!("Empty output of {} on node {}.", command, node);
+        match host_command(&access, name, command)
+            .await
+            .and_then(|o| ctx.write_file(folder, o.as_bytes(), filename, er))
+        {
+            Ok(_) => info!("File has been created {}/{}", folder, filename),
+            Err(e) => warn!("{}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+    use hyper::{Body, Request, Response};
+    use tokio::sync::mpsc;
+
+    fn template(config: &ConfigFile) -> DebugPodTemplate {
+        DebugPodTemplate::from_config(config)
+    }
+
+    fn read<T: serde::de::DeserializeOwned>(name: &str) -> T {
+        let text = fs::read_to_string(fixture(&format!("node_debug/{}", name))).unwrap();
+        serde_json::from_str(&text).unwrap()
+    }
+
+    fn pod_named(name: &str) -> Pod {
+        read::<Vec<Pod>>("pods.json")
+            .into_iter()
+            .find(|p| p.metadata.name.as_deref() == Some(name))
+            .unwrap()
+    }
+
+    #[test]
+    fn the_pod_is_privileged_and_bound_to_its_node() {
+        let pod = template(&ConfigFile::default()).pod("node-a").unwrap();
+        assert_eq!(
+            pod.metadata.name.as_deref(),
+            Some("antlog-node-debug-node-a")
+        );
+        assert_eq!(
+            pod.metadata.namespace.as_deref(),
+            Some(DEFAULT_NODE_DEBUG_NAMESPACE)
+        );
+        let spec = pod.spec.unwrap();
+        let terms = &spec.affinity.unwrap().node_affinity.unwrap();
+        let fields = terms
+            .required_during_scheduling_ignored_during_execution
+            .as_ref()
+            .unwrap()
+            .node_selector_terms[0]
+            .match_fields
+            .clone()
+            .unwrap();
+        assert_eq!(fields[0].key, "metadata.name");
+        assert_eq!(fields[0].values, Some(vec!["node-a".to_string()]));
+        assert_eq!(spec.node_name, None);
+        assert_eq!(spec.host_pid, Some(true));
+        assert_eq!(spec.restart_policy.as_deref(), Some("Never"));
+        assert_eq!(spec.runtime_class_name, None);
+        //every taint tolerated by default.
+        let tolerations = spec.tolerations.unwrap();
+        assert_eq!(tolerations.len(), 1);
+        assert_eq!(tolerations[0].operator.as_deref(), Some("Exists"));
+        assert_eq!(tolerations[0].key, None);
+        let container = &spec.containers[0];
+        assert_eq!(container.name, DEBUG_CONTAINER);
+        assert_eq!(container.image.as_deref(), Some(DEFAULT_NODE_DEBUG_IMAGE));
+        assert_eq!(
+            container.security_context.as_ref().unwrap().privileged,
+            Some(true)
+        );
+        let volume = &spec.volumes.unwrap()[0];
+        assert_eq!(volume.host_path.as_ref().unwrap().path, "/");
+    }
+
+    #[test]
+    fn the_pod_follows_the_node_debug_settings() {
+        let config = ConfigFile {
+            node_debug_image: "registry.local/busybox:1.36".to_string(),
+            node_debug_namespace: "antlog-debug".to_string(),
+            node_debug_tolerations: vec![Toleration {
+                key: Some("dedicated".to_string()),
+                operator: Some("Equal".to_string()),
+                value: Some("gpu".to_string()),
+                effect: Some("NoSchedule".to_string()),
+                ..Default::default()
+            }],
+            node_debug_runtime_class: "runc".to_string(),
+            node_debug_timeout_seconds: 30,
+            ..Default::default()
+        };
+        let template = template(&config);
+        assert_eq!(template.timeout, Duration::from_secs(30));
+        let pod = template.pod("gpu-1").unwrap();
+        assert_eq!(pod.metadata.namespace.as_deref(), Some("antlog-debug"));
+        let spec = pod.spec.unwrap();
+        assert_eq!(spec.runtime_class_name.as_deref(), Some("runc"));
+        assert_eq!(spec.tolerations, Some(config.node_debug_tolerations));
+        assert_eq!(
+            spec.containers[0].image.as_deref(),
+            Some("registry.local/busybox:1.36")
+        );
+        assert_eq!(
+            DebugPodTemplate::from_config(&ConfigFile::default()).timeout,
+            Duration::from_secs(DEFAULT_DEBUG_POD_TIMEOUT_SECONDS)
+        );
+    }
+
+    #[test]
+    fn long_node_names_give_a_valid_pod_name() {
+        let node = format!("{}-{}", "a".repeat(44), "b".repeat(10));
+        let name = template(&ConfigFile::default())
+            .pod(&node)
+            .unwrap()
+            .metadata
+            .name
+            .unwrap();
+        //cut at 63 characters, without the '-' the cut ends on.
+        assert_eq!(name, format!("antlog-node-debug-{}", "a".repeat(44)));
+    }
+
+    #[test]
+    fn failures_the_pod_does_not_recover_from() {
+        let events: Vec<Event> = read("events.json");
+        assert_eq!(debug_pod_failure(&pod_named("pending"), &[]), None);
+        //normal events and warnings it recovers from are not failures.
+        assert_eq!(debug_pod_failure(&pod_named("pending"), &events[..2]), None);
+        assert_eq!(
+            debug_pod_failure(&pod_named("pending"), &events),
+            Some(
+                "FailedCreatePodSandBox: Failed to create pod sandbox: rpc error: code = Unknown desc = failed to setup network"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            debug_pod_failure(&pod_named("failed"), &[]),
+            Some("pod failed: The node was low on resource: ephemeral-storage.".to_string())
+        );
+        assert_eq!(
+            debug_pod_failure(&pod_named("failed-without-message"), &[]),
+            Some("pod failed: NodeAffinity".to_string())
+        );
+        assert_eq!(
+            debug_pod_failure(&pod_named("unschedulable"), &events),
+            Some(
+                "unschedulable: 0/3 nodes are available: 1 node(s) had untolerated taint {dedicated: gpu}."
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            debug_pod_failure(&pod_named("image-pull"), &[]),
+            Some(
+                "ImagePullBackOff: Back-off pulling image \"registry.local/busybox:1.36\""
+                    .to_string()
+            )
+        );
+    }
+
+    //a client answering every request with a success Status, the "<method> <path>" of each sent.
+    fn recording_client() -> (Client, mpsc::UnboundedReceiver<String>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let service = tower::service_fn(move |request: Request<Body>| {
+            let _ = sender.send(format!("{} {}", request.method(), request.uri().path()));
+            async {
+                Ok::<_, std::io::Error>(Response::new(Body::from(
+                    r#"{"kind":"Status","apiVersion":"v1","status":"Success","code":200}"#,
+                )))
+            }
+        });
+        (Client::new(service, "default"), receiver)
+    }
+
+    fn guard(client: &Client) -> DebugPodGuard {
+        let pods: Api<Pod> = Api::namespaced(client.clone(), "kube-system");
+        DebugPodGuard::new(pods, "kube-system", "antlog-node-debug-node-a")
+    }
+
+    const DELETE: &str = "DELETE /api/v1/namespaces/kube-system/pods/antlog-node-debug-node-a";
+
+    #[tokio::test]
+    async fn the_pod_is_deleted_once() {
+        let (client, mut requests) = recording_client();
+        guard(&client).delete().await;
+        assert_eq!(requests.recv().await.as_deref(), Some(DELETE));
+        tokio::task::yield_now().await;
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn a_cancelled_collection_deletes_the_pod() {
+        let (client, mut requests) = recording_client();
+        let guard = guard(&client);
+        let collection = tokio::spawn(async move {
+            let _guard = guard;
+            std::future::pending::<()>().await;
+        });
+        tokio::task::yield_now().await;
+        collection.abort();
+        assert!(collection.await.unwrap_err().is_cancelled());
+        assert_eq!(requests.recv().await.as_deref(), Some(DELETE));
+    }
+
+    #[tokio::test]
+    async fn a_panicked_collection_deletes_the_pod() {
+        let (client, mut requests) = recording_client();
+        let guard = guard(&client);
+        let collection = tokio::spawn(async move {
+            let _guard = guard;
+            panic!("collector bug");
+        });
+        assert!(collection.await.unwrap_err().is_panic());
+        assert_eq!(requests.recv().await.as_deref(), Some(DELETE));
+    }
+}
@@ -0,0 +1,234 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::core::v1::{Event, Node, Pod};
+use kube::{api::ListParams, Api, ResourceExt};
+use simplelog::{__private::log::warn, info};
+
+use crate::{collector::Collector, timeline::event_time, PodInfo};
+
+pub const NODE_PRESSURE_FILE: &str = "node_pressure_report.txt";
+pub const PRESSURE_CONDITIONS: [&str; 3] = ["MemoryPressure", "DiskPressure", "PIDPressure"];
+pub const EVICTED_REASON: &str = "evicted";
+//node event reasons of the kubelet eviction manager and image/disk garbage collection,
+//memory and pid pressure show as NodeHasInsufficientMemory/PID.
+const PRESSURE_EVENT_MARKERS: [&str; 5] = [
+    "Pressure",
+    "Insufficient",
+    "Eviction",
+    "FreeDiskSpace",
+    "ImageGC",
+];
+
+//the eviction message of a pod evicted by the kubelet (phase Failed, reason Evicted).
+pub fn eviction_message(pod: &Pod) -> Option<String> {
+    let status = pod.status.as_ref()?;
+    (status.phase.as_deref() == Some("Failed") && status.reason.as_deref() == Some("Evicted"))
+        .then(|| status.message.clone().unwrap_or_default())
+}
+
+//"<type>=<status> since <lastTransitionTime>" of the pressure conditions of the node.
+pub fn pressure_conditions(node: &Node) -> Vec<String> {
+    let conditions = node
+        .status
+        .as_ref()
+        .and_then(|s| s.conditions.as_ref())
+        .cloned()
+        .unwrap_or_default();
+    PRESSURE_CONDITIONS
+        .iter()
+        .map(|kind| match conditions.iter().find(|c| c.type_ == *kind) {
+            Some(c) => format!(
+                "{}={} since {}",
+                kind,
+                c.status,
+                c.last_transition_time
+                    .as_ref()
+                    .map(|t| t.0.to_rfc3339())
+                    .unwrap_or_else(|| "unknown".to_string())
+            ),
+            None => format!("{}=unknown", kind),
+        })
+        .collect()
+}
+
+fn pressure_event(e: &Event) -> bool {
+    e.involved_object.kind.as_deref() == Some("Node")
+        && e.reason
+            .as_deref()
+            .is_some_and(|r| PRESSURE_EVENT_MARKERS.iter().any(|m| r.contains(m)))
+}
+
+pub fn render_report(nodes: &[Node], pods: &[Pod], events: &[Event]) -> String {
+    let mut out = String::new();
+    for n in nodes {
+        let name = n.name_any();
+        out.push_str(&format!("node {}\n", name));
+        pressure_conditions(n)
+            .iter()
+            .for_each(|c| out.push_str(&format!("  {}\n", c)));
+        let mut node_events = events
+            .iter()
+            .filter(|e| pressure_event(e) && e.involved_object.name.as_deref() == Some(&name))
+            .collect::<Vec<&Event>>();
+        node_events.sort_by_key(|e| event_time(e));
+        for e in node_events {
+            out.push_str(&format!(
+                "  {} {} (x{}): {}\n",
+                event_time(e)
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or_else(|| "unknown".to_string()),
+                e.reason.clone().unwrap_or_default(),
+                e.count.unwrap_or(1),
+                e.message.clone().unwrap_or_default().trim()
+            ));
+        }
+    }
+    let evicted = pods
+        .iter()
+        .filter_map(|p| Some((p, eviction_message(p)?)))
+        .collect::<Vec<_>>();
+    out.push_str(&format!("\nevicted pods: {}\n", evicted.len()));
+    for (p, message) in evicted {
+        out.push_str(&format!(
+            "  {}/{} on {}: {}\n",
+            p.namespace().unwrap_or_default(),
+            p.name_any(),
+            p.spec
+                .as_ref()
+                .and_then(|s| s.node_name.clone())
+                .unwrap_or_else(|| "unknown".to_string()),
+            message
+        ));
+    }
+    out
+}
+
+//the report goes to infra_folder, the status of every evicted pod to pods_folder:
+//an evicted pod has no logs, its status is all there is.
+pub async fn collect_node_pressure<A>(
+    collector: &Collector,
+    pods_list: &[PodInfo<A>],
+    infra_folder: &str,
+    pods_folder: &str,
+) -> Result<()> {
+    let nodes: Api<Node> = Api::all(collector.client.clone());
+    let nodes = nodes.list(&ListParams::default()).await?.items;
+    let events: Api<Event> = Api::all(collector.client.clone());
+    let events = events
+        .list(&ListParams::default().fields("involvedObject.kind=Node"))
+        .await
+        .map(|e| e.items)
+        .inspect_err(|e| warn!("Node events: {}", e))
+        .unwrap_or_default();
+    let pods = pods_list
+        .iter()
+        .map(|p| p.pod.clone())
+        .collect::<Vec<Pod>>();
+    let report = render_report(&nodes, &pods, &events);
+    let er = anyhow!("Empty {}.", NODE_PRESSURE_FILE);
+    collector
+        .ctx
+        .write_file(infra_folder, report.as_bytes(), NODE_PRESSURE_FILE, er)?;
+    info!(
+        "File has been created {}/{}",
+        infra_folder, NODE_PRESSURE_FILE
+    );
+
+    for p in pods_list
+        .iter()
+        .filter(|p| eviction_message(&p.pod).is_some())
+    {
+        let filename = format!("{}_{}.evicted_status.yaml", p.namespace, p.name);
+        let er = anyhow!("Empty status of the evicted pod {}.", p.name);
+        let written = serde_yaml::to_string(&p.pod.status)
+            .map_err(|e| e.into())
+            .and_then(|y| {
+                collector
+                    .ctx
+                    .write_file(pods_folder, y.as_bytes(), &filename, er)
+            });
+        match written {
+            Ok(_) => info!("File has been created {}/{}", pods_folder, filename),
+            Err(e) => warn!("{}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+    use serde_derive::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Cluster {
+        nodes: Vec<Node>,
+        events: Vec<Event>,
+        pods: Vec<Pod>,
+    }
+
+    fn cluster() -> Cluster {
+        let path = fixture("node_pressure/cluster.json");
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn pressure_conditions_of_each_kind() {
+        let c = cluster();
+        assert_eq!(
+            pressure_conditions(&c.nodes[0]),
+            vec![
+                "MemoryPressure=True since 2026-10-17T07:00:00+00:00",
+                "DiskPressure=False since 2026-10-01T00:00:00+00:00",
+                "PIDPressure=False since unknown",
+            ]
+        );
+        assert_eq!(
+            pressure_conditions(&c.nodes[1]),
+            vec![
+                "MemoryPressure=unknown",
+                "DiskPressure=unknown",
+                "PIDPressure=unknown"
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_kubelet_evictions() {
+        let c = cluster();
+        assert_eq!(
+            eviction_message(&c.pods[0]).as_deref(),
+            Some("The node was low on resource: memory.")
+        );
+        assert_eq!(eviction_message(&c.pods[1]).as_deref(), Some(""));
+        assert_eq!(eviction_message(&c.pods[2]), None);
+    }
+
+    #[test]
+    fn report_of_the_nodes_and_the_evicted_pods() {
+        let c = cluster();
+        assert_eq!(
+            render_report(&c.nodes, &c.pods, &c.events),
+            "node node-1\n\
+             \x20 MemoryPressure=True since 2026-10-17T07:00:00+00:00\n\
+             \x20 DiskPressure=False since 2026-10-01T00:00:00+00:00\n\
+             \x20 PIDPressure=False since unknown\n\
+             \x20 2026-10-17T07:00:00+00:00 NodeHasInsufficientMemory (x1): Node node-1 status is now: NodeHasInsufficientMemory\n\
+             \x20 2026-10-17T07:05:00+00:00 EvictionThresholdMet (x3): Attempting to reclaim memory\n\
+             node node-2\n\
+             \x20 MemoryPressure=unknown\n\
+             \x20 DiskPressure=unknown\n\
+             \x20 PIDPressure=unknown\n\
+             \x20 unknown FreeDiskSpaceFailed (x1): failed to garbage collect required amount of images\n\
+             \n\
+             evicted pods: 2\n\
+             \x20 web/web-0 on node-1: The node was low on resource: memory.\n\
+             \x20 web/web-1 on unknown: \n"
+        );
+    }
+
+    #[test]
+    fn empty_report() {
+        assert_eq!(render_report(&[], &[], &[]), "\nevicted pods: 0\n");
+    }
+}
@@ -0,0 +1,242 @@
+use anyhow::{anyhow, Result};
+use kube::{
+    api::{ApiResource, DynamicObject, GroupVersionKind, ListParams},
+    Api, Client,
+};
+use simplelog::{__private::log::warn, info};
+
+use std::fs;
+
+use crate::{collector::Collector, PodInfo};
+
+pub const OPENSHIFT_FOLDER: &str = "openshift";
+pub const ROUTE_GROUP: &str = "route.openshift.io";
+pub const SCC_ANNOTATION: &str = "openshift.io/scc";
+
+//the route api group only exists on OpenShift, any discovery error counts as vanilla Kubernetes.
+pub async fn is_openshift(client: &Client) -> bool {
+    client
+        .list_api_groups()
+        .await
+        .map(|g| g.groups.iter().any(|g| g.name == ROUTE_GROUP))
+        .unwrap_or(false)
+}
+
+//dynamic api, the crate has no OpenShift types.
+fn api(
+    client: &Client,
+    group: &str,
+    kind: &str,
+    plural: &str,
+    namespace: Option<&str>,
+) -> Api<DynamicObject> {
+    let ar = ApiResource::from_gvk_with_plural(&GroupVersionKind::gvk(group, "v1", kind), plural);
+    match namespace {
+        Some(ns) => Api::namespaced_with(client.clone(), ns, &ar),
+        None => Api::all_with(client.clone(), &ar),
+    }
+}
+
+fn to_yaml(objects: &[DynamicObject]) -> Result<String> {
+    let mut yaml = String::new();
+    for obj in objects {
+        let mut obj = obj.clone();
+        obj.metadata.managed_fields = None;
+        yaml.push_str("---\n");
+        yaml.push_str(&serde_yaml::to_string(&obj)?);
+    }
+    Ok(yaml)
+}
+
+fn condition(obj: &DynamicObject, kind: &str) -> String {
+    obj.data
+        .pointer("/status/conditions")
+        .and_then(|c| c.as_array())
+        .and_then(|c| {
+            c.iter()
+                .find(|c| c.get("type").and_then(|t| t.as_str()) == Some(kind))
+        })
+        .and_then(|c| c.get("status").and_then(|s| s.as_str()))
+        .unwrap_or("Unknown")
+        .to_string()
+}
+
+pub fn degraded_operators(operators: &[DynamicObject]) -> Vec<String> {
+    operators
+        .iter()
+        .filter(|o| condition(o, "Degraded") == "True")
+        .map(|o| o.metadata.name.clone().unwrap_or_default())
+        .collect()
+}
+
+pub fn render_operators(operators: &[DynamicObject]) -> String {
+    let mut out = String::new();
+    for o in operators {
+        let degraded = condition(o, "Degraded");
+        out.push_str(&format!(
+            "{} available={} progressing={} degraded={}{}\n",
+            o.metadata.name.clone().unwrap_or_default(),
+            condition(o, "Available"),
+            condition(o, "Progressing"),
+            degraded,
+            if degraded == "True" { " DEGRADED" } else { "" }
+        ));
+    }
+    out
+}
+
+fn json_strings(obj: &DynamicObject, field: &str) -> Vec<String> {
+    obj.data
+        .get(field)
+        .and_then(|u| u.as_array())
+        .map(|u| {
+            u.iter()
+                .filter_map(|s| s.as_str().map(|s| s.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+//an scc is kept when a collected pod was admitted with it (openshift.io/scc annotation)
+//or when it names one of their service accounts, directly or through its namespace group.
+pub fn referenced_scc<A>(scc: &DynamicObject, pods: &[PodInfo<A>]) -> bool {
+    let name = scc.metadata.name.clone().unwrap_or_default();
+    let users = json_strings(scc, "users");
+    let groups = json_strings(scc, "groups");
+    pods.iter().any(|p| {
+        let annotated = p
+            .pod
+            .metadata
+            .annotations
+            .as_ref()
+            .and_then(|a| a.get(SCC_ANNOTATION))
+            .is_some_and(|s| *s == name);
+        let sa = p
+            .pod
+            .spec
+            .as_ref()
+            .and_then(|s| s.service_account_name.clone())
+            .unwrap_or_else(|| "default".to_string());
+        annotated
+            || users.contains(&format!("system:serviceaccount:{}:{}", p.namespace, sa))
+            || groups.contains(&format!("system:serviceaccounts:{}", p.namespace))
+    })
+}
+
+//nothing is written for an empty list (no route in a namespace, no referenced scc).
+fn write(collector: &Collector, folder: &str, filename: &str, data: Result<String>) {
+    let written = match data {
+        Ok(d) if d.is_empty() => return,
+        Ok(d) => {
+            let er = anyhow!("Empty {}.", filename);
+            collector.ctx.write_file(folder, d.as_bytes(), filename, er)
+        }
+        Err(e) => {
+            collector.ctx.record_failure(folder, filename, "", &e);
+            Err(e)
+        }
+    };
+    match written {
+        Ok(_) => info!("File has been created {}/{}", folder, filename),
+        Err(e) => warn!("{}", e),
+    }
+}
+
+//routes, cluster version, cluster operators and the sccs of the collected pods under infra/openshift,
+//nothing at all on vanilla Kubernetes.
+pub async fn collect_openshift<A>(
+    collector: &Collector,
+    pods: &[PodInfo<A>],
+    infra_folder: &str,
+) -> Result<()> {
+    let client = &collector.client;
+    if !is_openshift(client).await {
+        return Ok(());
+    }
+    info!("<yellow>OpenShift cluster detected.</>");
+    let folder = format!("{}/{}", infra_folder, OPENSHIFT_FOLDER);
+    fs::create_dir_all(&folder)?;
+    let lp = ListParams::default();
+
+    for ns in &collector.config.context_namespace {
+        let routes = api(client, ROUTE_GROUP, "Route", "routes", Some(ns))
+            .list(&lp)
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|r| to_yaml(&r.items));
+        write(collector, &folder, &format!("routes_{}.yaml", ns), routes);
+    }
+
+    let versions = api(
+        client,
+        "config.openshift.io",
+        "ClusterVersion",
+        "clusterversions",
+        None,
+    )
+    .list(&lp)
+    .await
+    .map_err(anyhow::Error::from)
+    .and_then(|v| to_yaml(&v.items));
+    write(collector, &folder, "cluster_version.yaml", versions);
+
+    match api(
+        client,
+        "config.openshift.io",
+        "ClusterOperator",
+        "clusteroperators",
+        None,
+    )
+    .list(&lp)
+    .await
+    {
+        Ok(ops) => {
+            let degraded = degraded_operators(&ops.items);
+            if !degraded.is_empty() {
+                warn!(
+                    "<yellow>Degraded cluster operators: {}.</>",
+                    degraded.join(", ")
+                );
+            }
+            write(
+                collector,
+                &folder,
+                "cluster_operators.yaml",
+                to_yaml(&ops.items),
+            );
+            write(
+                collector,
+                &folder,
+                "cluster_operators_status.txt",
+                Ok(render_operators(&ops.items)),
+            );
+        }
+        Err(e) => write(collector, &folder, "cluster_operators.yaml", Err(e.into())),
+    }
+
+    let sccs = api(
+        client,
+        "security.openshift.io",
+        "SecurityContextConstraints",
+        "securitycontextconstraints",
+        None,
+    )
+    .list(&lp)
+    .await
+    .map_err(anyhow::Error::from)
+    .and_then(|s| {
+        let referenced = s
+            .items
+            .into_iter()
+            .filter(|s| referenced_scc(s, pods))
+            .collect::<Vec<_>>();
+        to_yaml(&referenced)
+    });
+    write(
+        collector,
+        &folder,
+        "security_context_constraints.yaml",
+        sccs,
+    );
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use anyhow::{anyhow, Result};
+use simplelog::__private::log::warn;
+
+use crate::PodInfo;
+
+//StatefulSet ordinals, "0-2,5" is 0, 1, 2 and 5.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrdinalRange(Vec<(u32, u32)>);
+
+impl OrdinalRange {
+    pub fn parse(range: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid ordinal range {}, expected e.g. 0-2,5.", range);
+        let mut ranges = vec![];
+        for part in range.split(',').map(|p| p.trim()) {
+            let (start, end) = part.split_once('-').unwrap_or((part, part));
+            let start = start.trim().parse::<u32>().map_err(|_| invalid())?;
+            let end = end.trim().parse::<u32>().map_err(|_| invalid())?;
+            if start > end {
+                return Err(invalid());
+            }
+            ranges.push((start, end));
+        }
+        Ok(OrdinalRange(ranges))
+    }
+
+    pub fn contains(&self, ordinal: u32) -> bool {
+        self.0.iter().any(|(s, e)| (*s..=*e).contains(&ordinal))
+    }
+}
+
+//the trailing "-<n>" of a StatefulSet pod name, kafka-2 -> 2.
+pub fn pod_ordinal(name: &str) -> Option<u32> {
+    let (_, n) = name.rsplit_once('-')?;
+    if n.is_empty() || !n.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    n.parse().ok()
+}
+
+//the pods whose ordinal is in the range, the ones without an ordinal are skipped with a warning.
+pub fn filter_pods<A>(pods: Vec<PodInfo<A>>, range: &OrdinalRange) -> Vec<PodInfo<A>> {
+    pods.into_iter()
+        .filter(|p| match pod_ordinal(&p.name) {
+            Some(o) => range.contains(o),
+            None => {
+                warn!(
+                    "Pod {}/{} has no StatefulSet ordinal, skipped by the ordinal range.",
+                    p.namespace, p.name
+                );
+                false
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::pod_info;
+
+    fn pod(name: &str) -> PodInfo<()> {
+        pod_info(serde_json::json!({
+            "metadata": {"name": name, "namespace": "kafka"}
+        }))
+    }
+
+    #[test]
+    fn ranges_and_single_ordinals() {
+        let r = OrdinalRange::parse("0-2, 5,7 - 8").unwrap();
+        let kept = (0..10).filter(|o| r.contains(*o)).collect::<Vec<_>>();
+        assert_eq!(kept, vec![0, 1, 2, 5, 7, 8]);
+        assert_eq!(
+            OrdinalRange::parse("3").unwrap(),
+            OrdinalRange(vec![(3, 3)])
+        );
+    }
+
+    #[test]
+    fn invalid_ranges_are_errors() {
+        for r in ["", "a", "2-1", "1-", "-1", "0-2,,3", "1.5"] {
+            assert!(OrdinalRange::parse(r).is_err(), "{}", r);
+        }
+    }
+
+    #[test]
+    fn ordinal_is_the_trailing_number() {
+        assert_eq!(pod_ordinal("kafka-2"), Some(2));
+        assert_eq!(pod_ordinal("es-master-10"), Some(10));
+        assert_eq!(pod_ordinal("web-7d9f8b6c4-x2k4p"), None);
+        assert_eq!(pod_ordinal("standalone"), None);
+        assert_eq!(pod_ordinal("trailing-"), None);
+    }
+
+    #[test]
+    fn pods_without_an_ordinal_are_skipped() {
+        let pods = [
+            "kafka-0",
+            "kafka-1",
+            "kafka-3",
+            "kafka-exporter-5c9d-zz1",
+            "kafka",
+        ]
+        .map(pod)
+        .to_vec();
+        let kept = filter_pods(pods, &OrdinalRange::parse("0,2-5").unwrap());
+        let names = kept.iter().map(|p| p.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["kafka-0", "kafka-3"]);
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+
+use std::{
+    fs,
+    io::{Stdout, Write},
+    path::PathBuf,
+};
+
+pub const STDOUT_TARGET: &str = "-";
+pub const TAR_STREAM_TARGET: &str = "tar:-";
+
+//where a single-target subcommand (exec, pod --logs-only) puts what it collected.
+pub enum OutputTarget {
+    Directory(PathBuf),
+    //the contents one after the other, for piping into grep or less.
+    Stdout(Stdout),
+    //a tar archive written to stdout, for `| tar x` on another machine.
+    TarStream(tar::Builder<Stdout>),
+}
+
+//stdout and tar:- stream to stdout, the logs then go to stderr and the tasks run one at a time.
+pub fn is_stream(value: Option<&str>) -> bool {
+    value.is_some_and(|v| v == STDOUT_TARGET || v == TAR_STREAM_TARGET)
+}
+
+//the collections writing many files into a folder tree cannot stream.
+pub fn reject_stream(value: Option<&str>, what: &str) -> Result<()> {
+    if is_stream(value) {
+        return Err(anyhow!(
+            "{} writes several files, it cannot be sent to stdout ({}). Give a directory instead.",
+            what,
+            value.unwrap_or_default()
+        ));
+    }
+    Ok(())
+}
+
+impl OutputTarget {
+    pub fn parse(value: &str) -> Result<Self> {
+        Ok(match value {
+            STDOUT_TARGET => OutputTarget::Stdout(std::io::stdout()),
+            TAR_STREAM_TARGET => OutputTarget::TarStream(tar::Builder::new(std::io::stdout())),
+            dir => {
+                fs::create_dir_all(dir)?;
+                OutputTarget::Directory(PathBuf::from(dir))
+            }
+        })
+    }
+
+    pub fn is_stream(&self) -> bool {
+        !matches!(self, OutputTarget::Directory(_))
+    }
+
+    //name is a file name relative to the target, only the directory and tar targets keep it.
+    pub fn write(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        match self {
+            OutputTarget::Directory(dir) => {
+                crate::write_atomic(&dir.join(name), data)?;
+            }
+            OutputTarget::Stdout(out) => {
+                let mut out = out.lock();
+                out.write_all(data)?;
+                out.flush()?;
+            }
+            OutputTarget::TarStream(tar) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(data.len() as u64);
+                header.set_mode(0o644);
+                header.set_mtime(Utc::now().timestamp().max(0) as u64);
+                header.set_cksum();
+                tar.append_data(&mut header, name, data)?;
+            }
+        }
+        Ok(())
+    }
+
+    //the tar stream needs its end blocks.
+    pub fn finish(self) -> Result<()> {
+        match self {
+            OutputTarget::TarStream(tar) => tar.into_inner()?.flush()?,
+            OutputTarget::Stdout(mut out) => out.flush()?,
+            OutputTarget::Directory(_) => {}
+        }
+        Ok(())
+    }
+
+    //where a file written under name ends up, for the info lines.
+    pub fn describe(&self, name: &str) -> String {
+        match self {
+            OutputTarget::Directory(dir) => dir.join(name).display().to_string(),
+            OutputTarget::Stdout(_) => format!("stdout ({})", name),
+            OutputTarget::TarStream(_) => format!("tar stream ({})", name),
+        }
+    }
+}
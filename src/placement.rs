@@ -0,0 +1,299 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::core::v1::{Node, Pod};
+use kube::{api::ListParams, Api, ResourceExt};
+use simplelog::info;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{collector::Collector, release_ownership::workload, PodInfo};
+
+pub const PLACEMENT_REPORT_FILE: &str = "placement_report.txt";
+pub const ZONE_LABEL: &str = "topology.kubernetes.io/zone";
+pub const HOSTNAME_LABEL: &str = "kubernetes.io/hostname";
+const UNKNOWN_ZONE: &str = "<no zone>";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WorkloadPlacement {
+    pub namespace: String,
+    //"<Kind>/<name>".
+    pub workload: String,
+    //pod -> (node, zone).
+    pub replicas: BTreeMap<String, (String, String)>,
+    //"<where> <topologyKey>" of the anti-affinity terms and the spread constraints of the pods.
+    pub declared: BTreeSet<String>,
+    pub violations: Vec<String>,
+}
+
+//the topology keys the pod asks to be spread over, with where they come from.
+fn spread_keys(pod: &Pod) -> BTreeSet<(String, String)> {
+    let mut keys = BTreeSet::new();
+    let Some(spec) = pod.spec.as_ref() else {
+        return keys;
+    };
+    if let Some(anti) = spec
+        .affinity
+        .as_ref()
+        .and_then(|a| a.pod_anti_affinity.as_ref())
+    {
+        for t in anti
+            .required_during_scheduling_ignored_during_execution
+            .iter()
+            .flatten()
+        {
+            keys.insert(("required anti-affinity".to_string(), t.topology_key.clone()));
+        }
+        for t in anti
+            .preferred_during_scheduling_ignored_during_execution
+            .iter()
+            .flatten()
+        {
+            keys.insert((
+                "preferred anti-affinity".to_string(),
+                t.pod_affinity_term.topology_key.clone(),
+            ));
+        }
+    }
+    for c in spec.topology_spread_constraints.iter().flatten() {
+        keys.insert((
+            "topologySpreadConstraint".to_string(),
+            c.topology_key.clone(),
+        ));
+    }
+    keys
+}
+
+//the replicas of each Deployment/StatefulSet with more than one scheduled pod, a workload is flagged
+//when all of them share the node or the zone it declares to be spread over while the cluster has more than one.
+pub fn evaluate<A>(pods: &[PodInfo<A>], nodes: &[Node]) -> Vec<WorkloadPlacement> {
+    let node_zones: BTreeMap<String, String> = nodes
+        .iter()
+        .map(|n| {
+            let zone = n.labels().get(ZONE_LABEL).cloned();
+            (
+                n.name_any(),
+                zone.unwrap_or_else(|| UNKNOWN_ZONE.to_string()),
+            )
+        })
+        .collect();
+    let cluster_zones = node_zones.values().collect::<BTreeSet<&String>>();
+    let mut placements: BTreeMap<(String, String), (WorkloadPlacement, BTreeSet<String>)> =
+        BTreeMap::new();
+    for p in pods.iter().filter(|p| !p.node_name.is_empty()) {
+        let Some(w) = workload(&p.pod) else {
+            continue;
+        };
+        if !w.starts_with("Deployment/") && !w.starts_with("StatefulSet/") {
+            continue;
+        }
+        let (placement, keys) = placements
+            .entry((p.namespace.clone(), w.clone()))
+            .or_insert_with(|| {
+                (
+                    WorkloadPlacement {
+                        namespace: p.namespace.clone(),
+                        workload: w,
+                        ..Default::default()
+                    },
+                    BTreeSet::new(),
+                )
+            });
+        let zone = node_zones
+            .get(&p.node_name)
+            .cloned()
+            .unwrap_or_else(|| UNKNOWN_ZONE.to_string());
+        placement
+            .replicas
+            .insert(p.name.clone(), (p.node_name.clone(), zone));
+        for (source, key) in spread_keys(&p.pod) {
+            placement.declared.insert(format!("{} {}", source, key));
+            keys.insert(key);
+        }
+    }
+    placements
+        .into_values()
+        .filter(|(placement, _)| placement.replicas.len() > 1)
+        .map(|(mut placement, keys)| {
+            let nodes_used = placement
+                .replicas
+                .values()
+                .map(|(n, _)| n)
+                .collect::<BTreeSet<&String>>();
+            let zones_used = placement
+                .replicas
+                .values()
+                .map(|(_, z)| z)
+                .collect::<BTreeSet<&String>>();
+            let mut violations = vec![];
+            if keys.contains(HOSTNAME_LABEL) && nodes_used.len() == 1 && node_zones.len() > 1 {
+                violations.push(format!(
+                    "all {} replicas on node {} despite the {} spreading",
+                    placement.replicas.len(),
+                    nodes_used.iter().next().unwrap(),
+                    HOSTNAME_LABEL
+                ));
+            }
+            if keys.contains(ZONE_LABEL) && zones_used.len() == 1 && cluster_zones.len() > 1 {
+                violations.push(format!(
+                    "all {} replicas in zone {} despite the {} spreading",
+                    placement.replicas.len(),
+                    zones_used.iter().next().unwrap(),
+                    ZONE_LABEL
+                ));
+            }
+            placement.violations = violations;
+            placement
+        })
+        .collect()
+}
+
+pub fn render_report(placements: &[WorkloadPlacement]) -> String {
+    let flagged = placements
+        .iter()
+        .filter(|p| !p.violations.is_empty())
+        .count();
+    let mut out = format!(
+        "{} workloads with more than one replica, {} with a placement against their spreading.\n",
+        placements.len(),
+        flagged
+    );
+    for p in placements {
+        out.push_str(&format!("\n{}/{}\n", p.namespace, p.workload));
+        for v in &p.violations {
+            out.push_str(&format!("  VIOLATION: {}\n", v));
+        }
+        if p.declared.is_empty() {
+            out.push_str("  declared: none\n");
+        }
+        for d in &p.declared {
+            out.push_str(&format!("  declared: {}\n", d));
+        }
+        for (pod, (node, zone)) in &p.replicas {
+            out.push_str(&format!("  {} on {} ({})\n", pod, node, zone));
+        }
+    }
+    out
+}
+
+pub async fn collect_placement<A>(
+    collector: &Collector,
+    pods_list: &[PodInfo<A>],
+    folder: &str,
+) -> Result<()> {
+    let nodes: Api<Node> = Api::all(collector.client.clone());
+    let nodes = nodes.list(&ListParams::default()).await?.items;
+    let report = render_report(&evaluate(pods_list, &nodes));
+    let er = anyhow!("Empty {}.", PLACEMENT_REPORT_FILE);
+    collector
+        .ctx
+        .write_file(folder, report.as_bytes(), PLACEMENT_REPORT_FILE, er)?;
+    info!("File has been created {}/{}", folder, PLACEMENT_REPORT_FILE);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    fn cluster() -> (Vec<PodInfo<()>>, Vec<Node>) {
+        #[derive(serde_derive::Deserialize)]
+        struct Cluster {
+            nodes: Vec<Node>,
+            pods: Vec<Pod>,
+        }
+        let json = std::fs::read_to_string(fixture("placement/cluster.json")).unwrap();
+        let c: Cluster = serde_json::from_str(&json).unwrap();
+        let pods = c.pods.iter().map(|p| PodInfo::from_pod(p, ())).collect();
+        (pods, c.nodes)
+    }
+
+    fn placement<'a>(placements: &'a [WorkloadPlacement], workload: &str) -> &'a WorkloadPlacement {
+        placements.iter().find(|p| p.workload == workload).unwrap()
+    }
+
+    #[test]
+    fn skewed_workloads_are_flagged() {
+        let (pods, nodes) = cluster();
+        let placements = evaluate(&pods, &nodes);
+        let web = placement(&placements, "Deployment/web");
+        //the pending replica is not placed.
+        assert_eq!(web.replicas.len(), 3);
+        assert_eq!(
+            web.violations,
+            vec![
+                "all 3 replicas on node n1 despite the kubernetes.io/hostname spreading",
+                "all 3 replicas in zone zone-a despite the topology.kubernetes.io/zone spreading",
+            ]
+        );
+        assert_eq!(
+            web.declared,
+            BTreeSet::from([
+                "required anti-affinity kubernetes.io/hostname".to_string(),
+                "topologySpreadConstraint topology.kubernetes.io/zone".to_string(),
+            ])
+        );
+        //two nodes of the same zone.
+        assert_eq!(
+            placement(&placements, "StatefulSet/db").violations,
+            vec!["all 2 replicas in zone zone-a despite the topology.kubernetes.io/zone spreading"]
+        );
+    }
+
+    #[test]
+    fn balanced_or_undeclared_workloads_pass() {
+        let (pods, nodes) = cluster();
+        let placements = evaluate(&pods, &nodes);
+        let api = placement(&placements, "Deployment/api");
+        assert!(api.violations.is_empty());
+        assert_eq!(
+            api.replicas["api-9d2e-b"],
+            ("n3".to_string(), "zone-b".to_string())
+        );
+        //on one node, but nothing asks for spreading.
+        let cache = placement(&placements, "StatefulSet/cache");
+        assert!(cache.violations.is_empty() && cache.declared.is_empty());
+        //one replica and the jobs are left out.
+        assert_eq!(
+            placements
+                .iter()
+                .map(|p| p.workload.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "StatefulSet/cache",
+                "StatefulSet/db",
+                "Deployment/api",
+                "Deployment/web"
+            ]
+        );
+    }
+
+    #[test]
+    fn one_zone_cluster_cannot_spread_over_zones() {
+        let (pods, mut nodes) = cluster();
+        nodes.retain(|n| n.name_any() != "n3");
+        let pods = pods
+            .into_iter()
+            .filter(|p| p.node_name != "n3")
+            .collect::<Vec<_>>();
+        let placements = evaluate(&pods, &nodes);
+        assert!(placement(&placements, "StatefulSet/db")
+            .violations
+            .is_empty());
+        assert_eq!(placement(&placements, "Deployment/web").violations.len(), 1);
+    }
+
+    #[test]
+    fn report_lists_the_violations_first() {
+        let (pods, nodes) = cluster();
+        let report = render_report(&evaluate(&pods, &nodes));
+        assert!(report.starts_with(
+            "4 workloads with more than one replica, 2 with a placement against their spreading.\n"
+        ));
+        assert!(report.contains(
+            "\ndata/StatefulSet/cache\n  declared: none\n  cache-0 on n1 (zone-a)\n  cache-1 on n1 (zone-a)\n"
+        ));
+        assert!(report.contains(
+            "\nshop/Deployment/web\n  VIOLATION: all 3 replicas on node n1 despite the kubernetes.io/hostname spreading\n"
+        ));
+    }
+}
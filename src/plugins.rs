@@ -0,0 +1,394 @@
+use anyhow::{anyhow, Result};
+use serde_derive::Deserialize;
+use simplelog::{__private::log::warn, info};
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    context::{RunContext, ERROR_FILE_SUFFIX},
+    external, line_endings, ConfigFile,
+};
+
+pub const PLUGINS_FOLDER: &str = "plugins";
+//environment of a plugin.
+pub const OUTPUT_DIR_ENV: &str = "ANTLOG_PLUGIN_OUTPUT_DIR";
+pub const CONTEXT_NAME_ENV: &str = "ANTLOG_CONTEXT_NAME";
+pub const NAMESPACES_ENV: &str = "ANTLOG_NAMESPACES";
+pub const KUBECONFIG_ENV: &str = "ANTLOG_KUBECONFIG";
+
+//a site specific collector, `plugins` section of the config.
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct Plugin {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    //the output folder of the plugin when empty.
+    #[serde(default)]
+    pub working_directory: String,
+    //under apps/plugins/, the plugin name when empty. The files the plugin writes there are collected.
+    #[serde(default)]
+    pub output_folder: String,
+}
+
+impl Plugin {
+    pub fn output_folder(&self, apps_folder: &str) -> String {
+        let sub = if self.output_folder.is_empty() {
+            &self.name
+        } else {
+            &self.output_folder
+        };
+        format!("{}/{}/{}", apps_folder, PLUGINS_FOLDER, sub)
+    }
+}
+
+pub fn validate(plugins: &[Plugin]) -> Result<()> {
+    for p in plugins {
+        if p.name.is_empty() || p.path.is_empty() {
+            return Err(anyhow!(
+                "plugins entries need a name and a path, got {:?}/{:?}.",
+                p.name,
+                p.path
+            ));
+        }
+        if p.name.contains('/')
+            || [&p.name, &p.output_folder]
+                .iter()
+                .any(|s| s.contains("..") || s.starts_with('/'))
+        {
+            return Err(anyhow!(
+                "Plugin {}: name and output_folder must stay inside the plugins folder.",
+                p.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+//"<name>.plugin_run.txt": the command, its exit code and duration; stdout and stderr next to it.
+async fn run_plugin(
+    plugin: &Plugin,
+    config: &ConfigFile,
+    timeout: Duration,
+    folder: &str,
+    ctx: &RunContext,
+) -> Result<i32> {
+    let working_directory = if plugin.working_directory.is_empty() {
+        PathBuf::from(folder)
+    } else {
+        PathBuf::from(&plugin.working_directory)
+    };
+    let output_dir = fs::canonicalize(folder)?;
+    let mut cmd = tokio::process::Command::new(&plugin.path);
+    cmd.args(&plugin.args)
+        .current_dir(working_directory)
+        .env(OUTPUT_DIR_ENV, &output_dir)
+        .env(CONTEXT_NAME_ENV, &config.context_name)
+        .env(NAMESPACES_ENV, config.context_namespace.join(","))
+        .env(
+            KUBECONFIG_ENV,
+            ctx.kubeconfig.path.as_deref().unwrap_or_default(),
+        )
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    //the kubectl of a plugin sees the kubeconfig of the run, never the one of the tool.
+    if let Some(p) = &ctx.kubeconfig.path {
+        cmd.env("KUBECONFIG", p);
+    }
+    let started = Instant::now();
+    ctx.usage.subprocess();
+    let child = cmd.spawn().map_err(|e| {
+        anyhow!(
+            "Plugin {} ({}) did not start: {}",
+            plugin.name,
+            plugin.path,
+            e
+        )
+    })?;
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| anyhow!("Plugin {} timed out after {:?}.", plugin.name, timeout))??;
+    for (data, kind) in [(&output.stdout, "stdout"), (&output.stderr, "stderr")] {
+        if data.is_empty() {
+            continue;
+        }
+        let filename = format!("{}.{}.log", plugin.name, kind);
+        let er = anyhow!("Empty {} of the plugin {}.", kind, plugin.name);
+        if let Err(e) = ctx.write_file(folder, data, &filename, er) {
+            warn!("{}", e)
+        }
+    }
+    let code = output.status.code().unwrap_or(-1);
+    let run = format!(
+        "command: {} {}\nexit code: {}\nduration: {:.1}s\n",
+        plugin.path,
+        plugin.args.join(" "),
+        code,
+        started.elapsed().as_secs_f64()
+    );
+    let filename = format!("{}.plugin_run.txt", plugin.name);
+    let er = anyhow!("Empty run of the plugin {}.", plugin.name);
+    ctx.write_file(folder, run.as_bytes(), &filename, er)?;
+    ctx.record_exit_code(folder, &filename, code);
+    Ok(code)
+}
+
+//the files written by the plugin below dir, listed in the manifest like the other outputs. They go
+//through write_file when anonymizing or normalizing, their folders anonymized like the ones of
+//copy_from_pod; a binary file cannot be anonymized and is left out. The outputs of the tool are skipped.
+fn collect_plugin_files(dir: &Path, folder: &str, ctx: &RunContext) -> Result<()> {
+    let written = ctx.manifest();
+    let rewrite = ctx.anonymizer.is_some() || ctx.normalize_line_endings;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir() {
+            let sub = ctx.subfolder(folder, &name);
+            fs::create_dir_all(&sub)?;
+            collect_plugin_files(&path, &sub, ctx)?;
+            //emptied when its anonymized name differs.
+            if Path::new(&sub) != path {
+                let _ = fs::remove_dir(&path);
+            }
+            continue;
+        }
+        let in_place = format!("{}/{}", folder, name);
+        if name.ends_with(ERROR_FILE_SUFFIX)
+            || written.contains_key(&ctx.relative(&in_place))
+            || !rewrite
+        {
+            ctx.record_output(folder, &name);
+            continue;
+        }
+        let data = fs::read(&path)?;
+        fs::remove_file(&path)?;
+        if data.is_empty() {
+            continue;
+        }
+        if ctx.anonymizer.is_some() && !line_endings::is_text(&data) {
+            let e = anyhow!(
+                "{} written by the plugin is binary and cannot be anonymized, not collected.",
+                ctx.relative(&in_place)
+            );
+            warn!("{}", e);
+            ctx.record_failure(folder, &name, "", &e);
+            continue;
+        }
+        let er = anyhow!("Empty plugin file {}.", name);
+        if let Err(e) = ctx.write_file(folder, &data, &name, er) {
+            warn!("{}", e);
+        }
+    }
+    Ok(())
+}
+
+//every plugin in apps/plugins/<output_folder>, within the external_command_concurrency
+//and external_command_timeout_seconds of the kubectl and helm processes.
+pub async fn run_plugins(config: &ConfigFile, apps_folder: &str, ctx: Arc<RunContext>) {
+    let timeout = external::timeout(config.external_command_timeout_seconds);
+    let mut handles = vec![];
+    for plugin in &config.plugins {
+        let folder = plugin.output_folder(apps_folder);
+        let (plugin, config, ctx) = (plugin.clone(), config.clone(), ctx.clone());
+        handles.push(tokio::task::spawn(async move {
+            let _permit = external::permit().await;
+            if let Err(e) = fs::create_dir_all(&folder) {
+                warn!("Plugin {}: {}", plugin.name, e);
+                ctx.record_folder(&folder, false);
+                return;
+            }
+            let filename = format!("{}.plugin_run.txt", plugin.name);
+            match run_plugin(&plugin, &config, timeout, &folder, &ctx).await {
+                Ok(0) => info!("Plugin {} done.", plugin.name),
+                Ok(code) => {
+                    warn!("Plugin {} exited with {}.", plugin.name, code);
+                    ctx.record_folder(&folder, false);
+                }
+                Err(e) => {
+                    warn!("{}", e);
+                    let attempted = format!("{} {}", plugin.path, plugin.args.join(" "));
+                    ctx.record_failure(&folder, &filename, &attempted, &e);
+                }
+            }
+            if let Err(e) = collect_plugin_files(Path::new(&folder), &folder, &ctx) {
+                warn!("Plugin {} files: {}", plugin.name, e);
+            }
+        }));
+    }
+    for handle in handles {
+        if let Err(e) = handle.await {
+            warn!("{}", e);
+            ctx.record_folder(apps_folder, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        anonymize::Anonymizer,
+        context::FileStatus,
+        test_support::{fixture, run_context, TempDir},
+        SubprocessKubeconfig,
+    };
+
+    fn plugin(name: &str, path: &str, args: &[&str]) -> Plugin {
+        Plugin {
+            name: name.to_string(),
+            path: path.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn script() -> String {
+        fixture("plugins/collect.sh").display().to_string()
+    }
+
+    //the plugins of the config run against a context with the kubeconfig of the run.
+    async fn run(ctx: RunContext, plugins: Vec<Plugin>, timeout_seconds: u64) -> Arc<RunContext> {
+        let config = ConfigFile {
+            context_name: "prod".to_string(),
+            context_namespace: vec!["web".to_string(), "kafka".to_string()],
+            external_command_timeout_seconds: timeout_seconds,
+            plugins,
+            ..Default::default()
+        };
+        let ctx = Arc::new(ctx.with_kubeconfig(SubprocessKubeconfig::files("/etc/kube/run.conf")));
+        run_plugins(&config, &ctx.folders[3].clone(), ctx.clone()).await;
+        ctx
+    }
+
+    #[tokio::test]
+    async fn plugin_runs_with_the_environment_of_the_run() {
+        let dir = TempDir::new();
+        let ctx = run(
+            run_context(&dir),
+            vec![plugin("site", &script(), &["0"])],
+            0,
+        )
+        .await;
+        let output_dir = fs::canonicalize(dir.path().join("apps/plugins/site")).unwrap();
+        assert_eq!(
+            dir.read("apps/plugins/site/site.stdout.log"),
+            format!(
+                "context=prod\nnamespaces=web,kafka\noutput={0}\nkubeconfig=/etc/kube/run.conf\nKUBECONFIG=/etc/kube/run.conf\npwd={0}\n",
+                output_dir.display()
+            )
+        );
+        assert_eq!(
+            dir.read("apps/plugins/site/site.stderr.log"),
+            "probing worker-a at 10.0.0.7\n"
+        );
+        let run = dir.read("apps/plugins/site/site.plugin_run.txt");
+        assert!(run.starts_with(&format!("command: {} 0\nexit code: 0\n", script())));
+        let manifest = ctx.manifest();
+        assert_eq!(
+            manifest["apps/plugins/site/site.plugin_run.txt"].exit_code,
+            Some(0)
+        );
+        //the files the plugin dropped are outputs as well.
+        for file in ["flags.txt", "core.bin", "worker-a/disk.txt"] {
+            assert_eq!(
+                manifest[&format!("apps/plugins/site/{}", file)].status,
+                FileStatus::Ok
+            );
+        }
+        assert_eq!(ctx.phase_results()["apps"].failed, 0);
+    }
+
+    #[tokio::test]
+    async fn failed_missing_and_hung_plugins_are_recorded() {
+        let dir = TempDir::new();
+        let plugins = vec![
+            plugin("exits", &script(), &["3"]),
+            plugin("missing", "/nonexistent/plugin", &[]),
+            plugin("hangs", &script(), &["0", "30"]),
+        ];
+        let ctx = run(run_context(&dir), plugins, 1).await;
+        let manifest = ctx.manifest();
+        let run = &manifest["apps/plugins/exits/exits.plugin_run.txt"];
+        assert_eq!((&run.status, run.exit_code), (&FileStatus::Ok, Some(3)));
+        assert!(dir
+            .read("apps/plugins/exits/exits.plugin_run.txt")
+            .contains("exit code: 3\n"));
+        let missing = &manifest["apps/plugins/missing/missing.plugin_run.txt"];
+        assert_eq!(missing.status, FileStatus::Failed);
+        assert!(dir
+            .read("apps/plugins/missing/missing.plugin_run.txt.error")
+            .contains("Plugin missing (/nonexistent/plugin) did not start: "));
+        assert_eq!(
+            manifest["apps/plugins/hangs/hangs.plugin_run.txt"].status,
+            FileStatus::Failed
+        );
+        assert!(dir
+            .read("apps/plugins/hangs/hangs.plugin_run.txt.error")
+            .contains("Plugin hangs timed out after 1s."));
+        //what it dropped before it was killed is kept.
+        assert!(manifest.contains_key("apps/plugins/hangs/flags.txt"));
+        assert_eq!(ctx.phase_results()["apps"].failed, 3);
+    }
+
+    #[tokio::test]
+    async fn anonymized_plugin_outputs_leave_the_binary_files_out() {
+        let dir = TempDir::new();
+        let nodes = ["worker-a".to_string()];
+        let ctx = run_context(&dir)
+            .with_line_endings(true)
+            .with_anonymizer(Anonymizer::new(&nodes, &[]).unwrap());
+        let ctx = run(ctx, vec![plugin("site", &script(), &["0"])], 0).await;
+        assert_eq!(
+            dir.read("apps/plugins/site/site.stderr.log"),
+            "probing node-1 at ip-1\n"
+        );
+        assert_eq!(
+            dir.read("apps/plugins/site/node-1/disk.txt"),
+            "disk of node-1: 91%\n"
+        );
+        assert!(!dir.path().join("apps/plugins/site/worker-a").exists());
+        assert!(!dir.path().join("apps/plugins/site/core.bin").exists());
+        let manifest = ctx.manifest();
+        assert_eq!(
+            manifest["apps/plugins/site/core.bin"].status,
+            FileStatus::Failed
+        );
+        assert!(dir
+            .read("apps/plugins/site/core.bin.error")
+            .contains("apps/plugins/site/core.bin written by the plugin is binary and cannot be anonymized, not collected."));
+        assert_eq!(
+            manifest["apps/plugins/site/flags.txt"].status,
+            FileStatus::Ok
+        );
+        assert!(manifest.contains_key("apps/plugins/site/node-1/disk.txt"));
+    }
+
+    #[test]
+    fn names_stay_inside_the_plugins_folder() {
+        assert!(validate(&[plugin("site", "/opt/site.sh", &[])]).is_ok());
+        let mut nested = plugin("site", "/opt/site.sh", &[]);
+        nested.output_folder = "site/nodes".to_string();
+        assert!(validate(&[nested]).is_ok());
+        for name in ["site/nodes", "../site", "/site"] {
+            assert_eq!(
+                validate(&[plugin(name, "/opt/site.sh", &[])])
+                    .unwrap_err()
+                    .to_string(),
+                format!(
+                    "Plugin {}: name and output_folder must stay inside the plugins folder.",
+                    name
+                )
+            );
+        }
+        assert!(validate(&[plugin("site", "", &[])]).is_err());
+    }
+}
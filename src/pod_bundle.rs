@@ -0,0 +1,230 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use flate2::{write::GzEncoder, Compression};
+use k8s_openapi::api::{apps::v1::ReplicaSet, core::v1::Pod};
+use kube::{api::ListParams, Api, ResourceExt};
+use simplelog::{__private::log::warn, info};
+
+use std::fs::{self, File};
+
+use crate::{
+    access::PodAccess, collector::Collector, get_logs, output::OutputTarget, output_directory,
+    PodInfo,
+};
+
+pub const CLOSE_MATCHES: usize = 5;
+
+//"<namespace>/<pod>".
+pub fn parse_pod_ref(value: &str) -> Result<(String, String)> {
+    match value.split_once('/') {
+        Some((ns, pod)) if !ns.is_empty() && !pod.is_empty() && !pod.contains('/') => {
+            Ok((ns.to_string(), pod.to_string()))
+        }
+        _ => Err(anyhow!(
+            "Invalid pod {}, expected <namespace>/<pod>.",
+            value
+        )),
+    }
+}
+
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut row = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for j in 0..b.len() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == b[j] {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(row[j + 1])
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+//names containing the wanted one or at most a third of it away, closest first.
+pub fn close_matches(name: &str, candidates: &[String]) -> Vec<String> {
+    let mut matches = candidates
+        .iter()
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|(d, c)| c.contains(name) || name.contains(c.as_str()) || *d <= name.len() / 3)
+        .collect::<Vec<_>>();
+    matches.sort();
+    matches
+        .into_iter()
+        .take(CLOSE_MATCHES)
+        .map(|(_, c)| c.clone())
+        .collect()
+}
+
+async fn find_pod(collector: &Collector, namespace: &str, name: &str) -> Result<PodInfo> {
+    let access = collector.access.in_namespace(namespace);
+    if let Some(pod) = access.pods.get_opt(name).await? {
+        return Ok(PodInfo::from_pod(&pod, access));
+    }
+    let names = access
+        .pods
+        .list(&ListParams::default())
+        .await?
+        .iter()
+        .map(|p| p.name_any())
+        .collect::<Vec<String>>();
+    let matches = close_matches(name, &names);
+    Err(if matches.is_empty() {
+        anyhow!("Pod {}/{} not found.", namespace, name)
+    } else {
+        anyhow!(
+            "Pod {}/{} not found, close matches: {}.",
+            namespace,
+            name,
+            matches.join(", ")
+        )
+    })
+}
+
+//the top level owner, a ReplicaSet is followed up to its Deployment.
+async fn owner(collector: &Collector, pod: &Pod) -> Option<(String, String)> {
+    let owner = pod.owner_references().first()?;
+    if owner.kind == "ReplicaSet" {
+        let rs: Api<ReplicaSet> = Api::namespaced(
+            collector.client.clone(),
+            &pod.namespace().unwrap_or_default(),
+        );
+        if let Some(d) = rs
+            .get_opt(&owner.name)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|rs| rs.owner_references().first().cloned())
+        {
+            return Some((d.kind, d.name));
+        }
+    }
+    Some((owner.kind.clone(), owner.name.clone()))
+}
+
+async fn collect_pod(collector: &Collector, pod: &PodInfo, folder: &str) {
+    let events = pod
+        .api
+        .list_events()
+        .await
+        .inspect_err(|e| warn!("{}", e))
+        .unwrap_or_default();
+    let results = [
+        collector.describe_pod(pod, &events, folder).await,
+        collector.pod_manifest(pod, folder),
+        collector.last_states(pod, folder),
+        collector
+            .kubectl_to_file(
+                &[
+                    "get",
+                    "events",
+                    "-n",
+                    &pod.namespace,
+                    "--field-selector",
+                    &format!("involvedObject.name={}", pod.name),
+                ],
+                folder,
+                &format!("kubernetes_events_{}_{}.events", pod.namespace, pod.name),
+            )
+            .await,
+    ];
+    results
+        .into_iter()
+        .filter_map(|r| r.err())
+        .for_each(|e| warn!("{}", e));
+    for c in &pod.containers {
+        for previous in [false, true] {
+            if let Err(e) = collector.pod_logs(pod, c, previous, None, folder).await {
+                warn!("{}", e)
+            }
+        }
+    }
+    if let Some((kind, name)) = owner(collector, &pod.pod).await {
+        let resource = format!("{}/{}", kind.to_lowercase(), name);
+        let filename = format!("{}_{}_{}.yaml", pod.namespace, kind.to_lowercase(), name);
+        if let Err(e) = collector
+            .kubectl_to_file(
+                &["get", &resource, "-n", &pod.namespace, "-o", "yaml"],
+                folder,
+                &filename,
+            )
+            .await
+        {
+            warn!("{}", e)
+        }
+    }
+    if !pod.node_name.is_empty() {
+        let filename = format!("{}.node.description", pod.node_name);
+        if let Err(e) = collector
+            .kubectl_to_file(&["describe", "node", &pod.node_name], folder, &filename)
+            .await
+        {
+            warn!("{}", e)
+        }
+    }
+}
+
+//everything about the given pods in antlog_pods_<context>_<date>.tar.gz, one folder per pod.
+//Every pod is looked up first, nothing is collected when one of them does not exist.
+pub async fn collect_pods(collector: &Collector, refs: &[(String, String)]) -> Result<String> {
+    let mut pods = vec![];
+    for (namespace, name) in refs {
+        pods.push(find_pod(collector, namespace, name).await?);
+    }
+    let name = format!(
+        "antlog_pods_{}_{}",
+        collector.config.context_name,
+        Utc::now().format("%Y%m%d%H%M%S")
+    );
+    let root = format!("{}/{}", output_directory(&collector.config), name);
+    for pod in &pods {
+        let folder = format!("{}/{}_{}", root, pod.namespace, pod.name);
+        fs::create_dir_all(&folder)?;
+        info!("Collecting pod {}/{}.", pod.namespace, pod.name);
+        collect_pod(collector, pod, &folder).await;
+    }
+    let path = format!("{}.tar.gz", root);
+    let mut tar = tar::Builder::new(GzEncoder::new(File::create(&path)?, Compression::default()));
+    tar.append_dir_all(&name, &root)?;
+    tar.into_inner()?.finish()?;
+    fs::remove_dir_all(&root)?;
+    Ok(path)
+}
+
+//only the current logs of the given pods, one container after the other into the target
+//(a directory, stdout or a tar stream), <namespace>_<pod>_<container>.log each.
+pub async fn collect_logs(
+    collector: &Collector,
+    refs: &[(String, String)],
+    target: &mut OutputTarget,
+) -> Result<()> {
+    let mut pods = vec![];
+    for (namespace, name) in refs {
+        pods.push(find_pod(collector, namespace, name).await?);
+    }
+    let config = &collector.config;
+    for pod in &pods {
+        for c in &pod.containers {
+            let l = get_logs(
+                pod.name.clone(),
+                c.clone(),
+                pod.api.clone(),
+                false,
+                None,
+                (config.log_tail_lines > 0).then_some(config.log_tail_lines),
+                (config.log_limit_bytes > 0).then_some(config.log_limit_bytes),
+            )
+            .await?;
+            let name = format!("{}_{}_{}.log", pod.namespace, pod.name, c);
+            info!("==== {}/{} [{}] ====", pod.namespace, pod.name, c);
+            target.write(&name, l.as_bytes())?;
+            info!("Logs have been written to {}", target.describe(&name));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,291 @@
+use regex::Regex;
+use serde::Deserialize;
+use serde_derive::Serialize;
+use simplelog::{__private::log::warn, info};
+
+use crate::{
+    access::{HttpGet, PodAccess},
+    component_command::{self, ComponentCommand, ComponentTarget},
+    components::ComponentScope,
+    credentials, PodInfo,
+};
+
+//which of the matched pods of a component its commands run in.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PodSelection {
+    #[default]
+    First,
+    All,
+    //the pod the component elects (es master, kafka controller, hbase active master).
+    Leader,
+}
+
+//components with a leader probe, the others have no leader to look for.
+pub const LEADER_COMPONENTS: [&str; 4] = ["elasticsearch", "kafka", "kafka_message_bus", "hbase"];
+
+//leader on a component without one means every pod: the prometheus replicas scrape
+//independently, both are needed.
+pub fn resolve(component: &str, selection: PodSelection) -> PodSelection {
+    match selection {
+        PodSelection::Leader if !LEADER_COMPONENTS.contains(&component) => PodSelection::All,
+        s => s,
+    }
+}
+
+//the command run in the first pod to find the leader, parsed by parse_leader.
+pub fn leader_probe(component: &str) -> Option<ComponentCommand> {
+    let (shell, http) = match component {
+        "elasticsearch" => (
+            format!(
+                "curl -k -u \"${}:${}\" -X GET \"https://localhost:9200/_cat/master?h=node\"",
+                credentials::USERNAME_ENV,
+                credentials::PASSWORD_ENV
+            ),
+            Some(HttpGet {
+                port: 9200,
+                tls: true,
+                path: "/_cat/master?h=node".to_string(),
+                ..Default::default()
+            }),
+        ),
+        "kafka" | "kafka_message_bus" => {
+            let prefix = if component == "kafka" { "bin/" } else { "" };
+            //the controller id from the metadata quorum, its host from the broker list.
+            (
+                format!(
+                    "{p}kafka-metadata-quorum.sh --bootstrap-server localhost:9092 describe --status; \
+                     {p}kafka-broker-api-versions.sh --bootstrap-server localhost:9092 | grep '(id:'",
+                    p = prefix
+                ),
+                None,
+            )
+        }
+        "hbase" => ("echo \"status 'detailed'\" | hbase shell".to_string(), None),
+        _ => return None,
+    };
+    Some(ComponentCommand {
+        name: "leader".to_string(),
+        shell,
+        http,
+        ..Default::default()
+    })
+}
+
+//kafka-0.kafka-headless.ns.svc.cluster.local -> kafka-0, the pod of a statefulset host name.
+fn host_pod(host: &str) -> String {
+    host.split('.').next().unwrap_or(host).to_string()
+}
+
+//_cat/master?h=node answers the node name, the last column of the full _cat/master line.
+pub fn parse_es_master(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .and_then(|l| l.split_whitespace().last())
+        .map(|n| n.to_string())
+}
+
+//LeaderId of describe --status, then the host of that id in the broker-api-versions lines
+//("kafka-1.kafka-headless:9092 (id: 1 rack: null) -> (").
+pub fn parse_kafka_controller(output: &str) -> Option<String> {
+    let leader = Regex::new(r"LeaderId:\s+(\d+)").unwrap();
+    let broker = Regex::new(r"^(\S+):\d+\s+\(id:\s*(\d+)").unwrap();
+    let id = leader.captures(output)?.get(1)?.as_str();
+    output.lines().find_map(|l| {
+        let c = broker.captures(l.trim())?;
+        (&c[2] == id).then(|| host_pod(&c[1]))
+    })
+}
+
+//"active master:  hbase-master-1.hbase-master.ns.svc:16000 1700000000000" of status 'detailed'.
+pub fn parse_hbase_active_master(output: &str) -> Option<String> {
+    let re = Regex::new(r"(?i)active master:\s+([^:\s,]+)").unwrap();
+    re.captures(output).map(|c| host_pod(&c[1]))
+}
+
+pub fn parse_leader(component: &str, output: &str) -> Option<String> {
+    match component {
+        "elasticsearch" => parse_es_master(output),
+        "kafka" | "kafka_message_bus" => parse_kafka_controller(output),
+        "hbase" => parse_hbase_active_master(output),
+        _ => None,
+    }
+}
+
+//the kind of an output, prefixed with its pod when several pods of the component are collected.
+pub fn output_kind<A>(pod: &PodInfo<A>, kind: &str, several: bool) -> String {
+    if several {
+        format!("{}_{}_{}", pod.namespace, pod.name, kind)
+    } else {
+        kind.to_string()
+    }
+}
+
+//the pods the component commands run in, pods being the matched ones (not empty).
+//probe replaces the default leader probe (the elasticsearch one needs the credentials);
+//a leader that cannot be found falls back to the first pod.
+pub async fn select_pods<A: PodAccess>(
+    component: &str,
+    scope: &ComponentScope,
+    access: &A,
+    mut pods: Vec<PodInfo<A>>,
+    probe: Option<ComponentCommand>,
+) -> Vec<PodInfo<A>> {
+    match resolve(component, scope.pod_selection) {
+        PodSelection::All => return pods,
+        PodSelection::First => {
+            pods.truncate(1);
+            return pods;
+        }
+        PodSelection::Leader => {}
+    }
+    let Some(probe) = probe.or_else(|| leader_probe(component)) else {
+        pods.truncate(1);
+        return pods;
+    };
+    let target =
+        ComponentTarget::new(component, pods[0].clone()).with_scope(scope.clone(), access.clone());
+    let leader = component_command::run_component_command(&target, &probe)
+        .await
+        .map(|(output, _)| parse_leader(component, &output));
+    match leader {
+        Ok(Some(name)) => match pods.iter().position(|p| p.name == name) {
+            Some(i) => {
+                info!("Leader of {}: pod {}.", component, name);
+                return vec![pods.swap_remove(i)];
+            }
+            None => warn!(
+                "Leader {} of {} is not one of the matched pods, the first pod is collected.",
+                name, component
+            ),
+        },
+        Ok(None) => warn!(
+            "No leader of {} in the probe output, the first pod is collected.",
+            component
+        ),
+        Err(e) => warn!(
+            "Probing the leader of {}: {}, the first pod is collected.",
+            component, e
+        ),
+    }
+    pods.truncate(1);
+    pods
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{access::FakeAccess, get_pod_list, test_support::fixture};
+
+    fn captured(name: &str) -> String {
+        std::fs::read_to_string(fixture(&format!("pod_selection/{}", name))).unwrap()
+    }
+
+    #[test]
+    fn elasticsearch_master_of_either_cat_format() {
+        assert_eq!(
+            parse_es_master(&captured("es_master.txt")).as_deref(),
+            Some("es-master-2")
+        );
+        assert_eq!(
+            parse_es_master(&captured("es_cat_master.txt")).as_deref(),
+            Some("es-master-2")
+        );
+        assert_eq!(parse_es_master("\n  \n"), None);
+    }
+
+    #[test]
+    fn kafka_controller_is_the_broker_of_the_leader_id() {
+        //broker 10 is listed first, its id only starts like the leader one.
+        assert_eq!(
+            parse_kafka_controller(&captured("kafka_controller.txt")).as_deref(),
+            Some("kafka-1")
+        );
+        //ZooKeeper clusters have no quorum to describe.
+        assert_eq!(
+            parse_kafka_controller(&captured("kafka_no_quorum.txt")),
+            None
+        );
+        //a leader id without its broker line.
+        assert_eq!(parse_kafka_controller("LeaderId: 7\n"), None);
+    }
+
+    #[test]
+    fn hbase_active_master_not_a_backup() {
+        assert_eq!(
+            parse_hbase_active_master(&captured("hbase_status.txt")).as_deref(),
+            Some("hbase-master-1")
+        );
+        assert_eq!(
+            parse_hbase_active_master("Active Master: hbase-master-0,16000,1697525000000")
+                .as_deref(),
+            Some("hbase-master-0")
+        );
+        assert_eq!(parse_hbase_active_master("0 backup masters\n"), None);
+    }
+
+    #[test]
+    fn leader_parsed_per_component() {
+        let kafka = captured("kafka_controller.txt");
+        assert_eq!(parse_leader("kafka", &kafka).as_deref(), Some("kafka-1"));
+        assert_eq!(
+            parse_leader("kafka_message_bus", &kafka).as_deref(),
+            Some("kafka-1")
+        );
+        assert_eq!(parse_leader("prometheus", &kafka), None);
+        assert_eq!(
+            resolve("prometheus", PodSelection::Leader),
+            PodSelection::All
+        );
+        assert_eq!(resolve("kafka", PodSelection::Leader), PodSelection::Leader);
+    }
+
+    fn leader_scope() -> ComponentScope {
+        ComponentScope {
+            selector: "".to_string(),
+            namespaces: vec!["kafka".to_string()],
+            ordinals: None,
+            pod_selection: PodSelection::Leader,
+        }
+    }
+
+    async fn select_kafka(probe_output: Option<String>) -> Vec<String> {
+        let mut access = FakeAccess::from_fixture(&fixture("access/cluster.json")).unwrap();
+        if let Some(output) = probe_output {
+            let shell = leader_probe("kafka").unwrap().shell;
+            access
+                .exec
+                .insert(format!("kafka-0/kafka//bin/sh -c {}", shell), output);
+        }
+        let access = access.in_namespace("kafka");
+        let pods = get_pod_list(vec![access.clone()], "".to_string(), "".to_string())
+            .await
+            .unwrap();
+        select_pods("kafka", &leader_scope(), &access, pods, None)
+            .await
+            .into_iter()
+            .map(|p| p.name)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn the_leader_pod_is_selected() {
+        let selected = select_kafka(Some(captured("kafka_controller.txt"))).await;
+        assert_eq!(selected, vec!["kafka-1"]);
+    }
+
+    #[tokio::test]
+    async fn the_first_pod_without_a_leader() {
+        //kafka-10 is not one of the matched pods.
+        let not_matched =
+            captured("kafka_controller.txt").replace("LeaderId:               1", "LeaderId: 10");
+        assert_eq!(select_kafka(Some(not_matched)).await, vec!["kafka-0"]);
+        assert_eq!(
+            select_kafka(Some(captured("kafka_no_quorum.txt"))).await,
+            vec!["kafka-0"]
+        );
+        //the probe itself failing.
+        assert_eq!(select_kafka(None).await, vec!["kafka-0"]);
+    }
+}
@@ -0,0 +1,121 @@
+use k8s_openapi::api::core::v1::Pod;
+
+use crate::ConfigFile;
+
+pub const QUICK_TAIL_LINES: i64 = 2000;
+//fastest gzip level, the quick archive is meant to be sent right away.
+pub const QUICK_COMPRESSION_LEVEL: u32 = 1;
+
+//first response preset: failing pods only, tail of their logs, no app collectors.
+//applied over the config file, explicit command line flags are applied after it.
+pub fn apply_quick(config: &mut ConfigFile) {
+    config.only_failing_pods = true;
+    config.log_tail_lines = QUICK_TAIL_LINES;
+    config.current_logs = true;
+    config.previous_logs = true;
+    config.skip_app_collectors = true;
+    config.archive_compression_level = Some(QUICK_COMPRESSION_LEVEL);
+}
+
+//read API access only: the collectors running pod exec, port-forward or debug pods are all disabled.
+pub fn apply_read_only(config: &mut ConfigFile) {
+    config.read_only = true;
+    config.skip_app_collectors = true;
+    config.collect_node_debug = false;
+    config.collect_disk_usage = false;
+    config.collect_jvm_gc = false;
+    config.custom_collectors.file_copies.clear();
+    config
+        .app_selectors
+        .values_mut()
+        .for_each(|s| s.log_files.clear());
+    config.plugins.clear();
+}
+
+//not running or completed, a container not ready, waiting or restarted.
+pub fn pod_failing(pod: &Pod) -> bool {
+    let Some(status) = pod.status.as_ref() else {
+        return true;
+    };
+    match status.phase.as_deref() {
+        Some("Succeeded") => return false,
+        Some("Running") => {}
+        _ => return true,
+    }
+    status.container_statuses.iter().flatten().any(|c| {
+        !c.ready || c.restart_count > 0 || c.state.as_ref().is_some_and(|s| s.waiting.is_some())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components::AppSelector, plugins::Plugin, CustomCollectors, FileCopy};
+
+    //every collector exec-ing into the pods or starting debug pods turned on.
+    fn everything_on() -> ConfigFile {
+        let selector = AppSelector {
+            log_files: vec!["/opt/kafka/logs/*.log".to_string()],
+            ..Default::default()
+        };
+        ConfigFile {
+            collect_node_debug: true,
+            collect_disk_usage: true,
+            collect_jvm_gc: true,
+            collect_cni: true,
+            custom_collectors: CustomCollectors {
+                file_copies: vec![FileCopy {
+                    selector: "app=web".to_string(),
+                    paths: vec!["/etc/nginx".to_string()],
+                    log_files: vec!["/var/log/nginx/*.log".to_string()],
+                    ..Default::default()
+                }],
+            },
+            app_selectors: [("kafka", selector.clone()), ("hbase", selector)]
+                .map(|(c, s)| (c.to_string(), s))
+                .into(),
+            plugins: vec![Plugin {
+                name: "site".to_string(),
+                path: "/usr/local/bin/site-collector".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn read_only_turns_off_every_exec_backed_option() {
+        let mut config = everything_on();
+        apply_read_only(&mut config);
+        //read_only is what the cni statuses and the clock skew probe check.
+        assert!(config.read_only);
+        assert!(config.skip_app_collectors);
+        assert!(!config.collect_node_debug);
+        assert!(!config.collect_disk_usage);
+        assert!(!config.collect_jvm_gc);
+        assert!(config.custom_collectors.file_copies.is_empty());
+        assert!(config.plugins.is_empty());
+        //the selectors stay, only their in-container log files go.
+        assert_eq!(config.app_selectors.len(), 2);
+        assert!(config
+            .app_selectors
+            .values()
+            .all(|s| s.log_files.is_empty()));
+        //the api-only parts of a collector are kept.
+        assert!(config.collect_cni);
+    }
+
+    #[test]
+    fn quick_skips_the_app_collectors() {
+        let mut config = everything_on();
+        apply_quick(&mut config);
+        assert!(config.skip_app_collectors);
+        assert!(config.only_failing_pods);
+        assert_eq!(config.log_tail_lines, QUICK_TAIL_LINES);
+        assert_eq!(
+            config.archive_compression_level,
+            Some(QUICK_COMPRESSION_LEVEL)
+        );
+        assert!(!config.read_only);
+    }
+}
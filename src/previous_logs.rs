@@ -0,0 +1,123 @@
+use chrono::{DateTime, Duration, Utc};
+
+use std::cmp::Reverse;
+
+use crate::PodInfo;
+
+//when the previous run of the container ended, from lastState.terminated.finishedAt.
+pub fn last_crash<A>(pod: &PodInfo<A>, container: &str) -> Option<DateTime<Utc>> {
+    pod.container_status(container)?
+        .last_state
+        .as_ref()?
+        .terminated
+        .as_ref()?
+        .finished_at
+        .as_ref()
+        .map(|t| t.0)
+}
+
+//with a max age only the containers whose last crash ended within it are kept (no crash, no previous log),
+//the most recent crashes come first and the containers without a known crash last.
+pub fn prioritize<A>(
+    tasks: Vec<(PodInfo<A>, String)>,
+    max_age_hours: Option<u64>,
+    now: DateTime<Utc>,
+) -> Vec<(PodInfo<A>, String)> {
+    let oldest = max_age_hours.map(|h| now - Duration::hours(h as i64));
+    let mut tasks = tasks
+        .into_iter()
+        .map(|(p, c)| (last_crash(&p, &c), p, c))
+        .filter(|(crash, _, _)| match oldest {
+            Some(o) => crash.is_some_and(|c| c >= o),
+            None => true,
+        })
+        .collect::<Vec<_>>();
+    tasks.sort_by_key(|(crash, _, _)| Reverse(*crash));
+    tasks.into_iter().map(|(_, p, c)| (p, c)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::pod_info;
+    use serde_json::json;
+
+    //one container per crash, None for a container that never terminated.
+    fn pod(name: &str, crashes: &[(&str, Option<&str>)]) -> PodInfo<()> {
+        let statuses = crashes
+            .iter()
+            .map(|(c, finished)| {
+                let last = match finished {
+                    Some(f) => json!({"terminated": {"exitCode": 1, "finishedAt": f}}),
+                    None => json!({}),
+                };
+                json!({"name": c, "image": "app:1", "imageID": "", "ready": true, "restartCount": 1, "lastState": last})
+            })
+            .collect::<Vec<_>>();
+        let containers = crashes
+            .iter()
+            .map(|(c, _)| json!({"name": c}))
+            .collect::<Vec<_>>();
+        pod_info(json!({
+            "metadata": {"name": name, "namespace": "ns"},
+            "spec": {"containers": containers},
+            "status": {"containerStatuses": statuses}
+        }))
+    }
+
+    fn now() -> DateTime<Utc> {
+        "2026-10-17T12:00:00Z".parse().unwrap()
+    }
+
+    fn names(tasks: &[(PodInfo<()>, String)]) -> Vec<String> {
+        tasks
+            .iter()
+            .map(|(p, c)| format!("{}/{}", p.name, c))
+            .collect()
+    }
+
+    fn tasks() -> Vec<(PodInfo<()>, String)> {
+        let a = pod(
+            "a",
+            &[("old", Some("2026-10-15T12:00:00Z")), ("never", None)],
+        );
+        let b = pod("b", &[("recent", Some("2026-10-17T11:00:00Z"))]);
+        let c = pod("c", &[("hour_ago", Some("2026-10-17T09:00:00Z"))]);
+        vec![
+            (a.clone(), "old".to_string()),
+            (a, "never".to_string()),
+            (b, "recent".to_string()),
+            (c, "hour_ago".to_string()),
+        ]
+    }
+
+    #[test]
+    fn last_crash_from_the_last_terminated_state() {
+        let p = pod("a", &[("x", Some("2026-10-17T11:00:00Z")), ("y", None)]);
+        assert_eq!(
+            last_crash(&p, "x"),
+            Some("2026-10-17T11:00:00Z".parse().unwrap())
+        );
+        assert_eq!(last_crash(&p, "y"), None);
+        assert_eq!(last_crash(&p, "missing"), None);
+    }
+
+    #[test]
+    fn most_recent_crashes_first_unknown_last() {
+        let ordered = prioritize(tasks(), None, now());
+        assert_eq!(
+            names(&ordered),
+            vec!["b/recent", "c/hour_ago", "a/old", "a/never"]
+        );
+    }
+
+    #[test]
+    fn max_age_drops_old_and_unknown_crashes() {
+        let ordered = prioritize(tasks(), Some(4), now());
+        assert_eq!(names(&ordered), vec!["b/recent", "c/hour_ago"]);
+        //the boundary itself is kept.
+        let ordered = prioritize(tasks(), Some(3), now());
+        assert_eq!(names(&ordered), vec!["b/recent", "c/hour_ago"]);
+        assert!(prioritize(tasks(), Some(0), now()).is_empty());
+    }
+}
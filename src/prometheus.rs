@@ -0,0 +1,170 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+//scrape target whose health is not "up".
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DownTarget {
+    pub job: String,
+    pub instance: String,
+    pub health: String,
+    pub last_error: String,
+}
+
+//rule group with failing evaluations or firing alerts, "<rule>: <lastError>" and alert names.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RuleGroupProblems {
+    pub file: String,
+    pub group: String,
+    pub failing: Vec<String>,
+    pub firing: Vec<String>,
+}
+
+fn text(v: &Value, pointer: &str) -> String {
+    v.pointer(pointer)
+        .and_then(|s| s.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn data_array<'a>(response: &'a Value, field: &str) -> Result<&'a Vec<Value>> {
+    response
+        .pointer(&format!("/data/{}", field))
+        .and_then(|a| a.as_array())
+        .ok_or_else(|| anyhow!("No data.{} in the Prometheus response.", field))
+}
+
+//from /api/v1/targets.
+pub fn down_targets(targets: &str) -> Result<Vec<DownTarget>> {
+    let response: Value = serde_json::from_str(targets)?;
+    Ok(data_array(&response, "activeTargets")?
+        .iter()
+        .filter(|t| text(t, "/health") != "up")
+        .map(|t| DownTarget {
+            job: text(t, "/labels/job"),
+            instance: text(t, "/labels/instance"),
+            health: text(t, "/health"),
+            last_error: text(t, "/lastError"),
+        })
+        .collect())
+}
+
+//from /api/v1/rules, a rule fails when its health is not "ok".
+pub fn rule_problems(rules: &str) -> Result<Vec<RuleGroupProblems>> {
+    let response: Value = serde_json::from_str(rules)?;
+    let mut problems = vec![];
+    for g in data_array(&response, "groups")? {
+        let mut p = RuleGroupProblems {
+            file: text(g, "/file"),
+            group: text(g, "/name"),
+            ..Default::default()
+        };
+        for r in g
+            .get("rules")
+            .and_then(|r| r.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let health = text(r, "/health");
+            if !health.is_empty() && health != "ok" {
+                p.failing
+                    .push(format!("{}: {}", text(r, "/name"), text(r, "/lastError")));
+            }
+            if text(r, "/state") == "firing" {
+                p.firing.push(text(r, "/name"));
+            }
+        }
+        if !p.failing.is_empty() || !p.firing.is_empty() {
+            problems.push(p);
+        }
+    }
+    Ok(problems)
+}
+
+pub fn render_problems(targets: &[DownTarget], groups: &[RuleGroupProblems]) -> String {
+    let mut out = format!("scrape targets not up: {}\n", targets.len());
+    targets.iter().for_each(|t| {
+        out.push_str(&format!(
+            "  job={} instance={} health={} lastError={}\n",
+            t.job, t.instance, t.health, t.last_error
+        ))
+    });
+    out.push_str(&format!(
+        "\nrule groups with problems: {} ({} failing rules, {} firing alerts)\n",
+        groups.len(),
+        groups.iter().map(|g| g.failing.len()).sum::<usize>(),
+        groups.iter().map(|g| g.firing.len()).sum::<usize>()
+    ));
+    for g in groups {
+        out.push_str(&format!(
+            "  group {} ({}): {} failing, {} firing\n",
+            g.group,
+            g.file,
+            g.failing.len(),
+            g.firing.len()
+        ));
+        g.failing
+            .iter()
+            .for_each(|f| out.push_str(&format!("    failing {}\n", f)));
+        g.firing
+            .iter()
+            .for_each(|f| out.push_str(&format!("    firing {}\n", f)));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    fn response(name: &str) -> String {
+        std::fs::read_to_string(fixture(&format!("prometheus/{}", name))).unwrap()
+    }
+
+    #[test]
+    fn down_targets_are_the_ones_not_up() {
+        let down = down_targets(&response("targets.json")).unwrap();
+        assert_eq!(
+            down,
+            vec![
+                DownTarget {
+                    job: "kafka".to_string(),
+                    instance: "kafka-0:9308".to_string(),
+                    health: "down".to_string(),
+                    last_error: "Get \"http://kafka-0:9308/metrics\": context deadline exceeded"
+                        .to_string(),
+                },
+                DownTarget {
+                    job: "elastic".to_string(),
+                    health: "unknown".to_string(),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rule_problems_keep_the_groups_with_failing_or_firing_rules() {
+        let problems = rule_problems(&response("rules.json")).unwrap();
+        assert_eq!(
+            problems,
+            vec![RuleGroupProblems {
+                file: "/etc/prometheus/rules/kafka.yaml".to_string(),
+                group: "kafka".to_string(),
+                failing: vec!["kafka:lag:sum: many-to-many matching not allowed".to_string()],
+                firing: vec!["KafkaUnderReplicated".to_string()],
+            }]
+        );
+        let report = render_problems(&[], &problems);
+        assert!(report.contains("rule groups with problems: 1 (1 failing rules, 1 firing alerts)"));
+        assert!(report.contains("    firing KafkaUnderReplicated\n"));
+    }
+
+    #[test]
+    fn responses_without_data_are_errors() {
+        let error = r#"{"status": "error", "errorType": "bad_data", "error": "unknown"}"#;
+        assert!(down_targets(error).is_err());
+        assert!(rule_problems(error).is_err());
+        assert!(down_targets("<html>").is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE, Engine};
+use hyper::{client::HttpConnector, Body, Method, Request};
+use openssl::ssl::{SslConnector, SslMethod};
+
+use std::time::Duration;
+
+use crate::report::{CollectionInfo, RunClassification};
+
+pub const PUSH_JOB: &str = "antlog";
+pub const PUSH_TIMEOUT_SECONDS: u64 = 30;
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+//the run in the Prometheus text format, every sample labeled with the context name.
+pub fn render_metrics(info: &CollectionInfo) -> String {
+    let context = format!("context=\"{}\"", escape(&info.context_name));
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, samples: Vec<(String, f64)>| {
+        out.push_str(&format!(
+            "# HELP {} {}\n# TYPE {} gauge\n",
+            name, help, name
+        ));
+        for (labels, value) in samples {
+            out.push_str(&format!("{}{{{}{}}} {}\n", name, context, labels, value));
+        }
+    };
+    let success = info.classification != RunClassification::Failed;
+    gauge(
+        "antlog_run_success",
+        "1 when the archive was created.",
+        vec![(String::new(), if success { 1.0 } else { 0.0 })],
+    );
+    gauge(
+        "antlog_files_collected",
+        "Files written to the collection.",
+        vec![(String::new(), info.collected_files as f64)],
+    );
+    gauge(
+        "antlog_bytes_collected",
+        "Size of the collection before compression.",
+        vec![(String::new(), info.collected_bytes as f64)],
+    );
+    gauge(
+        "antlog_phase_duration_seconds",
+        "Duration of each collection phase.",
+        info.phase_durations_seconds
+            .iter()
+            .map(|(p, d)| (format!(",phase=\"{}\"", escape(p)), *d))
+            .collect(),
+    );
+    gauge(
+        "antlog_collector_failures",
+        "Failed outputs of each collector.",
+        info.phases
+            .iter()
+            .map(|(p, r)| (format!(",collector=\"{}\"", escape(p)), r.failed as f64))
+            .collect(),
+    );
+    out
+}
+
+//the grouping key is the job and the context, base64 encoded as contexts hold '/' and ':'.
+pub fn push_url(base: &str, context: &str) -> String {
+    format!(
+        "{}/metrics/job/{}/context@base64/{}",
+        base.trim_end_matches('/'),
+        PUSH_JOB,
+        URL_SAFE.encode(context)
+    )
+}
+
+//PUT replaces the previous metrics of the same grouping key.
+pub async fn push(url: &str, metrics: String) -> Result<()> {
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    let https = hyper_openssl::HttpsConnector::with_connector(
+        http,
+        SslConnector::builder(SslMethod::tls())?,
+    )?;
+    let client: hyper::Client<_, Body> = hyper::Client::builder().build(https);
+    let request = Request::builder()
+        .method(Method::PUT)
+        .uri(url)
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(Body::from(metrics))?;
+    let response = tokio::time::timeout(
+        Duration::from_secs(PUSH_TIMEOUT_SECONDS),
+        client.request(request),
+    )
+    .await
+    .map_err(|_| {
+        anyhow!(
+            "No answer from the Pushgateway within {}s.",
+            PUSH_TIMEOUT_SECONDS
+        )
+    })??;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        return Err(anyhow!(
+            "Pushgateway answered {}: {}",
+            status,
+            String::from_utf8_lossy(&body).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::PhaseResult;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    fn info() -> CollectionInfo {
+        CollectionInfo {
+            context_name: "admin@\"prod\"".to_string(),
+            classification: RunClassification::Partial,
+            collected_files: 12,
+            collected_bytes: 4096,
+            phase_durations_seconds: [("pods".to_string(), 1.5)].into(),
+            phases: [(
+                "infra".to_string(),
+                PhaseResult {
+                    succeeded: 3,
+                    failed: 2,
+                },
+            )]
+            .into(),
+            ..Default::default()
+        }
+    }
+
+    //a Pushgateway answering every request with the given status line, returns the raw request received.
+    async fn pushgateway(status: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            loop {
+                let n = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..n]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|l| {
+                            l.to_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(|v| v.parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if body.len() >= length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+            let body = "bad metric";
+            stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    )
+                    .as_bytes(),
+                )
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn every_sample_carries_the_escaped_context() {
+        let metrics = render_metrics(&info());
+        assert!(metrics.contains("# TYPE antlog_run_success gauge\n"));
+        assert!(metrics.contains("antlog_run_success{context=\"admin@\\\"prod\\\"\"} 1\n"));
+        assert!(metrics.contains("antlog_files_collected{context=\"admin@\\\"prod\\\"\"} 12\n"));
+        assert!(metrics.contains("antlog_bytes_collected{context=\"admin@\\\"prod\\\"\"} 4096\n"));
+        assert!(metrics.contains(
+            "antlog_phase_duration_seconds{context=\"admin@\\\"prod\\\"\",phase=\"pods\"} 1.5\n"
+        ));
+        assert!(metrics.contains(
+            "antlog_collector_failures{context=\"admin@\\\"prod\\\"\",collector=\"infra\"} 2\n"
+        ));
+    }
+
+    #[test]
+    fn a_failed_run_is_not_a_success() {
+        let failed = CollectionInfo {
+            classification: RunClassification::Failed,
+            ..Default::default()
+        };
+        assert!(render_metrics(&failed).contains("antlog_run_success{context=\"\"} 0\n"));
+        //no phase, just the headers.
+        assert!(render_metrics(&failed).ends_with("# TYPE antlog_collector_failures gauge\n"));
+    }
+
+    #[test]
+    fn grouping_key_of_the_job_and_the_base64_context() {
+        assert_eq!(
+            push_url("http://pushgateway:9091/", "arn:aws:eks/prod"),
+            "http://pushgateway:9091/metrics/job/antlog/context@base64/YXJuOmF3czpla3MvcHJvZA=="
+        );
+    }
+
+    #[tokio::test]
+    async fn metrics_are_put_to_the_grouping_key() {
+        let (base, server) = pushgateway("200 OK").await;
+        let url = push_url(&base, "prod");
+        push(&url, render_metrics(&info())).await.unwrap();
+        let request = server.await.unwrap();
+        assert!(request.starts_with("PUT /metrics/job/antlog/context@base64/cHJvZA== HTTP/1.1\r\n"));
+        assert!(request.contains("content-type: text/plain; version=0.0.4\r\n"));
+        assert!(request.ends_with(&render_metrics(&info())));
+    }
+
+    #[tokio::test]
+    async fn a_rejected_push_reports_the_status_and_the_body() {
+        let (base, server) = pushgateway("400 Bad Request").await;
+        let error = push(&push_url(&base, "prod"), "bad".to_string())
+            .await
+            .unwrap_err()
+            .to_string();
+        server.await.unwrap();
+        assert_eq!(error, "Pushgateway answered 400 Bad Request: bad metric");
+    }
+}
@@ -0,0 +1,286 @@
+use anyhow::Result;
+use k8s_openapi::{
+    api::{
+        core::v1::{Pod, ResourceRequirements},
+        scheduling::v1::PriorityClass,
+    },
+    apimachinery::pkg::api::resource::Quantity,
+};
+use serde_derive::Serialize;
+
+use std::collections::BTreeMap;
+
+use crate::PodInfo;
+
+pub const PRIORITY_QOS_FILE: &str = "priority_qos.json";
+pub const PRIORITY_QOS_SUMMARY_FILE: &str = "priority_qos.txt";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum QosClass {
+    Guaranteed,
+    Burstable,
+    BestEffort,
+}
+
+//quantity as a number of base units ("500m" -> 0.5, "1Gi" -> 1073741824).
+pub fn quantity_value(q: &Quantity) -> Option<f64> {
+    let s = q.0.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(s.len());
+    let (number, suffix) = s.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let factor = match suffix {
+        "" => 1.0,
+        "m" => 1e-3,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024.0,
+        "Mi" => 1024f64.powi(2),
+        "Gi" => 1024f64.powi(3),
+        "Ti" => 1024f64.powi(4),
+        "Pi" => 1024f64.powi(5),
+        "Ei" => 1024f64.powi(6),
+        e if e.starts_with(['e', 'E']) => 10f64.powi(e[1..].parse().ok()?),
+        _ => return None,
+    };
+    Some(number * factor)
+}
+
+const QOS_RESOURCES: [&str; 2] = ["cpu", "memory"];
+
+//kubelet rules: only cpu and memory count, requests default to limits, init containers included.
+pub fn qos_class(pod: &Pod) -> QosClass {
+    let Some(spec) = pod.spec.as_ref() else {
+        return QosClass::BestEffort;
+    };
+    let containers = spec
+        .init_containers
+        .iter()
+        .flatten()
+        .chain(spec.containers.iter())
+        .collect::<Vec<_>>();
+    let get = |r: &Option<ResourceRequirements>, limits: bool, name: &str| {
+        r.as_ref()
+            .and_then(|r| {
+                if limits {
+                    r.limits.as_ref()
+                } else {
+                    r.requests.as_ref()
+                }
+            })
+            .and_then(|m| m.get(name))
+            .and_then(quantity_value)
+            .filter(|v| *v != 0.0)
+    };
+    let mut any_set = false;
+    let mut guaranteed = true;
+    for c in &containers {
+        for name in QOS_RESOURCES {
+            let request = get(&c.resources, false, name);
+            let limit = get(&c.resources, true, name);
+            any_set |= request.is_some() || limit.is_some();
+            match (request.or(limit), limit) {
+                (Some(r), Some(l)) if r == l => {}
+                _ => guaranteed = false,
+            }
+        }
+    }
+    if !any_set {
+        QosClass::BestEffort
+    } else if guaranteed {
+        QosClass::Guaranteed
+    } else {
+        QosClass::Burstable
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NamespaceQos {
+    pub qos: BTreeMap<String, usize>,
+    //"<none>" for the pods without priorityClassName.
+    pub priority_classes: BTreeMap<String, usize>,
+    //pods with a positive priority class running as BestEffort.
+    pub critical_best_effort: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PriorityQosReport {
+    //priority class name -> value.
+    pub priority_classes: BTreeMap<String, i32>,
+    pub namespaces: BTreeMap<String, NamespaceQos>,
+}
+
+pub fn priority_qos_report<A>(
+    pods: &[PodInfo<A>],
+    priority_classes: &[PriorityClass],
+) -> PriorityQosReport {
+    let classes = priority_classes
+        .iter()
+        .map(|p| (p.metadata.name.clone().unwrap_or_default(), p.value))
+        .collect::<BTreeMap<String, i32>>();
+    let mut namespaces: BTreeMap<String, NamespaceQos> = BTreeMap::new();
+    for p in pods {
+        let ns = namespaces.entry(p.namespace.clone()).or_default();
+        let qos = qos_class(&p.pod);
+        *ns.qos.entry(format!("{:?}", qos)).or_default() += 1;
+        let class = p
+            .pod
+            .spec
+            .as_ref()
+            .and_then(|s| s.priority_class_name.clone())
+            .unwrap_or_else(|| "<none>".to_string());
+        if qos == QosClass::BestEffort && classes.get(&class).is_some_and(|v| *v > 0) {
+            ns.critical_best_effort
+                .push(format!("{} ({})", p.name, class));
+        }
+        *ns.priority_classes.entry(class).or_default() += 1;
+    }
+    PriorityQosReport {
+        priority_classes: classes,
+        namespaces,
+    }
+}
+
+pub fn render_summary(report: &PriorityQosReport) -> String {
+    let mut out = String::from("priority classes:\n");
+    report
+        .priority_classes
+        .iter()
+        .for_each(|(n, v)| out.push_str(&format!("  {} = {}\n", n, v)));
+    for (name, ns) in &report.namespaces {
+        out.push_str(&format!("\nnamespace {}\n  qos:", name));
+        ns.qos
+            .iter()
+            .for_each(|(q, c)| out.push_str(&format!(" {}={}", q, c)));
+        out.push_str("\n  priority classes:");
+        ns.priority_classes
+            .iter()
+            .for_each(|(p, c)| out.push_str(&format!(" {}={}", p, c)));
+        out.push('\n');
+        if !ns.critical_best_effort.is_empty() {
+            out.push_str(&format!(
+                "  WARNING critical pods running as BestEffort: {}\n",
+                ns.critical_best_effort.join(", ")
+            ));
+        }
+    }
+    out
+}
+
+pub fn to_json(report: &PriorityQosReport) -> Result<String> {
+    Ok(serde_json::to_string_pretty(report)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn value(q: &str) -> Option<f64> {
+        quantity_value(&Quantity(q.to_string()))
+    }
+
+    #[test]
+    fn quantities_in_base_units() {
+        assert_eq!(value("500m"), Some(0.5));
+        assert_eq!(value("2"), Some(2.0));
+        assert_eq!(value(" 1.5 "), Some(1.5));
+        assert_eq!(value("1Gi"), Some(1073741824.0));
+        assert_eq!(value("512Mi"), Some(536870912.0));
+        assert_eq!(value("128974848"), Some(128974848.0));
+        assert_eq!(value("129M"), Some(129e6));
+        assert_eq!(value("1k"), Some(1000.0));
+        assert_eq!(value("1Ki"), Some(1024.0));
+        assert_eq!(value("12e6"), Some(12e6));
+        assert_eq!(value("1E"), Some(1e18));
+        assert_eq!(value("-1"), Some(-1.0));
+    }
+
+    #[test]
+    fn malformed_quantities_have_no_value() {
+        assert_eq!(value(""), None);
+        assert_eq!(value("Gi"), None);
+        assert_eq!(value("1GB"), None);
+        assert_eq!(value("1.2.3"), None);
+        assert_eq!(value("1ex"), None);
+    }
+
+    fn pod(containers: Value, init_containers: Value) -> Pod {
+        serde_json::from_value(json!({
+            "metadata": {"name": "p", "namespace": "n"},
+            "spec": {"containers": containers, "initContainers": init_containers}
+        }))
+        .unwrap()
+    }
+
+    fn container(resources: Value) -> Value {
+        json!({"name": "c", "resources": resources})
+    }
+
+    #[test]
+    fn no_request_nor_limit_is_best_effort() {
+        let p = pod(json!([container(json!({}))]), json!([]));
+        assert_eq!(qos_class(&p), QosClass::BestEffort);
+        //only cpu and memory count, and a zero quantity is no quantity.
+        let other = container(json!({
+            "requests": {"ephemeral-storage": "1Gi", "cpu": "0"},
+            "limits": {"nvidia.com/gpu": "1"}
+        }));
+        assert_eq!(
+            qos_class(&pod(json!([other]), json!([]))),
+            QosClass::BestEffort
+        );
+        let no_spec = Pod::default();
+        assert_eq!(qos_class(&no_spec), QosClass::BestEffort);
+    }
+
+    #[test]
+    fn equal_cpu_and_memory_limits_and_requests_are_guaranteed() {
+        let equal = container(json!({
+            "requests": {"cpu": "500m", "memory": "1Gi"},
+            "limits": {"cpu": "0.5", "memory": "1073741824"}
+        }));
+        assert_eq!(
+            qos_class(&pod(json!([equal]), json!([]))),
+            QosClass::Guaranteed
+        );
+        //requests default to the limits.
+        let limits_only = container(json!({"limits": {"cpu": "1", "memory": "1Gi"}}));
+        assert_eq!(
+            qos_class(&pod(json!([limits_only.clone(), limits_only]), json!([]))),
+            QosClass::Guaranteed
+        );
+    }
+
+    #[test]
+    fn anything_in_between_is_burstable() {
+        let guaranteed = container(json!({"limits": {"cpu": "1", "memory": "1Gi"}}));
+        let lower_request = container(json!({
+            "requests": {"cpu": "500m", "memory": "1Gi"},
+            "limits": {"cpu": "1", "memory": "1Gi"}
+        }));
+        let memory_only = container(json!({"limits": {"memory": "1Gi"}}));
+        let requests_only = container(json!({"requests": {"cpu": "1", "memory": "1Gi"}}));
+        let none = container(json!({}));
+        for other in [lower_request, memory_only, requests_only, none] {
+            let p = pod(json!([guaranteed.clone(), other]), json!([]));
+            assert_eq!(qos_class(&p), QosClass::Burstable);
+        }
+    }
+
+    #[test]
+    fn init_containers_count() {
+        let guaranteed = container(json!({"limits": {"cpu": "1", "memory": "1Gi"}}));
+        let init = container(json!({}));
+        let p = pod(json!([guaranteed]), json!([init]));
+        assert_eq!(qos_class(&p), QosClass::Burstable);
+        let init = container(json!({"requests": {"cpu": "100m"}}));
+        let p = pod(json!([container(json!({}))]), json!([init]));
+        assert_eq!(qos_class(&p), QosClass::Burstable);
+    }
+}
@@ -0,0 +1,441 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::core::v1::{ConfigMap, Pod, Secret};
+use kube::{api::ListParams, Api, ResourceExt};
+use simplelog::{__private::log::warn, info};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{collector::Collector, PodInfo};
+
+pub const CONFIG_MAP: &str = "ConfigMap";
+pub const SECRET: &str = "Secret";
+
+//a ConfigMap or Secret the pod needs, with the key when a single one is used.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Reference {
+    pub kind: &'static str,
+    pub name: String,
+    pub key: Option<String>,
+    pub optional: bool,
+    //"volume <name>", "container <name> envFrom", "container <name> env <VAR>", "imagePullSecrets".
+    pub used_by: String,
+}
+
+//object name -> its key names, values are never kept.
+pub type KeyListing = BTreeMap<String, BTreeSet<String>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenReference {
+    pub pod: String,
+    pub reference: Reference,
+    pub problem: String,
+}
+
+fn reference(
+    kind: &'static str,
+    name: &Option<String>,
+    key: Option<&str>,
+    optional: Option<bool>,
+    used_by: String,
+) -> Reference {
+    Reference {
+        kind,
+        name: name.clone().unwrap_or_default(),
+        key: key.map(|k| k.to_string()),
+        optional: optional.unwrap_or(false),
+        used_by,
+    }
+}
+
+//the ConfigMaps and Secrets of the volumes (projected ones included), envFrom, env valueFrom and imagePullSecrets.
+pub fn pod_references(pod: &Pod) -> Vec<Reference> {
+    let mut refs = vec![];
+    let Some(spec) = pod.spec.as_ref() else {
+        return refs;
+    };
+    for v in spec.volumes.iter().flatten() {
+        let used_by = format!("volume {}", v.name);
+        if let Some(c) = &v.config_map {
+            let items = c.items.iter().flatten().map(|i| Some(i.key.as_str()));
+            for key in items.chain(c.items.is_none().then_some(None)) {
+                refs.push(reference(
+                    CONFIG_MAP,
+                    &c.name,
+                    key,
+                    c.optional,
+                    used_by.clone(),
+                ));
+            }
+        }
+        if let Some(s) = &v.secret {
+            let items = s.items.iter().flatten().map(|i| Some(i.key.as_str()));
+            for key in items.chain(s.items.is_none().then_some(None)) {
+                refs.push(reference(
+                    SECRET,
+                    &s.secret_name,
+                    key,
+                    s.optional,
+                    used_by.clone(),
+                ));
+            }
+        }
+        for source in v.projected.iter().flat_map(|p| p.sources.iter().flatten()) {
+            if let Some(c) = &source.config_map {
+                let items = c.items.iter().flatten().map(|i| Some(i.key.as_str()));
+                for key in items.chain(c.items.is_none().then_some(None)) {
+                    refs.push(reference(
+                        CONFIG_MAP,
+                        &c.name,
+                        key,
+                        c.optional,
+                        used_by.clone(),
+                    ));
+                }
+            }
+            if let Some(s) = &source.secret {
+                let items = s.items.iter().flatten().map(|i| Some(i.key.as_str()));
+                for key in items.chain(s.items.is_none().then_some(None)) {
+                    refs.push(reference(SECRET, &s.name, key, s.optional, used_by.clone()));
+                }
+            }
+        }
+    }
+    let containers = spec
+        .init_containers
+        .iter()
+        .flatten()
+        .chain(spec.containers.iter());
+    for c in containers {
+        for e in c.env_from.iter().flatten() {
+            let used_by = format!("container {} envFrom", c.name);
+            if let Some(r) = &e.config_map_ref {
+                refs.push(reference(
+                    CONFIG_MAP,
+                    &r.name,
+                    None,
+                    r.optional,
+                    used_by.clone(),
+                ));
+            }
+            if let Some(r) = &e.secret_ref {
+                refs.push(reference(SECRET, &r.name, None, r.optional, used_by));
+            }
+        }
+        for e in c.env.iter().flatten() {
+            let used_by = format!("container {} env {}", c.name, e.name);
+            let Some(from) = &e.value_from else {
+                continue;
+            };
+            if let Some(r) = &from.config_map_key_ref {
+                refs.push(reference(
+                    CONFIG_MAP,
+                    &r.name,
+                    Some(&r.key),
+                    r.optional,
+                    used_by.clone(),
+                ));
+            }
+            if let Some(r) = &from.secret_key_ref {
+                refs.push(reference(
+                    SECRET,
+                    &r.name,
+                    Some(&r.key),
+                    r.optional,
+                    used_by,
+                ));
+            }
+        }
+    }
+    for s in spec.image_pull_secrets.iter().flatten() {
+        refs.push(reference(
+            SECRET,
+            &s.name,
+            None,
+            None,
+            "imagePullSecrets".to_string(),
+        ));
+    }
+    refs
+}
+
+//the references that are not optional and point to a missing object or key.
+pub fn check_references<'a, A: 'a>(
+    pods: impl IntoIterator<Item = &'a PodInfo<A>>,
+    config_maps: &KeyListing,
+    secrets: &KeyListing,
+) -> Vec<BrokenReference> {
+    let mut broken = vec![];
+    for p in pods {
+        for r in pod_references(&p.pod).into_iter().filter(|r| !r.optional) {
+            let listing = if r.kind == CONFIG_MAP {
+                config_maps
+            } else {
+                secrets
+            };
+            let problem = match (listing.get(&r.name), &r.key) {
+                (None, _) => format!("{} {} missing", r.kind, r.name),
+                (Some(keys), Some(k)) if !keys.contains(k) => {
+                    format!("key {} missing from {} {}", k, r.kind, r.name)
+                }
+                _ => continue,
+            };
+            broken.push(BrokenReference {
+                pod: p.name.clone(),
+                reference: r,
+                problem,
+            });
+        }
+    }
+    broken
+}
+
+pub fn render_report(namespace: &str, pods: usize, broken: &[BrokenReference]) -> String {
+    let mut out = format!(
+        "{} broken ConfigMap/Secret references over {} pods of {}.\n",
+        broken.len(),
+        pods,
+        namespace
+    );
+    for b in broken {
+        out.push_str(&format!(
+            "{}: {} (used by {})\n",
+            b.pod, b.problem, b.reference.used_by
+        ));
+    }
+    out
+}
+
+fn config_map_keys(cm: &ConfigMap) -> BTreeSet<String> {
+    let data = cm.data.iter().flat_map(|d| d.keys());
+    let binary = cm.binary_data.iter().flat_map(|d| d.keys());
+    data.chain(binary).cloned().collect()
+}
+
+fn secret_keys(s: &Secret) -> BTreeSet<String> {
+    s.data.iter().flat_map(|d| d.keys()).cloned().collect()
+}
+
+//pods/reference_check_<ns>.txt for each namespace of the collected pods, only key names are looked at.
+pub async fn collect_reference_check<A>(
+    collector: &Collector,
+    pods_list: &[PodInfo<A>],
+    folder: &str,
+) -> Result<()> {
+    let namespaces = pods_list
+        .iter()
+        .map(|p| p.namespace.as_str())
+        .collect::<BTreeSet<&str>>();
+    for ns in namespaces {
+        let pods = pods_list
+            .iter()
+            .filter(|p| p.namespace == ns)
+            .collect::<Vec<_>>();
+        let config_maps: Api<ConfigMap> = Api::namespaced(collector.client.clone(), ns);
+        let secrets: Api<Secret> = Api::namespaced(collector.client.clone(), ns);
+        let listings = async {
+            let config_maps = config_maps
+                .list(&ListParams::default())
+                .await?
+                .items
+                .iter()
+                .map(|c| (c.name_any(), config_map_keys(c)))
+                .collect::<KeyListing>();
+            let secrets = secrets
+                .list(&ListParams::default())
+                .await?
+                .items
+                .iter()
+                .map(|s| (s.name_any(), secret_keys(s)))
+                .collect::<KeyListing>();
+            Ok::<_, kube::Error>((config_maps, secrets))
+        };
+        let filename = format!("reference_check_{}.txt", ns);
+        let (config_maps, secrets) = match listings.await {
+            Ok(l) => l,
+            Err(e) => {
+                warn!("Reference check of {}: {}", ns, e);
+                let attempted = format!("list configmaps and secrets in {}", ns);
+                collector
+                    .ctx
+                    .record_failure(folder, &filename, &attempted, &e.into());
+                continue;
+            }
+        };
+        let broken = check_references(pods.iter().copied(), &config_maps, &secrets);
+        let report = render_report(ns, pods.len(), &broken);
+        let er = anyhow!("Empty {}.", filename);
+        collector
+            .ctx
+            .write_file(folder, report.as_bytes(), &filename, er)?;
+        info!("File has been created {}/{}", folder, filename);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        context::FileStatus,
+        test_support::{fixture, json_client, pod_info, run_context, TempDir},
+        ConfigFile,
+    };
+    use k8s_openapi::List;
+    use std::sync::Arc;
+
+    fn read(name: &str) -> String {
+        std::fs::read_to_string(fixture(&format!("reference_check/{}", name))).unwrap()
+    }
+
+    fn pods() -> Vec<PodInfo<()>> {
+        let pods: Vec<serde_json::Value> = serde_json::from_str(&read("pods.json")).unwrap();
+        pods.into_iter().map(pod_info).collect()
+    }
+
+    fn listings() -> (KeyListing, KeyListing) {
+        let config_maps: List<ConfigMap> = serde_json::from_str(&read("configmaps.json")).unwrap();
+        let secrets: List<Secret> = serde_json::from_str(&read("secrets.json")).unwrap();
+        (
+            config_maps
+                .items
+                .iter()
+                .map(|c| (c.name_any(), config_map_keys(c)))
+                .collect(),
+            secrets
+                .items
+                .iter()
+                .map(|s| (s.name_any(), secret_keys(s)))
+                .collect(),
+        )
+    }
+
+    fn used(refs: &[Reference], used_by: &str) -> Vec<(String, Option<String>, bool)> {
+        refs.iter()
+            .filter(|r| r.used_by == used_by)
+            .map(|r| (r.name.clone(), r.key.clone(), r.optional))
+            .collect()
+    }
+
+    #[test]
+    fn references_of_volumes_env_and_pull_secrets() {
+        let refs = pod_references(&pods()[0].pod);
+        let key = |k: &str| Some(k.to_string());
+        assert_eq!(
+            used(&refs, "volume config"),
+            vec![
+                ("web-config".to_string(), key("app.yaml"), false),
+                ("web-config".to_string(), key("logging.yaml"), false),
+            ]
+        );
+        //without items the whole object is needed.
+        assert_eq!(
+            used(&refs, "volume tls"),
+            vec![("web-tls".to_string(), None, false)]
+        );
+        assert_eq!(
+            used(&refs, "volume extra"),
+            vec![("web-extra".to_string(), None, true)]
+        );
+        let bundle = refs
+            .iter()
+            .filter(|r| r.used_by == "volume bundle")
+            .map(|r| (r.kind, r.name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bundle,
+            vec![(CONFIG_MAP, "ca-bundle"), (SECRET, "web-token")]
+        );
+        assert_eq!(
+            used(&refs, "container migrate envFrom"),
+            vec![("db-credentials".to_string(), None, false)]
+        );
+        assert_eq!(
+            used(&refs, "container web env FEATURE"),
+            vec![("web-config".to_string(), key("feature"), true)]
+        );
+        assert!(used(&refs, "container web env PLAIN").is_empty());
+        assert_eq!(
+            used(&refs, "imagePullSecrets"),
+            vec![("registry".to_string(), None, false)]
+        );
+        assert_eq!(refs.len(), 12);
+    }
+
+    #[test]
+    fn missing_objects_and_keys_are_broken() {
+        let (config_maps, secrets) = listings();
+        let broken = check_references(&pods(), &config_maps, &secrets);
+        let found = broken
+            .iter()
+            .map(|b| {
+                (
+                    b.pod.as_str(),
+                    b.problem.as_str(),
+                    b.reference.used_by.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        //optional ones (web-extra, FEATURE) are never reported, binaryData keys count as keys.
+        assert_eq!(
+            found,
+            vec![
+                ("web-0", "ConfigMap ca-bundle missing", "volume bundle"),
+                ("web-0", "Secret web-token missing", "volume bundle"),
+                (
+                    "web-0",
+                    "key password missing from Secret db-credentials",
+                    "container web env DB_PASSWORD"
+                ),
+                (
+                    "web-0",
+                    "key region missing from ConfigMap web-config",
+                    "container web env REGION"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn report_lists_broken_references() {
+        let (config_maps, secrets) = listings();
+        let broken = check_references(&pods(), &config_maps, &secrets);
+        let report = render_report("web", 2, &broken[..1]);
+        assert_eq!(
+            report,
+            "1 broken ConfigMap/Secret references over 2 pods of web.\n\
+             web-0: ConfigMap ca-bundle missing (used by volume bundle)\n"
+        );
+        assert_eq!(
+            render_report("web", 2, &[]),
+            "0 broken ConfigMap/Secret references over 2 pods of web.\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn report_written_per_namespace() {
+        let dir = TempDir::new();
+        let ctx = Arc::new(run_context(&dir));
+        let folder = ctx.folders[0].clone();
+        let client = json_client(&[
+            ("/api/v1/namespaces/web/configmaps", read("configmaps.json")),
+            ("/api/v1/namespaces/web/secrets", read("secrets.json")),
+        ]);
+        let collector = Collector::new(client, ConfigFile::default(), ctx.clone());
+        let mut pods = pods();
+        //a namespace whose listings fail is recorded and does not stop the others.
+        pods[1].namespace = "batch".to_string();
+        collect_reference_check(&collector, &pods, &folder)
+            .await
+            .unwrap();
+
+        let report = dir.read("pods/reference_check_web.txt");
+        assert!(report.starts_with("4 broken ConfigMap/Secret references over 1 pods of web.\n"));
+        assert!(report.contains("web-0: Secret web-token missing (used by volume bundle)\n"));
+        let manifest = ctx.manifest();
+        assert_eq!(
+            manifest["pods/reference_check_batch.txt"].status,
+            FileStatus::Failed
+        );
+        assert!(!dir.path().join("pods/reference_check_batch.txt").exists());
+    }
+}
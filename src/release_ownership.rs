@@ -0,0 +1,242 @@
+use k8s_openapi::api::core::v1::Pod;
+use serde_derive::Serialize;
+
+use std::collections::BTreeMap;
+
+use crate::{preset, run::Helm, PodInfo};
+
+pub const RELEASE_OWNERSHIP_FILE: &str = "release_ownership.json";
+pub const INSTANCE_LABEL: &str = "app.kubernetes.io/instance";
+pub const CHART_LABEL: &str = "helm.sh/chart";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReleaseOwnership {
+    pub release: String,
+    pub namespace: String,
+    pub chart: String,
+    pub app_version: String,
+    //"<Kind>/<name>" -> its current pods.
+    pub workloads: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct OwnershipReport {
+    pub releases: Vec<ReleaseOwnership>,
+    //"<namespace>/<pod>" -> "<release> (<chart>)" of the failing pods.
+    pub failing_pods: BTreeMap<String, String>,
+    //"<namespace>/<pod>" matching no release.
+    pub unowned_pods: Vec<String>,
+}
+
+fn label<'a>(pod: &'a Pod, key: &str) -> Option<&'a str> {
+    pod.metadata
+        .labels
+        .as_ref()
+        .and_then(|l| l.get(key))
+        .map(|v| v.as_str())
+}
+
+//the release of the namespace named by the instance label, helm.sh/chart only tells apart
+//releases of the same name when the label is there (a subchart pod carries its own chart).
+pub fn owning_release<'a>(pod: &Pod, namespace: &str, releases: &'a [Helm]) -> Option<&'a Helm> {
+    let instance = label(pod, INSTANCE_LABEL)?;
+    let candidates = releases
+        .iter()
+        .filter(|h| h.name == instance && h.namespace == namespace)
+        .collect::<Vec<&Helm>>();
+    match label(pod, CHART_LABEL) {
+        Some(chart) if candidates.len() > 1 => candidates
+            .iter()
+            .find(|h| h.chart == chart)
+            .or(candidates.first())
+            .copied(),
+        _ => candidates.first().copied(),
+    }
+}
+
+//"<Kind>/<name>" of the controller of the pod, a ReplicaSet is reported as its Deployment.
+pub fn workload(pod: &Pod) -> Option<String> {
+    let owner = pod
+        .metadata
+        .owner_references
+        .as_ref()?
+        .iter()
+        .find(|o| o.controller == Some(true))?;
+    if owner.kind == "ReplicaSet" {
+        if let Some(hash) = label(pod, "pod-template-hash") {
+            if let Some(deployment) = owner.name.strip_suffix(&format!("-{}", hash)) {
+                return Some(format!("Deployment/{}", deployment));
+            }
+        }
+    }
+    Some(format!("{}/{}", owner.kind, owner.name))
+}
+
+pub fn build_ownership<A>(pods: &[PodInfo<A>], releases: &[Helm]) -> OwnershipReport {
+    let mut owned: BTreeMap<(String, String), ReleaseOwnership> = releases
+        .iter()
+        .map(|h| {
+            (
+                (h.namespace.clone(), h.name.clone()),
+                ReleaseOwnership {
+                    release: h.name.clone(),
+                    namespace: h.namespace.clone(),
+                    chart: h.chart.clone(),
+                    app_version: h.app_version.clone(),
+                    workloads: BTreeMap::new(),
+                },
+            )
+        })
+        .collect();
+    let mut report = OwnershipReport::default();
+    for p in pods {
+        let pod_name = format!("{}/{}", p.namespace, p.name);
+        let Some(h) = owning_release(&p.pod, &p.namespace, releases) else {
+            report.unowned_pods.push(pod_name);
+            continue;
+        };
+        if preset::pod_failing(&p.pod) {
+            report
+                .failing_pods
+                .insert(pod_name, format!("{} ({})", h.name, h.chart));
+        }
+        if let Some(o) = owned.get_mut(&(h.namespace.clone(), h.name.clone())) {
+            o.workloads
+                .entry(workload(&p.pod).unwrap_or_else(|| "Pod".to_string()))
+                .or_default()
+                .push(p.name.clone());
+        }
+    }
+    report.releases = owned.into_values().collect();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, pod_info};
+
+    fn cluster() -> (Vec<PodInfo<()>>, Vec<Helm>) {
+        let read = |name: &str| {
+            std::fs::read_to_string(fixture(&format!("release_ownership/{}", name))).unwrap()
+        };
+        let pods: Vec<serde_json::Value> = serde_json::from_str(&read("pods.json")).unwrap();
+        let releases = serde_json::from_str(&read("releases.json")).unwrap();
+        (pods.into_iter().map(pod_info).collect(), releases)
+    }
+
+    fn release<'a>(
+        report: &'a OwnershipReport,
+        namespace: &str,
+        name: &str,
+    ) -> &'a ReleaseOwnership {
+        report
+            .releases
+            .iter()
+            .find(|r| r.namespace == namespace && r.release == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn pods_grouped_by_release_and_workload() {
+        let (pods, releases) = cluster();
+        let report = build_ownership(&pods, &releases);
+        assert_eq!(report.releases.len(), 4);
+        let web = release(&report, "web", "web");
+        assert_eq!(web.chart, "web-1.2.0");
+        assert_eq!(
+            web.workloads,
+            BTreeMap::from([
+                (
+                    "Deployment/web".to_string(),
+                    vec![
+                        "web-7d9f6c-x2k4p".to_string(),
+                        "web-7d9f6c-q8m2z".to_string()
+                    ]
+                ),
+                //a bare pod of the release.
+                ("Pod".to_string(), vec!["web-debug".to_string()]),
+            ])
+        );
+        let kafka = release(&report, "data", "kafka");
+        assert_eq!(
+            kafka.workloads.keys().collect::<Vec<_>>(),
+            vec!["Job/kafka-topics-init", "StatefulSet/kafka"]
+        );
+        //same release name, the namespace tells them apart.
+        assert_eq!(
+            release(&report, "staging", "kafka").workloads,
+            BTreeMap::from([("StatefulSet/kafka".to_string(), vec!["kafka-0".to_string()])])
+        );
+        //a release without pods is still listed.
+        assert!(release(&report, "web", "idle").workloads.is_empty());
+    }
+
+    #[test]
+    fn pods_matching_no_release_are_unowned() {
+        let (pods, releases) = cluster();
+        let report = build_ownership(&pods, &releases);
+        //no instance label, a release that is not installed, a release of another namespace.
+        assert_eq!(
+            report.unowned_pods,
+            vec!["web/toolbox", "data/legacy-5c8d-abcde", "web/kafka-1"]
+        );
+        //failing but unowned, so in no release.
+        assert!(!report.failing_pods.contains_key("data/legacy-5c8d-abcde"));
+    }
+
+    #[test]
+    fn failing_pods_name_their_release() {
+        let (pods, releases) = cluster();
+        let report = build_ownership(&pods, &releases);
+        assert_eq!(
+            report.failing_pods,
+            BTreeMap::from([
+                (
+                    "web/web-7d9f6c-q8m2z".to_string(),
+                    "web (web-1.2.0)".to_string()
+                ),
+                (
+                    "staging/kafka-0".to_string(),
+                    "kafka (kafka-2.9.0)".to_string()
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn chart_label_picks_among_releases_of_the_same_name() {
+        let (pods, mut releases) = cluster();
+        let mut subchart = releases[1].clone();
+        subchart.chart = "kafka-exporter-0.5.0".to_string();
+        releases.insert(1, subchart);
+        let kafka = pods
+            .iter()
+            .find(|p| p.namespace == "data" && p.name == "kafka-0")
+            .unwrap();
+        let owner = owning_release(&kafka.pod, "data", &releases).unwrap();
+        assert_eq!(owner.chart, "kafka-3.0.0");
+        //without the chart label the first one is taken.
+        let job = pods
+            .iter()
+            .find(|p| p.name == "kafka-topics-init-8xv2d")
+            .unwrap();
+        let owner = owning_release(&job.pod, "data", &releases).unwrap();
+        assert_eq!(owner.chart, "kafka-exporter-0.5.0");
+    }
+
+    #[test]
+    fn replica_set_reported_as_its_deployment() {
+        let (pods, _) = cluster();
+        let workload_of = |name: &str| workload(&pods.iter().find(|p| p.name == name).unwrap().pod);
+        assert_eq!(
+            workload_of("legacy-5c8d-abcde").as_deref(),
+            Some("Deployment/legacy")
+        );
+        assert_eq!(
+            workload_of("kafka-topics-init-8xv2d").as_deref(),
+            Some("Job/kafka-topics-init")
+        );
+        assert_eq!(workload_of("toolbox"), None);
+    }
+}
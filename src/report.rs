@@ -0,0 +1,352 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use schemars::{schema_for, JsonSchema};
+use serde_derive::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+
+pub use crate::{
+    context::{CoverageOutcome, FileStatus, ManifestEntry},
+    findings::Finding,
+    log_queue::LogPriority,
+};
+use crate::{
+    namespaces::EmptyNamespace, secrets_allowlist::SecretAccess, self_usage::ToolUsage,
+    sizing::ObjectCounts,
+};
+
+pub const COLLECTION_INFO_FILE: &str = "collection_info.json";
+//"<major>.<minor>" of manifest.json, collection_info.json and findings.json: a new optional field bumps
+//the minor, a removed, renamed or retyped field bumps the major.
+pub const SCHEMA_VERSION: &str = "1.7";
+pub const SCHEMA_DOCUMENTS: [&str; 3] = ["manifest", "collection_info", "findings"];
+
+//manifest.json, the outputs by path relative to the collection folder.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Manifest {
+    pub schema_version: String,
+    pub files: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn new(files: BTreeMap<String, ManifestEntry>) -> Self {
+        Manifest {
+            schema_version: SCHEMA_VERSION.to_string(),
+            files,
+        }
+    }
+
+    //archives before the schema version hold the bare map.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        serde_json::from_slice(data).ok().or_else(|| {
+            serde_json::from_slice::<BTreeMap<String, ManifestEntry>>(data)
+                .ok()
+                .map(|files| Manifest {
+                    schema_version: "0".to_string(),
+                    files,
+                })
+        })
+    }
+}
+
+//findings.json.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FindingsDocument {
+    pub schema_version: String,
+    pub findings: Vec<Finding>,
+}
+
+//JSON Schema of one of SCHEMA_DOCUMENTS.
+pub fn schema(document: &str) -> Result<String> {
+    let schema = match document {
+        "manifest" => schema_for!(Manifest),
+        "collection_info" => schema_for!(CollectionInfo),
+        "findings" => schema_for!(FindingsDocument),
+        _ => {
+            return Err(anyhow!(
+                "Unknown document {}, expected one of {}.",
+                document,
+                SCHEMA_DOCUMENTS.join(", ")
+            ))
+        }
+    };
+    Ok(serde_json::to_string_pretty(&schema)?)
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PhaseResult {
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RunClassification {
+    #[default]
+    Success,
+    //the archive was created but some collectors failed.
+    Partial,
+    Failed,
+}
+
+impl RunClassification {
+    pub fn from_phases(phases: &BTreeMap<String, PhaseResult>) -> Self {
+        if phases.values().any(|p| p.failed > 0) {
+            RunClassification::Partial
+        } else {
+            RunClassification::Success
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RunClassification::Success => 0,
+            RunClassification::Failed => 1,
+            RunClassification::Partial => 2,
+        }
+    }
+}
+
+//written as collection_info.json inside the archive and printed by --summary-format json,
+//the archive fields are only known once the archive has been built.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CollectionInfo {
+    //SCHEMA_VERSION, empty in archives before it.
+    #[serde(default)]
+    pub schema_version: String,
+    pub tool_version: String,
+    pub context_name: String,
+    pub namespaces: Vec<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub duration_seconds: Option<f64>,
+    pub anonymized: bool,
+    pub incremental: bool,
+    pub insecure_skip_tls_verify: bool,
+    pub phases: BTreeMap<String, PhaseResult>,
+    pub classification: RunClassification,
+    pub exit_code: i32,
+    pub archive_path: Option<String>,
+    pub archive_size: Option<u64>,
+    pub archive_sha256: Option<String>,
+    //"<namespace>/<pod>" collected whatever the filters, with the reason.
+    #[serde(default)]
+    pub forced_pods: BTreeMap<String, String>,
+    //pods and events per namespace counted before the collection.
+    #[serde(default)]
+    pub object_counts: Option<ObjectCounts>,
+    #[serde(default)]
+    pub phase_durations_seconds: BTreeMap<String, f64>,
+    //outputs listed as ok in manifest.json and the size of the collection before compression.
+    #[serde(default)]
+    pub collected_files: usize,
+    #[serde(default)]
+    pub collected_bytes: u64,
+    //--read-only: no exec, port-forward or debug pod, hence no app diagnostics.
+    #[serde(default)]
+    pub read_only: bool,
+    //the secrets of secrets_allowlist whose data is in the archive, who dumped them and when.
+    #[serde(default)]
+    pub secrets_dumped: Vec<SecretAccess>,
+    //every collector of the configuration by name, with what it collected or why it did not.
+    #[serde(default)]
+    pub coverage: BTreeMap<String, CoverageOutcome>,
+    //configured namespaces without any pod, missing ones with their close matches.
+    #[serde(default)]
+    pub empty_namespaces: Vec<EmptyNamespace>,
+    //api requests, exec sessions, subprocesses, bytes downloaded and peak memory of the tool.
+    #[serde(default)]
+    pub tool_usage: Option<ToolUsage>,
+}
+
+impl CollectionInfo {
+    pub fn finish(&mut self, phases: BTreeMap<String, PhaseResult>) {
+        let now = Utc::now();
+        self.finished_at = Some(now);
+        self.duration_seconds = Some((now - self.started_at).num_milliseconds() as f64 / 1000.0);
+        self.classification = RunClassification::from_phases(&phases);
+        self.exit_code = self.classification.exit_code();
+        self.phases = phases;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+    use serde_json::Value;
+
+    //"<document>-<version>.json" of every released SCHEMA_VERSION, oldest first.
+    fn snapshots(document: &str) -> Vec<((u32, u32), Value)> {
+        let mut out = std::fs::read_dir(fixture("schemas"))
+            .unwrap()
+            .filter_map(|e| {
+                let name = e.unwrap().file_name().to_string_lossy().to_string();
+                let version = name
+                    .strip_prefix(&format!("{}-", document))?
+                    .strip_suffix(".json")?;
+                let (major, minor) = version.split_once('.')?;
+                let json = std::fs::read_to_string(fixture(&format!("schemas/{}", name))).unwrap();
+                Some((
+                    (major.parse().unwrap(), minor.parse().unwrap()),
+                    serde_json::from_str(&json).unwrap(),
+                ))
+            })
+            .collect::<Vec<_>>();
+        out.sort_by_key(|(v, _)| *v);
+        out
+    }
+
+    fn required(schema: &Value) -> Vec<&str> {
+        schema["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|r| r.as_str())
+            .collect()
+    }
+
+    //what breaks a reader of old in new: a removed type, field or enum value, a retyped field,
+    //a new required field.
+    fn breaking_changes(old: &Value, new: &Value) -> Vec<String> {
+        let mut types = vec![("(root)".to_string(), old, new)];
+        for (name, schema) in old["definitions"].as_object().into_iter().flatten() {
+            types.push((name.clone(), schema, &new["definitions"][name]));
+        }
+        let mut out = vec![];
+        for (name, old, new) in types {
+            if new.is_null() {
+                out.push(format!("{} removed", name));
+                continue;
+            }
+            if old["type"] != new["type"] {
+                out.push(format!("{} retyped", name));
+            }
+            for (field, schema) in old["properties"].as_object().into_iter().flatten() {
+                match new["properties"].get(field) {
+                    None => out.push(format!("{}.{} removed", name, field)),
+                    Some(s) if s != schema => out.push(format!("{}.{} retyped", name, field)),
+                    _ => {}
+                }
+            }
+            let old_required = required(old);
+            for field in required(new) {
+                if !old_required.contains(&field) {
+                    out.push(format!("{}.{} required", name, field));
+                }
+            }
+            let new_values = new["enum"].as_array().cloned().unwrap_or_default();
+            for value in old["enum"].as_array().into_iter().flatten() {
+                if !new_values.contains(value) {
+                    out.push(format!("{} value {} removed", name, value));
+                }
+            }
+        }
+        out
+    }
+
+    fn version() -> (u32, u32) {
+        let (major, minor) = SCHEMA_VERSION.split_once('.').unwrap();
+        (major.parse().unwrap(), minor.parse().unwrap())
+    }
+
+    #[test]
+    fn generated_schemas_match_the_snapshot_of_the_version() {
+        for document in SCHEMA_DOCUMENTS {
+            let (latest, snapshot) = snapshots(document).pop().unwrap();
+            assert_eq!(
+                latest,
+                version(),
+                "no snapshot of {} {}",
+                document,
+                SCHEMA_VERSION
+            );
+            let generated: Value = serde_json::from_str(&schema(document).unwrap()).unwrap();
+            assert!(
+                generated == snapshot,
+                "the schema of {} changed: bump SCHEMA_VERSION and add tests/fixtures/schemas/{}-<version>.json",
+                document,
+                document
+            );
+        }
+    }
+
+    #[test]
+    fn minor_versions_only_add_optional_fields() {
+        for document in SCHEMA_DOCUMENTS {
+            for pair in snapshots(document).windows(2) {
+                let ((old_version, old), (new_version, new)) = (&pair[0], &pair[1]);
+                if old_version.0 != new_version.0 {
+                    continue;
+                }
+                assert_eq!(
+                    breaking_changes(old, new),
+                    Vec::<String>::new(),
+                    "{} {:?} -> {:?} needs a major bump",
+                    document,
+                    old_version,
+                    new_version
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn breaking_changes_of_a_schema() {
+        let old = serde_json::json!({
+            "type": "object",
+            "required": ["files"],
+            "properties": {"files": {"type": "object"}, "note": {"type": "string"}},
+            "definitions": {
+                "FileStatus": {"type": "string", "enum": ["ok", "failed"]},
+                "Old": {"type": "object"}
+            }
+        });
+        let mut additive = old.clone();
+        additive["properties"]["size"] = serde_json::json!({"type": "integer"});
+        additive["definitions"]["FileStatus"]["enum"] =
+            serde_json::json!(["ok", "failed", "skipped"]);
+        assert!(breaking_changes(&old, &additive).is_empty());
+
+        let mut breaking = additive.clone();
+        breaking["required"] = serde_json::json!(["files", "size"]);
+        breaking["properties"]["note"] = serde_json::json!({"type": "integer"});
+        breaking["properties"]
+            .as_object_mut()
+            .unwrap()
+            .remove("files");
+        breaking["definitions"]["FileStatus"]["enum"] = serde_json::json!(["ok"]);
+        breaking["definitions"]
+            .as_object_mut()
+            .unwrap()
+            .remove("Old");
+        assert_eq!(
+            breaking_changes(&old, &breaking),
+            vec![
+                "(root).files removed",
+                "(root).note retyped",
+                "(root).size required",
+                "FileStatus value \"failed\" removed",
+                "Old removed",
+            ]
+        );
+    }
+
+    #[test]
+    fn manifest_before_the_schema_version() {
+        let bare = br#"{"pods/a.log": {"status": "ok"}}"#;
+        let manifest = Manifest::parse(bare).unwrap();
+        assert_eq!(manifest.schema_version, "0");
+        assert_eq!(manifest.files["pods/a.log"].status, FileStatus::Ok);
+        let current = serde_json::to_vec(&Manifest::new(manifest.files.clone())).unwrap();
+        assert_eq!(
+            Manifest::parse(&current).unwrap().schema_version,
+            SCHEMA_VERSION
+        );
+        assert!(Manifest::parse(b"[]").is_none());
+        assert_eq!(
+            schema("other").unwrap_err().to_string(),
+            "Unknown document other, expected one of manifest, collection_info, findings."
+        );
+    }
+}
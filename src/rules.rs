@@ -0,0 +1,317 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::Deserialize;
+
+use std::{
+    fmt::Write,
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use crate::{context::RunContext, list_files};
+
+pub const DEFAULT_RULES_FILE: &str = "rules.yaml";
+pub const KNOWN_ISSUES_REPORT_FILE: &str = "known_issues_report.md";
+
+const MAX_EVIDENCE_PER_FILE: usize = 5;
+const MAX_EXCERPT_LEN: usize = 200;
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct Rule {
+    pub id: String,
+    pub description: String,
+    //glob relative to the collection folder, e.g. "apps/elastic_search_*.json".
+    pub target: String,
+    #[serde(default)]
+    pub regex: Option<String>,
+    #[serde(default)]
+    pub jsonpath: Option<String>,
+    #[serde(default)]
+    pub remediation: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Evidence {
+    pub file: String,
+    pub excerpt: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct RuleResult {
+    pub rule: Rule,
+    pub files_checked: usize,
+    pub evidence: Vec<Evidence>,
+    pub note: Option<String>,
+}
+
+impl RuleResult {
+    pub fn matched(&self) -> bool {
+        !self.evidence.is_empty()
+    }
+}
+
+//rules file shipped next to the config file when none is configured.
+pub fn default_rules_path(config_file_path: &Path) -> PathBuf {
+    config_file_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(DEFAULT_RULES_FILE)
+}
+
+pub fn read_rules<P: AsRef<Path>>(path: P) -> Result<Vec<Rule>> {
+    let content = fs::read_to_string(&path)?;
+    let rules: Vec<Rule> = serde_yaml::from_str(&content)?;
+    for r in &rules {
+        if r.regex.is_none() && r.jsonpath.is_none() {
+            return Err(anyhow!(
+                "Rule {} needs a regex or a jsonpath condition.",
+                r.id
+            ));
+        }
+        if let Some(re) = &r.regex {
+            Regex::new(re).map_err(|e| anyhow!("Rule {} has an invalid regex: {}", r.id, e))?;
+        }
+        glob::Pattern::new(&r.target)
+            .map_err(|e| anyhow!("Rule {} has an invalid target: {}", r.id, e))?;
+    }
+    Ok(rules)
+}
+
+fn excerpt(text: &str) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(MAX_EXCERPT_LEN) {
+        Some((i, _)) => format!("{}...", &text[..i]),
+        None => text.to_string(),
+    }
+}
+
+fn regex_evidence(path: &Path, re: &Regex) -> Result<Vec<String>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut evidence = vec![];
+    let mut buf = vec![];
+    let mut line_number = 0;
+    while evidence.len() < MAX_EVIDENCE_PER_FILE {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        line_number += 1;
+        let line = String::from_utf8_lossy(&buf);
+        if re.is_match(&line) {
+            evidence.push(format!("{}: {}", line_number, excerpt(&line)));
+        }
+    }
+    Ok(evidence)
+}
+
+fn jsonpath_evidence(path: &Path, jsonpath: &str, re: Option<&Regex>) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path)?;
+    let json: serde_json::Value = serde_json::from_str(&content)?;
+    let selected = jsonpath_lib::select(&json, jsonpath)
+        .map_err(|e| anyhow!("invalid jsonpath {}: {:?}", jsonpath, e))?;
+    Ok(selected
+        .into_iter()
+        .filter(|v| !v.is_null())
+        .map(|v| match v {
+            serde_json::Value::String(s) => s.clone(),
+            v => v.to_string(),
+        })
+        .filter(|v| re.is_none_or(|re| re.is_match(v)))
+        .take(MAX_EVIDENCE_PER_FILE)
+        .map(|v| excerpt(&v))
+        .collect())
+}
+
+//evaluate a single rule over the collected files below `dir`.
+pub fn evaluate_rule(rule: &Rule, dir: &Path) -> Result<RuleResult> {
+    let target = glob::Pattern::new(&rule.target)?;
+    let re = rule.regex.as_deref().map(Regex::new).transpose()?;
+    let mut result = RuleResult {
+        rule: rule.clone(),
+        ..Default::default()
+    };
+
+    let files = list_files(dir)?.into_iter().filter(|f| {
+        f.strip_prefix(dir)
+            .map(|r| target.matches_path(r))
+            .unwrap_or(false)
+    });
+
+    for f in files {
+        result.files_checked += 1;
+        let found = match (&rule.jsonpath, &re) {
+            (Some(jp), re) => jsonpath_evidence(&f, jp, re.as_ref()),
+            (None, Some(re)) => regex_evidence(&f, re),
+            (None, None) => Ok(vec![]),
+        };
+        let relative = f.strip_prefix(dir).unwrap_or(&f).display().to_string();
+        match found {
+            Ok(found) => found.into_iter().for_each(|excerpt| {
+                result.evidence.push(Evidence {
+                    file: relative.clone(),
+                    excerpt,
+                })
+            }),
+            Err(e) => result.note = Some(format!("{}: {}", relative, e)),
+        }
+    }
+
+    if result.files_checked == 0 {
+        result.note = Some(format!("No collected file matches {}.", rule.target));
+    }
+    Ok(result)
+}
+
+pub fn evaluate_rules(rules: &[Rule], dir: &Path) -> Vec<RuleResult> {
+    rules
+        .iter()
+        .map(|r| {
+            evaluate_rule(r, dir).unwrap_or_else(|e| RuleResult {
+                rule: r.clone(),
+                note: Some(e.to_string()),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+//written through ctx: atomically, and listed in manifest.json.
+pub fn write_known_issues_report(
+    ctx: &RunContext,
+    folder: &str,
+    results: &[RuleResult],
+) -> Result<()> {
+    let mut report = String::new();
+    let matched = results.iter().filter(|r| r.matched()).collect::<Vec<_>>();
+
+    writeln!(report, "# Known issues report\n")?;
+    writeln!(
+        report,
+        "{} of {} rules matched.\n",
+        matched.len(),
+        results.len()
+    )?;
+    for r in &matched {
+        writeln!(report, "## {}: {}\n", r.rule.id, r.rule.description)?;
+        if !r.rule.remediation.is_empty() {
+            writeln!(report, "**Remediation:** {}\n", r.rule.remediation)?;
+        }
+        writeln!(report, "Evidence:\n")?;
+        for e in &r.evidence {
+            writeln!(report, "- `{}`: `{}`", e.file, e.excerpt.replace('`', "'"))?;
+        }
+        writeln!(report)?;
+    }
+
+    let not_matched = results.iter().filter(|r| !r.matched()).collect::<Vec<_>>();
+    if !not_matched.is_empty() {
+        writeln!(report, "## Rules not matched\n")?;
+        for r in not_matched {
+            match &r.note {
+                Some(n) => writeln!(report, "- {} ({})", r.rule.id, n)?,
+                None => writeln!(report, "- {}", r.rule.id)?,
+            }
+        }
+    }
+    let er = anyhow!("Empty {}.", KNOWN_ISSUES_REPORT_FILE);
+    ctx.write_file(folder, report.as_bytes(), KNOWN_ISSUES_REPORT_FILE, er)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, TempDir};
+
+    fn results() -> Vec<RuleResult> {
+        let rules = read_rules(fixture("rules/rules.yaml")).unwrap();
+        evaluate_rules(&rules, &fixture("rules/collection"))
+    }
+
+    fn result<'a>(results: &'a [RuleResult], id: &str) -> &'a RuleResult {
+        results.iter().find(|r| r.rule.id == id).unwrap()
+    }
+
+    #[test]
+    fn regex_rule_matches_the_target_files_only() {
+        let results = results();
+        let oom = result(&results, "KAFKA-OOM");
+        assert!(oom.matched());
+        assert_eq!(oom.files_checked, 2);
+        assert_eq!(
+            oom.evidence,
+            vec![Evidence {
+                file: "pods/kafka_kafka-0_kafka.log".to_string(),
+                excerpt: "2: 2024-03-01 10:00:08 ERROR java.lang.OutOfMemoryError: Java heap space"
+                    .to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn jsonpath_rule_filters_the_selected_values_with_the_regex() {
+        let results = results();
+        assert_eq!(result(&results, "ES-RED").evidence[0].excerpt, "red");
+        assert_eq!(result(&results, "ES-UNASSIGNED").evidence[0].excerpt, "12");
+    }
+
+    #[test]
+    fn missing_targets_do_not_match_and_are_noted() {
+        let results = results();
+        for id in ["HDFS-SAFEMODE", "ZK-SESSION"] {
+            let r = result(&results, id);
+            assert!(!r.matched());
+            assert_eq!(r.files_checked, 0);
+            assert!(r
+                .note
+                .as_deref()
+                .unwrap()
+                .starts_with("No collected file matches"));
+        }
+    }
+
+    #[test]
+    fn unparsable_target_is_noted_not_fatal() {
+        let rule = Rule {
+            id: "BROKEN".to_string(),
+            target: "apps/elastic_search_*.json".to_string(),
+            jsonpath: Some("$.status".to_string()),
+            ..Default::default()
+        };
+        let r = evaluate_rule(&rule, &fixture("rules/collection")).unwrap();
+        assert_eq!(r.files_checked, 2);
+        assert_eq!(r.evidence.len(), 1);
+        assert!(r
+            .note
+            .as_deref()
+            .unwrap()
+            .starts_with("apps/elastic_search_broken.json"));
+    }
+
+    #[test]
+    fn rules_without_condition_or_with_invalid_regex_are_rejected() {
+        let dir = TempDir::new();
+        let path = dir.write(
+            "rules.yaml",
+            b"- id: A\n  description: a\n  target: '*.log'\n",
+        );
+        assert!(read_rules(&path).is_err());
+        let path = dir.write(
+            "rules.yaml",
+            b"- id: A\n  description: a\n  target: '*.log'\n  regex: '('\n",
+        );
+        assert!(read_rules(&path).is_err());
+    }
+
+    #[test]
+    fn report_lists_matched_rules_with_evidence_then_the_others() {
+        let dir = TempDir::new();
+        let ctx = RunContext::new(vec![]);
+        write_known_issues_report(&ctx, &dir.folder(), &results()).unwrap();
+        let report = dir.read(KNOWN_ISSUES_REPORT_FILE);
+        assert!(report.contains("3 of 5 rules matched."));
+        assert!(report.contains("## KAFKA-OOM: Kafka broker ran out of heap"));
+        assert!(report.contains("**Remediation:** Raise KAFKA_HEAP_OPTS."));
+        assert!(report.contains("- HDFS-SAFEMODE (No collected file matches"));
+    }
+}
@@ -0,0 +1,2351 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use flate2::Compression;
+use indicatif::{ProgressBar, ProgressStyle};
+use k8s_openapi::api::scheduling::v1::PriorityClass;
+use kube::{api::ListParams, Api, Client, ResourceExt};
+use serde_derive::{Deserialize, Serialize};
+use simplelog::{__private::log::warn, info};
+use tokio_util::sync::CancellationToken;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    access::{HttpGet, KubeAccess, PodAccess},
+    anonymize::Anonymizer,
+    api_versions, api_warnings,
+    api_warnings::ApiWarnings,
+    apiserver, apply_retention, archive, can_exec, check_not_nested, clock_skew, cluster_info, cni,
+    collector::Collector,
+    component_command::{self, ComponentCommand, ComponentTarget},
+    components,
+    context::{self, CoverageOutcome, RunContext},
+    copy_from_pod, credentials, dir_size, disk_usage, elastic, events,
+    exec::{self, ExecRecorder},
+    external::{self, run_external},
+    filters::ContainerFilter,
+    findings, get_pod_list, health, helm, image_pull,
+    incremental::{self, IncrementalState},
+    jvm_gc, kafka, kubectl_command, kubernetes_client, label_values, leases, limits, log_files,
+    log_queue,
+    logging::{self, LogScope, WarningAggregator},
+    manifests, manual_changes, move_archive, namespaces, node_debug, node_pressure, openshift,
+    output_directory, placement, plugins, pod_selection, previous_logs, prometheus, proxy,
+    pushgateway, qos, reference_check, release_ownership, remove_tmp_files,
+    report::{self, CollectionInfo, RunClassification, COLLECTION_INFO_FILE},
+    rules, scheduling, secrets_allowlist,
+    self_usage::{self, UsageCounters},
+    sha256_file, size_breakdown, sizing, spark, spawn_auth_check, storage_csi, timeline, verify,
+    work_directory, write_atomic, ConfigFile, PodInfo, SubprocessKubeconfig,
+    AUTH_CHECK_INTERVAL_SECONDS,
+};
+
+//the run outcome, same content as the collection_info.json stored in the archive.
+pub type CollectionReport = CollectionInfo;
+
+pub type ProgressCallback = Arc<dyn Fn(&str) + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub struct RunOptions {
+    pub kube_config_path: String,
+    //used instead of building a client from kube_config_path when set, kube_config_path can then stay empty.
+    pub client: Option<Client>,
+    pub anonymize: bool,
+    pub incremental: bool,
+    //no confirmation when the object counts are above the limits.
+    pub assume_yes: bool,
+    //tool log file added to the archive.
+    pub log_file: Option<String>,
+    pub cancel: CancellationToken,
+    //called with the name of each phase when it starts.
+    pub progress: Option<ProgressCallback>,
+    //the aggregator of the terminal logger, given to the RunContext of the run.
+    pub log_warnings: Arc<WarningAggregator>,
+}
+
+impl RunOptions {
+    fn phase(&self, ctx: &RunContext, name: &str) -> Result<()> {
+        if self.cancel.is_cancelled() {
+            //the collection folder stays on disk, without the half files of the interrupted writes.
+            if let Some(root) = ctx.folders.get(5) {
+                let _ = remove_tmp_files(Path::new(root));
+            }
+            return Err(anyhow!("Collection cancelled before the {} phase.", name));
+        }
+        logging::set_phase(name, &ctx.log_warnings);
+        ctx.start_phase(name);
+        if let Some(p) = &self.progress {
+            p(name)
+        }
+        Ok(())
+    }
+}
+
+pub fn folder_creation(c: ConfigFile) -> Result<Vec<String>> {
+    let date = Utc::now().format("%Y%m%d%H%M%S");
+    let file_name_gz = format!("info_{}_{}.tar.gz", c.context_name, date);
+    let folder_to_save = work_directory(&c);
+
+    let folder_vec = ["pods", "infra", "helm", "apps"];
+
+    let mut folder_vec = folder_vec
+        .iter()
+        .map(|f| format!("{}/info_{}_{}/{}", folder_to_save, c.context_name, date, f))
+        .collect::<Vec<String>>();
+
+    let folder_src_tar = format!("{}/info_{}_{}", folder_to_save, c.context_name, date);
+    folder_vec.push(file_name_gz);
+    folder_vec.push(folder_src_tar);
+    folder_vec.push(output_directory(&c));
+    folder_vec.push(folder_to_save);
+    Ok(folder_vec)
+}
+
+//the archive, the output and work directories and the tool log outside of the collection root.
+pub fn check_layout(folders: &[String], log_file: Option<&str>) -> Result<()> {
+    let archive = format!("{}/{}", folders[7], folders[4]);
+    let mut outputs = vec![
+        ("archive", Path::new(&archive)),
+        ("output directory", Path::new(&folders[6])),
+        ("work directory", Path::new(&folders[7])),
+    ];
+    if let Some(l) = log_file {
+        outputs.push(("tool log", Path::new(l)));
+    }
+    check_not_nested(Path::new(&folders[5]), &outputs)
+}
+
+pub type LsHelm = Vec<Helm>;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Helm {
+    pub name: String,
+    pub namespace: String,
+    pub revision: String,
+    pub updated: String,
+    pub status: String,
+    pub chart: String,
+    #[serde(rename = "app_version")]
+    pub app_version: String,
+}
+
+//the whole collection pipeline, from the pod listing to the archive and its retention.
+pub async fn run_collection(
+    config_file: ConfigFile,
+    options: RunOptions,
+) -> Result<CollectionReport> {
+    let kube_config_path = &options.kube_config_path;
+    let container_filter = ContainerFilter::from_config(&config_file)?;
+
+    let anonymize = options.anonymize;
+    let incremental_mode = options.incremental;
+
+    //the ones of the client setup have no phase.
+    logging::set_phase("", &options.log_warnings);
+    //the requests and warnings of a client given in the options are not counted.
+    let usage = Arc::new(UsageCounters::default());
+    let warnings = Arc::new(ApiWarnings::default());
+    let execs = Arc::new(ExecRecorder::default());
+    let memory_sampler =
+        self_usage::spawn_memory_sampler(usage.clone(), self_usage::MEMORY_SAMPLE_INTERVAL);
+    let client = match &options.client {
+        Some(c) => c.clone(),
+        None => {
+            kubernetes_client(
+                kube_config_path,
+                config_file.clone(),
+                usage.clone(),
+                warnings.clone(),
+            )
+            .await?
+        }
+    };
+    let auth_check = spawn_auth_check(
+        client.clone(),
+        Duration::from_secs(AUTH_CHECK_INTERVAL_SECONDS),
+    );
+
+    //the pods, nodes, logs and execs of the run, behind the collector once the context is set up.
+    let access = KubeAccess::namespaced(client.clone(), client.default_namespace())
+        .with_execs(execs.clone());
+
+    info!("<green>Starting Log collection...</>");
+    if options.client.is_none() {
+        info!(
+            "The following kube config path will be use: {}",
+            &kube_config_path
+        );
+    } else if kube_config_path.is_empty() && !config_file.in_cluster && !config_file.direct_auth() {
+        warn!("No kube config path with the given client, the kubectl, helm and plugin outputs will fail.");
+    }
+
+    let folders = folder_creation(config_file.clone()).unwrap();
+    check_layout(&folders, options.log_file.as_deref())?;
+
+    let nodes = access.list_nodes().await?;
+    let nodes_health = health::nodes_not_ready(&nodes);
+
+    let nodes_list = nodes.iter().map(|n| n.name_any()).collect::<Vec<String>>();
+
+    let mut ctx = RunContext::new(folders.clone())
+        .with_compression(config_file.compress_outputs_over_mb * 1024 * 1024)
+        .with_line_endings(config_file.normalize_line_endings)
+        .with_usage(usage)
+        .with_api_warnings(warnings)
+        .with_execs(execs)
+        .with_log_warnings(options.log_warnings.clone())
+        .with_kubeconfig(SubprocessKubeconfig::for_run(
+            &config_file,
+            kube_config_path,
+            options.client.is_some(),
+        )?)
+        .with_credentials(credentials::resolve(&client, &config_file.credentials).await);
+    if anonymize {
+        info!("<yellow>Anonymize mode enabled.</>");
+        ctx = ctx.with_anonymizer(Anonymizer::new(
+            &nodes_list,
+            &config_file.anonymize_strings,
+        )?);
+    }
+    ctx.health.lock().unwrap().nodes_not_ready = Some(nodes_health);
+    let ctx = Arc::new(ctx);
+    external::set_concurrency(config_file.external_command_concurrency);
+    let timeout = external::timeout(config_file.external_command_timeout_seconds);
+    let collector =
+        Collector::with_access(client.clone(), access, config_file.clone(), ctx.clone());
+    //events happening while the tool runs, the guard stops the watcher on early returns.
+    let events_cancel = options.cancel.child_token();
+    let _events_guard = events_cancel.clone().drop_guard();
+    let events_watch = events::watch_events(
+        &client,
+        &config_file.context_namespace,
+        events_cancel.clone(),
+    );
+
+    let state_path = incremental::state_path(&folders[6], &config_file.context_name);
+    let previous_state = if incremental_mode {
+        info!(
+            "<yellow>Incremental mode enabled, state file {}.</>",
+            &state_path
+        );
+        Arc::new(incremental::read_state(&state_path)?)
+    } else {
+        Arc::new(IncrementalState::default())
+    };
+    let new_state = Arc::new(Mutex::new(IncrementalState::default()));
+    let run_started = Utc::now();
+    let mut collection_info = CollectionInfo {
+        schema_version: report::SCHEMA_VERSION.to_string(),
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        context_name: config_file.context_name.clone(),
+        namespaces: config_file.context_namespace.clone(),
+        started_at: run_started,
+        anonymized: anonymize,
+        incremental: incremental_mode,
+        insecure_skip_tls_verify: config_file.insecure_skip_tls_verify,
+        read_only: config_file.read_only,
+        ..Default::default()
+    };
+
+    folders.clone()[0..4]
+        .iter()
+        .for_each(|fo| match fs::create_dir_all(fo) {
+            Ok(_) => info!("Directory has been created {}.", fo),
+            Err(e) => {
+                panic!("{}", e)
+            }
+        });
+    info!("Context Name: {}.", &config_file.context_name);
+    info!(
+        "Context NameSpace: {}.",
+        &config_file.context_namespace.join(", ")
+    );
+
+    options.phase(&ctx, "sizing")?;
+    let counts = sizing::count_objects(&client, &config_file.context_namespace).await?;
+    counts
+        .namespaces
+        .iter()
+        .for_each(|(n, c)| info!("Namespace {}: {} pods, {} events.", n, c.pods, c.events));
+    info!(
+        "Total: {} pods, {} events, about {} MiB to collect.",
+        counts.pods(),
+        counts.events(),
+        counts.estimated_bytes() / (1024 * 1024)
+    );
+    let available = sizing::available_bytes(Path::new(&folders[7]), &ctx.usage)
+        .inspect_err(|e| warn!("{}", e))
+        .ok();
+    let reasons = sizing::limits_exceeded(&counts, &config_file.object_count_limits, available);
+    collection_info.object_counts = Some(counts);
+    if !reasons.is_empty() {
+        reasons.iter().for_each(|r| warn!("<yellow>{}.</>", r));
+        if !options.assume_yes && !sizing::confirm("Collect anyway?") {
+            return Err(anyhow!(
+                "Collection stopped: {}. Narrow it with context_namespace, node_names, only_failing_pods or --quick, raise object_count_limits, or confirm with --yes.",
+                reasons.join(", ")
+            ));
+        }
+    }
+
+    //without the exec permission every exec collector would fail one by one.
+    if !config_file.read_only {
+        for ns in &config_file.context_namespace {
+            match can_exec(client.clone(), ns).await {
+                Ok(true) => {}
+                Ok(false) => warn!(
+                    "<yellow>No permission to exec into the pods of namespace {}, the app collectors will fail: run with --read-only (or read_only: true) to skip them.</>",
+                    ns
+                ),
+                Err(e) => warn!("Exec permission check in namespace {}: {}", ns, e),
+            }
+        }
+    }
+
+    options.phase(&ctx, "pods")?;
+    //kubectl args, folder, file name, the timeline ending a description and the log scope.
+    let mut cmdk = vec![];
+    config_file.context_namespace.iter().for_each(|cn| {
+        let file_name = format!("kubernetes_pods_{}.list", cn);
+        cmdk.push((
+            args(&["get", "pod", "-n", cn, "-o", "wide"]),
+            folders[0].clone(),
+            file_name,
+            None,
+            LogScope::namespace(cn),
+        ));
+        let file_name = format!("kubernetes_pods_{}.json", cn);
+        cmdk.push((
+            args(&["get", "pod", "-n", cn, "-o", "json"]),
+            folders[0].clone(),
+            file_name,
+            None,
+            LogScope::namespace(cn),
+        ))
+    });
+
+    //Get list pods.
+
+    let pods_list = collector.list_pods().await?;
+    match namespaces::check_empty_namespaces(&client, &config_file.context_namespace, &pods_list)
+        .await
+    {
+        Ok(empty) => collection_info.empty_namespaces = empty,
+        Err(e) => warn!("Checking the namespaces without pods: {}", e),
+    }
+    {
+        let mut h = ctx.health.lock().unwrap();
+        h.pods_not_running = Some(health::pods_not_running(pods_list.iter().map(|p| &p.pod)));
+        h.containers_restarted_last_hour = Some(health::containers_restarted_since(
+            pods_list.iter().map(|p| &p.pod),
+            Utc::now(),
+        ));
+    }
+
+    //the pods named by Warning events are collected whatever the filters say.
+    let events = collector.list_events().await;
+    let plan = collector.plan_pods(pods_list, &events);
+    let pods_list = &plan.pods;
+    let forced = |p: &PodInfo| plan.forced(p);
+
+    //node mode: the pods of the given nodes only, with the node level picture next to them.
+    let node_mode = !config_file.node_names.is_empty();
+    if node_mode {
+        for n in &config_file.node_names {
+            let folder = ctx.subfolder(&folders[0], n);
+            fs::create_dir_all(&folder)?;
+            cmdk.push((
+                args(&["describe", "node", n]),
+                folder.clone(),
+                format!("{}.description", n),
+                None,
+                LogScope::node(n),
+            ));
+            let raw = format!("/api/v1/nodes/{}/proxy/stats/summary", n);
+            cmdk.push((
+                args(&["get", "--raw", &raw]),
+                folder,
+                format!("{}_stats_summary.json", n),
+                None,
+                LogScope::node(n),
+            ));
+        }
+    }
+    collection_info.forced_pods = plan
+        .forced
+        .iter()
+        .map(|((n, p), reason)| (format!("{}/{}", n, p), reason.clone()))
+        .collect();
+    if !collection_info.forced_pods.is_empty() {
+        info!(
+            "{} pods named by Warning events or spark executors are collected in full.",
+            collection_info.forced_pods.len()
+        );
+    }
+    for app in plan.executors.values() {
+        fs::create_dir_all(
+            ctx.subfolder(&format!("{}/{}", &folders[0], spark::SPARK_FOLDER), app),
+        )?;
+    }
+
+    //which versions, charts and tenants the collected pods run.
+    let before = ctx.output_counts();
+    let labels = label_values::report_labels(&config_file.report_labels);
+    let r = label_values::write_report(&ctx, &folders[0], pods_list, &labels);
+    ctx.record_collected("label_values", before, &r);
+    if let Err(e) = r {
+        warn!("Label values report: {}", e);
+    }
+    let pod_folder = |p: &PodInfo| plan.folder(p, &folders[0], &ctx);
+
+    if config_file.check_references {
+        let before = ctx.output_counts();
+        let r = reference_check::collect_reference_check(&collector, pods_list, &folders[0]).await;
+        ctx.record_collected("reference_check", before, &r);
+        if let Err(e) = r {
+            warn!("Reference check: {}", e);
+            ctx.record_folder(&folders[0], false);
+        }
+    } else {
+        ctx.record_coverage("reference_check", CoverageOutcome::Disabled);
+    }
+
+    //secret data only for secrets_allowlist, audited in collection_info.json.
+    collection_info.secrets_dumped =
+        secrets_allowlist::collect_allowed_secrets(&collector, &folders[0]).await;
+
+    if config_file.export_manifests {
+        for ns in &config_file.context_namespace {
+            if let Err(e) = manifests::export_namespace(
+                client.clone(),
+                ns,
+                &folders[0],
+                config_file.export_manifests_keep_status,
+                &ctx,
+            )
+            .await
+            {
+                warn!("Manifest export of namespace {} failed: {}", ns, e)
+            }
+        }
+    }
+
+    pods_list.iter().for_each(|p| {
+        if let Err(e) = collector.pod_manifest(p, &pod_folder(p)) {
+            warn!("{}", e)
+        }
+        if let Err(e) = collector.last_states(p, &pod_folder(p)) {
+            warn!("{}", e)
+        }
+        let file_name = config_file.file_name_templates.description(p);
+        if let Err(e) = ctx.claim_output(&pod_folder(p), &file_name, &p.namespace, &p.name) {
+            warn!("{}", e);
+            return;
+        }
+        let timeline = timeline::pod_timeline(&p.pod, &events, Utc::now());
+
+        cmdk.push((
+            args(&["describe", "pod", &p.name, "-n", &p.namespace]),
+            pod_folder(p),
+            file_name,
+            Some(timeline),
+            LogScope::pod(&p.namespace, &p.name, ""),
+        ));
+    });
+    let mut fut_handle_kb: Vec<tokio::task::JoinHandle<()>> = vec![];
+    cmdk.into_iter().for_each(|c| {
+        let ctx = ctx.clone();
+        let cmd = kubectl_command(&config_file, &ctx.kubeconfig);
+        let scope = c.4.clone();
+        let task = tokio::task::spawn(logging::scoped(scope, async move {
+            let o = match run_external(cmd, &c.0, timeout, &ctx.usage).await {
+                Ok(o) => o,
+                Err(e) => {
+                    warn!("{}", e);
+                    let attempted = e.command().to_string();
+                    ctx.record_failure(&c.1, &c.2, &attempted, &e.into());
+                    return;
+                }
+            };
+            let er = anyhow!("{} empty response", o.command);
+            //the timeline ends a non empty description.
+            let mut stdout = o.stdout.clone();
+            if let (false, Some(t)) = (stdout.is_empty(), &c.3) {
+                stdout.extend(t.as_bytes());
+            }
+            match ctx.write_file(&c.1, &stdout, &c.2, er) {
+                Ok(_) => info!("File has been created {}/{}", &c.1, &c.2),
+                Err(e) => warn!("{}", e),
+            }
+            o.warn_stderr();
+        }));
+        fut_handle_kb.push(task);
+    });
+
+    for handle in fut_handle_kb {
+        match handle.await {
+            Ok(_) => {}
+            Err(e) => {
+                warn!("{}", e);
+                ctx.record_folder(&folders[0], false);
+            }
+        }
+    }
+    //image pull failures, their logs are empty and the registry error is in the events.
+    let pull_failures = pods_list
+        .iter()
+        .flat_map(image_pull::image_pull_failures)
+        .collect::<Vec<_>>();
+    if !pull_failures.is_empty() {
+        warn!(
+            "{} containers cannot pull their image, see {}.",
+            pull_failures.len(),
+            image_pull::IMAGE_PULL_FAILURES_FILE
+        );
+        let report = image_pull::render_failures(&pull_failures, &events);
+        let er = anyhow!("Empty image pull failures report.");
+        match ctx.write_file(
+            &folders[0],
+            report.as_bytes(),
+            image_pull::IMAGE_PULL_FAILURES_FILE,
+            er,
+        ) {
+            Ok(_) => info!(
+                "File has been created {}/{}",
+                &folders[0],
+                image_pull::IMAGE_PULL_FAILURES_FILE
+            ),
+            Err(e) => warn!("{}", e),
+        }
+    }
+    let pulling = |p: &PodInfo, c: &str| {
+        pull_failures
+            .iter()
+            .any(|f| f.namespace == p.namespace && f.pod == p.name && f.container == c)
+    };
+
+    //current and previous logs in one queue: the previous logs of crashed containers first,
+    //then the current logs of failing pods, then the rest, at most log_concurrency at a time.
+    options.phase(&ctx, "logs")?;
+    let logs_before = ctx.output_counts();
+    let mut log_tasks = vec![];
+    if config_file.current_logs {
+        pods_list.iter().for_each(|pl| {
+            let container = if forced(pl) {
+                pl.containers.clone()
+            } else {
+                container_filter.containers(pl)
+            };
+            for c in container {
+                //never started with this image, previous logs are still fetched.
+                if pulling(pl, &c) {
+                    let key = incremental::state_key(&pl.namespace, &pl.name, &c);
+                    if let Some(st) = previous_state.containers.get(&key) {
+                        new_state.lock().unwrap().containers.insert(key, st.clone());
+                    }
+                    info!(
+                        "Image pull failure, skipping current logs {} on container {}.",
+                        pl.name, c
+                    );
+                    continue;
+                }
+                let mut task = log_queue::LogTask::new(pl.clone(), c, false);
+                if incremental_mode {
+                    let key = incremental::state_key(&pl.namespace, &pl.name, &task.container);
+                    let current =
+                        incremental::current_container_state(pl, &task.container, run_started);
+                    task.since_seconds = incremental::since_seconds(
+                        previous_state.containers.get(&key),
+                        &current,
+                        run_started,
+                    );
+                }
+                log_tasks.push(task);
+            }
+        });
+    }
+    if config_file.previous_logs {
+        let mut tasks = vec![];
+        pods_list.iter().for_each(|pl| {
+            let container = if forced(pl) {
+                pl.containers.clone()
+            } else {
+                container_filter.containers(pl)
+            };
+            for c in container {
+                if incremental_mode {
+                    //previous logs only change when the container restarted since the last run.
+                    let key = incremental::state_key(&pl.namespace, &pl.name, &c);
+                    let current = incremental::current_container_state(pl, &c, run_started);
+                    if let Some(p) = previous_state.containers.get(&key) {
+                        if !incremental::restarted(p, &current) {
+                            info!(
+                                "No restart since the last run, skipping previous logs {} on container {}.",
+                                pl.name, c
+                            );
+                            continue;
+                        }
+                    }
+                }
+                tasks.push((pl.clone(), c));
+            }
+        });
+        let count = tasks.len();
+        let tasks =
+            previous_logs::prioritize(tasks, config_file.previous_logs_max_age_hours, Utc::now());
+        if tasks.len() < count {
+            info!(
+                "{} previous logs skipped, last crash older than {} hours.",
+                count - tasks.len(),
+                config_file.previous_logs_max_age_hours.unwrap_or_default()
+            );
+        }
+        log_tasks.extend(
+            tasks
+                .into_iter()
+                .map(|(pl, c)| log_queue::LogTask::new(pl, c, true)),
+        );
+    }
+    let concurrency = match config_file.log_concurrency {
+        0 => log_queue::DEFAULT_LOG_CONCURRENCY,
+        n => n,
+    };
+    let log_count = log_tasks.len();
+    let pod_count = log_tasks
+        .iter()
+        .map(|t| (&t.pod.namespace, &t.pod.name))
+        .collect::<HashSet<_>>()
+        .len();
+    let logs_started = Instant::now();
+    let fetched = collector
+        .collect_logs(log_tasks, concurrency, pod_folder)
+        .await?;
+    //the incremental state of the current logs, the one of the last run when the fetch failed.
+    for (t, ok) in fetched.into_iter().filter(|(t, _)| !t.previous) {
+        let key = incremental::state_key(&t.pod.namespace, &t.pod.name, &t.container);
+        let collected = if ok {
+            Some(incremental::current_container_state(
+                &t.pod,
+                &t.container,
+                run_started,
+            ))
+        } else {
+            previous_state.containers.get(&key).cloned()
+        };
+        if let Some(st) = collected {
+            new_state.lock().unwrap().containers.insert(key, st);
+        }
+    }
+    info!(
+        "{} logs of {} pods fetched in {:.1}s.",
+        log_count,
+        pod_count,
+        logs_started.elapsed().as_secs_f64()
+    );
+    if !config_file.current_logs && !config_file.previous_logs {
+        ctx.record_coverage("pod_logs", CoverageOutcome::Disabled);
+    } else if pods_list.is_empty() {
+        let reason = format!("no pods in {}", config_file.context_namespace.join(", "));
+        ctx.record_coverage("pod_logs", CoverageOutcome::Skipped { reason });
+    } else {
+        ctx.record_outputs("pod_logs", logs_before);
+    }
+
+    // Infra
+    options.phase(&ctx, "infra")?;
+
+    //unknown in the health summary when the secrets cannot be listed.
+    match health::count_expiring_certificates(&collector.pod_access(), Utc::now()).await {
+        Ok(c) => ctx.health.lock().unwrap().certificates_expiring = Some(c),
+        Err(e) => warn!("Expiring certificates: {}", e),
+    }
+
+    let mut cmdki = vec![];
+    let mut fut_handle_infra = vec![];
+    let file_name = "kubernetes_nodes.list".to_string();
+    cmdki.push((args(&["get", "nodes", "-o", "wide"]), file_name, None));
+
+    let file_name = "kubernetes_nodes_list.json".to_string();
+    cmdki.push((args(&["get", "nodes", "-o", "json"]), file_name, None));
+
+    let file_name = "kubernetes_version.json".to_string();
+    cmdki.push((args(&["version", "-o", "json"]), file_name, None));
+
+    let file_name = "kubernetes_cluster.events".to_string();
+    cmdki.push((args(&["get", "events", "-A"]), file_name, None));
+
+    nodes_list.iter().for_each(|n| {
+        let file_name = format!("{}.description", n);
+        cmdki.push((
+            args(&["describe", "node", n]),
+            file_name,
+            Some(LogScope::node(n)),
+        ));
+    });
+
+    cmdki.into_iter().for_each(|c| {
+        let folders = folders.clone();
+        let ctx = ctx.clone();
+        let cmd = kubectl_command(&config_file, &ctx.kubeconfig);
+        let scope = c.2.clone();
+        let task = async move {
+            let o = match run_external(cmd, &c.0, timeout, &ctx.usage).await {
+                Ok(o) => o,
+                Err(e) => {
+                    warn!("{}", e);
+                    let attempted = e.command().to_string();
+                    ctx.record_failure(&folders[1], &c.1, &attempted, &e.into());
+                    return;
+                }
+            };
+            let er = anyhow!("{} empty response", o.command);
+            match ctx.write_file(&folders[1], &o.stdout, &c.1, er) {
+                Ok(_) => info!("File has been created {}/{}", &folders[1], &c.1),
+                Err(e) => warn!("{}", e),
+            }
+            o.warn_stderr();
+        };
+        //the node descriptions are scoped to their node, the cluster wide lists to none.
+        fut_handle_infra.push(match scope {
+            Some(scope) => tokio::task::spawn(logging::scoped(scope, task)),
+            None => tokio::task::spawn(task),
+        });
+    });
+
+    for handle in fut_handle_infra {
+        match handle.await {
+            Ok(_) => {}
+            Err(e) => {
+                warn!("{}", e);
+                ctx.record_folder(&folders[1], false);
+            }
+        }
+    }
+
+    //priority classes and the qos distribution of the collected pods.
+    let priority_classes: Api<PriorityClass> = Api::all(client.clone());
+    match priority_classes.list(&ListParams::default()).await {
+        Ok(pcs) => {
+            let report = qos::priority_qos_report(pods_list, &pcs.items);
+            for (data, file_name) in [
+                (qos::to_json(&report)?, qos::PRIORITY_QOS_FILE),
+                (qos::render_summary(&report), qos::PRIORITY_QOS_SUMMARY_FILE),
+            ] {
+                let er = anyhow!("Empty {}.", file_name);
+                match ctx.write_file(&folders[1], data.as_bytes(), file_name, er) {
+                    Ok(_) => info!("File has been created {}/{}", &folders[1], file_name),
+                    Err(e) => warn!("{}", e),
+                }
+            }
+        }
+        Err(e) => {
+            warn!("{}", e);
+            ctx.record_failure(
+                &folders[1],
+                qos::PRIORITY_QOS_FILE,
+                "list priorityclasses",
+                &e.into(),
+            );
+        }
+    }
+
+    if config_file.collect_cluster_info {
+        let before = ctx.output_counts();
+        let r = cluster_info::collect_cluster_info(&collector, &folders[1]).await;
+        ctx.record_collected("cluster_info", before, &r);
+        if let Err(e) = r {
+            warn!("Cluster info: {}", e);
+            ctx.record_folder(&folders[1], false);
+        }
+    } else {
+        ctx.record_coverage("cluster_info", CoverageOutcome::Disabled);
+    }
+
+    if config_file.collect_cni {
+        let before = ctx.output_counts();
+        let r = cni::collect_cni(&collector, &folders[1]).await;
+        ctx.record_collected("cni", before, &r);
+        if let Err(e) = r {
+            warn!("CNI: {}", e);
+            ctx.record_folder(&folders[1], false);
+        }
+    } else {
+        ctx.record_coverage("cni", CoverageOutcome::Disabled);
+    }
+
+    if config_file.collect_storage_csi {
+        let before = ctx.output_counts();
+        let r = storage_csi::collect_storage_csi(&collector, &folders[1]).await;
+        ctx.record_collected("storage_csi", before, &r);
+        if let Err(e) = r {
+            warn!("Storage CSI: {}", e);
+            ctx.record_folder(&folders[1], false);
+        }
+    } else {
+        ctx.record_coverage("storage_csi", CoverageOutcome::Disabled);
+    }
+
+    let before = ctx.output_counts();
+    let r = scheduling::collect_headroom(&collector, pods_list, &folders[1]).await;
+    ctx.record_collected("scheduling_headroom", before, &r);
+    if let Err(e) = r {
+        warn!("Scheduling headroom: {}", e);
+        ctx.record_folder(&folders[1], false);
+    }
+
+    let before = ctx.output_counts();
+    let r =
+        node_pressure::collect_node_pressure(&collector, pods_list, &folders[1], &folders[0]).await;
+    ctx.record_collected("node_pressure", before, &r);
+    if let Err(e) = r {
+        warn!("Node pressure: {}", e);
+        ctx.record_folder(&folders[1], false);
+    }
+
+    let before = ctx.output_counts();
+    let r = placement::collect_placement(&collector, pods_list, &folders[1]).await;
+    ctx.record_collected("placement", before, &r);
+    if let Err(e) = r {
+        warn!("Placement: {}", e);
+        ctx.record_folder(&folders[1], false);
+    }
+
+    let before = ctx.output_counts();
+    let r = apiserver::collect_latency(&collector, &folders[1]).await;
+    ctx.record_collected("apiserver_latency", before, &r);
+    if let Err(e) = r {
+        warn!("Api server latency: {}", e);
+        ctx.record_folder(&folders[1], false);
+    }
+
+    let before = ctx.output_counts();
+    let r = clock_skew::collect_clock_skew(&collector, pods_list, &folders[1]).await;
+    ctx.record_collected("clock_skew", before, &r);
+    if let Err(e) = r {
+        warn!("Clock skew: {}", e);
+        ctx.record_folder(&folders[1], false);
+    }
+
+    let before = ctx.output_counts();
+    let r = api_versions::collect_versioned(&collector, &folders[1]).await;
+    ctx.record_collected("api_versions", before, &r);
+    if let Err(e) = r {
+        warn!("Versioned resources: {}", e);
+        ctx.record_folder(&folders[1], false);
+    }
+
+    let before = ctx.output_counts();
+    let r = leases::collect_leases(&collector, &folders[1]).await;
+    ctx.record_collected("leases", before, &r);
+    if let Err(e) = r {
+        warn!("Leases: {}", e);
+        ctx.record_folder(&folders[1], false);
+    }
+
+    let before = ctx.output_counts();
+    let r = openshift::collect_openshift(&collector, pods_list, &folders[1]).await;
+    ctx.record_collected("openshift", before, &r);
+    if let Err(e) = r {
+        warn!("OpenShift: {}", e);
+        ctx.record_folder(&folders[1], false);
+    }
+
+    //opt-in, it needs the right to create privileged pods.
+    if config_file.collect_node_debug {
+        let template = node_debug::DebugPodTemplate::from_config(&config_file);
+        let debug_nodes = if node_mode {
+            &config_file.node_names
+        } else {
+            &nodes_list
+        };
+        match node_debug::ensure_namespace(&client, &template.namespace).await {
+            Ok(created) => {
+                for n in debug_nodes {
+                    let before = ctx.output_counts();
+                    let r = logging::scoped(
+                        LogScope::node(n),
+                        node_debug::collect_node_runtime(
+                            client.clone(),
+                            &template,
+                            n,
+                            &folders[1],
+                            &ctx,
+                        ),
+                    )
+                    .await;
+                    ctx.record_collected("node_debug", before, &r);
+                    if let Err(e) = r {
+                        warn!("Node debug {}: {}", n, e);
+                    }
+                }
+                if created {
+                    node_debug::delete_namespace(&client, &template.namespace).await;
+                }
+            }
+            Err(e) => {
+                warn!("Node debug namespace {}: {}", template.namespace, e);
+                ctx.record_coverage(
+                    "node_debug",
+                    CoverageOutcome::Failed {
+                        error: e.to_string(),
+                    },
+                );
+                ctx.record_folder(&folders[1], false);
+            }
+        }
+    } else {
+        ctx.record_coverage("node_debug", CoverageOutcome::Disabled);
+    }
+
+    //helm
+    options.phase(&ctx, "helm")?;
+    //get helm version
+    //list helm charts
+    //get helm chart values.
+    let mut cmdhelms = vec![];
+    let mut fut_handle_helm = vec![];
+    let context = config_file.context_name.clone();
+    let file_name = "helm_version.log".to_string();
+    cmdhelms.push((args(&["version"]), file_name, None));
+
+    let mut releases: LsHelm = vec![];
+    for n in &config_file.context_namespace {
+        let file_name = format!("helm_list_{}.log", n);
+        cmdhelms.push((args(&["ls", "-n", n]), file_name, None));
+        let cmd = helm_command(&config_file, &ctx.kubeconfig);
+        let listed = run_external(cmd, ["ls", "-n", n, "-o", "json"], timeout, &ctx.usage)
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|o| Ok(serde_json::from_slice::<LsHelm>(&o.stdout)?));
+        let o = match listed {
+            Ok(o) => o,
+            Err(e) => {
+                warn!("Helm releases of {}: {}", n, e);
+                ctx.record_folder(&folders[2], false);
+                continue;
+            }
+        };
+        o.iter().for_each(|h| {
+            let file_name = format!("helm_values_{}_{}.yaml", h.name, n);
+            cmdhelms.push((
+                args(&["get", "values", "--all", &h.name, "-n", n, "-o", "yaml"]),
+                file_name,
+                Some((h.name.clone(), n.clone(), true)),
+            ));
+            //user supplied values only, with the drift from the previous revision.
+            let file_name = format!("helm_user_values_{}_{}.yaml", h.name, n);
+            cmdhelms.push((
+                args(&["get", "values", &h.name, "-n", n, "-o", "yaml"]),
+                file_name,
+                Some((h.name.clone(), n.clone(), false)),
+            ));
+        });
+        releases.extend(o);
+    }
+
+    cmdhelms.into_iter().for_each(|c| {
+        let folders = folders.clone();
+        let ctx = ctx.clone();
+        let client = client.clone();
+        let values_diff = config_file.helm_values_diff;
+        let cmd = helm_command(&config_file, &ctx.kubeconfig);
+        let scope = c.2.as_ref().map(|(_, namespace, _)| LogScope::namespace(namespace));
+        let task = async move {
+            let o = match run_external(cmd, &c.0, timeout, &ctx.usage).await {
+                Ok(o) => o,
+                Err(e) => {
+                    warn!("{}", e);
+                    let attempted = e.command().to_string();
+                    ctx.record_failure(&folders[2], &c.1, &attempted, &e.into());
+                    return;
+                }
+            };
+            let mut stdout = o.stdout.clone();
+            //release values go through the secret scrubbing, and the diff against the chart defaults on demand.
+            if let Some((release, namespace, all)) = &c.2 {
+                match serde_yaml::from_slice::<serde_yaml::Value>(&o.stdout) {
+                    Ok(mut values) => {
+                        if !all {
+                            let access = KubeAccess::namespaced(client.clone(), namespace);
+                            let file_name = format!("drift_{}_{}.yaml", release, namespace);
+                            let drift = helm::recent_user_values(&access, release)
+                                .await
+                                .and_then(|r| match r.as_slice() {
+                                    [(current, now), (previous, before), ..] => Ok(format!(
+                                        "# user supplied values, revision {} against revision {}\n{}",
+                                        current,
+                                        previous,
+                                        serde_yaml::to_string(&helm::values_drift(now, before))?
+                                    )),
+                                    _ => Ok(format!("# {} has a single revision, no drift\n", release)),
+                                });
+                            match drift {
+                                Ok(d) => {
+                                    let er = anyhow!("Empty drift for {}.", release);
+                                    match ctx.write_file(&folders[2], d.as_bytes(), &file_name, er)
+                                    {
+                                        Ok(_) => info!(
+                                            "File has been created {}/{}",
+                                            &folders[2], &file_name
+                                        ),
+                                        Err(e) => warn!("{}", e),
+                                    }
+                                }
+                                Err(e) => {
+                                    warn!("{}", e);
+                                    let attempted =
+                                        format!("read the release secrets of {}", release);
+                                    ctx.record_failure(&folders[2], &file_name, &attempted, &e);
+                                }
+                            }
+                        }
+                        if values_diff && *all {
+                            let access = KubeAccess::namespaced(client, namespace);
+                            let file_name = format!("values_diff_{}_{}.yaml", release, namespace);
+                            let diff = helm::release_default_values(&access, release)
+                                .await
+                                .and_then(|d| {
+                                    let mut diff = helm::values_diff(&values, &d)
+                                        .unwrap_or(serde_yaml::Value::Mapping(Default::default()));
+                                    helm::scrub_secrets(&mut diff);
+                                    Ok(serde_yaml::to_string(&diff)?)
+                                });
+                            match diff {
+                                Ok(d) => {
+                                    let er = anyhow!("Empty values diff for {}.", release);
+                                    match ctx.write_file(&folders[2], d.as_bytes(), &file_name, er)
+                                    {
+                                        Ok(_) => info!(
+                                            "File has been created {}/{}",
+                                            &folders[2], &file_name
+                                        ),
+                                        Err(e) => warn!("{}", e),
+                                    }
+                                }
+                                Err(e) => {
+                                    warn!("{}", e);
+                                    let attempted =
+                                        format!("read the release secret of {}", release);
+                                    ctx.record_failure(&folders[2], &file_name, &attempted, &e);
+                                }
+                            }
+                        }
+                        helm::scrub_secrets(&mut values);
+                        stdout = serde_yaml::to_string(&values)
+                            .unwrap_or_default()
+                            .into_bytes();
+                    }
+                    //never write values that could not be scrubbed.
+                    Err(e) => {
+                        warn!("Helm values of {} are not valid yaml: {}", release, e);
+                        stdout.clear();
+                    }
+                }
+            }
+            let er = anyhow!("{} empty response", o.command);
+            match ctx.write_file(&folders[2], &stdout, &c.1, er) {
+                Ok(_) => info!("File has been created {}/{}", &folders[2], &c.1),
+                Err(e) => warn!("{}", e),
+            }
+            o.warn_stderr();
+        };
+        //the values of a release are scoped to its namespace.
+        fut_handle_helm.push(match scope {
+            Some(scope) => tokio::task::spawn(logging::scoped(scope, task)),
+            None => tokio::task::spawn(task),
+        });
+    });
+
+    for handle in fut_handle_helm {
+        match handle.await {
+            Ok(_) => {}
+            Err(e) => {
+                warn!("{}", e);
+                ctx.record_folder(&folders[2], false);
+            }
+        }
+    }
+    options.phase(&ctx, "apps")?;
+    //which release deployed each workload and pod, by their app.kubernetes.io/instance label.
+    let ownership = release_ownership::build_ownership(pods_list, &releases);
+    ownership
+        .failing_pods
+        .iter()
+        .for_each(|(p, r)| info!("Failing pod {} belongs to the helm release {}.", p, r));
+    let er = anyhow!("Empty {}.", release_ownership::RELEASE_OWNERSHIP_FILE);
+    match serde_json::to_string_pretty(&ownership)
+        .map_err(|e| e.into())
+        .and_then(|j| {
+            ctx.write_file(
+                &folders[3],
+                j.as_bytes(),
+                release_ownership::RELEASE_OWNERSHIP_FILE,
+                er,
+            )
+        }) {
+        Ok(_) => info!(
+            "File has been created {}/{}",
+            &folders[3],
+            release_ownership::RELEASE_OWNERSHIP_FILE
+        ),
+        Err(e) => warn!("{}", e),
+    }
+    //workloads edited by hand since their last kubectl apply.
+    let before = ctx.output_counts();
+    let r = manual_changes::collect_manual_changes(&collector, &folders[3]).await;
+    ctx.record_collected("manual_changes", before, &r);
+    if let Err(e) = r {
+        warn!("Manual changes: {}", e);
+        ctx.record_folder(&folders[3], false);
+    }
+    //nothing matches the app selectors without namespaces.
+    let app_access = if config_file.skip_app_collectors {
+        info!("App collectors skipped.");
+        vec![]
+    } else {
+        collector.pod_access()
+    };
+    //Streaming Cores info.
+    //ElasticSearch.
+    //Hadoop hdfs info.
+    //Hbase info.
+    //Kafka info.
+    //Kafka Connect and Schema Registry info.
+    //Prometheus info.
+
+    //ElasticSearch
+    let es_scope = components::component_scope(&config_file, "elasticsearch");
+    let es_pods = es_scope.pods(&collector.access).await?;
+    if es_pods.is_empty() {
+        ctx.record_coverage("elasticsearch", es_scope.skipped());
+    } else {
+        let before = ctx.output_counts();
+        //the credentials section first, the elastic user of the ECK secret otherwise.
+        let mut es_credentials = ctx.credentials.get("elasticsearch").cloned();
+        if es_credentials.is_none() {
+            for sec in &es_scope.access(&collector.access) {
+                let secrets = sec
+                    .list_secrets(components::ELASTICSEARCH_SECRET_SELECTOR)
+                    .await
+                    .inspect_err(|e| warn!("Elasticsearch secrets: {}", e))
+                    .unwrap_or_default();
+                let source = credentials::CredentialSource {
+                    password_key: "elastic".to_string(),
+                    ..Default::default()
+                };
+                if let Some(c) = secrets
+                    .iter()
+                    .find_map(|s| credentials::from_secret(&source, s).ok())
+                {
+                    es_credentials = Some(credentials::Credentials {
+                        username: "elastic".to_string(),
+                        ..c
+                    });
+                }
+            }
+        }
+
+        //port-forward to 9200 first, curl in the container when the port-forward is blocked.
+        let authorization = es_credentials.as_ref().map(|c| c.basic_authorization());
+        let probe = pod_selection::leader_probe("elasticsearch").map(|p| ComponentCommand {
+            http: p.http.map(|h| HttpGet {
+                authorization: authorization.clone(),
+                ..h
+            }),
+            credentials: es_credentials.clone(),
+            ..p
+        });
+        let es_targets = pod_selection::select_pods(
+            "elasticsearch",
+            &es_scope,
+            &collector.access,
+            es_pods,
+            probe,
+        )
+        .await;
+        let several = es_targets.len() > 1;
+        for (i, es_pod) in es_targets.iter().enumerate() {
+            let command_es = [
+                ("/_cluster/health?pretty", "health"),
+                ("/_cat/indices?format=json&bytes=b&pretty&h=health,status,index,uuid,pri,rep,docs.count,docs.deleted,store.size,creation.date.string&s=creation.date:desc", "indices"),
+                ("/_cluster/settings?pretty", "settings"),
+                ("/_cluster/settings?include_defaults=true&pretty", "defaults_settings"),
+                ("/_cat/nodes?v&pretty", "nodes"),
+                ("/_cat/_cat/shards?v", "shards"),
+                ("/_cluster/state?pretty", "state"),
+                ("/_cluster/stats?human&pretty", "stats_human"),
+            ]
+            .map(|(path, name)| ComponentCommand {
+                name: name.to_string(),
+                filename: config_file.file_name_templates.app_output(
+                    es_pod,
+                    &es_pod.containers[0],
+                    &pod_selection::output_kind(
+                        es_pod,
+                        &format!("elastic_search_{}.json", name),
+                        several,
+                    ),
+                ),
+                shell: format!(
+                    "curl -k -u \"${}:${}\" -X GET \"https://localhost:9200{}\"",
+                    credentials::USERNAME_ENV,
+                    credentials::PASSWORD_ENV,
+                    path
+                ),
+                http: Some(HttpGet {
+                    port: 9200,
+                    tls: true,
+                    path: path.to_string(),
+                    authorization: authorization.clone(),
+                }),
+                credentials: es_credentials.clone(),
+                //the cluster state and the default settings are the big ones.
+                compressible: name == "state" || name == "defaults_settings",
+                ..Default::default()
+            });
+            //every elasticsearch api needs the elastic user with security enabled.
+            let (command_es, disabled) = credentials::enabled_commands(
+                command_es.to_vec(),
+                |_| true,
+                es_credentials.as_ref(),
+            );
+            for c in disabled {
+                let e = anyhow!("No elasticsearch credentials, the command is disabled.");
+                ctx.record_failure(&folders[3], &c.filename, &c.shell, &e);
+            }
+
+            let target = ComponentTarget::new("elasticsearch", es_pod.clone())
+                .with_scope(es_scope.clone(), collector.access.clone());
+            let outputs = component_command::collect_component_outputs(
+                Arc::new(target),
+                ctx.clone(),
+                &folders[3],
+                command_es,
+            )
+            .await;
+            //health and indices are the cluster ones, the same from every pod.
+            if i > 0 {
+                continue;
+            }
+            for (name, data) in outputs {
+                if name == "health" {
+                    ctx.health.lock().unwrap().elasticsearch_status =
+                        health::elasticsearch_status(&data);
+                }
+                if name == "indices" {
+                    let report = elastic::parse_indices(&data)
+                        .map(|i| elastic::render_index_report(&i))
+                        .and_then(|r| {
+                            let er = anyhow!("Empty {}.", elastic::INDEX_REPORT_FILE);
+                            ctx.write_file(
+                                &folders[3],
+                                r.as_bytes(),
+                                elastic::INDEX_REPORT_FILE,
+                                er,
+                            )
+                        });
+                    match report {
+                        Ok(_) => info!(
+                            "File has been created {}/{}",
+                            &folders[3],
+                            elastic::INDEX_REPORT_FILE
+                        ),
+                        Err(e) => warn!("Elasticsearch index report: {}", e),
+                    }
+                }
+            }
+        }
+        ctx.record_outputs("elasticsearch", before);
+    }
+
+    //Streaming Cores info
+    let scope = components::component_scope(&config_file, "streaming_core");
+    let streaming_core_pods = scope.pods(&collector.access).await?;
+    if streaming_core_pods.is_empty() {
+        ctx.record_coverage("streaming_core", scope.skipped());
+    }
+    for sc in streaming_core_pods {
+        let before = ctx.output_counts();
+        //the spark ui of the driver, port-forward to 4040 first and curl in the container otherwise.
+        //A driver runs its own application, it is not replaced by another pod when it is gone.
+        let target = Arc::new(ComponentTarget::new("streaming_core", sc.clone()));
+        let applications = ComponentCommand {
+            name: "applications".to_string(),
+            shell: "curl -s localhost:4040/api/v1/applications".to_string(),
+            http: Some(HttpGet {
+                port: 4040,
+                path: "/api/v1/applications".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let application_id = match component_command::run_component_command(&target, &applications)
+            .await
+            .and_then(|(a, _)| spark::first_application_id(&a))
+        {
+            Ok(id) => id,
+            Err(e) => {
+                warn!("Streaming core {} application: {}", sc.name, e);
+                ctx.record_failure(
+                    &folders[3],
+                    &format!("{}_{}_applications.json", sc.namespace, sc.name),
+                    &applications.shell,
+                    &e,
+                );
+                continue;
+            }
+        };
+
+        let command_sc = [
+            ("environment", "environment.json"),
+            ("executors", "executors.json"),
+            ("streaming/statistics", "streaming_statistics.json"),
+            ("streaming/batches", "streaming_batches.json"),
+        ]
+        .map(|(endpoint, name)| {
+            let path = format!("/api/v1/applications/{}/{}", application_id, endpoint);
+            ComponentCommand {
+                name: name.to_string(),
+                filename: config_file.file_name_templates.app_output(
+                    &sc,
+                    &sc.containers[0],
+                    &format!("{}_{}_{}", sc.namespace, sc.name, name),
+                ),
+                shell: format!("curl \"localhost:4040{}\"", path),
+                http: Some(HttpGet {
+                    port: 4040,
+                    path,
+                    ..Default::default()
+                }),
+                pretty_json: true,
+                compressible: true,
+                ..Default::default()
+            }
+        });
+        component_command::collect_component_outputs(
+            target,
+            ctx.clone(),
+            &folders[3],
+            command_sc.to_vec(),
+        )
+        .await;
+        ctx.record_outputs("streaming_core", before);
+    }
+
+    //Hadoop hdfs info
+    let scope = components::component_scope(&config_file, "hadoop");
+    let hadoop_pods = scope.pods(&collector.access).await?;
+    if hadoop_pods.is_empty() {
+        ctx.record_coverage("hadoop", scope.skipped());
+    } else {
+        let before = ctx.output_counts();
+        let targets =
+            pod_selection::select_pods("hadoop", &scope, &collector.access, hadoop_pods, None)
+                .await;
+        let several = targets.len() > 1;
+        for (i, hadoop_pod) in targets.iter().enumerate() {
+            let command_hd = [
+                ("hdfs dfsadmin -report", "report_dfsadmin"),
+                ("hdfs dfsadmin -safemode get", "safe_mode"),
+                (
+                    "time dd if=/dev/zero of=/dfs/test conv=fsync bs=384k count=10K",
+                    "hdfs_diskwrite_perf",
+                ),
+            ]
+            .map(|(shell, name)| ComponentCommand {
+                name: name.to_string(),
+                filename: config_file.file_name_templates.app_output(
+                    hadoop_pod,
+                    &hadoop_pod.containers[0],
+                    &pod_selection::output_kind(
+                        hadoop_pod,
+                        &format!("hadoop_{}.log", name),
+                        several,
+                    ),
+                ),
+                shell: shell.to_string(),
+                ..Default::default()
+            });
+            let target = ComponentTarget::new("hadoop", hadoop_pod.clone())
+                .with_scope(scope.clone(), collector.access.clone());
+            let outputs = component_command::collect_component_outputs(
+                Arc::new(target),
+                ctx.clone(),
+                &folders[3],
+                command_hd.to_vec(),
+            )
+            .await;
+            //the dfsadmin report is the cluster one, the same from every pod.
+            if i > 0 {
+                continue;
+            }
+            if let Some((_, report)) = outputs.iter().find(|(n, _)| n == "report_dfsadmin") {
+                ctx.health.lock().unwrap().hdfs_missing_blocks =
+                    health::hdfs_missing_blocks(report);
+            }
+        }
+        ctx.record_outputs("hadoop", before);
+    }
+    //Hbase info
+    let scope = components::component_scope(&config_file, "hbase");
+    let hbase_pods = scope.pods(&collector.access).await?;
+    if hbase_pods.is_empty() {
+        ctx.record_coverage("hbase", scope.skipped());
+    } else {
+        let before = ctx.output_counts();
+        let targets =
+            pod_selection::select_pods("hbase", &scope, &collector.access, hbase_pods, None).await;
+        let several = targets.len() > 1;
+        for hbase_pod in &targets {
+            let command_hb = [(
+                "echo \"status 'detailed'\" | hbase shell",
+                "status_detailed",
+            )]
+            .map(|(shell, name)| ComponentCommand {
+                name: name.to_string(),
+                filename: config_file.file_name_templates.app_output(
+                    hbase_pod,
+                    &hbase_pod.containers[0],
+                    &pod_selection::output_kind(hbase_pod, &format!("hbase_{}.log", name), several),
+                ),
+                shell: shell.to_string(),
+                ..Default::default()
+            });
+            let target = ComponentTarget::new("hbase", hbase_pod.clone())
+                .with_scope(scope.clone(), collector.access.clone());
+            component_command::collect_component_outputs(
+                Arc::new(target),
+                ctx.clone(),
+                &folders[3],
+                command_hb.to_vec(),
+            )
+            .await;
+        }
+        ctx.record_outputs("hbase", before);
+    }
+
+    //Kafka info
+    kafka::collect_kafka(&collector, &folders[3]).await?;
+    //Kafka Connect info
+    let scope = components::component_scope(&config_file, "kafka_connect");
+    let connect_pods = scope.pods(&collector.access).await?;
+    if connect_pods.is_empty() {
+        ctx.record_coverage("kafka_connect", scope.skipped());
+    } else {
+        let before = ctx.output_counts();
+        let targets = pod_selection::select_pods(
+            "kafka_connect",
+            &scope,
+            &collector.access,
+            connect_pods,
+            None,
+        )
+        .await;
+        let several = targets.len() > 1;
+        for (i, connect_pod) in targets.iter().enumerate() {
+            //port-forward to the connect rest api first, curl in the container otherwise.
+            let status = ComponentCommand {
+                name: "status".to_string(),
+                filename: config_file.file_name_templates.app_output(
+                    connect_pod,
+                    &connect_pod.containers[0],
+                    &pod_selection::output_kind(connect_pod, kafka::CONNECT_STATUS_FILE, several),
+                ),
+                shell: "curl -s 'localhost:8083/connectors?expand=status'".to_string(),
+                http: Some(HttpGet {
+                    port: 8083,
+                    path: "/connectors?expand=status".to_string(),
+                    ..Default::default()
+                }),
+                pretty_json: true,
+                ..Default::default()
+            };
+            let target = ComponentTarget::new("kafka_connect", connect_pod.clone())
+                .with_scope(scope.clone(), collector.access.clone());
+            let outputs = component_command::collect_component_outputs(
+                Arc::new(target),
+                ctx.clone(),
+                &folders[3],
+                vec![status],
+            )
+            .await;
+            //the workers of a cluster answer the same connectors.
+            if i > 0 {
+                continue;
+            }
+            let Some((_, data)) = outputs.iter().find(|(n, _)| n == "status") else {
+                continue;
+            };
+            match kafka::flatten_connect_status(data) {
+                Ok(states) => {
+                    let failed = kafka::failed_connect_states(&states);
+                    ctx.health.lock().unwrap().kafka_connect_failed = Some(failed.len());
+                    if failed.is_empty() {
+                        continue;
+                    }
+                    warn!(
+                        "<yellow>Kafka connect: {} failed connectors and tasks.</>",
+                        failed.len()
+                    );
+                    let report = kafka::render_connect_failures(&failed);
+                    let er = anyhow!("Empty {}.", kafka::CONNECT_FAILURES_FILE);
+                    match ctx.write_file(
+                        &folders[3],
+                        report.as_bytes(),
+                        kafka::CONNECT_FAILURES_FILE,
+                        er,
+                    ) {
+                        Ok(_) => info!(
+                            "File has been created {}/{}",
+                            &folders[3],
+                            kafka::CONNECT_FAILURES_FILE
+                        ),
+                        Err(e) => warn!("{}", e),
+                    }
+                }
+                Err(e) => {
+                    warn!("Kafka connect status: {}", e);
+                    ctx.record_failure(
+                        &folders[3],
+                        kafka::CONNECT_FAILURES_FILE,
+                        "parse the connectors status",
+                        &e,
+                    );
+                }
+            }
+        }
+        ctx.record_outputs("kafka_connect", before);
+    }
+    //Schema Registry info
+    let scope = components::component_scope(&config_file, "schema_registry");
+    let registry_pods = scope.pods(&collector.access).await?;
+    if registry_pods.is_empty() {
+        ctx.record_coverage("schema_registry", scope.skipped());
+    } else {
+        let before = ctx.output_counts();
+        let targets = pod_selection::select_pods(
+            "schema_registry",
+            &scope,
+            &collector.access,
+            registry_pods,
+            None,
+        )
+        .await;
+        let several = targets.len() > 1;
+        for registry_pod in &targets {
+            let command_sr = [
+                ("/subjects", kafka::SCHEMA_REGISTRY_SUBJECTS_FILE),
+                ("/config", kafka::SCHEMA_REGISTRY_CONFIG_FILE),
+            ]
+            .map(|(path, name)| ComponentCommand {
+                name: name.to_string(),
+                filename: config_file.file_name_templates.app_output(
+                    registry_pod,
+                    &registry_pod.containers[0],
+                    &pod_selection::output_kind(registry_pod, name, several),
+                ),
+                shell: format!("curl -s 'localhost:8081{}'", path),
+                http: Some(HttpGet {
+                    port: 8081,
+                    path: path.to_string(),
+                    ..Default::default()
+                }),
+                pretty_json: true,
+                ..Default::default()
+            });
+            let target = ComponentTarget::new("schema_registry", registry_pod.clone())
+                .with_scope(scope.clone(), collector.access.clone());
+            component_command::collect_component_outputs(
+                Arc::new(target),
+                ctx.clone(),
+                &folders[3],
+                command_sr.to_vec(),
+            )
+            .await;
+        }
+        ctx.record_outputs("schema_registry", before);
+    }
+    //Prometheus info
+    let scope = components::component_scope(&config_file, "prometheus");
+    let prometheus_pods = scope.pods(&collector.access).await?;
+    if prometheus_pods.is_empty() {
+        ctx.record_coverage("prometheus", scope.skipped());
+    } else {
+        let before = ctx.output_counts();
+        let targets = pod_selection::select_pods(
+            "prometheus",
+            &scope,
+            &collector.access,
+            prometheus_pods,
+            None,
+        )
+        .await;
+        let several = targets.len() > 1;
+        for (i, prometheus_pod) in targets.iter().enumerate() {
+            let pod_name = prometheus_pod.name.as_str();
+            let mut path = ["midlayer", "session", "titan-ns"]
+                .into_iter()
+                .filter(|&i| pod_name.contains(i))
+                .collect::<Vec<&str>>();
+            if path.is_empty() {
+                path.push(&prometheus_pod.namespace)
+            }
+            //port-forward to 9090 first, wget in the container when the port-forward is blocked.
+            let command_prometheus = [
+                ("rules", "rules.json"),
+                ("alerts", "alerts.json"),
+                ("targets", "targets.json"),
+                ("status/runtimeinfo", "runtime_info.json"),
+                ("status/buildinfo", "build_info.json"),
+            ]
+            .map(|(endpoint, name)| {
+                let path = format!("/{}/prometheus/api/v1/{}", path[0], endpoint);
+                ComponentCommand {
+                    name: name.to_string(),
+                    filename: config_file.file_name_templates.app_output(
+                        prometheus_pod,
+                        &prometheus_pod.containers[0],
+                        &pod_selection::output_kind(
+                            prometheus_pod,
+                            &format!("prometheus_{}_{}", prometheus_pod.namespace, name),
+                            several,
+                        ),
+                    ),
+                    shell: format!("wget -q 'http://127.0.0.1:9090{}' -O -", path),
+                    http: Some(HttpGet {
+                        port: 9090,
+                        path,
+                        ..Default::default()
+                    }),
+                    pretty_json: true,
+                    ..Default::default()
+                }
+            });
+            let target = ComponentTarget::new("prometheus", prometheus_pod.clone())
+                .with_scope(scope.clone(), collector.access.clone());
+            let responses = component_command::collect_component_outputs(
+                Arc::new(target),
+                ctx.clone(),
+                &folders[3],
+                command_prometheus.to_vec(),
+            )
+            .await
+            .into_iter()
+            .collect::<HashMap<String, String>>();
+            //the replicas scrape the same targets, the problems of the first one are reported.
+            if i > 0 {
+                continue;
+            }
+            //only the failing targets and rules out of targets.json and rules.json.
+            let namespace = &prometheus_pod.namespace;
+            let targets = responses
+                .get("targets.json")
+                .map(|t| prometheus::down_targets(t));
+            let rules = responses
+                .get("rules.json")
+                .map(|r| prometheus::rule_problems(r));
+            let filename = format!("prometheus_problems_{}.txt", namespace);
+            match (targets, rules) {
+                (Some(Ok(t)), Some(Ok(r))) => {
+                    ctx.health.lock().unwrap().prometheus_targets_down = Some(t.len());
+                    if !t.is_empty() || !r.is_empty() {
+                        warn!(
+                            "<yellow>Prometheus {}: {} targets down, {} rule groups with problems.</>",
+                            namespace,
+                            t.len(),
+                            r.len()
+                        );
+                    }
+                    let er = anyhow!("Empty {}.", filename);
+                    let report = prometheus::render_problems(&t, &r);
+                    match ctx.write_file(&folders[3], report.as_bytes(), &filename, er) {
+                        Ok(_) => info!("File has been created {}/{}", &folders[3], &filename),
+                        Err(e) => warn!("{}", e),
+                    }
+                }
+                (Some(Err(e)), _) | (_, Some(Err(e))) => {
+                    warn!("Prometheus problems: {}", e);
+                    ctx.record_failure(
+                        &folders[3],
+                        &filename,
+                        "parse targets.json and rules.json",
+                        &e,
+                    );
+                }
+                _ => {}
+            }
+        }
+        ctx.record_outputs("prometheus", before);
+    }
+    if config_file.skip_app_collectors {
+        ctx.record_coverage("limits", CoverageOutcome::Disabled);
+    } else {
+        let before = ctx.output_counts();
+        let r = limits::collect_limits(&collector, &folders[3]).await;
+        ctx.record_collected("limits", before, &r);
+        if let Err(e) = r {
+            warn!("Limits: {}", e);
+            ctx.record_folder(&folders[3], false);
+        }
+    }
+    if config_file.collect_disk_usage {
+        let before = ctx.output_counts();
+        let r = disk_usage::collect_disk_usage(
+            app_access.clone(),
+            &config_file.disk_usage_paths,
+            &ctx,
+            &folders[3],
+        )
+        .await;
+        ctx.record_collected("disk_usage", before, &r);
+        if let Err(e) = r {
+            warn!("Disk usage: {}", e);
+            ctx.record_folder(&folders[3], false);
+        }
+    } else {
+        ctx.record_coverage("disk_usage", CoverageOutcome::Disabled);
+    }
+    if config_file.collect_jvm_gc {
+        let max_bytes = match config_file.jvm_gc_log_max_mb {
+            0 => jvm_gc::DEFAULT_GC_LOG_MAX_BYTES,
+            mb => mb * 1024 * 1024,
+        };
+        let before = ctx.output_counts();
+        let r = jvm_gc::collect_jvm_gc(app_access.clone(), max_bytes, &ctx, &folders[3]).await;
+        ctx.record_collected("jvm_gc", before, &r);
+        if let Err(e) = r {
+            warn!("JVM diagnostics: {}", e);
+            ctx.record_folder(&folders[3], false);
+        }
+    } else {
+        ctx.record_coverage("jvm_gc", CoverageOutcome::Disabled);
+    }
+    //files declared in custom_collectors.file_copies, under apps/files_<ns>_<pod>.
+    options.phase(&ctx, "file copies")?;
+    let log_files_max_bytes = match config_file.log_files_max_mb {
+        0 => log_files::DEFAULT_LOG_FILE_MAX_BYTES,
+        mb => mb * 1024 * 1024,
+    };
+    let collect_log_files = |p: &PodInfo, container: &str, globs: &[String], folder: &str| {
+        let ctx = ctx.clone();
+        let p = p.clone();
+        let container = container.to_string();
+        let globs = globs.to_vec();
+        let folder = folder.to_string();
+        let scope = LogScope::pod(&p.namespace, &p.name, &container);
+        logging::scoped(scope, async move {
+            let r = match fs::create_dir_all(&folder) {
+                Ok(_) => {
+                    log_files::collect_log_files(
+                        &p,
+                        &container,
+                        &globs,
+                        log_files_max_bytes,
+                        &folder,
+                        &ctx,
+                    )
+                    .await
+                }
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = r {
+                warn!("Log files of {}: {}", p.name, e);
+                ctx.record_folder(&folder, false);
+            }
+        })
+    };
+    for fc in &config_file.custom_collectors.file_copies {
+        let coverage = format!("file_copies {}", fc.selector);
+        let fc_pods =
+            match get_pod_list(collector.pod_access(), fc.selector.clone(), "".to_string()).await {
+                Ok(p) => p,
+                Err(e) => {
+                    warn!("File copies {}: {}", fc.selector, e);
+                    ctx.record_folder(&folders[3], false);
+                    let error = e.to_string();
+                    ctx.record_coverage(&coverage, CoverageOutcome::Failed { error });
+                    continue;
+                }
+            };
+        if fc_pods.is_empty() {
+            warn!("File copies: no pod matches the selector {}.", fc.selector);
+            let reason = format!("no pods matched selector {}", fc.selector);
+            ctx.record_coverage(&coverage, CoverageOutcome::Skipped { reason });
+        }
+        for p in fc_pods {
+            let container = if fc.container.is_empty() {
+                p.containers[0].clone()
+            } else {
+                fc.container.clone()
+            };
+            let local_dir =
+                ctx.subfolder(&folders[3], &format!("files_{}_{}", p.namespace, p.name));
+            let scope = LogScope::pod(&p.namespace, &p.name, &container);
+            let copies = async {
+                let mut files = 0;
+                for path in &fc.paths {
+                    match copy_from_pod(
+                        p.api.clone(),
+                        &p.name,
+                        &container,
+                        path,
+                        &local_dir,
+                        fc.max_bytes,
+                        &ctx,
+                    )
+                    .await
+                    {
+                        Ok(written) => {
+                            info!("Files have been copied {} to {}", path, &local_dir);
+                            files += written.len();
+                        }
+                        Err(e) => {
+                            warn!("{}", e);
+                            let filename = format!(
+                                "files_{}_{}{}",
+                                p.namespace,
+                                p.name,
+                                path.replace('/', "_")
+                            );
+                            let attempted = format!("exec tar cf - {}", path);
+                            ctx.record_failure(&folders[3], &filename, &attempted, &e);
+                            let error = e.to_string();
+                            ctx.record_coverage(&coverage, CoverageOutcome::Failed { error });
+                        }
+                    }
+                }
+                files
+            };
+            let files = logging::scoped(scope, copies).await;
+            ctx.record_coverage(&coverage, CoverageOutcome::Collected { files });
+            if !fc.log_files.is_empty() {
+                let before = ctx.output_counts();
+                let folder = ctx.subfolder(
+                    &format!("{}/custom", &folders[3]),
+                    &format!("{}_{}", p.namespace, p.name),
+                );
+                collect_log_files(&p, &container, &fc.log_files, &folder).await;
+                ctx.record_outputs(&coverage, before);
+            }
+        }
+    }
+    //log files inside the containers of the app components, app_selectors.<component>.log_files.
+    for (component, _) in components::APP_COMPONENTS {
+        let Some(globs) = config_file
+            .app_selectors
+            .get(component)
+            .map(|s| &s.log_files)
+            .filter(|g| !g.is_empty())
+        else {
+            continue;
+        };
+        let coverage = format!("log_files {}", component);
+        //copied with an exec into the pods.
+        if config_file.skip_app_collectors || config_file.read_only {
+            ctx.record_coverage(&coverage, CoverageOutcome::Disabled);
+            continue;
+        }
+        let scope = components::component_scope(&config_file, component);
+        let component_pods = match scope.pods(&collector.access).await {
+            Ok(p) => p,
+            Err(e) => {
+                warn!("Log files of {}: {}", component, e);
+                ctx.record_folder(&folders[3], false);
+                let error = e.to_string();
+                ctx.record_coverage(&coverage, CoverageOutcome::Failed { error });
+                continue;
+            }
+        };
+        if component_pods.is_empty() {
+            ctx.record_coverage(&coverage, scope.skipped());
+        }
+        for p in component_pods {
+            let before = ctx.output_counts();
+            let folder = ctx.subfolder(
+                &format!("{}/{}", &folders[3], component),
+                &format!("{}_{}", p.namespace, p.name),
+            );
+            collect_log_files(&p, &p.containers[0], globs, &folder).await;
+            ctx.record_outputs(&coverage, before);
+        }
+    }
+
+    //site specific collectors, after the built-in ones.
+    if !config_file.plugins.is_empty() {
+        options.phase(&ctx, "plugins")?;
+        let before = ctx.output_counts();
+        plugins::run_plugins(&config_file, &folders[3], ctx.clone()).await;
+        ctx.record_outputs("plugins", before);
+    }
+
+    //findings scan over the collected logs.
+    events_cancel.cancel();
+    match events_watch.await {
+        Ok(lines) if lines.is_empty() => info!("No event during the collection."),
+        Ok(lines) => {
+            let er = anyhow!("No event during the collection.");
+            match ctx.write_file(
+                &folders[1],
+                (lines.join("\n") + "\n").as_bytes(),
+                events::EVENTS_DURING_COLLECTION_FILE,
+                er,
+            ) {
+                Ok(_) => info!(
+                    "File has been created {}/{}",
+                    &folders[1],
+                    events::EVENTS_DURING_COLLECTION_FILE
+                ),
+                Err(e) => warn!("{}", e),
+            }
+        }
+        Err(e) => warn!("Event watcher: {}", e),
+    }
+
+    options.phase(&ctx, "findings")?;
+    match findings::scan_directory(
+        Path::new(&folders[5]),
+        &config_file.findings_patterns,
+        config_file.findings_max_matches_per_file,
+    )
+    .and_then(|f| {
+        info!("{} findings found on the collected logs.", f.len());
+        findings::write_findings(&ctx, &folders[5], &f)
+    }) {
+        Ok(_) => info!(
+            "File has been created {}/{}",
+            &folders[5],
+            findings::FINDINGS_REPORT_FILE
+        ),
+        Err(e) => warn!("{}", e),
+    }
+
+    //known issues rules.
+    if !config_file.rules_file.is_empty() {
+        let rules_path = Path::new(&config_file.rules_file);
+        match rules::read_rules(rules_path).and_then(|r| {
+            let results = rules::evaluate_rules(&r, Path::new(&folders[5]));
+            info!(
+                "{} of {} known issue rules matched.",
+                results.iter().filter(|r| r.matched()).count(),
+                results.len()
+            );
+            rules::write_known_issues_report(&ctx, &folders[5], &results)
+        }) {
+            Ok(_) => info!(
+                "File has been created {}/{}",
+                &folders[5],
+                rules::KNOWN_ISSUES_REPORT_FILE
+            ),
+            Err(e) => warn!("Rules file {}: {}", rules_path.display(), e),
+        }
+    }
+
+    //deprecation warnings of the whole run, read from the responses of the client built by the tool.
+    let warnings = ctx.render_api_warnings();
+    if !warnings.is_empty() {
+        let er = anyhow!("No api server warning.");
+        match ctx.write_file(
+            &folders[1],
+            warnings.as_bytes(),
+            api_warnings::API_WARNINGS_FILE,
+            er,
+        ) {
+            Ok(_) => info!(
+                "File has been created {}/{}",
+                &folders[1],
+                api_warnings::API_WARNINGS_FILE
+            ),
+            Err(e) => warn!("{}", e),
+        }
+    }
+
+    //the logs are classified for manifest.json, their formats are counted in the breakdown.
+    ctx.record_log_formats();
+    //where the bytes went, the archive size is mostly a few namespaces or files.
+    match size_breakdown::file_sizes(Path::new(&folders[5])) {
+        Ok(files) => {
+            let breakdown = size_breakdown::render_breakdown(
+                &files,
+                &config_file.context_namespace,
+                &ctx.log_formats(),
+            );
+            breakdown.lines().for_each(|l| info!("{}", l));
+            let er = anyhow!("Empty {}.", size_breakdown::SIZE_BREAKDOWN_FILE);
+            match ctx.write_file(
+                &folders[5],
+                breakdown.as_bytes(),
+                size_breakdown::SIZE_BREAKDOWN_FILE,
+                er,
+            ) {
+                Ok(_) => info!(
+                    "File has been created {}/{}",
+                    &folders[5],
+                    size_breakdown::SIZE_BREAKDOWN_FILE
+                ),
+                Err(e) => warn!("{}", e),
+            }
+        }
+        Err(e) => warn!("Size breakdown: {}", e),
+    }
+
+    //one-liners out of what the collectors found.
+    let health_lines = health::summary_lines(&ctx.health.lock().unwrap());
+    health_lines.iter().for_each(|l| info!("<cyan>{}</>", l));
+    let er = anyhow!("Empty health summary.");
+    let summary = health_lines.join("\n") + "\n";
+    match ctx.write_file(
+        &folders[5],
+        summary.as_bytes(),
+        health::HEALTH_SUMMARY_FILE,
+        er,
+    ) {
+        Ok(_) => info!(
+            "File has been created {}/{}",
+            &folders[5],
+            health::HEALTH_SUMMARY_FILE
+        ),
+        Err(e) => warn!("{}", e),
+    }
+
+    //the execs of the whole run as seen by the access layer, to re-run them by hand.
+    if let Err(e) = exec::write_commands_executed(&ctx, &folders[3]) {
+        warn!("{}", e);
+    }
+
+    match ctx.write_manifest(&folders[5]) {
+        Ok(_) => info!(
+            "File has been created {}/{}",
+            &folders[5],
+            context::MANIFEST_FILE
+        ),
+        Err(e) => warn!("{}", e),
+    }
+    collection_info.collected_files = ctx
+        .manifest()
+        .values()
+        .filter(|e| e.status == context::FileStatus::Ok)
+        .count();
+    collection_info.collected_bytes = dir_size(Path::new(&folders[5]))
+        .inspect_err(|e| warn!("{}", e))
+        .unwrap_or_default();
+    collection_info.phase_durations_seconds = ctx.phase_durations();
+    collection_info.coverage = ctx.coverage();
+    //up to now in the archive, the final counts in the run summary.
+    self_usage::sample_memory(&ctx.usage);
+    collection_info.tool_usage = Some(ctx.usage.snapshot());
+    collection_info.finish(ctx.phase_results());
+    match serde_json::to_string_pretty(&collection_info)
+        .map_err(anyhow::Error::from)
+        .and_then(|s| {
+            write_atomic(
+                &Path::new(&folders[5]).join(COLLECTION_INFO_FILE),
+                s.as_bytes(),
+            )
+        }) {
+        Ok(_) => info!(
+            "File has been created {}/{}",
+            &folders[5], COLLECTION_INFO_FILE
+        ),
+        Err(e) => warn!("{}", e),
+    }
+
+    //tar file process
+    options.phase(&ctx, "archive")?;
+    match remove_tmp_files(Path::new(&folders[5])) {
+        Ok(removed) => removed
+            .iter()
+            .for_each(|r| warn!("Unfinished write removed {}", r.display())),
+        Err(e) => warn!("{}", e),
+    }
+    match verify::write_checksums(Path::new(&folders[5])) {
+        Ok(_) => info!(
+            "File has been created {}/{}",
+            &folders[5],
+            verify::CHECKSUMS_FILE
+        ),
+        Err(e) => warn!("{}", e),
+    }
+
+    let path = format!("{}/{}", &folders[7], &folders[4]);
+    info!(
+        "tar file is being created and then then it will be copied to the following path ...{}",
+        &path
+    );
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ")
+            .template("[{elapsed_precise}] {spinner:.yellow} {msg:.yellow}")?,
+    );
+    spinner.enable_steady_tick(Duration::from_millis(100)); // Update every 100ms
+    spinner.set_message("this action will take a few minutes...");
+
+    let compression = config_file
+        .archive_compression_level
+        .map(Compression::new)
+        .unwrap_or_default();
+    let tool_log = options
+        .log_file
+        .as_ref()
+        .map(|antlog| tool_log_members(antlog, run_started.into(), &ctx))
+        .unwrap_or_default();
+    let built = archive::build_archive(
+        Path::new(&folders[5]),
+        folders[7].split('/').next_back().unwrap(),
+        Path::new(&path),
+        compression,
+        &options.cancel,
+        false,
+        &tool_log,
+    );
+
+    spinner.finish_and_clear();
+    if built.is_ok() {
+        info!("tar file has been created on ... {}", &path);
+        //Finish log Collection Msg.
+        info!("<green>LOG collection has been completed!!</>");
+    }
+
+    info!("<yellow>Starting Cleaning Phase!!</>");
+    let mut archived = false;
+    match built.and_then(|_| move_archive(Path::new(&path), Path::new(&folders[6]))) {
+        Ok(moved) => {
+            archived = true;
+            let path = moved.display().to_string();
+            info!("tar file {} integrity its OK", path);
+            collection_info.archive_path = Some(path.clone());
+            collection_info.archive_size = fs::metadata(&path).ok().map(|m| m.len());
+            collection_info.archive_sha256 = sha256_file(Path::new(&path)).ok();
+            if incremental_mode && config_file.current_logs {
+                match incremental::write_state(&state_path, &new_state.lock().unwrap()) {
+                    Ok(_) => info!("Incremental state has been updated {}", &state_path),
+                    Err(e) => warn!("{}", e),
+                }
+            }
+        }
+        Err(e) => {
+            warn!("{}", e);
+            collection_info.classification = RunClassification::Failed;
+            collection_info.exit_code = RunClassification::Failed.exit_code();
+        }
+    }
+
+    //an unfinished archive is resumed from the collection folder (--resume).
+    if archived {
+        match fs::remove_dir_all(&folders[5]) {
+            Ok(_) => info!("Folder has been remove {}", folders[5]),
+            Err(e) => warn!("{}", e),
+        }
+    } else {
+        warn!("Collection folder kept for --resume {}", folders[5]);
+    }
+    if let Some(a) = &ctx.anonymizer {
+        //the mapping stays next to the archive, never inside it.
+        match a.lock().unwrap().write_map(&folders[6]) {
+            Ok(p) => info!("Anonymization map has been created {}", p),
+            Err(e) => warn!("{}", e),
+        }
+    }
+    if config_file.retention_max_archives > 0 {
+        match apply_retention(&folders[6], &context, config_file.retention_max_archives) {
+            Ok(removed) => removed
+                .iter()
+                .for_each(|r| info!("Archive removed by the retention policy {}", r)),
+            Err(e) => warn!("{}", e),
+        }
+    }
+    info!("<yellow>Finishing Cleaning Phase!!</>");
+    if !config_file.pushgateway_url.is_empty() {
+        //the archive phase is over now.
+        collection_info.phase_durations_seconds = ctx.phase_durations();
+        let url = pushgateway::push_url(&config_file.pushgateway_url, &context);
+        match pushgateway::push(&url, pushgateway::render_metrics(&collection_info)).await {
+            Ok(_) => info!(
+                "Metrics have been pushed to {}",
+                &config_file.pushgateway_url
+            ),
+            Err(e) => warn!("Metrics push to {}: {}", &config_file.pushgateway_url, e),
+        }
+    }
+    drop(auth_check);
+    drop(memory_sampler);
+    self_usage::sample_memory(&ctx.usage);
+    let usage = ctx.usage.snapshot();
+    info!("Tool usage: {}.", usage.render());
+    collection_info.tool_usage = Some(usage);
+    //what became of each collector of the configuration, the failed ones first.
+    info!("Coverage:");
+    ctx.render_coverage().iter().for_each(|l| info!("  {}", l));
+    for e in &collection_info.empty_namespaces {
+        info!("  {}", namespaces::describe(e));
+    }
+    logging::flush_warnings(&ctx.log_warnings);
+    info!("<green>END!!</>");
+    Ok(collection_info)
+}
+
+fn args(a: &[&str]) -> Vec<String> {
+    a.iter().map(|s| s.to_string()).collect()
+}
+
+fn helm_command(config: &ConfigFile, kubeconfig: &SubprocessKubeconfig) -> std::process::Command {
+    let mut cmd = std::process::Command::new("helm");
+    proxy::set_proxy_env(&mut cmd, config);
+    //inside the cluster helm picks up the service account on its own.
+    if config.in_cluster {
+        return cmd;
+    }
+    //helm only takes a single file in --kubeconfig, a merged list and the direct authentication
+    //go through KUBECONFIG.
+    kubeconfig.apply(&mut cmd);
+    cmd.arg(format!("--kube-context={}", config.context_name));
+    cmd
+}
+
+//the tool log goes through the anonymizer as well when enabled.
+//the segments of the tool log written during the run, rotated ones included, oldest first.
+fn tool_log_members(
+    antlog: &str,
+    since: std::time::SystemTime,
+    ctx: &RunContext,
+) -> Vec<(String, Vec<u8>)> {
+    logging::read_segments(Path::new(antlog), since)
+        .into_iter()
+        .filter_map(|(path, content)| {
+            let name = path.display().to_string();
+            match content {
+                Ok(content) if ctx.anonymizer.is_some() => {
+                    let content = ctx.anonymize(&String::from_utf8_lossy(&content));
+                    Some((name, content.into_bytes()))
+                }
+                Ok(content) => Some((name, content)),
+                Err(e) => {
+                    warn!("{}: {}", name, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+//the archive of a collection folder left by an interrupted archive step, continued from its last
+//checkpoint then moved to the output directory like a regular one.
+pub fn resume_archive(config_file: &ConfigFile, collection: &Path) -> Result<PathBuf> {
+    let collection = collection.canonicalize()?;
+    let output = output_directory(config_file);
+    check_not_nested(&collection, &[("output directory", Path::new(&output))])?;
+    let work_dir = collection
+        .parent()
+        .ok_or_else(|| anyhow!("{} has no parent folder.", collection.display()))?;
+    let name = collection
+        .file_name()
+        .ok_or_else(|| anyhow!("{} is not a collection folder.", collection.display()))?
+        .to_string_lossy();
+    let path = work_dir.join(format!("{}.tar.gz", name));
+    let compression = config_file
+        .archive_compression_level
+        .map(Compression::new)
+        .unwrap_or_default();
+    info!("tar file is being resumed {}", path.display());
+    archive::build_archive(
+        &collection,
+        &work_dir.file_name().unwrap_or_default().to_string_lossy(),
+        &path,
+        compression,
+        &CancellationToken::new(),
+        true,
+        &[],
+    )?;
+    let moved = move_archive(&path, Path::new(&output))?;
+    fs::remove_dir_all(&collection)?;
+    info!("Folder has been remove {}", collection.display());
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, json_client, TempDir};
+
+    fn read(name: &str) -> String {
+        fs::read_to_string(fixture(&format!("run/{}", name))).unwrap()
+    }
+
+    #[tokio::test]
+    async fn run_collection_lists_and_archives_the_pods_of_a_mocked_cluster() {
+        let dir = TempDir::new();
+        fs::create_dir_all(dir.path().join("output")).unwrap();
+        let client = json_client(&[
+            ("/api/v1/nodes", read("nodes.json")),
+            ("/api/v1/namespaces/web/pods", read("pods.json")),
+            ("/api/v1/namespaces/web/events", read("events.json")),
+            (
+                "/api/v1/namespaces/web/pods/web-0/log",
+                "GET / 200\n".to_string(),
+            ),
+        ]);
+        let config = ConfigFile {
+            context_name: "test".to_string(),
+            context_namespace: vec!["web".to_string()],
+            output_directory_path: dir.path().join("output").display().to_string(),
+            work_directory_path: dir.path().join("work").display().to_string(),
+            current_logs: true,
+            read_only: true,
+            skip_app_collectors: true,
+            ..Default::default()
+        };
+        let options = RunOptions {
+            client: Some(client),
+            assume_yes: true,
+            ..Default::default()
+        };
+        let report = run_collection(config, options).await.unwrap();
+
+        //the pod listing reached the log collection.
+        assert_eq!(report.object_counts.as_ref().map(|c| c.pods()), Some(1));
+        assert_eq!(
+            report.coverage["pod_logs"],
+            CoverageOutcome::Collected { files: 1 }
+        );
+        assert_eq!(report.coverage["jvm_gc"], CoverageOutcome::Disabled);
+        assert_eq!(report.coverage["node_debug"], CoverageOutcome::Disabled);
+        //kubectl, helm and the endpoints the mock does not serve fail.
+        assert_eq!(report.classification, RunClassification::Partial);
+        assert!(report.read_only);
+
+        //the archive is moved to the output directory, the collection folder removed.
+        let archive = PathBuf::from(report.archive_path.as_deref().unwrap());
+        assert_eq!(archive.parent(), Some(dir.path().join("output").as_path()));
+        assert_eq!(
+            report.archive_size,
+            fs::metadata(&archive).ok().map(|m| m.len())
+        );
+        assert_eq!(report.archive_sha256, sha256_file(&archive).ok());
+        let leftovers = fs::read_dir(dir.path().join("work")).unwrap().count();
+        assert_eq!(leftovers, 0);
+        let mut members = vec![];
+        let gz = flate2::read::MultiGzDecoder::new(fs::File::open(&archive).unwrap());
+        for entry in tar::Archive::new(gz).entries().unwrap() {
+            members.push(entry.unwrap().path().unwrap().display().to_string());
+        }
+        for member in [
+            "work/pods/logs_current_web_web-0_nginx.log",
+            "work/pods/web_web-0.yaml",
+            "work/collection_info.json",
+            "work/manifest.json",
+        ] {
+            assert!(
+                members.iter().any(|m| m == member),
+                "{} not archived",
+                member
+            );
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::{
+    api::core::v1::{Container, Event, Node, Pod},
+    apimachinery::pkg::api::resource::Quantity,
+};
+use kube::{api::ListParams, Api, ResourceExt};
+use simplelog::{__private::log::warn, info};
+
+use std::collections::BTreeMap;
+
+use crate::{collector::Collector, qos::quantity_value, timeline::event_time, PodInfo};
+
+pub const SCHEDULING_HEADROOM_FILE: &str = "scheduling_headroom.txt";
+
+//cpu in cores and memory in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Resources {
+    pub cpu: f64,
+    pub memory: f64,
+}
+
+impl Resources {
+    fn from_map(map: Option<&BTreeMap<String, Quantity>>) -> Self {
+        let get = |name: &str| {
+            map.and_then(|m| m.get(name))
+                .and_then(quantity_value)
+                .unwrap_or(0.0)
+        };
+        Resources {
+            cpu: get("cpu"),
+            memory: get("memory"),
+        }
+    }
+
+    fn add(&mut self, other: Resources) {
+        self.cpu += other.cpu;
+        self.memory += other.memory;
+    }
+
+    fn max(self, other: Resources) -> Resources {
+        Resources {
+            cpu: self.cpu.max(other.cpu),
+            memory: self.memory.max(other.memory),
+        }
+    }
+}
+
+//what the scheduler reserves: the sum of the containers or the biggest init container, plus the overhead.
+pub fn pod_requests(pod: &Pod) -> Resources {
+    let Some(spec) = pod.spec.as_ref() else {
+        return Resources::default();
+    };
+    let requests =
+        |c: &Container| Resources::from_map(c.resources.as_ref().and_then(|r| r.requests.as_ref()));
+    let mut total = Resources::default();
+    spec.containers.iter().for_each(|c| total.add(requests(c)));
+    let mut total = spec
+        .init_containers
+        .iter()
+        .flatten()
+        .fold(total, |t, c| t.max(requests(c)));
+    total.add(Resources::from_map(spec.overhead.as_ref()));
+    total
+}
+
+fn phase(pod: &Pod) -> String {
+    pod.status
+        .as_ref()
+        .and_then(|s| s.phase.clone())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeHeadroom {
+    pub node: String,
+    pub allocatable: Resources,
+    pub requested: Resources,
+    pub pods: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PendingPod {
+    //"<namespace>/<name>".
+    pub pod: String,
+    pub requests: Resources,
+    //message of the latest FailedScheduling event, empty without one.
+    pub reason: String,
+}
+
+//pods bound to a node and not finished count against its allocatable.
+pub fn node_headroom(nodes: &[Node], pods: &[Pod]) -> Vec<NodeHeadroom> {
+    let mut out = nodes
+        .iter()
+        .map(|n| {
+            (
+                n.name_any(),
+                NodeHeadroom {
+                    node: n.name_any(),
+                    allocatable: Resources::from_map(
+                        n.status.as_ref().and_then(|s| s.allocatable.as_ref()),
+                    ),
+                    ..Default::default()
+                },
+            )
+        })
+        .collect::<BTreeMap<String, NodeHeadroom>>();
+    for p in pods {
+        let phase = phase(p);
+        if phase == "Succeeded" || phase == "Failed" {
+            continue;
+        }
+        let Some(node) = p.spec.as_ref().and_then(|s| s.node_name.as_ref()) else {
+            continue;
+        };
+        if let Some(h) = out.get_mut(node) {
+            h.requested.add(pod_requests(p));
+            h.pods += 1;
+        }
+    }
+    out.into_values().collect()
+}
+
+pub fn pending_pods(pods: &[Pod], events: &[Event]) -> Vec<PendingPod> {
+    let mut reasons: BTreeMap<(String, String), (String, String)> = BTreeMap::new();
+    for e in events
+        .iter()
+        .filter(|e| e.reason.as_deref() == Some("FailedScheduling"))
+    {
+        let key = (
+            e.involved_object.namespace.clone().unwrap_or_default(),
+            e.involved_object.name.clone().unwrap_or_default(),
+        );
+        let time = event_time(e).map(|t| t.to_rfc3339()).unwrap_or_default();
+        let message = e.message.clone().unwrap_or_default();
+        match reasons.get(&key) {
+            Some((t, _)) if *t >= time => {}
+            _ => {
+                reasons.insert(key, (time, message));
+            }
+        }
+    }
+    pods.iter()
+        .filter(|p| phase(p) == "Pending")
+        .map(|p| {
+            let key = (p.namespace().unwrap_or_default(), p.name_any());
+            PendingPod {
+                pod: format!("{}/{}", key.0, key.1),
+                requests: pod_requests(p),
+                reason: reasons.remove(&key).map(|r| r.1).unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+fn cpu(cores: f64) -> String {
+    format!("{}m", (cores * 1000.0).round() as i64)
+}
+
+fn memory(bytes: f64) -> String {
+    format!("{}Mi", (bytes / (1024.0 * 1024.0)).round() as i64)
+}
+
+fn percent(used: f64, total: f64) -> String {
+    if total > 0.0 {
+        format!("{:.0}%", used / total * 100.0)
+    } else {
+        "-".to_string()
+    }
+}
+
+pub fn render_report(scope: &str, nodes: &[NodeHeadroom], pending: &[PendingPod]) -> String {
+    let mut out = format!(
+        "pod requests against node allocatable ({})\n\n{:<40} {:>5} {:>22} {:>5} {:>22} {:>5}\n",
+        scope, "NODE", "PODS", "CPU REQ/ALLOC", "CPU%", "MEM REQ/ALLOC", "MEM%"
+    );
+    for n in nodes {
+        out.push_str(&format!(
+            "{:<40} {:>5} {:>22} {:>5} {:>22} {:>5}\n",
+            n.node,
+            n.pods,
+            format!("{}/{}", cpu(n.requested.cpu), cpu(n.allocatable.cpu)),
+            percent(n.requested.cpu, n.allocatable.cpu),
+            format!(
+                "{}/{}",
+                memory(n.requested.memory),
+                memory(n.allocatable.memory)
+            ),
+            percent(n.requested.memory, n.allocatable.memory),
+        ));
+    }
+    out.push_str(&format!("\npending pods: {}\n", pending.len()));
+    for p in pending {
+        out.push_str(&format!(
+            "  {} requests cpu={} memory={}\n",
+            p.pod,
+            cpu(p.requests.cpu),
+            memory(p.requests.memory)
+        ));
+        if !p.reason.is_empty() {
+            out.push_str(&format!("    FailedScheduling: {}\n", p.reason));
+        }
+    }
+    out
+}
+
+//all the pods of the cluster when listing them is allowed, the collected ones otherwise.
+pub async fn collect_headroom<A>(
+    collector: &Collector,
+    pods_list: &[PodInfo<A>],
+    folder: &str,
+) -> Result<()> {
+    let nodes: Api<Node> = Api::all(collector.client.clone());
+    let nodes = nodes.list(&ListParams::default()).await?.items;
+    let all_pods: Api<Pod> = Api::all(collector.client.clone());
+    let (scope, pods) = match all_pods.list(&ListParams::default()).await {
+        Ok(list) => ("pods of all namespaces", list.items),
+        Err(e) => {
+            warn!(
+                "Listing the pods of all namespaces: {}, using the collected ones.",
+                e
+            );
+            (
+                "collected pods only",
+                pods_list.iter().map(|p| p.pod.clone()).collect(),
+            )
+        }
+    };
+    let events: Api<Event> = Api::all(collector.client.clone());
+    let events = events
+        .list(&ListParams::default().fields("reason=FailedScheduling"))
+        .await
+        .map(|e| e.items)
+        .unwrap_or_default();
+    let report = render_report(
+        scope,
+        &node_headroom(&nodes, &pods),
+        &pending_pods(&pods, &events),
+    );
+    let er = anyhow!("Empty {}.", SCHEDULING_HEADROOM_FILE);
+    collector
+        .ctx
+        .write_file(folder, report.as_bytes(), SCHEDULING_HEADROOM_FILE, er)?;
+    info!(
+        "File has been created {}/{}",
+        folder, SCHEDULING_HEADROOM_FILE
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn pod(name: &str, phase: &str, node: Option<&str>, spec: Value) -> Pod {
+        let mut spec = spec;
+        if let Some(n) = node {
+            spec["nodeName"] = json!(n);
+        }
+        serde_json::from_value(json!({
+            "metadata": {"name": name, "namespace": "web"},
+            "spec": spec,
+            "status": {"phase": phase}
+        }))
+        .unwrap()
+    }
+
+    fn container(cpu: &str, memory: &str) -> Value {
+        json!({"name": "c", "resources": {"requests": {"cpu": cpu, "memory": memory}}})
+    }
+
+    fn node(name: &str, cpu: &str, memory: &str) -> Node {
+        serde_json::from_value(json!({
+            "metadata": {"name": name},
+            "status": {"allocatable": {"cpu": cpu, "memory": memory, "pods": "110"}}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn requests_of_the_containers_the_biggest_init_and_the_overhead() {
+        let p = pod(
+            "a",
+            "Running",
+            None,
+            json!({
+                "containers": [container("250m", "256Mi"), container("0.5", "256Mi")],
+                "initContainers": [container("1", "64Mi"), container("100m", "1Gi")],
+                "overhead": {"cpu": "10m", "memory": "1Mi"}
+            }),
+        );
+        //cpu: max(0.75, 1) + 0.01, memory: max(512Mi, 1Gi) + 1Mi.
+        assert_eq!(
+            pod_requests(&p),
+            Resources {
+                cpu: 1.01,
+                memory: 1025.0 * 1024.0 * 1024.0
+            }
+        );
+    }
+
+    #[test]
+    fn missing_or_malformed_requests_count_as_zero() {
+        let p = pod(
+            "a",
+            "Running",
+            None,
+            json!({"containers": [{"name": "c"}, container("lots", "1Gi")]}),
+        );
+        assert_eq!(
+            pod_requests(&p),
+            Resources {
+                cpu: 0.0,
+                memory: 1073741824.0
+            }
+        );
+        let no_spec: Pod = serde_json::from_value(json!({"metadata": {"name": "x"}})).unwrap();
+        assert_eq!(pod_requests(&no_spec), Resources::default());
+    }
+
+    #[test]
+    fn finished_and_unbound_pods_do_not_use_the_node() {
+        let spec = || json!({"containers": [container("500m", "1Gi")]});
+        let pods = [
+            pod("running", "Running", Some("node-1"), spec()),
+            pod("pending", "Pending", Some("node-1"), spec()),
+            pod("done", "Succeeded", Some("node-1"), spec()),
+            pod("failed", "Failed", Some("node-1"), spec()),
+            pod("unbound", "Pending", None, spec()),
+            pod("elsewhere", "Running", Some("gone"), spec()),
+        ];
+        let headroom = node_headroom(&[node("node-1", "3900m", "15Gi")], &pods);
+        assert_eq!(headroom.len(), 1);
+        assert_eq!(headroom[0].pods, 2);
+        assert_eq!(headroom[0].requested.cpu, 1.0);
+        assert_eq!(headroom[0].allocatable.memory, 15.0 * 1073741824.0);
+        let report = render_report("all namespaces", &headroom, &[]);
+        assert!(report.contains("1000m/3900m"));
+        assert!(report.contains("2048Mi/15360Mi"));
+        assert!(report.contains("   26% "));
+    }
+
+    #[test]
+    fn pending_pods_with_their_latest_scheduling_failure() {
+        let event = |time: &str, message: &str| -> Event {
+            serde_json::from_value(json!({
+                "metadata": {"name": "e", "namespace": "web"},
+                "reason": "FailedScheduling",
+                "message": message,
+                "lastTimestamp": time,
+                "involvedObject": {"kind": "Pod", "name": "pending", "namespace": "web"}
+            }))
+            .unwrap()
+        };
+        let events = [
+            event(
+                "2026-10-17T08:00:00Z",
+                "0/3 nodes are available: 3 Insufficient cpu.",
+            ),
+            event(
+                "2026-10-17T07:00:00Z",
+                "0/3 nodes are available: 3 Insufficient memory.",
+            ),
+        ];
+        let pods = [
+            pod(
+                "pending",
+                "Pending",
+                None,
+                json!({"containers": [container("2", "1Gi")]}),
+            ),
+            pod(
+                "quiet",
+                "Pending",
+                None,
+                json!({"containers": [container("1", "1Gi")]}),
+            ),
+            pod(
+                "running",
+                "Running",
+                Some("node-1"),
+                json!({"containers": []}),
+            ),
+        ];
+        let pending = pending_pods(&pods, &events);
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].pod, "web/pending");
+        assert_eq!(
+            pending[0].reason,
+            "0/3 nodes are available: 3 Insufficient cpu."
+        );
+        assert_eq!(pending[1].reason, "");
+        let report = render_report("web", &[], &pending);
+        assert!(report.contains("pending pods: 2\n  web/pending requests cpu=2000m memory=1024Mi\n    FailedScheduling: 0/3 nodes are available: 3 Insufficient cpu.\n  web/quiet"));
+    }
+}
@@ -0,0 +1,359 @@
+use futures_util::future::BoxFuture;
+use hyper::{
+    body::{Buf, HttpBody},
+    HeaderMap, Request, Response,
+};
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use tower::{Layer, Service};
+
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use crate::AbortOnDrop;
+
+pub const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const PROC_STATUS: &str = "/proc/self/status";
+
+//what the run cost the cluster and the host, the evidence when the tool is said to have
+//hammered the api server. Shared by the RunContext, the client layer and the fake access.
+#[derive(Debug, Default)]
+pub struct UsageCounters {
+    api_requests: AtomicU64,
+    exec_sessions: AtomicU64,
+    subprocesses: AtomicU64,
+    bytes_downloaded: AtomicU64,
+    //0 until a sample is taken.
+    peak_memory_bytes: AtomicU64,
+}
+
+impl UsageCounters {
+    pub fn api_request(&self) {
+        self.api_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn exec_session(&self) {
+        self.exec_sessions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn subprocess(&self) {
+        self.subprocesses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn downloaded(&self, bytes: u64) {
+        self.bytes_downloaded.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn memory_sample(&self, bytes: u64) {
+        self.peak_memory_bytes.fetch_max(bytes, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ToolUsage {
+        let peak = self.peak_memory_bytes.load(Ordering::Relaxed);
+        ToolUsage {
+            api_requests: self.api_requests.load(Ordering::Relaxed),
+            exec_sessions: self.exec_sessions.load(Ordering::Relaxed),
+            subprocesses: self.subprocesses.load(Ordering::Relaxed),
+            bytes_downloaded: self.bytes_downloaded.load(Ordering::Relaxed),
+            peak_memory_bytes: (peak > 0).then_some(peak),
+        }
+    }
+}
+
+//the counters at the end of the run, in collection_info.json.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ToolUsage {
+    //every request to the api server, exec and port-forward upgrades included.
+    pub api_requests: u64,
+    pub exec_sessions: u64,
+    //kubectl, helm, plugins and df.
+    pub subprocesses: u64,
+    //response bodies of the api server; exec and port-forward streams are not counted.
+    pub bytes_downloaded: u64,
+    //peak resident memory of the tool, None where /proc/self/status is missing (not Linux).
+    pub peak_memory_bytes: Option<u64>,
+}
+
+impl ToolUsage {
+    pub fn render(&self) -> String {
+        format!(
+            "{} API requests, {} exec sessions, {} subprocesses, {} MiB downloaded, peak memory {}",
+            self.api_requests,
+            self.exec_sessions,
+            self.subprocesses,
+            self.bytes_downloaded / (1024 * 1024),
+            self.peak_memory_bytes
+                .map(|b| format!("{} MiB", b / (1024 * 1024)))
+                .unwrap_or_else(|| "unknown".to_string())
+        )
+    }
+}
+
+//VmHWM (peak resident set) of /proc/self/status, VmRSS on kernels without it, in bytes.
+pub fn parse_peak_memory(status: &str) -> Option<u64> {
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|l| l.strip_prefix(name))
+            .and_then(|v| v.trim().strip_suffix("kB"))
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+    };
+    field("VmHWM:").or_else(|| field("VmRSS:"))
+}
+
+pub fn sample_memory(counters: &UsageCounters) {
+    if let Some(bytes) = std::fs::read_to_string(PROC_STATUS)
+        .ok()
+        .and_then(|s| parse_peak_memory(&s))
+    {
+        counters.memory_sample(bytes);
+    }
+}
+
+//samples the memory of the tool until the returned guard is dropped.
+pub fn spawn_memory_sampler(counters: Arc<UsageCounters>, every: Duration) -> AbortOnDrop {
+    AbortOnDrop(tokio::task::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        loop {
+            interval.tick().await;
+            sample_memory(&counters);
+        }
+    }))
+}
+
+//counts the requests of a client, its exec sessions and the bytes of the response bodies.
+#[derive(Debug, Clone, Default)]
+pub struct UsageLayer(pub Arc<UsageCounters>);
+
+impl<S> Layer<S> for UsageLayer {
+    type Service = UsageService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        UsageService {
+            inner,
+            counters: self.0.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UsageService<S> {
+    inner: S,
+    counters: Arc<UsageCounters>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for UsageService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: 'static,
+{
+    type Response = Response<CountedBody<ResBody>>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        self.counters.api_request();
+        if request.uri().path().ends_with("/exec") {
+            self.counters.exec_session();
+        }
+        let response = self.inner.call(request);
+        let counters = self.counters.clone();
+        Box::pin(async move {
+            let response = response.await?;
+            Ok(response.map(|inner| CountedBody { inner, counters }))
+        })
+    }
+}
+
+pub struct CountedBody<B> {
+    inner: B,
+    counters: Arc<UsageCounters>,
+}
+
+impl<B> HttpBody for CountedBody<B>
+where
+    B: HttpBody + Unpin,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let data = ready!(Pin::new(&mut self.inner).poll_data(cx));
+        if let Some(Ok(d)) = &data {
+            self.counters.downloaded(d.remaining() as u64);
+        }
+        Poll::Ready(data)
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        access::{FakeAccess, PodAccess},
+        test_support::fixture,
+    };
+    use hyper::Body;
+    use kube::api::LogParams;
+    use tower::ServiceExt;
+
+    fn access(usage: &Arc<UsageCounters>) -> FakeAccess {
+        let mut access = FakeAccess::from_fixture(&fixture("access/cluster.json")).unwrap();
+        access.usage = usage.clone();
+        access
+    }
+
+    fn container_logs(container: &str) -> LogParams {
+        LogParams {
+            container: Some(container.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn the_fake_access_counts_requests_execs_and_log_bytes() {
+        let usage = Arc::new(UsageCounters::default());
+        let access = access(&usage);
+        //the access of a namespace counts into the same run.
+        let kafka = access.in_namespace("kafka");
+        assert_eq!(kafka.list_pods("", "").await.unwrap().len(), 2);
+        let log = kafka
+            .logs("kafka-0", container_logs("kafka"))
+            .await
+            .unwrap();
+        let command = [
+            "/bin/sh",
+            "-c",
+            "bin/kafka-topics.sh --bootstrap-server localhost:9092 --list",
+        ];
+        kafka
+            .exec(
+                "kafka-0",
+                "kafka",
+                command.iter().map(|c| c.to_string()).collect(),
+            )
+            .await
+            .unwrap();
+        //a failed call is still a request, without downloaded bytes.
+        assert!(access
+            .logs("web-0", container_logs("missing"))
+            .await
+            .is_err());
+        assert_eq!(
+            usage.snapshot(),
+            ToolUsage {
+                api_requests: 4,
+                exec_sessions: 1,
+                subprocesses: 0,
+                bytes_downloaded: log.len() as u64,
+                peak_memory_bytes: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn each_run_counts_its_own_access_only() {
+        let run = Arc::new(UsageCounters::default());
+        let other = Arc::new(UsageCounters::default());
+        access(&run)
+            .logs("web-0", container_logs("nginx"))
+            .await
+            .unwrap();
+        access(&other).list_nodes().await.unwrap();
+        assert_eq!(run.snapshot().api_requests, 1);
+        assert_eq!(run.snapshot().bytes_downloaded, "GET / 200\n".len() as u64);
+        assert_eq!(other.snapshot().api_requests, 1);
+        assert_eq!(other.snapshot().bytes_downloaded, 0);
+    }
+
+    #[tokio::test]
+    async fn the_layer_counts_requests_exec_upgrades_and_body_bytes() {
+        let usage = Arc::new(UsageCounters::default());
+        let server = tower::service_fn(|_: Request<Body>| async {
+            Ok::<_, hyper::Error>(Response::new(Body::from("0123456789")))
+        });
+        let mut service = UsageLayer(usage.clone()).layer(server);
+        for path in [
+            "/api/v1/namespaces/kafka/pods",
+            "/api/v1/namespaces/kafka/pods/kafka-0/exec",
+        ] {
+            let request = Request::get(path).body(Body::empty()).unwrap();
+            let response = service.ready().await.unwrap().call(request).await.unwrap();
+            hyper::body::to_bytes(response.into_body()).await.unwrap();
+        }
+        let counted = usage.snapshot();
+        assert_eq!(counted.api_requests, 2);
+        assert_eq!(counted.exec_sessions, 1);
+        assert_eq!(counted.bytes_downloaded, 20);
+    }
+
+    #[test]
+    fn the_peak_memory_is_kept() {
+        let usage = UsageCounters::default();
+        assert_eq!(usage.snapshot().peak_memory_bytes, None);
+        usage.memory_sample(300);
+        usage.memory_sample(100);
+        assert_eq!(usage.snapshot().peak_memory_bytes, Some(300));
+    }
+
+    #[test]
+    fn peak_memory_from_proc_status() {
+        let status = "Name:\tlogpv2\nVmPeak:\t  900 kB\nVmHWM:\t    2048 kB\nVmRSS:\t    1024 kB\n";
+        assert_eq!(parse_peak_memory(status), Some(2048 * 1024));
+        assert_eq!(parse_peak_memory("VmRSS:\t 1024 kB\n"), Some(1024 * 1024));
+        assert_eq!(parse_peak_memory("Name:\tlogpv2\n"), None);
+        assert_eq!(parse_peak_memory("VmHWM:\t unknown\n"), None);
+    }
+
+    #[test]
+    fn render_in_mib() {
+        let usage = ToolUsage {
+            api_requests: 12,
+            exec_sessions: 3,
+            subprocesses: 2,
+            bytes_downloaded: 5 * 1024 * 1024 + 10,
+            peak_memory_bytes: Some(64 * 1024 * 1024),
+        };
+        assert_eq!(
+            usage.render(),
+            "12 API requests, 3 exec sessions, 2 subprocesses, 5 MiB downloaded, peak memory 64 MiB"
+        );
+        let unknown = ToolUsage {
+            peak_memory_bytes: None,
+            ..usage
+        };
+        assert!(unknown.render().ends_with("peak memory unknown"));
+    }
+}
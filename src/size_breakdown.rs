@@ -0,0 +1,304 @@
+use anyhow::Result;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{components::APP_COMPONENTS, elastic::human, LogFormat};
+
+pub const SIZE_BREAKDOWN_FILE: &str = "size_breakdown.txt";
+pub const SIZE_BREAKDOWN_TOP: usize = 20;
+pub const NO_NAMESPACE: &str = "(none)";
+
+//file name prefixes of the app collector outputs -> component, the longest prefixes first.
+const APP_OUTPUT_PREFIXES: [(&str, &str); 8] = [
+    ("elastic_search", "elasticsearch"),
+    ("kafka_connect", "kafka_connect"),
+    ("kafka", "kafka"),
+    ("schema_registry", "schema_registry"),
+    ("hadoop", "hadoop"),
+    ("hbase", "hbase"),
+    ("prometheus", "prometheus"),
+    ("files_", "file copies"),
+];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileSize {
+    //relative to the collection folder.
+    pub path: String,
+    pub bytes: u64,
+}
+
+//every file under root, listed in the manifest or not.
+pub fn file_sizes(root: &Path) -> Result<Vec<FileSize>> {
+    let mut out = vec![];
+    let mut dirs = vec![PathBuf::from(root)];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+            } else {
+                out.push(FileSize {
+                    path: entry
+                        .path()
+                        .strip_prefix(root)
+                        .unwrap_or(&entry.path())
+                        .display()
+                        .to_string(),
+                    bytes: metadata.len(),
+                });
+            }
+        }
+    }
+    Ok(out)
+}
+
+//pods, infra, helm, apps, or the collection folder itself.
+pub fn phase(path: &str) -> String {
+    match path.split_once('/') {
+        Some((first, _)) => first.to_string(),
+        None => ".".to_string(),
+    }
+}
+
+//the configured namespace found between '/', '_' and '.' in the path, the outputs are named
+//<namespace>_<pod>... and a namespace has none of these characters.
+pub fn namespace<'a>(path: &str, namespaces: &'a [String]) -> Option<&'a str> {
+    let tokens = path.split(['/', '_', '.']).collect::<Vec<&str>>();
+    namespaces
+        .iter()
+        .find(|ns| tokens.contains(&ns.as_str()))
+        .map(|ns| ns.as_str())
+}
+
+//the app component of an apps/ output, from its folder (log_files) or its file name prefix.
+pub fn component(path: &str) -> Option<String> {
+    let rest = path.strip_prefix("apps/")?;
+    if let Some((dir, _)) = rest.split_once('/') {
+        if APP_COMPONENTS.iter().any(|(c, _)| *c == dir) || dir == "custom" {
+            return Some(dir.to_string());
+        }
+    }
+    let name = rest.rsplit('/').next().unwrap_or(rest);
+    Some(
+        APP_OUTPUT_PREFIXES
+            .iter()
+            .find(|(p, _)| name.starts_with(p) || rest.starts_with(p))
+            .map(|(_, c)| c.to_string())
+            .unwrap_or_else(|| "other".to_string()),
+    )
+}
+
+fn render_group(out: &mut String, title: &str, group: BTreeMap<String, (usize, u64)>) {
+    let mut group = group.into_iter().collect::<Vec<_>>();
+    group.sort_by_key(|(_, (_, bytes))| std::cmp::Reverse(*bytes));
+    out.push_str(&format!("\nby {}:\n", title));
+    for (name, (files, bytes)) in group {
+        out.push_str(&format!(
+            "  {:<50} {:>7} files {:>10}\n",
+            name,
+            files,
+            human(bytes)
+        ));
+    }
+}
+
+//formats: the classified logs by path (RunContext::log_formats).
+pub fn render_breakdown(
+    files: &[FileSize],
+    namespaces: &[String],
+    formats: &BTreeMap<String, LogFormat>,
+) -> String {
+    let mut by_phase: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+    let mut by_format: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+    let mut by_namespace: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+    let mut by_component: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+    for f in files {
+        let add = |group: &mut BTreeMap<String, (usize, u64)>, key: String| {
+            let g = group.entry(key).or_default();
+            g.0 += 1;
+            g.1 += f.bytes;
+        };
+        add(&mut by_phase, phase(&f.path));
+        add(
+            &mut by_namespace,
+            namespace(&f.path, namespaces)
+                .unwrap_or(NO_NAMESPACE)
+                .to_string(),
+        );
+        if let Some(c) = component(&f.path) {
+            add(&mut by_component, c);
+        }
+        if let Some(format) = formats.get(&f.path) {
+            add(&mut by_format, format.name().to_string());
+        }
+    }
+    let mut out = format!(
+        "total: {} files, {}\n",
+        files.len(),
+        human(files.iter().map(|f| f.bytes).sum())
+    );
+    render_group(&mut out, "phase", by_phase);
+    render_group(&mut out, "namespace", by_namespace);
+    render_group(&mut out, "app component", by_component);
+    if !by_format.is_empty() {
+        render_group(&mut out, "log format", by_format);
+    }
+    let mut largest = files.to_vec();
+    largest.sort_by_key(|f| std::cmp::Reverse(f.bytes));
+    out.push_str(&format!("\n{} largest files:\n", SIZE_BREAKDOWN_TOP));
+    for f in largest.iter().take(SIZE_BREAKDOWN_TOP) {
+        out.push_str(&format!("  {:>10} {}\n", human(f.bytes), f.path));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn namespaces() -> Vec<String> {
+        ["kafka", "web"].map(String::from).to_vec()
+    }
+
+    //a collection as the run leaves it: pod logs, cluster states, app outputs and copied files.
+    fn collection(dir: &TempDir) -> Vec<FileSize> {
+        for (path, bytes) in [
+            ("pods/logs_current_kafka_kafka-0_kafka.log", 4096),
+            ("pods/logs_current_web_web-0_nginx.log", 1024),
+            ("pods/node-1/describe_kafka_kafka-1.txt", 512),
+            ("infra/nodes.json", 2048),
+            ("apps/kafka_topics_describe.txt", 300),
+            ("apps/elastic_search_health.json", 100),
+            ("apps/elasticsearch/es-0/files/opt/es/logs/server.log", 700),
+            ("apps/files_web_web-0/opt/app/logs/app.log", 200),
+            ("apps/custom/web_web-0/log_files_notes.txt", 50),
+            ("manifest.json", 10),
+        ] {
+            dir.write(path, &vec![b'x'; bytes]);
+        }
+        let mut files = file_sizes(dir.path()).unwrap();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        files
+    }
+
+    #[test]
+    fn every_file_relative_to_the_collection() {
+        let dir = TempDir::new();
+        let files = collection(&dir);
+        assert_eq!(files.len(), 10);
+        assert_eq!(
+            files[0],
+            FileSize {
+                path: "apps/custom/web_web-0/log_files_notes.txt".to_string(),
+                bytes: 50
+            }
+        );
+        assert!(file_sizes(&dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn grouping_keys_of_a_path() {
+        assert_eq!(phase("pods/a.log"), "pods");
+        assert_eq!(phase("manifest.json"), ".");
+        let ns = namespaces();
+        assert_eq!(
+            namespace("pods/logs_current_web_web-0_nginx.log", &ns),
+            Some("web")
+        );
+        assert_eq!(
+            namespace("pods/node-1/describe_kafka_kafka-1.txt", &ns),
+            Some("kafka")
+        );
+        //part of a token is not the namespace.
+        assert_eq!(namespace("infra/webhooks.json", &ns), None);
+        assert_eq!(
+            component("apps/elasticsearch/es-0/files/a.log").as_deref(),
+            Some("elasticsearch")
+        );
+        assert_eq!(
+            component("apps/custom/web_web-0/notes.txt").as_deref(),
+            Some("custom")
+        );
+        assert_eq!(
+            component("apps/kafka_connect_status.json").as_deref(),
+            Some("kafka_connect")
+        );
+        assert_eq!(component("apps/kafka_topics.txt").as_deref(), Some("kafka"));
+        assert_eq!(
+            component("apps/files_web_web-0/opt/app.log").as_deref(),
+            Some("file copies")
+        );
+        assert_eq!(component("apps/unknown.txt").as_deref(), Some("other"));
+        assert_eq!(component("pods/a.log"), None);
+    }
+
+    #[test]
+    fn breakdown_by_phase_namespace_component_and_format() {
+        let dir = TempDir::new();
+        let files = collection(&dir);
+        let formats = BTreeMap::from([
+            (
+                "pods/logs_current_kafka_kafka-0_kafka.log".to_string(),
+                LogFormat::JsonLines,
+            ),
+            (
+                "pods/logs_current_web_web-0_nginx.log".to_string(),
+                LogFormat::PlainText,
+            ),
+        ]);
+        let report = render_breakdown(&files, &namespaces(), &formats);
+        assert!(report.starts_with("total: 10 files, 8.8kb\n\nby phase:\n"));
+        let lines = report.lines().collect::<Vec<_>>();
+        let group = |title: &str| {
+            let start = lines
+                .iter()
+                .position(|l| *l == format!("by {}:", title))
+                .unwrap();
+            lines[start + 1..]
+                .iter()
+                .take_while(|l| !l.is_empty())
+                .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            group("phase"),
+            vec![
+                "pods 3 files 5.5kb",
+                "infra 1 files 2.0kb",
+                "apps 5 files 1.3kb",
+                ". 1 files 10.0b"
+            ]
+        );
+        assert_eq!(
+            group("namespace"),
+            vec![
+                "kafka 3 files 4.8kb",
+                "(none) 4 files 2.8kb",
+                "web 3 files 1.2kb"
+            ]
+        );
+        //the log_files folder and the collector outputs of elasticsearch count together.
+        assert_eq!(
+            group("app component"),
+            vec![
+                "elasticsearch 2 files 800.0b",
+                "kafka 1 files 300.0b",
+                "file copies 1 files 200.0b",
+                "custom 1 files 50.0b"
+            ]
+        );
+        assert_eq!(
+            group("log format"),
+            vec!["json_lines 1 files 4.0kb", "plain_text 1 files 1.0kb"]
+        );
+        assert!(report.contains(
+            "\n20 largest files:\n       4.0kb pods/logs_current_kafka_kafka-0_kafka.log\n"
+        ));
+    }
+}
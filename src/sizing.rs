@@ -0,0 +1,177 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::{
+    api::core::v1::{Event, Pod},
+    NamespaceResourceScope,
+};
+use kube::{api::ListParams, Api, Client, Resource};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    io::{BufRead, IsTerminal, Write},
+    path::Path,
+    process::Command,
+};
+
+use crate::self_usage::UsageCounters;
+
+//rough size of the outputs of one pod (logs, description, manifest) and of one event line.
+pub const ESTIMATED_BYTES_PER_POD: u64 = 2 * 1024 * 1024;
+pub const ESTIMATED_BYTES_PER_EVENT: u64 = 512;
+const COUNT_PAGE_SIZE: u32 = 500;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct NamespaceCounts {
+    pub pods: u64,
+    pub events: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ObjectCounts {
+    pub namespaces: BTreeMap<String, NamespaceCounts>,
+}
+
+impl ObjectCounts {
+    pub fn pods(&self) -> u64 {
+        self.namespaces.values().map(|n| n.pods).sum()
+    }
+
+    pub fn events(&self) -> u64 {
+        self.namespaces.values().map(|n| n.events).sum()
+    }
+
+    pub fn estimated_bytes(&self) -> u64 {
+        self.pods() * ESTIMATED_BYTES_PER_POD + self.events() * ESTIMATED_BYTES_PER_EVENT
+    }
+}
+
+fn default_max_pods() -> u64 {
+    1000
+}
+
+fn default_max_events() -> u64 {
+    50000
+}
+
+//above these totals the collection needs --yes or an interactive confirmation.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ObjectCountLimits {
+    #[serde(default = "default_max_pods")]
+    pub max_pods: u64,
+    #[serde(default = "default_max_events")]
+    pub max_events: u64,
+}
+
+impl Default for ObjectCountLimits {
+    fn default() -> Self {
+        ObjectCountLimits {
+            max_pods: default_max_pods(),
+            max_events: default_max_events(),
+        }
+    }
+}
+
+//one object is fetched, the api server gives the remainder in remainingItemCount.
+//when it is not given the metadata is paginated.
+pub async fn count<K>(api: &Api<K>) -> Result<u64>
+where
+    K: Resource + Clone + DeserializeOwned + Debug,
+{
+    let first = api.list_metadata(&ListParams::default().limit(1)).await?;
+    if let Some(remaining) = first.metadata.remaining_item_count {
+        return Ok(first.items.len() as u64 + remaining.max(0) as u64);
+    }
+    let mut total = 0;
+    let mut lp = ListParams::default().limit(COUNT_PAGE_SIZE);
+    loop {
+        let page = api.list_metadata(&lp).await?;
+        total += page.items.len() as u64;
+        match page.metadata.continue_.filter(|c| !c.is_empty()) {
+            Some(c) => lp = lp.continue_token(&c),
+            None => return Ok(total),
+        }
+    }
+}
+
+async fn count_namespaced<K>(client: &Client, namespace: &str) -> Result<u64>
+where
+    K: Resource<Scope = NamespaceResourceScope> + Clone + DeserializeOwned + Debug,
+    <K as Resource>::DynamicType: Default,
+{
+    count(&Api::<K>::namespaced(client.clone(), namespace)).await
+}
+
+pub async fn count_objects(client: &Client, namespaces: &[String]) -> Result<ObjectCounts> {
+    let mut counts = ObjectCounts::default();
+    for ns in namespaces {
+        counts.namespaces.insert(
+            ns.clone(),
+            NamespaceCounts {
+                pods: count_namespaced::<Pod>(client, ns).await?,
+                events: count_namespaced::<Event>(client, ns).await?,
+            },
+        );
+    }
+    Ok(counts)
+}
+
+//free space of the file system holding path, from df.
+pub fn available_bytes(path: &Path, usage: &UsageCounters) -> Result<u64> {
+    usage.subprocess();
+    let o = Command::new("df").arg("-Pk").arg(path).output()?;
+    String::from_utf8_lossy(&o.stdout)
+        .lines()
+        .nth(1)
+        .and_then(|l| l.split_whitespace().nth(3))
+        .and_then(|a| a.parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+        .ok_or_else(|| anyhow!("Can not read the free space of {}.", path.display()))
+}
+
+//the reasons to stop before collecting, empty when the run can go on.
+pub fn limits_exceeded(
+    counts: &ObjectCounts,
+    limits: &ObjectCountLimits,
+    available: Option<u64>,
+) -> Vec<String> {
+    let mut reasons = vec![];
+    if counts.pods() > limits.max_pods {
+        reasons.push(format!(
+            "{} pods, more than object_count_limits.max_pods {}",
+            counts.pods(),
+            limits.max_pods
+        ));
+    }
+    if counts.events() > limits.max_events {
+        reasons.push(format!(
+            "{} events, more than object_count_limits.max_events {}",
+            counts.events(),
+            limits.max_events
+        ));
+    }
+    if let Some(a) = available.filter(|a| *a < counts.estimated_bytes()) {
+        reasons.push(format!(
+            "about {} MiB to write, only {} MiB free",
+            counts.estimated_bytes() / (1024 * 1024),
+            a / (1024 * 1024)
+        ));
+    }
+    reasons
+}
+
+//a y/yes answer on an interactive terminal, false without one.
+pub fn confirm(question: &str) -> bool {
+    if !std::io::stdin().is_terminal() {
+        return false;
+    }
+    print!("{} [y/N] ", question);
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    if std::io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
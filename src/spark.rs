@@ -0,0 +1,85 @@
+use anyhow::{anyhow, Result};
+
+use std::collections::BTreeMap;
+
+use crate::PodInfo;
+
+pub const SPARK_FOLDER: &str = "spark";
+pub const SPARK_ROLE_LABEL: &str = "spark-role";
+pub const SPARK_APP_LABEL: &str = "spark-app-selector";
+pub const SPARK_EXECUTOR_REASON: &str = "spark executor";
+
+fn label<'a, A>(p: &'a PodInfo<A>, name: &str) -> Option<&'a String> {
+    p.pod.metadata.labels.as_ref().and_then(|l| l.get(name))
+}
+
+//(namespace, pod) -> application id of the executors whose driver is in the list,
+//the driver carries the application id in its spark-app-selector label and the executors repeat it.
+pub fn driver_executors<A>(pods: &[PodInfo<A>]) -> BTreeMap<(String, String), String> {
+    let applications = pods
+        .iter()
+        .filter(|p| label(p, SPARK_ROLE_LABEL).is_some_and(|r| r == "driver"))
+        .filter_map(|p| Some((p.namespace.clone(), label(p, SPARK_APP_LABEL)?.clone())))
+        .collect::<Vec<(String, String)>>();
+    pods.iter()
+        .filter(|p| label(p, SPARK_ROLE_LABEL).is_some_and(|r| r == "executor"))
+        .filter_map(|p| {
+            let app = label(p, SPARK_APP_LABEL)?;
+            applications
+                .contains(&(p.namespace.clone(), app.clone()))
+                .then(|| ((p.namespace.clone(), p.name.clone()), app.clone()))
+        })
+        .collect()
+}
+
+//id of the first application of /api/v1/applications, a driver runs a single one.
+pub fn first_application_id(applications: &str) -> Result<String> {
+    let applications: serde_json::Value = serde_json::from_str(applications)?;
+    applications
+        .pointer("/0/id")
+        .and_then(|i| i.as_str())
+        .map(|i| i.to_string())
+        .ok_or_else(|| anyhow!("No application in the spark api response."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, pod_info};
+
+    fn pods() -> Vec<PodInfo<()>> {
+        let json = std::fs::read_to_string(fixture("spark/pods.json")).unwrap();
+        let pods: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+        pods.into_iter().map(pod_info).collect()
+    }
+
+    #[test]
+    fn executors_of_the_listed_drivers_only() {
+        let key = |ns: &str, pod: &str| (ns.to_string(), pod.to_string());
+        assert_eq!(
+            driver_executors(&pods()),
+            BTreeMap::from([
+                (key("apps", "exec-1"), "spark-1".to_string()),
+                (key("apps", "exec-2"), "spark-1".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn no_driver_no_executor() {
+        let without_driver = pods()
+            .into_iter()
+            .filter(|p| p.name != "driver")
+            .collect::<Vec<_>>();
+        assert!(driver_executors(&without_driver).is_empty());
+        assert!(driver_executors::<()>(&[]).is_empty());
+    }
+
+    #[test]
+    fn first_application_id_of_the_api_response() {
+        let applications = r#"[{"id": "spark-1", "name": "streaming"}, {"id": "spark-0"}]"#;
+        assert_eq!(first_application_id(applications).unwrap(), "spark-1");
+        assert!(first_application_id("[]").is_err());
+        assert!(first_application_id("not json").is_err());
+    }
+}
@@ -0,0 +1,374 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::{
+    apps::v1::{DaemonSet, Deployment, StatefulSet},
+    core::v1::PodTemplateSpec,
+    storage::v1::{CSIDriver, CSINode, VolumeAttachment},
+};
+use kube::{api::ListParams, Api, Resource};
+use serde::Serialize;
+use simplelog::{__private::log::warn, info};
+
+use std::{collections::BTreeMap, fs};
+
+use crate::{access::PodAccess, collector::Collector, get_logs, get_pod_list, preset, PodInfo};
+
+pub const STORAGE_CSI_FOLDER: &str = "storage_csi";
+pub const UNATTACHED_VOLUMES_FILE: &str = "unattached_volumes.log";
+const CSI_LOG_TAIL_LINES: i64 = 500;
+//per controller or node plugin workload, the failing pods first.
+const CSI_MAX_PODS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnattachedVolume {
+    pub attachment: String,
+    pub driver: String,
+    pub node: String,
+    pub persistent_volume: String,
+    pub attach_error: Option<String>,
+    pub detach_error: Option<String>,
+    //set while the attachment is being deleted, a stuck detach.
+    pub deleting: bool,
+}
+
+//the attachments not marked attached, or stuck detaching, with their attach/detach errors.
+pub fn unattached_volumes(attachments: &[VolumeAttachment]) -> Vec<UnattachedVolume> {
+    attachments
+        .iter()
+        .filter(|a| {
+            let attached = a.status.as_ref().is_some_and(|s| s.attached);
+            !attached || a.metadata.deletion_timestamp.is_some()
+        })
+        .map(|a| {
+            let status = a.status.as_ref();
+            UnattachedVolume {
+                attachment: a.metadata.name.clone().unwrap_or_default(),
+                driver: a.spec.attacher.clone(),
+                node: a.spec.node_name.clone(),
+                persistent_volume: a
+                    .spec
+                    .source
+                    .persistent_volume_name
+                    .clone()
+                    .unwrap_or_else(|| "<inline>".to_string()),
+                attach_error: status
+                    .and_then(|s| s.attach_error.as_ref())
+                    .and_then(|e| e.message.clone()),
+                detach_error: status
+                    .and_then(|s| s.detach_error.as_ref())
+                    .and_then(|e| e.message.clone()),
+                deleting: a.metadata.deletion_timestamp.is_some(),
+            }
+        })
+        .collect()
+}
+
+pub fn render_unattached(volumes: &[UnattachedVolume]) -> String {
+    let mut out = String::new();
+    for v in volumes {
+        out.push_str(&format!(
+            "{} pv={} node={} driver={}{}\n",
+            v.attachment,
+            v.persistent_volume,
+            v.node,
+            v.driver,
+            if v.deleting { " DETACHING" } else { "" }
+        ));
+        if let Some(e) = &v.attach_error {
+            out.push_str(&format!("  attach error: {}\n", e));
+        }
+        if let Some(e) = &v.detach_error {
+            out.push_str(&format!("  detach error: {}\n", e));
+        }
+    }
+    out
+}
+
+//ebs.csi.aws.com -> ebs, the prefix of the usual <prefix>-csi-node/<prefix>-csi-controller names.
+fn driver_prefix(driver: &str) -> &str {
+    driver.split(['.', '-']).next().unwrap_or(driver)
+}
+
+//a workload runs the plugin of the driver when a container passes the driver name
+//(--drivername, CSI_DRIVER_NAME, ..) or when it is named after its prefix, e.g. ebs-csi-node.
+pub fn runs_driver(driver: &str, name: &str, template: &PodTemplateSpec) -> bool {
+    let by_name = name.contains("csi") && name.starts_with(driver_prefix(driver));
+    let by_container = template.spec.as_ref().is_some_and(|s| {
+        s.containers.iter().any(|c| {
+            c.args
+                .iter()
+                .chain(c.command.iter())
+                .flatten()
+                .any(|a| a.contains(driver))
+                || c.env
+                    .iter()
+                    .flatten()
+                    .any(|e| e.value.as_ref().is_some_and(|v| v == driver))
+        })
+    });
+    by_name || by_container
+}
+
+fn match_labels(labels: Option<&BTreeMap<String, String>>) -> String {
+    labels
+        .map(|l| {
+            l.iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<String>>()
+                .join(",")
+        })
+        .unwrap_or_default()
+}
+
+//controller and node plugin workloads of any namespace: (namespace, name, selector, pod template).
+async fn plugin_workloads(
+    collector: &Collector,
+) -> Result<Vec<(String, String, String, PodTemplateSpec)>> {
+    let lp = ListParams::default();
+    let mut workloads = Vec::new();
+    let daemonsets: Api<DaemonSet> = Api::all(collector.client.clone());
+    for ds in daemonsets.list(&lp).await?.items {
+        if let Some(spec) = ds.spec {
+            workloads.push((
+                ds.metadata.namespace.unwrap_or_default(),
+                ds.metadata.name.unwrap_or_default(),
+                match_labels(spec.selector.match_labels.as_ref()),
+                spec.template,
+            ));
+        }
+    }
+    let deployments: Api<Deployment> = Api::all(collector.client.clone());
+    for d in deployments.list(&lp).await?.items {
+        if let Some(spec) = d.spec {
+            workloads.push((
+                d.metadata.namespace.unwrap_or_default(),
+                d.metadata.name.unwrap_or_default(),
+                match_labels(spec.selector.match_labels.as_ref()),
+                spec.template,
+            ));
+        }
+    }
+    let statefulsets: Api<StatefulSet> = Api::all(collector.client.clone());
+    for s in statefulsets.list(&lp).await?.items {
+        if let Some(spec) = s.spec {
+            workloads.push((
+                s.metadata.namespace.unwrap_or_default(),
+                s.metadata.name.unwrap_or_default(),
+                match_labels(spec.selector.match_labels.as_ref()),
+                spec.template,
+            ));
+        }
+    }
+    Ok(workloads)
+}
+
+async fn collect_plugin_logs(
+    collector: &Collector,
+    namespace: &str,
+    name: &str,
+    selector: &str,
+    folder: &str,
+) -> Result<()> {
+    let ctx = &collector.ctx;
+    let access = collector.access.in_namespace(namespace);
+    let mut pods: Vec<PodInfo> =
+        get_pod_list(vec![access], selector.to_string(), "".to_string()).await?;
+    pods.sort_by_key(|p| !preset::pod_failing(&p.pod));
+    pods.truncate(CSI_MAX_PODS);
+    info!(
+        "CSI plugin {}/{}, {} pods collected.",
+        namespace,
+        name,
+        pods.len()
+    );
+    for p in &pods {
+        for c in &p.containers {
+            let filename = format!("logs_{}_{}_{}.log", namespace, p.name, c);
+            let logs = get_logs(
+                p.name.clone(),
+                c.clone(),
+                p.api.clone(),
+                false,
+                None,
+                Some(CSI_LOG_TAIL_LINES),
+                None,
+            )
+            .await
+            .and_then(|l| {
+                let er = anyhow!("No Log found {} on container {}.", p.name, c);
+                ctx.write_file(folder, l.as_bytes(), &filename, er)
+            });
+            if let Err(e) = logs {
+                warn!("{}", e)
+            }
+        }
+    }
+    Ok(())
+}
+
+fn to_yaml<K: Resource + Serialize + Clone>(objects: &[K]) -> Result<String> {
+    let mut yaml = String::new();
+    for obj in objects {
+        let mut obj = obj.clone();
+        obj.meta_mut().managed_fields = None;
+        yaml.push_str("---\n");
+        yaml.push_str(&serde_yaml::to_string(&obj)?);
+    }
+    Ok(yaml)
+}
+
+fn write(collector: &Collector, folder: &str, filename: &str, data: Result<String>) {
+    let written = data
+        .inspect_err(|e| collector.ctx.record_failure(folder, filename, "", e))
+        .and_then(|d| {
+            let er = anyhow!("Empty {}.", filename);
+            collector.ctx.write_file(folder, d.as_bytes(), filename, er)
+        });
+    match written {
+        Ok(_) => info!("File has been created {}/{}", folder, filename),
+        Err(e) => warn!("{}", e),
+    }
+}
+
+//csi drivers, csi nodes and volume attachments under infra/storage_csi, the attachments not
+//attached summarized, plus the recent logs of the controller/node plugins of each driver.
+pub async fn collect_storage_csi(collector: &Collector, infra_folder: &str) -> Result<()> {
+    let folder = format!("{}/{}", infra_folder, STORAGE_CSI_FOLDER);
+    fs::create_dir_all(&folder)?;
+    let client = &collector.client;
+    let lp = ListParams::default();
+
+    let drivers = Api::<CSIDriver>::all(client.clone()).list(&lp).await?.items;
+    write(collector, &folder, "csi_drivers.yaml", to_yaml(&drivers));
+    let nodes = Api::<CSINode>::all(client.clone())
+        .list(&lp)
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|n| to_yaml(&n.items));
+    write(collector, &folder, "csi_nodes.yaml", nodes);
+
+    match Api::<VolumeAttachment>::all(client.clone()).list(&lp).await {
+        Ok(attachments) => {
+            write(
+                collector,
+                &folder,
+                "volume_attachments.yaml",
+                to_yaml(&attachments.items),
+            );
+            let unattached = unattached_volumes(&attachments.items);
+            if !unattached.is_empty() {
+                warn!(
+                    "<yellow>{} volume attachments not attached, see {}/{}.</>",
+                    unattached.len(),
+                    folder,
+                    UNATTACHED_VOLUMES_FILE
+                );
+                write(
+                    collector,
+                    &folder,
+                    UNATTACHED_VOLUMES_FILE,
+                    Ok(render_unattached(&unattached)),
+                );
+            }
+        }
+        Err(e) => {
+            let e = e.into();
+            warn!("Volume attachments: {}", e);
+            collector
+                .ctx
+                .record_failure(&folder, "volume_attachments.yaml", "", &e);
+        }
+    }
+
+    let workloads = plugin_workloads(collector).await?;
+    //a workload serving several drivers is collected once.
+    let mut plugins = BTreeMap::new();
+    for driver in &drivers {
+        let driver = driver.metadata.name.clone().unwrap_or_default();
+        let found = workloads
+            .iter()
+            .filter(|(_, name, _, template)| runs_driver(&driver, name, template))
+            .map(|(namespace, name, selector, _)| ((namespace, name), selector))
+            .collect::<Vec<_>>();
+        if found.is_empty() {
+            info!(
+                "No controller or node plugin found for the CSI driver {}.",
+                driver
+            );
+        }
+        plugins.extend(found);
+    }
+    for ((namespace, name), selector) in plugins {
+        if let Err(e) = collect_plugin_logs(collector, namespace, name, selector, &folder).await {
+            warn!("CSI plugin {}/{}: {}", namespace, name, e);
+            collector.ctx.record_folder(&folder, false);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    fn attachments() -> Vec<VolumeAttachment> {
+        let json = fs::read_to_string(fixture("storage_csi/volume_attachments.json")).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn attached_volumes_are_left_out() {
+        let volumes = unattached_volumes(&attachments());
+        let names = volumes
+            .iter()
+            .map(|v| v.attachment.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["csi-failing", "csi-detaching", "csi-inline"]);
+    }
+
+    #[test]
+    fn attach_and_detach_errors_are_kept() {
+        let volumes = unattached_volumes(&attachments());
+        assert_eq!(
+            volumes[0],
+            UnattachedVolume {
+                attachment: "csi-failing".to_string(),
+                driver: "ebs.csi.aws.com".to_string(),
+                node: "node-b".to_string(),
+                persistent_volume: "pvc-2".to_string(),
+                attach_error: Some(
+                    "rpc error: code = Internal desc = volume vol-2 is attached to another instance"
+                        .to_string()
+                ),
+                detach_error: None,
+                deleting: false,
+            }
+        );
+        //still attached but deleted: a stuck detach.
+        assert!(volumes[1].deleting);
+        assert_eq!(
+            volumes[1].detach_error.as_deref(),
+            Some("node node-a is not reachable")
+        );
+        //no status yet and no persistent volume.
+        assert_eq!(volumes[2].persistent_volume, "<inline>");
+        assert_eq!(
+            (&volumes[2].attach_error, &volumes[2].detach_error),
+            (&None, &None)
+        );
+    }
+
+    #[test]
+    fn unattached_report_lines() {
+        let report = render_unattached(&unattached_volumes(&attachments()));
+        assert_eq!(
+            report,
+            "csi-failing pv=pvc-2 node=node-b driver=ebs.csi.aws.com\n  \
+             attach error: rpc error: code = Internal desc = volume vol-2 is attached to another instance\n\
+             csi-detaching pv=pvc-3 node=node-a driver=ebs.csi.aws.com DETACHING\n  \
+             detach error: node node-a is not reachable\n\
+             csi-inline pv=<inline> node=node-c driver=pd.csi.storage.gke.io\n"
+        );
+        assert_eq!(render_unattached(&[]), "");
+    }
+}
@@ -0,0 +1,116 @@
+use hyper::{Body, Request, Response};
+use kube::Client;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{context::RunContext, PodInfo};
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+//a directory of its own under the system temp dir, removed with its content when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "logpv2_test_{}_{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    //the path as the &str folders of the collectors take.
+    pub fn folder(&self) -> String {
+        self.0.display().to_string()
+    }
+
+    //writes a file below the directory, its parent folders included, and returns its path.
+    pub fn write(&self, relative: &str, content: &[u8]) -> PathBuf {
+        let path = self.0.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    pub fn read(&self, relative: &str) -> String {
+        fs::read_to_string(self.0.join(relative)).unwrap()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+//tests/fixtures/<name>.
+pub fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+//a client failing every request, for the collectors that must not reach a cluster.
+pub fn offline_client() -> Client {
+    let service = tower::service_fn(|_: Request<Body>| async {
+        Err::<Response<Body>, _>(std::io::Error::other("no cluster in tests"))
+    });
+    Client::new(service, "default")
+}
+
+//a client answering the GETs of the given paths with the given json bodies, 404 otherwise.
+pub fn json_client(responses: &[(&str, String)]) -> Client {
+    let responses = responses
+        .iter()
+        .map(|(p, b)| (p.to_string(), b.clone()))
+        .collect::<BTreeMap<String, String>>();
+    let service = tower::service_fn(move |request: Request<Body>| {
+        let body = responses.get(request.uri().path()).cloned();
+        async move {
+            let response = match body {
+                Some(b) => Response::new(Body::from(b)),
+                None => Response::builder()
+                    .status(404)
+                    .body(Body::from(
+                        r#"{"kind":"Status","apiVersion":"v1","status":"Failure","reason":"NotFound","code":404,"message":"not found"}"#,
+                    ))
+                    .unwrap(),
+            };
+            Ok::<_, std::io::Error>(response)
+        }
+    });
+    Client::new(service, "default")
+}
+
+//a run context over the folders folder_creation would make below dir, pods to apps created.
+pub fn run_context(dir: &TempDir) -> RunContext {
+    let root = dir.folder();
+    let mut folders = ["pods", "infra", "helm", "apps"]
+        .map(|f| format!("{}/{}", root, f))
+        .to_vec();
+    folders.iter().for_each(|f| fs::create_dir_all(f).unwrap());
+    folders.extend([
+        "info_test.tar.gz".to_string(),
+        root.clone(),
+        format!("{}/output", root),
+        format!("{}/work", root),
+    ]);
+    RunContext::new(folders)
+}
+
+//a pod without access from its json, for the logic working on listed pods.
+pub fn pod_info(json: serde_json::Value) -> PodInfo<()> {
+    PodInfo::from_pod(&serde_json::from_value(json).unwrap(), ())
+}
@@ -0,0 +1,242 @@
+use chrono::{DateTime, Utc};
+use k8s_openapi::api::core::v1::{Event, Pod};
+
+//one line of a pod timeline, events without any timestamp have no time and go last.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineEntry {
+    pub time: Option<DateTime<Utc>>,
+    pub text: String,
+}
+
+//the most recent occurrence of the event.
+pub fn event_time(e: &Event) -> Option<DateTime<Utc>> {
+    e.last_timestamp
+        .as_ref()
+        .map(|t| t.0)
+        .or_else(|| e.event_time.as_ref().map(|t| t.0))
+        .or_else(|| e.first_timestamp.as_ref().map(|t| t.0))
+        .or_else(|| e.metadata.creation_timestamp.as_ref().map(|t| t.0))
+}
+
+pub fn event_entries(events: &[Event], namespace: &str, pod: &str) -> Vec<TimelineEntry> {
+    events
+        .iter()
+        .filter(|e| e.involved_object.kind.as_deref() == Some("Pod"))
+        .filter(|e| e.involved_object.name.as_deref() == Some(pod))
+        .filter(|e| {
+            e.involved_object
+                .namespace
+                .as_deref()
+                .or(e.metadata.namespace.as_deref())
+                == Some(namespace)
+        })
+        .map(|e| TimelineEntry {
+            time: event_time(e),
+            text: format!(
+                "{} {}{}: {}",
+                e.type_.clone().unwrap_or_default(),
+                e.reason.clone().unwrap_or_default(),
+                e.count
+                    .filter(|c| *c > 1)
+                    .map(|c| format!(" (x{})", c))
+                    .unwrap_or_default(),
+                e.message.clone().unwrap_or_default().trim()
+            ),
+        })
+        .collect()
+}
+
+//start and end of the previous run of each container, the kubelet only keeps the last one.
+pub fn restart_entries(pod: &Pod) -> Vec<TimelineEntry> {
+    let mut entries = vec![];
+    let statuses = pod.status.as_ref().into_iter().flat_map(|s| {
+        s.init_container_statuses
+            .iter()
+            .flatten()
+            .chain(s.container_statuses.iter().flatten())
+    });
+    for s in statuses {
+        let Some(t) = s.last_state.as_ref().and_then(|l| l.terminated.as_ref()) else {
+            continue;
+        };
+        if let Some(started) = &t.started_at {
+            entries.push(TimelineEntry {
+                time: Some(started.0),
+                text: format!("container {} started (previous run)", s.name),
+            });
+        }
+        entries.push(TimelineEntry {
+            time: t.finished_at.as_ref().map(|f| f.0),
+            text: format!(
+                "container {} terminated: {} exit code {}, {} restarts so far",
+                s.name,
+                t.reason.clone().unwrap_or_default(),
+                t.exit_code,
+                s.restart_count
+            ),
+        });
+    }
+    entries
+}
+
+//chronological, the entries without time keep their order at the end.
+pub fn merge(mut entries: Vec<TimelineEntry>) -> Vec<TimelineEntry> {
+    entries.sort_by_key(|e| (e.time.is_none(), e.time));
+    entries
+}
+
+pub fn age(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = (now - time).num_seconds().max(0);
+    match seconds {
+        s if s < 120 => format!("{}s", s),
+        s if s < 7200 => format!("{}m", s / 60),
+        s if s < 172800 => format!("{}h", s / 3600),
+        s => format!("{}d", s / 86400),
+    }
+}
+
+pub fn render_timeline(entries: &[TimelineEntry], now: DateTime<Utc>) -> String {
+    let mut out = String::from("\nTimeline:\n");
+    if entries.is_empty() {
+        out.push_str("  <none>\n");
+    }
+    for e in entries {
+        let (age, time) = match e.time {
+            Some(t) => (format!("{} ago", age(t, now)), t.to_rfc3339()),
+            None => ("unknown".to_string(), "-".to_string()),
+        };
+        out.push_str(&format!("  {:<9} {:<25} {}\n", age, time, e.text));
+    }
+    out
+}
+
+//events of the pod and restarts of its containers as the Timeline section of its description.
+pub fn pod_timeline(pod: &Pod, events: &[Event], now: DateTime<Utc>) -> String {
+    let namespace = pod.metadata.namespace.clone().unwrap_or_default();
+    let name = pod.metadata.name.clone().unwrap_or_default();
+    let mut entries = event_entries(events, &namespace, &name);
+    entries.extend(restart_entries(pod));
+    render_timeline(&merge(entries), now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+    use serde_json::json;
+
+    fn events() -> Vec<Event> {
+        serde_json::from_str(&std::fs::read_to_string(fixture("events/timeline.json")).unwrap())
+            .unwrap()
+    }
+
+    //the previous run of the container ended at the same time as the last BackOff.
+    fn pod() -> Pod {
+        serde_json::from_value(json!({
+            "metadata": {"name": "web-0", "namespace": "web"},
+            "status": {"containerStatuses": [{
+                "name": "nginx", "image": "nginx", "imageID": "", "ready": false, "restartCount": 4,
+                "lastState": {"terminated": {
+                    "exitCode": 1, "reason": "Error",
+                    "startedAt": "2026-10-17T07:58:00Z", "finishedAt": "2026-10-17T07:59:00Z"
+                }}
+            }]}
+        }))
+        .unwrap()
+    }
+
+    fn now() -> DateTime<Utc> {
+        "2026-10-17T08:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn only_the_events_of_the_pod_in_its_namespace() {
+        let entries = event_entries(&events(), "web", "web-0");
+        let texts = entries.iter().map(|e| e.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            vec![
+                "Normal Scheduled: Successfully assigned web/web-0 to node-1",
+                "Warning BackOff (x4): Back-off restarting failed container",
+                "Warning Unhealthy: Liveness probe failed",
+                "Normal Pulled: Container image already present",
+            ]
+        );
+        //the last occurrence wins over the first, the creation is the last resort.
+        assert_eq!(
+            entries[1].time,
+            Some("2026-10-17T07:59:00Z".parse().unwrap())
+        );
+        assert_eq!(entries[2].time, None);
+        assert_eq!(
+            entries[3].time,
+            Some("2026-10-17T07:10:00Z".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn events_without_timestamps_go_last_in_their_order() {
+        let at = |t: &str| Some(t.parse().unwrap());
+        let entry = |time, text: &str| TimelineEntry {
+            time,
+            text: text.to_string(),
+        };
+        let merged = merge(vec![
+            entry(None, "first unknown"),
+            entry(at("2026-10-17T07:59:00Z"), "late"),
+            entry(None, "second unknown"),
+            entry(at("2026-10-17T07:00:00Z"), "early"),
+            entry(at("2026-10-17T07:59:00Z"), "late too"),
+        ]);
+        let texts = merged.iter().map(|e| e.text.as_str()).collect::<Vec<_>>();
+        //the same timestamps keep their order.
+        assert_eq!(
+            texts,
+            vec![
+                "early",
+                "late",
+                "late too",
+                "first unknown",
+                "second unknown"
+            ]
+        );
+    }
+
+    #[test]
+    fn pod_timeline_interleaves_restarts_and_events() {
+        let timeline = pod_timeline(&pod(), &events(), now());
+        let lines = timeline.lines().collect::<Vec<_>>();
+        assert_eq!(lines[1], "Timeline:");
+        assert!(lines[2].starts_with("  60m ago   2026-10-17T07:00:00+00:00"));
+        assert!(lines[2].ends_with("Normal Scheduled: Successfully assigned web/web-0 to node-1"));
+        assert!(lines[3].ends_with("Normal Pulled: Container image already present"));
+        assert!(lines[4].ends_with("container nginx started (previous run)"));
+        //the overlapping BackOff and termination, the event first.
+        assert!(lines[5].ends_with("Warning BackOff (x4): Back-off restarting failed container"));
+        assert!(
+            lines[6].ends_with("container nginx terminated: Error exit code 1, 4 restarts so far")
+        );
+        assert_eq!(
+            lines[7],
+            "  unknown   -                         Warning Unhealthy: Liveness probe failed"
+        );
+        assert_eq!(lines.len(), 8);
+    }
+
+    #[test]
+    fn empty_timeline_and_ages() {
+        assert_eq!(render_timeline(&[], now()), "\nTimeline:\n  <none>\n");
+        let ago = |s: i64| age(now() - chrono::Duration::seconds(s), now());
+        assert_eq!(
+            [
+                ago(-5),
+                ago(119),
+                ago(120),
+                ago(7199),
+                ago(7200),
+                ago(172800)
+            ],
+            ["0s", "119s", "2m", "119m", "2h", "2d"].map(String::from)
+        );
+    }
+}
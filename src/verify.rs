@@ -0,0 +1,330 @@
+use anyhow::Result;
+use flate2::read::MultiGzDecoder;
+use sha2::{Digest, Sha256};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    context::{FileStatus, MANIFEST_FILE},
+    report::{CollectionInfo, Manifest, COLLECTION_INFO_FILE},
+    sha256_file,
+};
+
+pub const CHECKSUMS_FILE: &str = "checksums.txt";
+
+//files describing the collection itself, never listed in the manifest.
+const META_FILES: [&str; 3] = [CHECKSUMS_FILE, MANIFEST_FILE, COLLECTION_INFO_FILE];
+
+fn relative_files(root: &Path, dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            relative_files(root, &path, out)?;
+        } else {
+            out.push(path.strip_prefix(root)?.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+//"<sha256>  <path>" of every file of the collection folder, the sha256sum format.
+pub fn write_checksums(root: &Path) -> Result<()> {
+    let mut files = vec![];
+    relative_files(root, root, &mut files)?;
+    files.sort();
+    let mut out = String::new();
+    for f in files.iter().filter(|f| f.as_os_str() != CHECKSUMS_FILE) {
+        out.push_str(&format!(
+            "{}  {}\n",
+            sha256_file(&root.join(f))?,
+            f.display()
+        ));
+    }
+    crate::write_atomic(&root.join(CHECKSUMS_FILE), out.as_bytes())
+}
+
+pub fn parse_checksums(content: &str) -> BTreeMap<String, String> {
+    content
+        .lines()
+        .filter_map(|l| l.split_once("  "))
+        .map(|(sum, path)| (path.to_string(), sum.to_string()))
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VerifyReport {
+    //None when the archive could be read to the end.
+    pub unreadable: Option<String>,
+    pub members: usize,
+    pub checksums_checked: usize,
+    pub checksum_mismatches: Vec<String>,
+    //listed by checksums.txt or by an ok manifest entry but not in the archive.
+    pub missing_members: Vec<String>,
+    //collected files absent from the manifest.
+    pub unlisted_members: Vec<String>,
+    pub failed_outputs: usize,
+    pub collection_info: Option<CollectionInfo>,
+}
+
+impl VerifyReport {
+    pub fn failed_collectors(&self) -> Vec<String> {
+        self.collection_info
+            .iter()
+            .flat_map(|i| i.phases.iter())
+            .filter(|(_, p)| p.failed > 0)
+            .map(|(n, p)| format!("{} ({} failed)", n, p.failed))
+            .collect()
+    }
+
+    //0 clean, 2 readable with failed collectors, missing members or bad checksums, 1 unreadable.
+    pub fn exit_code(&self) -> i32 {
+        if self.unreadable.is_some() {
+            1
+        } else if !self.failed_collectors().is_empty()
+            || self.failed_outputs > 0
+            || !self.missing_members.is_empty()
+            || !self.checksum_mismatches.is_empty()
+        {
+            2
+        } else {
+            0
+        }
+    }
+}
+
+//sha256 of every member and content of the meta files, keyed by path without the root folder.
+struct Members {
+    hashes: BTreeMap<String, String>,
+    meta: BTreeMap<String, Vec<u8>>,
+}
+
+//every member is streamed to its hash, only the meta files are kept in memory.
+//The ones outside of the root folder (the tool log) are skipped.
+fn read_members(path: &Path) -> Result<Members> {
+    let mut archive = tar::Archive::new(MultiGzDecoder::new(File::open(path)?));
+    let mut hashes = BTreeMap::new();
+    let mut meta = BTreeMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let relative = entry
+            .path()?
+            .components()
+            .skip(1)
+            .collect::<PathBuf>()
+            .display()
+            .to_string();
+        if relative.is_empty() || entry.header().entry_type().is_dir() {
+            continue;
+        }
+        let mut hasher = Sha256::new();
+        if META_FILES.contains(&relative.as_str()) {
+            let mut content = vec![];
+            entry.read_to_end(&mut content)?;
+            hasher.update(&content);
+            meta.insert(relative.clone(), content);
+        } else {
+            std::io::copy(&mut entry, &mut hasher)?;
+        }
+        hashes.insert(
+            relative,
+            hasher
+                .finalize()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+        );
+    }
+    Ok(Members { hashes, meta })
+}
+
+pub fn verify_archive(path: &Path) -> VerifyReport {
+    let Members { hashes, meta } = match read_members(path) {
+        Ok(m) => m,
+        Err(e) => {
+            return VerifyReport {
+                unreadable: Some(e.to_string()),
+                ..Default::default()
+            }
+        }
+    };
+    let mut report = VerifyReport {
+        members: hashes.len(),
+        ..Default::default()
+    };
+    let mut missing = BTreeSet::new();
+    if let Some(c) = meta.get(CHECKSUMS_FILE) {
+        for (file, sum) in parse_checksums(&String::from_utf8_lossy(c)) {
+            report.checksums_checked += 1;
+            match hashes.get(&file) {
+                Some(h) if *h == sum => {}
+                Some(_) => report.checksum_mismatches.push(file),
+                None => {
+                    missing.insert(file);
+                }
+            }
+        }
+    }
+    let manifest = meta
+        .get(MANIFEST_FILE)
+        .and_then(|m| Manifest::parse(m))
+        .map(|m| m.files)
+        .unwrap_or_default();
+    let mut listed = BTreeSet::new();
+    for (file, entry) in &manifest {
+        match entry.status {
+            FileStatus::Ok => {
+                if !hashes.contains_key(file) {
+                    missing.insert(file.clone());
+                }
+                listed.insert(file.clone());
+            }
+            FileStatus::Failed => report.failed_outputs += 1,
+        }
+        if let Some(e) = &entry.error_file {
+            if !hashes.contains_key(e) {
+                missing.insert(e.clone());
+            }
+            listed.insert(e.clone());
+        }
+    }
+    report.missing_members = missing.into_iter().collect();
+    if !manifest.is_empty() {
+        report.unlisted_members = hashes
+            .keys()
+            .filter(|k| !listed.contains(*k) && !META_FILES.contains(&k.as_str()))
+            .cloned()
+            .collect();
+    }
+    report.collection_info = meta
+        .get(COLLECTION_INFO_FILE)
+        .and_then(|i| serde_json::from_slice(i).ok());
+    report
+}
+
+pub fn render_report(report: &VerifyReport) -> String {
+    if let Some(e) = &report.unreadable {
+        return format!("archive unreadable: {}\n", e);
+    }
+    let mut out = String::new();
+    if let Some(i) = &report.collection_info {
+        out.push_str(&format!(
+            "context {} namespaces {}, tool {}, started {}, {:?} (exit code {})\n",
+            i.context_name,
+            i.namespaces.join(","),
+            i.tool_version,
+            i.started_at.to_rfc3339(),
+            i.classification,
+            i.exit_code
+        ));
+    } else {
+        out.push_str(&format!("no {}\n", COLLECTION_INFO_FILE));
+    }
+    out.push_str(&format!("members: {}\n", report.members));
+    out.push_str(&format!(
+        "checksums checked: {}, mismatches: {}\n",
+        report.checksums_checked,
+        report.checksum_mismatches.len()
+    ));
+    report
+        .checksum_mismatches
+        .iter()
+        .for_each(|m| out.push_str(&format!("  mismatch {}\n", m)));
+    out.push_str(&format!(
+        "missing members: {}\n",
+        report.missing_members.len()
+    ));
+    report
+        .missing_members
+        .iter()
+        .for_each(|m| out.push_str(&format!("  missing  {}\n", m)));
+    out.push_str(&format!(
+        "members not in the manifest: {}\n",
+        report.unlisted_members.len()
+    ));
+    out.push_str(&format!("failed outputs: {}\n", report.failed_outputs));
+    let failed = report.failed_collectors();
+    out.push_str(&format!("failed collectors: {}\n", failed.len()));
+    failed
+        .iter()
+        .for_each(|f| out.push_str(&format!("  {}\n", f)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, TempDir};
+    use flate2::{write::GzEncoder, Compression};
+
+    #[test]
+    fn fixture_archive_report() {
+        let report = verify_archive(&fixture("verify/partial.tar.gz"));
+        assert_eq!(report.unreadable, None);
+        //the tool log outside of the root folder is not a member.
+        assert_eq!(report.members, 7);
+        assert_eq!(report.checksums_checked, 5);
+        assert_eq!(report.checksum_mismatches, vec!["pods/a.log"]);
+        assert_eq!(
+            report.missing_members,
+            vec!["infra/gone.json", "infra/missing.json"]
+        );
+        assert_eq!(report.unlisted_members, vec!["apps/unlisted.txt"]);
+        assert_eq!(report.failed_outputs, 1);
+        assert_eq!(report.failed_collectors(), vec!["infra (1 failed)"]);
+        assert_eq!(report.exit_code(), 2);
+        let rendered = render_report(&report);
+        assert!(rendered.starts_with(
+            "context prod namespaces kafka,web, tool 2.3.0, started 2026-10-17T08:00:00+00:00, Partial (exit code 0)\n"
+        ));
+        assert!(rendered.contains("  mismatch pods/a.log\n"));
+        assert!(rendered.contains("  missing  infra/gone.json\n"));
+    }
+
+    #[test]
+    fn streamed_hashes_match_the_files() {
+        let dir = TempDir::new();
+        let big = (0..200_000u32)
+            .flat_map(|i| i.to_le_bytes())
+            .collect::<Vec<u8>>();
+        let big_path = dir.write("info/pods/big.log", &big);
+        let manifest = dir.write("info/manifest.json", b"{}");
+        let archive = dir.path().join("info.tar.gz");
+        let mut tar = tar::Builder::new(GzEncoder::new(
+            File::create(&archive).unwrap(),
+            Compression::fast(),
+        ));
+        tar.append_dir_all("info", dir.path().join("info")).unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let members = read_members(&archive).unwrap();
+        assert_eq!(
+            members.hashes["pods/big.log"],
+            sha256_file(&big_path).unwrap()
+        );
+        assert_eq!(
+            members.hashes["manifest.json"],
+            sha256_file(&manifest).unwrap()
+        );
+        //only the meta files are kept.
+        assert_eq!(
+            members.meta.keys().collect::<Vec<_>>(),
+            vec!["manifest.json"]
+        );
+    }
+
+    #[test]
+    fn truncated_archive_is_unreadable() {
+        let dir = TempDir::new();
+        let data = fs::read(fixture("verify/partial.tar.gz")).unwrap();
+        let path = dir.write("truncated.tar.gz", &data[..data.len() / 2]);
+        let report = verify_archive(&path);
+        assert!(report.unreadable.is_some());
+        assert_eq!(report.exit_code(), 1);
+        assert!(render_report(&report).starts_with("archive unreadable: "));
+    }
+}
@@ -0,0 +1,363 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt};
+use k8s_openapi::api::core::v1::{ContainerStateTerminated, ContainerStatus, Pod};
+use kube::{
+    runtime::{watcher, WatchStreamExt},
+    ResourceExt,
+};
+use simplelog::{__private::log::warn, info};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    access::{KubeAccess, PodAccess},
+    collector::Collector,
+    filters::ContainerFilter,
+    logging::{self, LogScope},
+    output_directory, PodInfo,
+};
+
+pub const DEFAULT_DEBOUNCE_SECONDS: u64 = 600;
+
+//what the watcher knows of a pod, an incident needs a change from it: lastState keeps the
+//last termination of a container long after it happened.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PodObservation {
+    pub failed: bool,
+    //container -> restart count and end of its latest termination.
+    pub containers: BTreeMap<String, (i32, Option<DateTime<Utc>>)>,
+}
+
+//the running container's previous termination, or the current one while it is down.
+fn latest_termination(c: &ContainerStatus) -> Option<&ContainerStateTerminated> {
+    [c.state.as_ref(), c.last_state.as_ref()]
+        .into_iter()
+        .flatten()
+        .find_map(|s| s.terminated.as_ref())
+}
+
+pub fn observe(pod: &Pod) -> PodObservation {
+    let status = pod.status.as_ref();
+    PodObservation {
+        failed: status.and_then(|s| s.phase.as_deref()) == Some("Failed"),
+        containers: status
+            .and_then(|s| s.container_statuses.as_ref())
+            .into_iter()
+            .flatten()
+            .map(|c| {
+                let finished = latest_termination(c)
+                    .and_then(|t| t.finished_at.as_ref())
+                    .map(|t| t.0);
+                (c.name.clone(), (c.restart_count, finished))
+            })
+            .collect(),
+    }
+}
+
+//reason why a pod deserves an incident collection, if any: a pod turning Failed, a container
+//going to CrashLoopBackOff or OOMKilled again. `seen` is what the watcher recorded of the pod,
+//a pod seen for the first time only counts with a failure after the watch started.
+pub fn incident_reason(
+    pod: &Pod,
+    seen: Option<&PodObservation>,
+    watch_started: DateTime<Utc>,
+) -> Option<String> {
+    let status = pod.status.as_ref()?;
+    let now = observe(pod);
+    let recent = |t: Option<DateTime<Utc>>| t.is_some_and(|t| t >= watch_started);
+    if now.failed && !seen.is_some_and(|s| s.failed) {
+        let failed_at = now.containers.values().filter_map(|(_, f)| *f).max();
+        if seen.is_some() || recent(failed_at) {
+            return Some("Failed".to_string());
+        }
+    }
+    status.container_statuses.iter().flatten().find_map(|c| {
+        let (restarts, finished) = now.containers[&c.name];
+        let changed = match seen.and_then(|s| s.containers.get(&c.name)) {
+            Some((r, f)) => restarts > *r || finished > *f,
+            None => recent(finished),
+        };
+        if !changed {
+            return None;
+        }
+        let waiting = c
+            .state
+            .as_ref()
+            .and_then(|s| s.waiting.as_ref())
+            .and_then(|w| w.reason.clone())
+            .filter(|r| r == "CrashLoopBackOff");
+        let oom = latest_termination(c)
+            .and_then(|t| t.reason.clone())
+            .filter(|r| r == "OOMKilled");
+        waiting.or(oom)
+    })
+}
+
+async fn collect_incident(
+    collector: Collector,
+    pod: PodInfo,
+    reason: String,
+    container_filter: ContainerFilter,
+) {
+    let folder = format!(
+        "{}/incident_{}_{}_{}_{}",
+        output_directory(&collector.config),
+        collector.config.context_name,
+        pod.namespace,
+        pod.name,
+        Utc::now().format("%Y%m%d%H%M%S")
+    );
+    if let Err(e) = fs::create_dir_all(&folder) {
+        warn!("{}", e);
+        return;
+    }
+    info!(
+        "<yellow>Pod {}/{} is {}, collecting incident into {}.</>",
+        pod.namespace, pod.name, reason, folder
+    );
+
+    let events = pod
+        .api
+        .list_events()
+        .await
+        .inspect_err(|e| warn!("{}", e))
+        .unwrap_or_default();
+    if let Err(e) = collector.describe_pod(&pod, &events, &folder).await {
+        warn!("{}", e)
+    }
+    if let Err(e) = collector.pod_manifest(&pod, &folder) {
+        warn!("{}", e)
+    }
+    if let Err(e) = collector.last_states(&pod, &folder) {
+        warn!("{}", e)
+    }
+    for c in &container_filter.containers(&pod) {
+        for previous in [false, true] {
+            if let Err(e) = collector.pod_logs(&pod, c, previous, None, &folder).await {
+                warn!("{}", e)
+            }
+        }
+    }
+    if let Err(e) = collector.namespace_events(&pod.namespace, &folder).await {
+        warn!("{}", e)
+    }
+    info!("<green>Incident collection finished {}.</>", folder);
+}
+
+//watch the configured namespaces until Ctrl-C, collecting every failing pod at most once per debounce window.
+pub async fn watch(collector: Collector, debounce: Duration) -> Result<()> {
+    let container_filter = ContainerFilter::from_config(&collector.config)?;
+    let streams = collector
+        .pod_access()
+        .into_iter()
+        .map(|access: KubeAccess| {
+            watcher(access.pods.clone(), watcher::Config::default())
+                .default_backoff()
+                .applied_objects()
+                .map(move |p| p.map(|p| (p, access.clone())))
+                .boxed()
+        })
+        .collect::<Vec<_>>();
+    let mut events = stream::select_all(streams);
+    let mut last_incident: HashMap<(String, String), Instant> = HashMap::new();
+    //updated on the first sight of a pod and on each failure, a termination followed by its
+    //CrashLoopBackOff is still one change.
+    let mut seen: HashMap<(String, String), PodObservation> = HashMap::new();
+    let watch_started = Utc::now();
+
+    info!(
+        "<green>Watching namespaces {} for failing pods, press Ctrl-C to stop.</>",
+        collector.config.context_namespace.join(", ")
+    );
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                info!("<yellow>Stopping watch mode.</>");
+                break;
+            }
+            event = events.next() => match event {
+                Some(Ok((pod, access))) => {
+                    let key = (pod.namespace().unwrap_or_default(), pod.name_any());
+                    let previous = seen.get(&key);
+                    let reason = incident_reason(&pod, previous, watch_started);
+                    if previous.is_none() || reason.is_some() {
+                        seen.insert(key.clone(), observe(&pod));
+                    }
+                    let Some(reason) = reason else {
+                        continue;
+                    };
+                    if last_incident
+                        .get(&key)
+                        .is_some_and(|t| t.elapsed() < debounce)
+                    {
+                        continue;
+                    }
+                    last_incident.insert(key, Instant::now());
+                    let pod = PodInfo::from_pod(&pod, access);
+                    let scope = LogScope::pod(&pod.namespace, &pod.name, "");
+                    tokio::task::spawn(logging::scoped(
+                        scope,
+                        collect_incident(
+                            collector.clone(),
+                            pod,
+                            reason,
+                            container_filter.clone(),
+                        ),
+                    ));
+                }
+                Some(Err(e)) => warn!("{}", e),
+                None => break,
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn started() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap()
+    }
+
+    //a kafka-0 pod whose container has the given state, last state and restart count.
+    fn pod(phase: &str, restarts: i32, state: serde_json::Value, last: serde_json::Value) -> Pod {
+        serde_json::from_value(serde_json::json!({
+            "metadata": { "name": "kafka-0", "namespace": "kafka" },
+            "status": {
+                "phase": phase,
+                "containerStatuses": [{
+                    "name": "kafka", "image": "kafka", "imageID": "", "ready": false,
+                    "restartCount": restarts, "state": state, "lastState": last
+                }]
+            }
+        }))
+        .unwrap()
+    }
+
+    fn terminated(reason: &str, finished: &str) -> serde_json::Value {
+        serde_json::json!({ "terminated": { "exitCode": 137, "reason": reason, "finishedAt": finished } })
+    }
+
+    fn running() -> serde_json::Value {
+        serde_json::json!({ "running": { "startedAt": "2024-03-01T09:00:00Z" } })
+    }
+
+    fn backoff() -> serde_json::Value {
+        serde_json::json!({ "waiting": { "reason": "CrashLoopBackOff" } })
+    }
+
+    #[test]
+    fn old_oom_in_last_state_is_no_incident() {
+        let p = pod(
+            "Running",
+            1,
+            running(),
+            terminated("OOMKilled", "2024-02-01T00:00:00Z"),
+        );
+        assert_eq!(incident_reason(&p, None, started()), None);
+        //nor on the next updates of the pod.
+        let seen = observe(&p);
+        assert_eq!(incident_reason(&p, Some(&seen), started()), None);
+    }
+
+    #[test]
+    fn oom_after_the_watch_started_is_an_incident_once() {
+        let p = pod(
+            "Running",
+            1,
+            running(),
+            terminated("OOMKilled", "2024-03-01T10:05:00Z"),
+        );
+        assert_eq!(
+            incident_reason(&p, None, started()).as_deref(),
+            Some("OOMKilled")
+        );
+        let seen = observe(&p);
+        assert_eq!(incident_reason(&p, Some(&seen), started()), None);
+        //a second OOM is a new transition.
+        let again = pod(
+            "Running",
+            2,
+            running(),
+            terminated("OOMKilled", "2024-03-01T10:20:00Z"),
+        );
+        assert_eq!(
+            incident_reason(&again, Some(&seen), started()).as_deref(),
+            Some("OOMKilled")
+        );
+    }
+
+    #[test]
+    fn crash_loop_fires_on_a_new_termination() {
+        let before = pod(
+            "Running",
+            3,
+            running(),
+            terminated("Error", "2024-02-01T00:00:00Z"),
+        );
+        let seen = observe(&before);
+        //the container just terminated, not a failure reason yet.
+        let down = pod(
+            "Running",
+            3,
+            terminated("Error", "2024-03-01T10:01:00Z"),
+            serde_json::json!({}),
+        );
+        assert_eq!(incident_reason(&down, Some(&seen), started()), None);
+        //the backoff that follows carries the same termination.
+        let looping = pod(
+            "Running",
+            3,
+            backoff(),
+            terminated("Error", "2024-03-01T10:01:00Z"),
+        );
+        assert_eq!(
+            incident_reason(&looping, Some(&seen), started()).as_deref(),
+            Some("CrashLoopBackOff")
+        );
+        let seen = observe(&looping);
+        assert_eq!(incident_reason(&looping, Some(&seen), started()), None);
+    }
+
+    #[test]
+    fn crash_loop_seen_at_start_is_old_state() {
+        let looping = pod(
+            "Running",
+            40,
+            backoff(),
+            terminated("Error", "2024-03-01T09:59:00Z"),
+        );
+        assert_eq!(incident_reason(&looping, None, started()), None);
+    }
+
+    #[test]
+    fn failed_pods_fire_on_the_transition() {
+        let old = pod(
+            "Failed",
+            0,
+            terminated("Error", "2024-02-01T00:00:00Z"),
+            serde_json::json!({}),
+        );
+        assert_eq!(incident_reason(&old, None, started()), None);
+        assert_eq!(incident_reason(&old, Some(&observe(&old)), started()), None);
+        let was_running = pod("Running", 0, running(), serde_json::json!({}));
+        let failed = pod(
+            "Failed",
+            0,
+            terminated("Error", "2024-03-01T10:30:00Z"),
+            serde_json::json!({}),
+        );
+        assert_eq!(
+            incident_reason(&failed, Some(&observe(&was_running)), started()).as_deref(),
+            Some("Failed")
+        );
+    }
+}
@@ -0,0 +1,16 @@
+#!/bin/sh
+# plugin of the tests: prints its environment, drops files into its output folder,
+# sleeps $2 seconds and exits with $1.
+echo "context=$ANTLOG_CONTEXT_NAME"
+echo "namespaces=$ANTLOG_NAMESPACES"
+echo "output=$ANTLOG_PLUGIN_OUTPUT_DIR"
+echo "kubeconfig=$ANTLOG_KUBECONFIG"
+echo "KUBECONFIG=$KUBECONFIG"
+echo "pwd=$(pwd)"
+echo "probing worker-a at 10.0.0.7" >&2
+mkdir -p "$ANTLOG_PLUGIN_OUTPUT_DIR/worker-a"
+printf 'disk of worker-a: 91%%\r\n' > "$ANTLOG_PLUGIN_OUTPUT_DIR/worker-a/disk.txt"
+printf 'flags\n' > "$ANTLOG_PLUGIN_OUTPUT_DIR/flags.txt"
+printf '\000\001\002core' > "$ANTLOG_PLUGIN_OUTPUT_DIR/core.bin"
+sleep "${2:-0}"
+exit "${1:-0}"